@@ -2,12 +2,25 @@
 //!
 //! Messages larger than MTU need to be split into chunks for transmission.
 //! This module handles chunking, reassembly, and compression.
+//!
+//! Chunks carry the 21-byte header of the BLE mesh spec. They only go on
+//! air between peers that both set [`FEATURE_CHUNK_RECEIPTS`], where every
+//! write is a [`ChunkFrame`] so receipts can share the mesh characteristic.
+//!
+//! The chunked stream is the (possibly compressed) message followed by a
+//! SHA-256 digest of the original message, so the digest arrives with the
+//! last chunk and a chunk corrupted in transit is caught after reassembly.
+//! The digest is unkeyed: it is a corruption check, not authentication;
+//! anyone able to inject chunks can recompute it. Mesh messages carry their
+//! own signatures for that.
 
 use flate2::read::{DeflateDecoder, DeflateEncoder};
 use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::io::Read;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use uuid::Uuid;
 
@@ -17,12 +30,61 @@ pub const MAX_MTU: usize = 512;
 /// Chunk header size (21 bytes)
 pub const CHUNK_HEADER_SIZE: usize = 21;
 
+/// Frame type byte opening a chunk
+pub const FRAME_CHUNK: u8 = 0x01;
+
+/// Frame type byte opening a chunk receipt
+pub const FRAME_RECEIPT: u8 = 0x02;
+
+/// Feature bit of a peer that takes framed chunks and sends receipts
+pub const FEATURE_CHUNK_RECEIPTS: u8 = 0x01;
+
+/// Bytes a framed chunk spends before its payload (frame type and header)
+pub const CHUNK_FRAME_OVERHEAD: usize = 1 + CHUNK_HEADER_SIZE;
+
 /// Maximum payload per chunk
-pub const MAX_CHUNK_PAYLOAD: usize = MAX_MTU - CHUNK_HEADER_SIZE;
+pub const MAX_CHUNK_PAYLOAD: usize = MAX_MTU - CHUNK_FRAME_OVERHEAD;
+
+/// Header flag set on the last chunk of a message
+const FLAG_LAST: u8 = 0x01;
+
+/// Header flag set when the chunked stream is DEFLATE compressed
+const FLAG_COMPRESSED: u8 = 0x02;
+
+/// ATT MTU assumed when the platform doesn't report the negotiated one
+///
+/// What iOS and most Android stacks negotiate; the 23-byte spec minimum
+/// cannot fit a chunk header.
+pub const DEFAULT_ATT_MTU: u16 = 185;
+
+/// ATT write overhead (opcode and attribute handle) within each packet
+pub const ATT_WRITE_OVERHEAD: usize = 3;
+
+/// Length of the message digest trailing the chunked stream
+pub const MESSAGE_DIGEST_SIZE: usize = 32;
+
+/// Roughly how many progress reports one transfer produces
+pub const PROGRESS_REPORTS: usize = 10;
+
+/// Largest message that may be chunked, and the most a compressed stream
+/// may expand to when reassembled
+pub const MAX_MESSAGE_LEN: usize = 1 << 20;
 
 /// Minimum message size for compression (100 bytes)
 pub const COMPRESSION_THRESHOLD: usize = 100;
 
+/// How long a sender waits for a receipt before resending
+pub const DEFAULT_RETRY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Retransmission rounds before a transfer is abandoned
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Partial reassemblies one peer may have in progress at once
+pub const DEFAULT_MAX_PARTIAL_PER_PEER: usize = 8;
+
+/// How long a partial reassembly may wait for its missing chunks (ms)
+pub const DEFAULT_REASSEMBLY_TIMEOUT_MS: u64 = 30_000;
+
 /// Chunk errors
 #[derive(Debug, Error)]
 pub enum ChunkError {
@@ -46,20 +108,33 @@ pub enum ChunkError {
 
     #[error("Incomplete message")]
     IncompleteMessage,
+
+    #[error("Transfer {0} failed after {1} retries")]
+    RetriesExhausted(Uuid, u32),
+
+    #[error("Too many partial transfers from {0}")]
+    TooManyPartialTransfers(String),
+
+    #[error("Reassembled message does not match its digest")]
+    IntegrityMismatch,
+
+    #[error("Invalid chunk size: {0} bytes")]
+    InvalidChunkSize(usize),
 }
 
-/// Chunk header format (21 bytes):
+/// Chunk header format (21 bytes, little-endian as in the BLE mesh spec):
+/// - Chunk index: 2 bytes
+/// - Total chunks: 2 bytes
+/// - Flags: 1 byte (bit 0: last chunk, bit 1: compressed)
 /// - Message ID: 16 bytes (UUID)
-/// - Chunk index: 1 byte (0-255)
-/// - Total chunks: 1 byte (1-256)
-/// - Payload length: 2 bytes (big-endian)
-/// - Flags: 1 byte (bit 0: compressed)
+///
+/// The payload runs to the end of the write. Messages are limited to 255
+/// chunks, the most a receipt can list, so larger counts are rejected.
 #[derive(Debug, Clone)]
 pub struct ChunkHeader {
     pub message_id: Uuid,
     pub chunk_index: u8,
     pub total_chunks: u8,
-    pub payload_length: u16,
     pub compressed: bool,
 }
 
@@ -68,23 +143,23 @@ impl ChunkHeader {
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(CHUNK_HEADER_SIZE);
 
-        // Message ID (16 bytes)
-        bytes.extend_from_slice(self.message_id.as_bytes());
-
-        // Chunk index (1 byte)
-        bytes.push(self.chunk_index);
-
-        // Total chunks (1 byte)
-        bytes.push(self.total_chunks);
-
-        // Payload length (2 bytes, big-endian)
-        bytes.push(((self.payload_length >> 8) & 0xff) as u8);
-        bytes.push((self.payload_length & 0xff) as u8);
+        // Chunk index and total chunks (2 bytes each, little-endian)
+        bytes.extend_from_slice(&u16::from(self.chunk_index).to_le_bytes());
+        bytes.extend_from_slice(&u16::from(self.total_chunks).to_le_bytes());
 
         // Flags (1 byte)
-        let flags = if self.compressed { 0x01 } else { 0x00 };
+        let mut flags = 0;
+        if self.chunk_index as usize + 1 == self.total_chunks as usize {
+            flags |= FLAG_LAST;
+        }
+        if self.compressed {
+            flags |= FLAG_COMPRESSED;
+        }
         bytes.push(flags);
 
+        // Message ID (16 bytes)
+        bytes.extend_from_slice(self.message_id.as_bytes());
+
         bytes
     }
 
@@ -94,32 +169,32 @@ impl ChunkHeader {
             return Err(ChunkError::InvalidHeader);
         }
 
-        // Parse message ID
-        let message_id = Uuid::from_slice(&bytes[0..16])
+        // Parse chunk index and total chunks
+        let chunk_index = u8::try_from(u16::from_le_bytes([bytes[0], bytes[1]]))
+            .map_err(|_| ChunkError::InvalidHeader)?;
+        let total_chunks = u8::try_from(u16::from_le_bytes([bytes[2], bytes[3]]))
             .map_err(|_| ChunkError::InvalidHeader)?;
-
-        // Parse chunk index
-        let chunk_index = bytes[16];
-
-        // Parse total chunks
-        let total_chunks = bytes[17];
-
-        // Parse payload length
-        let payload_length = ((bytes[18] as u16) << 8) | (bytes[19] as u16);
 
         // Parse flags
-        let compressed = (bytes[20] & 0x01) != 0;
+        let is_last = (bytes[4] & FLAG_LAST) != 0;
+        let compressed = (bytes[4] & FLAG_COMPRESSED) != 0;
+
+        // Parse message ID
+        let message_id = Uuid::from_slice(&bytes[5..CHUNK_HEADER_SIZE])
+            .map_err(|_| ChunkError::InvalidHeader)?;
 
         // Validate
         if chunk_index >= total_chunks {
             return Err(ChunkError::InvalidChunkIndex(chunk_index, total_chunks));
         }
+        if is_last != (chunk_index as usize + 1 == total_chunks as usize) {
+            return Err(ChunkError::InvalidHeader);
+        }
 
         Ok(Self {
             message_id,
             chunk_index,
             total_chunks,
-            payload_length,
             compressed,
         })
     }
@@ -140,6 +215,13 @@ impl Chunk {
         bytes
     }
 
+    /// Serialize chunk as a framed write (see [`ChunkFrame`])
+    pub fn to_frame_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![FRAME_CHUNK];
+        bytes.extend_from_slice(&self.to_bytes());
+        bytes
+    }
+
     /// Deserialize chunk from bytes
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkError> {
         if bytes.len() < CHUNK_HEADER_SIZE {
@@ -149,35 +231,50 @@ impl Chunk {
         let header = ChunkHeader::from_bytes(&bytes[0..CHUNK_HEADER_SIZE])?;
         let payload = bytes[CHUNK_HEADER_SIZE..].to_vec();
 
-        // Verify payload length matches header
-        if payload.len() != header.payload_length as usize {
-            return Err(ChunkError::InvalidHeader);
-        }
-
         Ok(Self { header, payload })
     }
 }
 
-/// Split a message into chunks
-pub fn chunk_message(data: &[u8]) -> Result<Vec<Chunk>, ChunkError> {
+/// Payload bytes available in a framed chunk of `chunk_size` bytes
+///
+/// The chunk must fit the frame type, the header and at least one byte.
+fn chunk_payload_size(chunk_size: usize) -> Result<usize, ChunkError> {
+    let payload = chunk_size.saturating_sub(CHUNK_FRAME_OVERHEAD);
+    if payload == 0 {
+        return Err(ChunkError::InvalidChunkSize(chunk_size));
+    }
+    Ok(payload)
+}
+
+/// Split a message into chunks of at most `chunk_size` bytes each
+///
+/// `chunk_size` is the whole framed write, frame type and header included;
+/// pass the device's ATT MTU minus [`ATT_WRITE_OVERHEAD`], using
+/// [`DEFAULT_ATT_MTU`] when the MTU isn't known.
+pub fn chunk_message(data: &[u8], chunk_size: usize) -> Result<Vec<Chunk>, ChunkError> {
+    let chunk_payload = chunk_payload_size(chunk_size)?;
+    if data.len() > MAX_MESSAGE_LEN {
+        return Err(ChunkError::MessageTooLarge(data.len()));
+    }
+
     // Check if compression would be beneficial
-    let (to_chunk, compressed) = if data.len() >= COMPRESSION_THRESHOLD {
+    let (mut to_chunk, compressed) = if data.len() >= COMPRESSION_THRESHOLD {
         match compress(data) {
-            Ok(compressed_data) if compressed_data.len() < data.len() => {
-                (compressed_data, true)
-            }
+            Ok(compressed_data) if compressed_data.len() < data.len() => (compressed_data, true),
             _ => (data.to_vec(), false),
         }
     } else {
         (data.to_vec(), false)
     };
+    to_chunk.extend_from_slice(&Sha256::digest(data));
 
     // Calculate number of chunks needed
-    let total_chunks = ((to_chunk.len() + MAX_CHUNK_PAYLOAD - 1) / MAX_CHUNK_PAYLOAD) as u8;
+    let total_chunks = (to_chunk.len() + chunk_payload - 1) / chunk_payload;
 
     if total_chunks == 0 || total_chunks > 255 {
         return Err(ChunkError::MessageTooLarge(data.len()));
     }
+    let total_chunks = total_chunks as u8;
 
     // Generate message ID
     let message_id = Uuid::new_v4();
@@ -185,15 +282,14 @@ pub fn chunk_message(data: &[u8]) -> Result<Vec<Chunk>, ChunkError> {
     // Create chunks
     let mut chunks = Vec::new();
     for chunk_index in 0..total_chunks {
-        let start = (chunk_index as usize) * MAX_CHUNK_PAYLOAD;
-        let end = std::cmp::min(start + MAX_CHUNK_PAYLOAD, to_chunk.len());
+        let start = (chunk_index as usize) * chunk_payload;
+        let end = std::cmp::min(start + chunk_payload, to_chunk.len());
         let payload = to_chunk[start..end].to_vec();
 
         let header = ChunkHeader {
             message_id,
             chunk_index,
             total_chunks,
-            payload_length: payload.len() as u16,
             compressed,
         };
 
@@ -203,6 +299,81 @@ pub fn chunk_message(data: &[u8]) -> Result<Vec<Chunk>, ChunkError> {
     Ok(chunks)
 }
 
+/// Whether progress at `done` of `total` chunks is worth reporting
+///
+/// Reports about every tenth of the transfer rather than every chunk, and
+/// always reports completion.
+pub fn should_report_progress(done: usize, total: usize) -> bool {
+    let step = ((total + PROGRESS_REPORTS - 1) / PROGRESS_REPORTS).max(1);
+    done == total || done % step == 0
+}
+
+/// Expected size and duration of a chunked send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferEstimate {
+    pub chunk_count: usize,
+    pub estimated_ms: u64,
+}
+
+/// Estimate the chunks and time needed to send `payload_len` bytes
+///
+/// `att_mtu` is the device's ATT MTU, as [`DeviceMtu`] reports it; chunks
+/// are sized from it the way a real send sizes them. Assumes the payload
+/// doesn't compress, so this is an upper bound. Each chunk costs the ATT
+/// write overhead, its frame type and header, the stream carries the message
+/// digest, and every chunk is followed by `inter_chunk_delay_ms`.
+pub fn estimate_chunked_transfer(
+    payload_len: usize,
+    att_mtu: u16,
+    inter_chunk_delay_ms: u64,
+) -> Result<TransferEstimate, ChunkError> {
+    let chunk_payload = chunk_payload_size((att_mtu as usize).saturating_sub(ATT_WRITE_OVERHEAD))?;
+    let chunk_count = (payload_len + MESSAGE_DIGEST_SIZE + chunk_payload - 1) / chunk_payload;
+    if chunk_count > 255 {
+        return Err(ChunkError::MessageTooLarge(payload_len));
+    }
+
+    Ok(TransferEstimate {
+        chunk_count,
+        estimated_ms: chunk_count as u64 * inter_chunk_delay_ms,
+    })
+}
+
+/// Mesh bytes one write carries at `att_mtu`, after the ATT and chunk framing
+///
+/// Zero when the MTU cannot fit a chunk header and any payload.
+pub fn max_mesh_payload(att_mtu: u16) -> usize {
+    chunk_payload_size((att_mtu as usize).saturating_sub(ATT_WRITE_OVERHEAD)).unwrap_or(0)
+}
+
+/// Check that `data` can go as one chunked transfer to a device at `att_mtu`
+///
+/// Fails with `MessageTooLarge` past [`MAX_MESSAGE_LEN`] or when the
+/// message, compressed if that helps, needs more than 255 chunks.
+pub fn check_transfer_size(data: &[u8], att_mtu: u16) -> Result<(), ChunkError> {
+    let write_size = (att_mtu as usize).saturating_sub(ATT_WRITE_OVERHEAD);
+    chunk_message(data, write_size).map(|_| ())
+}
+
+/// Effective MTU of a device and the mesh payload each chunk carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceMtu {
+    pub att_mtu: u16,
+    pub max_mesh_payload: usize,
+}
+
+impl DeviceMtu {
+    /// Sizes for a device that negotiated `att_mtu`, or for
+    /// [`DEFAULT_ATT_MTU`] when the platform didn't report it
+    pub fn new(att_mtu: Option<u16>) -> Self {
+        let att_mtu = att_mtu.unwrap_or(DEFAULT_ATT_MTU);
+        Self {
+            att_mtu,
+            max_mesh_payload: max_mesh_payload(att_mtu),
+        }
+    }
+}
+
 /// Reassemble chunks into original message
 pub fn reassemble_chunks(chunks: &[Chunk]) -> Result<Vec<u8>, ChunkError> {
     if chunks.is_empty() {
@@ -241,12 +412,23 @@ pub fn reassemble_chunks(chunks: &[Chunk]) -> Result<Vec<u8>, ChunkError> {
         data.extend_from_slice(&chunk.payload);
     }
 
-    // Decompress if needed
-    if compressed {
-        decompress(&data)
+    // Split off the digest of the original message
+    if data.len() < MESSAGE_DIGEST_SIZE {
+        return Err(ChunkError::IntegrityMismatch);
+    }
+    let digest = data.split_off(data.len() - MESSAGE_DIGEST_SIZE);
+
+    // Decompress if needed, refusing to expand past the message limit
+    let message = if compressed {
+        decompress(&data, MAX_MESSAGE_LEN)?
     } else {
-        Ok(data)
+        data
+    };
+
+    if Sha256::digest(&message).as_slice() != digest.as_slice() {
+        return Err(ChunkError::IntegrityMismatch);
     }
+    Ok(message)
 }
 
 /// Compress data using DEFLATE
@@ -259,24 +441,40 @@ fn compress(data: &[u8]) -> Result<Vec<u8>, ChunkError> {
     Ok(compressed)
 }
 
-/// Decompress data using DEFLATE
-fn decompress(data: &[u8]) -> Result<Vec<u8>, ChunkError> {
-    let mut decoder = DeflateDecoder::new(data);
+/// Decompress data using DEFLATE, failing if it expands past `limit` bytes
+fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ChunkError> {
+    let mut decoder = DeflateDecoder::new(data).take(limit as u64 + 1);
     let mut decompressed = Vec::new();
     decoder
         .read_to_end(&mut decompressed)
         .map_err(|e| ChunkError::DecompressionFailed(e.to_string()))?;
+    if decompressed.len() > limit {
+        return Err(ChunkError::DecompressionFailed(format!(
+            "expands past {limit} bytes"
+        )));
+    }
     Ok(decompressed)
 }
 
+/// A transfer in the reassembly buffer: the sending peer and its message ID
+///
+/// Message IDs are chosen by the sender, so one peer's chunks can never
+/// land in another peer's transfer.
+type TransferKey = (String, Uuid);
+
 /// Chunk reassembly buffer
 pub struct ChunkBuffer {
-    /// Buffered chunks by message ID
-    buffers: HashMap<Uuid, Vec<Option<Chunk>>>,
+    /// Buffered chunks by peer and message ID
+    buffers: HashMap<TransferKey, Vec<Option<Chunk>>>,
     /// Message receive timestamps
-    timestamps: HashMap<Uuid, u64>,
+    timestamps: HashMap<TransferKey, u64>,
+    /// When recently reassembled messages completed, so resent chunks
+    /// are acknowledged again instead of delivered twice
+    completed: HashMap<TransferKey, u64>,
     /// Maximum age for incomplete messages (milliseconds)
     max_age_ms: u64,
+    /// Partial messages allowed per peer
+    max_partial_per_peer: usize,
 }
 
 impl ChunkBuffer {
@@ -285,26 +483,59 @@ impl ChunkBuffer {
         Self {
             buffers: HashMap::new(),
             timestamps: HashMap::new(),
+            completed: HashMap::new(),
             max_age_ms,
+            max_partial_per_peer: DEFAULT_MAX_PARTIAL_PER_PEER,
         }
     }
 
+    /// Set how many partial messages one peer may have in progress
+    pub fn with_max_partial_per_peer(mut self, max_partial_per_peer: usize) -> Self {
+        self.max_partial_per_peer = max_partial_per_peer;
+        self
+    }
+
+    /// Maximum age for incomplete messages (milliseconds)
+    pub fn max_age_ms(&self) -> u64 {
+        self.max_age_ms
+    }
+
     /// Add a chunk to the buffer
     ///
     /// Returns Some(message) if all chunks received, None otherwise
     pub fn add_chunk(&mut self, chunk: Chunk) -> Result<Option<Vec<u8>>, ChunkError> {
-        let message_id = chunk.header.message_id;
+        self.add_chunk_from("", chunk)
+    }
+
+    /// Add a chunk received from `peer`
+    ///
+    /// Starting a new message fails with `TooManyPartialTransfers` while the
+    /// peer already has the maximum number of incomplete messages buffered.
+    /// Chunks of a message that was already reassembled are ignored.
+    pub fn add_chunk_from(
+        &mut self,
+        peer: &str,
+        chunk: Chunk,
+    ) -> Result<Option<Vec<u8>>, ChunkError> {
+        let key = (peer.to_string(), chunk.header.message_id);
         let total_chunks = chunk.header.total_chunks as usize;
         let chunk_index = chunk.header.chunk_index as usize;
 
+        if self.completed.contains_key(&key) {
+            return Ok(None);
+        }
+
         // Initialize buffer for this message if needed
-        if !self.buffers.contains_key(&message_id) {
-            self.buffers.insert(message_id, vec![None; total_chunks]);
-            self.timestamps.insert(message_id, Self::current_time_ms());
+        if !self.buffers.contains_key(&key) {
+            if self.partial_count(peer) >= self.max_partial_per_peer {
+                return Err(ChunkError::TooManyPartialTransfers(peer.to_string()));
+            }
+            self.buffers.insert(key.clone(), vec![None; total_chunks]);
+            self.timestamps.insert(key.clone(), Self::current_time_ms());
         }
 
         // Get buffer
-        let buffer = self.buffers.get_mut(&message_id).unwrap();
+        let buffer = self.buffers.get_mut(&key).unwrap();
 
         // Verify chunk index is valid
         if chunk_index >= buffer.len() {
@@ -323,29 +554,113 @@ impl ChunkBuffer {
             let chunks: Vec<Chunk> = buffer.iter().filter_map(|c| c.clone()).collect();
 
             // Remove from buffer
-            self.buffers.remove(&message_id);
-            self.timestamps.remove(&message_id);
+            self.remove(&key);
 
             // Reassemble
-            reassemble_chunks(&chunks).map(Some)
+            let message = reassemble_chunks(&chunks)?;
+            self.completed.insert(key, Self::current_time_ms());
+            Ok(Some(message))
         } else {
             Ok(None)
         }
     }
 
+    /// Indices still missing from a message `peer` is sending
+    ///
+    /// Returns None if no chunk of the message is buffered.
+    pub fn missing_chunks(&self, peer: &str, message_id: &Uuid) -> Option<Vec<u8>> {
+        self.buffers
+            .get(&(peer.to_string(), *message_id))
+            .map(|buffer| {
+                (0..buffer.len())
+                    .filter(|&i| buffer[i].is_none())
+                    .map(|i| i as u8)
+                    .collect()
+            })
+    }
+
+    /// Chunks received so far and total for a message `peer` is sending
+    pub fn progress(&self, peer: &str, message_id: &Uuid) -> Option<(usize, usize)> {
+        self.buffers
+            .get(&(peer.to_string(), *message_id))
+            .map(|buffer| (buffer.iter().flatten().count(), buffer.len()))
+    }
+
+    /// Receipt asking `peer` to resend what is still missing
+    pub fn nak_for(&self, peer: &str, message_id: &Uuid) -> Option<ChunkReceipt> {
+        self.missing_chunks(peer, message_id)
+            .map(|missing| ChunkReceipt::Nak {
+                message_id: *message_id,
+                missing,
+            })
+    }
+
+    /// Whether the message from `peer` was reassembled within the buffer's
+    /// maximum age
+    pub fn is_completed(&self, peer: &str, message_id: &Uuid) -> bool {
+        self.completed
+            .contains_key(&(peer.to_string(), *message_id))
+    }
+
+    /// Receipt to send back to `peer` after buffering a chunk with `header`
+    ///
+    /// A reassembled message is acknowledged, again if its chunks are resent
+    /// because the first ACK was lost. Once the last chunk has arrived, the
+    /// gaps are NAKed; before that the receiver waits for more chunks.
+    pub fn receipt_after(&self, peer: &str, header: &ChunkHeader) -> Option<ChunkReceipt> {
+        let message_id = header.message_id;
+        if self.is_completed(peer, &message_id) {
+            Some(ChunkReceipt::Ack { message_id })
+        } else if header.chunk_index as usize + 1 == header.total_chunks as usize {
+            self.nak_for(peer, &message_id)
+        } else {
+            None
+        }
+    }
+
+    /// Incomplete messages buffered from `peer`
+    pub fn partial_count(&self, peer: &str) -> usize {
+        self.buffers.keys().filter(|(p, _)| p == peer).count()
+    }
+
+    /// Whether a partial message from `peer` was started more than `ttl_ms`
+    /// before `now_ms`
+    pub fn is_expired(&self, peer: &str, message_id: &Uuid, now_ms: u64, ttl_ms: u64) -> bool {
+        self.timestamps
+            .get(&(peer.to_string(), *message_id))
+            .is_some_and(|started| now_ms.saturating_sub(*started) > ttl_ms)
+    }
+
+    /// Drop partial messages older than the buffer's maximum age
+    ///
+    /// Returns the peer and ID of each evicted message.
+    pub fn evict_expired(&mut self, now_ms: u64) -> Vec<(String, Uuid)> {
+        let max_age_ms = self.max_age_ms;
+        let expired: Vec<TransferKey> = self
+            .timestamps
+            .iter()
+            .filter(|(_, started)| now_ms.saturating_sub(**started) > max_age_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            self.remove(key);
+        }
+        self.completed
+            .retain(|_, at| now_ms.saturating_sub(*at) <= max_age_ms);
+        if !expired.is_empty() {
+            log::debug!("Evicted {} abandoned chunked transfers", expired.len());
+        }
+        expired
+    }
+
     /// Clean up old incomplete messages
     pub fn cleanup(&mut self) {
-        let now = Self::current_time_ms();
-        let max_age = self.max_age_ms;
+        self.evict_expired(Self::current_time_ms());
+    }
 
-        self.timestamps.retain(|message_id, timestamp| {
-            if now - *timestamp > max_age {
-                self.buffers.remove(message_id);
-                false
-            } else {
-                true
-            }
-        });
+    fn remove(&mut self, key: &TransferKey) {
+        self.buffers.remove(key);
+        self.timestamps.remove(key);
     }
 
     /// Get current time in milliseconds
@@ -357,6 +672,211 @@ impl ChunkBuffer {
     }
 }
 
+/// Receiver's reply to a chunked transfer
+///
+/// Wire format:
+/// - Frame type: 1 byte ([`FRAME_RECEIPT`])
+/// - Message ID: 16 bytes (UUID)
+/// - Kind: 1 byte (0: ACK, 1: NAK)
+/// - NAK only: missing count (1 byte) followed by the missing indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkReceipt {
+    /// Every chunk arrived and the message was reassembled
+    Ack { message_id: Uuid },
+    /// These chunk indices are missing and should be resent
+    Nak { message_id: Uuid, missing: Vec<u8> },
+}
+
+impl ChunkReceipt {
+    /// Message the receipt refers to
+    pub fn message_id(&self) -> Uuid {
+        match self {
+            Self::Ack { message_id } | Self::Nak { message_id, .. } => *message_id,
+        }
+    }
+
+    /// Serialize receipt to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![FRAME_RECEIPT];
+        bytes.extend_from_slice(self.message_id().as_bytes());
+        match self {
+            Self::Ack { .. } => bytes.push(0),
+            Self::Nak { missing, .. } => {
+                bytes.push(1);
+                bytes.push(missing.len() as u8);
+                bytes.extend_from_slice(missing);
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize receipt from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkError> {
+        if bytes.len() < 18 || bytes[0] != FRAME_RECEIPT {
+            return Err(ChunkError::InvalidHeader);
+        }
+        let message_id = Uuid::from_slice(&bytes[1..17]).map_err(|_| ChunkError::InvalidHeader)?;
+
+        match bytes[17] {
+            0 => Ok(Self::Ack { message_id }),
+            1 => {
+                let count = *bytes.get(18).ok_or(ChunkError::InvalidHeader)? as usize;
+                let missing = bytes.get(19..19 + count).ok_or(ChunkError::InvalidHeader)?;
+                Ok(Self::Nak {
+                    message_id,
+                    missing: missing.to_vec(),
+                })
+            }
+            _ => Err(ChunkError::InvalidHeader),
+        }
+    }
+}
+
+/// One write on the mesh characteristic between peers that negotiated
+/// acknowledged transfers, told apart by its frame type byte
+///
+/// A chunk frame is [`FRAME_CHUNK`] followed by the chunk as the spec
+/// lays it out; a receipt opens with [`FRAME_RECEIPT`].
+#[derive(Debug, Clone)]
+pub enum ChunkFrame {
+    Chunk(Chunk),
+    Receipt(ChunkReceipt),
+}
+
+impl ChunkFrame {
+    /// Serialize frame to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Chunk(chunk) => chunk.to_frame_bytes(),
+            Self::Receipt(receipt) => receipt.to_bytes(),
+        }
+    }
+
+    /// Deserialize frame from bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ChunkError> {
+        match bytes.first() {
+            Some(&FRAME_CHUNK) => Chunk::from_bytes(&bytes[1..]).map(Self::Chunk),
+            Some(&FRAME_RECEIPT) => ChunkReceipt::from_bytes(bytes).map(Self::Receipt),
+            _ => Err(ChunkError::InvalidHeader),
+        }
+    }
+}
+
+/// Sender side of a chunked transfer, resending chunks until acknowledged
+///
+/// A NAK resends the chunks it lists; silence for `retry_timeout` resends
+/// every chunk. Each resend counts as a retry, and once `max_retries` are
+/// used up the transfer fails with `RetriesExhausted`.
+pub struct OutgoingTransfer {
+    chunks: Vec<Chunk>,
+    retry_timeout: Duration,
+    max_retries: u32,
+    retries: u32,
+    last_sent: Instant,
+    acknowledged: bool,
+}
+
+impl OutgoingTransfer {
+    /// Start a transfer whose chunks are sent at `now`
+    pub fn new(
+        chunks: Vec<Chunk>,
+        retry_timeout: Duration,
+        max_retries: u32,
+        now: Instant,
+    ) -> Self {
+        Self {
+            chunks,
+            retry_timeout,
+            max_retries,
+            retries: 0,
+            last_sent: now,
+            acknowledged: false,
+        }
+    }
+
+    /// Message ID shared by the transfer's chunks
+    pub fn message_id(&self) -> Option<Uuid> {
+        self.chunks.first().map(|c| c.header.message_id)
+    }
+
+    /// Chunks to send initially
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Whether the receiver has acknowledged the whole message
+    pub fn is_complete(&self) -> bool {
+        self.acknowledged
+    }
+
+    /// Retransmission rounds used so far
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Restart the retry timeout once the chunks finished going out at `now`
+    pub fn mark_sent(&mut self, now: Instant) {
+        self.last_sent = now;
+    }
+
+    /// When everything is resent if no receipt arrives first
+    pub fn retry_deadline(&self) -> Instant {
+        self.last_sent + self.retry_timeout
+    }
+
+    /// Apply a receipt from the receiver
+    ///
+    /// Returns the chunks to resend (empty once acknowledged). Receipts for
+    /// other messages are ignored.
+    pub fn handle_receipt(
+        &mut self,
+        receipt: &ChunkReceipt,
+        now: Instant,
+    ) -> Result<Vec<Chunk>, ChunkError> {
+        if Some(receipt.message_id()) != self.message_id() || self.acknowledged {
+            return Ok(Vec::new());
+        }
+
+        match receipt {
+            ChunkReceipt::Ack { .. } => {
+                self.acknowledged = true;
+                Ok(Vec::new())
+            }
+            ChunkReceipt::Nak { missing, .. } => {
+                let resend: Vec<Chunk> = self
+                    .chunks
+                    .iter()
+                    .filter(|c| missing.contains(&c.header.chunk_index))
+                    .cloned()
+                    .collect();
+                self.retry(now)?;
+                Ok(resend)
+            }
+        }
+    }
+
+    /// Resend everything if no receipt arrived within the retry timeout
+    pub fn poll_timeout(&mut self, now: Instant) -> Result<Vec<Chunk>, ChunkError> {
+        if self.acknowledged || now.duration_since(self.last_sent) < self.retry_timeout {
+            return Ok(Vec::new());
+        }
+        self.retry(now)?;
+        Ok(self.chunks.clone())
+    }
+
+    fn retry(&mut self, now: Instant) -> Result<(), ChunkError> {
+        if self.retries >= self.max_retries {
+            return Err(ChunkError::RetriesExhausted(
+                self.message_id().unwrap_or_default(),
+                self.retries,
+            ));
+        }
+        self.retries += 1;
+        self.last_sent = now;
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -367,7 +887,6 @@ mod tests {
             message_id: Uuid::new_v4(),
             chunk_index: 5,
             total_chunks: 10,
-            payload_length: 256,
             compressed: true,
         };
 
@@ -378,14 +897,131 @@ mod tests {
         assert_eq!(deserialized.message_id, header.message_id);
         assert_eq!(deserialized.chunk_index, header.chunk_index);
         assert_eq!(deserialized.total_chunks, header.total_chunks);
-        assert_eq!(deserialized.payload_length, header.payload_length);
         assert_eq!(deserialized.compressed, header.compressed);
     }
 
+    /// Chunking vectors shared with the other clients
+    const CHUNKING_VECTORS: &str =
+        include_str!("../../../../protocol/test-vectors/ble/chunking.json");
+
+    #[test]
+    fn test_decodes_chunking_vectors() {
+        let vectors: serde_json::Value = serde_json::from_str(CHUNKING_VECTORS).unwrap();
+        assert_eq!(
+            vectors["binary_header_format"]["total_size"],
+            CHUNK_HEADER_SIZE
+        );
+
+        // Header vectors describe the chunk in their input; chunk lists
+        // describe each chunk next to its header and payload
+        let mut decoded = 0;
+        for vector in vectors["vectors"].as_array().unwrap() {
+            let id = vector["id"].as_str().unwrap();
+            let expected = &vector["expected"];
+            let mut headers = Vec::new();
+            if let Some(header_hex) = expected["header_hex"].as_str() {
+                let input = &vector["input"];
+                headers.push((header_hex, input, &input["is_compressed"], None));
+            }
+            for chunk in expected["chunks"].as_array().into_iter().flatten() {
+                if let Some(header_hex) = chunk["header_hex"].as_str() {
+                    let compressed = &chunk["flags"]["is_compressed"];
+                    headers.push((header_hex, chunk, compressed, chunk["payload_hex"].as_str()));
+                }
+            }
+
+            for (header_hex, fields, compressed, payload_hex) in headers {
+                let bytes = hex::decode(header_hex).unwrap();
+                let header =
+                    ChunkHeader::from_bytes(&bytes).unwrap_or_else(|e| panic!("{}: {}", id, e));
+                assert_eq!(fields["chunk_index"], header.chunk_index, "{}", id);
+                assert_eq!(fields["total_chunks"], header.total_chunks, "{}", id);
+                assert_eq!(*compressed, header.compressed, "{}", id);
+                assert_eq!(
+                    fields["message_id"],
+                    header.message_id.to_string(),
+                    "{}",
+                    id
+                );
+                assert_eq!(header.to_bytes(), bytes, "{}", id);
+
+                if let Some(payload_hex) = payload_hex {
+                    let mut chunk = bytes;
+                    chunk.extend(hex::decode(payload_hex).unwrap());
+                    let chunk = Chunk::from_bytes(&chunk).unwrap();
+                    assert_eq!(hex::encode(&chunk.payload), payload_hex, "{}", id);
+                }
+                decoded += 1;
+            }
+        }
+        assert_eq!(decoded, 3);
+
+        // Frames of the acknowledged extension
+        for vector in vectors["acknowledged_extension"]["vectors"]
+            .as_array()
+            .unwrap()
+        {
+            let id = vector["id"].as_str().unwrap();
+            let input = &vector["input"];
+            let bytes = hex::decode(vector["expected"]["frame_hex"].as_str().unwrap()).unwrap();
+            let message_id: Uuid = input["message_id"].as_str().unwrap().parse().unwrap();
+
+            match ChunkFrame::from_bytes(&bytes).unwrap_or_else(|e| panic!("{}: {}", id, e)) {
+                ChunkFrame::Chunk(chunk) => {
+                    assert_eq!(chunk.header.message_id, message_id, "{}", id);
+                    assert_eq!(
+                        reassemble_chunks(std::slice::from_ref(&chunk)).unwrap(),
+                        input["message"].as_str().unwrap().as_bytes(),
+                        "{}",
+                        id
+                    );
+                    assert_eq!(chunk.to_frame_bytes(), bytes, "{}", id);
+                }
+                ChunkFrame::Receipt(receipt) => {
+                    let expected = match input["kind"].as_str().unwrap() {
+                        "ack" => ChunkReceipt::Ack { message_id },
+                        _ => ChunkReceipt::Nak {
+                            message_id,
+                            missing: serde_json::from_value(input["missing"].clone()).unwrap(),
+                        },
+                    };
+                    assert_eq!(receipt, expected, "{}", id);
+                    assert_eq!(receipt.to_bytes(), bytes, "{}", id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_rejects_inconsistent_last_flag() {
+        let header = ChunkHeader {
+            message_id: Uuid::new_v4(),
+            chunk_index: 1,
+            total_chunks: 3,
+            compressed: false,
+        };
+        let mut bytes = header.to_bytes();
+        assert_eq!(bytes[4], 0x00);
+
+        bytes[4] = FLAG_LAST;
+        assert!(matches!(
+            ChunkHeader::from_bytes(&bytes),
+            Err(ChunkError::InvalidHeader)
+        ));
+
+        // More chunks than a receipt can list
+        let mut bytes = header.to_bytes();
+        bytes[2..4].copy_from_slice(&256u16.to_le_bytes());
+        assert!(matches!(
+            ChunkHeader::from_bytes(&bytes),
+            Err(ChunkError::InvalidHeader)
+        ));
+    }
+
     #[test]
     fn test_small_message_chunking() {
         let data = b"Hello, World!";
-        let chunks = chunk_message(data).unwrap();
+        let chunks = chunk_message(data, MAX_MTU).unwrap();
 
         assert_eq!(chunks.len(), 1);
         assert!(!chunks[0].header.compressed);
@@ -397,7 +1033,7 @@ mod tests {
     #[test]
     fn test_large_message_chunking() {
         let data = vec![0x42u8; 2000]; // 2KB message
-        let chunks = chunk_message(&data).unwrap();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
 
         assert!(chunks.len() > 1);
 
@@ -408,7 +1044,7 @@ mod tests {
     #[test]
     fn test_compression() {
         let data = vec![0x41u8; 500]; // Highly compressible data
-        let chunks = chunk_message(&data).unwrap();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
 
         // Should be compressed
         assert!(chunks[0].header.compressed);
@@ -422,7 +1058,7 @@ mod tests {
         let mut buffer = ChunkBuffer::new(60000);
 
         let data = vec![0x42u8; 2000];
-        let chunks = chunk_message(&data).unwrap();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
 
         // Add chunks in reverse order
         for chunk in chunks.iter().rev() {
@@ -435,4 +1071,477 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_dropped_chunk_is_retransmitted() {
+        // Random bytes don't compress, so this spans several chunks
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        assert!(chunks.len() >= 3);
+        let message_id = chunks[0].header.message_id;
+
+        let now = Instant::now();
+        let mut sender = OutgoingTransfer::new(chunks.clone(), DEFAULT_RETRY_TIMEOUT, 3, now);
+        let mut receiver = ChunkBuffer::new(60000);
+
+        // The middle chunk is lost in transit
+        for chunk in sender.chunks().iter().filter(|c| c.header.chunk_index != 1) {
+            assert!(receiver.add_chunk(chunk.clone()).unwrap().is_none());
+        }
+        let nak = receiver.nak_for("", &message_id).unwrap();
+        assert_eq!(
+            nak,
+            ChunkReceipt::Nak {
+                message_id,
+                missing: vec![1],
+            }
+        );
+
+        let nak = ChunkReceipt::from_bytes(&nak.to_bytes()).unwrap();
+        let resend = sender.handle_receipt(&nak, now).unwrap();
+        assert_eq!(resend.len(), 1);
+        assert_eq!(resend[0].header.chunk_index, 1);
+
+        let message = receiver.add_chunk(resend[0].clone()).unwrap().unwrap();
+        assert_eq!(message, data);
+
+        let ack = ChunkReceipt::Ack { message_id };
+        let ack = ChunkReceipt::from_bytes(&ack.to_bytes()).unwrap();
+        assert!(sender.handle_receipt(&ack, now).unwrap().is_empty());
+        assert!(sender.is_complete());
+        assert_eq!(sender.retries(), 1);
+    }
+
+    #[test]
+    fn test_frames_tell_chunks_from_receipts() {
+        let chunks = chunk_message(b"Hello, World!", MAX_MTU).unwrap();
+        let message_id = chunks[0].header.message_id;
+        let receipts = [
+            ChunkReceipt::Ack { message_id },
+            ChunkReceipt::Nak {
+                message_id,
+                missing: vec![0],
+            },
+        ];
+
+        let frame = ChunkFrame::from_bytes(&ChunkFrame::Chunk(chunks[0].clone()).to_bytes());
+        assert!(matches!(frame, Ok(ChunkFrame::Chunk(c)) if c.header.message_id == message_id));
+        for receipt in receipts {
+            let frame = ChunkFrame::from_bytes(&receipt.to_bytes()).unwrap();
+            assert!(matches!(frame, ChunkFrame::Receipt(r) if r == receipt));
+        }
+
+        // A chunk frame never parses as a receipt
+        assert!(ChunkReceipt::from_bytes(&chunks[0].to_frame_bytes()).is_err());
+        assert!(matches!(
+            ChunkFrame::from_bytes(&[0x7b, 0x22]),
+            Err(ChunkError::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_receipts_after_each_chunk() {
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let last = chunks.len() - 1;
+        let message_id = chunks[0].header.message_id;
+        let mut buffer = ChunkBuffer::new(60000);
+
+        // Nothing to say until the last chunk shows a gap
+        buffer.add_chunk(chunks[0].clone()).unwrap();
+        assert_eq!(buffer.receipt_after("", &chunks[0].header), None);
+        buffer.add_chunk(chunks[last].clone()).unwrap();
+        assert_eq!(
+            buffer.receipt_after("", &chunks[last].header),
+            Some(ChunkReceipt::Nak {
+                message_id,
+                missing: (1..last as u8).collect(),
+            })
+        );
+
+        for chunk in &chunks[1..last] {
+            buffer.add_chunk(chunk.clone()).unwrap();
+        }
+        assert!(buffer.is_completed("", &message_id));
+        assert_eq!(
+            buffer.receipt_after("", &chunks[1].header),
+            Some(ChunkReceipt::Ack { message_id })
+        );
+
+        // Resent after a lost ACK: acknowledged again, delivered once
+        for chunk in &chunks {
+            assert!(buffer.add_chunk(chunk.clone()).unwrap().is_none());
+        }
+        assert_eq!(
+            buffer.receipt_after("", &chunks[last].header),
+            Some(ChunkReceipt::Ack { message_id })
+        );
+        assert!(buffer.missing_chunks("", &message_id).is_none());
+    }
+
+    #[test]
+    fn test_permanently_lost_chunk_fails_after_max_retries() {
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let message_id = chunks[0].header.message_id;
+        let timeout = Duration::from_millis(100);
+        let mut now = Instant::now();
+        let mut sender = OutgoingTransfer::new(chunks, timeout, 2, now);
+
+        // Nothing before the timeout
+        assert!(sender.poll_timeout(now + timeout / 2).unwrap().is_empty());
+
+        // The receiver never answers: two full resends, then failure
+        for _ in 0..2 {
+            now += timeout;
+            assert_eq!(
+                sender.poll_timeout(now).unwrap().len(),
+                sender.chunks().len()
+            );
+        }
+        now += timeout;
+        assert!(matches!(
+            sender.poll_timeout(now),
+            Err(ChunkError::RetriesExhausted(id, 2)) if id == message_id
+        ));
+        assert!(!sender.is_complete());
+    }
+
+    #[test]
+    fn test_abandoned_partial_transfer_evicted_after_ttl() {
+        let mut buffer = ChunkBuffer::new(1000);
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let message_id = chunks[0].header.message_id;
+
+        // Only the first chunk ever arrives
+        assert!(buffer
+            .add_chunk_from("peer-a", chunks[0].clone())
+            .unwrap()
+            .is_none());
+        let started = ChunkBuffer::current_time_ms();
+
+        assert!(!buffer.is_expired("peer-a", &message_id, started, 1000));
+        assert!(buffer.evict_expired(started + 500).is_empty());
+        assert!(buffer.missing_chunks("peer-a", &message_id).is_some());
+
+        assert!(buffer.is_expired("peer-a", &message_id, started + 1001, 1000));
+        assert_eq!(
+            buffer.evict_expired(started + 1001),
+            vec![("peer-a".to_string(), message_id)]
+        );
+        assert!(buffer.missing_chunks("peer-a", &message_id).is_none());
+        assert_eq!(buffer.partial_count("peer-a"), 0);
+    }
+
+    #[test]
+    fn test_partial_transfers_capped_per_peer() {
+        let mut buffer = ChunkBuffer::new(60000).with_max_partial_per_peer(2);
+        let first_chunk = || {
+            let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+            chunk_message(&data, MAX_MTU).unwrap().remove(0)
+        };
+
+        let open = first_chunk();
+        buffer.add_chunk_from("peer-a", open.clone()).unwrap();
+        buffer.add_chunk_from("peer-a", first_chunk()).unwrap();
+        assert!(matches!(
+            buffer.add_chunk_from("peer-a", first_chunk()),
+            Err(ChunkError::TooManyPartialTransfers(peer)) if peer == "peer-a"
+        ));
+
+        // Other peers and chunks of transfers already open are unaffected
+        buffer.add_chunk_from("peer-b", first_chunk()).unwrap();
+        let mut next = open;
+        next.header.chunk_index = 1;
+        assert!(buffer.add_chunk_from("peer-a", next).unwrap().is_none());
+        assert_eq!(buffer.partial_count("peer-a"), 2);
+    }
+
+    #[test]
+    fn test_same_message_id_from_two_peers_kept_apart() {
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let message_id = chunks[0].header.message_id;
+        let mut buffer = ChunkBuffer::new(60000);
+
+        // peer-b reuses peer-a's message ID with a chunk of its own
+        let mut forged = chunks[1].clone();
+        forged.payload.fill(0);
+        for chunk in &chunks[..chunks.len() - 1] {
+            buffer.add_chunk_from("peer-a", chunk.clone()).unwrap();
+        }
+        assert!(buffer.add_chunk_from("peer-b", forged).unwrap().is_none());
+        assert_eq!(buffer.partial_count("peer-b"), 1);
+
+        let last = chunks.last().unwrap().clone();
+        assert_eq!(buffer.add_chunk_from("peer-a", last).unwrap(), Some(data));
+        assert!(buffer.is_completed("peer-a", &message_id));
+        assert!(!buffer.is_completed("peer-b", &message_id));
+        assert_eq!(
+            buffer
+                .missing_chunks("peer-b", &message_id)
+                .map(|m| m.len()),
+            Some(chunks.len() - 1)
+        );
+    }
+
+    #[test]
+    fn test_reassembly_verifies_message_digest() {
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        assert_eq!(reassemble_chunks(&chunks).unwrap(), data);
+
+        // A chunk with altered bytes still parses but fails verification
+        let mut tampered = chunks.clone();
+        tampered[1].payload[7] ^= 0x01;
+        let tampered: Vec<Chunk> = tampered
+            .iter()
+            .map(|c| Chunk::from_bytes(&c.to_bytes()).unwrap())
+            .collect();
+        assert!(matches!(
+            reassemble_chunks(&tampered),
+            Err(ChunkError::IntegrityMismatch)
+        ));
+
+        // So does a compressed message whose digest was altered
+        let data = vec![0x41u8; 500];
+        let mut chunks = chunk_message(&data, MAX_MTU).unwrap();
+        assert!(chunks[0].header.compressed);
+        let last = chunks.last_mut().unwrap().payload.last_mut().unwrap();
+        *last ^= 0x01;
+        assert!(matches!(
+            reassemble_chunks(&chunks),
+            Err(ChunkError::IntegrityMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_decompression_is_capped() {
+        // Zeros deflate to almost nothing, so a few chunks could expand to
+        // far more than any message the sender was allowed to chunk
+        let bomb = compress(&vec![0u8; MAX_MESSAGE_LEN + 1]).unwrap();
+        assert!(bomb.len() < MAX_CHUNK_PAYLOAD * 8);
+        assert!(matches!(
+            decompress(&bomb, MAX_MESSAGE_LEN),
+            Err(ChunkError::DecompressionFailed(_))
+        ));
+
+        let mut stream = bomb;
+        stream.extend_from_slice(&[0u8; MESSAGE_DIGEST_SIZE]);
+        let total_chunks = ((stream.len() + MAX_CHUNK_PAYLOAD - 1) / MAX_CHUNK_PAYLOAD) as u8;
+        let message_id = Uuid::new_v4();
+        let chunks: Vec<Chunk> = stream
+            .chunks(MAX_CHUNK_PAYLOAD)
+            .enumerate()
+            .map(|(i, payload)| Chunk {
+                header: ChunkHeader {
+                    message_id,
+                    chunk_index: i as u8,
+                    total_chunks,
+                    compressed: true,
+                },
+                payload: payload.to_vec(),
+            })
+            .collect();
+        assert!(matches!(
+            reassemble_chunks(&chunks),
+            Err(ChunkError::DecompressionFailed(_))
+        ));
+
+        // The sender refuses anything the receiver would refuse
+        assert!(matches!(
+            chunk_message(&vec![0u8; MAX_MESSAGE_LEN + 1], MAX_MTU),
+            Err(ChunkError::MessageTooLarge(_))
+        ));
+        let data = vec![0x41u8; MAX_MESSAGE_LEN];
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        assert_eq!(reassemble_chunks(&chunks).unwrap(), data);
+    }
+
+    #[test]
+    fn test_estimate_chunked_transfer_at_mtu_boundaries() {
+        let per_chunk = max_mesh_payload(DEFAULT_ATT_MTU);
+        let estimate = |len| estimate_chunked_transfer(len, DEFAULT_ATT_MTU, 30).unwrap();
+
+        // Exactly filling chunks, then one byte over
+        let exact = 2 * per_chunk - MESSAGE_DIGEST_SIZE;
+        assert_eq!(estimate(exact).chunk_count, 2);
+        assert_eq!(estimate(exact + 1).chunk_count, 3);
+        assert_eq!(estimate(0).chunk_count, 1);
+
+        // Matches what chunk_message produces for incompressible data
+        let data: Vec<u8> = (0..exact / 16 + 1)
+            .flat_map(|_| *Uuid::new_v4().as_bytes())
+            .take(exact)
+            .collect();
+        let write_size = DEFAULT_ATT_MTU as usize - ATT_WRITE_OVERHEAD;
+        assert_eq!(chunk_message(&data, write_size).unwrap().len(), 2);
+
+        // A smaller MTU means more chunks
+        assert_eq!(
+            estimate_chunked_transfer(exact, 103, 30)
+                .unwrap()
+                .chunk_count,
+            (exact + MESSAGE_DIGEST_SIZE + 77) / 78
+        );
+        assert!(matches!(
+            estimate_chunked_transfer(10, (ATT_WRITE_OVERHEAD + CHUNK_FRAME_OVERHEAD) as u16, 30),
+            Err(ChunkError::InvalidChunkSize(_))
+        ));
+        assert!(matches!(
+            estimate_chunked_transfer(256 * per_chunk, DEFAULT_ATT_MTU, 30),
+            Err(ChunkError::MessageTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_estimate_matches_chunks_sent_at_the_same_mtu() {
+        // Hash output, which deflate can't shrink
+        let random = |len: usize| -> Vec<u8> {
+            (0..len / 32 + 1)
+                .flat_map(|i: usize| Sha256::digest(i.to_le_bytes()))
+                .take(len)
+                .collect()
+        };
+
+        for att_mtu in [DEFAULT_ATT_MTU, 247, 517] {
+            // Sized the way MeshLink::send sizes a chunked send
+            let write_size = CHUNK_FRAME_OVERHEAD + DeviceMtu::new(Some(att_mtu)).max_mesh_payload;
+            for len in [0, 100, 1000, 10_000, 30_000] {
+                let sent = chunk_message(&random(len), write_size).unwrap().len();
+                let estimate = estimate_chunked_transfer(len, att_mtu, 0).unwrap();
+                assert_eq!(estimate.chunk_count, sent, "mtu {} len {}", att_mtu, len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_estimate_chunked_transfer_time() {
+        assert_eq!(
+            estimate_chunked_transfer(2000, 517, 50).unwrap(),
+            TransferEstimate {
+                chunk_count: 5,
+                estimated_ms: 250,
+            }
+        );
+        assert_eq!(
+            estimate_chunked_transfer(2000, 517, 0)
+                .unwrap()
+                .estimated_ms,
+            0
+        );
+    }
+
+    #[test]
+    fn test_chunking_at_several_chunk_sizes() {
+        let data: Vec<u8> = (0..100).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+
+        for chunk_size in [30, 64, 185, 244, MAX_MTU, 4096] {
+            let chunks = chunk_message(&data, chunk_size).unwrap_or_else(|e| {
+                panic!("chunk size {}: {}", chunk_size, e);
+            });
+            assert!(chunks
+                .iter()
+                .all(|c| c.to_frame_bytes().len() <= chunk_size));
+            assert_eq!(
+                chunks.len(),
+                estimate_chunked_transfer(data.len(), (chunk_size + ATT_WRITE_OVERHEAD) as u16, 0)
+                    .unwrap()
+                    .chunk_count
+            );
+
+            // Deliver out of order through the reassembly buffer
+            let mut buffer = ChunkBuffer::new(60000);
+            let mut message = None;
+            for chunk in chunks.into_iter().rev() {
+                message = buffer.add_chunk(chunk).unwrap();
+            }
+            assert_eq!(message, Some(data.clone()), "chunk size {}", chunk_size);
+        }
+
+        // Too small for header and payload, or too many chunks
+        for chunk_size in [0, CHUNK_FRAME_OVERHEAD] {
+            assert!(matches!(
+                chunk_message(&data, chunk_size),
+                Err(ChunkError::InvalidChunkSize(size)) if size == chunk_size
+            ));
+        }
+        assert!(matches!(
+            chunk_message(&data, CHUNK_FRAME_OVERHEAD + 4),
+            Err(ChunkError::MessageTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn test_max_mesh_payload_from_mtu() {
+        let framing = ATT_WRITE_OVERHEAD + CHUNK_FRAME_OVERHEAD;
+        assert_eq!(max_mesh_payload(517), 517 - framing);
+        assert_eq!(max_mesh_payload(247), 222);
+        assert_eq!(max_mesh_payload(DEFAULT_ATT_MTU), 160);
+
+        // The spec minimum MTU cannot carry a chunk
+        assert_eq!(max_mesh_payload(23), 0);
+        assert_eq!(max_mesh_payload(framing as u16), 0);
+        assert_eq!(max_mesh_payload(framing as u16 + 1), 1);
+
+        // A chunk filled to the payload fits one write
+        let data: Vec<u8> = (0..20).flat_map(|_| *Uuid::new_v4().as_bytes()).collect();
+        let write_size = 247 - ATT_WRITE_OVERHEAD;
+        let chunks = chunk_message(&data, write_size).unwrap();
+        assert_eq!(chunks[0].payload.len(), max_mesh_payload(247));
+        assert!(chunks
+            .iter()
+            .all(|c| c.to_frame_bytes().len() <= write_size));
+    }
+
+    #[test]
+    fn test_device_mtu_falls_back_to_default() {
+        let unreported = DeviceMtu::new(None);
+        assert_eq!(unreported.att_mtu, DEFAULT_ATT_MTU);
+        assert_eq!(
+            unreported.max_mesh_payload,
+            max_mesh_payload(DEFAULT_ATT_MTU)
+        );
+        assert!(unreported.max_mesh_payload > 0);
+
+        assert_eq!(
+            DeviceMtu::new(Some(247)),
+            DeviceMtu {
+                att_mtu: 247,
+                max_mesh_payload: 222,
+            }
+        );
+    }
+
+    #[test]
+    fn test_check_transfer_size() {
+        // Hash output, which deflate can't shrink
+        let random = |len: usize| -> Vec<u8> {
+            (0..len / 32 + 1)
+                .flat_map(|i: usize| Sha256::digest(i.to_le_bytes()))
+                .take(len)
+                .collect()
+        };
+
+        // Well past one write, but within 255 chunks
+        assert!(check_transfer_size(&random(1024), DEFAULT_ATT_MTU).is_ok());
+
+        // 255 chunks, digest trailer included, is the limit at any MTU
+        let capacity = max_mesh_payload(DEFAULT_ATT_MTU) * 255 - 32;
+        assert!(check_transfer_size(&random(capacity), DEFAULT_ATT_MTU).is_ok());
+        let too_many = random(capacity + 1);
+        assert!(matches!(
+            check_transfer_size(&too_many, DEFAULT_ATT_MTU),
+            Err(ChunkError::MessageTooLarge(_))
+        ));
+        assert!(check_transfer_size(&too_many, 517).is_ok());
+
+        // Compressible data is checked at its compressed size
+        assert!(check_transfer_size(&vec![0u8; 100_000], DEFAULT_ATT_MTU).is_ok());
+        assert!(matches!(
+            check_transfer_size(&vec![0u8; MAX_MESSAGE_LEN + 1], 517),
+            Err(ChunkError::MessageTooLarge(_))
+        ));
+    }
 }
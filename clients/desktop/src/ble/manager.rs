@@ -7,18 +7,29 @@
 //! - Commitment-based identity (H(pubkey || nonce)) instead of exposing public keys
 //! - No public key exposure in advertisements
 
+use super::chunk::{
+    chunk_message, should_report_progress, Chunk, ChunkBuffer, ChunkError, ChunkFrame,
+    ChunkReceipt, DeviceMtu, OutgoingTransfer, CHUNK_FRAME_OVERHEAD, DEFAULT_MAX_RETRIES,
+    DEFAULT_REASSEMBLY_TIMEOUT_MS, DEFAULT_RETRY_TIMEOUT, FEATURE_CHUNK_RECEIPTS,
+};
+use super::mesh::{MeshMessage, MessageType, MAX_MESSAGE_SIZE};
+use super::peripheral::{self, AdvertisementPayload, GattServer, PeripheralBackend};
 use btleplug::api::{
     BDAddr, Central, Characteristic, Manager as BtManager, Peripheral, ScanFilter, WriteType,
 };
 use btleplug::platform::{Adapter, Manager, Peripheral as PlatformPeripheral};
+use futures::future::LocalBoxFuture;
 use futures::stream::StreamExt;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
 /// Base UUID components for BuildIt Network BLE Service
@@ -34,6 +45,15 @@ const BUILDIT_IDENTITY_CHAR_OFFSET: u128 = 0x02;
 /// BuildIt Network Handshake Characteristic UUID (for commitment reveal)
 const BUILDIT_HANDSHAKE_CHAR_OFFSET: u128 = 0x03;
 
+/// BuildIt Network Features Characteristic UUID (supported feature bitmap)
+///
+/// Matches the spec's FEATURES suffix so it never collides with a spec
+/// characteristic.
+const BUILDIT_FEATURES_CHAR_OFFSET: u128 = 0x07;
+
+/// Feature bitmap we serve on, and write to, the features characteristic
+pub const SUPPORTED_FEATURES: u8 = FEATURE_CHUNK_RECEIPTS;
+
 /// Service UUID rotation interval in seconds (24 hours)
 const UUID_ROTATION_INTERVAL_SECS: u64 = 86400;
 
@@ -41,6 +61,30 @@ const UUID_ROTATION_INTERVAL_SECS: u64 = 86400;
 /// In production, this could be derived from a more sophisticated mechanism
 const UUID_DERIVATION_SEED: &[u8] = b"BuildItNetwork-BLE-UUID-Seed-v1";
 
+/// Length of the identity commitment carried in advertisement service data
+pub const COMMITMENT_ADVERTISEMENT_LEN: usize = 20;
+
+/// Default capacity of the BLE event broadcast channel
+pub const DEFAULT_EVENT_CAPACITY: usize = 100;
+
+/// Length of the hex public key at the start of a handshake payload
+const HANDSHAKE_PUBKEY_HEX_LEN: usize = 64;
+
+/// Scheme prefix of an identity verification QR payload
+pub const IDENTITY_QR_PREFIX: &str = "buildit-id:";
+
+/// Default time to wait for pongs in a mesh connectivity check
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// RSSI change (dB) that on its own makes a `DeviceUpdated` worth sending
+pub const RSSI_CHANGE_THRESHOLD: u16 = 8;
+
+/// How long `reset_adapter` waits for devices on the old adapter to disconnect
+const RESET_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often abandoned partial chunk transfers are evicted
+const CHUNK_EVICTION_INTERVAL: Duration = Duration::from_secs(10);
+
 /// BLE operation errors
 #[derive(Debug, Error)]
 pub enum BleError {
@@ -79,6 +123,18 @@ pub enum BleError {
 
     #[error("Commitment verification failed")]
     CommitmentVerificationFailed,
+
+    #[error("Device not authenticated: {0}")]
+    NotAuthenticated(String),
+
+    #[error("Scan mode not supported on this platform: {0:?}")]
+    ScanModeUnsupported(ScanMode),
+
+    #[error("Invalid scan duty cycle: {0}")]
+    InvalidDutyCycle(String),
+
+    #[error("Chunked transfer failed: {0}")]
+    Transfer(#[from] ChunkError),
 }
 
 /// Generate the current service UUID based on daily rotation
@@ -131,6 +187,23 @@ pub fn get_handshake_characteristic_uuid() -> Uuid {
     Uuid::from_u128(service_u128.wrapping_add(BUILDIT_HANDSHAKE_CHAR_OFFSET))
 }
 
+/// Get the features characteristic UUID for the current service
+pub fn get_features_characteristic_uuid() -> Uuid {
+    let service = get_current_service_uuid();
+    let service_u128 = u128::from_be_bytes(*service.as_bytes());
+    Uuid::from_u128(service_u128.wrapping_add(BUILDIT_FEATURES_CHAR_OFFSET))
+}
+
+/// Whether a feature bitmap read from a peer includes chunk receipts
+///
+/// Peers without the features characteristic, or that leave it empty,
+/// support none.
+pub fn takes_chunk_receipts(features: &[u8]) -> bool {
+    features
+        .first()
+        .is_some_and(|bits| bits & FEATURE_CHUNK_RECEIPTS != 0)
+}
+
 /// Legacy static UUIDs (deprecated, kept for migration)
 /// DO NOT USE - these expose users to tracking
 #[deprecated(note = "Use get_current_service_uuid() for rotating UUIDs")]
@@ -189,6 +262,103 @@ impl IdentityCommitment {
     pub fn advertisement_data(&self) -> Vec<u8> {
         self.commitment.clone()
     }
+
+    /// Handshake payload revealing the commitment: pubkey (64 hex chars) + nonce
+    pub fn handshake_data(&self) -> Vec<u8> {
+        let mut data = self.pubkey.as_bytes().to_vec();
+        data.extend_from_slice(&self.nonce);
+        data
+    }
+
+    /// Split a handshake payload into (pubkey hex, nonce)
+    pub fn parse_handshake(data: &[u8]) -> Result<(String, &[u8]), BleError> {
+        if data.len() < HANDSHAKE_PUBKEY_HEX_LEN + 1 {
+            return Err(BleError::CommitmentVerificationFailed);
+        }
+        let pubkey = std::str::from_utf8(&data[..HANDSHAKE_PUBKEY_HEX_LEN])
+            .map_err(|_| BleError::CommitmentVerificationFailed)?;
+        Ok((pubkey.to_string(), &data[HANDSHAKE_PUBKEY_HEX_LEN..]))
+    }
+}
+
+/// QR payload for verifying `pubkey` in person
+///
+/// Format: `buildit-id:<commitment hex>:<nonce hex>:<pubkey>`, with a fresh
+/// nonce each time. The scanning side checks it with
+/// `verify_identity_qr_payload` against the key from the BLE handshake.
+pub fn make_identity_qr_payload(pubkey: &str) -> String {
+    let identity = IdentityCommitment::new(pubkey);
+    format!(
+        "{}{}:{}:{}",
+        IDENTITY_QR_PREFIX,
+        hex::encode(&identity.commitment),
+        hex::encode(&identity.nonce),
+        identity.pubkey
+    )
+}
+
+/// Whether a scanned QR payload commits to `expected_pubkey`
+///
+/// Fails on a malformed payload, a pubkey other than the expected one, or a
+/// commitment that does not match the pubkey and nonce it carries.
+pub fn verify_identity_qr_payload(scanned: &str, expected_pubkey: &str) -> bool {
+    let Some(fields) = scanned.trim().strip_prefix(IDENTITY_QR_PREFIX) else {
+        return false;
+    };
+    let mut parts = fields.split(':');
+    let (Some(commitment), Some(nonce), Some(pubkey), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let (Ok(commitment), Ok(nonce)) = (hex::decode(commitment), hex::decode(nonce)) else {
+        return false;
+    };
+
+    commitment.len() == COMMITMENT_ADVERTISEMENT_LEN
+        && !nonce.is_empty()
+        && pubkey.eq_ignore_ascii_case(expected_pubkey.trim())
+        && IdentityCommitment::verify(&commitment, pubkey, &nonce)
+}
+
+/// Classification of a peripheral's advertisement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdvertisementClass {
+    /// Does not advertise the current BuildIt service
+    Other,
+    /// Advertises the BuildIt service; the commitment is well-formed or absent
+    BuildIt { commitment: Option<Vec<u8>> },
+    /// Advertises the BuildIt service with a commitment of the wrong length
+    ///
+    /// Real BuildIt nodes always advertise exactly
+    /// `COMMITMENT_ADVERTISEMENT_LEN` bytes, so this is a possible spoof.
+    MalformedCommitment { len: usize },
+}
+
+/// Classify advertisement data against the current service UUID
+///
+/// A device only counts as BuildIt when its commitment (if any) is exactly
+/// `COMMITMENT_ADVERTISEMENT_LEN` bytes, so arbitrary service data from
+/// unrelated devices cannot masquerade as an identity commitment.
+pub fn classify_advertisement(
+    services: &[Uuid],
+    service_data: &HashMap<Uuid, Vec<u8>>,
+    service_uuid: &Uuid,
+) -> AdvertisementClass {
+    let data = service_data.get(service_uuid);
+    if data.is_none() && !services.contains(service_uuid) {
+        return AdvertisementClass::Other;
+    }
+
+    match data {
+        Some(bytes) if bytes.len() != COMMITMENT_ADVERTISEMENT_LEN => {
+            AdvertisementClass::MalformedCommitment { len: bytes.len() }
+        }
+        Some(bytes) => AdvertisementClass::BuildIt {
+            commitment: Some(bytes.clone()),
+        },
+        None => AdvertisementClass::BuildIt { commitment: None },
+    }
 }
 
 /// Discovered BLE device information
@@ -210,6 +380,69 @@ pub struct DiscoveredDevice {
     pub verified_pubkey: Option<String>,
 }
 
+/// Whether `current` differs enough from the state last reported to the
+/// frontend to be worth a `DeviceUpdated`
+///
+/// Name, BuildIt flag and commitment changes always count; RSSI only when it
+/// moved by at least `rssi_threshold` or appeared or vanished.
+fn is_significant_update(
+    reported: &DiscoveredDevice,
+    current: &DiscoveredDevice,
+    rssi_threshold: u16,
+) -> bool {
+    let rssi_changed = match (reported.rssi, current.rssi) {
+        (Some(before), Some(now)) => before.abs_diff(now) >= rssi_threshold,
+        (before, now) => before.is_some() != now.is_some(),
+    };
+    rssi_changed
+        || reported.name != current.name
+        || reported.is_buildit_device != current.is_buildit_device
+        || reported.identity_commitment != current.identity_commitment
+}
+
+/// Turns discovery polls into events, dropping insignificant updates
+///
+/// Compares against the last state *reported* for each device rather than
+/// the last poll, so RSSI jitter is coalesced but steady drift is still
+/// reported once it adds up to the threshold.
+#[derive(Debug)]
+struct DiscoveryReporter {
+    rssi_threshold: u16,
+    reported: HashMap<String, DiscoveredDevice>,
+}
+
+impl DiscoveryReporter {
+    fn new(rssi_threshold: u16) -> Self {
+        Self {
+            rssi_threshold,
+            reported: HashMap::new(),
+        }
+    }
+
+    /// Event for this sighting of `device`, if any
+    fn observe(&mut self, device: &DiscoveredDevice) -> Option<BleEvent> {
+        let event = match self.reported.get(&device.address) {
+            None => BleEvent::DeviceDiscovered(device.clone()),
+            Some(reported) if is_significant_update(reported, device, self.rssi_threshold) => {
+                BleEvent::DeviceUpdated(device.clone())
+            }
+            Some(_) => return None,
+        };
+        self.reported.insert(device.address.clone(), device.clone());
+        Some(event)
+    }
+
+    /// Drop a device so its next sighting is reported as discovered
+    fn forget(&mut self, address: &str) {
+        self.reported.remove(address);
+    }
+
+    /// Drop every device so all are reported as discovered again
+    fn clear(&mut self) {
+        self.reported.clear();
+    }
+}
+
 /// BLE connection status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ConnectionStatus {
@@ -223,6 +456,19 @@ pub enum ConnectionStatus {
     Authenticated,
 }
 
+/// Refuse a unicast to an unauthenticated device unless opted out
+fn check_send_allowed(
+    address: &str,
+    status: &ConnectionStatus,
+    authenticated_only: bool,
+) -> Result<(), BleError> {
+    if authenticated_only && *status != ConnectionStatus::Authenticated {
+        log::warn!("Refusing to send to unauthenticated device {}", address);
+        return Err(BleError::NotAuthenticated(address.to_string()));
+    }
+    Ok(())
+}
+
 /// Connected device with characteristics
 #[derive(Debug)]
 pub struct ConnectedDevice {
@@ -236,6 +482,11 @@ pub struct ConnectedDevice {
     pub their_pubkey: Option<String>,
     /// Connection status
     pub status: ConnectionStatus,
+    /// Negotiated ATT MTU, when the platform reports it
+    pub att_mtu: Option<u16>,
+    /// Whether the device advertised chunk receipts on its features
+    /// characteristic; otherwise mesh writes are sent and received raw
+    pub chunk_receipts: bool,
 }
 
 /// BLE scan event for broadcasting to frontend
@@ -261,188 +512,1151 @@ pub enum BleEvent {
         address: String,
         pubkey: String,
     },
+    /// Device advertises the BuildIt service with a malformed commitment
+    SuspiciousAdvertisement {
+        address: String,
+        commitment_len: usize,
+    },
+    /// Device was forgotten and removed from all tracked state
+    DeviceForgotten {
+        address: String,
+    },
+    /// The receiver fell behind and `count` events were lost; re-sync
+    /// state (e.g. via `get_discovered_devices`)
+    EventsDropped {
+        count: u64,
+    },
+    /// A duty-cycled scan opened or closed its scan window
+    ScanPhaseChanged {
+        phase: ScanPhase,
+    },
+    /// Chunks of a large message written to a device so far
+    ChunkProgress {
+        address: String,
+        sent: usize,
+        total: usize,
+    },
+    /// Chunks of a large message received from a device so far
+    ChunkReceiveProgress {
+        address: String,
+        received: usize,
+        total: usize,
+    },
+    /// The adapter was reset; `available` is false if it could not be
+    /// opened again
+    AdapterStateChanged {
+        available: bool,
+    },
 }
 
-/// BLE Manager for handling all Bluetooth operations
-pub struct BleManager {
-    /// Platform BLE manager
-    manager: Option<Manager>,
-    /// Primary adapter
-    adapter: Option<Adapter>,
-    /// Discovered devices
-    discovered_devices: HashMap<String, DiscoveredDevice>,
-    /// Connected devices
-    connected_devices: HashMap<String, ConnectedDevice>,
-    /// Scan status
-    is_scanning: bool,
-    /// Event broadcaster
-    event_tx: broadcast::Sender<BleEvent>,
-    /// Our identity commitment
-    our_commitment: Option<IdentityCommitment>,
-    /// Last known service UUID (for rotation detection)
-    last_service_uuid: Uuid,
+/// BLE event receiver that reports lag instead of silently skipping
+///
+/// When the broadcast channel overflows, the next `recv` yields
+/// [`BleEvent::EventsDropped`] before the events that remain.
+pub struct BleEventReceiver {
+    rx: broadcast::Receiver<BleEvent>,
+    dropped: u64,
 }
 
-impl BleManager {
-    /// Create a new BLE manager
-    pub fn new() -> Self {
-        let (event_tx, _) = broadcast::channel(100);
-        Self {
-            manager: None,
-            adapter: None,
-            discovered_devices: HashMap::new(),
-            connected_devices: HashMap::new(),
-            is_scanning: false,
-            event_tx,
-            our_commitment: None,
-            last_service_uuid: get_current_service_uuid(),
-        }
+impl BleEventReceiver {
+    pub fn new(rx: broadcast::Receiver<BleEvent>) -> Self {
+        Self { rx, dropped: 0 }
     }
 
-    /// Initialize the BLE manager and find adapter
-    pub async fn initialize(&mut self) -> Result<(), BleError> {
-        let manager = Manager::new()
-            .await
-            .map_err(|e| BleError::OperationError(e.to_string()))?;
-
-        let adapters = manager
-            .adapters()
-            .await
-            .map_err(|e| BleError::OperationError(e.to_string()))?;
-
-        let adapter = adapters
-            .into_iter()
-            .next()
-            .ok_or(BleError::AdapterNotFound)?;
-
-        self.manager = Some(manager);
-        self.adapter = Some(adapter);
-
-        log::info!("BLE manager initialized successfully");
-        Ok(())
+    /// Next event, or `None` once the manager is gone
+    pub async fn recv(&mut self) -> Option<BleEvent> {
+        match self.rx.recv().await {
+            Ok(event) => Some(event),
+            Err(broadcast::error::RecvError::Lagged(count)) => Some(self.lagged(count)),
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
     }
 
-    /// Set our identity for commitment-based advertisement
-    pub fn set_identity(&mut self, pubkey: &str) {
-        self.our_commitment = Some(IdentityCommitment::new(pubkey));
-        log::info!("Identity commitment created for BLE");
+    /// Next event if one is ready, without waiting
+    pub fn try_recv(&mut self) -> Option<BleEvent> {
+        match self.rx.try_recv() {
+            Ok(event) => Some(event),
+            Err(broadcast::error::TryRecvError::Lagged(count)) => Some(self.lagged(count)),
+            Err(_) => None,
+        }
     }
 
-    /// Get our identity commitment for advertisement
-    pub fn get_advertisement_data(&self) -> Option<Vec<u8>> {
-        self.our_commitment.as_ref().map(|c| c.advertisement_data())
+    /// Total events this receiver has missed
+    pub fn dropped(&self) -> u64 {
+        self.dropped
     }
 
-    /// Check if service UUID needs rotation and notify if so
-    pub fn check_uuid_rotation(&mut self) {
-        let current = get_current_service_uuid();
-        if current != self.last_service_uuid {
-            self.last_service_uuid = current;
-            let _ = self.event_tx.send(BleEvent::ServiceUuidRotated {
-                new_uuid: current.to_string(),
-            });
-            log::info!("Service UUID rotated to: {}", current);
-        }
+    fn lagged(&mut self, count: u64) -> BleEvent {
+        self.dropped += count;
+        log::warn!("BLE event receiver lagged, {} events dropped", count);
+        BleEvent::EventsDropped { count }
     }
+}
 
-    /// Start scanning for BuildIt devices
-    pub async fn start_scan(&mut self, timeout_seconds: Option<u64>) -> Result<(), BleError> {
-        if self.is_scanning {
-            return Err(BleError::ScanInProgress);
-        }
+/// How a scan discovers advertisers
+///
+/// Active scanning sends scan requests, which reveals the scanner to nearby
+/// devices and costs battery; passive scanning only listens for
+/// advertisements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    #[default]
+    Active,
+    Passive,
+}
 
-        // Ensure we're initialized
-        if self.adapter.is_none() {
-            self.initialize().await?;
+/// Settings for one scan, as handed to the adapter
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    pub filter: ScanFilter,
+    pub mode: ScanMode,
+    /// Debugging only: discover every device, not just BuildIt peers
+    pub scan_all: bool,
+}
+
+impl ScanConfig {
+    /// Drop the service filter so non-BuildIt devices are discovered too
+    ///
+    /// Meant for debugging radio issues; it exposes nearby unrelated devices
+    /// to the app, so the filtered scan stays the default.
+    pub fn with_scan_all(mut self, scan_all: bool) -> Self {
+        self.scan_all = scan_all;
+        if scan_all {
+            self.filter = ScanFilter::default();
         }
+        self
+    }
+}
 
-        // Check for UUID rotation
-        self.check_uuid_rotation();
+/// Whether a device advertising `class` is reported from discovery
+///
+/// Only BuildIt peers are, unless the scan was started with `scan_all`.
+pub fn is_reported(class: &AdvertisementClass, scan_all: bool) -> bool {
+    scan_all || matches!(class, AdvertisementClass::BuildIt { .. })
+}
 
-        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+/// Scan modes the btleplug adapter can honour on this platform
+///
+/// btleplug 0.11 has no scan mode setting: BlueZ discovery always sends
+/// scan requests, the WinRT watcher is created in active mode, and
+/// CoreBluetooth does not expose the choice. Passive scanning is refused
+/// rather than silently performed as an active scan.
+pub fn platform_scan_modes() -> &'static [ScanMode] {
+    &[ScanMode::Active]
+}
 
-        // Set up scan filter for current BuildIt service UUID
-        let current_service_uuid = get_current_service_uuid();
-        let scan_filter = ScanFilter {
-            services: vec![current_service_uuid],
-        };
+/// Build the scan configuration for `mode`, checked against the modes the
+/// adapter supports
+pub fn scan_config(
+    mode: ScanMode,
+    service_uuid: Uuid,
+    supported: &[ScanMode],
+) -> Result<ScanConfig, BleError> {
+    if !supported.contains(&mode) {
+        return Err(BleError::ScanModeUnsupported(mode));
+    }
+    Ok(ScanConfig {
+        filter: ScanFilter {
+            services: vec![service_uuid],
+        },
+        mode,
+        scan_all: false,
+    })
+}
 
-        adapter
-            .start_scan(scan_filter)
-            .await
-            .map_err(|e| BleError::OperationError(e.to_string()))?;
+/// Longest scan or idle window accepted in a [`ScanDutyCycle`]
+pub const MAX_DUTY_WINDOW_SECS: u64 = 3600;
 
-        self.is_scanning = true;
-        log::info!(
-            "BLE scan started with service UUID: {}",
-            current_service_uuid
-        );
+/// Low-power scanning: listen for `on_secs`, then idle for `off_secs`,
+/// repeating until the scan is stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanDutyCycle {
+    pub on_secs: u64,
+    pub off_secs: u64,
+}
 
-        // Handle scan timeout if specified
-        if let Some(timeout) = timeout_seconds {
-            let event_tx = self.event_tx.clone();
-            tokio::spawn(async move {
-                tokio::time::sleep(Duration::from_secs(timeout)).await;
-                log::info!("BLE scan timeout reached");
-                let _ = event_tx.send(BleEvent::DeviceLost("scan_timeout".to_string()));
-            });
+impl Default for ScanDutyCycle {
+    /// Scan 10s every 60s
+    fn default() -> Self {
+        Self {
+            on_secs: 10,
+            off_secs: 50,
         }
-
-        Ok(())
     }
+}
 
-    /// Stop scanning for devices
-    pub async fn stop_scan(&mut self) -> Result<(), BleError> {
-        if !self.is_scanning {
-            return Err(BleError::ScanNotRunning);
-        }
+/// Whether a scan is currently listening or sitting out an idle window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanPhase {
+    Scanning,
+    Idle,
+}
 
-        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+impl ScanDutyCycle {
+    /// Both windows must be non-zero and at most [`MAX_DUTY_WINDOW_SECS`]
+    pub fn validate(&self) -> Result<(), BleError> {
+        let in_range = |secs: u64| (1..=MAX_DUTY_WINDOW_SECS).contains(&secs);
+        if !in_range(self.on_secs) || !in_range(self.off_secs) {
+            return Err(BleError::InvalidDutyCycle(format!(
+                "windows must be 1-{}s (on {}s, off {}s)",
+                MAX_DUTY_WINDOW_SECS, self.on_secs, self.off_secs
+            )));
+        }
+        Ok(())
+    }
 
-        adapter
-            .stop_scan()
-            .await
-            .map_err(|e| BleError::OperationError(e.to_string()))?;
+    /// Length of one scan window plus one idle window
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.on_secs + self.off_secs)
+    }
 
-        self.is_scanning = false;
-        log::info!("BLE scan stopped");
-        Ok(())
+    /// Phase `elapsed` after the scan started, and the time left until the
+    /// next transition
+    ///
+    /// Every period starts with the scan window. Expects a validated cycle.
+    pub fn phase_at(&self, elapsed: Duration) -> (ScanPhase, Duration) {
+        let on = Duration::from_secs(self.on_secs);
+        let period = self.period();
+        let offset = Duration::from_nanos((elapsed.as_nanos() % period.as_nanos()) as u64);
+        if offset < on {
+            (ScanPhase::Scanning, on - offset)
+        } else {
+            (ScanPhase::Idle, period - offset)
+        }
     }
+}
 
-    /// Get all discovered devices
-    pub async fn get_discovered_devices(&mut self) -> Result<Vec<DiscoveredDevice>, BleError> {
-        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+/// Evict partial transfers that outlived their maximum age from `buffer`
+/// every `interval`
+fn spawn_chunk_eviction(buffer: Arc<Mutex<ChunkBuffer>>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            buffer.lock().cleanup();
+        }
+    })
+}
 
-        let peripherals = adapter
-            .peripherals()
-            .await
-            .map_err(|e| BleError::OperationError(e.to_string()))?;
+/// Drive a duty-cycled scan, starting in the scan window that `start_scan`
+/// already opened
+///
+/// The phase is recomputed from the start time on every wakeup, so timer
+/// drift never accumulates. A failed transition leaves the adapter in its
+/// current phase until the next boundary. Scan windows pick up the current
+/// service UUID, following daily rotation, unless `scan_all` drops the
+/// filter.
+fn spawn_duty_cycle(
+    adapter: Adapter,
+    mode: ScanMode,
+    scan_all: bool,
+    cycle: ScanDutyCycle,
+    window_open: Arc<AtomicBool>,
+    event_tx: broadcast::Sender<BleEvent>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let started = tokio::time::Instant::now();
+        let mut phase = ScanPhase::Scanning;
+        loop {
+            let (_, remaining) = cycle.phase_at(started.elapsed());
+            tokio::time::sleep(remaining).await;
+
+            let (next, _) = cycle.phase_at(started.elapsed());
+            if next == phase {
+                continue;
+            }
+            let result = match next {
+                ScanPhase::Idle => adapter.stop_scan().await,
+                ScanPhase::Scanning => {
+                    let config = match scan_config(
+                        mode,
+                        get_current_service_uuid(),
+                        platform_scan_modes(),
+                    ) {
+                        Ok(config) => config.with_scan_all(scan_all),
+                        Err(e) => {
+                            log::error!("Duty-cycled scan cannot resume: {}", e);
+                            return;
+                        }
+                    };
+                    adapter.start_scan(config.filter).await
+                }
+            };
+            if let Err(e) = result {
+                log::warn!("Duty-cycled scan failed to enter {:?}: {}", next, e);
+                continue;
+            }
 
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+            phase = next;
+            window_open.store(phase == ScanPhase::Scanning, Ordering::SeqCst);
+            log::debug!("BLE duty-cycled scan now {:?}", phase);
+            let _ = event_tx.send(BleEvent::ScanPhaseChanged { phase });
+        }
+    })
+}
 
-        let current_service_uuid = get_current_service_uuid();
+/// Disconnect one peripheral, giving up at `deadline`
+///
+/// The device is reported `Disconnected` whatever the outcome, since the
+/// caller has already stopped tracking it. Returns whether the peripheral
+/// acknowledged in time.
+async fn disconnect_until<F, E>(
+    address: &str,
+    disconnect: F,
+    deadline: tokio::time::Instant,
+    event_tx: &broadcast::Sender<BleEvent>,
+) -> bool
+where
+    F: std::future::Future<Output = Result<(), E>>,
+    E: std::fmt::Display,
+{
+    let _ = event_tx.send(BleEvent::ConnectionChanged {
+        address: address.to_string(),
+        status: ConnectionStatus::Disconnecting,
+    });
+
+    let clean = match tokio::time::timeout_at(deadline, disconnect).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            log::warn!("Failed to disconnect {}: {}", address, e);
+            false
+        }
+        Err(_) => {
+            log::warn!("Timed out disconnecting {}", address);
+            false
+        }
+    };
 
-        for peripheral in peripherals {
-            let properties = peripheral
-                .properties()
-                .await
-                .map_err(|e| BleError::OperationError(e.to_string()))?;
+    let _ = event_tx.send(BleEvent::ConnectionChanged {
+        address: address.to_string(),
+        status: ConnectionStatus::Disconnected,
+    });
+    clean
+}
 
-            if let Some(props) = properties {
-                let address = peripheral.address().to_string();
-                let is_buildit = props.services.iter().any(|s| *s == current_service_uuid);
+/// How a peer answered a connectivity check ping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PingStatus {
+    /// A matching pong arrived in time
+    Reachable,
+    /// No pong before the deadline
+    Timeout,
+    /// The peer disconnected while the ping was outstanding
+    Disconnected,
+    /// The ping could not be written
+    SendFailed,
+}
 
-                // Extract identity commitment from service data if available
-                let identity_commitment = props
-                    .service_data
-                    .get(&current_service_uuid)
-                    .cloned();
+/// Per-peer outcome of [`ping_peers`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerPingResult {
+    pub address: String,
+    /// Verified public key of the peer, if known
+    pub pubkey: Option<String>,
+    pub status: PingStatus,
+    /// Round-trip time, when reachable
+    pub rtt_ms: Option<u64>,
+    /// Write error, when the send failed
+    pub error: Option<String>,
+}
 
-                let device = DiscoveredDevice {
-                    address: address.clone(),
+impl PeerPingResult {
+    fn new(address: &str, pubkey: Option<String>, status: PingStatus) -> Self {
+        Self {
+            address: address.to_string(),
+            pubkey,
+            status,
+            rtt_ms: None,
+            error: None,
+        }
+    }
+}
+
+/// Ping each peer once and wait up to `timeout` for their pongs
+///
+/// `peers` are `(address, verified pubkey)` pairs and `send` writes one
+/// encoded message to an address. `events` must be subscribed before the
+/// call so no pong is missed. A pong only counts if it comes from the
+/// pinged address and names that ping's id. Results keep the order of
+/// `peers`.
+pub async fn ping_peers<F, Fut>(
+    peers: Vec<(String, Option<String>)>,
+    mut events: BleEventReceiver,
+    send: F,
+    timeout: Duration,
+) -> Vec<PeerPingResult>
+where
+    F: Fn(String, Vec<u8>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), BleError>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    let pings = peers.into_iter().map(|(address, pubkey)| {
+        let send = &send;
+        async move {
+            let ping = MeshMessage::ping();
+            let data = match ping.to_bytes() {
+                Ok(data) => data,
+                Err(e) => {
+                    let mut result = PeerPingResult::new(&address, pubkey, PingStatus::SendFailed);
+                    result.error = Some(e.to_string());
+                    return (result, None);
+                }
+            };
+
+            let sent_at = tokio::time::Instant::now();
+            match send(address.clone(), data).await {
+                Ok(()) => {
+                    let result = PeerPingResult::new(&address, pubkey, PingStatus::Timeout);
+                    (result, Some((ping.id, sent_at)))
+                }
+                Err(e) => {
+                    log::debug!("Failed to ping {}: {}", address, e);
+                    let mut result = PeerPingResult::new(&address, pubkey, PingStatus::SendFailed);
+                    result.error = Some(e.to_string());
+                    (result, None)
+                }
+            }
+        }
+    });
+    let (mut results, outstanding): (Vec<_>, Vec<_>) =
+        futures::future::join_all(pings).await.into_iter().unzip();
+
+    // Address -> (index into results, ping id, sent at)
+    let mut pending: HashMap<String, (usize, String, tokio::time::Instant)> = outstanding
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, ping)| {
+            ping.map(|(id, sent_at)| (results[i].address.clone(), (i, id, sent_at)))
+        })
+        .collect();
+
+    while !pending.is_empty() {
+        let event = match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Some(event)) => event,
+            Ok(None) | Err(_) => break,
+        };
+        match event {
+            BleEvent::MessageReceived { from_address, data } => {
+                let Some((_, ping_id, _)) = pending.get(&from_address) else {
+                    continue;
+                };
+                let is_pong = MeshMessage::from_bytes(&data).is_ok_and(|message| {
+                    message.message_type == MessageType::Pong
+                        && message.payload == ping_id.as_bytes()
+                });
+                if is_pong {
+                    if let Some((i, _, sent_at)) = pending.remove(&from_address) {
+                        results[i].status = PingStatus::Reachable;
+                        results[i].rtt_ms = Some(sent_at.elapsed().as_millis() as u64);
+                    }
+                }
+            }
+            BleEvent::ConnectionChanged {
+                address,
+                status: ConnectionStatus::Disconnected,
+            }
+            | BleEvent::DeviceForgotten { address } => {
+                if let Some((i, _, _)) = pending.remove(&address) {
+                    results[i].status = PingStatus::Disconnected;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    results
+}
+
+/// Write `chunks` in order with `write`, reporting progress as they go
+///
+/// [`BleEvent::ChunkProgress`] is throttled to about a tenth of the
+/// transfer at a time, and the last event reports every chunk sent. Stops
+/// at the first failed write.
+pub async fn send_chunks<F, Fut>(
+    address: &str,
+    chunks: &[Chunk],
+    mut write: F,
+    event_tx: &broadcast::Sender<BleEvent>,
+) -> Result<(), BleError>
+where
+    F: FnMut(Vec<u8>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), BleError>>,
+{
+    let total = chunks.len();
+    for (i, chunk) in chunks.iter().enumerate() {
+        write(chunk.to_frame_bytes()).await?;
+        if should_report_progress(i + 1, total) {
+            let _ = event_tx.send(BleEvent::ChunkProgress {
+                address: address.to_string(),
+                sent: i + 1,
+                total,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Send a chunked transfer to `address` until the receiver acknowledges it
+///
+/// The chunks go out through [`send_chunks`]. `receipts` must be subscribed
+/// before the call and carries `(address, receipt)` for every receipt that
+/// arrives; those from other addresses or for other messages are ignored.
+/// A NAK resends the chunks it lists and silence until the retry deadline
+/// resends them all, until the transfer's retries are used up.
+pub async fn send_transfer<F, Fut>(
+    address: &str,
+    mut transfer: OutgoingTransfer,
+    mut write: F,
+    receipts: &mut broadcast::Receiver<(String, ChunkReceipt)>,
+    event_tx: &broadcast::Sender<BleEvent>,
+) -> Result<(), BleError>
+where
+    F: FnMut(Vec<u8>) -> Fut,
+    Fut: std::future::Future<Output = Result<(), BleError>>,
+{
+    send_chunks(address, transfer.chunks(), &mut write, event_tx).await?;
+    transfer.mark_sent(std::time::Instant::now());
+
+    while !transfer.is_complete() {
+        let deadline = tokio::time::Instant::from_std(transfer.retry_deadline());
+        let resend = match tokio::time::timeout_at(deadline, receipts.recv()).await {
+            Ok(Ok((from, receipt))) if from == address => {
+                transfer.handle_receipt(&receipt, std::time::Instant::now())?
+            }
+            Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+            Ok(Err(broadcast::error::RecvError::Closed)) => {
+                return Err(BleError::OperationError(
+                    "Receipt channel closed".to_string(),
+                ))
+            }
+            Err(_) => transfer.poll_timeout(std::time::Instant::now())?,
+        };
+        if !resend.is_empty() {
+            for chunk in resend {
+                write(chunk.to_frame_bytes()).await?;
+            }
+            transfer.mark_sent(std::time::Instant::now());
+        }
+    }
+    Ok(())
+}
+
+/// What buffering one received chunk produced
+#[derive(Debug, Default)]
+pub struct ReceivedChunk {
+    /// The reassembled message, once its last chunk is in
+    pub message: Option<Vec<u8>>,
+    /// Receipt to write back to the sender, if one is due
+    pub receipt: Option<ChunkReceipt>,
+}
+
+/// Buffer a chunk received from `address`, reporting progress
+///
+/// Emits a throttled [`BleEvent::ChunkReceiveProgress`] as chunks arrive.
+/// Returns the message once its last chunk is in, and the receipt the
+/// sender is waiting for (see [`ChunkBuffer::receipt_after`]).
+pub fn receive_chunk(
+    address: &str,
+    buffer: &mut ChunkBuffer,
+    chunk: Chunk,
+    event_tx: &broadcast::Sender<BleEvent>,
+) -> Result<ReceivedChunk, ChunkError> {
+    let header = chunk.header.clone();
+    let message_id = header.message_id;
+    let total = header.total_chunks as usize;
+
+    // Resent after our ACK was lost: acknowledge again without progress
+    if buffer.is_completed(address, &message_id) {
+        return Ok(ReceivedChunk {
+            message: None,
+            receipt: buffer.receipt_after(address, &header),
+        });
+    }
+
+    let message = buffer.add_chunk_from(address, chunk)?;
+    let received = match &message {
+        Some(_) => total,
+        None => buffer
+            .progress(address, &message_id)
+            .map_or(0, |(received, _)| received),
+    };
+    if should_report_progress(received, total) {
+        let _ = event_tx.send(BleEvent::ChunkReceiveProgress {
+            address: address.to_string(),
+            received,
+            total,
+        });
+    }
+    Ok(ReceivedChunk {
+        message,
+        receipt: buffer.receipt_after(address, &header),
+    })
+}
+
+/// Where writes received on the mesh characteristic go
+///
+/// Shared by the notification tasks of connected devices and the GATT
+/// server, so every peer's chunks reassemble in one buffer and receipts
+/// reach the transfer waiting for them.
+#[derive(Clone)]
+pub struct MeshInbox {
+    chunk_buffer: Arc<Mutex<ChunkBuffer>>,
+    receipt_tx: broadcast::Sender<(String, ChunkReceipt)>,
+    event_tx: broadcast::Sender<BleEvent>,
+}
+
+impl MeshInbox {
+    /// Inbox with an empty reassembly buffer, emitting on `event_tx`
+    pub fn new(event_tx: broadcast::Sender<BleEvent>) -> Self {
+        let (receipt_tx, _) = broadcast::channel(DEFAULT_EVENT_CAPACITY);
+        Self {
+            chunk_buffer: Arc::new(Mutex::new(ChunkBuffer::new(DEFAULT_REASSEMBLY_TIMEOUT_MS))),
+            receipt_tx,
+            event_tx,
+        }
+    }
+
+    /// Event broadcaster the inbox reports on
+    pub fn event_tx(&self) -> &broadcast::Sender<BleEvent> {
+        &self.event_tx
+    }
+
+    /// Subscribe to receipts for our chunked transfers, as `(address, receipt)`
+    pub fn subscribe_receipts(&self) -> broadcast::Receiver<(String, ChunkReceipt)> {
+        self.receipt_tx.subscribe()
+    }
+
+    /// Handle one write received on the mesh characteristic from `address`
+    ///
+    /// Without `chunk_receipts` the peer writes whole messages, which are
+    /// emitted as [`BleEvent::MessageReceived`] as they are. Otherwise every
+    /// write is a [`ChunkFrame`]: receipts are passed to the transfer
+    /// waiting for them, chunks go through [`receive_chunk`] and each
+    /// completed message is emitted. Returns the receipt to write back to
+    /// `address`, if one is due; malformed frames are dropped.
+    pub fn handle_write(
+        &self,
+        address: &str,
+        data: &[u8],
+        chunk_receipts: bool,
+    ) -> Option<ChunkReceipt> {
+        if !chunk_receipts {
+            let _ = self.event_tx.send(BleEvent::MessageReceived {
+                from_address: address.to_string(),
+                data: data.to_vec(),
+            });
+            return None;
+        }
+
+        match ChunkFrame::from_bytes(data) {
+            Ok(ChunkFrame::Receipt(receipt)) => {
+                let _ = self.receipt_tx.send((address.to_string(), receipt));
+                None
+            }
+            Ok(ChunkFrame::Chunk(chunk)) => {
+                let mut buffer = self.chunk_buffer.lock();
+                match receive_chunk(address, &mut buffer, chunk, &self.event_tx) {
+                    Ok(received) => {
+                        if let Some(data) = received.message {
+                            let _ = self.event_tx.send(BleEvent::MessageReceived {
+                                from_address: address.to_string(),
+                                data,
+                            });
+                        }
+                        received.receipt
+                    }
+                    Err(e) => {
+                        log::warn!("Dropped chunk from {}: {}", address, e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                log::warn!("Dropped malformed chunk frame from {}: {}", address, e);
+                None
+            }
+        }
+    }
+}
+
+/// A connected device's mesh characteristic, cloned out of the manager
+///
+/// Holds everything one mesh send needs, so the send (including waits for
+/// receipts) runs without keeping the manager locked.
+#[derive(Clone)]
+pub struct MeshLink {
+    address: String,
+    peripheral: PlatformPeripheral,
+    characteristic: Characteristic,
+    chunk_receipts: bool,
+    mtu: DeviceMtu,
+    inbox: MeshInbox,
+}
+
+impl MeshLink {
+    /// Address of the device this link writes to
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Effective MTU of the device
+    pub fn mtu(&self) -> DeviceMtu {
+        self.mtu
+    }
+
+    /// Send a mesh message over the link
+    ///
+    /// A device that takes chunk receipts gets the message chunked to fit
+    /// its MTU and acknowledged; see [`MeshLink::send_chunked`]. Any other
+    /// device gets the whole message in one write, as the spec's unframed
+    /// peers expect, so it must fit [`MAX_MESSAGE_SIZE`].
+    pub async fn send(&self, data: &[u8]) -> Result<(), BleError> {
+        if !self.chunk_receipts {
+            if data.len() > MAX_MESSAGE_SIZE {
+                return Err(ChunkError::MessageTooLarge(data.len()).into());
+            }
+            return self.write(data).await;
+        }
+
+        let chunk_size = CHUNK_FRAME_OVERHEAD + self.mtu.max_mesh_payload;
+        self.send_chunked(data, chunk_size).await
+    }
+
+    /// Send a message too large for one write as a series of chunks
+    ///
+    /// `chunk_size` is the largest write the device accepts, header
+    /// included. Emits [`BleEvent::ChunkProgress`] as the chunks go out,
+    /// then resends what the receiver reports missing, failing once
+    /// [`DEFAULT_MAX_RETRIES`] retransmission rounds are used up.
+    pub async fn send_chunked(&self, data: &[u8], chunk_size: usize) -> Result<(), BleError> {
+        let chunks = chunk_message(data, chunk_size)?;
+        let transfer = OutgoingTransfer::new(
+            chunks,
+            DEFAULT_RETRY_TIMEOUT,
+            DEFAULT_MAX_RETRIES,
+            std::time::Instant::now(),
+        );
+        let mut receipts = self.inbox.subscribe_receipts();
+        send_transfer(
+            &self.address,
+            transfer,
+            |bytes| async move { self.write(&bytes).await },
+            &mut receipts,
+            self.inbox.event_tx(),
+        )
+        .await
+    }
+
+    async fn write(&self, data: &[u8]) -> Result<(), BleError> {
+        self.peripheral
+            .write(&self.characteristic, data, WriteType::WithResponse)
+            .await
+            .map_err(|e| BleError::WriteFailed(e.to_string()))?;
+
+        log::debug!("Sent {} bytes to {}", data.len(), self.address);
+        Ok(())
+    }
+}
+
+/// Send a mesh message over every link at once
+///
+/// Returns how many devices the message reached (acknowledged it, for
+/// chunked transfers).
+pub async fn broadcast_over_links(links: &[MeshLink], data: &[u8]) -> usize {
+    let sends = links.iter().map(|link| async move {
+        match link.send(data).await {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to send mesh message to {}: {}", link.address(), e);
+                false
+            }
+        }
+    });
+    let sent_count = futures::future::join_all(sends)
+        .await
+        .into_iter()
+        .filter(|sent| *sent)
+        .count();
+    log::debug!(
+        "Broadcast mesh message to {} authenticated devices",
+        sent_count
+    );
+    sent_count
+}
+
+/// BLE Manager for handling all Bluetooth operations
+pub struct BleManager {
+    /// Platform BLE manager
+    manager: Option<Manager>,
+    /// Primary adapter
+    adapter: Option<Adapter>,
+    /// Discovered devices
+    discovered_devices: HashMap<String, DiscoveredDevice>,
+    /// Filters device discovery events sent to the frontend
+    discovery_reporter: DiscoveryReporter,
+    /// Connected devices
+    connected_devices: HashMap<String, ConnectedDevice>,
+    /// Scan status
+    is_scanning: bool,
+    /// Mode of the running scan
+    scan_mode: ScanMode,
+    /// Whether the running scan also reports non-BuildIt devices
+    scan_all: bool,
+    /// Duty cycle of the running scan, if it is duty-cycled
+    scan_duty_cycle: Option<ScanDutyCycle>,
+    /// Whether the adapter is currently scanning (false during idle windows)
+    scan_window_open: Arc<AtomicBool>,
+    /// Background task switching duty-cycled scan windows
+    duty_cycle_task: Option<JoinHandle<()>>,
+    /// Reassembles received chunks and routes receipts
+    inbox: MeshInbox,
+    /// Background task evicting abandoned partial transfers
+    chunk_eviction_task: Option<JoinHandle<()>>,
+    /// Event broadcaster
+    event_tx: broadcast::Sender<BleEvent>,
+    /// Our identity commitment
+    our_commitment: Option<IdentityCommitment>,
+    /// Last known service UUID (for rotation detection)
+    last_service_uuid: Uuid,
+    /// Peripheral backend while advertising
+    peripheral_backend: Option<Box<dyn PeripheralBackend>>,
+    /// GATT server answering centrals while advertising
+    gatt_server: Option<GattServer>,
+}
+
+impl BleManager {
+    /// Create a new BLE manager
+    pub fn new() -> Self {
+        Self::with_event_capacity(DEFAULT_EVENT_CAPACITY)
+    }
+
+    /// Create a BLE manager whose event channel buffers `capacity` events
+    /// per receiver before the oldest are dropped
+    pub fn with_event_capacity(capacity: usize) -> Self {
+        let (event_tx, _) = broadcast::channel(capacity.max(1));
+        Self {
+            manager: None,
+            adapter: None,
+            discovered_devices: HashMap::new(),
+            discovery_reporter: DiscoveryReporter::new(RSSI_CHANGE_THRESHOLD),
+            connected_devices: HashMap::new(),
+            is_scanning: false,
+            scan_mode: ScanMode::default(),
+            scan_all: false,
+            scan_duty_cycle: None,
+            scan_window_open: Arc::new(AtomicBool::new(false)),
+            duty_cycle_task: None,
+            inbox: MeshInbox::new(event_tx.clone()),
+            chunk_eviction_task: None,
+            event_tx,
+            our_commitment: None,
+            last_service_uuid: get_current_service_uuid(),
+            peripheral_backend: None,
+            gatt_server: None,
+        }
+    }
+
+    /// Initialize the BLE manager and find adapter
+    pub async fn initialize(&mut self) -> Result<(), BleError> {
+        let manager = Manager::new()
+            .await
+            .map_err(|e| BleError::OperationError(e.to_string()))?;
+
+        let adapters = manager
+            .adapters()
+            .await
+            .map_err(|e| BleError::OperationError(e.to_string()))?;
+
+        let adapter = adapters
+            .into_iter()
+            .next()
+            .ok_or(BleError::AdapterNotFound)?;
+
+        self.manager = Some(manager);
+        self.adapter = Some(adapter);
+        if self.chunk_eviction_task.is_none() {
+            self.chunk_eviction_task = Some(spawn_chunk_eviction(
+                self.inbox.chunk_buffer.clone(),
+                CHUNK_EVICTION_INTERVAL,
+            ));
+        }
+
+        log::info!("BLE manager initialized successfully");
+        Ok(())
+    }
+
+    /// Recover a wedged Bluetooth stack by opening the adapter again
+    ///
+    /// Stops scanning, drops connections and discovered devices, then
+    /// releases the platform manager and adapter and initializes afresh.
+    /// Our identity, the service UUID, the scan mode and advertising are
+    /// kept. Emits [`BleEvent::AdapterStateChanged`] with whether an adapter
+    /// is available afterwards.
+    pub async fn reset_adapter(&mut self) -> Result<(), BleError> {
+        self.reset_adapter_with(|manager| Box::pin(manager.initialize()))
+            .await
+    }
+
+    /// [`BleManager::reset_adapter`] with `reinitialize` in place of
+    /// [`BleManager::initialize`]
+    async fn reset_adapter_with<F>(&mut self, reinitialize: F) -> Result<(), BleError>
+    where
+        F: for<'a> FnOnce(&'a mut BleManager) -> LocalBoxFuture<'a, Result<(), BleError>>,
+    {
+        if self.is_scanning {
+            if let Err(e) = self.stop_scan().await {
+                log::warn!("Failed to stop scan before adapter reset: {}", e);
+            }
+        }
+        // stop_scan bails out early on a wedged adapter
+        if let Some(task) = self.duty_cycle_task.take() {
+            task.abort();
+        }
+        self.is_scanning = false;
+        self.scan_all = false;
+        self.scan_duty_cycle = None;
+        self.scan_window_open.store(false, Ordering::SeqCst);
+
+        let deadline = tokio::time::Instant::now() + RESET_DISCONNECT_TIMEOUT;
+        self.disconnect_all(deadline).await;
+        for (address, _) in self.discovered_devices.drain() {
+            let _ = self.event_tx.send(BleEvent::DeviceLost(address));
+        }
+        self.discovery_reporter.clear();
+
+        self.adapter = None;
+        self.manager = None;
+        let result = reinitialize(self).await;
+        match &result {
+            Ok(()) => log::info!("BLE adapter reset"),
+            Err(e) => log::warn!("BLE adapter reset failed: {}", e),
+        }
+        let _ = self.event_tx.send(BleEvent::AdapterStateChanged {
+            available: result.is_ok(),
+        });
+        result
+    }
+
+    /// Whether `initialize` has opened an adapter
+    pub fn has_adapter(&self) -> bool {
+        self.adapter.is_some()
+    }
+
+    /// Set our identity for commitment-based advertisement
+    pub fn set_identity(&mut self, pubkey: &str) {
+        self.our_commitment = Some(IdentityCommitment::new(pubkey));
+        log::info!("Identity commitment created for BLE");
+    }
+
+    /// Our identity commitment, if an identity is set
+    pub fn identity(&self) -> Option<&IdentityCommitment> {
+        self.our_commitment.as_ref()
+    }
+
+    /// Drop our identity, stopping any advertising made under it
+    pub fn clear_identity(&mut self) {
+        if self.is_advertising() {
+            if let Err(e) = self.stop_advertising() {
+                log::warn!("Failed to stop advertising while clearing identity: {}", e);
+            }
+        }
+        if self.our_commitment.take().is_some() {
+            log::info!("BLE identity cleared");
+        }
+    }
+
+    /// Get our identity commitment for advertisement
+    pub fn get_advertisement_data(&self) -> Option<Vec<u8>> {
+        self.our_commitment.as_ref().map(|c| c.advertisement_data())
+    }
+
+    /// Check if service UUID needs rotation and notify if so
+    pub fn check_uuid_rotation(&mut self) {
+        let current = get_current_service_uuid();
+        if current != self.last_service_uuid {
+            self.last_service_uuid = current;
+            let _ = self.event_tx.send(BleEvent::ServiceUuidRotated {
+                new_uuid: current.to_string(),
+            });
+            log::info!("Service UUID rotated to: {}", current);
+        }
+    }
+
+    /// Start scanning for BuildIt devices
+    ///
+    /// Fails with [`BleError::ScanModeUnsupported`] if the platform cannot
+    /// scan in `mode` (see [`platform_scan_modes`]).
+    ///
+    /// With a `duty_cycle`, the scan alternates scan and idle windows until
+    /// stopped, emitting [`BleEvent::ScanPhaseChanged`] on each transition.
+    ///
+    /// `scan_all` is a debugging aid: the service filter is dropped and
+    /// unrelated devices are reported with `is_buildit_device` false.
+    pub async fn start_scan(
+        &mut self,
+        timeout_seconds: Option<u64>,
+        mode: ScanMode,
+        duty_cycle: Option<ScanDutyCycle>,
+        scan_all: bool,
+    ) -> Result<(), BleError> {
+        if self.is_scanning {
+            return Err(BleError::ScanInProgress);
+        }
+        if let Some(cycle) = &duty_cycle {
+            cycle.validate()?;
+        }
+
+        // Ensure we're initialized
+        if self.adapter.is_none() {
+            self.initialize().await?;
+        }
+
+        // Check for UUID rotation
+        self.check_uuid_rotation();
+
+        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+
+        // Set up scan filter for current BuildIt service UUID
+        let current_service_uuid = get_current_service_uuid();
+        let config =
+            scan_config(mode, current_service_uuid, platform_scan_modes())?.with_scan_all(scan_all);
+        if config.scan_all {
+            log::warn!("BLE scan_all enabled: discovering non-BuildIt devices (debugging only)");
+        }
+
+        adapter
+            .start_scan(config.filter)
+            .await
+            .map_err(|e| BleError::OperationError(e.to_string()))?;
+
+        self.is_scanning = true;
+        self.scan_mode = config.mode;
+        self.scan_all = config.scan_all;
+        self.scan_duty_cycle = duty_cycle;
+        self.scan_window_open.store(true, Ordering::SeqCst);
+        log::info!(
+            "BLE {:?} scan started with service UUID: {}",
+            config.mode,
+            current_service_uuid
+        );
+
+        if let Some(cycle) = duty_cycle {
+            log::info!(
+                "BLE scan duty-cycled: {}s on, {}s off",
+                cycle.on_secs,
+                cycle.off_secs
+            );
+            let _ = self.event_tx.send(BleEvent::ScanPhaseChanged {
+                phase: ScanPhase::Scanning,
+            });
+            self.duty_cycle_task = Some(spawn_duty_cycle(
+                adapter.clone(),
+                config.mode,
+                config.scan_all,
+                cycle,
+                self.scan_window_open.clone(),
+                self.event_tx.clone(),
+            ));
+        }
+
+        // Handle scan timeout if specified
+        if let Some(timeout) = timeout_seconds {
+            let event_tx = self.event_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(timeout)).await;
+                log::info!("BLE scan timeout reached");
+                let _ = event_tx.send(BleEvent::DeviceLost("scan_timeout".to_string()));
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stop scanning for devices
+    pub async fn stop_scan(&mut self) -> Result<(), BleError> {
+        if !self.is_scanning {
+            return Err(BleError::ScanNotRunning);
+        }
+
+        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+
+        if let Some(task) = self.duty_cycle_task.take() {
+            task.abort();
+        }
+        // An idle duty-cycle window has already stopped the adapter
+        if self.scan_window_open.load(Ordering::SeqCst) {
+            adapter
+                .stop_scan()
+                .await
+                .map_err(|e| BleError::OperationError(e.to_string()))?;
+        }
+
+        self.is_scanning = false;
+        self.scan_all = false;
+        self.scan_duty_cycle = None;
+        self.scan_window_open.store(false, Ordering::SeqCst);
+        log::info!("BLE scan stopped");
+        Ok(())
+    }
+
+    /// Get all discovered devices
+    pub async fn get_discovered_devices(&mut self) -> Result<Vec<DiscoveredDevice>, BleError> {
+        let adapter = self.adapter.as_ref().ok_or(BleError::AdapterNotFound)?;
+
+        let peripherals = adapter
+            .peripherals()
+            .await
+            .map_err(|e| BleError::OperationError(e.to_string()))?;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        let current_service_uuid = get_current_service_uuid();
+
+        for peripheral in peripherals {
+            let properties = peripheral
+                .properties()
+                .await
+                .map_err(|e| BleError::OperationError(e.to_string()))?;
+
+            if let Some(props) = properties {
+                let address = peripheral.address().to_string();
+
+                // Only trust a well-formed identity commitment from service data
+                let class = classify_advertisement(
+                    &props.services,
+                    &props.service_data,
+                    &current_service_uuid,
+                );
+                let reported = is_reported(&class, self.scan_all);
+                let (is_buildit, identity_commitment) = match class {
+                    AdvertisementClass::Other => (false, None),
+                    AdvertisementClass::BuildIt { commitment } => (true, commitment),
+                    AdvertisementClass::MalformedCommitment { len } => {
+                        log::warn!(
+                            "Device {} advertises BuildIt service with malformed {}-byte commitment",
+                            address,
+                            len
+                        );
+                        let _ = self.event_tx.send(BleEvent::SuspiciousAdvertisement {
+                            address: address.clone(),
+                            commitment_len: len,
+                        });
+                        (false, None)
+                    }
+                };
+                if !reported {
+                    continue;
+                }
+
+                let device = DiscoveredDevice {
+                    address: address.clone(),
                     name: props.local_name,
                     rssi: props.rssi,
                     is_buildit_device: is_buildit,
@@ -451,17 +1665,19 @@ impl BleManager {
                     verified_pubkey: None, // Not verified until handshake
                 };
 
-                // Check if this is a new or updated device
-                let is_new = !self.discovered_devices.contains_key(&address);
-                self.discovered_devices.insert(address.clone(), device.clone());
+                // Let this device authenticate to us if it connects as a central
+                if let (Some(server), Some(commitment)) = (
+                    self.gatt_server.as_mut(),
+                    device.identity_commitment.as_ref(),
+                ) {
+                    server.remember_commitment(commitment.clone());
+                }
 
-                // Broadcast event
-                let event = if is_new {
-                    BleEvent::DeviceDiscovered(device)
-                } else {
-                    BleEvent::DeviceUpdated(device)
-                };
-                let _ = self.event_tx.send(event);
+                // Broadcast new devices and significant changes only
+                if let Some(event) = self.discovery_reporter.observe(&device) {
+                    let _ = self.event_tx.send(event);
+                }
+                self.discovered_devices.insert(address, device);
             }
         }
 
@@ -505,11 +1721,13 @@ impl BleManager {
         let mesh_char_uuid = get_mesh_characteristic_uuid();
         let identity_char_uuid = get_identity_characteristic_uuid();
         let handshake_char_uuid = get_handshake_characteristic_uuid();
+        let features_char_uuid = get_features_characteristic_uuid();
 
         // Find BuildIt characteristics
         let mut mesh_char = None;
         let mut identity_char = None;
         let mut handshake_char = None;
+        let mut features_char = None;
 
         for service in peripheral.services() {
             if service.uuid == current_service_uuid {
@@ -520,6 +1738,8 @@ impl BleManager {
                         identity_char = Some(characteristic.clone());
                     } else if characteristic.uuid == handshake_char_uuid {
                         handshake_char = Some(characteristic.clone());
+                    } else if characteristic.uuid == features_char_uuid {
+                        features_char = Some(characteristic.clone());
                     }
                 }
             }
@@ -531,6 +1751,31 @@ impl BleManager {
             .get(address)
             .and_then(|d| d.identity_commitment.clone());
 
+        // Peers without the features characteristic get raw writes. Ours is
+        // written back so the peer frames its notifications to us too.
+        let chunk_receipts = match features_char {
+            Some(ref char) => match peripheral.read(char).await {
+                Ok(features) if takes_chunk_receipts(&features) => {
+                    match peripheral
+                        .write(char, &[SUPPORTED_FEATURES], WriteType::WithResponse)
+                        .await
+                    {
+                        Ok(()) => true,
+                        Err(e) => {
+                            log::debug!("Failed to send our features to {}: {}", address, e);
+                            false
+                        }
+                    }
+                }
+                Ok(_) => false,
+                Err(e) => {
+                    log::debug!("Failed to read features of {}: {}", address, e);
+                    false
+                }
+            },
+            None => false,
+        };
+
         // Subscribe to mesh characteristic notifications
         if let Some(ref char) = mesh_char {
             peripheral
@@ -544,14 +1789,22 @@ impl BleManager {
                 .await
                 .map_err(|e| BleError::OperationError(e.to_string()))?;
 
-            let event_tx = self.event_tx.clone();
+            let inbox = self.inbox.clone();
+            let reply_to = peripheral.clone();
+            let reply_char = char.clone();
             let addr = address.to_string();
             tokio::spawn(async move {
                 while let Some(data) = notification_stream.next().await {
-                    let _ = event_tx.send(BleEvent::MessageReceived {
-                        from_address: addr.clone(),
-                        data: data.value,
-                    });
+                    let Some(receipt) = inbox.handle_write(&addr, &data.value, chunk_receipts)
+                    else {
+                        continue;
+                    };
+                    if let Err(e) = reply_to
+                        .write(&reply_char, &receipt.to_bytes(), WriteType::WithoutResponse)
+                        .await
+                    {
+                        log::debug!("Failed to send chunk receipt to {}: {}", addr, e);
+                    }
                 }
             });
         }
@@ -565,8 +1818,12 @@ impl BleManager {
             their_commitment,
             their_pubkey: None,
             status: ConnectionStatus::Connected,
+            // btleplug doesn't expose the negotiated MTU
+            att_mtu: None,
+            chunk_receipts,
         };
-        self.connected_devices.insert(address.to_string(), connected);
+        self.connected_devices
+            .insert(address.to_string(), connected);
 
         // Broadcast connected status
         let _ = self.event_tx.send(BleEvent::ConnectionChanged {
@@ -605,12 +1862,7 @@ impl BleManager {
             .map_err(|e| BleError::ReadFailed(e.to_string()))?;
 
         // Parse handshake data: pubkey (64 bytes hex = 32 bytes) + nonce (16 bytes)
-        if handshake_data.len() < 48 {
-            return Err(BleError::CommitmentVerificationFailed);
-        }
-
-        let their_pubkey_hex = String::from_utf8_lossy(&handshake_data[..64]).to_string();
-        let their_nonce = &handshake_data[64..];
+        let (their_pubkey_hex, their_nonce) = IdentityCommitment::parse_handshake(&handshake_data)?;
 
         // Verify commitment
         let their_commitment = device
@@ -629,8 +1881,7 @@ impl BleManager {
 
         // Send our handshake data (pubkey + nonce)
         if let Some(ref our_commitment) = self.our_commitment {
-            let mut our_handshake = our_commitment.pubkey.as_bytes().to_vec();
-            our_handshake.extend_from_slice(&our_commitment.nonce);
+            let our_handshake = our_commitment.handshake_data();
 
             device
                 .peripheral
@@ -650,10 +1901,7 @@ impl BleManager {
             pubkey: their_pubkey_hex.clone(),
         });
 
-        log::info!(
-            "Handshake completed with {}: pubkey verified",
-            address
-        );
+        log::info!("Handshake completed with {}: pubkey verified", address);
         Ok(their_pubkey_hex)
     }
 
@@ -686,12 +1934,108 @@ impl BleManager {
         Ok(())
     }
 
+    /// Disconnect every connected device in parallel, giving up at `deadline`
+    ///
+    /// All devices are dropped from tracking and reported `Disconnected`,
+    /// even ones that fail or do not answer in time, so a stuck peer cannot
+    /// hold up shutdown. Returns the addresses that did not disconnect
+    /// cleanly.
+    pub async fn disconnect_all(&mut self, deadline: tokio::time::Instant) -> Vec<String> {
+        let devices: Vec<(String, ConnectedDevice)> = self.connected_devices.drain().collect();
+        let event_tx = &self.event_tx;
+
+        let disconnects = devices.iter().map(|(address, device)| async move {
+            let disconnect = device.peripheral.disconnect();
+            let clean = disconnect_until(address, disconnect, deadline, event_tx).await;
+            (address.clone(), clean)
+        });
+        let results = futures::future::join_all(disconnects).await;
+
+        if !results.is_empty() {
+            log::info!("Disconnected {} BLE device(s)", results.len());
+        }
+        results
+            .into_iter()
+            .filter_map(|(address, clean)| (!clean).then_some(address))
+            .collect()
+    }
+
+    /// Forget a device: disconnect it and drop it from all tracked state
+    ///
+    /// Idempotent; forgetting an unknown device still emits
+    /// `DeviceForgotten`. Returns the device's verified public key, if one
+    /// was known, so the caller can delete persisted contact data.
+    pub async fn forget_device(&mut self, address: &str) -> Option<String> {
+        let (connected, pubkey) = self.forget_tracked(address);
+
+        if let Some(device) = connected {
+            if let Err(e) = device.peripheral.disconnect().await {
+                log::warn!("Failed to disconnect forgotten device {}: {}", address, e);
+            }
+            let _ = self.event_tx.send(BleEvent::ConnectionChanged {
+                address: address.to_string(),
+                status: ConnectionStatus::Disconnected,
+            });
+        }
+
+        let _ = self.event_tx.send(BleEvent::DeviceForgotten {
+            address: address.to_string(),
+        });
+        log::info!("Forgot device: {}", address);
+        pubkey
+    }
+
+    /// Remove a device from every tracked collection
+    ///
+    /// Returns the still-connected device (for the caller to disconnect) and
+    /// its verified public key.
+    fn forget_tracked(&mut self, address: &str) -> (Option<ConnectedDevice>, Option<String>) {
+        let pubkey = self.known_pubkey(address);
+
+        let connected = self.connected_devices.remove(address);
+        self.discovered_devices.remove(address);
+        self.discovery_reporter.forget(address);
+        if let Some(server) = self.gatt_server.as_mut() {
+            server.central_disconnected(address);
+        }
+
+        (connected, pubkey)
+    }
+
+    /// Verified public key of a device from any tracked collection
+    pub fn known_pubkey(&self, address: &str) -> Option<String> {
+        self.connected_devices
+            .get(address)
+            .and_then(|d| d.their_pubkey.clone())
+            .or_else(|| {
+                self.discovered_devices
+                    .get(address)
+                    .and_then(|d| d.verified_pubkey.clone())
+            })
+            .or_else(|| {
+                self.gatt_server
+                    .as_ref()
+                    .and_then(|s| s.authenticated_pubkey(address))
+                    .map(str::to_string)
+            })
+    }
+
     /// Send a message to a connected device
-    pub async fn send_message(&self, address: &str, data: &[u8]) -> Result<(), BleError> {
+    ///
+    /// With `authenticated_only` the device must have completed the
+    /// commitment handshake, matching `broadcast_mesh_message`. Opting out
+    /// allows writing to a peer whose identity is unverified.
+    pub async fn send_message(
+        &self,
+        address: &str,
+        data: &[u8],
+        authenticated_only: bool,
+    ) -> Result<(), BleError> {
         let device = self
             .connected_devices
             .get(address)
             .ok_or_else(|| BleError::DeviceNotFound(address.to_string()))?;
+        check_send_allowed(address, &device.status, authenticated_only)?;
 
         let characteristic = device
             .mesh_characteristic
@@ -708,6 +2052,79 @@ impl BleManager {
         Ok(())
     }
 
+    /// Link for mesh sends to a connected device
+    ///
+    /// With `authenticated_only` the device must have completed the
+    /// commitment handshake, as for [`BleManager::send_message`].
+    pub fn mesh_link(&self, address: &str, authenticated_only: bool) -> Result<MeshLink, BleError> {
+        let device = self
+            .connected_devices
+            .get(address)
+            .ok_or_else(|| BleError::DeviceNotFound(address.to_string()))?;
+        check_send_allowed(address, &device.status, authenticated_only)?;
+        self.link_to(address, device)
+    }
+
+    fn link_to(&self, address: &str, device: &ConnectedDevice) -> Result<MeshLink, BleError> {
+        let characteristic = device
+            .mesh_characteristic
+            .clone()
+            .ok_or(BleError::CharacteristicNotFound)?;
+        Ok(MeshLink {
+            address: address.to_string(),
+            peripheral: device.peripheral.clone(),
+            characteristic,
+            chunk_receipts: device.chunk_receipts,
+            mtu: DeviceMtu::new(device.att_mtu),
+            inbox: self.inbox.clone(),
+        })
+    }
+
+    /// Links to every authenticated device, for broadcasts
+    pub fn broadcast_links(&self) -> Vec<MeshLink> {
+        self.connected_devices
+            .iter()
+            .filter(|(address, device)| {
+                // Only send to authenticated devices
+                if device.status != ConnectionStatus::Authenticated {
+                    log::debug!("Skipping unauthenticated device: {}", address);
+                    return false;
+                }
+                true
+            })
+            .filter_map(|(address, device)| self.link_to(address, device).ok())
+            .collect()
+    }
+
+    /// Send a mesh message to a connected device; see [`MeshLink::send`]
+    ///
+    /// The manager stays borrowed until the device acknowledges; callers
+    /// sharing it behind a lock should send over [`BleManager::mesh_link`]
+    /// instead.
+    pub async fn send_mesh_message(
+        &self,
+        address: &str,
+        data: &[u8],
+        authenticated_only: bool,
+    ) -> Result<(), BleError> {
+        self.mesh_link(address, authenticated_only)?
+            .send(data)
+            .await
+    }
+
+    /// Connected devices that completed the handshake, as
+    /// `(address, verified pubkey)` sorted by address
+    pub fn authenticated_peers(&self) -> Vec<(String, Option<String>)> {
+        let mut peers: Vec<(String, Option<String>)> = self
+            .connected_devices
+            .iter()
+            .filter(|(_, device)| device.status == ConnectionStatus::Authenticated)
+            .map(|(address, device)| (address.clone(), device.their_pubkey.clone()))
+            .collect();
+        peers.sort();
+        peers
+    }
+
     /// Read identity from a connected device (returns commitment, not pubkey)
     pub async fn read_identity(&self, address: &str) -> Result<Vec<u8>, BleError> {
         let device = self
@@ -734,6 +2151,89 @@ impl BleManager {
         self.is_scanning
     }
 
+    /// Mode of the running scan, if any
+    pub fn scan_mode(&self) -> Option<ScanMode> {
+        self.is_scanning.then_some(self.scan_mode)
+    }
+
+    /// Duty cycle of the running scan, if it is duty-cycled
+    pub fn scan_duty_cycle(&self) -> Option<ScanDutyCycle> {
+        self.scan_duty_cycle
+    }
+
+    /// Whether the running scan is listening or in an idle window
+    pub fn scan_phase(&self) -> Option<ScanPhase> {
+        self.is_scanning.then(|| {
+            if self.scan_window_open.load(Ordering::SeqCst) {
+                ScanPhase::Scanning
+            } else {
+                ScanPhase::Idle
+            }
+        })
+    }
+
+    /// Start peripheral mode: advertise our commitment and serve GATT
+    ///
+    /// Requires `set_identity` and a platform peripheral backend.
+    pub fn start_advertising(&mut self) -> Result<(), BleError> {
+        if self.peripheral_backend.is_some() {
+            return Ok(());
+        }
+
+        let identity = self
+            .our_commitment
+            .clone()
+            .ok_or_else(|| BleError::OperationError("Identity not set".to_string()))?;
+        let payload = AdvertisementPayload::new(&identity)?;
+
+        let mut backend = peripheral::platform_backend()?;
+        backend.start_advertising(&payload)?;
+
+        let mut server = GattServer::new(identity, self.inbox.clone());
+        for commitment in self
+            .discovered_devices
+            .values()
+            .filter_map(|d| d.identity_commitment.clone())
+        {
+            server.remember_commitment(commitment);
+        }
+
+        self.peripheral_backend = Some(backend);
+        self.gatt_server = Some(server);
+        log::info!(
+            "BLE advertising started with service UUID: {}",
+            payload.service_uuid
+        );
+        Ok(())
+    }
+
+    /// Stop peripheral mode
+    pub fn stop_advertising(&mut self) -> Result<(), BleError> {
+        let mut backend = self
+            .peripheral_backend
+            .take()
+            .ok_or_else(|| BleError::OperationError("Not advertising".to_string()))?;
+        self.gatt_server = None;
+        backend.stop_advertising()?;
+        log::info!("BLE advertising stopped");
+        Ok(())
+    }
+
+    /// Forget the identity commitments the GATT server learned from
+    /// advertisements (relearned as scanning continues)
+    ///
+    /// Returns how many were forgotten; 0 when not advertising.
+    pub fn forget_advertised_commitments(&mut self) -> usize {
+        self.gatt_server
+            .as_mut()
+            .map_or(0, GattServer::forget_commitments)
+    }
+
+    /// Get current advertising status
+    pub fn is_advertising(&self) -> bool {
+        self.peripheral_backend.is_some()
+    }
+
     /// Get connection status for a device
     pub fn get_connection_status(&self, address: &str) -> ConnectionStatus {
         self.connected_devices
@@ -749,36 +2249,42 @@ impl BleManager {
             .and_then(|d| d.their_pubkey.clone())
     }
 
+    /// Record the ATT MTU a connected device negotiated
+    pub fn record_mtu(&mut self, address: &str, att_mtu: u16) -> Result<(), BleError> {
+        let device = self
+            .connected_devices
+            .get_mut(address)
+            .ok_or_else(|| BleError::DeviceNotFound(address.to_string()))?;
+        device.att_mtu = Some(att_mtu);
+        Ok(())
+    }
+
+    /// Effective MTU and per-chunk mesh payload of a connected device
+    pub fn device_mtu(&self, address: &str) -> Result<DeviceMtu, BleError> {
+        self.connected_devices
+            .get(address)
+            .map(|d| DeviceMtu::new(d.att_mtu))
+            .ok_or_else(|| BleError::DeviceNotFound(address.to_string()))
+    }
+
     /// Subscribe to BLE events
     pub fn subscribe(&self) -> broadcast::Receiver<BleEvent> {
         self.event_tx.subscribe()
     }
 
-    /// Broadcast all connected device messages (for mesh routing)
-    pub async fn broadcast_mesh_message(&self, data: &[u8]) -> Result<usize, BleError> {
-        let mut sent_count = 0;
-        for (address, device) in &self.connected_devices {
-            // Only send to authenticated devices
-            if device.status != ConnectionStatus::Authenticated {
-                log::debug!("Skipping unauthenticated device: {}", address);
-                continue;
-            }
+    /// Subscribe to BLE events, with dropped events reported as
+    /// [`BleEvent::EventsDropped`]
+    pub fn subscribe_events(&self) -> BleEventReceiver {
+        BleEventReceiver::new(self.event_tx.subscribe())
+    }
 
-            if let Some(ref char) = device.mesh_characteristic {
-                if device
-                    .peripheral
-                    .write(char, data, WriteType::WithoutResponse)
-                    .await
-                    .is_ok()
-                {
-                    sent_count += 1;
-                } else {
-                    log::warn!("Failed to send mesh message to {}", address);
-                }
-            }
-        }
-        log::debug!("Broadcast mesh message to {} authenticated devices", sent_count);
-        Ok(sent_count)
+    /// Broadcast a mesh message to every authenticated device (for mesh routing)
+    ///
+    /// Each device gets the message through [`MeshLink::send`], chunked to
+    /// its MTU if it takes receipts. Returns how many devices the message
+    /// reached; see [`broadcast_over_links`].
+    pub async fn broadcast_mesh_message(&self, data: &[u8]) -> Result<usize, BleError> {
+        Ok(broadcast_over_links(&self.broadcast_links(), data).await)
     }
 
     /// Get the current service UUID (for external use)
@@ -793,9 +2299,103 @@ impl Default for BleManager {
     }
 }
 
+impl Drop for BleManager {
+    fn drop(&mut self) {
+        if let Some(task) = self.duty_cycle_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.chunk_eviction_task.take() {
+            task.abort();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ble::chunk::{MAX_MTU, PROGRESS_REPORTS};
+
+    #[tokio::test]
+    async fn test_overflowed_event_channel_reports_dropped() {
+        let manager = BleManager::with_event_capacity(2);
+        let mut events = manager.subscribe_events();
+
+        for i in 0..5 {
+            manager
+                .event_tx
+                .send(BleEvent::DeviceLost(format!("device-{}", i)))
+                .unwrap();
+        }
+
+        assert!(matches!(
+            events.recv().await,
+            Some(BleEvent::EventsDropped { count: 3 })
+        ));
+        assert_eq!(events.dropped(), 3);
+        assert!(matches!(events.recv().await, Some(BleEvent::DeviceLost(a)) if a == "device-3"));
+        assert!(matches!(events.try_recv(), Some(BleEvent::DeviceLost(a)) if a == "device-4"));
+        assert!(events.try_recv().is_none());
+
+        drop(manager);
+        assert!(events.recv().await.is_none());
+    }
+
+    #[test]
+    fn test_scan_mode_threaded_into_config() {
+        let service = get_current_service_uuid();
+        let both = [ScanMode::Active, ScanMode::Passive];
+
+        let config = scan_config(ScanMode::Passive, service, &both).unwrap();
+        assert_eq!(config.mode, ScanMode::Passive);
+        assert_eq!(config.filter.services, vec![service]);
+
+        let config = scan_config(ScanMode::Active, service, &both).unwrap();
+        assert_eq!(config.mode, ScanMode::Active);
+    }
+
+    #[test]
+    fn test_unsupported_scan_mode_refused() {
+        let service = get_current_service_uuid();
+        assert!(matches!(
+            scan_config(ScanMode::Passive, service, &[ScanMode::Active]),
+            Err(BleError::ScanModeUnsupported(ScanMode::Passive))
+        ));
+        assert!(platform_scan_modes().contains(&ScanMode::default()));
+        assert_eq!(
+            serde_json::to_string(&ScanMode::Passive).unwrap(),
+            "\"passive\""
+        );
+    }
+
+    #[test]
+    fn test_duty_cycle_schedule() {
+        let cycle = ScanDutyCycle::default();
+        assert_eq!(cycle.period(), Duration::from_secs(60));
+
+        let at = |secs: f64| cycle.phase_at(Duration::from_secs_f64(secs));
+        assert_eq!(at(0.0), (ScanPhase::Scanning, Duration::from_secs(10)));
+        assert_eq!(at(9.5), (ScanPhase::Scanning, Duration::from_millis(500)));
+        assert_eq!(at(10.0), (ScanPhase::Idle, Duration::from_secs(50)));
+        assert_eq!(at(59.0), (ScanPhase::Idle, Duration::from_secs(1)));
+
+        // Later periods repeat the same schedule
+        assert_eq!(at(60.0), (ScanPhase::Scanning, Duration::from_secs(10)));
+        assert_eq!(at(3605.0), (ScanPhase::Scanning, Duration::from_secs(5)));
+        assert_eq!(at(3630.0), (ScanPhase::Idle, Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn test_duty_cycle_validation() {
+        assert!(ScanDutyCycle::default().validate().is_ok());
+        for (on_secs, off_secs) in [(0, 50), (10, 0), (MAX_DUTY_WINDOW_SECS + 1, 50)] {
+            let cycle = ScanDutyCycle { on_secs, off_secs };
+            assert!(matches!(
+                cycle.validate(),
+                Err(BleError::InvalidDutyCycle(_))
+            ));
+        }
+        assert_eq!(serde_json::to_string(&ScanPhase::Idle).unwrap(), "\"idle\"");
+    }
 
     #[test]
     fn test_uuid_rotation_deterministic() {
@@ -810,10 +2410,14 @@ mod tests {
         let mesh = get_mesh_characteristic_uuid();
         let identity = get_identity_characteristic_uuid();
         let handshake = get_handshake_characteristic_uuid();
+        let features = get_features_characteristic_uuid();
 
         assert_ne!(mesh, identity);
         assert_ne!(mesh, handshake);
         assert_ne!(identity, handshake);
+        for other in [mesh, identity, handshake] {
+            assert_ne!(features, other);
+        }
     }
 
     #[test]
@@ -840,6 +2444,300 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_identity_qr_payload_round_trip() {
+        let pubkey = "abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234";
+        let payload = make_identity_qr_payload(pubkey);
+
+        assert!(payload.starts_with(IDENTITY_QR_PREFIX));
+        assert!(verify_identity_qr_payload(&payload, pubkey));
+        // Each payload uses a fresh nonce
+        assert_ne!(payload, make_identity_qr_payload(pubkey));
+    }
+
+    #[test]
+    fn test_identity_qr_payload_detects_tampering() {
+        let pubkey = "abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234abcd1234";
+        let other = "1111111111111111111111111111111111111111111111111111111111111111";
+        let payload = make_identity_qr_payload(pubkey);
+
+        // Someone else's key
+        assert!(!verify_identity_qr_payload(&payload, other));
+
+        // Pubkey swapped in the payload without a matching commitment
+        let tampered = payload.replace(pubkey, other);
+        assert!(!verify_identity_qr_payload(&tampered, other));
+
+        // Malformed payloads
+        let fields = payload.strip_prefix(IDENTITY_QR_PREFIX).unwrap();
+        assert!(!verify_identity_qr_payload(fields, pubkey));
+        let extended = format!("{payload}:extra");
+        assert!(!verify_identity_qr_payload(&extended, pubkey));
+        let truncated = format!("{IDENTITY_QR_PREFIX}:{}:{pubkey}", "00".repeat(16));
+        assert!(!verify_identity_qr_payload(&truncated, pubkey));
+    }
+
+    #[test]
+    fn test_classify_valid_commitment() {
+        let service = get_current_service_uuid();
+        let commitment = IdentityCommitment::new("abcd").advertisement_data();
+        let data = HashMap::from([(service, commitment.clone())]);
+
+        assert_eq!(
+            classify_advertisement(&[service], &data, &service),
+            AdvertisementClass::BuildIt {
+                commitment: Some(commitment)
+            }
+        );
+        // Service UUID without service data is still a BuildIt device
+        assert_eq!(
+            classify_advertisement(&[service], &HashMap::new(), &service),
+            AdvertisementClass::BuildIt { commitment: None }
+        );
+    }
+
+    #[test]
+    fn test_classify_malformed_commitment() {
+        let service = get_current_service_uuid();
+
+        for len in [0usize, 19, 21, 32] {
+            let data = HashMap::from([(service, vec![0xAB; len])]);
+            assert_eq!(
+                classify_advertisement(&[service], &data, &service),
+                AdvertisementClass::MalformedCommitment { len }
+            );
+        }
+
+        // Service data under our UUID counts even if the UUID is not listed
+        let data = HashMap::from([(service, vec![1, 2, 3])]);
+        assert_eq!(
+            classify_advertisement(&[], &data, &service),
+            AdvertisementClass::MalformedCommitment { len: 3 }
+        );
+    }
+
+    #[test]
+    fn test_scan_all_surfaces_unrelated_device() {
+        let service = get_current_service_uuid();
+        let other = Uuid::from_u128(0x0000180f_0000_1000_8000_00805f9b34fb);
+        let unrelated = classify_advertisement(&[other], &HashMap::new(), &service);
+        let buildit = AdvertisementClass::BuildIt { commitment: None };
+
+        let filtered = scan_config(ScanMode::Active, service, platform_scan_modes()).unwrap();
+        assert!(!filtered.scan_all);
+        assert_eq!(filtered.filter.services, vec![service]);
+        assert!(!is_reported(&unrelated, filtered.scan_all));
+        assert!(is_reported(&buildit, filtered.scan_all));
+
+        let all = filtered.with_scan_all(true);
+        assert!(all.filter.services.is_empty());
+        assert!(is_reported(&unrelated, all.scan_all));
+        assert!(is_reported(&buildit, all.scan_all));
+    }
+
+    #[test]
+    fn test_classify_unrelated_device() {
+        let service = get_current_service_uuid();
+        let other = Uuid::from_u128(0x0000180f_0000_1000_8000_00805f9b34fb);
+        let data = HashMap::from([(other, vec![0u8; COMMITMENT_ADVERTISEMENT_LEN])]);
+
+        assert_eq!(
+            classify_advertisement(&[other], &data, &service),
+            AdvertisementClass::Other
+        );
+    }
+
+    fn sighting(rssi: Option<i16>, commitment: Option<Vec<u8>>) -> DiscoveredDevice {
+        DiscoveredDevice {
+            address: "AA:BB:CC:DD:EE:FF".to_string(),
+            name: None,
+            rssi,
+            is_buildit_device: true,
+            last_seen: 0,
+            identity_commitment: commitment,
+            verified_pubkey: None,
+        }
+    }
+
+    #[test]
+    fn test_rssi_jitter_suppressed() {
+        let mut reporter = DiscoveryReporter::new(RSSI_CHANGE_THRESHOLD);
+
+        assert!(matches!(
+            reporter.observe(&sighting(Some(-60), None)),
+            Some(BleEvent::DeviceDiscovered(_))
+        ));
+        for rssi in [-62, -57, -61, -55, -64] {
+            assert!(reporter.observe(&sighting(Some(rssi), None)).is_none());
+        }
+
+        // Drift is measured from the last reported value, not the last poll
+        assert!(reporter.observe(&sighting(Some(-66), None)).is_none());
+        assert!(matches!(
+            reporter.observe(&sighting(Some(-68), None)),
+            Some(BleEvent::DeviceUpdated(d)) if d.rssi == Some(-68)
+        ));
+        assert!(reporter.observe(&sighting(Some(-64), None)).is_none());
+        assert!(matches!(
+            reporter.observe(&sighting(None, None)),
+            Some(BleEvent::DeviceUpdated(_))
+        ));
+    }
+
+    #[test]
+    fn test_commitment_change_emitted() {
+        let mut reporter = DiscoveryReporter::new(RSSI_CHANGE_THRESHOLD);
+        let commitment = IdentityCommitment::new(&"b".repeat(64)).advertisement_data();
+
+        reporter.observe(&sighting(Some(-60), None));
+        assert!(matches!(
+            reporter.observe(&sighting(Some(-61), Some(commitment.clone()))),
+            Some(BleEvent::DeviceUpdated(d)) if d.identity_commitment == Some(commitment.clone())
+        ));
+        assert!(reporter
+            .observe(&sighting(Some(-61), Some(commitment.clone())))
+            .is_none());
+
+        // A forgotten device is discovered afresh
+        reporter.forget("AA:BB:CC:DD:EE:FF");
+        assert!(matches!(
+            reporter.observe(&sighting(Some(-61), Some(commitment))),
+            Some(BleEvent::DeviceDiscovered(_))
+        ));
+    }
+
+    #[test]
+    fn test_forget_device_clears_tracked_state() {
+        let pubkey = "bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678";
+        let address = "AA:BB:CC:DD:EE:FF";
+        let theirs = IdentityCommitment::new(pubkey);
+
+        let mut manager = BleManager::new();
+        manager.discovered_devices.insert(
+            address.to_string(),
+            DiscoveredDevice {
+                address: address.to_string(),
+                name: None,
+                rssi: Some(-60),
+                is_buildit_device: true,
+                last_seen: 0,
+                identity_commitment: Some(theirs.advertisement_data()),
+                verified_pubkey: None,
+            },
+        );
+        let mut server = GattServer::new(
+            IdentityCommitment::new(&"a".repeat(64)),
+            manager.inbox.clone(),
+        );
+        server.remember_commitment(theirs.advertisement_data());
+        server
+            .handle_write(
+                address,
+                &get_handshake_characteristic_uuid(),
+                &theirs.handshake_data(),
+            )
+            .unwrap();
+        manager.gatt_server = Some(server);
+
+        let (connected, forgotten) = manager.forget_tracked(address);
+        assert!(connected.is_none());
+        assert_eq!(forgotten.as_deref(), Some(pubkey));
+
+        assert!(!manager.discovered_devices.contains_key(address));
+        assert!(!manager.connected_devices.contains_key(address));
+        let server = manager.gatt_server.as_ref().unwrap();
+        assert!(server.authenticated_pubkey(address).is_none());
+        assert!(manager.known_pubkey(address).is_none());
+
+        // Forgetting again is a no-op
+        let (connected, forgotten) = manager.forget_tracked(address);
+        assert!(connected.is_none());
+        assert!(forgotten.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reset_adapter_clears_transient_state() {
+        let mut manager = BleManager::new();
+        manager.set_identity(&"a".repeat(64));
+        let commitment = manager.identity().unwrap().commitment.clone();
+        let service_uuid = manager.last_service_uuid;
+        let device = sighting(Some(-60), None);
+        manager.discovery_reporter.observe(&device);
+        manager
+            .discovered_devices
+            .insert(device.address.clone(), device.clone());
+        manager.is_scanning = true;
+        manager.scan_mode = ScanMode::Passive;
+        manager.scan_all = true;
+        manager.scan_duty_cycle = Some(ScanDutyCycle::default());
+        let mut events = manager.subscribe_events();
+
+        let mut reinitialized = 0;
+        manager
+            .reset_adapter_with(|manager| {
+                reinitialized += 1;
+                // The old adapter is released before opening a new one
+                assert!(manager.adapter.is_none() && manager.manager.is_none());
+                Box::pin(async { Ok(()) })
+            })
+            .await
+            .unwrap();
+        assert_eq!(reinitialized, 1);
+
+        assert!(manager.discovered_devices.is_empty());
+        assert!(!manager.is_scanning);
+        assert!(!manager.scan_all);
+        assert!(manager.scan_duty_cycle.is_none());
+        assert!(matches!(
+            manager.discovery_reporter.observe(&device),
+            Some(BleEvent::DeviceDiscovered(_))
+        ));
+
+        // Identity and settings survive
+        assert_eq!(manager.identity().unwrap().commitment, commitment);
+        assert_eq!(manager.last_service_uuid, service_uuid);
+        assert_eq!(manager.scan_mode, ScanMode::Passive);
+
+        assert!(matches!(
+            events.try_recv(),
+            Some(BleEvent::DeviceLost(address)) if address == device.address
+        ));
+        assert!(matches!(
+            events.try_recv(),
+            Some(BleEvent::AdapterStateChanged { available: true })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reset_adapter_reports_missing_adapter() {
+        let mut manager = BleManager::new();
+        let mut events = manager.subscribe_events();
+
+        let result = manager
+            .reset_adapter_with(|_| Box::pin(async { Err(BleError::AdapterNotFound) }))
+            .await;
+        assert!(matches!(result, Err(BleError::AdapterNotFound)));
+        assert!(matches!(
+            events.try_recv(),
+            Some(BleEvent::AdapterStateChanged { available: false })
+        ));
+    }
+
+    #[test]
+    fn test_send_requires_authentication_by_default() {
+        let address = "AA:BB:CC:DD:EE:FF";
+
+        assert!(matches!(
+            check_send_allowed(address, &ConnectionStatus::Connected, true),
+            Err(BleError::NotAuthenticated(_))
+        ));
+        assert!(check_send_allowed(address, &ConnectionStatus::Handshaking, true).is_err());
+        assert!(check_send_allowed(address, &ConnectionStatus::Authenticated, true).is_ok());
+
+        // Explicit opt-out restores the old behavior
+        assert!(check_send_allowed(address, &ConnectionStatus::Connected, false).is_ok());
+    }
+
     #[test]
     fn test_uuid_is_valid_uuid4() {
         let uuid = get_current_service_uuid();
@@ -848,4 +2746,423 @@ mod tests {
         assert_eq!((bytes[6] >> 4) & 0x0f, 4); // Version 4
         assert!((bytes[8] >> 6) & 0x03 >= 2); // Variant 1
     }
+
+    fn connection_events(rx: &mut broadcast::Receiver<BleEvent>) -> Vec<ConnectionStatus> {
+        let mut statuses = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let BleEvent::ConnectionChanged { status, .. } = event {
+                statuses.push(status);
+            }
+        }
+        statuses
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_until_gives_up_on_stuck_peer() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(20);
+
+        let stuck = std::future::pending::<Result<(), BleError>>();
+        let started = std::time::Instant::now();
+        assert!(!disconnect_until("AA:BB", stuck, deadline, &tx).await);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // A stuck peer still ends up reported as disconnected
+        assert_eq!(
+            connection_events(&mut rx),
+            vec![
+                ConnectionStatus::Disconnecting,
+                ConnectionStatus::Disconnected
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_until_reports_clean_and_failed() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+
+        assert!(disconnect_until("AA:BB", async { Ok::<(), BleError>(()) }, deadline, &tx).await);
+        let failed = async { Err(BleError::OperationError("gone".to_string())) };
+        assert!(!disconnect_until("AA:BB", failed, deadline, &tx).await);
+        assert_eq!(connection_events(&mut rx).len(), 4);
+    }
+
+    /// How the mock BLE link treats a pinged peer
+    #[derive(Clone, Copy)]
+    enum MockPeer {
+        Responsive,
+        Silent,
+        Vanishing,
+        Broken,
+        /// Answers with a pong for some other ping
+        WrongPong,
+        /// Its pong arrives from another address
+        PongFrom(&'static str),
+    }
+
+    /// Send function for a mock BLE link that plays each peer's part by
+    /// injecting events into `tx`
+    fn mock_link(
+        peers: Vec<(&'static str, MockPeer)>,
+        tx: broadcast::Sender<BleEvent>,
+    ) -> impl Fn(String, Vec<u8>) -> futures::future::Ready<Result<(), BleError>> {
+        let peers: HashMap<String, MockPeer> =
+            peers.into_iter().map(|(a, p)| (a.to_string(), p)).collect();
+        move |address, data| {
+            let ping = MeshMessage::from_bytes(&data).unwrap();
+            assert_eq!(ping.message_type, MessageType::Ping);
+
+            let reply = match peers[&address] {
+                MockPeer::Responsive => Some(BleEvent::MessageReceived {
+                    from_address: address.clone(),
+                    data: MeshMessage::pong(&ping.id).to_bytes().unwrap(),
+                }),
+                MockPeer::PongFrom(other) => Some(BleEvent::MessageReceived {
+                    from_address: other.to_string(),
+                    data: MeshMessage::pong(&ping.id).to_bytes().unwrap(),
+                }),
+                MockPeer::WrongPong => Some(BleEvent::MessageReceived {
+                    from_address: address.clone(),
+                    data: MeshMessage::pong("another-ping").to_bytes().unwrap(),
+                }),
+                MockPeer::Vanishing => Some(BleEvent::ConnectionChanged {
+                    address: address.clone(),
+                    status: ConnectionStatus::Disconnected,
+                }),
+                MockPeer::Silent => None,
+                MockPeer::Broken => {
+                    return futures::future::ready(Err(BleError::WriteFailed(
+                        "link lost".to_string(),
+                    )))
+                }
+            };
+            if let Some(reply) = reply {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    let _ = tx.send(reply);
+                });
+            }
+            futures::future::ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping_peers_reports_each_peer() {
+        let (tx, rx) = broadcast::channel(16);
+        let peers = vec![
+            ("AA:01", MockPeer::Responsive),
+            ("AA:02", MockPeer::Silent),
+            ("AA:03", MockPeer::Vanishing),
+            ("AA:04", MockPeer::Broken),
+            ("AA:05", MockPeer::Responsive),
+        ];
+        let targets = peers
+            .iter()
+            .map(|(address, _)| (address.to_string(), Some(format!("pk-{}", address))))
+            .collect();
+
+        let started = std::time::Instant::now();
+        let results = ping_peers(
+            targets,
+            BleEventReceiver::new(rx),
+            mock_link(peers, tx.clone()),
+            Duration::from_millis(300),
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let statuses: Vec<(&str, PingStatus)> = results
+            .iter()
+            .map(|r| (r.address.as_str(), r.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("AA:01", PingStatus::Reachable),
+                ("AA:02", PingStatus::Timeout),
+                ("AA:03", PingStatus::Disconnected),
+                ("AA:04", PingStatus::SendFailed),
+                ("AA:05", PingStatus::Reachable),
+            ]
+        );
+        assert!(results[0].rtt_ms.is_some());
+        assert_eq!(results[0].pubkey.as_deref(), Some("pk-AA:01"));
+        assert!(results[1].rtt_ms.is_none());
+        assert!(results[3].error.as_deref().unwrap().contains("link lost"));
+    }
+
+    #[tokio::test]
+    async fn test_ping_peers_ignores_unmatched_pongs() {
+        let (tx, rx) = broadcast::channel(16);
+        let peers = vec![
+            ("AA:01", MockPeer::WrongPong),
+            ("AA:02", MockPeer::PongFrom("AA:09")),
+        ];
+        let targets = peers
+            .iter()
+            .map(|(address, _)| (address.to_string(), None))
+            .collect();
+
+        let results = ping_peers(
+            targets,
+            BleEventReceiver::new(rx),
+            mock_link(peers, tx),
+            Duration::from_millis(100),
+        )
+        .await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.status == PingStatus::Timeout));
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_all_without_devices() {
+        let mut manager = BleManager::new();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+        assert!(manager.disconnect_all(deadline).await.is_empty());
+    }
+
+    fn random_payload(len: usize) -> Vec<u8> {
+        (0..len / 16 + 1)
+            .flat_map(|_| *Uuid::new_v4().as_bytes())
+            .take(len)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_chunk_send_progress_is_throttled() {
+        let (event_tx, _) = broadcast::channel(64);
+        let mut events = BleEventReceiver::new(event_tx.subscribe());
+        let chunks = chunk_message(&random_payload(12_000), MAX_MTU).unwrap();
+        let total = chunks.len();
+        assert!(total > 20);
+
+        let mut written = Vec::new();
+        send_chunks(
+            "AA:BB",
+            &chunks,
+            |bytes| {
+                written.push(bytes);
+                async { Ok(()) }
+            },
+            &event_tx,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written.len(), total);
+
+        let mut progress = Vec::new();
+        while let Some(BleEvent::ChunkProgress {
+            address,
+            sent,
+            total,
+        }) = events.try_recv()
+        {
+            assert_eq!(address, "AA:BB");
+            progress.push((sent, total));
+        }
+        // About one report per tenth of the transfer, ending at completion
+        assert!(progress.len() <= PROGRESS_REPORTS + 1, "{:?}", progress);
+        assert!(progress.len() >= PROGRESS_REPORTS / 2);
+        assert!(progress.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(progress.last(), Some(&(total, total)));
+    }
+
+    #[tokio::test]
+    async fn test_chunk_receive_progress_reports_completion() {
+        let (event_tx, _) = broadcast::channel(64);
+        let mut events = BleEventReceiver::new(event_tx.subscribe());
+        let data = random_payload(3_000);
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let total = chunks.len();
+
+        let mut buffer = ChunkBuffer::new(60_000);
+        let mut message = None;
+        for chunk in chunks {
+            message = receive_chunk("AA:BB", &mut buffer, chunk, &event_tx)
+                .unwrap()
+                .message;
+        }
+        assert_eq!(message, Some(data));
+
+        let mut progress = Vec::new();
+        while let Some(BleEvent::ChunkReceiveProgress {
+            received, total, ..
+        }) = events.try_recv()
+        {
+            progress.push((received, total));
+        }
+        // Few chunks: each one is reported
+        assert_eq!(
+            progress,
+            (1..=total).map(|i| (i, total)).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_resends_chunks_the_receiver_missed() {
+        let (event_tx, _) = broadcast::channel(64);
+        let (receipt_tx, mut receipts) = broadcast::channel(16);
+        let data = random_payload(3_000);
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let total = chunks.len();
+        let transfer = OutgoingTransfer::new(
+            chunks,
+            Duration::from_secs(5),
+            DEFAULT_MAX_RETRIES,
+            std::time::Instant::now(),
+        );
+
+        // The receiver answers over the same link; chunk 1 is lost once
+        let mut buffer = ChunkBuffer::new(60_000);
+        let mut delivered = Vec::new();
+        let mut dropped = false;
+        let mut writes = 0;
+        send_transfer(
+            "AA:BB",
+            transfer,
+            |bytes| {
+                writes += 1;
+                let Ok(ChunkFrame::Chunk(chunk)) = ChunkFrame::from_bytes(&bytes) else {
+                    panic!("sender wrote something other than a chunk");
+                };
+                if chunk.header.chunk_index == 1 && !dropped {
+                    dropped = true;
+                } else {
+                    let received = receive_chunk("BB:AA", &mut buffer, chunk, &event_tx).unwrap();
+                    delivered.extend(received.message);
+                    if let Some(receipt) = received.receipt {
+                        let _ = receipt_tx.send(("AA:BB".to_string(), receipt));
+                    }
+                }
+                async { Ok(()) }
+            },
+            &mut receipts,
+            &event_tx,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(delivered, vec![data]);
+        assert_eq!(writes, total + 1);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_fails_when_receiver_never_answers() {
+        let (event_tx, _) = broadcast::channel(64);
+        let (_receipt_tx, mut receipts) = broadcast::channel(16);
+        let chunks = chunk_message(&random_payload(1_000), MAX_MTU).unwrap();
+        let total = chunks.len();
+        let transfer = OutgoingTransfer::new(
+            chunks,
+            Duration::from_millis(20),
+            2,
+            std::time::Instant::now(),
+        );
+
+        let mut writes = 0;
+        let result = send_transfer(
+            "AA:BB",
+            transfer,
+            |_| {
+                writes += 1;
+                async { Ok(()) }
+            },
+            &mut receipts,
+            &event_tx,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(BleError::Transfer(ChunkError::RetriesExhausted(_, 2)))
+        ));
+        // The first send and two full resends
+        assert_eq!(writes, 3 * total);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_transfers_are_evicted_on_schedule() {
+        let buffer = Arc::new(Mutex::new(ChunkBuffer::new(1)));
+        let chunks = chunk_message(&random_payload(3_000), MAX_MTU).unwrap();
+        buffer
+            .lock()
+            .add_chunk_from("AA:BB", chunks[0].clone())
+            .unwrap();
+        assert_eq!(buffer.lock().partial_count("AA:BB"), 1);
+
+        let task = spawn_chunk_eviction(buffer.clone(), Duration::from_millis(5));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        task.abort();
+        assert_eq!(buffer.lock().partial_count("AA:BB"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_inbox_reassembles_chunks_and_routes_receipts() {
+        let (event_tx, _) = broadcast::channel(64);
+        let mut events = BleEventReceiver::new(event_tx.subscribe());
+        let inbox = MeshInbox::new(event_tx);
+        let mut receipts = inbox.subscribe_receipts();
+        let data = random_payload(3_000);
+        let chunks = chunk_message(&data, MAX_MTU).unwrap();
+        let message_id = chunks[0].header.message_id;
+
+        let mut last_receipt = None;
+        for chunk in &chunks {
+            last_receipt = inbox.handle_write("AA:BB", &chunk.to_frame_bytes(), true);
+        }
+        assert_eq!(last_receipt, Some(ChunkReceipt::Ack { message_id }));
+        let mut delivered = Vec::new();
+        while let Some(event) = events.try_recv() {
+            if let BleEvent::MessageReceived { from_address, data } = event {
+                assert_eq!(from_address, "AA:BB");
+                delivered.push(data);
+            }
+        }
+        assert_eq!(delivered, vec![data]);
+
+        // A receipt goes to the waiting transfer, not to the frontend
+        let ack = ChunkReceipt::Ack { message_id };
+        assert_eq!(inbox.handle_write("CC:DD", &ack.to_bytes(), true), None);
+        assert_eq!(receipts.try_recv().unwrap(), ("CC:DD".to_string(), ack));
+        assert!(events.try_recv().is_none());
+    }
+
+    #[test]
+    fn test_inbox_delivers_raw_writes_from_peers_without_receipts() {
+        let (event_tx, _) = broadcast::channel(64);
+        let mut events = BleEventReceiver::new(event_tx.subscribe());
+        let inbox = MeshInbox::new(event_tx);
+        let mut receipts = inbox.subscribe_receipts();
+
+        // Spec peers write whole messages, whatever their first byte
+        let chunk = chunk_message(b"hello", MAX_MTU).unwrap().remove(0);
+        let ack = ChunkReceipt::Ack {
+            message_id: chunk.header.message_id,
+        };
+        for write in [
+            b"{\"type\":1}".to_vec(),
+            chunk.to_frame_bytes(),
+            ack.to_bytes(),
+        ] {
+            assert_eq!(inbox.handle_write("AA:BB", &write, false), None);
+            assert!(matches!(
+                events.try_recv(),
+                Some(BleEvent::MessageReceived { from_address, data })
+                    if from_address == "AA:BB" && data == write
+            ));
+        }
+        assert!(receipts.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_chunk_receipts_read_from_feature_bitmap() {
+        assert!(takes_chunk_receipts(&[SUPPORTED_FEATURES]));
+        assert!(takes_chunk_receipts(&[0xff, 0x00]));
+        assert!(!takes_chunk_receipts(&[0x00]));
+        assert!(!takes_chunk_receipts(&[0xfe]));
+        assert!(!takes_chunk_receipts(&[]));
+    }
 }
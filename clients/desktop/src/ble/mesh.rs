@@ -11,14 +11,20 @@
 //! - Dynamic service UUID rotation for privacy
 
 use buildit_crypto::{
-    derive_conversation_key, generate_keypair, get_public_key, nip44_decrypt_with_key,
-    nip44_encrypt_with_key, randomize_timestamp, schnorr_sign, schnorr_verify,
+    create_duress_alerts, derive_conversation_key, generate_keypair, get_public_key,
+    nip44_decrypt_with_key, nip44_encrypt_with_key, randomize_timestamp, schnorr_sign,
+    schnorr_verify, secure_destroy_key, CryptoError, DuressAlertConfig, KeyPair, NostrEvent,
 };
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
 use uuid::Uuid;
+use zeroize::Zeroize;
+
+use crate::contacts::BlockList;
 
 /// Maximum message size for BLE transmission (MTU - overhead)
 pub const MAX_MESSAGE_SIZE: usize = 512;
@@ -29,8 +35,36 @@ pub const DEFAULT_TTL: u8 = 5;
 /// Timestamp randomization range in seconds (2 days as per NIP-17)
 const TIMESTAMP_RANGE_SECONDS: u32 = 172800;
 
-/// How long to remember message correlation tokens (5 minutes in ms)
-const CORRELATION_TOKEN_TTL_MS: u64 = 300_000;
+/// Default time to remember message correlation tokens (5 minutes in ms)
+pub const DEFAULT_CORRELATION_TOKEN_TTL_MS: u64 = 300_000;
+
+/// Upper bound on remembered correlation tokens; the oldest are evicted first
+pub const MAX_SEEN_TOKENS: usize = 10_000;
+
+/// Domain separator (and version) for mesh group broadcast keys
+const GROUP_KEY_DOMAIN: &[u8] = b"BuildIt-Mesh-GroupKey-v1";
+
+/// How long a `PerSession` ephemeral signing key is reused (10 minutes in ms)
+pub const EPHEMERAL_SESSION_TTL_MS: u64 = 600_000;
+
+/// Ephemeral signing key policy for outgoing direct messages
+///
+/// `signer_pubkey` is the only cleartext field of a direct message that can
+/// tie two messages together, so this is a deliberate privacy-vs-cost choice:
+///
+/// - `PerMessage` (default): a fresh keypair for every message. No observer,
+///   relay node or recipient can link two messages by their signer, at the
+///   cost of one key generation per message.
+/// - `PerSession`: one keypair per recipient, reused for at most
+///   `EPHEMERAL_SESSION_TTL_MS`. Cheaper, and lets the recipient correlate a
+///   session, but any node relaying the traffic can also group every message
+///   to that recipient within the window as coming from one sender.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EphemeralPolicy {
+    #[default]
+    PerMessage,
+    PerSession,
+}
 
 /// Message types for mesh protocol
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -83,7 +117,7 @@ pub struct MeshMessage {
     pub routing: EncryptedRoutingInfo,
     /// The encrypted payload (NIP-44 ciphertext)
     /// For Direct: only final recipient can decrypt
-    /// For Broadcast: encrypted with a broadcast key derivable by recipients
+    /// For Broadcast: encrypted with the group key (see `derive_group_key`)
     pub payload: Vec<u8>,
     /// Message signature over (id || routing.ciphertext || payload)
     /// Signed by an ephemeral key (not the actual sender) for unlinkability
@@ -103,6 +137,15 @@ struct RoutingData {
     correlation_token: String,
 }
 
+/// Inner decrypted routing data of a group broadcast
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BroadcastRoutingData {
+    /// Sender's commitment; the pubkey is never put in a broadcast
+    sender_commitment: String,
+    /// Correlation token (encrypted for group members only)
+    correlation_token: String,
+}
+
 impl MeshMessage {
     /// Create a new direct mesh message with full privacy protection
     ///
@@ -116,7 +159,46 @@ impl MeshMessage {
     ) -> Result<Self, MeshError> {
         // Generate ephemeral keypair for signing (unlinkable)
         let ephemeral = generate_keypair();
+        Self::new_direct_with_signer(
+            our_private_key,
+            our_public_key,
+            recipient_pubkey,
+            payload,
+            &ephemeral,
+        )
+    }
 
+    /// Create a direct mesh message signed by the given ephemeral keypair
+    ///
+    /// Reusing `ephemeral` across messages makes them linkable by
+    /// `signer_pubkey`; see `EphemeralPolicy`.
+    pub fn new_direct_with_signer(
+        our_private_key: &[u8],
+        our_public_key: &str,
+        recipient_pubkey: &str,
+        payload: &[u8],
+        ephemeral: &KeyPair,
+    ) -> Result<Self, MeshError> {
+        let routing_key =
+            derive_conversation_key(our_private_key.to_vec(), recipient_pubkey.to_string())
+                .map_err(|_| MeshError::EncryptionFailed)?;
+        Self::build_direct(
+            routing_key,
+            our_public_key,
+            recipient_pubkey,
+            payload,
+            ephemeral,
+        )
+    }
+
+    /// Build a direct message with an already-derived conversation key
+    fn build_direct(
+        routing_key: Vec<u8>,
+        our_public_key: &str,
+        recipient_pubkey: &str,
+        payload: &[u8],
+        ephemeral: &KeyPair,
+    ) -> Result<Self, MeshError> {
         // Generate correlation token for endpoint deduplication
         let correlation_token = Uuid::new_v4().to_string();
 
@@ -130,13 +212,12 @@ impl MeshMessage {
             serde_json::to_string(&routing_data).map_err(|_| MeshError::SerializationFailed)?;
 
         // Encrypt routing data to recipient
-        let routing_key = derive_conversation_key(our_private_key.to_vec(), recipient_pubkey.to_string())
-            .map_err(|_| MeshError::EncryptionFailed)?;
         let encrypted_routing = nip44_encrypt_with_key(routing_key.clone(), routing_json)
             .map_err(|_| MeshError::EncryptionFailed)?;
 
         // Encrypt payload with NIP-44
-        let payload_str = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, payload);
+        let payload_str =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, payload);
         let encrypted_payload = nip44_encrypt_with_key(routing_key, payload_str)
             .map_err(|_| MeshError::EncryptionFailed)?;
 
@@ -151,7 +232,8 @@ impl MeshMessage {
         let message_id = Uuid::new_v4().to_string();
 
         // Create signature material
-        let sig_material = create_signature_material(&message_id, &encrypted_routing, &encrypted_payload);
+        let sig_material =
+            create_signature_material(&message_id, &encrypted_routing, &encrypted_payload);
 
         // Sign with ephemeral key
         let signature = schnorr_sign(sig_material, ephemeral.private_key.clone())
@@ -168,6 +250,62 @@ impl MeshMessage {
             },
             payload: encrypted_payload.into_bytes(),
             signature: hex::encode(signature),
+            signer_pubkey: ephemeral.public_key.clone(),
+        })
+    }
+
+    /// Create a broadcast readable by every holder of `group_key`
+    ///
+    /// Routing data and payload are both encrypted to the group key, so
+    /// relaying nodes outside the group learn nothing but the TTL. The sender
+    /// is identified by commitment only; members who know the sender's
+    /// pubkey and nonce can check it with `MeshNode::verify_commitment`.
+    pub fn new_broadcast(
+        group_key: &[u8],
+        sender_commitment: &str,
+        payload: &[u8],
+    ) -> Result<Self, MeshError> {
+        let ephemeral = generate_keypair();
+        let correlation_token = Uuid::new_v4().to_string();
+
+        let routing_data = BroadcastRoutingData {
+            sender_commitment: sender_commitment.to_string(),
+            correlation_token,
+        };
+        let routing_json =
+            serde_json::to_string(&routing_data).map_err(|_| MeshError::SerializationFailed)?;
+        let encrypted_routing = nip44_encrypt_with_key(group_key.to_vec(), routing_json)
+            .map_err(|_| MeshError::EncryptionFailed)?;
+
+        let payload_str =
+            base64::Engine::encode(&base64::engine::general_purpose::STANDARD, payload);
+        let encrypted_payload = nip44_encrypt_with_key(group_key.to_vec(), payload_str)
+            .map_err(|_| MeshError::EncryptionFailed)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let randomized_ts = randomize_timestamp(now, TIMESTAMP_RANGE_SECONDS);
+
+        let message_id = Uuid::new_v4().to_string();
+        let sig_material =
+            create_signature_material(&message_id, &encrypted_routing, &encrypted_payload);
+        let signature = schnorr_sign(sig_material, ephemeral.private_key.clone())
+            .map_err(|_| MeshError::SigningFailed)?;
+
+        Ok(Self {
+            id: message_id,
+            message_type: MessageType::Broadcast,
+            ttl: DEFAULT_TTL,
+            timestamp: randomized_ts,
+            routing: EncryptedRoutingInfo {
+                ciphertext: encrypted_routing,
+                // No ECDH for broadcasts; never our identity key
+                ephemeral_pubkey: ephemeral.public_key.clone(),
+            },
+            payload: encrypted_payload.into_bytes(),
+            signature: hex::encode(signature),
             signer_pubkey: ephemeral.public_key,
         })
     }
@@ -336,6 +474,38 @@ impl MeshMessage {
             correlation_token: routing_data.correlation_token,
         })
     }
+
+    /// Try to decrypt a group broadcast with `group_key`
+    ///
+    /// Fails with `NotForUs` when the message is not a broadcast or was sent
+    /// to another group.
+    pub fn try_decrypt_broadcast(&self, group_key: &[u8]) -> Result<DecryptedBroadcast, MeshError> {
+        if self.message_type != MessageType::Broadcast || self.routing.ciphertext.is_empty() {
+            return Err(MeshError::NotForUs);
+        }
+
+        let routing_json =
+            nip44_decrypt_with_key(group_key.to_vec(), self.routing.ciphertext.clone())
+                .map_err(|_| MeshError::NotForUs)?;
+        let routing_data: BroadcastRoutingData =
+            serde_json::from_str(&routing_json).map_err(|_| MeshError::NotForUs)?;
+
+        let payload_str =
+            String::from_utf8(self.payload.clone()).map_err(|_| MeshError::DecryptionFailed)?;
+        let decrypted_payload_b64 = nip44_decrypt_with_key(group_key.to_vec(), payload_str)
+            .map_err(|_| MeshError::DecryptionFailed)?;
+        let decrypted_payload = base64::Engine::decode(
+            &base64::engine::general_purpose::STANDARD,
+            &decrypted_payload_b64,
+        )
+        .map_err(|_| MeshError::DecryptionFailed)?;
+
+        Ok(DecryptedBroadcast {
+            sender_commitment: routing_data.sender_commitment,
+            payload: decrypted_payload,
+            correlation_token: routing_data.correlation_token,
+        })
+    }
 }
 
 /// Result of successfully decrypting a message
@@ -346,6 +516,27 @@ pub struct DecryptedMessage {
     pub correlation_token: String,
 }
 
+/// Result of successfully decrypting a group broadcast
+#[derive(Debug, Clone)]
+pub struct DecryptedBroadcast {
+    pub sender_commitment: String,
+    pub payload: Vec<u8>,
+    pub correlation_token: String,
+}
+
+/// Derive the broadcast key for a group from its shared secret
+///
+/// Every member holding `group_secret` derives the same key; binding
+/// `group_id` keeps one secret from yielding the same key in two groups.
+pub fn derive_group_key(group_secret: &[u8], group_id: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(GROUP_KEY_DOMAIN);
+    hasher.update((group_id.len() as u32).to_be_bytes());
+    hasher.update(group_id.as_bytes());
+    hasher.update(group_secret);
+    hasher.finalize().to_vec()
+}
+
 /// Create signature material from message components
 fn create_signature_material(id: &str, routing_ciphertext: &str, payload: &str) -> Vec<u8> {
     let mut hasher = Sha256::new();
@@ -356,12 +547,17 @@ fn create_signature_material(id: &str, routing_ciphertext: &str, payload: &str)
 }
 
 /// Mesh-specific errors
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Error)]
 pub enum MeshError {
+    #[error("Mesh message serialization failed")]
     SerializationFailed,
+    #[error("Mesh message encryption failed")]
     EncryptionFailed,
+    #[error("Mesh message decryption failed")]
     DecryptionFailed,
+    #[error("Mesh message signing failed")]
     SigningFailed,
+    #[error("Mesh message is not addressed to us")]
     NotForUs,
 }
 
@@ -405,6 +601,54 @@ impl MeshNode {
     }
 }
 
+/// What we know about one mesh node, for the debug mesh map
+///
+/// Carries no nonce, and a pubkey only once the node revealed it in a
+/// handshake; unrevealed nodes are identified by commitment alone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeshNodeSnapshot {
+    pub commitment: String,
+    pub ble_address: String,
+    pub is_direct: bool,
+    pub rssi: Option<i16>,
+    pub last_seen: u64,
+    pub pubkey_revealed: bool,
+    pub pubkey: Option<String>,
+}
+
+impl From<&MeshNode> for MeshNodeSnapshot {
+    fn from(node: &MeshNode) -> Self {
+        Self {
+            commitment: node.commitment.clone(),
+            ble_address: node.ble_address.clone(),
+            is_direct: node.is_direct,
+            rssi: node.rssi,
+            last_seen: node.last_seen,
+            pubkey_revealed: node.pubkey.is_some(),
+            pubkey: node.pubkey.clone(),
+        }
+    }
+}
+
+/// Snapshot of the mesh as this node sees it
+///
+/// Nodes are listed direct first, then most recently seen first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MeshTopology {
+    pub our_pubkey: String,
+    pub nodes: Vec<MeshNodeSnapshot>,
+    pub direct_count: usize,
+    pub indirect_count: usize,
+}
+
+/// Current unix time in milliseconds
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
 /// Mesh network state
 pub struct MeshNetwork {
     /// Our private key (for decryption)
@@ -415,25 +659,175 @@ pub struct MeshNetwork {
     pub nodes: HashMap<String, MeshNode>,
     /// Seen correlation tokens (for endpoint deduplication)
     seen_tokens: HashMap<String, u64>,
+    /// How long a seen correlation token is remembered (ms)
+    token_ttl_ms: u64,
+    /// Last time expired tokens were evicted (unix ms)
+    last_token_cleanup: u64,
     /// Pending outgoing messages (by correlation token)
     pub pending_messages: HashMap<String, String>, // correlation_token -> original_id
+    /// Cached `PerSession` signing keys (recipient pubkey -> signer)
+    session_signers: HashMap<String, SessionSigner>,
+    /// Fingerprint of the current identity, tagging derived cache entries
+    identity_epoch: String,
+    /// Cached conversation keys (peer pubkey -> key)
+    conversation_keys: HashMap<String, CachedConversationKey>,
+    /// Senders whose messages are dropped after decryption
+    blocked: Arc<BlockList>,
+}
+
+/// Conversation key derived under a specific identity epoch
+struct CachedConversationKey {
+    epoch: String,
+    key: Vec<u8>,
+}
+
+/// Fingerprint identifying which private key derived state belongs to
+///
+/// Derived from the public key so the fingerprint itself reveals nothing
+/// about the private key.
+pub fn identity_fingerprint(pubkey: &str) -> String {
+    hex::encode(&Sha256::digest(pubkey.as_bytes())[..8])
+}
+
+/// Ephemeral signing key reused for one recipient under `PerSession`
+struct SessionSigner {
+    keypair: KeyPair,
+    /// Creation time (unix ms)
+    created_at: u64,
 }
 
 impl MeshNetwork {
     /// Create a new mesh network state
     pub fn new(private_key: Vec<u8>) -> Result<Self, MeshError> {
-        let pubkey = get_public_key(private_key.clone())
-            .map_err(|_| MeshError::EncryptionFailed)?;
+        Self::with_token_ttl(private_key, DEFAULT_CORRELATION_TOKEN_TTL_MS)
+    }
+
+    /// Create a new mesh network state remembering correlation tokens for
+    /// `token_ttl_ms`
+    pub fn with_token_ttl(private_key: Vec<u8>, token_ttl_ms: u64) -> Result<Self, MeshError> {
+        let pubkey =
+            get_public_key(private_key.clone()).map_err(|_| MeshError::EncryptionFailed)?;
 
         Ok(Self {
             our_private_key: private_key,
+            identity_epoch: identity_fingerprint(&pubkey),
             our_pubkey: pubkey,
             nodes: HashMap::new(),
             seen_tokens: HashMap::new(),
+            token_ttl_ms,
+            last_token_cleanup: now_ms(),
             pending_messages: HashMap::new(),
+            session_signers: HashMap::new(),
+            conversation_keys: HashMap::new(),
+            blocked: Arc::new(BlockList::new()),
         })
     }
 
+    /// Share the app's block list, consulted for every decrypted message
+    pub fn set_block_list(&mut self, blocked: Arc<BlockList>) {
+        self.blocked = blocked;
+    }
+
+    /// Fingerprint of the current identity
+    ///
+    /// Anything cached from `derive_conversation_key` should be tagged with
+    /// this and discarded once it no longer matches.
+    pub fn identity_epoch(&self) -> &str {
+        &self.identity_epoch
+    }
+
+    /// Switch to a new identity key
+    ///
+    /// Re-derives `our_pubkey` and drops every cache derived from the old
+    /// key (conversation keys and `PerSession` signers). Messages already
+    /// built are self-contained and unaffected, and seen/pending correlation
+    /// tokens are kept so in-flight messages are still deduplicated and
+    /// acknowledged. On an invalid key the current identity is left intact.
+    pub fn rotate_identity(&mut self, new_private_key: Vec<u8>) -> Result<(), MeshError> {
+        let pubkey =
+            get_public_key(new_private_key.clone()).map_err(|_| MeshError::EncryptionFailed)?;
+
+        self.clear_derived_caches();
+        self.our_private_key.zeroize();
+        self.our_private_key = new_private_key;
+        self.identity_epoch = identity_fingerprint(&pubkey);
+        self.our_pubkey = pubkey;
+        Ok(())
+    }
+
+    /// Sign duress alerts from this identity to `config`'s trusted contacts
+    pub fn create_duress_alerts(
+        &self,
+        config: DuressAlertConfig,
+        created_at: i64,
+    ) -> Result<Vec<NostrEvent>, CryptoError> {
+        create_duress_alerts(self.our_private_key.clone(), config, created_at)
+    }
+
+    /// Tear down this identity, wiping the private key with
+    /// `secure_destroy_key` rather than a single zeroize
+    ///
+    /// For a key believed compromised. Derived caches are zeroized as on drop.
+    pub fn destroy(mut self) -> Result<(), CryptoError> {
+        self.clear_derived_caches();
+        secure_destroy_key(std::mem::take(&mut self.our_private_key))
+    }
+
+    /// Conversation key with `peer_pubkey` for the current identity
+    fn conversation_key(&mut self, peer_pubkey: &str) -> Result<Vec<u8>, MeshError> {
+        if let Some(cached) = self.conversation_keys.get(peer_pubkey) {
+            if cached.epoch == self.identity_epoch {
+                return Ok(cached.key.clone());
+            }
+        }
+
+        let key = derive_conversation_key(self.our_private_key.clone(), peer_pubkey.to_string())
+            .map_err(|_| MeshError::EncryptionFailed)?;
+        let stale = self.conversation_keys.insert(
+            peer_pubkey.to_string(),
+            CachedConversationKey {
+                epoch: self.identity_epoch.clone(),
+                key: key.clone(),
+            },
+        );
+        if let Some(mut stale) = stale {
+            stale.key.zeroize();
+        }
+        Ok(key)
+    }
+
+    /// Zeroize and drop all state derived from the current private key
+    ///
+    /// Conversation keys are derived again and `PerSession` signers
+    /// regenerated on next use. Returns the number of conversation keys
+    /// dropped.
+    pub fn clear_derived_caches(&mut self) -> usize {
+        let count = self.conversation_keys.len();
+        for (_, mut cached) in self.conversation_keys.drain() {
+            cached.key.zeroize();
+        }
+        for (_, mut signer) in self.session_signers.drain() {
+            signer.keypair.private_key.zeroize();
+        }
+        count
+    }
+
+    /// Zeroize and drop the state derived for one peer
+    ///
+    /// Used when a contact is forgotten. Returns whether anything was cached.
+    pub fn forget_peer(&mut self, peer_pubkey: &str) -> bool {
+        let mut forgotten = false;
+        if let Some(mut cached) = self.conversation_keys.remove(peer_pubkey) {
+            cached.key.zeroize();
+            forgotten = true;
+        }
+        if let Some(mut signer) = self.session_signers.remove(peer_pubkey) {
+            signer.keypair.private_key.zeroize();
+            forgotten = true;
+        }
+        forgotten
+    }
+
     /// Add or update a node in the network
     pub fn update_node(&mut self, node: MeshNode) {
         self.nodes.insert(node.commitment.clone(), node);
@@ -451,11 +845,57 @@ impl MeshNetwork {
 
     /// Mark a correlation token as seen
     pub fn mark_token_seen(&mut self, token: &str) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+        self.mark_token_seen_at(token, now_ms());
+    }
+
+    /// Mark a correlation token as seen at `now` (unix ms)
+    ///
+    /// Runs `tick` once a full TTL has passed since the last cleanup, and
+    /// evicts the oldest tokens beyond `MAX_SEEN_TOKENS`, so the set stays
+    /// bounded even if the app never drives cleanup itself.
+    fn mark_token_seen_at(&mut self, token: &str, now: u64) {
+        if now.saturating_sub(self.last_token_cleanup) >= self.token_ttl_ms {
+            self.tick(now);
+        }
+
         self.seen_tokens.insert(token.to_string(), now);
+
+        while self.seen_tokens.len() > MAX_SEEN_TOKENS {
+            let oldest = self
+                .seen_tokens
+                .iter()
+                .min_by_key(|(_, seen_at)| **seen_at)
+                .map(|(token, _)| token.clone());
+            match oldest {
+                Some(oldest) => self.seen_tokens.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    /// Forget every seen correlation token, returning how many there were
+    ///
+    /// Copies of a message still circulating may be delivered once more.
+    pub fn clear_seen_tokens(&mut self) -> usize {
+        let count = self.seen_tokens.len();
+        self.seen_tokens.clear();
+        count
+    }
+
+    /// How long seen correlation tokens are remembered (ms)
+    pub fn token_ttl_ms(&self) -> u64 {
+        self.token_ttl_ms
+    }
+
+    /// Evict correlation tokens older than the TTL as of `now` (unix ms)
+    ///
+    /// Meant to be driven periodically by the app; returns the number of
+    /// evicted tokens.
+    pub fn tick(&mut self, now: u64) -> usize {
+        let before = self.seen_tokens.len();
+        self.evict_tokens_older_than(self.token_ttl_ms, now);
+        self.last_token_cleanup = now;
+        before - self.seen_tokens.len()
     }
 
     /// Process an incoming message
@@ -477,6 +917,11 @@ impl MeshNetwork {
                             return ProcessResult::Duplicate;
                         }
                         self.mark_token_seen(&decrypted.correlation_token);
+                        // The sender pubkey is authenticated by decryption
+                        if self.blocked.is_blocked(&decrypted.sender_pubkey) {
+                            log::debug!("Dropped mesh message from a blocked contact");
+                            return ProcessResult::Drop;
+                        }
                         ProcessResult::Deliver(decrypted)
                     }
                     Err(MeshError::NotForUs) => {
@@ -500,12 +945,12 @@ impl MeshNetwork {
 
     /// Clean up old correlation tokens (garbage collection)
     pub fn cleanup_old_tokens(&mut self, max_age_ms: u64) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+        self.evict_tokens_older_than(max_age_ms, now_ms());
+    }
 
-        self.seen_tokens.retain(|_, timestamp| now - *timestamp < max_age_ms);
+    fn evict_tokens_older_than(&mut self, max_age_ms: u64, now: u64) {
+        self.seen_tokens
+            .retain(|_, timestamp| now.saturating_sub(*timestamp) < max_age_ms);
     }
 
     /// Get all directly connected nodes
@@ -518,17 +963,49 @@ impl MeshNetwork {
         self.nodes.get(commitment)
     }
 
+    /// Snapshot the known nodes for debugging and visualization
+    pub fn mesh_topology(&self) -> MeshTopology {
+        let mut nodes: Vec<MeshNodeSnapshot> =
+            self.nodes.values().map(MeshNodeSnapshot::from).collect();
+        nodes.sort_by(|a, b| {
+            b.is_direct
+                .cmp(&a.is_direct)
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then_with(|| a.commitment.cmp(&b.commitment))
+        });
+        let direct_count = nodes.iter().filter(|n| n.is_direct).count();
+
+        MeshTopology {
+            our_pubkey: self.our_pubkey.clone(),
+            indirect_count: nodes.len() - direct_count,
+            direct_count,
+            nodes,
+        }
+    }
+
     /// Create a new message to send
+    ///
+    /// `policy` selects whether the ephemeral signing key is fresh for this
+    /// message or reused for the session with `recipient_pubkey`.
     pub fn create_message(
         &mut self,
         recipient_pubkey: &str,
         payload: &[u8],
+        policy: EphemeralPolicy,
     ) -> Result<MeshMessage, MeshError> {
-        let message = MeshMessage::new_direct(
-            &self.our_private_key,
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let ephemeral = self.ephemeral_signer(recipient_pubkey, policy, now);
+        let routing_key = self.conversation_key(recipient_pubkey)?;
+
+        let message = MeshMessage::build_direct(
+            routing_key,
             &self.our_pubkey,
             recipient_pubkey,
             payload,
+            &ephemeral,
         )?;
 
         // Store correlation token for ack tracking
@@ -536,6 +1013,45 @@ impl MeshNetwork {
 
         Ok(message)
     }
+
+    /// Pick the ephemeral signing key for a message under `policy`
+    ///
+    /// Expired session keys are zeroized and evicted on every call so the
+    /// cache stays bounded by the number of recipients in the last window.
+    fn ephemeral_signer(
+        &mut self,
+        recipient_pubkey: &str,
+        policy: EphemeralPolicy,
+        now: u64,
+    ) -> KeyPair {
+        self.session_signers.retain(|_, signer| {
+            let live = now.saturating_sub(signer.created_at) < EPHEMERAL_SESSION_TTL_MS;
+            if !live {
+                signer.keypair.private_key.zeroize();
+            }
+            live
+        });
+
+        match policy {
+            EphemeralPolicy::PerMessage => generate_keypair(),
+            EphemeralPolicy::PerSession => self
+                .session_signers
+                .entry(recipient_pubkey.to_string())
+                .or_insert_with(|| SessionSigner {
+                    keypair: generate_keypair(),
+                    created_at: now,
+                })
+                .keypair
+                .clone(),
+        }
+    }
+}
+
+impl Drop for MeshNetwork {
+    fn drop(&mut self) {
+        self.clear_derived_caches();
+        self.our_private_key.zeroize();
+    }
 }
 
 /// Result of processing a mesh message
@@ -631,6 +1147,56 @@ mod tests {
         assert_eq!(forwarded.ttl, DEFAULT_TTL - 1);
     }
 
+    #[test]
+    fn test_group_members_decrypt_broadcast() {
+        let sender = generate_keypair();
+        let (commitment, _) = MeshNode::create_commitment(&sender.public_key);
+        let group_key = derive_group_key(b"group secret", "group-1");
+
+        let msg = MeshMessage::new_broadcast(&group_key, &commitment, b"meeting moved").unwrap();
+        assert_eq!(msg.message_type, MessageType::Broadcast);
+        assert_ne!(msg.routing.ephemeral_pubkey, sender.public_key);
+        assert!(!msg.routing.ciphertext.contains(&commitment));
+
+        // Each member derives the key independently from the shared secret
+        for member_key in [
+            group_key.clone(),
+            derive_group_key(b"group secret", "group-1"),
+        ] {
+            let decrypted = msg.try_decrypt_broadcast(&member_key).unwrap();
+            assert_eq!(decrypted.sender_commitment, commitment);
+            assert_eq!(decrypted.payload, b"meeting moved");
+        }
+    }
+
+    #[test]
+    fn test_non_members_cannot_decrypt_broadcast() {
+        let outsider = generate_keypair();
+        let group_key = derive_group_key(b"group secret", "group-1");
+        let msg = MeshMessage::new_broadcast(&group_key, "commitment", b"members only").unwrap();
+
+        for other_key in [
+            derive_group_key(b"other secret", "group-1"),
+            derive_group_key(b"group secret", "group-2"),
+        ] {
+            assert!(matches!(
+                msg.try_decrypt_broadcast(&other_key),
+                Err(MeshError::NotForUs)
+            ));
+        }
+        assert!(matches!(
+            msg.try_decrypt_for_us(&outsider.private_key),
+            Err(MeshError::NotForUs)
+        ));
+
+        // Still relayed by nodes outside the group
+        let mut network = MeshNetwork::new(outsider.private_key).unwrap();
+        assert!(matches!(
+            network.process_message(&msg),
+            ProcessResult::Forward(_)
+        ));
+    }
+
     #[test]
     fn test_commitment_scheme() {
         let keypair = generate_keypair();
@@ -653,6 +1219,39 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_blocked_sender_dropped_after_decryption() {
+        let sender = generate_keypair();
+        let us = generate_keypair();
+        let mut network = MeshNetwork::new(us.private_key.clone()).unwrap();
+        let blocked = Arc::new(BlockList::new());
+        network.set_block_list(Arc::clone(&blocked));
+        let message = |body: &[u8]| {
+            MeshMessage::new_direct(
+                &sender.private_key,
+                &sender.public_key,
+                &us.public_key,
+                body,
+            )
+            .unwrap()
+        };
+
+        blocked.block(&sender.public_key).unwrap();
+        assert!(matches!(
+            network.process_message(&message(b"spam")),
+            ProcessResult::Drop
+        ));
+
+        blocked.unblock(&sender.public_key).unwrap();
+        match network.process_message(&message(b"sorry")) {
+            ProcessResult::Deliver(decrypted) => {
+                assert_eq!(decrypted.sender_pubkey, sender.public_key);
+                assert_eq!(decrypted.payload, b"sorry");
+            }
+            other => panic!("expected delivery, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_mesh_network_deduplication() {
         let our_keypair = generate_keypair();
@@ -665,6 +1264,300 @@ mod tests {
         assert!(network.has_seen_token(token));
     }
 
+    #[test]
+    fn test_mesh_topology_snapshot() {
+        let our_keypair = generate_keypair();
+        let mut network = MeshNetwork::new(our_keypair.private_key.clone()).unwrap();
+
+        let revealed = generate_keypair();
+        let (revealed_commitment, revealed_nonce) =
+            MeshNode::create_commitment(&revealed.public_key);
+        network.update_node(MeshNode {
+            commitment: revealed_commitment.clone(),
+            ble_address: "AA:AA:AA:AA:AA:AA".to_string(),
+            nonce: Some(revealed_nonce.clone()),
+            pubkey: Some(revealed.public_key.clone()),
+            last_seen: 1_000,
+            rssi: Some(-60),
+            is_direct: true,
+        });
+        for (i, last_seen) in [2_000u64, 3_000].into_iter().enumerate() {
+            let (commitment, nonce) = MeshNode::create_commitment(&generate_keypair().public_key);
+            network.update_node(MeshNode {
+                commitment,
+                ble_address: format!("BB:BB:BB:BB:BB:0{i}"),
+                nonce: Some(nonce),
+                pubkey: None,
+                last_seen,
+                rssi: None,
+                is_direct: false,
+            });
+        }
+
+        let topology = network.mesh_topology();
+        assert_eq!(topology.our_pubkey, our_keypair.public_key);
+        assert_eq!((topology.direct_count, topology.indirect_count), (1, 2));
+
+        // Direct first, then most recently seen
+        let order: Vec<u64> = topology.nodes.iter().map(|n| n.last_seen).collect();
+        assert_eq!(order, vec![1_000, 3_000, 2_000]);
+
+        let direct = &topology.nodes[0];
+        assert_eq!(direct.commitment, revealed_commitment);
+        assert!(direct.pubkey_revealed);
+        assert_eq!(direct.pubkey.as_deref(), Some(revealed.public_key.as_str()));
+        assert!(topology.nodes[1..]
+            .iter()
+            .all(|n| !n.pubkey_revealed && n.pubkey.is_none()));
+
+        // Neither our private key nor commitment nonces leave the network
+        let json = serde_json::to_string(&topology).unwrap();
+        assert!(!json.contains(&hex::encode(&our_keypair.private_key)));
+        assert!(!json.contains(&revealed_nonce));
+    }
+
+    #[test]
+    fn test_tokens_expire_after_ttl() {
+        let our_keypair = generate_keypair();
+        let mut network = MeshNetwork::with_token_ttl(our_keypair.private_key, 1_000).unwrap();
+        let start = network.last_token_cleanup;
+
+        network.mark_token_seen_at("old", start);
+        network.mark_token_seen_at("new", start + 600);
+
+        assert_eq!(network.tick(start + 999), 0);
+        assert!(network.has_seen_token("old"));
+
+        assert_eq!(network.tick(start + 1_000), 1);
+        assert!(!network.has_seen_token("old"));
+        assert!(network.has_seen_token("new"));
+
+        // Marking a token runs cleanup once a full TTL has passed
+        network.mark_token_seen_at("newer", start + 2_000);
+        assert!(!network.has_seen_token("new"));
+        assert!(network.has_seen_token("newer"));
+    }
+
+    #[test]
+    fn test_seen_tokens_capped() {
+        let our_keypair = generate_keypair();
+        let mut network = MeshNetwork::with_token_ttl(our_keypair.private_key, u64::MAX).unwrap();
+        let start = network.last_token_cleanup;
+
+        for i in 0..MAX_SEEN_TOKENS as u64 + 2 {
+            network.mark_token_seen_at(&format!("token-{}", i), start + i);
+        }
+
+        assert_eq!(network.seen_tokens.len(), MAX_SEEN_TOKENS);
+        assert!(!network.has_seen_token("token-0"));
+        assert!(!network.has_seen_token("token-1"));
+        assert!(network.has_seen_token("token-2"));
+        assert!(network.has_seen_token(&format!("token-{}", MAX_SEEN_TOKENS + 1)));
+    }
+
+    #[test]
+    fn test_per_message_policy_uses_fresh_signers() {
+        let our_keypair = generate_keypair();
+        let recipient = generate_keypair();
+        let mut network = MeshNetwork::new(our_keypair.private_key.clone()).unwrap();
+
+        let msg1 = network
+            .create_message(&recipient.public_key, b"one", EphemeralPolicy::PerMessage)
+            .unwrap();
+        let msg2 = network
+            .create_message(&recipient.public_key, b"two", EphemeralPolicy::PerMessage)
+            .unwrap();
+
+        assert_ne!(msg1.signer_pubkey, msg2.signer_pubkey);
+        assert_eq!(EphemeralPolicy::default(), EphemeralPolicy::PerMessage);
+        assert!(network.session_signers.is_empty());
+    }
+
+    #[test]
+    fn test_per_session_policy_reuses_signer() {
+        let our_keypair = generate_keypair();
+        let recipient = generate_keypair();
+        let other = generate_keypair();
+        let mut network = MeshNetwork::new(our_keypair.private_key.clone()).unwrap();
+
+        let msg1 = network
+            .create_message(&recipient.public_key, b"one", EphemeralPolicy::PerSession)
+            .unwrap();
+        let msg2 = network
+            .create_message(&recipient.public_key, b"two", EphemeralPolicy::PerSession)
+            .unwrap();
+        let msg3 = network
+            .create_message(&other.public_key, b"three", EphemeralPolicy::PerSession)
+            .unwrap();
+
+        // Same recipient shares a signer, other recipients do not
+        assert_eq!(msg1.signer_pubkey, msg2.signer_pubkey);
+        assert_ne!(msg1.signer_pubkey, msg3.signer_pubkey);
+
+        // Messages still decrypt normally
+        let decrypted = msg2.try_decrypt_for_us(&recipient.private_key).unwrap();
+        assert_eq!(decrypted.payload, b"two");
+    }
+
+    #[test]
+    fn test_per_session_signer_expires() {
+        let our_keypair = generate_keypair();
+        let recipient = generate_keypair();
+        let mut network = MeshNetwork::new(our_keypair.private_key.clone()).unwrap();
+        let start = 1_700_000_000_000u64;
+
+        let first =
+            network.ephemeral_signer(&recipient.public_key, EphemeralPolicy::PerSession, start);
+        let within = network.ephemeral_signer(
+            &recipient.public_key,
+            EphemeralPolicy::PerSession,
+            start + EPHEMERAL_SESSION_TTL_MS - 1,
+        );
+        assert_eq!(first.public_key, within.public_key);
+
+        let after = network.ephemeral_signer(
+            &recipient.public_key,
+            EphemeralPolicy::PerSession,
+            start + EPHEMERAL_SESSION_TTL_MS,
+        );
+        assert_ne!(first.public_key, after.public_key);
+        assert_eq!(network.session_signers.len(), 1);
+    }
+
+    #[test]
+    fn test_rotate_identity_updates_pubkey() {
+        let old_identity = generate_keypair();
+        let new_identity = generate_keypair();
+        let recipient = generate_keypair();
+        let mut network = MeshNetwork::new(old_identity.private_key.clone()).unwrap();
+        let old_epoch = network.identity_epoch().to_string();
+
+        // Built before rotation, delivered after
+        let in_flight = network
+            .create_message(
+                &recipient.public_key,
+                b"before",
+                EphemeralPolicy::PerSession,
+            )
+            .unwrap();
+        network.mark_token_seen("in-flight-token");
+
+        network
+            .rotate_identity(new_identity.private_key.clone())
+            .unwrap();
+        assert_eq!(network.our_pubkey, new_identity.public_key);
+        assert_ne!(network.identity_epoch(), old_epoch);
+        assert!(network.has_seen_token("in-flight-token"));
+
+        let decrypted = in_flight
+            .try_decrypt_for_us(&recipient.private_key)
+            .unwrap();
+        assert_eq!(decrypted.sender_pubkey, old_identity.public_key);
+        assert_eq!(decrypted.payload, b"before");
+
+        let after = network
+            .create_message(&recipient.public_key, b"after", EphemeralPolicy::PerSession)
+            .unwrap();
+        let decrypted = after.try_decrypt_for_us(&recipient.private_key).unwrap();
+        assert_eq!(decrypted.sender_pubkey, new_identity.public_key);
+        assert_ne!(after.signer_pubkey, in_flight.signer_pubkey);
+    }
+
+    #[test]
+    fn test_rotate_identity_drops_stale_cache() {
+        let old_identity = generate_keypair();
+        let new_identity = generate_keypair();
+        let peer = generate_keypair();
+        let mut network = MeshNetwork::new(old_identity.private_key.clone()).unwrap();
+
+        let old_key = network.conversation_key(&peer.public_key).unwrap();
+        assert_eq!(network.conversation_keys.len(), 1);
+
+        network
+            .rotate_identity(new_identity.private_key.clone())
+            .unwrap();
+        assert!(network.conversation_keys.is_empty());
+        assert!(network.session_signers.is_empty());
+
+        let new_key = network.conversation_key(&peer.public_key).unwrap();
+        assert_ne!(new_key, old_key);
+        assert_eq!(
+            new_key,
+            derive_conversation_key(new_identity.private_key.clone(), peer.public_key.clone())
+                .unwrap()
+        );
+
+        // An entry tagged with another epoch is never served
+        network.conversation_keys.insert(
+            peer.public_key.clone(),
+            CachedConversationKey {
+                epoch: "stale".to_string(),
+                key: old_key,
+            },
+        );
+        assert_eq!(network.conversation_key(&peer.public_key).unwrap(), new_key);
+    }
+
+    #[test]
+    fn test_forget_peer_drops_only_that_peer() {
+        let identity = generate_keypair();
+        let forgotten = generate_keypair();
+        let kept = generate_keypair();
+        let mut network = MeshNetwork::new(identity.private_key.clone()).unwrap();
+        network.conversation_key(&forgotten.public_key).unwrap();
+        network.conversation_key(&kept.public_key).unwrap();
+
+        assert!(network.forget_peer(&forgotten.public_key));
+        assert!(!network
+            .conversation_keys
+            .contains_key(&forgotten.public_key));
+        assert!(network.conversation_keys.contains_key(&kept.public_key));
+        assert!(!network.forget_peer(&forgotten.public_key));
+    }
+
+    #[test]
+    fn test_clear_ephemeral_caches_keeps_identity() {
+        let identity = generate_keypair();
+        let peer = generate_keypair();
+        let mut network = MeshNetwork::new(identity.private_key.clone()).unwrap();
+        let epoch = network.identity_epoch().to_string();
+
+        let key = network.conversation_key(&peer.public_key).unwrap();
+        network
+            .create_message(&peer.public_key, b"hi", EphemeralPolicy::PerSession)
+            .unwrap();
+        network.mark_token_seen("token");
+        network
+            .pending_messages
+            .insert("pending".to_string(), "original".to_string());
+
+        assert_eq!(network.clear_derived_caches(), 1);
+        assert!(network.conversation_keys.is_empty());
+        assert!(network.session_signers.is_empty());
+        assert_eq!(network.clear_seen_tokens(), 1);
+        assert!(!network.has_seen_token("token"));
+
+        // The identity and pending acknowledgements are untouched
+        assert_eq!(network.our_private_key, identity.private_key);
+        assert_eq!(network.our_pubkey, identity.public_key);
+        assert_eq!(network.identity_epoch(), epoch);
+        assert!(network.pending_messages.contains_key("pending"));
+        assert_eq!(network.conversation_key(&peer.public_key).unwrap(), key);
+    }
+
+    #[test]
+    fn test_rotate_identity_rejects_invalid_key() {
+        let identity = generate_keypair();
+        let mut network = MeshNetwork::new(identity.private_key.clone()).unwrap();
+
+        assert!(network.rotate_identity(vec![0u8; 32]).is_err());
+        assert_eq!(network.our_pubkey, identity.public_key);
+        assert_eq!(
+            network.identity_epoch(),
+            identity_fingerprint(&identity.public_key)
+        );
+    }
+
     #[test]
     fn test_timestamp_not_exact() {
         let msg1 = MeshMessage::ping();
@@ -5,12 +5,20 @@
 //! - Connection management
 //! - GATT read/write operations
 //! - Mesh message routing
-//! - Message chunking and reassembly
+//! - Peripheral (GATT server) mode
+//! - Message chunking, reassembly and retransmission
+//! - Priority scheduling of outbound sends
 
 pub mod chunk;
 pub mod manager;
 pub mod mesh;
+pub mod peripheral;
+pub mod send_queue;
 
-pub use chunk::{chunk_message, reassemble_chunks, Chunk, ChunkBuffer, ChunkError};
+pub use chunk::{
+    chunk_message, reassemble_chunks, Chunk, ChunkBuffer, ChunkError, ChunkFrame, ChunkReceipt,
+    OutgoingTransfer,
+};
 pub use manager::BleManager;
-pub use mesh::{MeshMessage, MeshNode};
+pub use mesh::{EphemeralPolicy, MeshMessage, MeshNode};
+pub use send_queue::{MessagePriority, SendQueue};
@@ -0,0 +1,543 @@
+//! BLE peripheral (GATT server) mode
+//!
+//! btleplug only implements the central role, so two desktops that both scan
+//! will never find each other. In peripheral mode we advertise the current
+//! rotating service UUID with our identity commitment and serve the mesh,
+//! identity, handshake and features characteristics ourselves.
+//!
+//! The GATT logic here is platform independent: a `PeripheralBackend`
+//! (CoreBluetooth `CBPeripheralManager`, a BlueZ GATT application, WinRT
+//! `GattServiceProvider`) only has to put the advertisement on air and feed
+//! central reads/writes into `GattServer`. No backend ships yet, so
+//! `platform_backend` reports `NotAvailable` on every platform.
+//!
+//! Security:
+//! - The advertisement carries only the commitment, never the public key
+//! - A central is authenticated only when its handshake opens a commitment
+//!   we have seen advertised, mirroring `BleManager::perform_handshake`
+//! - Mesh writes from unauthenticated centrals are rejected
+
+use super::chunk::ChunkReceipt;
+use super::manager::{
+    get_current_service_uuid, get_features_characteristic_uuid, get_handshake_characteristic_uuid,
+    get_identity_characteristic_uuid, get_mesh_characteristic_uuid, takes_chunk_receipts, BleError,
+    BleEvent, ConnectionStatus, IdentityCommitment, MeshInbox, COMMITMENT_ADVERTISEMENT_LEN,
+    SUPPORTED_FEATURES,
+};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Encoded size of the service data AD structure (length + type + UUID + commitment)
+///
+/// At 38 bytes this exceeds a legacy 31-byte advertisement, so backends need
+/// extended advertising (BLE 5) to carry the commitment.
+pub const SERVICE_DATA_AD_LEN: usize = 2 + 16 + COMMITMENT_ADVERTISEMENT_LEN;
+
+/// AD type for "Service Data - 128-bit UUID"
+const AD_TYPE_SERVICE_DATA_128: u8 = 0x21;
+
+/// What we put on air in peripheral mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvertisementPayload {
+    /// Current rotating service UUID
+    pub service_uuid: Uuid,
+    /// Identity commitment carried as service data
+    pub service_data: Vec<u8>,
+}
+
+impl AdvertisementPayload {
+    /// Build the advertisement for our identity commitment
+    pub fn new(commitment: &IdentityCommitment) -> Result<Self, BleError> {
+        let service_data = commitment.advertisement_data();
+        if service_data.len() != COMMITMENT_ADVERTISEMENT_LEN {
+            return Err(BleError::OperationError(format!(
+                "Commitment must be {} bytes, got {}",
+                COMMITMENT_ADVERTISEMENT_LEN,
+                service_data.len()
+            )));
+        }
+        Ok(Self {
+            service_uuid: get_current_service_uuid(),
+            service_data,
+        })
+    }
+
+    /// Encode the service data AD structure (UUID little-endian, per the spec)
+    pub fn service_data_ad(&self) -> Vec<u8> {
+        let mut ad = Vec::with_capacity(SERVICE_DATA_AD_LEN);
+        ad.push((SERVICE_DATA_AD_LEN - 1) as u8);
+        ad.push(AD_TYPE_SERVICE_DATA_128);
+        ad.extend(self.service_uuid.as_bytes().iter().rev());
+        ad.extend_from_slice(&self.service_data);
+        ad
+    }
+}
+
+/// The characteristics served in peripheral mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GattCharacteristic {
+    /// Mesh messages (write from central, notify to central)
+    Mesh,
+    /// Identity commitment (read)
+    Identity,
+    /// Commitment reveal (read ours, write theirs)
+    Handshake,
+    /// Supported feature bitmap (read ours, write theirs)
+    Features,
+}
+
+impl GattCharacteristic {
+    /// Map a characteristic UUID of the current service
+    pub fn from_uuid(uuid: &Uuid) -> Option<Self> {
+        if *uuid == get_mesh_characteristic_uuid() {
+            Some(Self::Mesh)
+        } else if *uuid == get_identity_characteristic_uuid() {
+            Some(Self::Identity)
+        } else if *uuid == get_handshake_characteristic_uuid() {
+            Some(Self::Handshake)
+        } else if *uuid == get_features_characteristic_uuid() {
+            Some(Self::Features)
+        } else {
+            None
+        }
+    }
+
+    /// UUID of this characteristic for the current service
+    pub fn uuid(self) -> Uuid {
+        match self {
+            Self::Mesh => get_mesh_characteristic_uuid(),
+            Self::Identity => get_identity_characteristic_uuid(),
+            Self::Handshake => get_handshake_characteristic_uuid(),
+            Self::Features => get_features_characteristic_uuid(),
+        }
+    }
+}
+
+/// Platform peripheral implementation
+pub trait PeripheralBackend: Send + Sync {
+    /// Register the GATT service and start advertising `payload`
+    fn start_advertising(&mut self, payload: &AdvertisementPayload) -> Result<(), BleError>;
+
+    /// Stop advertising and remove the GATT service
+    fn stop_advertising(&mut self) -> Result<(), BleError>;
+
+    /// Send a notification on `characteristic` to a subscribed central
+    fn notify(
+        &mut self,
+        central: &str,
+        characteristic: GattCharacteristic,
+        data: &[u8],
+    ) -> Result<(), BleError>;
+}
+
+/// Peripheral backend for the current platform, if one is available
+pub fn platform_backend() -> Result<Box<dyn PeripheralBackend>, BleError> {
+    Err(BleError::NotAvailable)
+}
+
+/// Whether peripheral mode can run on this platform
+pub fn is_available() -> bool {
+    platform_backend().is_ok()
+}
+
+/// GATT server state: answers central reads and dispatches central writes
+pub struct GattServer {
+    /// Our identity (commitment is advertised, pubkey revealed on handshake)
+    identity: IdentityCommitment,
+    /// Commitments seen in advertisements, which a handshake may open
+    known_commitments: HashSet<Vec<u8>>,
+    /// Verified public keys of authenticated centrals
+    authenticated: HashMap<String, String>,
+    /// Centrals that wrote a feature bitmap including chunk receipts
+    chunk_receipts: HashSet<String>,
+    /// Event broadcaster (shared with `BleManager`)
+    event_tx: broadcast::Sender<BleEvent>,
+    /// Mesh writes from centrals (shared with `BleManager`)
+    inbox: MeshInbox,
+}
+
+impl GattServer {
+    pub fn new(identity: IdentityCommitment, inbox: MeshInbox) -> Self {
+        Self {
+            identity,
+            known_commitments: HashSet::new(),
+            authenticated: HashMap::new(),
+            chunk_receipts: HashSet::new(),
+            event_tx: inbox.event_tx().clone(),
+            inbox,
+        }
+    }
+
+    /// Remember a commitment seen while scanning so its owner can authenticate
+    pub fn remember_commitment(&mut self, commitment: Vec<u8>) {
+        self.known_commitments.insert(commitment);
+    }
+
+    /// Forget the commitments seen while scanning, returning how many there were
+    ///
+    /// They are learned again from the next advertisements; centrals that
+    /// already authenticated stay authenticated.
+    pub fn forget_commitments(&mut self) -> usize {
+        let count = self.known_commitments.len();
+        self.known_commitments.clear();
+        count
+    }
+
+    /// Verified public key of a central, once its handshake succeeded
+    pub fn authenticated_pubkey(&self, central: &str) -> Option<&str> {
+        self.authenticated.get(central).map(String::as_str)
+    }
+
+    /// Forget a central after it disconnects
+    pub fn central_disconnected(&mut self, central: &str) {
+        self.chunk_receipts.remove(central);
+        if self.authenticated.remove(central).is_some() {
+            let _ = self.event_tx.send(BleEvent::ConnectionChanged {
+                address: central.to_string(),
+                status: ConnectionStatus::Disconnected,
+            });
+        }
+    }
+
+    /// Answer a read request from `central`
+    pub fn handle_read(&self, _central: &str, uuid: &Uuid) -> Result<Vec<u8>, BleError> {
+        match GattCharacteristic::from_uuid(uuid) {
+            Some(GattCharacteristic::Identity) => Ok(self.identity.advertisement_data()),
+            Some(GattCharacteristic::Handshake) => Ok(self.identity.handshake_data()),
+            Some(GattCharacteristic::Features) => Ok(vec![SUPPORTED_FEATURES]),
+            Some(GattCharacteristic::Mesh) => Err(BleError::ReadFailed(
+                "Mesh characteristic is write/notify only".to_string(),
+            )),
+            None => Err(BleError::CharacteristicNotFound),
+        }
+    }
+
+    /// Dispatch a write request from `central`
+    ///
+    /// Mesh writes go to the shared [`MeshInbox`], as chunk frames once the
+    /// central wrote a feature bitmap with chunk receipts and as whole
+    /// messages before that. Returns the receipt the backend should notify
+    /// back to `central` on the mesh characteristic, if one is due.
+    pub fn handle_write(
+        &mut self,
+        central: &str,
+        uuid: &Uuid,
+        data: &[u8],
+    ) -> Result<Option<ChunkReceipt>, BleError> {
+        match GattCharacteristic::from_uuid(uuid) {
+            Some(GattCharacteristic::Handshake) => {
+                self.handle_handshake(central, data).map(|()| None)
+            }
+            Some(GattCharacteristic::Features) => {
+                if takes_chunk_receipts(data) {
+                    self.chunk_receipts.insert(central.to_string());
+                } else {
+                    self.chunk_receipts.remove(central);
+                }
+                Ok(None)
+            }
+            Some(GattCharacteristic::Mesh) => {
+                if !self.authenticated.contains_key(central) {
+                    return Err(BleError::WriteFailed(
+                        "Central is not authenticated".to_string(),
+                    ));
+                }
+                let chunk_receipts = self.chunk_receipts.contains(central);
+                Ok(self.inbox.handle_write(central, data, chunk_receipts))
+            }
+            Some(GattCharacteristic::Identity) => Err(BleError::WriteFailed(
+                "Identity characteristic is read-only".to_string(),
+            )),
+            None => Err(BleError::CharacteristicNotFound),
+        }
+    }
+
+    /// Verify a central's commitment reveal
+    fn handle_handshake(&mut self, central: &str, data: &[u8]) -> Result<(), BleError> {
+        let (pubkey, nonce) = IdentityCommitment::parse_handshake(data)?;
+
+        let opened = self
+            .known_commitments
+            .iter()
+            .any(|commitment| IdentityCommitment::verify(commitment, &pubkey, nonce));
+        if !opened {
+            log::warn!(
+                "Handshake from {} does not open a known commitment",
+                central
+            );
+            return Err(BleError::CommitmentVerificationFailed);
+        }
+
+        self.authenticated
+            .insert(central.to_string(), pubkey.clone());
+        let _ = self.event_tx.send(BleEvent::ConnectionChanged {
+            address: central.to_string(),
+            status: ConnectionStatus::Authenticated,
+        });
+        let _ = self.event_tx.send(BleEvent::HandshakeCompleted {
+            address: central.to_string(),
+            pubkey,
+        });
+        log::info!("Peripheral handshake completed with {}", central);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ble::chunk::{chunk_message, MAX_MTU};
+
+    const OUR_PUBKEY: &str = "aaaa1234aaaa1234aaaa1234aaaa1234aaaa1234aaaa1234aaaa1234aaaa1234";
+    const THEIR_PUBKEY: &str = "bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678bbbb5678";
+
+    fn server() -> (GattServer, broadcast::Receiver<BleEvent>) {
+        let (event_tx, event_rx) = broadcast::channel(16);
+        (
+            GattServer::new(
+                IdentityCommitment::new(OUR_PUBKEY),
+                MeshInbox::new(event_tx),
+            ),
+            event_rx,
+        )
+    }
+
+    /// Authenticate `central` as the owner of a freshly advertised commitment
+    fn authenticate(server: &mut GattServer, central: &str) -> IdentityCommitment {
+        let theirs = IdentityCommitment::new(THEIR_PUBKEY);
+        server.remember_commitment(theirs.advertisement_data());
+        server
+            .handle_write(
+                central,
+                &GattCharacteristic::Handshake.uuid(),
+                &theirs.handshake_data(),
+            )
+            .unwrap();
+        theirs
+    }
+
+    #[test]
+    fn test_advertisement_payload() {
+        let identity = IdentityCommitment::new(OUR_PUBKEY);
+        let payload = AdvertisementPayload::new(&identity).unwrap();
+
+        assert_eq!(payload.service_uuid, get_current_service_uuid());
+        assert_eq!(payload.service_data, identity.commitment);
+
+        let ad = payload.service_data_ad();
+        assert_eq!(ad.len(), SERVICE_DATA_AD_LEN);
+        assert_eq!(ad[0] as usize, SERVICE_DATA_AD_LEN - 1);
+        assert_eq!(ad[1], AD_TYPE_SERVICE_DATA_128);
+        let mut uuid_le = *payload.service_uuid.as_bytes();
+        uuid_le.reverse();
+        assert_eq!(&ad[2..18], &uuid_le);
+        assert_eq!(&ad[18..], identity.commitment.as_slice());
+        // The public key never appears in the advertisement
+        assert!(!ad.windows(8).any(|w| w == &OUR_PUBKEY.as_bytes()[..8]));
+    }
+
+    #[test]
+    fn test_advertisement_rejects_bad_commitment() {
+        let mut identity = IdentityCommitment::new(OUR_PUBKEY);
+        identity.commitment.truncate(10);
+        assert!(AdvertisementPayload::new(&identity).is_err());
+    }
+
+    #[test]
+    fn test_characteristic_uuid_mapping() {
+        for characteristic in [
+            GattCharacteristic::Mesh,
+            GattCharacteristic::Identity,
+            GattCharacteristic::Handshake,
+            GattCharacteristic::Features,
+        ] {
+            assert_eq!(
+                GattCharacteristic::from_uuid(&characteristic.uuid()),
+                Some(characteristic)
+            );
+        }
+        assert_eq!(
+            GattCharacteristic::from_uuid(&get_current_service_uuid()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_dispatch() {
+        let (server, _rx) = server();
+
+        let identity = server
+            .handle_read("central", &GattCharacteristic::Identity.uuid())
+            .unwrap();
+        assert_eq!(identity, server.identity.commitment);
+
+        let handshake = server
+            .handle_read("central", &GattCharacteristic::Handshake.uuid())
+            .unwrap();
+        let (pubkey, nonce) = IdentityCommitment::parse_handshake(&handshake).unwrap();
+        assert_eq!(pubkey, OUR_PUBKEY);
+        assert!(IdentityCommitment::verify(&identity, &pubkey, nonce));
+
+        let features = server
+            .handle_read("central", &GattCharacteristic::Features.uuid())
+            .unwrap();
+        assert!(takes_chunk_receipts(&features));
+
+        assert!(server
+            .handle_read("central", &GattCharacteristic::Mesh.uuid())
+            .is_err());
+        assert!(matches!(
+            server.handle_read("central", &Uuid::nil()),
+            Err(BleError::CharacteristicNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_handshake_write_authenticates() {
+        let (mut server, mut rx) = server();
+        authenticate(&mut server, "central");
+
+        assert_eq!(server.authenticated_pubkey("central"), Some(THEIR_PUBKEY));
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            BleEvent::ConnectionChanged {
+                status: ConnectionStatus::Authenticated,
+                ..
+            }
+        ));
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            BleEvent::HandshakeCompleted { pubkey, .. } if pubkey == THEIR_PUBKEY
+        ));
+    }
+
+    #[test]
+    fn test_handshake_with_unknown_commitment_rejected() {
+        let (mut server, _rx) = server();
+        let stranger = IdentityCommitment::new(THEIR_PUBKEY);
+
+        let result = server.handle_write(
+            "central",
+            &GattCharacteristic::Handshake.uuid(),
+            &stranger.handshake_data(),
+        );
+        assert!(matches!(
+            result,
+            Err(BleError::CommitmentVerificationFailed)
+        ));
+        assert!(server.authenticated_pubkey("central").is_none());
+
+        // Truncated payloads are rejected without panicking
+        assert!(server
+            .handle_write(
+                "central",
+                &GattCharacteristic::Handshake.uuid(),
+                &[b'a'; 10]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_forget_commitments_keeps_authenticated_centrals() {
+        let (mut server, _rx) = server();
+        let theirs = authenticate(&mut server, "central");
+
+        assert_eq!(server.forget_commitments(), 1);
+        assert_eq!(server.forget_commitments(), 0);
+        assert_eq!(server.authenticated_pubkey("central"), Some(THEIR_PUBKEY));
+
+        // A new handshake needs the commitment to be advertised again
+        let handshake = GattCharacteristic::Handshake.uuid();
+        assert!(matches!(
+            server.handle_write("other", &handshake, &theirs.handshake_data()),
+            Err(BleError::CommitmentVerificationFailed)
+        ));
+        server.remember_commitment(theirs.advertisement_data());
+        server
+            .handle_write("other", &handshake, &theirs.handshake_data())
+            .unwrap();
+    }
+
+    /// Next message delivered to the frontend, skipping progress events
+    fn next_message(rx: &mut broadcast::Receiver<BleEvent>) -> (String, Vec<u8>) {
+        loop {
+            match rx.try_recv().unwrap() {
+                BleEvent::ChunkReceiveProgress { .. } => continue,
+                BleEvent::MessageReceived { from_address, data } => return (from_address, data),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mesh_write_requires_authentication() {
+        let (mut server, mut rx) = server();
+        let mesh = GattCharacteristic::Mesh.uuid();
+
+        assert!(server.handle_write("central", &mesh, b"hello").is_err());
+
+        authenticate(&mut server, "central");
+        while rx.try_recv().is_ok() {}
+
+        assert_eq!(
+            server.handle_write("central", &mesh, b"hello").unwrap(),
+            None
+        );
+        assert_eq!(
+            next_message(&mut rx),
+            ("central".to_string(), b"hello".to_vec())
+        );
+
+        assert!(server
+            .handle_write("central", &GattCharacteristic::Identity.uuid(), b"x")
+            .is_err());
+
+        server.central_disconnected("central");
+        assert!(server.handle_write("central", &mesh, b"hello").is_err());
+    }
+
+    #[test]
+    fn test_mesh_writes_framed_once_central_takes_receipts() {
+        let (mut server, mut rx) = server();
+        let mesh = GattCharacteristic::Mesh.uuid();
+        let features = GattCharacteristic::Features.uuid();
+        let chunks = chunk_message(b"hello", MAX_MTU).unwrap();
+        let hello = chunks[0].to_frame_bytes();
+        authenticate(&mut server, "central");
+        while rx.try_recv().is_ok() {}
+
+        // Until the central writes its features, a frame is just a message
+        assert_eq!(server.handle_write("central", &mesh, &hello).unwrap(), None);
+        assert_eq!(next_message(&mut rx).1, hello);
+
+        server
+            .handle_write("central", &features, &[SUPPORTED_FEATURES])
+            .unwrap();
+        let receipt = server.handle_write("central", &mesh, &hello).unwrap();
+        assert_eq!(
+            receipt,
+            Some(ChunkReceipt::Ack {
+                message_id: chunks[0].header.message_id,
+            })
+        );
+        assert_eq!(
+            next_message(&mut rx),
+            ("central".to_string(), b"hello".to_vec())
+        );
+
+        // Writes that aren't chunk frames are dropped
+        assert_eq!(
+            server.handle_write("central", &mesh, b"hello").unwrap(),
+            None
+        );
+        assert!(rx.try_recv().is_err());
+
+        // A reconnecting central negotiates again
+        server.central_disconnected("central");
+        authenticate(&mut server, "central");
+        while rx.try_recv().is_ok() {}
+        assert_eq!(server.handle_write("central", &mesh, &hello).unwrap(), None);
+        assert_eq!(next_message(&mut rx).1, hello);
+    }
+}
@@ -0,0 +1,295 @@
+//! Priority scheduling for outbound mesh sends
+//!
+//! Sends are queued in one lane per `MessagePriority` and drained highest
+//! lane first, so a duress alert overtakes routine traffic. To keep a busy
+//! high lane from starving the others, a message that has been overtaken
+//! `max_bypass` times goes next regardless of its priority.
+//!
+//! The queue does no I/O itself: a worker claims the drain with a
+//! [`DrainGuard`], pulls messages through it and sends them. The guard hands
+//! the drain back even if the worker panics, so the next send starts a new
+//! worker instead of waiting on a dead one.
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+use super::manager::BleError;
+
+/// Sends a lower-priority message may be overtaken by before it goes next
+pub const DEFAULT_MAX_BYPASS: u64 = 8;
+
+/// Scheduling priority of an outbound mesh message
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MessagePriority {
+    Low,
+    #[default]
+    Normal,
+    /// Duress alerts and other emergency traffic
+    High,
+}
+
+impl MessagePriority {
+    /// Priority used for duress alerts unless the caller overrides it
+    pub const DURESS_ALERT: Self = Self::High;
+
+    const ALL: [Self; 3] = [Self::Low, Self::Normal, Self::High];
+
+    fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+/// A queued send, answered through `reply` once it has gone out
+pub struct PendingSend {
+    /// Target device, or `None` to broadcast to all connected devices
+    pub address: Option<String>,
+    pub data: Vec<u8>,
+    pub authenticated_only: bool,
+    /// Receives the number of devices the message was sent to
+    pub reply: oneshot::Sender<Result<usize, BleError>>,
+}
+
+struct Queued<T> {
+    item: T,
+    /// Value of `SendQueue::dequeued` when the item was queued
+    queued_at: u64,
+}
+
+/// Outbound messages ordered by priority, with anti-starvation
+pub struct SendQueue<T> {
+    lanes: [VecDeque<Queued<T>>; 3],
+    /// Messages dequeued so far, the clock for anti-starvation
+    dequeued: u64,
+    max_bypass: u64,
+    draining: bool,
+}
+
+impl<T> SendQueue<T> {
+    /// Create an empty queue with `DEFAULT_MAX_BYPASS`
+    pub fn new() -> Self {
+        Self::with_max_bypass(DEFAULT_MAX_BYPASS)
+    }
+
+    /// Create an empty queue where a message goes next once it has been
+    /// overtaken `max_bypass` times
+    pub fn with_max_bypass(max_bypass: u64) -> Self {
+        Self {
+            lanes: Default::default(),
+            dequeued: 0,
+            max_bypass: max_bypass.max(1),
+            draining: false,
+        }
+    }
+
+    /// Queue `item` behind earlier messages of the same priority
+    pub fn push(&mut self, priority: MessagePriority, item: T) {
+        self.lanes[priority.lane()].push_back(Queued {
+            item,
+            queued_at: self.dequeued,
+        });
+    }
+
+    /// Take the next message to send
+    ///
+    /// The oldest message that has waited through `max_bypass` sends goes
+    /// first (lowest priority wins a tie, having been passed over longest);
+    /// otherwise the front of the highest non-empty lane.
+    pub fn pop(&mut self) -> Option<(MessagePriority, T)> {
+        let starved = MessagePriority::ALL.into_iter().find(|priority| {
+            self.lanes[priority.lane()]
+                .front()
+                .is_some_and(|q| self.dequeued - q.queued_at >= self.max_bypass)
+        });
+        let priority = starved.or_else(|| {
+            MessagePriority::ALL
+                .into_iter()
+                .rev()
+                .find(|priority| !self.lanes[priority.lane()].is_empty())
+        })?;
+
+        let queued = self.lanes[priority.lane()].pop_front()?;
+        self.dequeued += 1;
+        Some((priority, queued.item))
+    }
+
+    /// Number of queued messages
+    pub fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    /// Whether nothing is queued
+    pub fn is_empty(&self) -> bool {
+        self.lanes.iter().all(VecDeque::is_empty)
+    }
+
+    /// Claim the right to drain the queue
+    ///
+    /// Returns false if another caller is already draining; it will pick up
+    /// anything pushed in the meantime.
+    pub fn begin_drain(&mut self) -> bool {
+        !std::mem::replace(&mut self.draining, true)
+    }
+
+    /// Next message for the current drainer
+    ///
+    /// Returns `None` and releases the drain once the queue is empty. Doing
+    /// both under one lock means a push can never be left behind by a
+    /// drainer that is just finishing.
+    pub fn next_to_drain(&mut self) -> Option<T> {
+        let next = self.pop().map(|(_, item)| item);
+        if next.is_none() {
+            self.draining = false;
+        }
+        next
+    }
+}
+
+impl<T> Default for SendQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The drain of a shared queue, released when dropped
+pub struct DrainGuard<T> {
+    queue: Arc<Mutex<SendQueue<T>>>,
+    released: bool,
+}
+
+impl<T> DrainGuard<T> {
+    /// Claim the drain of `queue`, or `None` if another worker holds it
+    pub fn claim(queue: &Arc<Mutex<SendQueue<T>>>) -> Option<Self> {
+        queue.lock().begin_drain().then(|| Self {
+            queue: Arc::clone(queue),
+            released: false,
+        })
+    }
+
+    /// Next message to send; `None` once the queue is empty, which also
+    /// releases the drain
+    pub fn next(&mut self) -> Option<T> {
+        let next = self.queue.lock().next_to_drain();
+        self.released = next.is_none();
+        next
+    }
+}
+
+impl<T> Drop for DrainGuard<T> {
+    fn drop(&mut self) {
+        // Only when the worker stopped early; after `next` returned `None`
+        // another worker may already hold the drain
+        if !self.released {
+            self.queue.lock().draining = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain_all(queue: &mut SendQueue<&'static str>) -> Vec<&'static str> {
+        std::iter::from_fn(|| queue.pop().map(|(_, item)| item)).collect()
+    }
+
+    #[test]
+    fn test_higher_priority_goes_first() {
+        let mut queue = SendQueue::new();
+        queue.push(MessagePriority::Low, "low");
+        queue.push(MessagePriority::Normal, "normal-1");
+        queue.push(MessagePriority::High, "alert");
+        queue.push(MessagePriority::Normal, "normal-2");
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(
+            drain_all(&mut queue),
+            vec!["alert", "normal-1", "normal-2", "low"]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_low_priority_not_starved_by_continuous_high_traffic() {
+        let mut queue = SendQueue::with_max_bypass(3);
+        queue.push(MessagePriority::Low, "low");
+
+        // Keep the high lane busy: one new alert for every send
+        let mut sent = Vec::new();
+        for _ in 0..10 {
+            queue.push(MessagePriority::High, "high");
+            sent.push(queue.pop().unwrap().1);
+        }
+
+        let position = sent.iter().position(|item| *item == "low").unwrap();
+        assert_eq!(position, 3);
+        assert_eq!(sent.iter().filter(|item| **item == "low").count(), 1);
+    }
+
+    #[test]
+    fn test_starved_messages_keep_fifo_within_lane() {
+        let mut queue = SendQueue::with_max_bypass(2);
+        queue.push(MessagePriority::Low, "low-1");
+        queue.push(MessagePriority::Low, "low-2");
+        for _ in 0..4 {
+            queue.push(MessagePriority::High, "high");
+        }
+
+        assert_eq!(
+            drain_all(&mut queue),
+            vec!["high", "high", "low-1", "low-2", "high", "high"]
+        );
+    }
+
+    #[test]
+    fn test_single_drainer() {
+        let mut queue = SendQueue::new();
+        queue.push(MessagePriority::Normal, "a");
+
+        assert!(queue.begin_drain());
+        assert!(!queue.begin_drain());
+        assert_eq!(queue.next_to_drain(), Some("a"));
+        assert_eq!(queue.next_to_drain(), None);
+
+        // The drain is released once the queue ran dry
+        assert!(queue.begin_drain());
+    }
+
+    #[test]
+    fn test_drain_released_when_worker_panics() {
+        let queue = Arc::new(Mutex::new(SendQueue::new()));
+        queue.lock().push(MessagePriority::Normal, "a");
+        queue.lock().push(MessagePriority::Normal, "b");
+
+        let worker_queue = Arc::clone(&queue);
+        let worker = std::thread::spawn(move || {
+            let mut drain = DrainGuard::claim(&worker_queue).unwrap();
+            assert_eq!(drain.next(), Some("a"));
+            panic!("send failed");
+        });
+        assert!(worker.join().is_err());
+
+        // A new worker picks up what the dead one left
+        let mut drain = DrainGuard::claim(&queue).unwrap();
+        assert!(DrainGuard::claim(&queue).is_none());
+        assert_eq!(drain.next(), Some("b"));
+        assert_eq!(drain.next(), None);
+        drop(drain);
+        assert!(DrainGuard::claim(&queue).is_some());
+    }
+
+    #[test]
+    fn test_duress_alerts_default_high() {
+        assert_eq!(MessagePriority::DURESS_ALERT, MessagePriority::High);
+        assert_eq!(MessagePriority::default(), MessagePriority::Normal);
+        assert_eq!(
+            serde_json::to_string(&MessagePriority::High).unwrap(),
+            "\"high\""
+        );
+    }
+}
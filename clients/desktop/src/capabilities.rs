@@ -0,0 +1,128 @@
+//! Platform capability detection
+//!
+//! What works depends on the platform and on how the app was built: BLE
+//! peripheral mode needs a platform backend, the keyring needs a running
+//! secret service, and search needs SQLite's FTS5. The frontend reads
+//! [`Capabilities`] at startup and hides what is unavailable instead of
+//! hitting runtime errors.
+
+use std::collections::BTreeMap;
+
+use buildit_crypto::SelfTestReport;
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Features available in this build on this machine
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// A Bluetooth adapter could be opened for scanning and connecting
+    pub ble_central: bool,
+    /// BLE peripheral mode (advertising, serving GATT) has a backend
+    pub ble_peripheral: bool,
+    /// The OS keyring answers requests
+    pub keyring: bool,
+    pub sqlite: SqliteCapabilities,
+    /// Crypto self-test checks by name, true if the check passed
+    pub crypto_self_tests: BTreeMap<String, bool>,
+}
+
+/// Features of the linked SQLite library
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SqliteCapabilities {
+    /// SQLite version (e.g. "3.44.2")
+    pub version: String,
+    /// FTS5 full-text search
+    pub fts5: bool,
+    /// JSON functions
+    pub json1: bool,
+    /// SQLCipher version; None when linked against plain SQLite
+    pub sqlcipher_version: Option<String>,
+}
+
+/// Probe the SQLite library behind `conn` (use a scratch connection)
+///
+/// Features are probed by using them: compile options do not list
+/// features SQLite builds in by default, such as JSON since 3.38.
+pub fn sqlite_capabilities(conn: &Connection) -> Result<SqliteCapabilities, String> {
+    let version = conn
+        .query_row("SELECT sqlite_version()", [], |row| row.get(0))
+        .map_err(|e| format!("Query failed: {e}"))?;
+    let fts5 = conn
+        .execute_batch(
+            "CREATE VIRTUAL TABLE temp.capability_probe USING fts5(body);
+             DROP TABLE temp.capability_probe;",
+        )
+        .is_ok();
+    let json1 = conn
+        .query_row("SELECT json_valid('{}')", [], |row| row.get(0))
+        .unwrap_or(false);
+    let sqlcipher_version = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()
+        .unwrap_or(None);
+
+    Ok(SqliteCapabilities {
+        version,
+        fts5,
+        json1,
+        sqlcipher_version,
+    })
+}
+
+/// Pass/fail of each self-test check, by check name
+pub fn crypto_checks(report: &SelfTestReport) -> BTreeMap<String, bool> {
+    report
+        .checks
+        .iter()
+        .map(|check| (check.name.clone(), check.passed))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buildit_crypto::SelfTestCheck;
+
+    #[test]
+    fn test_bundled_sqlcipher_capabilities() {
+        let conn = Connection::open_in_memory().unwrap();
+        let sqlite = sqlite_capabilities(&conn).unwrap();
+
+        // The app links the bundled SQLCipher build, which enables FTS5 and JSON
+        assert!(sqlite.version.starts_with("3."));
+        assert!(sqlite.fts5);
+        assert!(sqlite.json1);
+        assert!(sqlite.sqlcipher_version.is_some());
+
+        // Probing leaves nothing behind
+        let leftover: i64 = conn
+            .query_row("SELECT COUNT(*) FROM temp.sqlite_master", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(leftover, 0);
+    }
+
+    #[test]
+    fn test_crypto_checks_by_name() {
+        let check = |name: &str, passed: bool| SelfTestCheck {
+            name: name.to_string(),
+            passed,
+            error: (!passed).then(|| "mismatch".to_string()),
+            duration_ms: 1,
+        };
+        let report = SelfTestReport {
+            passed: false,
+            checks: vec![check("nip44", true), check("aes_gcm", false)],
+        };
+
+        let checks = crypto_checks(&report);
+        assert_eq!(checks.len(), 2);
+        assert!(checks["nip44"]);
+        assert!(!checks["aes_gcm"]);
+
+        // Every check of the real self-test is reported
+        let report = buildit_crypto::crypto_self_test();
+        assert_eq!(crypto_checks(&report).len(), report.checks.len());
+    }
+}
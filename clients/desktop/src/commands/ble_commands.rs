@@ -1,66 +1,78 @@
 //! BLE Tauri commands exposed to the frontend
 
-use crate::ble::manager::{BleError, ConnectionStatus, DiscoveredDevice};
-use crate::ble::mesh::MeshMessage;
+use super::error::CommandError;
+pub use super::error::CommandResult;
+use crate::ble::chunk::{self, ChunkError, DeviceMtu, TransferEstimate, DEFAULT_ATT_MTU};
+use crate::ble::manager::{
+    broadcast_over_links, make_identity_qr_payload as identity_qr_payload, ping_peers,
+    verify_identity_qr_payload as check_identity_qr_payload, BleError, BleManager,
+    ConnectionStatus, DiscoveredDevice, PeerPingResult, ScanDutyCycle, ScanMode, ScanPhase,
+    PING_TIMEOUT,
+};
+use crate::ble::mesh::{MeshMessage, MeshTopology};
+use crate::ble::peripheral;
+use crate::ble::send_queue::{DrainGuard, MessagePriority, PendingSend};
+use crate::contacts::normalize_pubkey;
+use crate::db::Database;
 use crate::AppState;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
 use tauri::State;
+use tokio::sync::oneshot;
 
 /// BLE status response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BleStatus {
     pub is_scanning: bool,
+    pub scan_mode: Option<ScanMode>,
+    pub scan_phase: Option<ScanPhase>,
+    pub scan_duty_cycle: Option<ScanDutyCycle>,
+    pub is_advertising: bool,
     pub connected_devices: Vec<String>,
     pub discovered_count: usize,
 }
 
-/// Command result wrapper
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CommandResult<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
-
-impl<T> CommandResult<T> {
-    pub fn ok(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn err(error: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error),
-        }
-    }
-}
-
 /// Start BLE scanning for BuildIt devices
+///
+/// `scan_mode` defaults to active; passive is refused where the platform
+/// cannot honour it. With `duty_cycle`, the scan alternates scan and idle
+/// windows to save power, emitting `ScanPhaseChanged` events.
+///
+/// `scan_all` is for debugging only: it drops the BuildIt service filter
+/// and also reports unrelated nearby devices. Defaults to false.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn start_ble_scan(
     state: State<'_, AppState>,
     timeout_seconds: Option<u64>,
+    scan_mode: Option<ScanMode>,
+    duty_cycle: Option<ScanDutyCycle>,
+    scan_all: Option<bool>,
 ) -> Result<CommandResult<()>, String> {
     let mut manager = state.ble_manager.write();
+    let mode = scan_mode.unwrap_or_default();
 
     // We need to use tokio runtime for async operations
     let result = tokio::task::block_in_place(|| {
-        tokio::runtime::Handle::current().block_on(manager.start_scan(timeout_seconds))
+        tokio::runtime::Handle::current().block_on(manager.start_scan(
+            timeout_seconds,
+            mode,
+            duty_cycle,
+            scan_all.unwrap_or(false),
+        ))
     });
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Stop BLE scanning
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn stop_ble_scan(state: State<'_, AppState>) -> Result<CommandResult<()>, String> {
     let mut manager = state.ble_manager.write();
 
@@ -70,12 +82,79 @@ pub async fn stop_ble_scan(state: State<'_, AppState>) -> Result<CommandResult<(
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Reset a wedged Bluetooth adapter
+///
+/// Stops scanning, drops connections and discovered devices and opens the
+/// adapter again; identity and advertising are kept. Emits
+/// `AdapterStateChanged`. Scanning must be restarted afterwards.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn reset_ble_adapter(state: State<'_, AppState>) -> Result<CommandResult<()>, String> {
+    let mut manager = state.ble_manager.write();
+
+    let result = tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(manager.reset_adapter())
+    });
+
+    match result {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Start BLE advertising (peripheral mode) as `pubkey`
+///
+/// No peripheral backend ships yet, so this fails with `ble_not_available`
+/// on every platform; check `ble_peripheral` in `get_capabilities` before
+/// offering it. The BLE identity is left untouched when advertising cannot
+/// start, and a new commitment is only made if `pubkey` is not already the
+/// BLE identity.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn start_ble_advertising(
+    state: State<'_, AppState>,
+    pubkey: String,
+) -> Result<CommandResult<()>, String> {
+    if pubkey.len() != 64 || hex::decode(&pubkey).is_err() {
+        return Ok(CommandResult::err("Invalid public key".to_string()));
+    }
+    if !peripheral::is_available() {
+        return Ok(CommandResult::fail(BleError::NotAvailable));
+    }
+
+    let mut manager = state.ble_manager.write();
+    if manager.is_advertising() {
+        return Ok(CommandResult::ok(()));
+    }
+
+    if manager.identity().map(|identity| identity.pubkey.as_str()) != Some(pubkey.as_str()) {
+        manager.set_identity(&pubkey);
+    }
+    match manager.start_advertising() {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Stop BLE advertising
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn stop_ble_advertising(state: State<'_, AppState>) -> Result<CommandResult<()>, String> {
+    let mut manager = state.ble_manager.write();
+
+    match manager.stop_advertising() {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Get list of discovered BLE devices
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn get_discovered_devices(
     state: State<'_, AppState>,
 ) -> Result<CommandResult<Vec<DiscoveredDevice>>, String> {
@@ -87,12 +166,13 @@ pub async fn get_discovered_devices(
 
     match result {
         Ok(devices) => Ok(CommandResult::ok(devices)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Connect to a BLE device by address
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn connect_device(
     state: State<'_, AppState>,
     address: String,
@@ -105,12 +185,13 @@ pub async fn connect_device(
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Disconnect from a BLE device
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn disconnect_device(
     state: State<'_, AppState>,
     address: String,
@@ -123,48 +204,420 @@ pub async fn disconnect_device(
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
+/// Forget a device: disconnect it, drop it from BLE state, wipe the mesh
+/// keys derived for it and delete the active user's contact record
+///
+/// The device's public key is taken from BLE state, or from `pubkey` (the
+/// contact's key as stored) once BLE no longer knows the address, e.g.
+/// after a restart. The contact is deleted first, so a locked database
+/// leaves everything in place for a retry. Safe to call repeatedly.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn forget_device(
+    state: State<'_, AppState>,
+    db: State<'_, Database>,
+    address: String,
+    pubkey: Option<String>,
+) -> Result<CommandResult<()>, String> {
+    let Some(user_pubkey) = state.active_pubkey() else {
+        return Ok(CommandResult::fail(CommandError::no_active_identity()));
+    };
+    let known = state.ble_manager.read().known_pubkey(&address);
+    let pubkey = match (known, pubkey) {
+        (Some(known), _) => Some(known),
+        (None, Some(pubkey)) => match normalize_pubkey(&pubkey) {
+            Ok(pubkey) => Some(pubkey),
+            Err(e) => return Ok(CommandResult::fail(e)),
+        },
+        (None, None) => None,
+    };
+
+    if let Some(pubkey) = &pubkey {
+        let deleted = db.with_connection(|conn| {
+            conn.execute(
+                "DELETE FROM friends WHERE user_pubkey = ?1 AND friend_pubkey = ?2",
+                rusqlite::params![user_pubkey, pubkey],
+            )
+            .map_err(|e| format!("forget_device failed: {e}"))
+        });
+        if let Err(e) = deleted {
+            return Ok(CommandResult::fail(e));
+        }
+        if let Some(mesh) = state.mesh_network.write().as_mut() {
+            mesh.forget_peer(pubkey);
+        }
+    }
+
+    let mut manager = state.ble_manager.write();
+    tokio::task::block_in_place(|| {
+        tokio::runtime::Handle::current().block_on(manager.forget_device(&address))
+    });
+
+    Ok(CommandResult::ok(()))
+}
+
+/// Snapshot the mesh nodes this device knows about, for the mesh map
+///
+/// Unrevealed nodes are reported by commitment only. Requires an active
+/// identity.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_mesh_topology(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<MeshTopology>, String> {
+    match state.mesh_network.read().as_ref() {
+        Some(mesh) => Ok(CommandResult::ok(mesh.mesh_topology())),
+        None => Ok(CommandResult::fail(CommandError::no_active_identity())),
+    }
+}
+
+/// Ping every authenticated peer and report who answers ("test my mesh")
+///
+/// Waits up to `timeout_ms` (default 5 s) for pongs and reports the
+/// round-trip time per peer. Peers that drop during the check are reported
+/// as disconnected rather than timed out.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn ping_all_peers(
+    state: State<'_, AppState>,
+    timeout_ms: Option<u64>,
+) -> Result<CommandResult<Vec<PeerPingResult>>, String> {
+    let (peers, events) = {
+        let manager = state.ble_manager.read();
+        (manager.authenticated_peers(), manager.subscribe_events())
+    };
+    let timeout = timeout_ms.map_or(PING_TIMEOUT, Duration::from_millis);
+
+    let state: &AppState = &state;
+    let send = |address: String, data: Vec<u8>| async move {
+        send_queued(state, Some(address), data, true, MessagePriority::Normal)
+            .await
+            .map(|_| ())
+    };
+    Ok(CommandResult::ok(
+        ping_peers(peers, events, send, timeout).await,
+    ))
+}
+
 /// Send a mesh message to connected devices
+///
+/// A unicast only goes to an authenticated device unless
+/// `authenticated_only` is explicitly `false`. Sends are queued by
+/// `priority` (default normal), so urgent messages overtake routine ones.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn send_mesh_message(
     state: State<'_, AppState>,
     address: Option<String>,
     data: Vec<u8>,
+    authenticated_only: Option<bool>,
+    priority: Option<MessagePriority>,
 ) -> Result<CommandResult<usize>, String> {
-    let manager = state.ble_manager.read();
+    // Oversized transfers would only fail once chunking starts
+    if let Err(e) = check_send_size(&state, address.as_deref(), &data) {
+        return Ok(CommandResult::fail(e));
+    }
 
-    let result = if let Some(addr) = address {
-        // Send to specific device
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(manager.send_message(&addr, &data))
-        })
-        .map(|_| 1usize)
-    } else {
-        // Broadcast to all connected devices
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(manager.broadcast_mesh_message(&data))
-        })
-    };
+    let authenticated_only = authenticated_only.unwrap_or(true);
+    let priority = priority.unwrap_or_default();
+    match send_queued(&state, address, data, authenticated_only, priority).await {
+        Ok(count) => Ok(CommandResult::ok(count)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
 
-    match result {
+/// Broadcast a duress alert to all connected devices
+///
+/// Same as a broadcast `send_mesh_message`, but `priority` defaults to
+/// `MessagePriority::DURESS_ALERT` so the alert jumps the send queue.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn broadcast_duress_alert(
+    state: State<'_, AppState>,
+    data: Vec<u8>,
+    priority: Option<MessagePriority>,
+) -> Result<CommandResult<usize>, String> {
+    if let Err(e) = check_send_size(&state, None, &data) {
+        return Ok(CommandResult::fail(e));
+    }
+
+    let priority = priority.unwrap_or(MessagePriority::DURESS_ALERT);
+    match send_queued(&state, None, data, true, priority).await {
         Ok(count) => Ok(CommandResult::ok(count)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Refuse a mesh send too large for one chunked transfer
+///
+/// Checked at the device's MTU for a unicast and at the smallest MTU among
+/// authenticated devices for a broadcast, or at `DEFAULT_ATT_MTU` when
+/// there is none to go by.
+fn check_send_size(state: &AppState, address: Option<&str>, data: &[u8]) -> Result<(), ChunkError> {
+    let att_mtu = {
+        let manager = state.ble_manager.read();
+        match address {
+            Some(address) => manager.device_mtu(address).ok().map(|mtu| mtu.att_mtu),
+            None => manager
+                .broadcast_links()
+                .iter()
+                .map(|link| link.mtu().att_mtu)
+                .min(),
+        }
+    };
+    chunk::check_transfer_size(data, att_mtu.unwrap_or(DEFAULT_ATT_MTU))
+}
+
+/// Queue a send and wait until it has gone out
+///
+/// The first send to find the queue idle starts a drain worker, which sends
+/// everyone's messages in priority order until the queue is empty. Callers
+/// only wait for their own reply, however long the queue keeps filling.
+async fn send_queued(
+    state: &AppState,
+    address: Option<String>,
+    data: Vec<u8>,
+    authenticated_only: bool,
+    priority: MessagePriority,
+) -> Result<usize, BleError> {
+    let (reply, result) = oneshot::channel();
+    let send = PendingSend {
+        address,
+        data,
+        authenticated_only,
+        reply,
+    };
+
+    state.mesh_send_queue.lock().push(priority, send);
+    if let Some(drain) = DrainGuard::claim(&state.mesh_send_queue) {
+        let ble_manager = Arc::clone(&state.ble_manager);
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || drain_send_queue(drain, &ble_manager, &handle));
+    }
+
+    result
+        .await
+        .map_err(|_| BleError::OperationError("Queued mesh send was dropped".to_string()))?
+}
+
+/// Drain worker: send queued messages until the queue is empty
+///
+/// Runs on a blocking thread, waiting out each send in turn. The BLE manager
+/// is only locked to clone the links out, so scans, connects and shutdown
+/// aren't held up while a transfer waits for receipts.
+fn drain_send_queue(
+    mut drain: DrainGuard<PendingSend>,
+    ble_manager: &RwLock<BleManager>,
+    handle: &tokio::runtime::Handle,
+) {
+    // `next` releases the queue lock before sending so others can keep queueing
+    while let Some(send) = drain.next() {
+        let result = match &send.address {
+            Some(addr) => {
+                let link = ble_manager.read().mesh_link(addr, send.authenticated_only);
+                link.and_then(|link| handle.block_on(link.send(&send.data)))
+                    .map(|_| 1usize)
+            }
+            // Broadcast to all connected devices
+            None => {
+                let links = ble_manager.read().broadcast_links();
+                Ok(handle.block_on(broadcast_over_links(&links, &send.data)))
+            }
+        };
+
+        let _ = send.reply.send(result);
+    }
+}
+
+/// Estimate the chunks and time a mesh send of `payload_len` bytes needs
+///
+/// `mtu` is the device's ATT MTU as `get_device_mtu` reports it, and
+/// defaults to the conservative `DEFAULT_ATT_MTU`. Lets the UI show
+/// progress estimates and confirm unusually large sends.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn estimate_chunked_transfer(
+    payload_len: usize,
+    mtu: Option<u16>,
+    inter_chunk_delay_ms: u64,
+) -> Result<CommandResult<TransferEstimate>, String> {
+    match chunk::estimate_chunked_transfer(
+        payload_len,
+        mtu.unwrap_or(DEFAULT_ATT_MTU),
+        inter_chunk_delay_ms,
+    ) {
+        Ok(estimate) => Ok(CommandResult::ok(estimate)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Report a connected device's ATT MTU and the mesh payload per chunk
+///
+/// Falls back to the conservative `DEFAULT_ATT_MTU` when the platform
+/// doesn't report the negotiated MTU, so frontends can check whether a file
+/// is too big for the peer before sending.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_device_mtu(
+    state: State<'_, AppState>,
+    address: String,
+) -> Result<CommandResult<DeviceMtu>, String> {
+    match state.ble_manager.read().device_mtu(&address) {
+        Ok(mtu) => Ok(CommandResult::ok(mtu)),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Get current BLE status
 #[tauri::command]
-pub async fn get_ble_status(state: State<'_, AppState>) -> Result<CommandResult<BleStatus>, String> {
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_ble_status(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<BleStatus>, String> {
     let manager = state.ble_manager.read();
 
     let status = BleStatus {
         is_scanning: manager.is_scanning(),
+        scan_mode: manager.scan_mode(),
+        scan_phase: manager.scan_phase(),
+        scan_duty_cycle: manager.scan_duty_cycle(),
+        is_advertising: manager.is_advertising(),
         connected_devices: vec![], // Would need to track this in manager
         discovered_count: 0,       // Would need to expose this
     };
 
     Ok(CommandResult::ok(status))
 }
+
+/// Build the identity verification QR payload for `pubkey`
+///
+/// Shown to a contact met in person, who scans it and checks it with
+/// `verify_identity_qr_payload` before trusting the BLE handshake.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn make_identity_qr_payload(pubkey: String) -> Result<CommandResult<String>, String> {
+    match normalize_pubkey(&pubkey) {
+        Ok(pubkey) => Ok(CommandResult::ok(identity_qr_payload(&pubkey))),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Check a scanned identity QR payload against the pubkey expected for the
+/// contact (e.g. the one verified by the BLE handshake)
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn verify_identity_qr_payload(
+    scanned: String,
+    expected_pubkey: String,
+) -> Result<CommandResult<bool>, String> {
+    Ok(CommandResult::ok(check_identity_qr_payload(
+        &scanned,
+        &expected_pubkey,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ble::chunk::MAX_MESSAGE_LEN;
+    use sha2::{Digest, Sha256};
+    use tauri::Manager;
+
+    /// Bytes that don't compress, so their size is what gets chunked
+    fn random_payload(len: usize) -> Vec<u8> {
+        (0..len / 32 + 1)
+            .flat_map(|i: usize| Sha256::digest(i.to_le_bytes()))
+            .take(len)
+            .collect()
+    }
+
+    fn error_code<T>(result: &CommandResult<T>) -> Option<&str> {
+        result.error_detail.as_ref().map(|e| e.code.as_str())
+    }
+
+    #[test]
+    fn test_send_takes_messages_larger_than_one_write() {
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let data = random_payload(4 * chunk::MAX_MTU);
+
+        // Nobody is connected, so nothing is sent, but the size is accepted
+        let sent = runtime
+            .block_on(send_mesh_message(
+                app.state(),
+                None,
+                data.clone(),
+                None,
+                None,
+            ))
+            .unwrap();
+        assert!(sent.success, "{:?}", sent.error);
+        assert_eq!(sent.data, Some(0));
+        let sent = runtime
+            .block_on(broadcast_duress_alert(app.state(), data, None))
+            .unwrap();
+        assert!(sent.success, "{:?}", sent.error);
+
+        // A unicast to an unknown device still fails when it is sent
+        let sent = runtime
+            .block_on(send_mesh_message(
+                app.state(),
+                Some("AA:BB".to_string()),
+                random_payload(4 * chunk::MAX_MTU),
+                Some(false),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(error_code(&sent), Some("ble_device_not_found"));
+    }
+
+    #[test]
+    fn test_estimate_matches_chunks_at_the_device_mtu() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let data = random_payload(5000);
+
+        for mtu in [None, Some(247)] {
+            let device = DeviceMtu::new(mtu);
+            let estimate = runtime
+                .block_on(estimate_chunked_transfer(data.len(), mtu, 20))
+                .unwrap()
+                .data
+                .unwrap();
+            let write_size = chunk::CHUNK_FRAME_OVERHEAD + device.max_mesh_payload;
+            let chunks = chunk::chunk_message(&data, write_size).unwrap();
+            assert_eq!(estimate.chunk_count, chunks.len(), "mtu {:?}", mtu);
+            assert_eq!(estimate.estimated_ms, 20 * chunks.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_send_refuses_messages_past_the_transfer_limits() {
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // Past the message limit, however well it compresses
+        let sent = runtime
+            .block_on(send_mesh_message(
+                app.state(),
+                None,
+                vec![0u8; MAX_MESSAGE_LEN + 1],
+                None,
+                None,
+            ))
+            .unwrap();
+        assert_eq!(error_code(&sent), Some("chunk_message_too_large"));
+
+        // More than 255 chunks at the default MTU
+        let too_many_chunks = random_payload(256 * chunk::max_mesh_payload(DEFAULT_ATT_MTU));
+        let sent = runtime
+            .block_on(broadcast_duress_alert(app.state(), too_many_chunks, None))
+            .unwrap();
+        assert_eq!(error_code(&sent), Some("chunk_message_too_large"));
+    }
+}
@@ -0,0 +1,49 @@
+//! Capability Tauri commands for adapting the frontend to platform support
+
+pub use super::error::CommandResult;
+use crate::ble::peripheral;
+use crate::capabilities::{self, Capabilities};
+use crate::AppState;
+use rusqlite::Connection;
+use tauri::State;
+
+/// Report which optional features work on this machine
+///
+/// Opens the Bluetooth adapter if it is not open yet and runs the crypto
+/// self-test on the blocking pool, so call it once at startup rather than
+/// on every render.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_capabilities(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<Capabilities>, String> {
+    let ble_central = {
+        let mut manager = state.ble_manager.write();
+        manager.has_adapter()
+            || tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current().block_on(manager.initialize())
+            })
+            .is_ok()
+    };
+
+    let sqlite = match Connection::open_in_memory()
+        .map_err(|e| format!("Failed to open scratch database: {e}"))
+        .and_then(|conn| capabilities::sqlite_capabilities(&conn))
+    {
+        Ok(sqlite) => sqlite,
+        Err(e) => return Ok(CommandResult::fail(e)),
+    };
+
+    let report = match tokio::task::spawn_blocking(buildit_crypto::crypto_self_test).await {
+        Ok(report) => report,
+        Err(e) => return Err(format!("Self-test task failed: {e}")),
+    };
+
+    Ok(CommandResult::ok(Capabilities {
+        ble_central,
+        ble_peripheral: peripheral::is_available(),
+        keyring: state.keyring_manager.is_available(),
+        sqlite,
+        crypto_self_tests: capabilities::crypto_checks(&report),
+    }))
+}
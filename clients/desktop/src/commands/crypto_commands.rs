@@ -1,52 +1,47 @@
 //! Crypto/Keyring Tauri commands exposed to the frontend
 
-use crate::crypto::keyring::{KeyringError, KeyringManager, SecretType};
-use crate::AppState;
+use super::encoding::decode_flexible;
+use super::error::CommandError;
+pub use super::error::CommandResult;
+use crate::crypto::keyring::{KeyringError, KeyringManager, SecretType, SecretValue};
+use crate::db::Database;
+use crate::nostr::relay::{publish_to_relays, PUBLISH_ACK_TIMEOUT};
+use crate::{AppState, EphemeralStateCleared};
 use buildit_crypto::{
     aes_decrypt as crypto_aes_decrypt, aes_encrypt as crypto_aes_encrypt,
-    check_duress_password as crypto_check_duress_password, compute_event_id as crypto_compute_event_id,
-    create_duress_alert as crypto_create_duress_alert, create_duress_alerts as crypto_create_duress_alerts,
+    calibrate_argon2 as crypto_calibrate_argon2,
+    check_duress_password as crypto_check_duress_password,
+    compute_event_id as crypto_compute_event_id, conversation_id as crypto_conversation_id,
+    create_duress_alert as crypto_create_duress_alert,
+    create_duress_alerts as crypto_create_duress_alerts,
+    create_recovery_kit as crypto_create_recovery_kit, crypto_self_test as run_crypto_self_test,
     derive_conversation_key as crypto_derive_conversation_key,
-    derive_database_key as crypto_derive_database_key, derive_master_key as crypto_derive_master_key,
+    derive_database_key as crypto_derive_database_key,
+    derive_master_key as crypto_derive_master_key,
+    derive_master_key_with_params as crypto_derive_master_key_with_params,
+    duress_dry_run as crypto_duress_dry_run,
     generate_decoy_contacts as crypto_generate_decoy_contacts,
     generate_decoy_identity as crypto_generate_decoy_identity,
-    generate_decoy_messages as crypto_generate_decoy_messages, generate_keypair as crypto_generate_keypair,
-    generate_salt as crypto_generate_salt, get_public_key,
-    hash_duress_password as crypto_hash_duress_password, nip44_decrypt_with_key, nip44_encrypt_with_key,
-    randomize_timestamp as crypto_randomize_timestamp, schnorr_sign as crypto_schnorr_sign,
+    generate_decoy_messages as crypto_generate_decoy_messages,
+    generate_keypair as crypto_generate_keypair, generate_salt as crypto_generate_salt,
+    get_public_key, hash_duress_password as crypto_hash_duress_password,
+    nip44_decrypt_legacy_pre_spec_with_key, nip44_decrypt_with_key, nip44_encrypt_with_key,
+    open_recovery_share as crypto_open_recovery_share,
+    randomize_timestamp as crypto_randomize_timestamp, recover_from_kit as crypto_recover_from_kit,
+    reveal_recovery_share as crypto_reveal_recovery_share, schnorr_sign as crypto_schnorr_sign,
     schnorr_verify as crypto_schnorr_verify, secure_destroy_key as crypto_secure_destroy_key,
-    validate_duress_password as crypto_validate_duress_password, DecoyContact, DecoyIdentity,
-    DuressAlertConfig, DuressCheckResult, EncryptedData, KeyPair, NostrEvent, UnsignedEvent,
+    validate_duress_password as crypto_validate_duress_password,
+    verify_keypair as crypto_verify_keypair, verify_release_digest as crypto_verify_release_digest,
+    Argon2Params, CryptoError, DecoyContact, DecoyIdentity, DuressAlertConfig, DuressCheckResult,
+    EncryptedData, KeyPair, NostrEvent, RecoveryKit, SelfTestReport, UnsignedEvent,
 };
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::State;
-
-/// Command result wrapper
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CommandResult<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
-
-impl<T> CommandResult<T> {
-    pub fn ok(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn err(error: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error),
-        }
-    }
-}
+use zeroize::Zeroizing;
 
 /// Key pair response for frontend
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,6 +58,7 @@ pub enum FrontendSecretType {
     MasterKey,
     DatabaseKey,
     ApiToken,
+    KdfSalt,
     Custom(String),
 }
 
@@ -73,6 +69,7 @@ impl From<FrontendSecretType> for SecretType {
             FrontendSecretType::MasterKey => SecretType::MasterKey,
             FrontendSecretType::DatabaseKey => SecretType::DatabaseKey,
             FrontendSecretType::ApiToken => SecretType::ApiToken,
+            FrontendSecretType::KdfSalt => SecretType::KdfSalt,
             FrontendSecretType::Custom(name) => SecretType::Custom(name),
         }
     }
@@ -80,6 +77,7 @@ impl From<FrontendSecretType> for SecretType {
 
 /// Store a secret in the system keyring
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn store_secret(
     state: State<'_, AppState>,
     user: String,
@@ -88,65 +86,73 @@ pub async fn store_secret(
     label: Option<String>,
 ) -> Result<CommandResult<()>, String> {
     let result = state
-        .keyring_manager
-        .store_secret(&user, secret_type.into(), &value, label);
+        .keyring()
+        .and_then(|keyring| keyring.store_secret(&user, secret_type.into(), &value, label));
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Retrieve a secret from the system keyring
+///
+/// The value necessarily crosses to the frontend; the Rust-side copy is held
+/// in a `SecretValue` and zeroized once the response has been serialized.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn retrieve_secret(
     state: State<'_, AppState>,
     user: String,
     secret_type: FrontendSecretType,
-) -> Result<CommandResult<String>, String> {
+) -> Result<CommandResult<SecretValue>, String> {
     let result = state
-        .keyring_manager
-        .retrieve_secret(&user, &secret_type.into());
+        .keyring()
+        .and_then(|keyring| keyring.retrieve_secret_zeroizing(&user, &secret_type.into()));
 
     match result {
-        Ok(stored) => Ok(CommandResult::ok(stored.value)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Ok(value) => Ok(CommandResult::ok(SecretValue::new(value))),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Delete a secret from the system keyring
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn delete_secret(
     state: State<'_, AppState>,
     user: String,
     secret_type: FrontendSecretType,
 ) -> Result<CommandResult<()>, String> {
     let result = state
-        .keyring_manager
-        .delete_secret(&user, &secret_type.into());
+        .keyring()
+        .and_then(|keyring| keyring.delete_secret(&user, &secret_type.into()));
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Check if a secret exists in the keyring
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn has_secret(
     state: State<'_, AppState>,
     user: String,
     secret_type: FrontendSecretType,
 ) -> Result<CommandResult<bool>, String> {
-    let exists = state
-        .keyring_manager
-        .has_secret(&user, &secret_type.into());
-
-    Ok(CommandResult::ok(exists))
+    match state.keyring() {
+        Ok(keyring) => Ok(CommandResult::ok(
+            keyring.has_secret(&user, &secret_type.into()),
+        )),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
 }
 
 /// Generate a new secp256k1 keypair
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn generate_keypair() -> Result<CommandResult<KeyPairResponse>, String> {
     let keypair = crypto_generate_keypair();
 
@@ -160,52 +166,65 @@ pub async fn generate_keypair() -> Result<CommandResult<KeyPairResponse>, String
 
 /// Encrypt a message using NIP-44
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn encrypt_nip44(
     conversation_key_hex: String,
     plaintext: String,
 ) -> Result<CommandResult<String>, String> {
-    let conversation_key = match hex::decode(&conversation_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid conversation key".to_string())),
+    let conversation_key = match decode_flexible(&conversation_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid conversation key: {e}"))),
     };
 
     match nip44_encrypt_with_key(conversation_key, plaintext) {
         Ok(ciphertext) => Ok(CommandResult::ok(ciphertext)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Decrypt a message using NIP-44
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn decrypt_nip44(
     conversation_key_hex: String,
     ciphertext: String,
 ) -> Result<CommandResult<String>, String> {
-    let conversation_key = match hex::decode(&conversation_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid conversation key".to_string())),
+    let conversation_key = match decode_flexible(&conversation_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid conversation key: {e}"))),
     };
 
-    match nip44_decrypt_with_key(conversation_key, ciphertext) {
+    match nip44_decrypt_with_key(conversation_key.clone(), ciphertext.clone()) {
         Ok(plaintext) => Ok(CommandResult::ok(plaintext)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        // Stored payloads from before the spec alignment fail the spec MAC
+        Err(CryptoError::InvalidMac) => {
+            match nip44_decrypt_legacy_pre_spec_with_key(conversation_key, ciphertext) {
+                Ok(plaintext) => {
+                    tracing::warn!("Decrypted a pre-spec NIP-44 payload; re-encrypt it");
+                    Ok(CommandResult::ok(plaintext))
+                }
+                Err(_) => Ok(CommandResult::fail(CryptoError::InvalidMac)),
+            }
+        }
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Derive a NIP-44 conversation key from ECDH
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn derive_conversation_key(
     private_key_hex: String,
     recipient_pubkey_hex: String,
 ) -> Result<CommandResult<String>, String> {
-    let private_key = match hex::decode(&private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     match crypto_derive_conversation_key(private_key, recipient_pubkey_hex) {
         Ok(key) => Ok(CommandResult::ok(hex::encode(&key))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -215,39 +234,232 @@ pub async fn derive_conversation_key(
 
 /// Derive a master key from password using Argon2id
 /// This is the primary key derivation for password-based login
-/// Argon2id params: 64MB memory, 3 iterations, 4 parallelism (~50-200ms)
+/// Argon2id params: 64MB memory, 3 iterations, 4 parallelism (~50-200ms),
+/// or the calibrated `params` stored alongside the salt
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn derive_master_key(
     password: String,
     salt_hex: String,
+    params: Option<Argon2Params>,
 ) -> Result<CommandResult<String>, String> {
     let salt = match hex::decode(&salt_hex) {
         Ok(s) if s.len() >= 16 => s,
-        _ => return Ok(CommandResult::err("Invalid salt (must be at least 16 bytes hex)".to_string())),
+        _ => {
+            return Ok(CommandResult::err(
+                "Invalid salt (must be at least 16 bytes hex)".to_string(),
+            ))
+        }
     };
 
-    match crypto_derive_master_key(password.as_bytes().to_vec(), salt) {
+    let result = match params {
+        Some(params) => {
+            crypto_derive_master_key_with_params(password.as_bytes().to_vec(), salt, params)
+        }
+        None => crypto_derive_master_key(password.as_bytes().to_vec(), salt),
+    };
+    match result {
         Ok(key) => Ok(CommandResult::ok(hex::encode(&key))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
-/// Derive database encryption key from master key using HKDF-SHA256
+/// Tune Argon2id parameters so a derivation takes about `target_ms` here
+///
+/// Runs several trial derivations, so it executes on the blocking pool.
+/// Store the result alongside the user's salt and pass it to `derive_master_key`.
 #[tauri::command]
-pub async fn derive_database_key(
-    master_key_hex: String,
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn calibrate_argon2(target_ms: u32) -> Result<CommandResult<Argon2Params>, String> {
+    match tokio::task::spawn_blocking(move || crypto_calibrate_argon2(target_ms)).await {
+        Ok(Ok(params)) => Ok(CommandResult::ok(params)),
+        Ok(Err(e)) => Ok(CommandResult::fail(e)),
+        Err(e) => Err(format!("Calibration task failed: {e}")),
+    }
+}
+
+/// Store the salt `user`'s master key is derived with
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn store_kdf_salt(
+    state: State<'_, AppState>,
+    user: String,
+    salt_hex: String,
+) -> Result<CommandResult<()>, String> {
+    let salt = match hex::decode(&salt_hex) {
+        Ok(s) => s,
+        Err(_) => return Ok(CommandResult::err("Invalid salt hex".to_string())),
+    };
+
+    let result = state
+        .keyring()
+        .and_then(|keyring| keyring.store_kdf_salt(&user, &salt));
+
+    match result {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Retrieve the salt stored for `user`, to pass to `derive_master_key`
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_kdf_salt(
+    state: State<'_, AppState>,
+    user: String,
+) -> Result<CommandResult<String>, String> {
+    let result = state
+        .keyring()
+        .and_then(|keyring| keyring.get_kdf_salt(&user));
+
+    match result {
+        Ok(salt) => Ok(CommandResult::ok(hex::encode(salt))),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Retrieve `user`'s salt, generating and storing one on first setup
+///
+/// An existing salt is never replaced, so every later login derives the
+/// same master key.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_or_create_kdf_salt(
+    state: State<'_, AppState>,
+    user: String,
 ) -> Result<CommandResult<String>, String> {
-    let master_key = match hex::decode(&master_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid master key".to_string())),
+    let result = state
+        .keyring()
+        .and_then(|keyring| keyring.get_or_create_kdf_salt(&user));
+
+    match result {
+        Ok(salt) => Ok(CommandResult::ok(hex::encode(salt))),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Run the crypto self-test and return a per-check pass/fail report
+///
+/// Exercises every primitive with a quick round-trip, including an Argon2id
+/// derivation, so it executes on the blocking pool.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn crypto_self_test() -> Result<CommandResult<SelfTestReport>, String> {
+    match tokio::task::spawn_blocking(run_crypto_self_test).await {
+        Ok(report) => Ok(CommandResult::ok(report)),
+        Err(e) => Err(format!("Self-test task failed: {e}")),
+    }
+}
+
+/// Derive database encryption key from master key using HKDF-SHA256
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn derive_database_key(master_key_hex: String) -> Result<CommandResult<String>, String> {
+    let master_key = match decode_flexible(&master_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid master key: {e}"))),
     };
 
     match crypto_derive_database_key(master_key) {
         Ok(key) => Ok(CommandResult::ok(hex::encode(&key))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
+// =============================================================================
+// Social Recovery
+// =============================================================================
+
+/// Split the master key into a `threshold`-of-`total_shares` recovery kit
+///
+/// Returns printable share blobs; nothing is stored. `recipient_pubkeys`
+/// holds one optional contact per share to NIP-44 encrypt that share to.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn create_recovery_kit(
+    master_key_hex: String,
+    threshold: u32,
+    total_shares: u32,
+    recipient_pubkeys: Option<Vec<Option<String>>>,
+) -> Result<CommandResult<RecoveryKit>, String> {
+    let master_key = match decode_flexible(&master_key_hex, Some(32)) {
+        Ok(k) => Zeroizing::new(k),
+        Err(e) => return Ok(CommandResult::err(format!("Invalid master key: {e}"))),
+    };
+
+    match crypto_create_recovery_kit(
+        master_key.to_vec(),
+        threshold,
+        total_shares,
+        recipient_pubkeys.unwrap_or_default(),
+    ) {
+        Ok(kit) => Ok(CommandResult::ok(kit)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Restore the master key from recovery kit share blobs
+///
+/// `private_key_hex` opens shares that were encrypted to this identity;
+/// clear shares need no key. Fails unless the shares reconstruct the exact
+/// key the kit was made from.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn recover_from_kit(
+    share_blobs: Vec<String>,
+    private_key_hex: Option<String>,
+) -> Result<CommandResult<String>, String> {
+    let private_key = match decode_share_key(private_key_hex) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    let shares = match share_blobs
+        .into_iter()
+        .map(|blob| crypto_open_recovery_share(blob, private_key.as_ref().map(|k| k.to_vec())))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(shares) => shares,
+        Err(e) => return Ok(CommandResult::fail(e)),
+    };
+
+    match crypto_recover_from_kit(shares) {
+        Ok(key) => Ok(CommandResult::ok(hex::encode(&*Zeroizing::new(key)))),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Open a recovery share encrypted to this identity and return it as a
+/// clear share blob
+///
+/// This is what a contact runs to hand their share back to the kit's owner,
+/// who can then recover without the contact's key. Clear blobs come back
+/// unchanged.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn open_recovery_share(
+    share_blob: String,
+    private_key_hex: Option<String>,
+) -> Result<CommandResult<String>, String> {
+    let private_key = match decode_share_key(private_key_hex) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(e)),
+    };
+
+    match crypto_reveal_recovery_share(share_blob, private_key.as_ref().map(|k| k.to_vec())) {
+        Ok(blob) => Ok(CommandResult::ok(blob)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Decode the optional private key that opens encrypted recovery shares
+fn decode_share_key(private_key_hex: Option<String>) -> Result<Option<Zeroizing<Vec<u8>>>, String> {
+    private_key_hex
+        .map(|k| decode_flexible(&k, Some(32)).map(Zeroizing::new))
+        .transpose()
+        .map_err(|e| format!("Invalid private key: {e}"))
+}
+
 // =============================================================================
 // AES-256-GCM Storage Encryption
 // =============================================================================
@@ -261,13 +473,14 @@ pub struct AesEncryptResponse {
 
 /// Encrypt data using AES-256-GCM for local storage
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn aes_encrypt(
     key_hex: String,
     plaintext_hex: String,
 ) -> Result<CommandResult<AesEncryptResponse>, String> {
-    let key = match hex::decode(&key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid key (must be 32 bytes)".to_string())),
+    let key = match decode_flexible(&key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid key: {e}"))),
     };
 
     let plaintext = match hex::decode(&plaintext_hex) {
@@ -280,37 +493,38 @@ pub async fn aes_encrypt(
             ciphertext_hex: hex::encode(&encrypted.ciphertext),
             nonce_hex: hex::encode(&encrypted.nonce),
         })),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Decrypt data using AES-256-GCM
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn aes_decrypt(
     key_hex: String,
     ciphertext_hex: String,
     nonce_hex: String,
 ) -> Result<CommandResult<String>, String> {
-    let key = match hex::decode(&key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid key (must be 32 bytes)".to_string())),
+    let key = match decode_flexible(&key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid key: {e}"))),
     };
 
-    let ciphertext = match hex::decode(&ciphertext_hex) {
+    let ciphertext = match decode_flexible(&ciphertext_hex, None) {
         Ok(c) => c,
-        Err(_) => return Ok(CommandResult::err("Invalid ciphertext hex".to_string())),
+        Err(e) => return Ok(CommandResult::err(format!("Invalid ciphertext: {e}"))),
     };
 
-    let nonce = match hex::decode(&nonce_hex) {
-        Ok(n) if n.len() == 12 => n,
-        _ => return Ok(CommandResult::err("Invalid nonce (must be 12 bytes)".to_string())),
+    let nonce = match decode_flexible(&nonce_hex, Some(12)) {
+        Ok(n) => n,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid nonce: {e}"))),
     };
 
     let encrypted = EncryptedData { ciphertext, nonce };
 
     match crypto_aes_decrypt(key, encrypted) {
         Ok(plaintext) => Ok(CommandResult::ok(hex::encode(&plaintext))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -320,6 +534,7 @@ pub async fn aes_decrypt(
 
 /// Sign a message using BIP-340 Schnorr signature
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn schnorr_sign(
     message_hex: String,
     private_key_hex: String,
@@ -329,19 +544,20 @@ pub async fn schnorr_sign(
         Err(_) => return Ok(CommandResult::err("Invalid message hex".to_string())),
     };
 
-    let private_key = match hex::decode(&private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     match crypto_schnorr_sign(message, private_key) {
         Ok(sig) => Ok(CommandResult::ok(hex::encode(&sig))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Verify a BIP-340 Schnorr signature
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn schnorr_verify(
     message_hex: String,
     signature_hex: String,
@@ -352,30 +568,60 @@ pub async fn schnorr_verify(
         Err(_) => return Ok(CommandResult::err("Invalid message hex".to_string())),
     };
 
-    let signature = match hex::decode(&signature_hex) {
-        Ok(s) if s.len() == 64 => s,
-        _ => return Ok(CommandResult::err("Invalid signature (must be 64 bytes)".to_string())),
+    let signature = match decode_flexible(&signature_hex, Some(64)) {
+        Ok(s) => s,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid signature: {e}"))),
     };
 
-    let public_key = match hex::decode(&public_key_hex) {
-        Ok(p) if p.len() == 32 => p,
-        _ => return Ok(CommandResult::err("Invalid public key (must be 32 bytes)".to_string())),
+    let public_key = match decode_flexible(&public_key_hex, Some(32)) {
+        Ok(p) => p,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid public key: {e}"))),
     };
 
     match crypto_schnorr_verify(message, signature, public_key) {
         Ok(valid) => Ok(CommandResult::ok(valid)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// SHA-256 of a file, hashed in a streaming pass
+fn hash_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+/// Verify a downloaded update against the pinned release signing key
+///
+/// The artifact is read and hashed here from `artifact_path`, so installers
+/// never cross IPC. The updater must not apply it unless this returns `true`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn verify_release_artifact(
+    artifact_path: String,
+    signature_hex: String,
+) -> Result<CommandResult<bool>, String> {
+    let digest =
+        match tokio::task::spawn_blocking(move || hash_file(Path::new(&artifact_path))).await {
+            Ok(Ok(digest)) => digest,
+            Ok(Err(e)) => return Ok(CommandResult::err(format!("Failed to read artifact: {e}"))),
+            Err(e) => return Err(format!("Artifact hashing task failed: {e}")),
+        };
+
+    match crypto_verify_release_digest(&digest, &signature_hex) {
+        Ok(valid) => Ok(CommandResult::ok(valid)),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Compute Nostr event ID (SHA-256 hash of serialized event)
 #[tauri::command]
-pub async fn compute_event_id(
-    event: UnsignedEvent,
-) -> Result<CommandResult<String>, String> {
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn compute_event_id(event: UnsignedEvent) -> Result<CommandResult<String>, String> {
     match crypto_compute_event_id(event) {
         Ok(id) => Ok(CommandResult::ok(id)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -385,6 +631,7 @@ pub async fn compute_event_id(
 
 /// Hash a duress password using Argon2id
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn hash_duress_password(
     password: String,
     salt_hex: String,
@@ -396,7 +643,7 @@ pub async fn hash_duress_password(
 
     match crypto_hash_duress_password(password.as_bytes().to_vec(), salt) {
         Ok(hash) => Ok(CommandResult::ok(hex::encode(&hash))),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -410,6 +657,7 @@ pub struct DuressCheckResponse {
 /// Check if entered password is the duress password
 /// Returns whether duress mode should be activated
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn check_duress_password(
     entered_password: String,
     salt_hex: String,
@@ -431,17 +679,23 @@ pub async fn check_duress_password(
         Err(_) => return Ok(CommandResult::err("Invalid normal hash".to_string())),
     };
 
-    match crypto_check_duress_password(entered_password.as_bytes().to_vec(), salt, duress_hash, normal_hash) {
+    match crypto_check_duress_password(
+        entered_password.as_bytes().to_vec(),
+        salt,
+        duress_hash,
+        normal_hash,
+    ) {
         Ok(result) => Ok(CommandResult::ok(DuressCheckResponse {
             is_duress: result.is_duress,
             password_valid: result.password_valid,
         })),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Validate that duress password is sufficiently different from normal password
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn validate_duress_password(
     duress_password: String,
     normal_password: String,
@@ -451,7 +705,7 @@ pub async fn validate_duress_password(
         normal_password.as_bytes().to_vec(),
     ) {
         Ok(valid) => Ok(CommandResult::ok(valid)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -467,6 +721,7 @@ pub struct DecoyIdentityResponse {
 
 /// Generate a decoy identity for duress mode
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn generate_decoy_identity() -> Result<CommandResult<DecoyIdentityResponse>, String> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -493,6 +748,7 @@ pub struct DecoyContactResponse {
 
 /// Generate fake contacts for duress mode
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn generate_decoy_contacts(
     count: u32,
 ) -> Result<CommandResult<Vec<DecoyContactResponse>>, String> {
@@ -511,6 +767,7 @@ pub async fn generate_decoy_contacts(
 
 /// Generate fake messages for duress mode
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn generate_decoy_messages() -> Result<CommandResult<Vec<String>>, String> {
     let messages = crypto_generate_decoy_messages();
     Ok(CommandResult::ok(messages))
@@ -524,16 +781,78 @@ pub struct FrontendDuressAlertConfig {
     pub custom_message: Option<String>,
 }
 
+/// Duress mode response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuressModeResponse {
+    pub decoy_pubkey: String,
+    /// Duress alerts accepted by at least one relay
+    pub alerts_sent: usize,
+}
+
+/// Switch every subsystem to a decoy identity after a duress unlock
+///
+/// Call when `check_duress_password` reports `is_duress`. BLE and the mesh
+/// take on the decoy identity, the real key is wiped from memory, relay
+/// subscriptions stop forwarding, the real database is closed for good and
+/// keyring commands fail with `keyring_duress_mode`. With
+/// `alert_config`, silent duress alerts signed by the real identity go out
+/// to the write relays first. The identity cannot change again until the
+/// app restarts.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn enter_duress_mode(
+    state: State<'_, AppState>,
+    db: State<'_, Database>,
+    decoy_private_key_hex: String,
+    alert_config: Option<FrontendDuressAlertConfig>,
+) -> Result<CommandResult<DuressModeResponse>, String> {
+    let decoy_private_key = match decode_flexible(&decoy_private_key_hex, Some(32)) {
+        Ok(k) => Zeroizing::new(k),
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
+    };
+    let alerts = alert_config.map(|config| DuressAlertConfig {
+        trusted_contact_pubkeys: config.trusted_contact_pubkeys,
+        include_location: config.include_location,
+        custom_message: config.custom_message,
+    });
+
+    let entered = match state.enter_duress_mode(decoy_private_key.to_vec(), alerts, Some(&db)) {
+        Ok(entered) => entered,
+        Err(e) => return Ok(CommandResult::fail(e)),
+    };
+
+    let relays: Vec<_> = state
+        .nostr_relays
+        .read()
+        .values()
+        .filter(|relay| relay.role().can_write())
+        .cloned()
+        .collect();
+    let mut alerts_sent = 0;
+    for alert in &entered.alerts {
+        let results = publish_to_relays(&relays, alert, PUBLISH_ACK_TIMEOUT).await;
+        if results.iter().any(|r| r.accepted) {
+            alerts_sent += 1;
+        }
+    }
+
+    Ok(CommandResult::ok(DuressModeResponse {
+        decoy_pubkey: entered.decoy_pubkey,
+        alerts_sent,
+    }))
+}
+
 /// Create silent duress alert to send to trusted contacts
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn create_duress_alert(
     sender_private_key_hex: String,
     recipient_pubkey: String,
     custom_message: Option<String>,
 ) -> Result<CommandResult<NostrEvent>, String> {
-    let private_key = match hex::decode(&sender_private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&sender_private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     let now = SystemTime::now()
@@ -543,19 +862,20 @@ pub async fn create_duress_alert(
 
     match crypto_create_duress_alert(private_key, recipient_pubkey, now, custom_message) {
         Ok(event) => Ok(CommandResult::ok(event)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Create multiple duress alerts to trusted contacts
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn create_duress_alerts(
     sender_private_key_hex: String,
     config: FrontendDuressAlertConfig,
 ) -> Result<CommandResult<Vec<NostrEvent>>, String> {
-    let private_key = match hex::decode(&sender_private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&sender_private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     let now = SystemTime::now()
@@ -571,15 +891,133 @@ pub async fn create_duress_alerts(
 
     match crypto_create_duress_alerts(private_key, crypto_config, now) {
         Ok(events) => Ok(CommandResult::ok(events)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Key the frontend would hand to `secure_destroy_key` on real activation
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrontendWipeTarget {
+    pub label: String,
+    pub key_hex: String,
+}
+
+/// Alert that would be sent, as reported by a duress dry run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuressAlertPreviewResponse {
+    pub recipient_pubkey: String,
+    pub message: String,
+    pub kind: i32,
+    pub include_location: bool,
+}
+
+/// Key that would be wiped, as reported by a duress dry run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuressWipePreviewResponse {
+    pub label: String,
+    pub key_len: usize,
+    pub passes: u32,
+}
+
+/// Duress dry-run response
+///
+/// Decoy private key is omitted: the rehearsal only shows what the decoy
+/// would look like.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DuressDryRunResponse {
+    pub dry_run: bool,
+    pub decoy_public_key: String,
+    pub decoy_display_name: String,
+    pub decoy_about: String,
+    pub decoy_contacts: Vec<DecoyContactResponse>,
+    pub decoy_messages: Vec<String>,
+    pub alerts: Vec<DuressAlertPreviewResponse>,
+    pub wiped_keys: Vec<DuressWipePreviewResponse>,
+}
+
+/// Rehearse the duress flow without sending alerts or destroying keys
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn duress_dry_run(
+    sender_private_key_hex: String,
+    config: FrontendDuressAlertConfig,
+    wipe_targets: Vec<FrontendWipeTarget>,
+) -> Result<CommandResult<DuressDryRunResponse>, String> {
+    let private_key = match decode_flexible(&sender_private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
+    };
+
+    let mut keys = Vec::with_capacity(wipe_targets.len());
+    for target in &wipe_targets {
+        match hex::decode(&target.key_hex) {
+            Ok(k) => keys.push((target.label.as_str(), k)),
+            Err(_) => {
+                let message = format!("Invalid key hex: {}", target.label);
+                return Ok(CommandResult::fail(CommandError::new(
+                    "crypto_invalid_hex",
+                    message,
+                    false,
+                )));
+            }
+        }
+    }
+    let key_refs: Vec<(&str, &[u8])> = keys.iter().map(|(l, k)| (*l, k.as_slice())).collect();
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+
+    let crypto_config = DuressAlertConfig {
+        trusted_contact_pubkeys: config.trusted_contact_pubkeys,
+        include_location: config.include_location,
+        custom_message: config.custom_message,
+    };
+
+    match crypto_duress_dry_run(&private_key, &crypto_config, &key_refs, now) {
+        Ok(result) => Ok(CommandResult::ok(DuressDryRunResponse {
+            dry_run: result.dry_run,
+            decoy_public_key: result.decoy_identity.keypair.public_key,
+            decoy_display_name: result.decoy_identity.display_name,
+            decoy_about: result.decoy_identity.about,
+            decoy_contacts: result
+                .decoy_contacts
+                .into_iter()
+                .map(|c| DecoyContactResponse {
+                    pubkey: c.pubkey,
+                    display_name: c.display_name,
+                })
+                .collect(),
+            decoy_messages: result.decoy_messages,
+            alerts: result
+                .alerts
+                .into_iter()
+                .map(|a| DuressAlertPreviewResponse {
+                    recipient_pubkey: a.recipient_pubkey,
+                    message: a.message,
+                    kind: a.kind,
+                    include_location: a.include_location,
+                })
+                .collect(),
+            wiped_keys: result
+                .wiped_keys
+                .into_iter()
+                .map(|w| DuressWipePreviewResponse {
+                    label: w.label,
+                    key_len: w.key_len,
+                    passes: w.passes,
+                })
+                .collect(),
+        })),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Securely destroy a key by overwriting memory
 #[tauri::command]
-pub async fn secure_destroy_key(
-    key_hex: String,
-) -> Result<CommandResult<()>, String> {
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn secure_destroy_key(key_hex: String) -> Result<CommandResult<()>, String> {
     let mut key = match hex::decode(&key_hex) {
         Ok(k) => k,
         Err(_) => return Ok(CommandResult::err("Invalid key hex".to_string())),
@@ -587,7 +1025,7 @@ pub async fn secure_destroy_key(
 
     match crypto_secure_destroy_key(key) {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
@@ -597,15 +1035,15 @@ pub async fn secure_destroy_key(
 
 /// Generate a cryptographically secure random salt
 #[tauri::command]
-pub async fn generate_salt(
-    length: u32,
-) -> Result<CommandResult<String>, String> {
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn generate_salt(length: u32) -> Result<CommandResult<String>, String> {
     let salt = crypto_generate_salt(length);
     Ok(CommandResult::ok(hex::encode(&salt)))
 }
 
 /// Randomize a timestamp for privacy (±range_seconds)
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn randomize_timestamp(
     timestamp: i64,
     range_seconds: u32,
@@ -616,16 +1054,110 @@ pub async fn randomize_timestamp(
 
 /// Get public key from private key
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn get_public_key_from_private(
     private_key_hex: String,
 ) -> Result<CommandResult<String>, String> {
-    let private_key = match hex::decode(&private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     match get_public_key(private_key) {
         Ok(pubkey) => Ok(CommandResult::ok(pubkey)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Check that an imported private key matches the public key it is expected
+/// to belong to
+///
+/// Returns false on a mismatch; a malformed key is an error.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn verify_keypair(
+    private_key_hex: String,
+    expected_public_key_hex: String,
+) -> Result<CommandResult<bool>, String> {
+    match crypto_verify_keypair(private_key_hex, expected_public_key_hex) {
+        Ok(matches) => Ok(CommandResult::ok(matches)),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
+
+/// Derive the conversation id shared by two participants
+///
+/// Argument order does not matter, so both sides key their local
+/// conversation records identically.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn conversation_id(
+    pubkey_a: String,
+    pubkey_b: String,
+) -> Result<CommandResult<String>, String> {
+    Ok(CommandResult::ok(crypto_conversation_id(
+        pubkey_a, pubkey_b,
+    )))
+}
+
+/// Make a private key the active identity for BLE and the mesh
+///
+/// Returns the identity's public key.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn set_active_identity(
+    state: State<'_, AppState>,
+    private_key_hex: String,
+) -> Result<CommandResult<String>, String> {
+    let private_key = match decode_flexible(&private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
+    };
+
+    match state.set_active_identity(private_key) {
+        Ok(pubkey) => Ok(CommandResult::ok(pubkey)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Replace a compromised identity key with a freshly generated one
+///
+/// The new key is stored in the keyring under `user`, replacing the old one,
+/// and becomes the active identity; the old key is securely destroyed.
+/// Returns the new public key so the UI can publish a migration notice. If
+/// the keyring write fails, nothing changes.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn rotate_identity_key(
+    state: State<'_, AppState>,
+    user: String,
+) -> Result<CommandResult<String>, String> {
+    let keyring = &state.keyring_manager;
+    match state.rotate_identity_key(|key| keyring.store_nostr_key(&user, key, None)) {
+        Ok(pubkey) => Ok(CommandResult::ok(pubkey)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Wipe the active identity from BLE and the mesh
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn clear_active_identity(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<()>, String> {
+    state.clear_active_identity();
+    Ok(CommandResult::ok(()))
+}
+
+/// Drop session caches that are safe to rebuild without logging out
+///
+/// Clears cached conversation keys, mesh deduplication state and
+/// commitments learned from advertisements. The identity, keyring and
+/// database are untouched. Returns how much of each was dropped.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn clear_ephemeral_state(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<EphemeralStateCleared>, String> {
+    Ok(CommandResult::ok(state.clear_ephemeral_state()))
+}
@@ -10,13 +10,52 @@
 //! ensuring the frontend can pass `{ groupId: "abc" }` and it maps to `group_id`.
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use rusqlite::types::ValueRef;
-use serde::Deserialize;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
+use zeroize::Zeroizing;
+
+use super::encoding::decode_flexible;
+use super::error::CommandError;
+use crate::contacts;
+use crate::crypto::keyring::SecretValue;
+use crate::db::field_encryption::{
+    FieldCipher, FieldEncryptionPolicy, ENCRYPTED_COLUMN_ERROR, SEALED_PREFIX,
+};
+use crate::db::observe::DB_OBSERVE_CHANNEL;
+use crate::db::pool::{CipherInfo, CipherSettings};
+use crate::db::secure_kv;
+use crate::db::{Database, CONFLICT_ERROR};
+use crate::AppState;
+
+/// Column description returned by db_table_info
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnInfo {
+    /// Column name (camelCase)
+    pub name: String,
+    /// Declared SQLite type (e.g. "TEXT", "INTEGER"), empty if undeclared
+    #[serde(rename = "type")]
+    pub column_type: String,
+    /// Whether the column is NOT NULL
+    pub notnull: bool,
+    /// 1-based position in the primary key, 0 if not part of it
+    pub pk: u32,
+}
 
-use crate::db::Database;
+/// One group returned by db_count_grouped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupCount {
+    /// Grouped column name (camelCase)
+    pub column: String,
+    /// Value of the grouped column shared by this group
+    pub value: Value,
+    pub count: u32,
+}
 
 /// Query filter for db_query command
 #[derive(Debug, Deserialize)]
@@ -79,21 +118,33 @@ fn primary_key_for(table: &str) -> &'static str {
     match table {
         "identities" => "public_key",
         "username_settings" | "user_presence" => "pubkey",
-        "cache_metadata" => "key",
+        "cache_metadata" | "secure_kv" => "key",
         _ => "id",
     }
 }
 
+/// Error prefix for rejected table names (mapped to invalid_input)
+pub(crate) const INVALID_TABLE_ERROR: &str = "Invalid table name";
+
+/// Error prefix for rejected column names (mapped to invalid_input)
+pub(crate) const INVALID_COLUMN_ERROR: &str = "Invalid column name";
+
+/// Error prefix for records that aren't JSON objects (mapped to invalid_input)
+pub(crate) const INVALID_RECORD_ERROR: &str = "Record must be a JSON object";
+
+/// Error prefix for rejected JSON queries (mapped to invalid_input)
+pub(crate) const INVALID_JSON_QUERY_ERROR: &str = "Invalid JSON query";
+
 /// Validate table name to prevent SQL injection
 fn validate_table_name(table: &str) -> Result<(), String> {
     if table.is_empty() {
-        return Err("Table name cannot be empty".to_string());
+        return Err(format!("{INVALID_TABLE_ERROR}: name cannot be empty"));
     }
     if !table.chars().next().unwrap().is_alphabetic() && table.chars().next().unwrap() != '_' {
-        return Err(format!("Invalid table name: {table}"));
+        return Err(format!("{INVALID_TABLE_ERROR}: {table}"));
     }
     if !table.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Err(format!("Invalid table name: {table}"));
+        return Err(format!("{INVALID_TABLE_ERROR}: {table}"));
     }
     Ok(())
 }
@@ -101,7 +152,7 @@ fn validate_table_name(table: &str) -> Result<(), String> {
 /// Validate column name to prevent SQL injection
 fn validate_column_name(col: &str) -> Result<(), String> {
     if col.is_empty() || !col.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        return Err(format!("Invalid column name: {col}"));
+        return Err(format!("{INVALID_COLUMN_ERROR}: {col}"));
     }
     Ok(())
 }
@@ -177,197 +228,614 @@ fn get_column_names(stmt: &rusqlite::Statement<'_>) -> Vec<String> {
         .collect()
 }
 
-// ── Tauri Commands ────────────────────────────────────────────────────────────
-
-/// Open the database with an encryption key
-#[tauri::command]
-pub async fn db_open(state: State<'_, Database>, key: String) -> Result<(), String> {
-    state.open(&key)
+/// INSERT OR REPLACE a snake_case record into an already validated table
+fn upsert_record(
+    conn: &rusqlite::Connection,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+) -> Result<(), String> {
+    write_record(conn, "INSERT OR REPLACE", table, snake_obj).map(|_| ())
 }
 
-/// Close the database
-#[tauri::command]
-pub async fn db_close(state: State<'_, Database>) -> Result<(), String> {
-    state.close();
-    Ok(())
+/// Write a snake_case record with `insert` (e.g. `INSERT OR IGNORE`),
+/// returning the number of rows changed
+fn write_record(
+    conn: &rusqlite::Connection,
+    insert: &str,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+) -> Result<usize, String> {
+    let columns: Vec<String> = snake_obj.keys().cloned().collect();
+
+    for col in &columns {
+        validate_column_name(col)?;
+    }
+
+    let col_list = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholder_list = columns
+        .iter()
+        .enumerate()
+        .map(|(i, _)| format!("?{}", i + 1))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = format!("{insert} INTO \"{table}\" ({col_list}) VALUES ({placeholder_list})");
+
+    let params: Vec<Box<dyn rusqlite::types::ToSql>> =
+        columns.iter().map(|c| json_to_sql(&snake_obj[c])).collect();
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    conn.execute(&sql, param_refs.as_slice())
+        .map_err(|e| format!("db_put failed: {e}"))
 }
 
-/// Check if database is open
-#[tauri::command]
-pub async fn db_is_open(state: State<'_, Database>) -> Result<bool, String> {
-    Ok(state.is_open())
+/// Upsert a record and read the written row back (camelCase keys)
+///
+/// The row is located by the table's primary key, or by `rowid` when the
+/// record leaves the key for SQLite to assign.
+fn upsert_returning(
+    conn: &rusqlite::Connection,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+) -> Result<Value, String> {
+    put_record(conn, table, snake_obj)?;
+
+    let pk_col = primary_key_for(table);
+    let (sql, key) = match snake_obj.get(pk_col) {
+        Some(key) if !key.is_null() => (
+            format!("SELECT * FROM \"{table}\" WHERE \"{pk_col}\" = ?1"),
+            json_to_sql(key),
+        ),
+        _ => (
+            format!("SELECT * FROM \"{table}\" WHERE rowid = ?1"),
+            Box::new(conn.last_insert_rowid()) as Box<dyn rusqlite::types::ToSql>,
+        ),
+    };
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+    let column_names = get_column_names(&stmt);
+
+    let mut rows = stmt
+        .query([key.as_ref()])
+        .map_err(|e| format!("Query failed: {e}"))?;
+
+    match rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+        Some(row) => {
+            row_to_json(row, &column_names).map_err(|e| format!("Row conversion failed: {e}"))
+        }
+        None => Err(format!(
+            "db_put_returning failed: written row not found in {table}"
+        )),
+    }
 }
 
-/// Insert or replace a record (upsert)
-#[tauri::command]
-pub async fn db_put(
-    state: State<'_, Database>,
-    table: String,
-    record: Value,
-) -> Result<(), String> {
-    validate_table_name(&table)?;
+/// Split a JSON path like `tags` or `metadata.labels[0]` into the
+/// snake_case column and an SQLite JSON path (`$`, `$.labels[0]`)
+fn parse_json_path(json_path: &str) -> Result<(String, String), String> {
+    let invalid = || format!("{INVALID_JSON_QUERY_ERROR} path: {json_path}");
+    let mut segments = json_path.split('.');
+
+    let column = to_snake_case(segments.next().unwrap_or_default());
+    validate_column_name(&column).map_err(|_| invalid())?;
+
+    let mut path = String::from("$");
+    for segment in segments {
+        let (key, indexes) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(invalid());
+        }
+        path.push('.');
+        path.push_str(key);
 
-    let obj = record
-        .as_object()
-        .ok_or_else(|| "Record must be a JSON object".to_string())?;
+        let mut rest = indexes;
+        while !rest.is_empty() {
+            if !rest.starts_with('[') {
+                return Err(invalid());
+            }
+            let close = rest.find(']').ok_or_else(invalid)?;
+            let index = &rest[1..close];
+            if index.is_empty() || !index.chars().all(|c| c.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            path.push_str(&rest[..=close]);
+            rest = &rest[close + 1..];
+        }
+    }
 
-    let snake_obj = keys_to_snake_case(obj);
+    Ok((column, path))
+}
 
-    state.with_connection(|conn| {
-        let columns: Vec<String> = snake_obj.keys().cloned().collect();
+/// Records whose JSON at `json_path` equals `value` or, for an array,
+/// contains it
+///
+/// Rows whose column is not valid JSON are skipped rather than failing the
+/// whole query. The column must not be encrypted, and sealed columns of the
+/// matching rows are opened.
+fn query_json(
+    conn: &rusqlite::Connection,
+    cipher: &FieldCipher,
+    table: &str,
+    json_path: &str,
+    value: &Value,
+) -> Result<Vec<Value>, String> {
+    if value.is_array() || value.is_object() {
+        return Err(format!(
+            "{INVALID_JSON_QUERY_ERROR} value: cannot be an array or object"
+        ));
+    }
+    let (column, path) = parse_json_path(json_path)?;
+    check_queryable(cipher, table, &column)?;
+
+    // json_valid/json_each come from JSON1, built in since SQLite 3.38 but
+    // optional before that
+    conn.query_row("SELECT json_valid('[]')", [], |_| Ok(()))
+        .map_err(|e| format!("JSON queries need SQLite's JSON1 extension: {e}"))?;
+
+    // CASE guarantees json_each never sees malformed JSON
+    let sql = format!(
+        "SELECT * FROM \"{table}\" AS t WHERE CASE WHEN json_valid(t.\"{column}\") THEN \
+         EXISTS (SELECT 1 FROM json_each(t.\"{column}\", ?1) AS j WHERE j.value = ?2) \
+         ELSE 0 END"
+    );
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+    let column_names = get_column_names(&stmt);
+
+    let value = json_to_sql(value);
+    let mut rows = stmt
+        .query(rusqlite::params![path, value])
+        .map_err(|e| format!("Query failed: {e}"))?;
+
+    let mut results = Vec::new();
+    while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+        results.push(
+            row_to_json(row, &column_names).map_err(|e| format!("Row conversion failed: {e}"))?,
+        );
+    }
+    open_rows(cipher, table, &mut results)?;
+    Ok(results)
+}
 
-        for col in &columns {
-            validate_column_name(col)?;
-        }
+/// Bound parameters per statement, kept at SQLite's historical default limit
+const MAX_SQL_PARAMS: usize = 999;
+
+/// Fetch records by primary key, `chunk_size` keys per query
+///
+/// Rows come back in the order of their first key in `keys`; missing keys
+/// are omitted.
+fn get_many(
+    conn: &rusqlite::Connection,
+    table: &str,
+    keys: &[String],
+    chunk_size: usize,
+) -> Result<Vec<Value>, String> {
+    let pk_col = primary_key_for(table);
+    let pk_field = to_camel_case(pk_col);
+    let mut found: HashMap<String, Value> = HashMap::with_capacity(keys.len());
 
-        let col_list = columns
-            .iter()
-            .map(|c| format!("\"{}\"", c))
-            .collect::<Vec<_>>()
-            .join(", ");
-        let placeholder_list = columns
-            .iter()
-            .enumerate()
-            .map(|(i, _)| format!("?{}", i + 1))
+    for chunk in keys.chunks(chunk_size.max(1)) {
+        let placeholders = (1..=chunk.len())
+            .map(|i| format!("?{i}"))
             .collect::<Vec<_>>()
             .join(", ");
+        let sql = format!("SELECT * FROM \"{table}\" WHERE \"{pk_col}\" IN ({placeholders})");
 
-        let sql = format!(
-            "INSERT OR REPLACE INTO \"{table}\" ({col_list}) VALUES ({placeholder_list})"
-        );
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("Prepare failed: {e}"))?;
+        let column_names = get_column_names(&stmt);
 
-        let params: Vec<Box<dyn rusqlite::types::ToSql>> = columns
-            .iter()
-            .map(|c| json_to_sql(&snake_obj[c]))
-            .collect();
+        let mut rows = stmt
+            .query(rusqlite::params_from_iter(chunk))
+            .map_err(|e| format!("Query failed: {e}"))?;
+
+        while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+            let json = row_to_json(row, &column_names)
+                .map_err(|e| format!("Row conversion failed: {e}"))?;
+            let key = match &json[&pk_field] {
+                Value::String(key) => key.clone(),
+                other => other.to_string(),
+            };
+            found.insert(key, json);
+        }
+    }
+
+    Ok(keys.iter().filter_map(|key| found.remove(key)).collect())
+}
+
+/// Revision column used for optimistic concurrency (see migration 004)
+const REV_COLUMN: &str = "rev";
+
+/// Error prefix for a versioned write to a table without a rev column
+pub(crate) const UNVERSIONED_TABLE_ERROR: &str = "Table has no rev column";
+
+/// Upsert a record only if its stored revision matches `expected_rev`
+///
+/// `expected_rev` is `None` for a record that must not exist yet. On success
+/// the record is written with the next revision, which is returned. A
+/// mismatch fails with a `Conflict:` error (`db_conflict`) and writes nothing.
+fn put_checked(
+    conn: &rusqlite::Connection,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+    expected_rev: Option<i64>,
+) -> Result<i64, String> {
+    if !has_rev_column(conn, table)? {
+        return Err(format!("{UNVERSIONED_TABLE_ERROR}: {table}"));
+    }
 
-        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-            params.iter().map(|p| p.as_ref()).collect();
+    let pk_col = primary_key_for(table);
+    let key = snake_obj
+        .get(pk_col)
+        .filter(|key| !key.is_null())
+        .ok_or_else(|| format!("Record must include primary key {pk_col}"))?;
+
+    let stored_rev: Option<i64> = conn
+        .query_row(
+            &format!("SELECT \"{REV_COLUMN}\" FROM \"{table}\" WHERE \"{pk_col}\" = ?1"),
+            [json_to_sql(key).as_ref()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query failed: {e}"))?;
+
+    if stored_rev != expected_rev {
+        let describe = |rev: Option<i64>| match rev {
+            Some(rev) => format!("rev {rev}"),
+            None => "absent".to_string(),
+        };
+        return Err(format!(
+            "{CONFLICT_ERROR} {table} record {key} is {}, expected {}",
+            describe(stored_rev),
+            describe(expected_rev)
+        ));
+    }
 
-        conn.execute(&sql, param_refs.as_slice())
-            .map_err(|e| format!("db_put failed: {e}"))?;
+    let next_rev = expected_rev.unwrap_or(0) + 1;
+    let mut record = snake_obj.clone();
+    record.insert(REV_COLUMN.to_string(), Value::from(next_rev));
+    upsert_record(conn, table, &record)?;
 
-        Ok(())
-    })
+    Ok(next_rev)
 }
 
-/// Get a single record by primary key
-#[tauri::command]
-pub async fn db_get(
-    state: State<'_, Database>,
-    table: String,
-    key: String,
-) -> Result<Option<Value>, String> {
-    validate_table_name(&table)?;
+/// Whether `table` keeps a `rev` column for optimistic concurrency
+fn has_rev_column(conn: &rusqlite::Connection, table: &str) -> Result<bool, String> {
+    Ok(table_info(conn, table)?
+        .iter()
+        .any(|c| c.name == REV_COLUMN))
+}
 
-    let pk_col = primary_key_for(&table);
+/// The rev an unchecked write of `snake_obj` stores
+///
+/// The stored rev is carried forward and bumped, so a `db_put_checked`
+/// writer still holding the old rev gets a conflict instead of finding the
+/// count reset; a new record starts at the column default of 0.
+fn next_unchecked_rev(
+    conn: &rusqlite::Connection,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+) -> Result<i64, String> {
+    let pk_col = primary_key_for(table);
+    let Some(key) = snake_obj.get(pk_col).filter(|key| !key.is_null()) else {
+        return Ok(0);
+    };
+    let stored_rev: Option<i64> = conn
+        .query_row(
+            &format!("SELECT \"{REV_COLUMN}\" FROM \"{table}\" WHERE \"{pk_col}\" = ?1"),
+            [json_to_sql(key).as_ref()],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query failed: {e}"))?;
+    Ok(stored_rev.map_or(0, |rev| rev + 1))
+}
 
-    state.with_connection(|conn| {
-        let sql = format!("SELECT * FROM \"{table}\" WHERE \"{pk_col}\" = ?1");
+/// Upsert a snake_case record without a revision check
+///
+/// Any `rev` in the record is replaced by [`next_unchecked_rev`].
+fn put_record(
+    conn: &rusqlite::Connection,
+    table: &str,
+    snake_obj: &serde_json::Map<String, Value>,
+) -> Result<(), String> {
+    if !has_rev_column(conn, table)? {
+        return upsert_record(conn, table, snake_obj);
+    }
+    let mut record = snake_obj.clone();
+    let rev = next_unchecked_rev(conn, table, snake_obj)?;
+    record.insert(REV_COLUMN.to_string(), Value::from(rev));
+    upsert_record(conn, table, &record)
+}
 
-        let mut stmt = conn.prepare(&sql).map_err(|e| format!("Prepare failed: {e}"))?;
-        let column_names = get_column_names(&stmt);
+/// Seal the policy-encrypted columns of a snake_case record in place
+///
+/// Fails without the field key whenever `table` has policy columns.
+fn seal_record(
+    cipher: &FieldCipher,
+    table: &str,
+    snake_obj: &mut serde_json::Map<String, Value>,
+) -> Result<(), String> {
+    cipher.check_writable(table)?;
+    for column in cipher.policy().columns(table) {
+        if let Some(value) = snake_obj.get_mut(column) {
+            *value = cipher.seal(table, column, value)?;
+        }
+    }
+    Ok(())
+}
 
-        let mut rows = stmt
-            .query(rusqlite::params![key])
-            .map_err(|e| format!("Query failed: {e}"))?;
+/// Open the policy-encrypted columns of camelCase rows in place
+fn open_rows(cipher: &FieldCipher, table: &str, rows: &mut [Value]) -> Result<(), String> {
+    let columns: Vec<(&str, String)> = cipher
+        .policy()
+        .columns(table)
+        .map(|column| (column, to_camel_case(column)))
+        .collect();
+    if columns.is_empty() {
+        return Ok(());
+    }
 
-        match rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
-            Some(row) => {
-                let json = row_to_json(row, &column_names)
-                    .map_err(|e| format!("Row conversion failed: {e}"))?;
-                Ok(Some(json))
+    for row in rows.iter_mut().filter_map(Value::as_object_mut) {
+        for (column, field) in &columns {
+            if let Some(value) = row.get_mut(field) {
+                *value = cipher.open(table, column, value.take())?;
             }
-            None => Ok(None),
         }
-    })
+    }
+    Ok(())
 }
 
-/// Get all records from a table
-#[tauri::command]
-pub async fn db_get_all(
-    state: State<'_, Database>,
-    table: String,
-) -> Result<Vec<Value>, String> {
-    validate_table_name(&table)?;
-
-    state.with_connection(|conn| {
-        let sql = format!("SELECT * FROM \"{table}\"");
+/// Refuse to filter or sort on a column that only holds ciphertext
+fn check_queryable(cipher: &FieldCipher, table: &str, column: &str) -> Result<(), String> {
+    if cipher.policy().is_encrypted(table, column) {
+        return Err(format!(
+            "{ENCRYPTED_COLUMN_ERROR}: {column} cannot be filtered or sorted on"
+        ));
+    }
+    Ok(())
+}
 
-        let mut stmt = conn.prepare(&sql).map_err(|e| format!("Prepare failed: {e}"))?;
-        let column_names = get_column_names(&stmt);
+/// Build ` WHERE a = ?1 AND b = ?2` from camelCase column-value pairs
+///
+/// Values are appended to `params`; returns an empty string for an empty
+/// filter. Columns are validated and must not be encrypted.
+fn where_sql(
+    cipher: &FieldCipher,
+    table: &str,
+    where_clause: &HashMap<String, Value>,
+    params: &mut Vec<Box<dyn rusqlite::types::ToSql>>,
+) -> Result<String, String> {
+    let mut conditions = Vec::new();
+    for (key, value) in where_clause {
+        let col = to_snake_case(key);
+        validate_column_name(&col)?;
+        check_queryable(cipher, table, &col)?;
+        params.push(json_to_sql(value));
+        conditions.push(format!("\"{}\" = ?{}", col, params.len()));
+    }
+    if conditions.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!(" WHERE {}", conditions.join(" AND ")))
+}
 
-        let mut rows = stmt
-            .query(rusqlite::params![])
-            .map_err(|e| format!("Query failed: {e}"))?;
+/// Count rows per distinct value of `group_by` (camelCase), ordered by value
+fn count_grouped(
+    conn: &rusqlite::Connection,
+    cipher: &FieldCipher,
+    table: &str,
+    group_by: &str,
+    filter: Option<&HashMap<String, Value>>,
+) -> Result<Vec<GroupCount>, String> {
+    let col = to_snake_case(group_by);
+    validate_column_name(&col)?;
+    check_queryable(cipher, table, &col)?;
+
+    let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+    let where_part = match filter {
+        Some(where_clause) => where_sql(cipher, table, where_clause, &mut params)?,
+        None => String::new(),
+    };
+    let sql = format!(
+        "SELECT \"{col}\", COUNT(*) FROM \"{table}\"{where_part} GROUP BY \"{col}\" ORDER BY \"{col}\""
+    );
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+    let column = to_camel_case(&col);
+    let groups = stmt
+        .query_map(param_refs.as_slice(), |row| {
+            Ok(GroupCount {
+                column: column.clone(),
+                value: sql_to_json(row.get_ref(0)?),
+                count: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Grouped count failed: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row fetch failed: {e}"))?;
+    Ok(groups)
+}
 
-        let mut results = Vec::new();
-        while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
-            let json = row_to_json(row, &column_names)
-                .map_err(|e| format!("Row conversion failed: {e}"))?;
-            results.push(json);
-        }
-        Ok(results)
-    })
+/// Columns of a table via `PRAGMA table_info`
+fn table_info(conn: &rusqlite::Connection, table: &str) -> Result<Vec<ColumnInfo>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info(\"{table}\")"))
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(ColumnInfo {
+                name: to_camel_case(&row.get::<_, String>("name")?),
+                column_type: row.get("type")?,
+                notnull: row.get("notnull")?,
+                pk: row.get("pk")?,
+            })
+        })
+        .map_err(|e| format!("Query failed: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row fetch failed: {e}"))?;
+
+    // PRAGMA table_info returns no rows for a missing table
+    if columns.is_empty() {
+        return Err(format!("{INVALID_TABLE_ERROR}: {table} (no such table)"));
+    }
+    Ok(columns)
 }
 
-/// Query records with filtering, sorting, and pagination
-#[tauri::command]
-pub async fn db_query(
-    state: State<'_, Database>,
-    table: String,
-    filter: QueryFilter,
-) -> Result<Vec<Value>, String> {
-    validate_table_name(&table)?;
+/// User tables, excluding SQLite internal and virtual-table shadow tables
+fn list_tables(conn: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name FROM pragma_table_list \
+             WHERE schema = 'main' AND type IN ('table', 'virtual') \
+             AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+             ORDER BY name",
+        )
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+
+    let tables = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query failed: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row fetch failed: {e}"))?;
+    Ok(tables)
+}
 
-    state.with_connection(|conn| {
-        let mut sql = format!("SELECT * FROM \"{table}\"");
-        let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+/// Error prefix for rejected `db_execute_query` SQL (mapped to invalid_input)
+pub(crate) const READ_ONLY_QUERY_ERROR: &str =
+    "Only SELECT queries are allowed via db_execute_query";
+
+/// Check that `sql` is one statement starting with `SELECT` or `WITH`
+///
+/// Quotes, bracketed identifiers and comments are skipped, so a semicolon
+/// inside them does not count; a single trailing semicolon is fine. This is
+/// a cheap first line of defence: a `WITH` can still lead into a write,
+/// which the authorizer in `execute_read_only` catches.
+fn validate_read_only_sql(sql: &str) -> Result<(), String> {
+    let mut statements = 0;
+    let mut first_keyword = String::new();
+    let mut keyword_done = false;
+    let mut in_statement = false;
+    let mut chars = sql.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let closing = match c {
+            '\'' | '"' | '`' => Some(c),
+            '[' => Some(']'),
+            '-' if chars.peek() == Some(&'-') => {
+                chars.find(|&c| c == '\n');
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = '\0';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                continue;
+            }
+            _ => None,
+        };
 
-        // WHERE clause
-        if let Some(ref where_clause) = filter.where_clause {
-            let mut conditions = Vec::new();
-            for (key, value) in where_clause {
-                let col = to_snake_case(key);
-                validate_column_name(&col)?;
-                params.push(json_to_sql(value));
-                conditions.push(format!("\"{}\" = ?{}", col, params.len()));
+        if c == ';' {
+            in_statement = false;
+            continue;
+        }
+        if c.is_whitespace() {
+            keyword_done |= !first_keyword.is_empty();
+            continue;
+        }
+        if !in_statement {
+            in_statement = true;
+            statements += 1;
+        }
+        if statements == 1 && !keyword_done {
+            if c.is_ascii_alphabetic() {
+                first_keyword.push(c.to_ascii_uppercase());
+            } else {
+                keyword_done = true;
             }
-            if !conditions.is_empty() {
-                sql.push_str(" WHERE ");
-                sql.push_str(&conditions.join(" AND "));
+        }
+        // Quoted text may contain anything, including ';' and '--'
+        if let Some(closing) = closing {
+            for c in chars.by_ref() {
+                if c == closing {
+                    break;
+                }
             }
         }
+    }
 
-        // ORDER BY
-        if let Some(ref order_by) = filter.order_by {
-            let col = to_snake_case(order_by);
-            validate_column_name(&col)?;
-            let dir = match filter.order_dir.as_deref() {
-                Some("desc") | Some("DESC") => "DESC",
-                _ => "ASC",
-            };
-            sql.push_str(&format!(" ORDER BY \"{col}\" {dir}"));
-        }
+    if statements > 1 {
+        return Err(format!(
+            "{READ_ONLY_QUERY_ERROR} (found multiple statements)"
+        ));
+    }
+    if first_keyword != "SELECT" && first_keyword != "WITH" {
+        return Err(READ_ONLY_QUERY_ERROR.to_string());
+    }
+    Ok(())
+}
 
-        // LIMIT / OFFSET - use parameterized queries for defense in depth
-        if let Some(limit) = filter.limit {
-            params.push(Box::new(limit as i64));
-            sql.push_str(&format!(" LIMIT ?{}", params.len()));
+/// Run a query with SQLite's authorizer denying anything but reads
+///
+/// The authorizer is consulted while the statement is compiled, so writes
+/// hidden anywhere (e.g. a `WITH` leading into `DELETE`) fail before
+/// anything runs. It is removed again before returning.
+fn execute_read_only(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    params: &[&dyn rusqlite::types::ToSql],
+) -> Result<Vec<Value>, String> {
+    use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+
+    let denied = std::sync::Arc::new(std::sync::Mutex::new(None::<String>));
+    let denied_in_hook = std::sync::Arc::clone(&denied);
+    conn.authorizer(Some(move |ctx: AuthContext<'_>| match ctx.action {
+        AuthAction::Select
+        | AuthAction::Read { .. }
+        | AuthAction::Function { .. }
+        | AuthAction::Recursive => Authorization::Allow,
+        action => {
+            if let Ok(mut denied) = denied_in_hook.lock() {
+                denied.get_or_insert_with(|| format!("{action:?}"));
+            }
+            Authorization::Deny
         }
-        if let Some(offset) = filter.offset {
-            params.push(Box::new(offset as i64));
-            sql.push_str(&format!(" OFFSET ?{}", params.len()));
+    }));
+
+    let result = (|| {
+        let mut stmt = conn
+            .prepare(sql)
+            .map_err(|e| format!("Prepare failed: {e}"))?;
+        if !stmt.readonly() {
+            return Err(format!("{READ_ONLY_QUERY_ERROR} (statement writes)"));
         }
-
-        let mut stmt = conn.prepare(&sql).map_err(|e| format!("Prepare failed: {e}"))?;
         let column_names = get_column_names(&stmt);
 
-        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-            params.iter().map(|p| p.as_ref()).collect();
-
         let mut rows = stmt
-            .query(param_refs.as_slice())
+            .query(params)
             .map_err(|e| format!("Query failed: {e}"))?;
 
         let mut results = Vec::new();
@@ -377,239 +845,2186 @@ pub async fn db_query(
             results.push(json);
         }
         Ok(results)
-    })
-}
-
-/// Delete a record by primary key
-#[tauri::command]
-pub async fn db_delete(
-    state: State<'_, Database>,
-    table: String,
-    key: String,
-) -> Result<bool, String> {
-    validate_table_name(&table)?;
+    })();
+    conn.authorizer(None::<fn(AuthContext<'_>) -> Authorization>);
 
-    let pk_col = primary_key_for(&table);
+    match denied.lock().ok().and_then(|mut denied| denied.take()) {
+        Some(action) => Err(format!("{READ_ONLY_QUERY_ERROR} (denied {action})")),
+        None => result,
+    }
+}
 
-    state.with_connection(|conn| {
-        let sql = format!("DELETE FROM \"{table}\" WHERE \"{pk_col}\" = ?1");
-        let affected = conn
-            .execute(&sql, rusqlite::params![key])
-            .map_err(|e| format!("db_delete failed: {e}"))?;
-        Ok(affected > 0)
-    })
+/// Decode a 32-byte secure_kv encryption key
+fn secure_kv_key(enc_key_hex: &str) -> Result<Zeroizing<Vec<u8>>, CommandError> {
+    decode_flexible(enc_key_hex, Some(32))
+        .map(Zeroizing::new)
+        .map_err(|e| CommandError::invalid_input(format!("Invalid encryption key: {e}")))
 }
 
-/// Bulk insert/replace records
-#[tauri::command]
-pub async fn db_bulk_put(
-    state: State<'_, Database>,
-    table: String,
-    records: Vec<Value>,
+/// Re-seal every sealed value in the database under `new_key`
+///
+/// Every column of every ordinary table is scanned rather than only the
+/// policy: a value left under the old key would be lost, so nothing is
+/// assumed about where sealed values live. Plaintext values are left alone.
+fn rewrap_sealed_columns(
+    conn: &rusqlite::Connection,
+    old_key: &[u8],
+    new_key: &[u8],
 ) -> Result<u32, String> {
-    validate_table_name(&table)?;
+    // Sealing binds the table and column as AAD; the policy plays no part
+    let old = FieldCipher::new(
+        FieldEncryptionPolicy::new(),
+        Some(Zeroizing::new(old_key.to_vec())),
+    );
+    let new = FieldCipher::new(
+        FieldEncryptionPolicy::new(),
+        Some(Zeroizing::new(new_key.to_vec())),
+    );
+    // GLOB, unlike LIKE, is case-sensitive
+    let sealed_pattern = format!("{SEALED_PREFIX}*");
+
+    let mut rewrapped = 0;
+    for table in sealable_tables(conn)? {
+        let columns: Vec<String> = {
+            let mut stmt = conn
+                .prepare("SELECT name FROM pragma_table_info(?1)")
+                .map_err(|e| format!("Prepare failed: {e}"))?;
+            let rows = stmt
+                .query_map([&table], |row| row.get(0))
+                .map_err(|e| format!("Query failed: {e}"))?;
+            rows.collect::<Result<_, _>>()
+                .map_err(|e| format!("Row fetch failed: {e}"))?
+        };
+
+        for column in &columns {
+            let sealed: Vec<(i64, String)> = {
+                let mut stmt = conn
+                    .prepare(&format!(
+                        "SELECT rowid, \"{column}\" FROM \"{table}\" \
+                         WHERE typeof(\"{column}\") = 'text' AND \"{column}\" GLOB ?1"
+                    ))
+                    .map_err(|e| format!("Prepare failed: {e}"))?;
+                let rows = stmt
+                    .query_map([&sealed_pattern], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .map_err(|e| format!("Query failed: {e}"))?;
+                rows.collect::<Result<_, _>>()
+                    .map_err(|e| format!("Row fetch failed: {e}"))?
+            };
 
-    if records.is_empty() {
-        return Ok(0);
+            for (rowid, value) in sealed {
+                let value = old.open(&table, column, Value::String(value))?;
+                let resealed = new.seal(&table, column, &value)?;
+                conn.execute(
+                    &format!("UPDATE \"{table}\" SET \"{column}\" = ?1 WHERE rowid = ?2"),
+                    rusqlite::params![resealed.as_str(), rowid],
+                )
+                .map_err(|e| format!("Update failed: {e}"))?;
+                rewrapped += 1;
+            }
+        }
     }
+    Ok(rewrapped)
+}
 
-    // Use with_connection_mut to get &mut Connection, required for safe
-    // transaction() which checks nesting (unlike unchecked_transaction)
-    state.with_connection_mut(|conn| {
-        let mut count = 0u32;
+/// Ordinary user tables that may hold sealed values
+///
+/// secure_kv has its own encryption, and virtual tables are skipped since
+/// their content follows the tables they index.
+fn sealable_tables(conn: &rusqlite::Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT name FROM pragma_table_list \
+             WHERE schema = 'main' AND type = 'table' AND name != 'secure_kv' \
+             AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\' \
+             ORDER BY name",
+        )
+        .map_err(|e| format!("Prepare failed: {e}"))?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query failed: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row fetch failed: {e}"))?;
+    Ok(tables)
+}
 
-        // Use the first record to determine columns
-        let first_obj = records[0]
-            .as_object()
-            .ok_or_else(|| "Records must be JSON objects".to_string())?;
+/// Re-encrypt secure_kv and every sealed column value in a single transaction
+///
+/// Any value that fails to decrypt rolls everything back.
+fn rewrap_all(
+    conn: &mut rusqlite::Connection,
+    old_key: &[u8],
+    new_key: &[u8],
+) -> Result<u32, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction start failed: {e}"))?;
+    let rewrapped =
+        secure_kv::rewrap(&tx, old_key, new_key)? + rewrap_sealed_columns(&tx, old_key, new_key)?;
+    tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
+    Ok(rewrapped)
+}
 
-        let snake_first = keys_to_snake_case(first_obj);
-        let columns: Vec<String> = snake_first.keys().cloned().collect();
+/// Encryption scheme of an imported legacy direct message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LegacyScheme {
+    Nip04,
+    Nip44,
+    /// NIP-44 written before the v2 spec alignment, re-encrypted on import
+    #[serde(rename = "nip44-pre-spec")]
+    Nip44PreSpec,
+}
 
-        for col in &columns {
-            validate_column_name(col)?;
-        }
+/// A NIP-04 or NIP-44 direct message to import
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyMessage {
+    /// Nostr event id, kept as the message id
+    pub id: String,
+    pub author_pubkey: String,
+    pub recipient_pubkey: String,
+    /// Encrypted event content
+    pub content: String,
+    /// Event kind (4 for NIP-04 DMs)
+    pub kind: i64,
+    /// Event `created_at` (unix seconds)
+    pub created_at: i64,
+    #[serde(default)]
+    pub tags: Vec<Vec<String>>,
+}
 
-        let col_list = columns
-            .iter()
-            .map(|c| format!("\"{}\"", c))
-            .collect::<Vec<_>>()
-            .join(", ");
-        let placeholders = columns
-            .iter()
-            .enumerate()
-            .map(|(i, _)| format!("?{}", i + 1))
-            .collect::<Vec<_>>()
-            .join(", ");
+/// Outcome of importing one legacy message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LegacyImportResult {
+    pub id: String,
+    pub imported: bool,
+    pub scheme: Option<LegacyScheme>,
+    /// Why the message was skipped
+    pub error: Option<String>,
+}
 
-        let sql = format!(
-            "INSERT OR REPLACE INTO \"{table}\" ({col_list}) VALUES ({placeholders})"
+/// Decrypt a legacy DM sent to or by `our_pubkey`
+///
+/// NIP-04 content is recognised by its `?iv=` suffix; anything else is
+/// treated as NIP-44, falling back to the pre-spec NIP-44 layout when the
+/// spec MAC fails.
+fn decrypt_legacy_message(
+    private_key: &[u8],
+    our_pubkey: &str,
+    message: &LegacyMessage,
+) -> Result<(LegacyScheme, Zeroizing<String>), String> {
+    // Both directions share one ECDH secret, keyed by the other party
+    let counterparty = if message.author_pubkey.eq_ignore_ascii_case(our_pubkey) {
+        &message.recipient_pubkey
+    } else if message.recipient_pubkey.eq_ignore_ascii_case(our_pubkey) {
+        &message.author_pubkey
+    } else {
+        return Err("Message was not sent to or by this identity".to_string());
+    };
+
+    let (scheme, plaintext) = if buildit_crypto::is_nip04_payload(&message.content) {
+        let plaintext = buildit_crypto::nip04_decrypt(
+            private_key.to_vec(),
+            counterparty.clone(),
+            message.content.clone(),
         );
+        (LegacyScheme::Nip04, plaintext)
+    } else {
+        match buildit_crypto::nip44_decrypt(
+            private_key.to_vec(),
+            counterparty.clone(),
+            message.content.clone(),
+        ) {
+            // A spec MAC failure may be a payload from before the spec alignment
+            Err(buildit_crypto::CryptoError::InvalidMac) => {
+                let plaintext = buildit_crypto::nip44_decrypt_legacy_pre_spec(
+                    private_key.to_vec(),
+                    counterparty.clone(),
+                    message.content.clone(),
+                );
+                (LegacyScheme::Nip44PreSpec, plaintext)
+            }
+            plaintext => (LegacyScheme::Nip44, plaintext),
+        }
+    };
+    plaintext
+        .map(|plaintext| (scheme, Zeroizing::new(plaintext)))
+        .map_err(|e| format!("{scheme:?} decryption failed: {e}"))
+}
 
-        let tx = conn
-            .transaction()
-            .map_err(|e| format!("Transaction start failed: {e}"))?;
-
-        {
-            let mut stmt = tx
-                .prepare(&sql)
-                .map_err(|e| format!("Prepare failed: {e}"))?;
+/// Decrypt legacy DMs into the messages table in one transaction
+///
+/// A message that cannot be decrypted or stored is reported and skipped
+/// rather than aborting the import. Messages whose id is already stored are
+/// skipped too, so an import never overwrites existing history.
+fn import_legacy(
+    conn: &mut rusqlite::Connection,
+    cipher: &FieldCipher,
+    private_key: &[u8],
+    our_pubkey: &str,
+    messages: &[LegacyMessage],
+) -> Result<Vec<LegacyImportResult>, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction start failed: {e}"))?;
+
+    let mut results = Vec::with_capacity(messages.len());
+    for message in messages {
+        let imported = decrypt_legacy_message(private_key, our_pubkey, message).and_then(
+            |(scheme, plaintext)| {
+                let mut record = serde_json::Map::new();
+                record.insert("id".into(), message.id.clone().into());
+                record.insert("author_pubkey".into(), message.author_pubkey.clone().into());
+                record.insert(
+                    "recipient_pubkey".into(),
+                    message.recipient_pubkey.clone().into(),
+                );
+                record.insert("content".into(), plaintext.as_str().into());
+                record.insert("kind".into(), message.kind.into());
+                record.insert("timestamp".into(), message.created_at.into());
+                record.insert("tags".into(), serde_json::json!(message.tags));
+
+                seal_record(cipher, "messages", &mut record)?;
+                if write_record(&tx, "INSERT OR IGNORE", "messages", &record)? == 0 {
+                    return Err("A message with this id is already stored".to_string());
+                }
+                Ok(scheme)
+            },
+        );
 
-            for record in &records {
-                let obj = record
-                    .as_object()
-                    .ok_or_else(|| "Record must be a JSON object".to_string())?;
+        results.push(match imported {
+            Ok(scheme) => LegacyImportResult {
+                id: message.id.clone(),
+                imported: true,
+                scheme: Some(scheme),
+                error: None,
+            },
+            Err(e) => LegacyImportResult {
+                id: message.id.clone(),
+                imported: false,
+                scheme: None,
+                error: Some(e),
+            },
+        });
+    }
 
-                let snake_obj = keys_to_snake_case(obj);
-                let params: Vec<Box<dyn rusqlite::types::ToSql>> = columns
-                    .iter()
-                    .map(|c| json_to_sql(snake_obj.get(c).unwrap_or(&Value::Null)))
-                    .collect();
+    tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
+    Ok(results)
+}
 
-                let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-                    params.iter().map(|p| p.as_ref()).collect();
+// ── Tauri Commands ────────────────────────────────────────────────────────────
 
-                stmt.execute(param_refs.as_slice())
-                    .map_err(|e| format!("Insert failed: {e}"))?;
-                count += 1;
-            }
+/// Open the database with an encryption key
+///
+/// `field_key` (32 bytes, hex or base64) seals the columns named in the
+/// field encryption policy; it is wiped again when the database locks.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_open(
+    state: State<'_, Database>,
+    key: String,
+    field_key: Option<String>,
+) -> Result<(), CommandError> {
+    match field_key {
+        Some(field_key) => {
+            let field_key = decode_flexible(&field_key, Some(32))
+                .map(Zeroizing::new)
+                .map_err(|e| CommandError::invalid_input(format!("Invalid field key: {e}")))?;
+            state.open_with_field_key(&key, field_key)
         }
+        None => state.open(&key),
+    }
+    .map_err(CommandError::from)
+}
 
-        tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
-        Ok(count)
-    })
+/// Close the database
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_close(state: State<'_, Database>) -> Result<(), CommandError> {
+    state.close();
+    Ok(())
 }
 
-/// Count records, optionally with a filter
+/// Check if database is open
 #[tauri::command]
-pub async fn db_count(
-    state: State<'_, Database>,
-    table: String,
-    filter: Option<HashMap<String, Value>>,
-) -> Result<u32, String> {
-    validate_table_name(&table)?;
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_is_open(state: State<'_, Database>) -> Result<bool, CommandError> {
+    Ok(state.is_open())
+}
 
-    state.with_connection(|conn| {
-        let mut sql = format!("SELECT COUNT(*) FROM \"{table}\"");
-        let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+/// Get the idle auto-lock timeout in seconds (`null` when disabled)
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get_idle_timeout(state: State<'_, Database>) -> Result<Option<u64>, CommandError> {
+    Ok(state.idle_timeout().map(|t| t.as_secs()))
+}
 
-        if let Some(ref where_clause) = filter {
-            let mut conditions = Vec::new();
-            for (key, value) in where_clause {
-                let col = to_snake_case(key);
-                validate_column_name(&col)?;
-                params.push(json_to_sql(value));
-                conditions.push(format!("\"{}\" = ?{}", col, params.len()));
-            }
-            if !conditions.is_empty() {
-                sql.push_str(" WHERE ");
-                sql.push_str(&conditions.join(" AND "));
-            }
-        }
+/// Set the idle auto-lock timeout in seconds (`null` disables auto-lock)
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_set_idle_timeout(
+    state: State<'_, Database>,
+    seconds: Option<u64>,
+) -> Result<(), CommandError> {
+    if seconds == Some(0) {
+        return Err(CommandError::invalid_input(
+            "Idle timeout must be positive (use null to disable)",
+        ));
+    }
+    state
+        .set_idle_timeout(seconds.map(std::time::Duration::from_secs))
+        .map_err(CommandError::from)
+}
 
-        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-            params.iter().map(|p| p.as_ref()).collect();
+/// Get the SQLCipher settings in effect on the open database
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get_cipher_info(state: State<'_, Database>) -> Result<CipherInfo, CommandError> {
+    state.cipher_info().map_err(CommandError::from)
+}
 
-        let count: u32 = conn
-            .query_row(&sql, param_refs.as_slice(), |row| row.get(0))
-            .map_err(|e| format!("Count failed: {e}"))?;
+/// Set the SQLCipher page size and KDF iterations for a new database
+///
+/// Takes effect on the next `db_open`. Existing databases must use
+/// `db_rekey_cipher` instead.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_set_cipher_settings(
+    state: State<'_, Database>,
+    settings: CipherSettings,
+) -> Result<(), CommandError> {
+    state
+        .set_cipher_settings(settings)
+        .map_err(CommandError::from)
+}
 
-        Ok(count)
-    })
+/// Re-encrypt the open database under a new key and cipher settings
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_rekey_cipher(
+    state: State<'_, Database>,
+    new_key: String,
+    settings: CipherSettings,
+) -> Result<(), CommandError> {
+    state
+        .rekey_cipher(&new_key, settings)
+        .map_err(CommandError::from)
 }
 
-/// Execute raw SQL (for complex queries not covered by the CRUD commands)
-/// Only SELECT statements are allowed for safety
+/// Get the columns sealed with the field key, per table (camelCase)
 #[tauri::command]
-pub async fn db_execute_query(
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get_field_encryption_policy(
     state: State<'_, Database>,
-    sql: String,
-    params: Option<Vec<Value>>,
-) -> Result<Vec<Value>, String> {
-    // Safety: only allow SELECT
-    let trimmed = sql.trim().to_uppercase();
-    if !trimmed.starts_with("SELECT") {
-        return Err("Only SELECT queries are allowed via db_execute_query".to_string());
-    }
+) -> Result<HashMap<String, Vec<String>>, CommandError> {
+    let policy = state.field_encryption_policy();
+    Ok(policy
+        .tables()
+        .map(|table| {
+            let columns = policy.columns(table).map(to_camel_case).collect();
+            (table.to_string(), columns)
+        })
+        .collect())
+}
 
-    let query_params: Vec<Box<dyn rusqlite::types::ToSql>> = params
-        .unwrap_or_default()
-        .iter()
-        .map(json_to_sql)
-        .collect();
+/// Replace the columns sealed with the field key, per table (camelCase)
+///
+/// Policy columns are encrypted by `db_put`, `db_put_returning`,
+/// `db_put_checked` and `db_bulk_put` and decrypted by `db_get`,
+/// `db_get_many`, `db_get_all` and `db_query`, which cannot filter or sort
+/// on them. Raw SQL (`db_execute_query`, `db_query_json`) sees ciphertext.
+/// Rows written before a column joined the policy stay readable. The policy
+/// is saved with the database; removing a column that still holds sealed
+/// values fails with `db_conflict`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_set_field_encryption_policy(
+    state: State<'_, Database>,
+    policy: HashMap<String, Vec<String>>,
+) -> Result<(), CommandError> {
+    let mut field_policy = FieldEncryptionPolicy::new();
+    for (table, columns) in &policy {
+        validate_table_name(table)?;
+        let columns: Vec<String> = columns.iter().map(|c| to_snake_case(c)).collect();
+        for column in &columns {
+            validate_column_name(column)?;
+        }
+        let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+        field_policy = field_policy.with_table(table, &columns);
+    }
+    state
+        .set_field_encryption_policy(field_policy)
+        .map_err(CommandError::from)
+}
 
-    state.with_connection(|conn| {
-        let mut stmt = conn.prepare(&sql).map_err(|e| format!("Prepare failed: {e}"))?;
-        let column_names = get_column_names(&stmt);
+/// Encrypt and store a small secret in the secure_kv table
+///
+/// Complements the OS keyring for secrets too many or too large for it.
+/// The value is AES-256-GCM encrypted under `enc_key_hex` with the key name
+/// bound as AAD, inside the SQLCipher-encrypted database.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn secure_kv_set(
+    state: State<'_, Database>,
+    key: String,
+    plaintext: String,
+    enc_key_hex: String,
+) -> Result<(), CommandError> {
+    let plaintext = Zeroizing::new(plaintext);
+    let enc_key = secure_kv_key(&enc_key_hex)?;
+    state
+        .with_connection(|conn| secure_kv::put(conn, &key, &plaintext, &enc_key))
+        .map_err(CommandError::from)
+}
 
-        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-            query_params.iter().map(|p| p.as_ref()).collect();
+/// Read and decrypt a secret from the secure_kv table (`null` if absent)
+///
+/// The Rust-side copy is zeroized once the response has been serialized.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn secure_kv_get(
+    state: State<'_, Database>,
+    key: String,
+    enc_key_hex: String,
+) -> Result<Option<SecretValue>, CommandError> {
+    let enc_key = secure_kv_key(&enc_key_hex)?;
+    state
+        .with_connection(|conn| secure_kv::read(conn, &key, &enc_key))
+        .map(|value| value.map(SecretValue::new))
+        .map_err(CommandError::from)
+}
 
-        let mut rows = stmt
-            .query(param_refs.as_slice())
-            .map_err(|e| format!("Query failed: {e}"))?;
+/// Delete a secret from the secure_kv table, returning whether it existed
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn secure_kv_delete(
+    state: State<'_, Database>,
+    key: String,
+) -> Result<bool, CommandError> {
+    state
+        .with_connection(|conn| secure_kv::remove(conn, &key))
+        .map_err(CommandError::from)
+}
 
-        let mut results = Vec::new();
-        while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
-            let json = row_to_json(row, &column_names)
-                .map_err(|e| format!("Row conversion failed: {e}"))?;
-            results.push(json);
-        }
-        Ok(results)
-    })
+/// Re-encrypt every stored secret after the master key changes
+///
+/// `old_master_hex` and `new_master_hex` (32 bytes, hex or base64) are the
+/// keys passed as `enc_key_hex` to the `secure_kv_*` commands and as
+/// `field_key` to `db_open`. Every secure_kv value and every sealed column
+/// value, in any table, is decrypted with the old key and re-encrypted with the new one
+/// in a single transaction, so if any value fails nothing changes and all
+/// of it still opens with the old key. On success the new key replaces the
+/// field key of the open database. Returns the number of values re-wrapped.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn rewrap_secrets(
+    state: State<'_, Database>,
+    old_master_hex: String,
+    new_master_hex: String,
+) -> Result<u32, CommandError> {
+    let old_key = secure_kv_key(&old_master_hex)?;
+    let new_key = secure_kv_key(&new_master_hex)?;
+    state
+        .rotate_field_key(new_key.clone(), |conn| rewrap_all(conn, &old_key, &new_key))
+        .map_err(CommandError::from)
+}
+
+/// Block a contact by public key
+///
+/// Their mesh messages and relay events are dropped from now on. The block
+/// is persisted, so the database must be open.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn block_contact(
+    state: State<'_, AppState>,
+    db: State<'_, Database>,
+    pubkey: String,
+) -> Result<(), CommandError> {
+    db.with_connection(|conn| contacts::insert_blocked(conn, &pubkey))
+        .map_err(CommandError::from)?;
+    state
+        .blocked_contacts
+        .block(&pubkey)
+        .map_err(CommandError::from)?;
+    Ok(())
+}
+
+/// Unblock a contact, returning whether they were blocked
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn unblock_contact(
+    state: State<'_, AppState>,
+    db: State<'_, Database>,
+    pubkey: String,
+) -> Result<bool, CommandError> {
+    let existed = db
+        .with_connection(|conn| contacts::delete_blocked(conn, &pubkey))
+        .map_err(CommandError::from)?;
+    state
+        .blocked_contacts
+        .unblock(&pubkey)
+        .map_err(CommandError::from)?;
+    Ok(existed)
+}
+
+/// Check whether a contact is blocked
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn is_contact_blocked(
+    state: State<'_, AppState>,
+    pubkey: String,
+) -> Result<bool, CommandError> {
+    Ok(state.blocked_contacts.is_blocked(&pubkey))
+}
+
+/// Insert or replace a record (upsert)
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_put(
+    state: State<'_, Database>,
+    table: String,
+    record: Value,
+) -> Result<(), CommandError> {
+    validate_table_name(&table)?;
+
+    let obj = record
+        .as_object()
+        .ok_or_else(|| INVALID_RECORD_ERROR.to_string())?;
+
+    let mut snake_obj = keys_to_snake_case(obj);
+    seal_record(&state.field_cipher(), &table, &mut snake_obj)?;
+
+    state
+        .with_connection(|conn| put_record(conn, &table, &snake_obj))
+        .map_err(CommandError::from)
+}
+
+/// Insert or replace a record and return the resulting row
+///
+/// The upsert and the read-back run in one transaction, so the returned row
+/// includes defaulted columns and cannot be overwritten by another writer
+/// in between.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_put_returning(
+    state: State<'_, Database>,
+    table: String,
+    record: Value,
+) -> Result<Value, CommandError> {
+    validate_table_name(&table)?;
+
+    let obj = record
+        .as_object()
+        .ok_or_else(|| INVALID_RECORD_ERROR.to_string())?;
+
+    let cipher = state.field_cipher();
+    let mut snake_obj = keys_to_snake_case(obj);
+    seal_record(&cipher, &table, &mut snake_obj)?;
+
+    let mut row = state
+        .with_connection_mut(|conn| {
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("Transaction start failed: {e}"))?;
+            let row = upsert_returning(&tx, &table, &snake_obj)?;
+            tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
+            Ok(row)
+        })
+        .map_err(CommandError::from)?;
+    open_rows(&cipher, &table, std::slice::from_mut(&mut row))?;
+    Ok(row)
+}
+
+/// Insert or replace a record with optimistic concurrency control
+///
+/// Fails with `db_conflict` if the stored `rev` is not `expected_rev` (pass
+/// `null` when creating). Returns the record's new `rev`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_put_checked(
+    state: State<'_, Database>,
+    table: String,
+    record: Value,
+    expected_rev: Option<i64>,
+) -> Result<i64, CommandError> {
+    validate_table_name(&table)?;
+
+    let obj = record
+        .as_object()
+        .ok_or_else(|| INVALID_RECORD_ERROR.to_string())?;
+
+    let mut snake_obj = keys_to_snake_case(obj);
+    seal_record(&state.field_cipher(), &table, &mut snake_obj)?;
+
+    state
+        .with_connection_mut(|conn| {
+            let tx = conn
+                .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+                .map_err(|e| format!("Transaction start failed: {e}"))?;
+            let rev = put_checked(&tx, &table, &snake_obj, expected_rev)?;
+            tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
+            Ok(rev)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Get a single record by primary key
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get(
+    state: State<'_, Database>,
+    table: String,
+    key: String,
+) -> Result<Option<Value>, CommandError> {
+    validate_table_name(&table)?;
+
+    let pk_col = primary_key_for(&table);
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| {
+            let sql = format!("SELECT * FROM \"{table}\" WHERE \"{pk_col}\" = ?1");
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| format!("Prepare failed: {e}"))?;
+            let column_names = get_column_names(&stmt);
+
+            let mut rows = stmt
+                .query(rusqlite::params![key])
+                .map_err(|e| format!("Query failed: {e}"))?;
+
+            match rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+                Some(row) => {
+                    let mut json = row_to_json(row, &column_names)
+                        .map_err(|e| format!("Row conversion failed: {e}"))?;
+                    open_rows(&cipher, &table, std::slice::from_mut(&mut json))?;
+                    Ok(Some(json))
+                }
+                None => Ok(None),
+            }
+        })
+        .map_err(CommandError::from)
+}
+
+/// Stream changes to one record to the frontend
+///
+/// Changes to the record with primary key `key` are emitted on
+/// `db-observed-change` until `db_unobserve` is called with the returned
+/// observer id. The record need not exist yet; its insertion is reported,
+/// and so are rewrites by `db_put`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_observe(
+    app: AppHandle,
+    state: State<'_, Database>,
+    table: String,
+    key: String,
+) -> Result<String, CommandError> {
+    validate_table_name(&table)?;
+
+    let pk_col = primary_key_for(&table);
+    state
+        .observe(
+            &table,
+            pk_col,
+            &key,
+            Arc::new(move |change| {
+                let _ = app.emit(DB_OBSERVE_CHANNEL, &change);
+            }),
+        )
+        .map_err(CommandError::from)
+}
+
+/// Stop streaming changes for an observer id returned by `db_observe`
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_unobserve(
+    state: State<'_, Database>,
+    observer_id: String,
+) -> Result<bool, CommandError> {
+    Ok(state.unobserve(&observer_id))
+}
+
+/// Get multiple records by primary key in one call
+///
+/// Returns the found records in the order of `keys`; missing keys are
+/// skipped.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get_many(
+    state: State<'_, Database>,
+    table: String,
+    keys: Vec<String>,
+) -> Result<Vec<Value>, CommandError> {
+    validate_table_name(&table)?;
+
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut rows = state
+        .with_connection(|conn| get_many(conn, &table, &keys, MAX_SQL_PARAMS))
+        .map_err(CommandError::from)?;
+    open_rows(&state.field_cipher(), &table, &mut rows)?;
+    Ok(rows)
+}
+
+/// Get all records from a table
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_get_all(
+    state: State<'_, Database>,
+    table: String,
+) -> Result<Vec<Value>, CommandError> {
+    validate_table_name(&table)?;
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| {
+            let sql = format!("SELECT * FROM \"{table}\"");
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| format!("Prepare failed: {e}"))?;
+            let column_names = get_column_names(&stmt);
+
+            let mut rows = stmt
+                .query(rusqlite::params![])
+                .map_err(|e| format!("Query failed: {e}"))?;
+
+            let mut results = Vec::new();
+            while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+                let json = row_to_json(row, &column_names)
+                    .map_err(|e| format!("Row conversion failed: {e}"))?;
+                results.push(json);
+            }
+            open_rows(&cipher, &table, &mut results)?;
+            Ok(results)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Query records with filtering, sorting, and pagination
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_query(
+    state: State<'_, Database>,
+    table: String,
+    filter: QueryFilter,
+) -> Result<Vec<Value>, CommandError> {
+    validate_table_name(&table)?;
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| {
+            let mut sql = format!("SELECT * FROM \"{table}\"");
+            let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+            // WHERE clause
+            if let Some(ref where_clause) = filter.where_clause {
+                sql.push_str(&where_sql(&cipher, &table, where_clause, &mut params)?);
+            }
+
+            // ORDER BY
+            if let Some(ref order_by) = filter.order_by {
+                let col = to_snake_case(order_by);
+                validate_column_name(&col)?;
+                check_queryable(&cipher, &table, &col)?;
+                let dir = match filter.order_dir.as_deref() {
+                    Some("desc") | Some("DESC") => "DESC",
+                    _ => "ASC",
+                };
+                sql.push_str(&format!(" ORDER BY \"{col}\" {dir}"));
+            }
+
+            // LIMIT / OFFSET - use parameterized queries for defense in depth
+            if let Some(limit) = filter.limit {
+                params.push(Box::new(limit as i64));
+                sql.push_str(&format!(" LIMIT ?{}", params.len()));
+            }
+            if let Some(offset) = filter.offset {
+                params.push(Box::new(offset as i64));
+                sql.push_str(&format!(" OFFSET ?{}", params.len()));
+            }
+
+            let mut stmt = conn
+                .prepare(&sql)
+                .map_err(|e| format!("Prepare failed: {e}"))?;
+            let column_names = get_column_names(&stmt);
+
+            let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+                params.iter().map(|p| p.as_ref()).collect();
+
+            let mut rows = stmt
+                .query(param_refs.as_slice())
+                .map_err(|e| format!("Query failed: {e}"))?;
+
+            let mut results = Vec::new();
+            while let Some(row) = rows.next().map_err(|e| format!("Row fetch failed: {e}"))? {
+                let json = row_to_json(row, &column_names)
+                    .map_err(|e| format!("Row conversion failed: {e}"))?;
+                results.push(json);
+            }
+            open_rows(&cipher, &table, &mut results)?;
+            Ok(results)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Query records by a value inside a JSON TEXT column
+///
+/// `json_path` is the camelCase column optionally followed by a path into
+/// the stored JSON (`tags`, `metadata.labels[0]`). Matches records where the
+/// value at that path equals `value`, or is an array containing it. Paths
+/// into encrypted columns are refused, as in `db_query`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_query_json(
+    state: State<'_, Database>,
+    table: String,
+    json_path: String,
+    value: Value,
+) -> Result<Vec<Value>, CommandError> {
+    validate_table_name(&table)?;
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| query_json(conn, &cipher, &table, &json_path, &value))
+        .map_err(CommandError::from)
+}
+
+/// Delete a record by primary key
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_delete(
+    state: State<'_, Database>,
+    table: String,
+    key: String,
+) -> Result<bool, CommandError> {
+    validate_table_name(&table)?;
+
+    let pk_col = primary_key_for(&table);
+
+    state
+        .with_connection(|conn| {
+            let sql = format!("DELETE FROM \"{table}\" WHERE \"{pk_col}\" = ?1");
+            let affected = conn
+                .execute(&sql, rusqlite::params![key])
+                .map_err(|e| format!("db_delete failed: {e}"))?;
+            Ok(affected > 0)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Bulk insert/replace records
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_bulk_put(
+    state: State<'_, Database>,
+    table: String,
+    records: Vec<Value>,
+) -> Result<u32, CommandError> {
+    validate_table_name(&table)?;
+
+    if records.is_empty() {
+        return Ok(0);
+    }
+
+    let cipher = state.field_cipher();
+
+    // Use with_connection_mut to get &mut Connection, required for safe
+    // transaction() which checks nesting (unlike unchecked_transaction)
+    state
+        .with_connection_mut(|conn| {
+            let mut count = 0u32;
+
+            // Use the first record to determine columns
+            let first_obj = records[0]
+                .as_object()
+                .ok_or_else(|| format!("{INVALID_RECORD_ERROR} (bulk put)"))?;
+
+            let snake_first = keys_to_snake_case(first_obj);
+            let mut columns: Vec<String> = snake_first.keys().cloned().collect();
+            let versioned = has_rev_column(conn, &table)?;
+            if versioned && !columns.iter().any(|c| c == REV_COLUMN) {
+                columns.push(REV_COLUMN.to_string());
+            }
+
+            for col in &columns {
+                validate_column_name(col)?;
+            }
+
+            let col_list = columns
+                .iter()
+                .map(|c| format!("\"{}\"", c))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let placeholders = columns
+                .iter()
+                .enumerate()
+                .map(|(i, _)| format!("?{}", i + 1))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let sql =
+                format!("INSERT OR REPLACE INTO \"{table}\" ({col_list}) VALUES ({placeholders})");
+
+            let tx = conn
+                .transaction()
+                .map_err(|e| format!("Transaction start failed: {e}"))?;
+
+            {
+                let mut stmt = tx
+                    .prepare(&sql)
+                    .map_err(|e| format!("Prepare failed: {e}"))?;
+
+                for record in &records {
+                    let obj = record
+                        .as_object()
+                        .ok_or_else(|| INVALID_RECORD_ERROR.to_string())?;
+
+                    let mut snake_obj = keys_to_snake_case(obj);
+                    seal_record(&cipher, &table, &mut snake_obj)?;
+                    if versioned {
+                        let rev = next_unchecked_rev(&tx, &table, &snake_obj)?;
+                        snake_obj.insert(REV_COLUMN.to_string(), Value::from(rev));
+                    }
+                    let params: Vec<Box<dyn rusqlite::types::ToSql>> = columns
+                        .iter()
+                        .map(|c| json_to_sql(snake_obj.get(c).unwrap_or(&Value::Null)))
+                        .collect();
+
+                    let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+                        params.iter().map(|p| p.as_ref()).collect();
+
+                    stmt.execute(param_refs.as_slice())
+                        .map_err(|e| format!("Insert failed: {e}"))?;
+                    count += 1;
+                }
+            }
+
+            tx.commit().map_err(|e| format!("Commit failed: {e}"))?;
+            Ok(count)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Import NIP-04/NIP-44 direct message history into the messages table
+///
+/// Each message is decrypted with `private_key_hex` (NIP-04 or NIP-44 is
+/// detected from the content) and stored as plaintext in the encrypted
+/// database, sealed further if the field encryption policy covers
+/// `messages.content`. Returns one result per message, in order; messages
+/// that cannot be decrypted are skipped.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn import_legacy_messages(
+    state: State<'_, Database>,
+    messages: Vec<LegacyMessage>,
+    private_key_hex: String,
+) -> Result<Vec<LegacyImportResult>, CommandError> {
+    let private_key = decode_flexible(&private_key_hex, Some(32))
+        .map(Zeroizing::new)
+        .map_err(|e| CommandError::invalid_input(format!("Invalid private key: {e}")))?;
+    let our_pubkey = buildit_crypto::get_public_key(private_key.to_vec())
+        .map_err(|e| CommandError::invalid_input(format!("Invalid private key: {e}")))?;
+
+    let cipher = state.field_cipher();
+    state
+        .with_connection_mut(|conn| {
+            import_legacy(conn, &cipher, &private_key, &our_pubkey, &messages)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Count records, optionally with a filter
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_count(
+    state: State<'_, Database>,
+    table: String,
+    filter: Option<HashMap<String, Value>>,
+) -> Result<u32, CommandError> {
+    validate_table_name(&table)?;
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| {
+            let mut sql = format!("SELECT COUNT(*) FROM \"{table}\"");
+            let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+            if let Some(ref where_clause) = filter {
+                sql.push_str(&where_sql(&cipher, &table, where_clause, &mut params)?);
+            }
+
+            let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+                params.iter().map(|p| p.as_ref()).collect();
+
+            let count: u32 = conn
+                .query_row(&sql, param_refs.as_slice(), |row| row.get(0))
+                .map_err(|e| format!("Count failed: {e}"))?;
+
+            Ok(count)
+        })
+        .map_err(CommandError::from)
+}
+
+/// Count records per distinct value of a column, optionally with a filter
+///
+/// `group_by_column` and the filter keys are camelCase. Groups are ordered
+/// by value; NULL values form their own group.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_count_grouped(
+    state: State<'_, Database>,
+    table: String,
+    group_by_column: String,
+    filter: Option<HashMap<String, Value>>,
+) -> Result<Vec<GroupCount>, CommandError> {
+    validate_table_name(&table)?;
+    let cipher = state.field_cipher();
+
+    state
+        .with_connection(|conn| {
+            count_grouped(conn, &cipher, &table, &group_by_column, filter.as_ref())
+        })
+        .map_err(CommandError::from)
+}
+
+/// Execute raw SQL (for complex queries not covered by the CRUD commands)
+///
+/// Only a single read-only `SELECT` or `WITH ... SELECT` statement is
+/// allowed; see `execute_read_only`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_execute_query(
+    state: State<'_, Database>,
+    sql: String,
+    params: Option<Vec<Value>>,
+) -> Result<Vec<Value>, CommandError> {
+    validate_read_only_sql(&sql)?;
+
+    let query_params: Vec<Box<dyn rusqlite::types::ToSql>> =
+        params.unwrap_or_default().iter().map(json_to_sql).collect();
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+        query_params.iter().map(|p| p.as_ref()).collect();
+
+    state
+        .with_connection(|conn| execute_read_only(conn, &sql, &param_refs))
+        .map_err(CommandError::from)
 }
 
 /// Delete records matching a WHERE clause
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn db_delete_where(
     state: State<'_, Database>,
     table: String,
     where_clause: HashMap<String, Value>,
-) -> Result<u32, String> {
+) -> Result<u32, CommandError> {
     validate_table_name(&table)?;
 
     if where_clause.is_empty() {
-        return Err("where_clause cannot be empty for db_delete_where (use db_clear_table instead)".to_string());
+        return Err(CommandError::invalid_input(
+            "where_clause cannot be empty for db_delete_where (use db_clear_table instead)",
+        ));
     }
 
-    state.with_connection(|conn| {
-        let mut sql = format!("DELETE FROM \"{table}\"");
-        let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-        let mut conditions = Vec::new();
-
-        for (key, value) in &where_clause {
-            let col = to_snake_case(key);
-            validate_column_name(&col)?;
-            params.push(json_to_sql(value));
-            conditions.push(format!("\"{}\" = ?{}", col, params.len()));
-        }
+    let cipher = state.field_cipher();
 
-        sql.push_str(" WHERE ");
-        sql.push_str(&conditions.join(" AND "));
+    state
+        .with_connection(|conn| {
+            let mut sql = format!("DELETE FROM \"{table}\"");
+            let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+            sql.push_str(&where_sql(&cipher, &table, &where_clause, &mut params)?);
 
-        let param_refs: Vec<&dyn rusqlite::types::ToSql> =
-            params.iter().map(|p| p.as_ref()).collect();
+            let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+                params.iter().map(|p| p.as_ref()).collect();
 
-        let affected = conn
-            .execute(&sql, param_refs.as_slice())
-            .map_err(|e| format!("db_delete_where failed: {e}"))?;
+            let affected = conn
+                .execute(&sql, param_refs.as_slice())
+                .map_err(|e| format!("db_delete_where failed: {e}"))?;
 
-        Ok(affected as u32)
-    })
+            Ok(affected as u32)
+        })
+        .map_err(CommandError::from)
 }
 
 /// Clear all data from a specific table
 #[tauri::command]
-pub async fn db_clear_table(
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_clear_table(state: State<'_, Database>, table: String) -> Result<(), CommandError> {
+    validate_table_name(&table)?;
+
+    state
+        .with_connection(|conn| {
+            let sql = format!("DELETE FROM \"{table}\"");
+            conn.execute(&sql, rusqlite::params![])
+                .map_err(|e| format!("Clear table failed: {e}"))?;
+            Ok(())
+        })
+        .map_err(CommandError::from)
+}
+
+/// Describe the columns of a table
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_table_info(
     state: State<'_, Database>,
     table: String,
-) -> Result<(), String> {
+) -> Result<Vec<ColumnInfo>, CommandError> {
     validate_table_name(&table)?;
 
-    state.with_connection(|conn| {
-        let sql = format!("DELETE FROM \"{table}\"");
-        conn.execute(&sql, rusqlite::params![])
-            .map_err(|e| format!("Clear table failed: {e}"))?;
-        Ok(())
-    })
+    state
+        .with_connection(|conn| table_info(conn, &table))
+        .map_err(CommandError::from)
+}
+
+/// List user tables
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn db_list_tables(state: State<'_, Database>) -> Result<Vec<String>, CommandError> {
+    state
+        .with_connection(list_tables)
+        .map_err(CommandError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn test_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (
+                id TEXT PRIMARY KEY,
+                group_id TEXT,
+                status TEXT NOT NULL DEFAULT 'draft',
+                tags TEXT DEFAULT '[]',
+                revision INTEGER NOT NULL DEFAULT 1
+            );
+            CREATE TABLE cache_metadata (
+                key TEXT PRIMARY KEY,
+                type TEXT NOT NULL,
+                size INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE counters (
+                id INTEGER PRIMARY KEY,
+                label TEXT
+            );",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn snake(record: Value) -> serde_json::Map<String, Value> {
+        keys_to_snake_case(record.as_object().unwrap())
+    }
+
+    #[test]
+    fn test_put_returning_includes_defaults() {
+        let conn = test_conn();
+
+        let row = upsert_returning(
+            &conn,
+            "notes",
+            &snake(serde_json::json!({ "id": "n1", "groupId": "g1" })),
+        )
+        .unwrap();
+
+        assert_eq!(
+            row,
+            serde_json::json!({
+                "id": "n1",
+                "groupId": "g1",
+                "status": "draft",
+                "tags": [],
+                "revision": 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_put_returning_replaces_existing_row() {
+        let conn = test_conn();
+        let record = snake(serde_json::json!({ "id": "n1", "status": "published" }));
+        upsert_returning(&conn, "notes", &record).unwrap();
+
+        let row = upsert_returning(
+            &conn,
+            "notes",
+            &snake(serde_json::json!({ "id": "n1", "revision": 2 })),
+        )
+        .unwrap();
+
+        // INSERT OR REPLACE resets omitted columns to their defaults
+        assert_eq!(row["status"], "draft");
+        assert_eq!(row["revision"], 2);
+    }
+
+    #[test]
+    fn test_put_returning_non_id_primary_key() {
+        let conn = test_conn();
+
+        let row = upsert_returning(
+            &conn,
+            "cache_metadata",
+            &snake(serde_json::json!({ "key": "avatar:1", "type": "image" })),
+        )
+        .unwrap();
+
+        assert_eq!(
+            row,
+            serde_json::json!({ "key": "avatar:1", "type": "image", "size": 0 })
+        );
+    }
+
+    #[test]
+    fn test_put_returning_assigned_rowid() {
+        let conn = test_conn();
+
+        let first = upsert_returning(
+            &conn,
+            "counters",
+            &snake(serde_json::json!({ "label": "a" })),
+        )
+        .unwrap();
+        let second = upsert_returning(
+            &conn,
+            "counters",
+            &snake(serde_json::json!({ "label": "b" })),
+        )
+        .unwrap();
+
+        assert_eq!(first, serde_json::json!({ "id": 1, "label": "a" }));
+        assert_eq!(second, serde_json::json!({ "id": 2, "label": "b" }));
+    }
+
+    fn migrated_conn() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::schema::run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_list_tables_includes_migrated_tables() {
+        let conn = migrated_conn();
+        conn.execute_batch("CREATE VIRTUAL TABLE notes_fts USING fts5(title, body);")
+            .unwrap();
+
+        let tables = list_tables(&conn).unwrap();
+
+        for table in [
+            "identities",
+            "messages",
+            "friends",
+            "offline_queue",
+            "notes_fts",
+        ] {
+            assert!(tables.iter().any(|t| t == table), "missing {table}");
+        }
+        // Internal and FTS shadow tables are hidden
+        assert!(tables.iter().all(|t| !t.starts_with("sqlite_")));
+        assert!(!tables
+            .iter()
+            .any(|t| t == "notes_fts_data" || t == "notes_fts_idx"));
+    }
+
+    #[test]
+    fn test_table_info_for_migrated_table() {
+        let conn = migrated_conn();
+
+        let columns = table_info(&conn, "friends").unwrap();
+        let column = |name: &str| columns.iter().find(|c| c.name == name).unwrap();
+
+        assert_eq!(
+            column("id"),
+            &ColumnInfo {
+                name: "id".to_string(),
+                column_type: "TEXT".to_string(),
+                notnull: false,
+                pk: 1,
+            }
+        );
+        assert_eq!(column("friendPubkey").column_type, "TEXT");
+        assert!(column("friendPubkey").notnull);
+        assert_eq!(column("friendPubkey").pk, 0);
+        assert_eq!(column("verifiedInPerson").column_type, "INTEGER");
+
+        let json = serde_json::to_value(column("addedAt")).unwrap();
+        assert_eq!(json["type"], "INTEGER");
+        assert_eq!(json["notnull"], true);
+    }
+
+    #[test]
+    fn test_table_info_missing_table() {
+        let conn = migrated_conn();
+        let err = table_info(&conn, "no_such_table").unwrap_err();
+        assert_eq!(CommandError::from(err).code, "invalid_input");
+    }
+
+    fn document(title: &str) -> serde_json::Map<String, Value> {
+        snake(serde_json::json!({
+            "id": "d1",
+            "title": title,
+            "createdBy": "alice",
+            "createdAt": 1
+        }))
+    }
+
+    #[test]
+    fn test_put_checked_versioned_update() {
+        let conn = migrated_conn();
+
+        assert_eq!(
+            put_checked(&conn, "documents", &document("v1"), None).unwrap(),
+            1
+        );
+        assert_eq!(
+            put_checked(&conn, "documents", &document("v2"), Some(1)).unwrap(),
+            2
+        );
+
+        let (title, rev): (String, i64) = conn
+            .query_row(
+                "SELECT title, rev FROM documents WHERE id = 'd1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(title, "v2");
+        assert_eq!(rev, 2);
+    }
+
+    #[test]
+    fn test_put_checked_rejects_stale_update() {
+        let conn = migrated_conn();
+        put_checked(&conn, "documents", &document("v1"), None).unwrap();
+        put_checked(&conn, "documents", &document("v2"), Some(1)).unwrap();
+
+        // A second writer still holding rev 1 must not clobber v2
+        let err = put_checked(&conn, "documents", &document("stale"), Some(1)).unwrap_err();
+        assert_eq!(CommandError::from(err).code, "db_conflict");
+
+        // Creating a record that already exists is a conflict too
+        let err = put_checked(&conn, "documents", &document("dup"), None).unwrap_err();
+        assert_eq!(CommandError::from(err).code, "db_conflict");
+
+        let title: String = conn
+            .query_row("SELECT title FROM documents WHERE id = 'd1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(title, "v2");
+    }
+
+    #[test]
+    fn test_unchecked_put_bumps_rev() {
+        let conn = migrated_conn();
+        put_checked(&conn, "documents", &document("v1"), None).unwrap();
+
+        // An unchecked write moves rev on instead of resetting it
+        let mut unchecked = document("unchecked");
+        unchecked.insert(REV_COLUMN.to_string(), Value::from(0));
+        let row = upsert_returning(&conn, "documents", &unchecked).unwrap();
+        assert_eq!(row["rev"], 2);
+        put_record(&conn, "documents", &document("again")).unwrap();
+
+        // So a writer still holding rev 1 sees the conflict
+        let err = put_checked(&conn, "documents", &document("stale"), Some(1)).unwrap_err();
+        assert_eq!(CommandError::from(err).code, "db_conflict");
+        assert_eq!(
+            put_checked(&conn, "documents", &document("v4"), Some(3)).unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_put_checked_requires_rev_column() {
+        let conn = migrated_conn();
+        let record = snake(
+            serde_json::json!({ "key": "k", "type": "t", "lastAccessedAt": 1, "createdAt": 1 }),
+        );
+        let err = put_checked(&conn, "cache_metadata", &record, None).unwrap_err();
+        assert_eq!(CommandError::from(err).code, "db_unversioned_table");
+    }
+
+    fn insert_notes(conn: &Connection, count: usize) {
+        for i in 0..count {
+            upsert_record(
+                conn,
+                "notes",
+                &snake(serde_json::json!({ "id": format!("n{i}") })),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_get_many_preserves_order_and_skips_missing() {
+        let conn = test_conn();
+        insert_notes(&conn, 5);
+
+        let keys: Vec<String> = ["n3", "missing", "n0", "n3", "n4"]
+            .iter()
+            .map(|k| k.to_string())
+            .collect();
+        let rows = get_many(&conn, "notes", &keys, MAX_SQL_PARAMS).unwrap();
+
+        let ids: Vec<&str> = rows.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, ["n3", "n0", "n4"]);
+        assert_eq!(rows[0]["status"], "draft");
+    }
+
+    #[test]
+    fn test_get_many_chunks_at_parameter_limit() {
+        let conn = test_conn();
+        insert_notes(&conn, MAX_SQL_PARAMS + 1);
+
+        let keys: Vec<String> = (0..=MAX_SQL_PARAMS)
+            .rev()
+            .map(|i| format!("n{i}"))
+            .collect();
+
+        // Exactly at the limit is a single query, one more needs a second chunk
+        let rows = get_many(&conn, "notes", &keys[..MAX_SQL_PARAMS], MAX_SQL_PARAMS).unwrap();
+        assert_eq!(rows.len(), MAX_SQL_PARAMS);
+
+        let rows = get_many(&conn, "notes", &keys, MAX_SQL_PARAMS).unwrap();
+        assert_eq!(rows.len(), MAX_SQL_PARAMS + 1);
+        assert_eq!(rows[0]["id"], format!("n{MAX_SQL_PARAMS}"));
+        assert_eq!(rows[MAX_SQL_PARAMS]["id"], "n0");
+
+        // Small chunks split mid-list and still keep input order
+        let rows = get_many(&conn, "notes", &keys[..7], 3).unwrap();
+        let ids: Vec<&Value> = rows.iter().map(|r| &r["id"]).collect();
+        let expected: Vec<Value> = keys[..7].iter().map(|k| Value::from(k.as_str())).collect();
+        assert_eq!(ids, expected.iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_get_many_integer_primary_key() {
+        let conn = test_conn();
+        for label in ["a", "b"] {
+            upsert_record(
+                &conn,
+                "counters",
+                &snake(serde_json::json!({ "label": label })),
+            )
+            .unwrap();
+        }
+
+        let keys = vec!["2".to_string(), "1".to_string(), "3".to_string()];
+        let rows = get_many(&conn, "counters", &keys, MAX_SQL_PARAMS).unwrap();
+        assert_eq!(
+            rows,
+            [
+                serde_json::json!({ "id": 2, "label": "b" }),
+                serde_json::json!({ "id": 1, "label": "a" })
+            ]
+        );
+    }
+
+    fn tagged_notes(conn: &Connection) {
+        for record in [
+            serde_json::json!({ "id": "n1", "tags": ["urgent", "housing"] }),
+            serde_json::json!({ "id": "n2", "tags": ["food"] }),
+            serde_json::json!({ "id": "n3", "tags": { "labels": ["urgent"], "priority": 2 } }),
+            serde_json::json!({ "id": "n4", "tags": "not json" }),
+        ] {
+            upsert_record(conn, "notes", &snake(record)).unwrap();
+        }
+    }
+
+    fn ids(rows: &[Value]) -> Vec<&str> {
+        rows.iter().map(|r| r["id"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_query_json_array_membership() {
+        let conn = test_conn();
+        let plain = field_cipher(None);
+        tagged_notes(&conn);
+
+        let rows = query_json(&conn, &plain, "notes", "tags", &Value::from("urgent")).unwrap();
+        assert_eq!(ids(&rows), ["n1"]);
+        assert_eq!(rows[0]["tags"], serde_json::json!(["urgent", "housing"]));
+
+        let rows = query_json(&conn, &plain, "notes", "tags", &Value::from("food")).unwrap();
+        assert_eq!(ids(&rows), ["n2"]);
+
+        assert!(
+            query_json(&conn, &plain, "notes", "tags", &Value::from("none"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_query_json_nested_path() {
+        let conn = test_conn();
+        let plain = field_cipher(None);
+        tagged_notes(&conn);
+
+        let rows = query_json(
+            &conn,
+            &plain,
+            "notes",
+            "tags.labels",
+            &Value::from("urgent"),
+        )
+        .unwrap();
+        assert_eq!(ids(&rows), ["n3"]);
+
+        let rows = query_json(&conn, &plain, "notes", "tags.priority", &Value::from(2)).unwrap();
+        assert_eq!(ids(&rows), ["n3"]);
+
+        let rows = query_json(
+            &conn,
+            &plain,
+            "notes",
+            "tags.labels[0]",
+            &Value::from("urgent"),
+        )
+        .unwrap();
+        assert_eq!(ids(&rows), ["n3"]);
+    }
+
+    #[test]
+    fn test_query_json_validates_input() {
+        let conn = test_conn();
+        let plain = field_cipher(None);
+
+        for path in [
+            "",
+            "tags.",
+            "tags.a-b",
+            "tags.x[",
+            "tags.x[a]",
+            "tags.x[0]y",
+            "tags.x[0]]",
+            "tags') --",
+        ] {
+            let err = query_json(&conn, &plain, "notes", path, &Value::from("x")).unwrap_err();
+            assert_eq!(CommandError::from(err).code, "invalid_input", "{path}");
+        }
+
+        assert!(query_json(&conn, &plain, "notes", "tags", &serde_json::json!(["x"])).is_err());
+        assert_eq!(
+            parse_json_path("groupId.members[2].name").unwrap(),
+            ("group_id".to_string(), "$.members[2].name".to_string())
+        );
+    }
+
+    fn run_query(conn: &Connection, sql: &str) -> Result<Vec<Value>, String> {
+        validate_read_only_sql(sql)?;
+        execute_read_only(conn, sql, &[])
+    }
+
+    #[test]
+    fn test_read_only_query_blocks_multi_statement_injection() {
+        let conn = migrated_conn();
+        conn.execute_batch("CREATE TABLE notes (id TEXT); INSERT INTO notes VALUES ('n1');")
+            .unwrap();
+
+        for sql in [
+            "SELECT * FROM notes; DELETE FROM notes",
+            "SELECT * FROM notes;DELETE FROM notes;",
+            "DELETE FROM notes",
+            "  -- comment\n  UPDATE notes SET id = 'x'",
+            "SELECTX FROM notes",
+        ] {
+            let err = run_query(&conn, sql).unwrap_err();
+            assert!(err.starts_with(READ_ONLY_QUERY_ERROR), "{sql}: {err}");
+            assert_eq!(CommandError::from(err).code, "invalid_input");
+        }
+        assert_eq!(run_query(&conn, "SELECT * FROM notes").unwrap().len(), 1);
+
+        // Semicolons inside quotes and comments, and a trailing one, are fine
+        let rows = run_query(
+            &conn,
+            "/* a; b */ SELECT ';' AS \"x;y\", id FROM [notes] -- ; DELETE\n ;",
+        )
+        .unwrap();
+        assert_eq!(rows, vec![serde_json::json!({ "x;y": ";", "id": "n1" })]);
+    }
+
+    #[test]
+    fn test_read_only_query_allows_cte() {
+        let conn = migrated_conn();
+        let rows = run_query(
+            &conn,
+            "WITH RECURSIVE counter(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM counter WHERE n < 3) \
+             SELECT n FROM counter",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2], serde_json::json!({ "n": 3 }));
+    }
+
+    #[test]
+    fn test_read_only_query_denies_sneaky_write() {
+        let conn = migrated_conn();
+        conn.execute_batch("CREATE TABLE notes (id TEXT); INSERT INTO notes VALUES ('n1');")
+            .unwrap();
+
+        // Passes the keyword check; the authorizer must stop it
+        let sql = "WITH doomed AS (SELECT id FROM notes) DELETE FROM notes WHERE id IN doomed";
+        let err = run_query(&conn, sql).unwrap_err();
+        assert!(err.starts_with(READ_ONLY_QUERY_ERROR), "{err}");
+        assert!(err.contains("Delete"), "{err}");
+
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM notes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // The authorizer is gone afterwards, so regular writes still work
+        conn.execute("INSERT INTO notes VALUES ('n2')", []).unwrap();
+    }
+
+    #[test]
+    fn test_secure_kv_round_trip() {
+        let conn = migrated_conn();
+        let enc_key = [7u8; 32];
+
+        assert!(secure_kv::read(&conn, "session:alice", &enc_key)
+            .unwrap()
+            .is_none());
+        secure_kv::put(&conn, "session:alice", "ratchet-state", &enc_key).unwrap();
+        secure_kv::put(&conn, "session:bob", "other-state", &enc_key).unwrap();
+        assert_eq!(
+            secure_kv::read(&conn, "session:alice", &enc_key)
+                .unwrap()
+                .as_deref()
+                .map(String::as_str),
+            Some("ratchet-state")
+        );
+
+        // Overwrite, then delete
+        secure_kv::put(&conn, "session:alice", "rotated", &enc_key).unwrap();
+        assert_eq!(
+            secure_kv::read(&conn, "session:alice", &enc_key)
+                .unwrap()
+                .as_deref()
+                .map(String::as_str),
+            Some("rotated")
+        );
+        assert!(secure_kv::remove(&conn, "session:alice").unwrap());
+        assert!(!secure_kv::remove(&conn, "session:alice").unwrap());
+        assert!(secure_kv::read(&conn, "session:bob", &enc_key)
+            .unwrap()
+            .is_some());
+
+        // The stored row is not plaintext
+        let stored: Vec<u8> = conn
+            .query_row(
+                "SELECT ciphertext FROM secure_kv WHERE key = 'session:bob'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!stored.windows(11).any(|w| w == b"other-state"));
+    }
+
+    #[test]
+    fn test_secure_kv_rejects_moved_row_and_wrong_key() {
+        let conn = migrated_conn();
+        let enc_key = [7u8; 32];
+        secure_kv::put(&conn, "conversation:1", "secret", &enc_key).unwrap();
+
+        // Copy the row under another name: the AAD no longer matches
+        conn.execute(
+            "INSERT INTO secure_kv (key, ciphertext, nonce, updated_at) \
+             SELECT 'conversation:2', ciphertext, nonce, updated_at \
+             FROM secure_kv WHERE key = 'conversation:1'",
+            [],
+        )
+        .unwrap();
+        let err = secure_kv::read(&conn, "conversation:2", &enc_key).unwrap_err();
+        assert!(err.starts_with("Decryption failed"), "{err}");
+
+        assert!(secure_kv::read(&conn, "conversation:1", &[8u8; 32]).is_err());
+        assert!(secure_kv_key("abcd").is_err());
+    }
+
+    /// Database with one secure_kv entry and one sealed note under `old_key`
+    ///
+    /// No policy is registered, as after a restart.
+    fn rewrap_fixture(old_key: &[u8; 32]) -> Connection {
+        let conn = migrated_conn();
+        conn.execute_batch("CREATE TABLE private_notes (id TEXT PRIMARY KEY, body TEXT);")
+            .unwrap();
+        let sealed = note_cipher(old_key)
+            .seal("private_notes", "body", &serde_json::json!("meeting at 6"))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO private_notes VALUES ('a', ?1), ('b', 'legacy plaintext'), \
+             ('d', 'ENC:V1:not sealed')",
+            [sealed.as_str()],
+        )
+        .unwrap();
+        secure_kv::put(&conn, "ratchet:alice", "session-state", old_key).unwrap();
+        conn
+    }
+
+    fn note_cipher(key: &[u8; 32]) -> FieldCipher {
+        let policy = FieldEncryptionPolicy::new().with_table("private_notes", &["body"]);
+        FieldCipher::new(policy, Some(Zeroizing::new(key.to_vec())))
+    }
+
+    fn note_body(conn: &Connection, id: &str) -> Value {
+        conn.query_row(
+            "SELECT body FROM private_notes WHERE id = ?1",
+            [id],
+            |row| row.get::<_, String>(0),
+        )
+        .map(Value::String)
+        .unwrap()
+    }
+
+    #[test]
+    fn test_rewrap_secrets_moves_values_to_new_key() {
+        let (old_key, new_key) = ([7u8; 32], [9u8; 32]);
+        let mut conn = rewrap_fixture(&old_key);
+
+        assert_eq!(rewrap_all(&mut conn, &old_key, &new_key).unwrap(), 2);
+
+        assert!(secure_kv::read(&conn, "ratchet:alice", &old_key).is_err());
+        assert_eq!(
+            secure_kv::read(&conn, "ratchet:alice", &new_key)
+                .unwrap()
+                .as_deref()
+                .map(String::as_str),
+            Some("session-state")
+        );
+        let body = note_body(&conn, "a");
+        assert!(note_cipher(&old_key)
+            .open("private_notes", "body", body.clone())
+            .is_err());
+        assert_eq!(
+            note_cipher(&new_key)
+                .open("private_notes", "body", body)
+                .unwrap(),
+            serde_json::json!("meeting at 6")
+        );
+        assert_eq!(note_body(&conn, "b"), serde_json::json!("legacy plaintext"));
+        assert_eq!(
+            note_body(&conn, "d"),
+            serde_json::json!("ENC:V1:not sealed")
+        );
+    }
+
+    #[test]
+    fn test_failed_rewrap_leaves_old_key_working() {
+        let (old_key, new_key) = ([7u8; 32], [9u8; 32]);
+        let mut conn = rewrap_fixture(&old_key);
+        // A note sealed under some other key fails after secure_kv was re-wrapped
+        let stray = note_cipher(&[8u8; 32])
+            .seal("private_notes", "body", &serde_json::json!("stray"))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO private_notes VALUES ('c', ?1)",
+            [stray.as_str()],
+        )
+        .unwrap();
+
+        let err = rewrap_all(&mut conn, &old_key, &new_key).unwrap_err();
+        assert!(err.starts_with("Decryption failed"), "{err}");
+
+        assert_eq!(
+            secure_kv::read(&conn, "ratchet:alice", &old_key)
+                .unwrap()
+                .as_deref()
+                .map(String::as_str),
+            Some("session-state")
+        );
+        assert!(secure_kv::read(&conn, "ratchet:alice", &new_key).is_err());
+        assert_eq!(
+            note_cipher(&old_key)
+                .open("private_notes", "body", note_body(&conn, "a"))
+                .unwrap(),
+            serde_json::json!("meeting at 6")
+        );
+    }
+
+    fn field_cipher(key: Option<[u8; 32]>) -> FieldCipher {
+        let policy = FieldEncryptionPolicy::new().with_table("chat", &["body"]);
+        FieldCipher::new(policy, key.map(|k| Zeroizing::new(k.to_vec())))
+    }
+
+    #[test]
+    fn test_observe_follows_record_through_db_put() {
+        let path = std::env::temp_dir().join(format!(
+            "buildit-observe-{}.db",
+            uuid::Uuid::new_v4().simple()
+        ));
+        let db = Database::new(path.clone());
+        db.open("test-key").unwrap();
+        db.with_connection(|conn| {
+            conn.execute_batch("CREATE TABLE chat (id TEXT PRIMARY KEY, body TEXT);")
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+        let put = |id: &str, body: &str| {
+            let record = snake(serde_json::json!({ "id": id, "body": body }));
+            db.with_connection(|conn| upsert_record(conn, "chat", &record))
+                .unwrap();
+        };
+
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink_changes = Arc::clone(&changes);
+        db.observe(
+            "chat",
+            "id",
+            "m1",
+            Arc::new(move |change| sink_changes.lock().push(change)),
+        )
+        .unwrap();
+
+        // Created after the observation, then rewritten by INSERT OR REPLACE,
+        // which gives the record a new rowid each time
+        put("m1", "first");
+        put("m2", "other");
+        put("m1", "second");
+        put("m1", "third");
+        db.with_connection(|conn| {
+            conn.execute("DELETE FROM chat WHERE id = 'm1'", [])
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+
+        let actions: Vec<String> = changes.lock().iter().map(|c| c.action.clone()).collect();
+        assert_eq!(actions, ["INSERT", "INSERT", "INSERT", "DELETE"]);
+        assert!(changes.lock().iter().all(|c| c.key == "m1"));
+
+        db.close();
+        for suffix in ["", "-wal", "-shm", ".cipher.json"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    fn chat_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE chat (
+                id TEXT PRIMARY KEY,
+                conversation_id TEXT,
+                body TEXT,
+                created_at INTEGER
+            );",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_field_policy_encrypts_configured_columns() {
+        let conn = chat_conn();
+        let cipher = field_cipher(Some([7u8; 32]));
+
+        for (id, body) in [("m1", "meet at noon"), ("m2", "bring water")] {
+            let mut record = snake(serde_json::json!({
+                "id": id,
+                "conversationId": "c1",
+                "body": body,
+                "createdAt": 1700000000
+            }));
+            seal_record(&cipher, "chat", &mut record).unwrap();
+            upsert_record(&conn, "chat", &record).unwrap();
+        }
+
+        // The body is stored as ciphertext, the other columns as plaintext
+        let (conversation, body): (String, String) = conn
+            .query_row(
+                "SELECT conversation_id, body FROM chat WHERE id = 'm1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(conversation, "c1");
+        assert!(body.starts_with(SEALED_PREFIX), "{body}");
+        assert!(!body.contains("noon"));
+
+        // Plaintext columns stay queryable
+        let count: u32 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM chat WHERE conversation_id = 'c1' AND created_at > 0",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // Reads decrypt transparently
+        let mut rows = get_many(&conn, "chat", &["m2".into(), "m1".into()], 10).unwrap();
+        open_rows(&cipher, "chat", &mut rows).unwrap();
+        assert_eq!(rows[0]["body"], "bring water");
+        assert_eq!(rows[1]["body"], "meet at noon");
+        assert_eq!(rows[1]["conversationId"], "c1");
+
+        // Without the field key the ciphertext cannot be read
+        let mut rows = get_many(&conn, "chat", &["m1".into()], 10).unwrap();
+        assert!(open_rows(&field_cipher(None), "chat", &mut rows).is_err());
+    }
+
+    #[test]
+    fn test_field_policy_keeps_legacy_rows_and_blocks_queries() {
+        let conn = chat_conn();
+        let cipher = field_cipher(Some([7u8; 32]));
+        conn.execute(
+            "INSERT INTO chat (id, conversation_id, body) VALUES ('old', 'c1', 'plain')",
+            [],
+        )
+        .unwrap();
+
+        let mut rows = get_many(&conn, "chat", &["old".into()], 10).unwrap();
+        open_rows(&cipher, "chat", &mut rows).unwrap();
+        assert_eq!(rows[0]["body"], "plain");
+
+        // Writing needs the key; other tables are untouched by the policy
+        let mut record = snake(serde_json::json!({ "id": "m1", "body": "hi" }));
+        assert!(seal_record(&field_cipher(None), "chat", &mut record).is_err());
+        let mut note = snake(serde_json::json!({ "id": "n1", "body": "hi" }));
+        seal_record(&field_cipher(None), "notes", &mut note).unwrap();
+        assert_eq!(note["body"], "hi");
+        // Not even a write leaving the sealed column out
+        let mut bare = snake(serde_json::json!({ "id": "m2", "conversationId": "c1" }));
+        let err =
+            CommandError::from(seal_record(&field_cipher(None), "chat", &mut bare).unwrap_err());
+        assert_eq!(err.code, "db_field_key_missing");
+
+        assert!(check_queryable(&cipher, "chat", "conversation_id").is_ok());
+        let err = CommandError::from(check_queryable(&cipher, "chat", "body").unwrap_err());
+        assert_eq!(err.code, "db_column_encrypted");
+    }
+
+    #[test]
+    fn test_query_json_opens_sealed_columns_and_refuses_them_as_paths() {
+        let conn = chat_conn();
+        let cipher = field_cipher(Some([7u8; 32]));
+        for (id, conversations, body) in [
+            ("m1", r#"["c1", "c2"]"#, r#"{"tag": "urgent"}"#),
+            ("m2", r#"["c2"]"#, "other"),
+        ] {
+            let mut record = snake(serde_json::json!({
+                "id": id,
+                "conversationId": conversations,
+                "body": body,
+            }));
+            seal_record(&cipher, "chat", &mut record).unwrap();
+            upsert_record(&conn, "chat", &record).unwrap();
+        }
+
+        let rows = query_json(&conn, &cipher, "chat", "conversationId", &"c1".into()).unwrap();
+        assert_eq!(ids(&rows), ["m1"]);
+        assert_eq!(rows[0]["body"], r#"{"tag": "urgent"}"#);
+
+        // Without the key the matching rows can't be opened
+        let locked = field_cipher(None);
+        assert!(query_json(&conn, &locked, "chat", "conversationId", &"c1".into()).is_err());
+
+        for path in ["body", "body.tag"] {
+            let err = query_json(&conn, &cipher, "chat", path, &"urgent".into()).unwrap_err();
+            assert_eq!(
+                CommandError::from(err).code,
+                "db_column_encrypted",
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_grouped_by_conversation() {
+        let conn = chat_conn();
+        let cipher = field_cipher(Some([7u8; 32]));
+        for (id, conversation, created_at) in [
+            ("m1", Some("c1"), 1),
+            ("m2", Some("c2"), 1),
+            ("m3", Some("c1"), 2),
+            ("m4", Some("c1"), 2),
+            ("m5", None, 2),
+        ] {
+            conn.execute(
+                "INSERT INTO chat (id, conversation_id, created_at) VALUES (?1, ?2, ?3)",
+                rusqlite::params![id, conversation, created_at],
+            )
+            .unwrap();
+        }
+
+        let groups = count_grouped(&conn, &cipher, "chat", "conversationId", None).unwrap();
+        let counts: Vec<(Value, u32)> = groups.iter().map(|g| (g.value.clone(), g.count)).collect();
+        assert_eq!(
+            counts,
+            vec![(Value::Null, 1), ("c1".into(), 3), ("c2".into(), 1)]
+        );
+        assert!(groups.iter().all(|g| g.column == "conversationId"));
+
+        let filter = HashMap::from([("createdAt".to_string(), Value::from(2))]);
+        let groups =
+            count_grouped(&conn, &cipher, "chat", "conversationId", Some(&filter)).unwrap();
+        let counts: Vec<(Value, u32)> = groups.iter().map(|g| (g.value.clone(), g.count)).collect();
+        assert_eq!(counts, vec![(Value::Null, 1), ("c1".into(), 2)]);
+
+        // Column names are validated, and ciphertext cannot be grouped
+        assert!(count_grouped(&conn, &cipher, "chat", "id\" OR 1", None).is_err());
+        assert!(count_grouped(&conn, &cipher, "chat", "body", None).is_err());
+    }
+
+    #[test]
+    fn test_import_legacy_messages_skips_corrupt() {
+        let mut conn = migrated_conn();
+        let policy = FieldEncryptionPolicy::new().with_table("messages", &["content"]);
+        let cipher = FieldCipher::new(policy, Some(Zeroizing::new(vec![7u8; 32])));
+
+        // Fixed NIP-04 vector: new messages can no longer be NIP-04 encrypted
+        let us = buildit_crypto::KeyPair {
+            private_key: vec![3u8; 32],
+            public_key: buildit_crypto::get_public_key(vec![3u8; 32]).unwrap(),
+        };
+        let bob = buildit_crypto::KeyPair {
+            private_key: vec![2u8; 32],
+            public_key: buildit_crypto::get_public_key(vec![2u8; 32]).unwrap(),
+        };
+        let dm = |id: &str, from: &str, to: &str, content: String| LegacyMessage {
+            id: id.to_string(),
+            author_pubkey: from.to_string(),
+            recipient_pubkey: to.to_string(),
+            content,
+            kind: 4,
+            created_at: 1700000000,
+            tags: vec![vec!["p".to_string(), to.to_string()]],
+        };
+        let messages = vec![
+            dm(
+                "m1",
+                &bob.public_key,
+                &us.public_key,
+                "oUegg6jUHQsYDaK4sCm+iQ==?iv=5O80XPcRcjXSFSWChVBVYQ==".to_string(),
+            ),
+            // Our own outgoing message decrypts with the recipient's key
+            dm(
+                "m2",
+                &us.public_key,
+                &bob.public_key,
+                buildit_crypto::nip44_encrypt(
+                    us.private_key.clone(),
+                    bob.public_key.clone(),
+                    "nip44 reply".to_string(),
+                )
+                .unwrap(),
+            ),
+            dm(
+                "m3",
+                &bob.public_key,
+                &us.public_key,
+                "not ciphertext".to_string(),
+            ),
+            dm(
+                "m4",
+                &bob.public_key,
+                &us.public_key,
+                "%%%?iv=%%%".to_string(),
+            ),
+        ];
+
+        let results = import_legacy(
+            &mut conn,
+            &cipher,
+            &us.private_key,
+            &us.public_key,
+            &messages,
+        )
+        .unwrap();
+
+        let summary: Vec<(&str, bool, Option<LegacyScheme>)> = results
+            .iter()
+            .map(|r| (r.id.as_str(), r.imported, r.scheme))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("m1", true, Some(LegacyScheme::Nip04)),
+                ("m2", true, Some(LegacyScheme::Nip44)),
+                ("m3", false, None),
+                ("m4", false, None),
+            ]
+        );
+        assert!(results[2].error.as_deref().unwrap().contains("Nip44"));
+        assert!(results[3].error.as_deref().unwrap().contains("Nip04"));
+
+        // Stored sealed under the field policy, readable through it
+        let stored: String = conn
+            .query_row("SELECT content FROM messages WHERE id = 'm1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(stored.starts_with(SEALED_PREFIX));
+        let mut rows = get_many(&conn, "messages", &["m1".into(), "m2".into()], 10).unwrap();
+        open_rows(&cipher, "messages", &mut rows).unwrap();
+        assert_eq!(rows[0]["content"], "old nip04 hello");
+        assert_eq!(rows[1]["content"], "nip44 reply");
+        assert_eq!(rows[1]["authorPubkey"], us.public_key);
+        assert_eq!(rows[0]["timestamp"], 1700000000);
+
+        let count: u32 = conn
+            .query_row("SELECT COUNT(*) FROM messages", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+
+        // A second import keeps the stored messages
+        let forged = vec![dm(
+            "m2",
+            &bob.public_key,
+            &us.public_key,
+            buildit_crypto::nip44_encrypt(
+                bob.private_key.clone(),
+                us.public_key.clone(),
+                "forged".to_string(),
+            )
+            .unwrap(),
+        )];
+        let results =
+            import_legacy(&mut conn, &cipher, &us.private_key, &us.public_key, &forged).unwrap();
+        assert!(!results[0].imported);
+        let mut rows = get_many(&conn, "messages", &["m2".into()], 10).unwrap();
+        open_rows(&cipher, "messages", &mut rows).unwrap();
+        assert_eq!(rows[0]["content"], "nip44 reply");
+    }
+
+    #[test]
+    fn test_import_legacy_reencrypts_pre_spec_nip44() {
+        let mut conn = migrated_conn();
+        let policy = FieldEncryptionPolicy::new().with_table("messages", &["content"]);
+        let cipher = FieldCipher::new(policy, Some(Zeroizing::new(vec![7u8; 32])));
+
+        // Written by the pre-spec NIP-44 code from secret key 1 to secret key 2
+        let mut sender = vec![0u8; 32];
+        sender[31] = 1;
+        let mut us = vec![0u8; 32];
+        us[31] = 2;
+        let sender_pubkey = buildit_crypto::get_public_key(sender).unwrap();
+        let our_pubkey = buildit_crypto::get_public_key(us.clone()).unwrap();
+        let messages = vec![LegacyMessage {
+            id: "old".to_string(),
+            author_pubkey: sender_pubkey,
+            recipient_pubkey: our_pubkey.clone(),
+            content: "AiB5siVtdCjuJQSupjII8ic3LaDFROPi1lIxE5mjPT+ZuE7aibrXk0IYjLKHA467rf3jgLUa97rZrbvIQKZ15qo+dUJyXqeAwvMnqj4NeJ9EJKrPYylgrdwB1DwsfzRzTQQnbBKYP0fnqj9scb9849nx2Q==".to_string(),
+            kind: 14,
+            created_at: 1700000000,
+            tags: vec![],
+        }];
+
+        let results = import_legacy(&mut conn, &cipher, &us, &our_pubkey, &messages).unwrap();
+        assert!(results[0].imported, "{:?}", results[0].error);
+        assert_eq!(results[0].scheme, Some(LegacyScheme::Nip44PreSpec));
+
+        let mut rows = get_many(&conn, "messages", &["old".into()], 10).unwrap();
+        open_rows(&cipher, "messages", &mut rows).unwrap();
+        assert_eq!(rows[0]["content"], "pre-spec payload");
+    }
 }
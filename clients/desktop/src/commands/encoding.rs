@@ -0,0 +1,102 @@
+//! Binary input decoding shared by the command modules
+//!
+//! Frontends hand binary values over IPC as strings. Hex is the documented
+//! format, but base64 is common for binary in JS, so key, nonce and
+//! ciphertext parameters accept either.
+
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+
+/// Encodings tried by `decode_flexible`, in order
+const ATTEMPTED_ENCODINGS: &str = "hex, base64, base64url";
+
+/// Decode a hex or base64 (standard or URL-safe) string
+///
+/// Hex is tried first. When `expected_len` is set, the first encoding that
+/// decodes to exactly that many bytes wins, so a 64-character hex key is never
+/// mistaken for 48 bytes of base64. Padding is optional for base64.
+pub fn decode_flexible(s: &str, expected_len: Option<usize>) -> Result<Vec<u8>, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("value is empty".to_string());
+    }
+
+    let candidates = [
+        hex::decode(s).ok(),
+        STANDARD.decode(s).ok(),
+        STANDARD_NO_PAD.decode(s).ok(),
+        URL_SAFE.decode(s).ok(),
+        URL_SAFE_NO_PAD.decode(s).ok(),
+    ];
+
+    let mut decoded_lens = Vec::new();
+    for bytes in candidates.into_iter().flatten() {
+        match expected_len {
+            Some(len) if bytes.len() != len => decoded_lens.push(bytes.len()),
+            _ => return Ok(bytes),
+        }
+    }
+
+    match expected_len {
+        Some(len) if !decoded_lens.is_empty() => Err(format!(
+            "expected {} bytes but decoded to {} (tried {})",
+            len, decoded_lens[0], ATTEMPTED_ENCODINGS
+        )),
+        _ => Err(format!("not valid {}", ATTEMPTED_ENCODINGS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        // 0xfb/0xff bytes make standard and URL-safe base64 differ
+        let mut bytes: Vec<u8> = (0u8..32).collect();
+        bytes[0] = 0xfb;
+        bytes[1] = 0xff;
+        bytes
+    }
+
+    #[test]
+    fn test_same_value_decodes_from_every_encoding() {
+        let bytes = sample();
+        let encodings = [
+            hex::encode(&bytes),
+            STANDARD.encode(&bytes),
+            URL_SAFE.encode(&bytes),
+            URL_SAFE_NO_PAD.encode(&bytes),
+        ];
+        assert_ne!(encodings[1], encodings[2]);
+
+        for encoded in encodings {
+            assert_eq!(
+                decode_flexible(&encoded, Some(32)).unwrap(),
+                bytes,
+                "{encoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_hex_preferred_when_length_matches() {
+        // All-hex-digit input is also valid base64; hex must win
+        let encoded = "ab".repeat(32);
+        assert_eq!(decode_flexible(&encoded, Some(32)).unwrap(), vec![0xab; 32]);
+        assert_eq!(decode_flexible(&encoded, None).unwrap(), vec![0xab; 32]);
+    }
+
+    #[test]
+    fn test_wrong_length_errors() {
+        let err = decode_flexible(&hex::encode([7u8; 16]), Some(32)).unwrap_err();
+        assert!(err.contains("expected 32 bytes"), "{err}");
+        assert!(err.contains(ATTEMPTED_ENCODINGS), "{err}");
+    }
+
+    #[test]
+    fn test_invalid_input_names_encodings() {
+        let err = decode_flexible("not*valid", None).unwrap_err();
+        assert_eq!(err, format!("not valid {}", ATTEMPTED_ENCODINGS));
+        assert!(decode_flexible("  ", None).is_err());
+    }
+}
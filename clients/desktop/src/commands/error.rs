@@ -0,0 +1,472 @@
+//! Structured command errors shared by all Tauri command modules
+//!
+//! Every error crossing the IPC boundary carries a stable, machine-readable
+//! `code` so the frontend can branch on it instead of string-matching the
+//! human-readable `message`. Codes are part of the frontend contract: add new
+//! ones freely, but never rename or reuse an existing code.
+//!
+//! Commands returning `CommandResult` keep the plain `error` string next to
+//! `error_detail`. The `db_*` commands instead reject with a `CommandError`
+//! object where they used to reject with a string; the web DAL
+//! (`clients/web/src/core/storage/dal.ts`) turns it into a `DatabaseError`
+//! carrying the code, so other frontend callers must go through the DAL.
+
+use crate::ble::chunk::ChunkError;
+use crate::ble::manager::BleError;
+use crate::ble::mesh::MeshError;
+use crate::commands::db_commands::{
+    INVALID_COLUMN_ERROR, INVALID_JSON_QUERY_ERROR, INVALID_RECORD_ERROR, INVALID_TABLE_ERROR,
+    READ_ONLY_QUERY_ERROR, UNVERSIONED_TABLE_ERROR,
+};
+use crate::contacts::INVALID_PUBKEY_ERROR;
+use crate::crypto::keyring::KeyringError;
+use crate::db::field_encryption::{ENCRYPTED_COLUMN_ERROR, FIELD_KEY_MISSING_ERROR};
+use crate::db::pool::INVALID_CIPHER_ERROR;
+use crate::db::secure_kv::EMPTY_KEY_ERROR;
+use crate::db::{CONFLICT_ERROR, DB_CLOSED_ERROR, DB_SEALED_ERROR};
+use crate::nostr::{CertPinError, RelayError};
+use crate::{ActiveIdentityError, IdentityRotationError};
+use buildit_crypto::CryptoError;
+use serde::{Deserialize, Serialize};
+
+/// Prefixes of database errors caused by the caller's input
+const DB_INPUT_ERRORS: &[&str] = &[
+    INVALID_TABLE_ERROR,
+    INVALID_COLUMN_ERROR,
+    INVALID_RECORD_ERROR,
+    INVALID_JSON_QUERY_ERROR,
+    READ_ONLY_QUERY_ERROR,
+    INVALID_CIPHER_ERROR,
+    INVALID_PUBKEY_ERROR,
+    EMPTY_KEY_ERROR,
+];
+
+/// SQLite's message for SQLITE_BUSY
+const SQLITE_BUSY_MESSAGE: &str = "database is locked";
+
+/// SQLite's message for SQLITE_LOCKED
+const SQLITE_LOCKED_MESSAGE: &str = "database table is locked";
+
+/// Suffix of SQLite's messages for SQLITE_CONSTRAINT ("UNIQUE constraint failed")
+const SQLITE_CONSTRAINT_MESSAGE: &str = "constraint failed";
+
+/// Structured error returned to the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandError {
+    /// Stable machine-readable error code (snake_case)
+    pub code: String,
+    /// Human-readable description
+    pub message: String,
+    /// Whether retrying the same call may succeed
+    pub retryable: bool,
+}
+
+impl CommandError {
+    pub fn new(code: &str, message: impl Into<String>, retryable: bool) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            retryable,
+        }
+    }
+
+    /// Malformed or out-of-range input from the caller
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new("invalid_input", message, false)
+    }
+
+    /// The command needs an active identity and none is set
+    pub fn no_active_identity() -> Self {
+        Self::new("identity_not_set", "No active identity", false)
+    }
+
+    /// Classify a database error message
+    ///
+    /// The database layer reports errors as strings, so the code is derived
+    /// from the error prefix constants exported next to each producer. The
+    /// only substrings matched are SQLite's own fixed messages for busy
+    /// databases and constraint violations, which callers wrap in context.
+    pub fn from_db_message(message: String) -> Self {
+        let (code, retryable) = if message.starts_with(DB_CLOSED_ERROR) {
+            ("db_locked", false)
+        } else if message.starts_with(DB_SEALED_ERROR) {
+            ("db_sealed", false)
+        } else if message.starts_with(UNVERSIONED_TABLE_ERROR) {
+            ("db_unversioned_table", false)
+        } else if message.starts_with(ENCRYPTED_COLUMN_ERROR) {
+            ("db_column_encrypted", false)
+        } else if message.starts_with(FIELD_KEY_MISSING_ERROR) {
+            ("db_field_key_missing", false)
+        } else if message.starts_with(CONFLICT_ERROR) {
+            ("db_conflict", false)
+        } else if DB_INPUT_ERRORS
+            .iter()
+            .any(|prefix| message.starts_with(prefix))
+        {
+            ("invalid_input", false)
+        } else if message.contains(SQLITE_BUSY_MESSAGE) || message.contains(SQLITE_LOCKED_MESSAGE) {
+            ("db_busy", true)
+        } else if message.contains(SQLITE_CONSTRAINT_MESSAGE) {
+            ("db_constraint", false)
+        } else {
+            ("db_error", false)
+        };
+        Self::new(code, message, retryable)
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<CryptoError> for CommandError {
+    fn from(e: CryptoError) -> Self {
+        let code = match e {
+            CryptoError::InvalidKey => "crypto_invalid_key",
+            CryptoError::InvalidPublicKey => "crypto_invalid_public_key",
+            CryptoError::InvalidSignature => "crypto_invalid_signature",
+            CryptoError::EncryptionFailed => "crypto_encryption_failed",
+            CryptoError::DecryptionFailed => "crypto_decryption_failed",
+            CryptoError::InvalidPlaintextLength => "crypto_invalid_plaintext_length",
+            CryptoError::InvalidCiphertext => "crypto_invalid_ciphertext",
+            CryptoError::InvalidPadding => "crypto_invalid_padding",
+            CryptoError::InvalidMac => "crypto_invalid_mac",
+            CryptoError::InvalidHex => "crypto_invalid_hex",
+            CryptoError::InvalidJson => "crypto_invalid_json",
+            CryptoError::SigningFailed => "crypto_signing_failed",
+            CryptoError::KeyDerivationFailed => "crypto_key_derivation_failed",
+            CryptoError::RandomGenerationFailed => "crypto_random_generation_failed",
+            CryptoError::InvalidDuressPassword => "crypto_invalid_duress_password",
+            CryptoError::DuressPasswordTooSimilar => "crypto_duress_password_too_similar",
+            CryptoError::KeyDestructionFailed => "crypto_key_destruction_failed",
+            CryptoError::DuressAlertFailed => "crypto_duress_alert_failed",
+            CryptoError::InvalidVersion => "crypto_invalid_version",
+            CryptoError::DuplicateMessage => "crypto_duplicate_message",
+            CryptoError::UnsupportedSessionVersion => "crypto_unsupported_session_version",
+        };
+        let retryable = matches!(e, CryptoError::RandomGenerationFailed);
+        Self::new(code, e.to_string(), retryable)
+    }
+}
+
+impl From<BleError> for CommandError {
+    fn from(e: BleError) -> Self {
+        let (code, retryable) = match e {
+            BleError::NotAvailable => ("ble_not_available", false),
+            BleError::AdapterNotFound => ("ble_adapter_not_found", false),
+            BleError::DeviceNotFound(_) => ("ble_device_not_found", true),
+            BleError::ConnectionFailed(_) => ("ble_connection_failed", true),
+            BleError::ServiceNotFound => ("ble_service_not_found", false),
+            BleError::CharacteristicNotFound => ("ble_characteristic_not_found", false),
+            BleError::WriteFailed(_) => ("ble_write_failed", true),
+            BleError::ReadFailed(_) => ("ble_read_failed", true),
+            BleError::ScanInProgress => ("ble_scan_in_progress", true),
+            BleError::ScanNotRunning => ("ble_scan_not_running", false),
+            BleError::OperationError(_) => ("ble_operation_error", true),
+            BleError::CommitmentVerificationFailed => ("ble_commitment_verification_failed", false),
+            BleError::NotAuthenticated(_) => ("ble_not_authenticated", false),
+            BleError::ScanModeUnsupported(_) => ("ble_scan_mode_unsupported", false),
+            BleError::InvalidDutyCycle(_) => ("invalid_input", false),
+            BleError::Transfer(e) => return e.into(),
+        };
+        Self::new(code, e.to_string(), retryable)
+    }
+}
+
+impl From<ChunkError> for CommandError {
+    fn from(e: ChunkError) -> Self {
+        let code = match e {
+            ChunkError::MessageTooLarge(_) => "chunk_message_too_large",
+            ChunkError::InvalidHeader => "chunk_invalid_header",
+            ChunkError::InvalidChunkIndex(..) => "chunk_invalid_index",
+            ChunkError::CompressionFailed(_) => "chunk_compression_failed",
+            ChunkError::DecompressionFailed(_) => "chunk_decompression_failed",
+            ChunkError::MessageIdMismatch => "chunk_message_id_mismatch",
+            ChunkError::IncompleteMessage => "chunk_incomplete_message",
+            ChunkError::RetriesExhausted(..) => "chunk_retries_exhausted",
+            ChunkError::TooManyPartialTransfers(_) => "chunk_too_many_partial_transfers",
+            ChunkError::IntegrityMismatch => "chunk_integrity_mismatch",
+            ChunkError::InvalidChunkSize(_) => "chunk_invalid_size",
+        };
+        Self::new(code, e.to_string(), false)
+    }
+}
+
+impl From<MeshError> for CommandError {
+    fn from(e: MeshError) -> Self {
+        let code = match e {
+            MeshError::SerializationFailed => "mesh_serialization_failed",
+            MeshError::EncryptionFailed => "mesh_encryption_failed",
+            MeshError::DecryptionFailed => "mesh_decryption_failed",
+            MeshError::SigningFailed => "mesh_signing_failed",
+            MeshError::NotForUs => "mesh_not_for_us",
+        };
+        Self::new(code, e.to_string(), false)
+    }
+}
+
+impl From<KeyringError> for CommandError {
+    fn from(e: KeyringError) -> Self {
+        let (code, retryable) = match e {
+            KeyringError::AccessError(_) => ("keyring_access_error", true),
+            KeyringError::NotFound(_) => ("keyring_not_found", false),
+            KeyringError::StoreError(_) => ("keyring_store_error", true),
+            KeyringError::DeleteError(_) => ("keyring_delete_error", true),
+            KeyringError::InvalidFormat => ("keyring_invalid_format", false),
+            KeyringError::NotSupported => ("keyring_not_supported", false),
+            KeyringError::DuressMode => ("keyring_duress_mode", false),
+        };
+        Self::new(code, e.to_string(), retryable)
+    }
+}
+
+impl From<IdentityRotationError> for CommandError {
+    fn from(e: IdentityRotationError) -> Self {
+        match e {
+            IdentityRotationError::Keyring(e) => e.into(),
+            IdentityRotationError::Mesh(e) => e.into(),
+            IdentityRotationError::DuressMode => duress_mode_error(),
+        }
+    }
+}
+
+impl From<ActiveIdentityError> for CommandError {
+    fn from(e: ActiveIdentityError) -> Self {
+        match e {
+            ActiveIdentityError::Mesh(e) => e.into(),
+            ActiveIdentityError::DuressMode => duress_mode_error(),
+        }
+    }
+}
+
+fn duress_mode_error() -> CommandError {
+    CommandError::new(
+        "identity_duress_mode",
+        "Identity changes are disabled in duress mode",
+        false,
+    )
+}
+
+impl From<RelayError> for CommandError {
+    fn from(e: RelayError) -> Self {
+        let (code, retryable) = match e {
+            RelayError::ConnectionFailed(_) => ("relay_connection_failed", true),
+            RelayError::AlreadyConnected(_) => ("relay_already_connected", false),
+            RelayError::NotConnected(_) => ("relay_not_connected", true),
+            RelayError::SendFailed(_) => ("relay_send_failed", true),
+            RelayError::InvalidUrl(_) => ("relay_invalid_url", false),
+            RelayError::SubscriptionNotFound(_) => ("relay_subscription_not_found", false),
+            RelayError::SerializationError(_) => ("relay_serialization_error", false),
+            RelayError::WebSocketError(_) => ("relay_websocket_error", true),
+            RelayError::CertificatePinningFailed(_) => ("relay_certificate_pinning_failed", false),
+            RelayError::TlsError(_) => ("relay_tls_error", true),
+            RelayError::DuplicateRelay(_) => ("relay_duplicate", false),
+            RelayError::RelayNotFound(_) => ("relay_not_found", false),
+            RelayError::CountUnsupported(_) => ("relay_count_unsupported", false),
+            RelayError::Timeout(_) => ("relay_timeout", true),
+            RelayError::RoleForbidden(_) => ("relay_role_forbidden", false),
+            RelayError::PublishTimeout(_) => ("relay_publish_timeout", true),
+        };
+        Self::new(code, e.to_string(), retryable)
+    }
+}
+
+impl From<CertPinError> for CommandError {
+    fn from(e: CertPinError) -> Self {
+        let (code, retryable) = match e {
+            CertPinError::PinMismatch { .. } => ("cert_pin_mismatch", false),
+            CertPinError::TofuCertificateChanged { .. } => ("cert_tofu_changed", false),
+            CertPinError::NoCertificate => ("cert_missing", false),
+            CertPinError::InvalidCertificate(_) => ("cert_invalid", false),
+            CertPinError::StorageError(_) => ("cert_pin_storage_error", true),
+            CertPinError::TofuStorageCorrupt { .. } => ("cert_pin_storage_corrupt", false),
+            CertPinError::TofuRepairRequired { .. } => ("cert_pin_repair_required", false),
+            CertPinError::ConfigError(_) => ("cert_pin_config_error", false),
+        };
+        Self::new(code, e.to_string(), retryable)
+    }
+}
+
+/// Database helpers and commands report errors as `String`
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::from_db_message(message)
+    }
+}
+
+/// Command result wrapper
+///
+/// `error` keeps the human-readable message for existing frontend callers;
+/// `error_detail` carries the structured error with its stable code.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommandResult<T> {
+    pub success: bool,
+    pub data: Option<T>,
+    pub error: Option<String>,
+    pub error_detail: Option<CommandError>,
+}
+
+impl<T> CommandResult<T> {
+    pub fn ok(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+            error_detail: None,
+        }
+    }
+
+    /// Fail with an input validation message (`invalid_input`)
+    ///
+    /// Only for the command's own argument checks; errors from the database,
+    /// BLE, keyring or other layers go through `fail` to keep their code.
+    pub fn err(error: String) -> Self {
+        Self::fail(CommandError::invalid_input(error))
+    }
+
+    /// Fail with a structured error
+    pub fn fail(error: impl Into<CommandError>) -> Self {
+        let error = error.into();
+        Self {
+            success: false,
+            data: None,
+            error: Some(error.message.clone()),
+            error_detail: Some(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crypto_error_codes() {
+        assert_eq!(
+            CommandError::from(CryptoError::InvalidMac).code,
+            "crypto_invalid_mac"
+        );
+        assert_eq!(
+            CommandError::from(CryptoError::InvalidKey).code,
+            "crypto_invalid_key"
+        );
+        assert!(!CommandError::from(CryptoError::DecryptionFailed).retryable);
+    }
+
+    #[test]
+    fn test_ble_error_codes() {
+        let err = CommandError::from(BleError::DeviceNotFound("AA:BB".to_string()));
+        assert_eq!(err.code, "ble_device_not_found");
+        assert!(err.retryable);
+        assert_eq!(err.message, "Device not found: AA:BB");
+
+        assert_eq!(
+            CommandError::from(BleError::NotAvailable).code,
+            "ble_not_available"
+        );
+    }
+
+    #[test]
+    fn test_mesh_error_codes() {
+        let err = CommandError::from(MeshError::NotForUs);
+        assert_eq!(err.code, "mesh_not_for_us");
+        assert_eq!(err.message, "Mesh message is not addressed to us");
+        assert!(!err.retryable);
+    }
+
+    #[test]
+    fn test_keyring_error_codes() {
+        let err = CommandError::from(KeyringError::NotFound("alice_nostr".to_string()));
+        assert_eq!(err.code, "keyring_not_found");
+        assert!(!err.retryable);
+        assert!(CommandError::from(KeyringError::AccessError("x".to_string())).retryable);
+    }
+
+    #[test]
+    fn test_relay_error_codes() {
+        let err = CommandError::from(RelayError::DuplicateRelay("wss://a".to_string()));
+        assert_eq!(err.code, "relay_duplicate");
+        assert_eq!(err.message, "Relay already configured: wss://a");
+        assert!(CommandError::from(RelayError::ConnectionFailed("x".to_string())).retryable);
+        assert_eq!(
+            CommandError::from(RelayError::CountUnsupported("wss://a".to_string())).code,
+            "relay_count_unsupported"
+        );
+    }
+
+    #[test]
+    fn test_db_error_codes() {
+        assert_eq!(
+            CommandError::from("Database is locked/closed".to_string()).code,
+            "db_locked"
+        );
+        let busy = CommandError::from("Query failed: database is locked".to_string());
+        assert_eq!(busy.code, "db_busy");
+        assert!(busy.retryable);
+        assert_eq!(
+            CommandError::from("Invalid table name: 1abc".to_string()).code,
+            "invalid_input"
+        );
+        assert_eq!(
+            CommandError::from("db_put failed: UNIQUE constraint failed: x.id".to_string()).code,
+            "db_constraint"
+        );
+        assert_eq!(
+            CommandError::from("Conflict: documents d1 is at rev 2, expected 1".to_string()).code,
+            "db_conflict"
+        );
+        assert_eq!(
+            CommandError::from("Prepare failed: boom".to_string()).code,
+            "db_error"
+        );
+        assert_eq!(
+            CommandError::from("Database is sealed in duress mode".to_string()).code,
+            "db_sealed"
+        );
+        assert_eq!(
+            CommandError::from("secure_kv key cannot be empty".to_string()).code,
+            "invalid_input"
+        );
+        // Prefixes only count at the start of the message
+        assert_eq!(
+            CommandError::from("Row fetch failed: Invalid table name".to_string()).code,
+            "db_error"
+        );
+    }
+
+    #[test]
+    fn test_chunk_error_codes() {
+        assert_eq!(
+            CommandError::from(ChunkError::InvalidChunkSize(10)).code,
+            "chunk_invalid_size"
+        );
+        assert_eq!(
+            CommandError::from(ChunkError::MessageTooLarge(1 << 20)).code,
+            "chunk_message_too_large"
+        );
+
+        // A failed transfer keeps the chunk error's code
+        let err = CommandError::from(BleError::Transfer(ChunkError::RetriesExhausted(
+            uuid::Uuid::nil(),
+            3,
+        )));
+        assert_eq!(err.code, "chunk_retries_exhausted");
+    }
+
+    #[test]
+    fn test_command_result_keeps_message() {
+        let result: CommandResult<()> = CommandResult::fail(CryptoError::InvalidMac);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["success"], false);
+        assert_eq!(json["error"], "Invalid MAC");
+        assert_eq!(json["error_detail"]["code"], "crypto_invalid_mac");
+        assert_eq!(json["error_detail"]["retryable"], false);
+
+        let invalid: CommandResult<()> = CommandResult::err("Invalid private key".to_string());
+        assert_eq!(invalid.error_detail.unwrap().code, "invalid_input");
+    }
+}
@@ -3,7 +3,11 @@
 //! These commands are exposed to the frontend via Tauri's IPC mechanism.
 
 pub mod ble_commands;
+pub mod capability_commands;
 pub mod crypto_commands;
 pub mod db_commands;
+pub mod encoding;
+pub mod error;
 pub mod nostr_commands;
+pub mod notification_commands;
 pub mod storage_commands;
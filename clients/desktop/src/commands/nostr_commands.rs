@@ -1,39 +1,30 @@
 //! Nostr Tauri commands for relay communication and NIP-17 gift wrapping
 
+use super::encoding::decode_flexible;
+pub use super::error::CommandResult;
+use crate::db::Database;
+use crate::nostr::cert_pinning::TofuStorageStatus;
+use crate::nostr::defaults::{self, DefaultRelay, RelayTestResult, RELAY_TEST_TIMEOUT};
+use crate::nostr::outbox::{self, OutboxEntry};
+use crate::nostr::registry::{
+    self, RelayConnectResult, RelayInfo, DEFAULT_CONNECT_CONCURRENCY, RELAY_CONNECT_CHANNEL,
+    STARTUP_CONNECT_TIMEOUT,
+};
+use crate::nostr::relay::{
+    publish_to_relays, NostrRelay, PublishResult, RelayError, RelayRole, PUBLISH_ACK_TIMEOUT,
+};
+use crate::nostr::subscriptions::{EventSink, RELAY_EVENT_CHANNEL};
+use crate::nostr::types::Filter;
+use crate::nostr::watermarks::{self, WatermarkStore};
 use crate::AppState;
 use buildit_crypto::{
-    create_gift_wrap, create_rumor, create_seal, sign_event, unwrap_gift_wrap, verify_event,
-    NostrEvent, UnsignedEvent,
+    create_gift_wrap, create_rumor, create_seal, sign_event, unwrap_gift_wrap, validate_event,
+    verify_event, NostrEvent, UnsignedEvent, ValidationReport,
 };
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
-use tauri::State;
-
-/// Command result wrapper
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CommandResult<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
-
-impl<T> CommandResult<T> {
-    pub fn ok(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn err(error: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error),
-        }
-    }
-}
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Manager, State};
 
 /// Unwrap result from NIP-17
 #[derive(Debug, Serialize, Deserialize)]
@@ -45,43 +36,63 @@ pub struct UnwrapResponse {
 
 /// Sign a Nostr event
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn sign_nostr_event(
     private_key_hex: String,
     event: UnsignedEvent,
 ) -> Result<CommandResult<NostrEvent>, String> {
-    let private_key = match hex::decode(&private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid private key".to_string())),
+    let private_key = match decode_flexible(&private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => return Ok(CommandResult::err(format!("Invalid private key: {e}"))),
     };
 
     match sign_event(private_key, event) {
         Ok(signed) => Ok(CommandResult::ok(signed)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Verify a Nostr event signature
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn verify_nostr_event(event: NostrEvent) -> Result<CommandResult<bool>, String> {
     let verified = verify_event(event);
     Ok(CommandResult::ok(verified))
 }
 
+/// Fully validate a Nostr event before storing or forwarding it
+///
+/// Recomputes the id, verifies the signature and checks the hex shape of
+/// `id`, `pubkey` and `sig`, the kind range and the tags. Every problem is
+/// reported, not just the first.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn validate_nostr_event(
+    event: NostrEvent,
+) -> Result<CommandResult<ValidationReport>, String> {
+    Ok(CommandResult::ok(validate_event(&event)))
+}
+
 /// Create a NIP-17 gift-wrapped message
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn gift_wrap_message(
     sender_private_key_hex: String,
     recipient_pubkey: String,
     content: String,
 ) -> Result<CommandResult<NostrEvent>, String> {
-    let sender_private_key = match hex::decode(&sender_private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid sender private key".to_string())),
+    let sender_private_key = match decode_flexible(&sender_private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => {
+            return Ok(CommandResult::err(format!(
+                "Invalid sender private key: {e}"
+            )))
+        }
     };
 
     let sender_pubkey = match buildit_crypto::get_public_key(sender_private_key.clone()) {
         Ok(pk) => pk,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::fail(e)),
     };
 
     let now = SystemTime::now()
@@ -97,36 +108,36 @@ pub async fn gift_wrap_message(
         now,
     ) {
         Ok(r) => r,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::fail(e)),
     };
 
     // Create seal
-    let seal = match create_seal(
-        sender_private_key,
-        recipient_pubkey.clone(),
-        rumor,
-        now,
-    ) {
+    let seal = match create_seal(sender_private_key, recipient_pubkey.clone(), rumor, now) {
         Ok(s) => s,
-        Err(e) => return Ok(CommandResult::err(e.to_string())),
+        Err(e) => return Ok(CommandResult::fail(e)),
     };
 
     // Create gift wrap
     match create_gift_wrap(recipient_pubkey, seal, now) {
         Ok(gift_wrap) => Ok(CommandResult::ok(gift_wrap)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Unwrap a NIP-17 gift-wrapped message
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn unwrap_gift_message(
     recipient_private_key_hex: String,
     gift_wrap: NostrEvent,
 ) -> Result<CommandResult<UnwrapResponse>, String> {
-    let recipient_private_key = match hex::decode(&recipient_private_key_hex) {
-        Ok(k) if k.len() == 32 => k,
-        _ => return Ok(CommandResult::err("Invalid recipient private key".to_string())),
+    let recipient_private_key = match decode_flexible(&recipient_private_key_hex, Some(32)) {
+        Ok(k) => k,
+        Err(e) => {
+            return Ok(CommandResult::err(format!(
+                "Invalid recipient private key: {e}"
+            )))
+        }
     };
 
     match unwrap_gift_wrap(recipient_private_key, gift_wrap) {
@@ -135,6 +146,369 @@ pub async fn unwrap_gift_message(
             sender_pubkey: result.sender_pubkey,
             seal_verified: result.seal_verified,
         })),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Publish a signed event to the managed relays
+///
+/// Publishes to `relay_urls`, or to every relay in `AppState` when `None`, and
+/// returns one result per relay. Relays that reject the event, time out, are
+/// not pinned (when `require_pinned_for_write` is set) or are not configured
+/// still get an entry with `accepted: false`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn publish_event(
+    state: State<'_, AppState>,
+    event: NostrEvent,
+    relay_urls: Option<Vec<String>>,
+) -> Result<CommandResult<Vec<PublishResult>>, String> {
+    if !verify_event(event.clone()) {
+        return Ok(CommandResult::err("Invalid event signature".to_string()));
+    }
+
+    let (relays, unknown) = registry::writable_relays(&state.nostr_relays, relay_urls.as_deref());
+
+    let mut results = publish_to_relays(&relays, &event, PUBLISH_ACK_TIMEOUT).await;
+    results.extend(
+        unknown
+            .iter()
+            .map(|url| PublishResult::rejected(url, "relay not configured")),
+    );
+
+    log::info!(
+        "Published event {} to {}/{} relays",
+        event.id,
+        results.iter().filter(|r| r.accepted).count(),
+        results.len()
+    );
+
+    Ok(CommandResult::ok(results))
+}
+
+/// Queue a signed event in the outbox to be published in the background
+///
+/// The event is kept until one of `relay_urls` (every writable relay when
+/// `None`) accepts it, so it is sent once the relays are reachable again.
+/// Entries that run out of attempts are reported with `outbox-failed`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn queue_event(
+    db: State<'_, Database>,
+    event: NostrEvent,
+    relay_urls: Option<Vec<String>>,
+) -> Result<CommandResult<()>, String> {
+    if !verify_event(event.clone()) {
+        return Ok(CommandResult::err("Invalid event signature".to_string()));
+    }
+
+    let relay_urls = relay_urls.unwrap_or_default();
+    match db.with_connection(|conn| outbox::enqueue(conn, &event, &relay_urls, outbox::now_secs()))
+    {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// List the outbox: events waiting to be published and ones that failed
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn list_outbox(
+    db: State<'_, Database>,
+) -> Result<CommandResult<Vec<OutboxEntry>>, String> {
+    match db.with_connection(outbox::list_entries) {
+        Ok(entries) => Ok(CommandResult::ok(entries)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Retry a failed outbox entry from scratch, returning whether it was failed
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn retry_outbox_entry(
+    db: State<'_, Database>,
+    event_id: String,
+) -> Result<CommandResult<bool>, String> {
+    match db.with_connection(|conn| outbox::retry(conn, &event_id, outbox::now_secs())) {
+        Ok(retried) => Ok(CommandResult::ok(retried)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Discard an outbox entry without publishing it, returning whether it existed
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn remove_outbox_entry(
+    db: State<'_, Database>,
+    event_id: String,
+) -> Result<CommandResult<bool>, String> {
+    match db.with_connection(|conn| outbox::remove(conn, &event_id)) {
+        Ok(removed) => Ok(CommandResult::ok(removed)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Add and connect a relay at runtime
+///
+/// Fails with `relay_duplicate` if the relay is already configured. The
+/// connection uses the bundled certificate pins plus the persisted TOFU
+/// pins. `role` defaults
+/// to read-write. Dropped connections and private messages on the relay
+/// raise system notifications as the notification policy allows.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn add_relay(
+    state: State<'_, AppState>,
+    url: String,
+    role: Option<RelayRole>,
+) -> Result<CommandResult<RelayInfo>, String> {
+    let pin_store = Arc::clone(&state.pin_store);
+    match registry::add_relay(&state.nostr_relays, &url, role.unwrap_or_default(), |url| {
+        Ok(NostrRelay::new(url, pin_store))
+    })
+    .await
+    {
+        Ok(relay) => {
+            tokio::spawn(Arc::clone(&state.notifications).watch_relay(relay.subscribe_events()));
+            Ok(CommandResult::ok(RelayInfo::of(&relay).await))
+        }
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Add and connect several relays concurrently
+///
+/// At most `max_concurrent` (default 4) connections are attempted at once
+/// and any still pending after `timeout_ms` (default 10s) are abandoned.
+/// Each result is emitted on `nostr-relay-connect` as soon as it settles,
+/// so the UI can show progress and carry on once enough relays are up.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn connect_relays(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    urls: Vec<String>,
+    max_concurrent: Option<usize>,
+    timeout_ms: Option<u64>,
+) -> Result<CommandResult<Vec<RelayConnectResult>>, String> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(STARTUP_CONNECT_TIMEOUT);
+    let results = registry::connect_all_parallel(
+        &state.nostr_relays,
+        &urls,
+        RelayRole::default(),
+        |url| Ok(NostrRelay::new(url, Arc::clone(&state.pin_store))),
+        max_concurrent.unwrap_or(DEFAULT_CONNECT_CONCURRENCY),
+        tokio::time::Instant::now() + timeout,
+        |result| {
+            let _ = app.emit(RELAY_CONNECT_CHANNEL, result);
+        },
+    )
+    .await;
+
+    for result in results.iter().filter(|r| r.connected) {
+        if let Some(relay) = registry::get_relay(&state.nostr_relays, &result.url) {
+            tokio::spawn(Arc::clone(&state.notifications).watch_relay(relay.subscribe_events()));
+        }
+    }
+    Ok(CommandResult::ok(results))
+}
+
+/// Restrict a configured relay to reading, writing, or both
+///
+/// Publishing to a read-only relay and subscribing to a write-only relay
+/// fail with `relay_role_forbidden`.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn set_relay_role(
+    state: State<'_, AppState>,
+    url: String,
+    role: RelayRole,
+) -> Result<CommandResult<RelayInfo>, String> {
+    match registry::set_relay_role(&state.nostr_relays, &url, role).await {
+        Ok(info) => Ok(CommandResult::ok(info)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// List the bundled default relays with their certificate pins
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_default_relays() -> Result<CommandResult<Vec<DefaultRelay>>, String> {
+    defaults::get_default_relays()
+        .map(CommandResult::ok)
+        .map_err(|e| e.to_string())
+}
+
+/// Check whether a relay is reachable without adding it
+///
+/// Opens a short-lived connection with the default pins and reports
+/// reachability, round-trip time and whether the certificate was pinned.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn test_relay(url: String) -> Result<CommandResult<RelayTestResult>, String> {
+    let relay =
+        match registry::normalize_relay_url(&url).and_then(NostrRelay::new_with_default_pinning) {
+            Ok(relay) => relay,
+            Err(e) => return Ok(CommandResult::fail(e)),
+        };
+    Ok(CommandResult::ok(
+        defaults::test_relay(relay, RELAY_TEST_TIMEOUT).await,
+    ))
+}
+
+/// Reload and check the persisted TOFU certificate pins
+///
+/// Fails with `cert_pin_storage_corrupt` when the pin file can't be read.
+/// Relays are then refused on first use, with `cert_pin_repair_required`,
+/// until `repair_tofu_pins` is called.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn validate_tofu_pins(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<TofuStorageStatus>, String> {
+    match state.pin_store.validate_tofu_storage() {
+        Ok(status) => Ok(CommandResult::ok(status)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Move a corrupt TOFU pin file aside and start with no TOFU pins
+///
+/// Every relay without a bundled pin is then trusted afresh on its next
+/// connection, so call this only once the user has confirmed. Returns the
+/// backup path, or `null` when the file was not corrupt.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn repair_tofu_pins(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<Option<String>>, String> {
+    match state.pin_store.repair_tofu_storage() {
+        Ok(backup) => Ok(CommandResult::ok(
+            backup.map(|path| path.display().to_string()),
+        )),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Disconnect and remove a relay
+///
+/// Fails with `relay_not_found` if the relay is not configured.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn remove_relay(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<CommandResult<()>, String> {
+    match registry::remove_relay(&state.nostr_relays, &url).await {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// List configured relays with their connection status
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn list_relays(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<Vec<RelayInfo>>, String> {
+    Ok(CommandResult::ok(
+        registry::list_relays(&state.nostr_relays).await,
+    ))
+}
+
+/// Watermark store over the app database
+///
+/// Nothing is loaded or saved while the database is locked; subscriptions
+/// opened then fetch their full history.
+struct DatabaseWatermarks(AppHandle);
+
+impl WatermarkStore for DatabaseWatermarks {
+    fn load(&self, filter_key: &str, relay_url: &str) -> Option<i64> {
+        let db = self.0.state::<Database>();
+        if !db.is_open() {
+            return None;
+        }
+        db.with_background_connection(|conn| {
+            watermarks::load_watermark(conn, filter_key, relay_url)
+        })
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to load watermark for {}: {}", relay_url, e);
+            None
+        })
+    }
+
+    fn save(&self, filter_key: &str, relay_url: &str, created_at: i64) {
+        let db = self.0.state::<Database>();
+        if !db.is_open() {
+            return;
+        }
+        if let Err(e) = db.with_background_connection(|conn| {
+            watermarks::save_watermark(conn, filter_key, relay_url, created_at)
+        }) {
+            log::warn!("Failed to save watermark for {}: {}", relay_url, e);
+        }
+    }
+}
+
+/// Open a REQ subscription on the given relays
+///
+/// Returns the subscription id. Matching events and EOSE notices are emitted
+/// to the frontend on the `nostr-relay-event` channel as `SubscriptionUpdate`s.
+///
+/// Unless `resume` is false, each relay is asked only for events since the
+/// newest one it delivered for the same filter before (less a small overlap),
+/// so reopening a feed after a restart does not re-download its history.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn subscribe(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    relay_urls: Vec<String>,
+    filter: Filter,
+    resume: Option<bool>,
+) -> Result<CommandResult<String>, String> {
+    let mut relays = Vec::with_capacity(relay_urls.len());
+    for url in &relay_urls {
+        match registry::get_relay(&state.nostr_relays, url) {
+            Some(relay) => relays.push(relay),
+            None => return Ok(CommandResult::fail(RelayError::RelayNotFound(url.clone()))),
+        }
+    }
+
+    let store: Arc<dyn WatermarkStore> = Arc::new(DatabaseWatermarks(app.clone()));
+    let sink: EventSink = Arc::new(move |update| {
+        let _ = app.emit(RELAY_EVENT_CHANNEL, &update);
+    });
+
+    let subscriptions = &state.nostr_subscriptions;
+    let result = if resume.unwrap_or(true) {
+        subscriptions
+            .subscribe_resuming(relays, vec![filter], sink, store)
+            .await
+    } else {
+        subscriptions.subscribe(relays, vec![filter], sink).await
+    };
+    match result {
+        Ok(subscription_id) => Ok(CommandResult::ok(subscription_id)),
+        Err(e) => Ok(CommandResult::fail(e)),
+    }
+}
+
+/// Close a subscription on every relay holding it
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn unsubscribe(
+    state: State<'_, AppState>,
+    subscription_id: String,
+) -> Result<CommandResult<()>, String> {
+    match state
+        .nostr_subscriptions
+        .unsubscribe(&subscription_id)
+        .await
+    {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
@@ -0,0 +1,36 @@
+//! Notification policy Tauri commands
+
+use super::error::CommandError;
+pub use super::error::CommandResult;
+use crate::notifications::NotificationPolicy;
+use crate::AppState;
+use tauri::State;
+
+/// Get the policy deciding which events raise system notifications
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn get_notification_policy(
+    state: State<'_, AppState>,
+) -> Result<CommandResult<NotificationPolicy>, String> {
+    Ok(CommandResult::ok(state.notifications.policy()))
+}
+
+/// Replace the notification policy
+///
+/// Fails with `notification_policy_invalid` if the rate limit is zero or the quiet hours
+/// are out of range; the current policy then stays in effect.
+#[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
+pub async fn set_notification_policy(
+    state: State<'_, AppState>,
+    policy: NotificationPolicy,
+) -> Result<CommandResult<()>, String> {
+    match state.notifications.set_policy(policy) {
+        Ok(()) => Ok(CommandResult::ok(())),
+        Err(e) => Ok(CommandResult::fail(CommandError::new(
+            "notification_policy_invalid",
+            e,
+            false,
+        ))),
+    }
+}
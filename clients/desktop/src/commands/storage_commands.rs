@@ -1,37 +1,13 @@
 //! Storage Tauri commands for encrypted local storage
 
+pub use super::error::CommandResult;
+use crate::crypto::keyring::SecretValue;
 use crate::AppState;
-use serde::{Deserialize, Serialize};
 use tauri::State;
 
-/// Command result wrapper
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CommandResult<T> {
-    pub success: bool,
-    pub data: Option<T>,
-    pub error: Option<String>,
-}
-
-impl<T> CommandResult<T> {
-    pub fn ok(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn err(error: String) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(error),
-        }
-    }
-}
-
 /// Store encrypted data in keyring
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn store_encrypted_key(
     state: State<'_, AppState>,
     user: String,
@@ -40,40 +16,41 @@ pub async fn store_encrypted_key(
 ) -> Result<CommandResult<()>, String> {
     use crate::crypto::keyring::SecretType;
 
-    let result = state.keyring_manager.store_secret(
-        &user,
-        SecretType::Custom(key_id),
-        &encrypted_key,
-        None,
-    );
+    let result = state.keyring().and_then(|keyring| {
+        keyring.store_secret(&user, SecretType::Custom(key_id), &encrypted_key, None)
+    });
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Retrieve encrypted data from keyring
+///
+/// The Rust-side copy is zeroized once the response has been serialized.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn retrieve_encrypted_key(
     state: State<'_, AppState>,
     user: String,
     key_id: String,
-) -> Result<CommandResult<String>, String> {
+) -> Result<CommandResult<SecretValue>, String> {
     use crate::crypto::keyring::SecretType;
 
     let result = state
-        .keyring_manager
-        .retrieve_secret(&user, &SecretType::Custom(key_id));
+        .keyring()
+        .and_then(|keyring| keyring.retrieve_secret_zeroizing(&user, &SecretType::Custom(key_id)));
 
     match result {
-        Ok(stored) => Ok(CommandResult::ok(stored.value)),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Ok(value) => Ok(CommandResult::ok(SecretValue::new(value))),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
 
 /// Delete encrypted data from keyring
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn delete_key(
     state: State<'_, AppState>,
     user: String,
@@ -82,11 +59,11 @@ pub async fn delete_key(
     use crate::crypto::keyring::SecretType;
 
     let result = state
-        .keyring_manager
-        .delete_secret(&user, &SecretType::Custom(key_id));
+        .keyring()
+        .and_then(|keyring| keyring.delete_secret(&user, &SecretType::Custom(key_id)));
 
     match result {
         Ok(()) => Ok(CommandResult::ok(())),
-        Err(e) => Ok(CommandResult::err(e.to_string())),
+        Err(e) => Ok(CommandResult::fail(e)),
     }
 }
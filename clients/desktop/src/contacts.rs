@@ -0,0 +1,164 @@
+//! Contact blocking
+//!
+//! Blocked public keys are persisted in the `blocked_contacts` table and
+//! mirrored in a [`BlockList`] shared by the mesh and relay paths, which drop
+//! messages from blocked senders before they reach the frontend. Blocking is
+//! always by verified public key: a BLE address is chosen by the peer and
+//! changes freely.
+
+use std::collections::HashSet;
+
+use parking_lot::RwLock;
+use rusqlite::Connection;
+
+/// Error prefix for malformed public keys (mapped to invalid_input)
+pub const INVALID_PUBKEY_ERROR: &str = "Invalid public key";
+
+/// Validate a hex public key and bring it into canonical (lowercase) form
+pub fn normalize_pubkey(pubkey: &str) -> Result<String, String> {
+    let pubkey = pubkey.trim().to_ascii_lowercase();
+    if pubkey.len() != 64 || !pubkey.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "{INVALID_PUBKEY_ERROR}: expected 64 hex characters"
+        ));
+    }
+    Ok(pubkey)
+}
+
+/// In-memory set of blocked public keys
+#[derive(Debug, Default)]
+pub struct BlockList {
+    pubkeys: RwLock<HashSet<String>>,
+}
+
+impl BlockList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Block `pubkey`, returning whether it was newly blocked
+    pub fn block(&self, pubkey: &str) -> Result<bool, String> {
+        let pubkey = normalize_pubkey(pubkey)?;
+        Ok(self.pubkeys.write().insert(pubkey))
+    }
+
+    /// Unblock `pubkey`, returning whether it was blocked
+    pub fn unblock(&self, pubkey: &str) -> Result<bool, String> {
+        let pubkey = normalize_pubkey(pubkey)?;
+        Ok(self.pubkeys.write().remove(&pubkey))
+    }
+
+    /// Whether messages from `pubkey` must be dropped
+    pub fn is_blocked(&self, pubkey: &str) -> bool {
+        self.pubkeys
+            .read()
+            .contains(&pubkey.trim().to_ascii_lowercase())
+    }
+
+    /// Replace the whole list, e.g. with the rows loaded on unlock
+    pub fn replace(&self, pubkeys: impl IntoIterator<Item = String>) {
+        *self.pubkeys.write() = pubkeys.into_iter().collect();
+    }
+
+    /// Forget every blocked key (the database copy is untouched)
+    pub fn clear(&self) {
+        self.pubkeys.write().clear();
+    }
+
+    /// Number of blocked keys
+    pub fn len(&self) -> usize {
+        self.pubkeys.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Load every blocked public key from the database
+pub fn load_blocked(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT pubkey FROM blocked_contacts ORDER BY pubkey")
+        .map_err(|e| format!("Failed to prepare query: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query failed: {e}"))?;
+    rows.collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Failed to read row: {e}"))
+}
+
+/// Persist a block; blocking an already blocked key keeps its original time
+pub fn insert_blocked(conn: &Connection, pubkey: &str) -> Result<(), String> {
+    let pubkey = normalize_pubkey(pubkey)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    conn.execute(
+        "INSERT OR IGNORE INTO blocked_contacts (pubkey, blocked_at) VALUES (?1, ?2)",
+        rusqlite::params![pubkey, now],
+    )
+    .map_err(|e| format!("Insert failed: {e}"))?;
+    Ok(())
+}
+
+/// Remove a persisted block, returning whether one existed
+pub fn delete_blocked(conn: &Connection, pubkey: &str) -> Result<bool, String> {
+    let pubkey = normalize_pubkey(pubkey)?;
+    conn.execute("DELETE FROM blocked_contacts WHERE pubkey = ?1", [pubkey])
+        .map(|n| n > 0)
+        .map_err(|e| format!("Delete failed: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const BOB: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    #[test]
+    fn test_block_and_unblock() {
+        let list = BlockList::new();
+        assert!(!list.is_blocked(ALICE));
+
+        assert!(list.block(&ALICE.to_uppercase()).unwrap());
+        assert!(!list.block(ALICE).unwrap());
+        assert!(list.is_blocked(ALICE));
+        assert!(list.is_blocked(&format!(" {} ", ALICE.to_uppercase())));
+        assert!(!list.is_blocked(BOB));
+
+        assert!(list.unblock(ALICE).unwrap());
+        assert!(!list.unblock(ALICE).unwrap());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_non_pubkeys() {
+        let list = BlockList::new();
+        // A BLE address is not a pubkey
+        assert!(list.block("AA:BB:CC:DD:EE:FF").is_err());
+        assert!(list.block(&"a".repeat(63)).is_err());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_persisted_blocks_round_trip() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::schema::run_migrations(&mut conn).unwrap();
+
+        insert_blocked(&conn, BOB).unwrap();
+        insert_blocked(&conn, &ALICE.to_uppercase()).unwrap();
+        insert_blocked(&conn, ALICE).unwrap();
+        assert_eq!(load_blocked(&conn).unwrap(), vec![ALICE, BOB]);
+
+        let list = BlockList::new();
+        list.replace(load_blocked(&conn).unwrap());
+        assert!(list.is_blocked(ALICE) && list.is_blocked(BOB));
+
+        assert!(delete_blocked(&conn, ALICE).unwrap());
+        assert!(!delete_blocked(&conn, ALICE).unwrap());
+        assert_eq!(load_blocked(&conn).unwrap(), vec![BOB]);
+    }
+}
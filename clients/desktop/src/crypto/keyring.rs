@@ -4,10 +4,16 @@
 //! - macOS: Keychain
 //! - Windows: Credential Manager
 //! - Linux: libsecret (GNOME Keyring, KWallet)
+//!
+//! SECURITY: This layer never logs secret values at any level. Only key names
+//! (`{user}_{type}`) appear in log output, and `StoredSecret`'s `Debug` output
+//! redacts the value.
 
 use keyring::Entry;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Keyring operation errors
 #[derive(Debug, Error)]
@@ -29,6 +35,9 @@ pub enum KeyringError {
 
     #[error("Keyring operation not supported on this platform")]
     NotSupported,
+
+    #[error("Keyring access is disabled in duress mode")]
+    DuressMode,
 }
 
 /// Secret types that can be stored in the keyring
@@ -42,6 +51,8 @@ pub enum SecretType {
     DatabaseKey,
     /// API token
     ApiToken,
+    /// Salt the master key is derived with
+    KdfSalt,
     /// Custom secret
     Custom(String),
 }
@@ -54,13 +65,14 @@ impl SecretType {
             SecretType::MasterKey => "master_key",
             SecretType::DatabaseKey => "database_key",
             SecretType::ApiToken => "api_token",
+            SecretType::KdfSalt => "kdf_salt",
             SecretType::Custom(name) => name,
         }
     }
 }
 
 /// Stored secret with metadata
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StoredSecret {
     /// The secret value (hex-encoded for binary data)
     pub value: String,
@@ -74,6 +86,48 @@ pub struct StoredSecret {
     pub label: Option<String>,
 }
 
+impl fmt::Debug for StoredSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoredSecret")
+            .field("value", &"[REDACTED]")
+            .field("secret_type", &self.secret_type)
+            .field("created_at", &self.created_at)
+            .field("last_accessed", &self.last_accessed)
+            .field("label", &self.label)
+            .finish()
+    }
+}
+
+/// Secret value that is wiped from memory when dropped
+///
+/// Serializes as the bare inner value so it can be returned from a Tauri
+/// command. The value necessarily crosses to the frontend, but the Rust-side
+/// copy is zeroized as soon as the response has been serialized and dropped.
+pub struct SecretValue<T: Zeroize = String>(Zeroizing<T>);
+
+impl<T: Zeroize> SecretValue<T> {
+    pub fn new(value: Zeroizing<T>) -> Self {
+        Self(value)
+    }
+
+    /// Borrow the secret for internal use
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for SecretValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretValue([REDACTED])")
+    }
+}
+
+impl<T: Zeroize + Serialize> Serialize for SecretValue<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
 /// Manager for system keyring operations
 pub struct KeyringManager {
     /// Application service identifier
@@ -112,7 +166,7 @@ impl KeyringManager {
             .unwrap()
             .as_millis() as u64;
 
-        let stored = StoredSecret {
+        let mut stored = StoredSecret {
             value: value.to_string(),
             secret_type,
             created_at: now,
@@ -120,8 +174,10 @@ impl KeyringManager {
             label,
         };
 
-        let serialized = serde_json::to_string(&stored)
-            .map_err(|_| KeyringError::InvalidFormat)?;
+        let serialized = Zeroizing::new(
+            serde_json::to_string(&stored).map_err(|_| KeyringError::InvalidFormat)?,
+        );
+        stored.value.zeroize();
 
         entry
             .set_password(&serialized)
@@ -142,15 +198,13 @@ impl KeyringManager {
         let entry = Entry::new(&self.service, &key)
             .map_err(|e| KeyringError::AccessError(e.to_string()))?;
 
-        let password = entry
-            .get_password()
-            .map_err(|e| match e {
-                keyring::Error::NoEntry => KeyringError::NotFound(key.clone()),
-                _ => KeyringError::AccessError(e.to_string()),
-            })?;
+        let password = Zeroizing::new(entry.get_password().map_err(|e| match e {
+            keyring::Error::NoEntry => KeyringError::NotFound(key.clone()),
+            _ => KeyringError::AccessError(e.to_string()),
+        })?);
 
-        let mut stored: StoredSecret = serde_json::from_str(&password)
-            .map_err(|_| KeyringError::InvalidFormat)?;
+        let mut stored: StoredSecret =
+            serde_json::from_str(&password).map_err(|_| KeyringError::InvalidFormat)?;
 
         // Update last accessed time
         stored.last_accessed = Some(
@@ -164,6 +218,19 @@ impl KeyringManager {
         Ok(stored)
     }
 
+    /// Retrieve only the secret value, wiped from memory on drop
+    ///
+    /// Prefer this over `retrieve_secret` for internal callers that use the
+    /// value (e.g. to open the database) and then discard it.
+    pub fn retrieve_secret_zeroizing(
+        &self,
+        user: &str,
+        secret_type: &SecretType,
+    ) -> Result<Zeroizing<String>, KeyringError> {
+        let mut stored = self.retrieve_secret(user, secret_type)?;
+        Ok(Zeroizing::new(std::mem::take(&mut stored.value)))
+    }
+
     /// Delete a secret from the system keyring
     pub fn delete_secret(
         &self,
@@ -252,6 +319,36 @@ impl KeyringManager {
         Ok(stored.value)
     }
 
+    /// Store the salt `user`'s master key is derived with
+    ///
+    /// The salt is not secret, but it has to be read back before the
+    /// database is unlocked, so it lives beside the keys it protects.
+    pub fn store_kdf_salt(&self, user: &str, salt: &[u8]) -> Result<(), KeyringError> {
+        if salt.len() < MIN_KDF_SALT_LEN {
+            return Err(KeyringError::InvalidFormat);
+        }
+        self.store_secret(
+            user,
+            SecretType::KdfSalt,
+            &hex::encode(salt),
+            Some("BuildIt Network KDF Salt".to_string()),
+        )
+    }
+
+    /// Retrieve the salt `user`'s master key is derived with
+    pub fn get_kdf_salt(&self, user: &str) -> Result<Vec<u8>, KeyringError> {
+        let stored = self.retrieve_secret(user, &SecretType::KdfSalt)?;
+        decode_kdf_salt(&stored.value)
+    }
+
+    /// Retrieve `user`'s KDF salt, generating and storing one on first setup
+    pub fn get_or_create_kdf_salt(&self, user: &str) -> Result<Vec<u8>, KeyringError> {
+        load_or_create_salt(
+            || self.get_kdf_salt(user),
+            |salt| self.store_kdf_salt(user, salt),
+        )
+    }
+
     /// List all secrets for a user (returns types, not values)
     pub fn list_secrets(&self, user: &str) -> Vec<SecretType> {
         let types = vec![
@@ -259,6 +356,7 @@ impl KeyringManager {
             SecretType::MasterKey,
             SecretType::DatabaseKey,
             SecretType::ApiToken,
+            SecretType::KdfSalt,
         ];
 
         types
@@ -275,6 +373,59 @@ impl KeyringManager {
         }
         Ok(())
     }
+
+    /// Check whether a keyring backend answers requests
+    ///
+    /// Looks up an entry that is never stored; a missing entry still means
+    /// the backend is working.
+    pub fn is_available(&self) -> bool {
+        match Entry::new(&self.service, KEYRING_PROBE_KEY) {
+            Ok(entry) => backend_responded(&entry.get_password()),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Length of a newly generated KDF salt in bytes
+pub const KDF_SALT_LEN: u32 = 32;
+
+/// Shortest salt `derive_master_key` accepts
+const MIN_KDF_SALT_LEN: usize = 16;
+
+/// Decode a stored hex salt, rejecting one too short to derive with
+fn decode_kdf_salt(value: &str) -> Result<Vec<u8>, KeyringError> {
+    let salt = hex::decode(value).map_err(|_| KeyringError::InvalidFormat)?;
+    if salt.len() < MIN_KDF_SALT_LEN {
+        return Err(KeyringError::InvalidFormat);
+    }
+    Ok(salt)
+}
+
+/// Load a salt, generating and storing a new one only if none exists
+///
+/// Any other failure is returned as is: replacing the salt of an existing
+/// account would derive a different master key and lock the user out.
+fn load_or_create_salt<L, S>(load: L, store: S) -> Result<Vec<u8>, KeyringError>
+where
+    L: FnOnce() -> Result<Vec<u8>, KeyringError>,
+    S: FnOnce(&[u8]) -> Result<(), KeyringError>,
+{
+    match load() {
+        Err(KeyringError::NotFound(_)) => {
+            let salt = buildit_crypto::generate_salt(KDF_SALT_LEN);
+            store(&salt)?;
+            Ok(salt)
+        }
+        result => result,
+    }
+}
+
+/// Entry name looked up by `KeyringManager::is_available`
+const KEYRING_PROBE_KEY: &str = "buildit_keyring_probe";
+
+/// Whether a lookup result came from a working keyring backend
+fn backend_responded(result: &Result<String, keyring::Error>) -> bool {
+    matches!(result, Ok(_) | Err(keyring::Error::NoEntry))
 }
 
 #[cfg(test)]
@@ -288,10 +439,144 @@ mod tests {
         assert_eq!(key, "alice_nostr_private_key");
     }
 
+    #[test]
+    fn test_backend_availability_from_lookup() {
+        assert!(backend_responded(&Err(keyring::Error::NoEntry)));
+        assert!(backend_responded(&Ok("value".to_string())));
+        assert!(!backend_responded(&Err(keyring::Error::PlatformFailure(
+            "no secret service".into()
+        ))));
+        assert!(!backend_responded(&Err(keyring::Error::NoStorageAccess(
+            "locked".into()
+        ))));
+    }
+
     #[test]
     fn test_secret_type_suffix() {
-        assert_eq!(SecretType::NostrPrivateKey.key_suffix(), "nostr_private_key");
+        assert_eq!(
+            SecretType::NostrPrivateKey.key_suffix(),
+            "nostr_private_key"
+        );
         assert_eq!(SecretType::MasterKey.key_suffix(), "master_key");
-        assert_eq!(SecretType::Custom("my_secret".to_string()).key_suffix(), "my_secret");
+        assert_eq!(SecretType::KdfSalt.key_suffix(), "kdf_salt");
+        assert_eq!(
+            SecretType::Custom("my_secret".to_string()).key_suffix(),
+            "my_secret"
+        );
+    }
+
+    /// Stand-in for the keyring that stores salts the way `store_kdf_salt` does
+    #[derive(Default)]
+    struct FakeSaltEntry(std::cell::RefCell<Option<String>>);
+
+    impl FakeSaltEntry {
+        fn load(&self) -> Result<Vec<u8>, KeyringError> {
+            match self.0.borrow().as_deref() {
+                Some(value) => decode_kdf_salt(value),
+                None => Err(KeyringError::NotFound("alice_kdf_salt".to_string())),
+            }
+        }
+
+        fn store(&self, salt: &[u8]) -> Result<(), KeyringError> {
+            *self.0.borrow_mut() = Some(hex::encode(salt));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_kdf_salt_created_once_and_round_trips() {
+        let entry = FakeSaltEntry::default();
+        let created = load_or_create_salt(|| entry.load(), |salt| entry.store(salt)).unwrap();
+        assert_eq!(created.len(), KDF_SALT_LEN as usize);
+
+        let retrieved = load_or_create_salt(
+            || entry.load(),
+            |_| panic!("an existing salt must not be replaced"),
+        )
+        .unwrap();
+        assert_eq!(retrieved, created);
+
+        let params = buildit_crypto::Argon2Params {
+            memory_kb: 19456,
+            time_cost: 2,
+            parallelism: 1,
+        };
+        let first = buildit_crypto::derive_master_key_with_params(
+            b"correct horse".to_vec(),
+            created,
+            params,
+        )
+        .unwrap();
+        let second = buildit_crypto::derive_master_key_with_params(
+            b"correct horse".to_vec(),
+            retrieved,
+            params,
+        )
+        .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_kdf_salt_not_replaced_on_keyring_failure() {
+        let result = load_or_create_salt(
+            || Err(KeyringError::AccessError("locked".to_string())),
+            |_| panic!("a salt must not be generated when the keyring fails"),
+        );
+        assert!(matches!(result, Err(KeyringError::AccessError(_))));
+
+        assert!(matches!(
+            decode_kdf_salt("zz"),
+            Err(KeyringError::InvalidFormat)
+        ));
+        assert!(matches!(
+            decode_kdf_salt(&hex::encode([0u8; 8])),
+            Err(KeyringError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_stored_secret_debug_redacts_value() {
+        let stored = StoredSecret {
+            value: "deadbeefcafebabe".to_string(),
+            secret_type: SecretType::NostrPrivateKey,
+            created_at: 0,
+            last_accessed: None,
+            label: Some("main".to_string()),
+        };
+        let debug = format!("{:?}", stored);
+        assert!(!debug.contains("deadbeefcafebabe"));
+        assert!(debug.contains("[REDACTED]"));
+    }
+
+    /// Drop-counting wrapper recording every zeroize call
+    #[derive(Serialize)]
+    struct CountingSecret {
+        value: String,
+        #[serde(skip)]
+        wipes: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl Zeroize for CountingSecret {
+        fn zeroize(&mut self) {
+            self.value.zeroize();
+            self.wipes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_secret_value_zeroized_after_serialization() {
+        let wipes = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let secret = SecretValue::new(Zeroizing::new(CountingSecret {
+            value: "nsec-material".to_string(),
+            wipes: wipes.clone(),
+        }));
+
+        let json = serde_json::to_string(&secret).unwrap();
+        assert!(json.contains("nsec-material"));
+        assert_eq!(format!("{:?}", secret), "SecretValue([REDACTED])");
+        assert_eq!(wipes.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        drop(secret);
+        assert_eq!(wipes.load(std::sync::atomic::Ordering::SeqCst), 1);
     }
 }
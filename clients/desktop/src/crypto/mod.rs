@@ -3,7 +3,9 @@
 //! This module provides:
 //! - System keyring integration for secure credential storage
 //! - Integration with buildit-crypto crate for NIP-44/NIP-17 encryption
+//! - Automatic persistence of Double Ratchet sessions
 
 pub mod keyring;
+pub mod ratchet_store;
 
-pub use keyring::KeyringManager;
+pub use keyring::{KeyringManager, SecretValue};
@@ -0,0 +1,281 @@
+//! Persistence of Double Ratchet sessions
+//!
+//! Every encrypt and decrypt advances a `RatchetSession`, and a session
+//! that is not stored again afterwards comes back stale after a crash. The
+//! state is kept in `secure_kv` under `ratchet:<conversation id>`, and
+//! [`AutosavedSession`] stores it again shortly after each change so callers
+//! cannot forget to.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use buildit_crypto::RatchetSession;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use zeroize::Zeroizing;
+
+use crate::db::{secure_kv, Database};
+
+/// Quiet period after a change before the session is saved
+pub const AUTOSAVE_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Longest a change waits while the session keeps changing
+pub const AUTOSAVE_MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Stores a conversation's session, e.g. with [`save_session`]
+pub type SaveSession = Arc<dyn Fn(&str, &RatchetSession) -> Result<(), String> + Send + Sync>;
+
+/// secure_kv key holding the session of `conversation_id`
+pub fn session_key(conversation_id: &str) -> String {
+    format!("ratchet:{conversation_id}")
+}
+
+/// Encrypt the session state under `enc_key` and store it
+pub fn save_session(
+    conn: &Connection,
+    conversation_id: &str,
+    session: &RatchetSession,
+    enc_key: &[u8],
+) -> Result<(), String> {
+    let state = Zeroizing::new(
+        session
+            .serialize_unencrypted()
+            .map_err(|e| format!("Session serialization failed: {e}"))?,
+    );
+    let state =
+        std::str::from_utf8(&state).map_err(|_| "Session state is not valid UTF-8".to_string())?;
+    secure_kv::put(conn, &session_key(conversation_id), state, enc_key)
+}
+
+/// Load the stored session of `conversation_id`, if any
+pub fn load_session(
+    conn: &Connection,
+    conversation_id: &str,
+    enc_key: &[u8],
+) -> Result<Option<RatchetSession>, String> {
+    let Some(state) = secure_kv::read(conn, &session_key(conversation_id), enc_key)? else {
+        return Ok(None);
+    };
+    RatchetSession::deserialize_unencrypted(state.as_bytes().to_vec())
+        .map(Some)
+        .map_err(|e| format!("Stored session for {conversation_id} is invalid: {e}"))
+}
+
+/// Save sessions into the app's database under `enc_key`
+///
+/// Saves use a background connection so they do not hold off the idle
+/// auto-lock; while the database is locked they fail and are retried on the
+/// next change or flush.
+pub fn database_saver(app: AppHandle, enc_key: Zeroizing<Vec<u8>>) -> SaveSession {
+    Arc::new(move |conversation_id, session| {
+        app.state::<Database>().with_background_connection(|conn| {
+            save_session(conn, conversation_id, session, &enc_key)
+        })
+    })
+}
+
+struct Autosave {
+    conversation_id: String,
+    save: SaveSession,
+    dirty: AtomicBool,
+    changed: Notify,
+    saving: Mutex<()>,
+}
+
+impl Autosave {
+    fn flush(&self, session: &RatchetSession) -> Result<(), String> {
+        let _saving = self.saving.lock();
+        if !self.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+        (self.save)(&self.conversation_id, session).map_err(|e| {
+            self.dirty.store(true, Ordering::SeqCst);
+            e
+        })
+    }
+}
+
+/// A ratchet session that stores itself after every change
+///
+/// Changes are saved once the session has been quiet for
+/// [`AUTOSAVE_DEBOUNCE`], or after [`AUTOSAVE_MAX_DELAY`] while it keeps
+/// changing, so a burst of messages costs one write. Dropping the wrapper
+/// saves any change still pending.
+pub struct AutosavedSession {
+    session: Arc<RatchetSession>,
+    autosave: Arc<Autosave>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl AutosavedSession {
+    pub fn new(
+        conversation_id: impl Into<String>,
+        session: RatchetSession,
+        save: SaveSession,
+    ) -> Self {
+        let session = Arc::new(session);
+        let autosave = Arc::new(Autosave {
+            conversation_id: conversation_id.into(),
+            save,
+            dirty: AtomicBool::new(false),
+            changed: Notify::new(),
+            saving: Mutex::new(()),
+        });
+
+        let hook_autosave = autosave.clone();
+        session.set_on_state_change(Arc::new(move || {
+            hook_autosave.dirty.store(true, Ordering::SeqCst);
+            hook_autosave.changed.notify_one();
+        }));
+        let task =
+            tauri::async_runtime::spawn(run_autosave(Arc::downgrade(&session), autosave.clone()));
+
+        Self {
+            session,
+            autosave,
+            task,
+        }
+    }
+
+    pub fn conversation_id(&self) -> &str {
+        &self.autosave.conversation_id
+    }
+
+    /// Save now if anything changed since the last save
+    pub fn flush(&self) -> Result<(), String> {
+        self.autosave.flush(&self.session)
+    }
+}
+
+impl Deref for AutosavedSession {
+    type Target = RatchetSession;
+
+    fn deref(&self) -> &RatchetSession {
+        &self.session
+    }
+}
+
+impl Drop for AutosavedSession {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let Err(e) = self.flush() {
+            log::warn!(
+                "Failed to save ratchet session {}: {}",
+                self.autosave.conversation_id,
+                e
+            );
+        }
+    }
+}
+
+async fn run_autosave(session: Weak<RatchetSession>, autosave: Arc<Autosave>) {
+    loop {
+        autosave.changed.notified().await;
+        let deadline = Instant::now() + AUTOSAVE_MAX_DELAY;
+        loop {
+            let quiet_until = (Instant::now() + AUTOSAVE_DEBOUNCE).min(deadline);
+            if tokio::time::timeout_at(quiet_until, autosave.changed.notified())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        let Some(session) = session.upgrade() else {
+            return;
+        };
+        if let Err(e) = autosave.flush(&session) {
+            log::warn!(
+                "Failed to save ratchet session {}: {}",
+                autosave.conversation_id,
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENC_KEY: [u8; 32] = [5u8; 32];
+
+    fn migrated_conn() -> Arc<std::sync::Mutex<Connection>> {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::schema::run_migrations(&mut conn).unwrap();
+        Arc::new(std::sync::Mutex::new(conn))
+    }
+
+    fn saver(conn: &Arc<std::sync::Mutex<Connection>>) -> SaveSession {
+        let conn = conn.clone();
+        Arc::new(move |conversation_id, session| {
+            save_session(&conn.lock().unwrap(), conversation_id, session, &ENC_KEY)
+        })
+    }
+
+    fn session_pair() -> (RatchetSession, RatchetSession) {
+        let shared_secret = vec![9u8; 32];
+        let bob_prekey = buildit_crypto::generate_keypair();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret.clone(), bob_prekey.private_key).unwrap();
+        let alice = RatchetSession::initialize_alice(shared_secret, bob.get_public_key()).unwrap();
+        (alice, bob)
+    }
+
+    #[tokio::test]
+    async fn test_autosave_stores_latest_counters() {
+        let conn = migrated_conn();
+        let (alice, bob) = session_pair();
+        let alice = AutosavedSession::new("conv-1", alice, saver(&conn));
+
+        let sent: Vec<_> = (0..3)
+            .map(|i| alice.encrypt(format!("Message {i}").into_bytes()).unwrap())
+            .collect();
+        // Nothing is written during the burst
+        assert!(load_session(&conn.lock().unwrap(), "conv-1", &ENC_KEY)
+            .unwrap()
+            .is_none());
+
+        tokio::time::sleep(AUTOSAVE_DEBOUNCE * 4).await;
+        let restored = load_session(&conn.lock().unwrap(), "conv-1", &ENC_KEY)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            restored.serialize_unencrypted().unwrap(),
+            alice.serialize_unencrypted().unwrap()
+        );
+
+        // The reloaded session continues the chain where the live one was
+        let next = restored.encrypt(b"After reload".to_vec()).unwrap();
+        assert_eq!(next.header.message_number, 3);
+        for (i, message) in sent.into_iter().enumerate() {
+            assert_eq!(
+                bob.decrypt(message).unwrap(),
+                format!("Message {i}").into_bytes()
+            );
+        }
+        assert_eq!(bob.decrypt(next).unwrap(), b"After reload");
+    }
+
+    #[tokio::test]
+    async fn test_drop_saves_pending_change() {
+        let conn = migrated_conn();
+        let (alice, _bob) = session_pair();
+        let alice = AutosavedSession::new("conv-2", alice, saver(&conn));
+        alice.encrypt(b"Hello".to_vec()).unwrap();
+        let expected = alice.serialize_unencrypted().unwrap();
+        drop(alice);
+
+        let restored = load_session(&conn.lock().unwrap(), "conv-2", &ENC_KEY)
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.serialize_unencrypted().unwrap(), expected);
+        assert!(load_session(&conn.lock().unwrap(), "conv-2", &[6u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Field-level encryption policy
+//!
+//! SQLCipher protects the file at rest, but every column is plaintext to
+//! whoever holds the open database. A [`FieldEncryptionPolicy`] names the
+//! columns of each table (message bodies, titles) that are additionally
+//! sealed with AES-256-GCM under a field key supplied at unlock. Columns
+//! left out of the policy (ids, timestamps) stay plaintext, so they can be
+//! indexed, filtered and sorted.
+//!
+//! Sealed values are stored as TEXT `enc:v1:<base64(nonce || ciphertext)>`
+//! with the table and column bound as AAD, so a value copied into another
+//! column fails to decrypt. Values without the prefix are returned as they
+//! are, so a column can join the policy without migrating existing rows.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+/// Prefix marking a sealed column value
+pub const SEALED_PREFIX: &str = "enc:v1:";
+
+/// Error prefix for a filter or sort on a sealed column
+pub const ENCRYPTED_COLUMN_ERROR: &str = "Encrypted column";
+
+/// Error prefix for a seal or open attempted without the field key
+pub const FIELD_KEY_MISSING_ERROR: &str = "Field encryption key not set";
+
+/// Domain prefix for the AAD binding a sealed value to its column
+const FIELD_AAD_PREFIX: &[u8] = b"buildit-field:";
+
+/// AES-256-GCM nonce length
+const NONCE_LEN: usize = 12;
+
+/// Columns to encrypt, per table (snake_case names)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FieldEncryptionPolicy {
+    tables: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl FieldEncryptionPolicy {
+    /// Create a policy that encrypts nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encrypt `columns` of `table`, in addition to any already registered
+    pub fn with_table(mut self, table: &str, columns: &[&str]) -> Self {
+        self.tables
+            .entry(table.to_string())
+            .or_default()
+            .extend(columns.iter().map(|c| c.to_string()));
+        self
+    }
+
+    /// Encrypted columns of `table`
+    pub fn columns(&self, table: &str) -> impl Iterator<Item = &str> {
+        self.tables
+            .get(table)
+            .into_iter()
+            .flat_map(|columns| columns.iter().map(String::as_str))
+    }
+
+    /// Tables with encrypted columns
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+
+    /// Whether `column` of `table` is encrypted
+    pub fn is_encrypted(&self, table: &str, column: &str) -> bool {
+        self.tables
+            .get(table)
+            .is_some_and(|columns| columns.contains(column))
+    }
+}
+
+/// A policy together with the field key of the open database
+pub struct FieldCipher {
+    policy: FieldEncryptionPolicy,
+    key: Option<Zeroizing<Vec<u8>>>,
+}
+
+impl FieldCipher {
+    pub fn new(policy: FieldEncryptionPolicy, key: Option<Zeroizing<Vec<u8>>>) -> Self {
+        Self { policy, key }
+    }
+
+    pub fn policy(&self) -> &FieldEncryptionPolicy {
+        &self.policy
+    }
+
+    /// Refuse to write `table` while its sealed columns cannot be sealed
+    ///
+    /// Any write to a table with policy columns needs the key, even one that
+    /// leaves those columns out, so a locked field key never lets plaintext
+    /// rows slip in beside sealed ones.
+    pub fn check_writable(&self, table: &str) -> Result<(), String> {
+        if self.policy.columns(table).next().is_none() {
+            return Ok(());
+        }
+        self.key().map(|_| ())
+    }
+
+    fn key(&self) -> Result<&[u8], String> {
+        self.key.as_deref().map(Vec::as_slice).ok_or_else(|| {
+            format!("{FIELD_KEY_MISSING_ERROR}: reopen the database with a field key")
+        })
+    }
+
+    /// Seal `value` for `column` of `table`; `null` stays `null`
+    pub fn seal(&self, table: &str, column: &str, value: &Value) -> Result<Value, String> {
+        if value.is_null() {
+            return Ok(Value::Null);
+        }
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(value).map_err(|e| format!("Failed to encode {column}: {e}"))?,
+        );
+        let encrypted = buildit_crypto::aes_encrypt_with_aad(
+            self.key()?,
+            &plaintext,
+            &field_aad(table, column),
+        )
+        .map_err(|e| format!("Encryption failed for {table}.{column}: {e}"))?;
+
+        let mut sealed = encrypted.nonce;
+        sealed.extend_from_slice(&encrypted.ciphertext);
+        Ok(Value::String(format!(
+            "{SEALED_PREFIX}{}",
+            BASE64.encode(sealed)
+        )))
+    }
+
+    /// Recover the value sealed in `column` of `table`
+    ///
+    /// Anything that is not a sealed string is returned unchanged.
+    pub fn open(&self, table: &str, column: &str, value: Value) -> Result<Value, String> {
+        let Some(encoded) = value.as_str().and_then(|s| s.strip_prefix(SEALED_PREFIX)) else {
+            return Ok(value);
+        };
+        let sealed = BASE64
+            .decode(encoded)
+            .map_err(|e| format!("Corrupt encrypted value in {table}.{column}: {e}"))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(format!("Corrupt encrypted value in {table}.{column}"));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let encrypted = buildit_crypto::EncryptedData {
+            ciphertext: ciphertext.to_vec(),
+            nonce: nonce.to_vec(),
+        };
+
+        let plaintext = Zeroizing::new(
+            buildit_crypto::aes_decrypt_with_aad(
+                self.key()?,
+                &encrypted,
+                &field_aad(table, column),
+            )
+            .map_err(|e| format!("Decryption failed for {table}.{column}: {e}"))?,
+        );
+        serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Corrupt encrypted value in {table}.{column}: {e}"))
+    }
+}
+
+/// Whether `column` of `table` holds any sealed value
+///
+/// A table or column that does not exist holds none.
+pub fn holds_sealed_values(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+            [table, column],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(|e| format!("Query failed: {e}"))?
+        .is_some();
+    if !exists {
+        return Ok(false);
+    }
+    conn.query_row(
+        &format!(
+            "SELECT EXISTS(SELECT 1 FROM \"{table}\" \
+             WHERE typeof(\"{column}\") = 'text' AND \"{column}\" GLOB ?1)"
+        ),
+        [format!("{SEALED_PREFIX}*")],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Query failed: {e}"))
+}
+
+fn field_aad(table: &str, column: &str) -> Vec<u8> {
+    [FIELD_AAD_PREFIX, table.as_bytes(), b".", column.as_bytes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cipher(key: Option<[u8; 32]>) -> FieldCipher {
+        let policy = FieldEncryptionPolicy::new().with_table("messages", &["content"]);
+        FieldCipher::new(policy, key.map(|k| Zeroizing::new(k.to_vec())))
+    }
+
+    #[test]
+    fn test_seal_round_trip() {
+        let cipher = cipher(Some([7u8; 32]));
+
+        for value in [json!("hello"), json!(42), json!({ "a": [1, 2] })] {
+            let sealed = cipher.seal("messages", "content", &value).unwrap();
+            assert!(sealed.as_str().unwrap().starts_with(SEALED_PREFIX));
+            assert_eq!(cipher.open("messages", "content", sealed).unwrap(), value);
+        }
+        assert_eq!(
+            cipher.seal("messages", "content", &Value::Null).unwrap(),
+            Value::Null
+        );
+    }
+
+    #[test]
+    fn test_sealed_value_bound_to_column() {
+        let cipher = cipher(Some([7u8; 32]));
+        let sealed = cipher.seal("messages", "content", &json!("hello")).unwrap();
+
+        assert!(cipher.open("messages", "subject", sealed.clone()).is_err());
+        assert!(cipher.open("posts", "content", sealed).is_err());
+    }
+
+    #[test]
+    fn test_plaintext_passes_through_and_key_required() {
+        let locked = cipher(None);
+        assert_eq!(
+            locked.open("messages", "content", json!("legacy")).unwrap(),
+            json!("legacy")
+        );
+        assert!(locked.seal("messages", "content", &json!("hi")).is_err());
+
+        let sealed = cipher(Some([7u8; 32]))
+            .seal("messages", "content", &json!("hi"))
+            .unwrap();
+        assert!(locked.open("messages", "content", sealed.clone()).is_err());
+        assert!(cipher(Some([8u8; 32]))
+            .open("messages", "content", sealed)
+            .is_err());
+    }
+
+    #[test]
+    fn test_policy_columns() {
+        let policy = FieldEncryptionPolicy::new()
+            .with_table("messages", &["content"])
+            .with_table("messages", &["subject"]);
+
+        assert!(policy.is_encrypted("messages", "content"));
+        assert!(policy.is_encrypted("messages", "subject"));
+        assert!(!policy.is_encrypted("messages", "id"));
+        assert!(!policy.is_encrypted("posts", "content"));
+        assert_eq!(
+            policy.columns("messages").collect::<Vec<_>>(),
+            vec!["content", "subject"]
+        );
+        assert_eq!(policy.columns("posts").count(), 0);
+        assert_eq!(
+            serde_json::to_value(&policy).unwrap(),
+            json!({ "messages": ["content", "subject"] })
+        );
+    }
+}
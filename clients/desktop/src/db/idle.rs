@@ -0,0 +1,173 @@
+//! Idle auto-lock for the encrypted database
+//!
+//! Every connection access records activity. A background task periodically
+//! asks the `Database` to close itself once no activity has been seen for the
+//! configured timeout, so an unlocked but unattended machine does not keep
+//! the decrypted data reachable indefinitely.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Default inactivity period before the database auto-locks
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// How often the background task checks for inactivity
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Event emitted to the frontend when the database auto-locks
+pub const DB_AUTO_LOCKED_EVENT: &str = "db-auto-locked";
+
+/// Idle auto-lock setting as saved next to the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleSettings {
+    /// Seconds of inactivity before locking (`None` disables auto-lock)
+    pub timeout_secs: Option<u64>,
+}
+
+impl IdleSettings {
+    pub fn new(timeout: Option<Duration>) -> Self {
+        Self {
+            timeout_secs: timeout.map(|t| t.as_secs()),
+        }
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout_secs.map(Duration::from_secs)
+    }
+}
+
+/// Source of the current time, replaceable in tests
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// Monotonic system clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Tracks the last database activity against an optional idle timeout
+pub struct IdleTracker {
+    clock: Arc<dyn Clock>,
+    last_activity: Mutex<Instant>,
+    /// `None` disables auto-lock
+    timeout: Mutex<Option<Duration>>,
+}
+
+impl IdleTracker {
+    pub fn new(clock: Arc<dyn Clock>, timeout: Option<Duration>) -> Self {
+        let now = clock.now();
+        Self {
+            clock,
+            last_activity: Mutex::new(now),
+            timeout: Mutex::new(timeout),
+        }
+    }
+
+    /// Record activity, restarting the idle period
+    pub fn touch(&self) {
+        *self.last_activity.lock() = self.clock.now();
+    }
+
+    /// Current idle timeout (`None` when auto-lock is disabled)
+    pub fn timeout(&self) -> Option<Duration> {
+        *self.timeout.lock()
+    }
+
+    /// Change the idle timeout; `None` disables auto-lock
+    ///
+    /// Also restarts the idle period so a shorter timeout does not lock
+    /// immediately.
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        *self.timeout.lock() = timeout;
+        self.touch();
+    }
+
+    /// Whether the timeout has elapsed since the last activity
+    pub fn is_idle(&self) -> bool {
+        match self.timeout() {
+            Some(timeout) => {
+                let last = *self.last_activity.lock();
+                self.clock.now().saturating_duration_since(last) >= timeout
+            }
+            None => false,
+        }
+    }
+}
+
+/// Manually advanced clock for idle tests
+#[cfg(test)]
+pub struct MockClock {
+    now: Mutex<Instant>,
+}
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            now: Mutex::new(Instant::now()),
+        })
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.now.lock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_after_timeout() {
+        let clock = MockClock::new();
+        let tracker = IdleTracker::new(clock.clone(), Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(59));
+        assert!(!tracker.is_idle());
+        clock.advance(Duration::from_secs(1));
+        assert!(tracker.is_idle());
+    }
+
+    #[test]
+    fn test_touch_resets_idle_period() {
+        let clock = MockClock::new();
+        let tracker = IdleTracker::new(clock.clone(), Some(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(45));
+        tracker.touch();
+        clock.advance(Duration::from_secs(45));
+        assert!(!tracker.is_idle());
+        clock.advance(Duration::from_secs(15));
+        assert!(tracker.is_idle());
+    }
+
+    #[test]
+    fn test_disabled_timeout_never_idles() {
+        let clock = MockClock::new();
+        let tracker = IdleTracker::new(clock.clone(), None);
+
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+        assert!(!tracker.is_idle());
+
+        tracker.set_timeout(Some(Duration::from_secs(10)));
+        assert!(!tracker.is_idle());
+        clock.advance(Duration::from_secs(10));
+        assert!(tracker.is_idle());
+    }
+}
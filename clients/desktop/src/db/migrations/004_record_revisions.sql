@@ -0,0 +1,12 @@
+-- Optimistic concurrency: a revision counter on records edited from several
+-- windows or devices. db_put_checked compares it before writing and bumps it
+-- on every successful write.
+
+ALTER TABLE groups ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE events ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE proposals ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE wiki_pages ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE mutual_aid_requests ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE database_records ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE documents ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN rev INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+-- Secure key-value store for small secrets (session states, cached
+-- conversation keys) that outgrow the OS keyring. Values are AES-256-GCM
+-- encrypted by the secure_kv_* commands with the key name bound as AAD, on
+-- top of the database's own SQLCipher encryption.
+
+CREATE TABLE IF NOT EXISTS secure_kv (
+    key TEXT PRIMARY KEY,
+    ciphertext BLOB NOT NULL,
+    nonce BLOB NOT NULL,
+    updated_at INTEGER NOT NULL
+);
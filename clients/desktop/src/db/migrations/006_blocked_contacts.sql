@@ -0,0 +1,8 @@
+-- Contacts the user has blocked, by verified Nostr public key (64-char
+-- lowercase hex). Mirrored in memory by crate::contacts::BlockList, which
+-- the mesh and relay paths consult to drop messages from blocked senders.
+
+CREATE TABLE IF NOT EXISTS blocked_contacts (
+    pubkey TEXT PRIMARY KEY,
+    blocked_at INTEGER NOT NULL
+);
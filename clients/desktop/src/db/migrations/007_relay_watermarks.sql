@@ -0,0 +1,11 @@
+-- Highest created_at seen per subscription filter and relay. Resubscribing
+-- with the same filter starts from the watermark (less a small overlap)
+-- instead of re-downloading history; see crate::nostr::watermarks.
+
+CREATE TABLE IF NOT EXISTS relay_watermarks (
+    filter_key TEXT NOT NULL,
+    relay_url TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    PRIMARY KEY (filter_key, relay_url)
+);
@@ -0,0 +1,18 @@
+-- Signed events waiting to be published. An entry is removed once any of
+-- its relays accepts the event with OK; after too many failed attempts it
+-- stays behind with status 'failed' until retried or discarded. See
+-- crate::nostr::outbox.
+
+CREATE TABLE IF NOT EXISTS outbox (
+    event_id TEXT PRIMARY KEY,
+    event_json TEXT NOT NULL,
+    -- JSON array of relay URLs; empty means every writable relay
+    relay_urls TEXT NOT NULL DEFAULT '[]',
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_outbox_due ON outbox(status, next_attempt_at);
@@ -9,24 +9,80 @@
 //! - SQLCipher encrypts the entire DB file at rest (AES-256)
 //! - Connection pool via r2d2 for concurrent access
 //! - `update_hook` fires Tauri events on every INSERT/UPDATE/DELETE
+//! - Single records can be observed for their changes alone; see `observe`
 //! - Migrations managed by `rusqlite_migration`
 //!
 //! ## Key Lifecycle
 //!
 //! - On unlock: derive SQLCipher key from user's master password, open DB
 //! - On lock: close DB connection, wipe key from memory
+//! - After a period of inactivity: auto-lock and emit `db-auto-locked`
+//! - The inactivity timeout is kept in a `<db>.idle.json` sidecar and loaded
+//!   on open
+//!
+//! ## Cipher Settings
+//!
+//! SQLCipher page size and KDF iterations are fixed when the file is created
+//! and must be supplied identically on every open. They are kept in a
+//! `<db>.cipher.json` sidecar (they are not secret). Changing them on an
+//! existing database goes through `Database::rekey_cipher`, which exports
+//! into a fresh file created with the new settings. The new settings are
+//! staged in `<db>.cipher.json.rekey` before the file is replaced, so an
+//! interrupted rekey is finished by the next open.
+//!
+//! ## Field Encryption
+//!
+//! Columns named in the `FieldEncryptionPolicy` are additionally sealed with
+//! AES-256-GCM under a field key supplied at unlock and wiped on lock; see
+//! `field_encryption`. The policy is kept in a `<db>.fields.json` sidecar
+//! and loaded on open, so sealed columns stay sealed across restarts.
 
+pub mod field_encryption;
+pub mod idle;
+pub mod observe;
 pub mod pool;
 pub mod schema;
+pub mod secure_kv;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use parking_lot::RwLock;
-use rusqlite::Connection;
-use tauri::AppHandle;
+use rusqlite::{Connection, OptionalExtension};
+use tauri::{AppHandle, Emitter, Manager};
+use zeroize::Zeroizing;
+
+use crate::db::field_encryption::{FieldCipher, FieldEncryptionPolicy};
+use crate::db::idle::{Clock, IdleSettings, IdleTracker, SystemClock};
+use crate::db::observe::{ChangeObservers, ChangeSink};
+use crate::db::pool::{CipherInfo, CipherSettings, DbPool};
+
+/// Error prefix while no database is open (mapped to db_locked)
+pub const DB_CLOSED_ERROR: &str = "Database is locked/closed";
+
+/// Error prefix while the database is sealed for duress (mapped to db_sealed)
+pub const DB_SEALED_ERROR: &str = "Database is sealed";
 
-use crate::db::pool::DbPool;
+/// Error prefix for writes that lost a race or contradict stored state
+/// (mapped to db_conflict)
+pub const CONFLICT_ERROR: &str = "Conflict:";
+
+/// Sidecar holding the SQLCipher settings, next to the database file
+const CIPHER_SETTINGS_SUFFIX: &str = ".cipher.json";
+
+/// New cipher settings written before a rekey replaces the database file
+const PENDING_CIPHER_SUFFIX: &str = ".cipher.json.rekey";
+
+/// Sidecar holding the field encryption policy, next to the database file
+const FIELD_POLICY_SUFFIX: &str = ".fields.json";
+
+/// Sidecar holding the idle auto-lock timeout, next to the database file
+const IDLE_SETTINGS_SUFFIX: &str = ".idle.json";
+
+/// Event emitted whenever the database opens or closes (payload: `true` when locked)
+pub const DB_LOCK_STATE_EVENT: &str = "db-lock-state";
 
 /// Database state managed by the Tauri app
 pub struct Database {
@@ -36,15 +92,51 @@ pub struct Database {
     db_path: PathBuf,
     /// Tauri app handle for emitting change events
     app_handle: Arc<RwLock<Option<AppHandle>>>,
+    /// Activity tracking for idle auto-lock
+    idle: IdleTracker,
+    /// SQLCipher settings the database file was created with
+    cipher: RwLock<CipherSettings>,
+    /// Columns sealed with the field key
+    field_policy: RwLock<FieldEncryptionPolicy>,
+    /// AES-256 key for policy columns (None when locked or not supplied)
+    field_key: RwLock<Option<Zeroizing<Vec<u8>>>>,
+    /// Single-record change observations fed by the update hook
+    observers: Arc<ChangeObservers>,
+    /// Set by `seal`; refuses every later open until the app restarts
+    sealed: AtomicBool,
 }
 
 impl Database {
     /// Create a new Database instance (starts locked/disconnected)
     pub fn new(db_path: PathBuf) -> Self {
+        Self::with_clock(db_path, Arc::new(SystemClock))
+    }
+
+    /// Create a Database whose idle timer reads the given clock
+    pub fn with_clock(db_path: PathBuf, clock: Arc<dyn Clock>) -> Self {
         Self {
             pool: RwLock::new(None),
-            db_path,
             app_handle: Arc::new(RwLock::new(None)),
+            // Reloaded strictly on open, like the cipher settings
+            idle: IdleTracker::new(
+                clock,
+                load_idle_settings(&db_path)
+                    .ok()
+                    .flatten()
+                    .map_or(Some(idle::DEFAULT_IDLE_TIMEOUT), |s| s.timeout()),
+            ),
+            // Reloaded strictly on open; a corrupt sidecar fails there
+            cipher: RwLock::new(
+                load_cipher_settings(&db_path)
+                    .ok()
+                    .flatten()
+                    .unwrap_or_default(),
+            ),
+            field_policy: RwLock::new(FieldEncryptionPolicy::new()),
+            field_key: RwLock::new(None),
+            observers: Arc::new(ChangeObservers::new()),
+            sealed: AtomicBool::new(false),
+            db_path,
         }
     }
 
@@ -57,33 +149,134 @@ impl Database {
     ///
     /// The key should be derived from the user's master password via
     /// Argon2id + HKDF (matching the existing key derivation in SecureKeyManager).
+    /// The cipher settings, field encryption policy and idle timeout saved
+    /// with the database are loaded too; an unreadable file fails the open
+    /// rather than keying the database with the wrong KDF, leaving sealed
+    /// columns unprotected or never auto-locking.
     pub fn open(&self, key: &str) -> Result<(), String> {
+        if self.is_sealed() {
+            return Err(format!("{DB_SEALED_ERROR} in duress mode"));
+        }
+        let saved_cipher = load_cipher_settings(&self.db_path)?;
+        let pending_cipher =
+            read_cipher_settings(&sibling_path(&self.db_path, PENDING_CIPHER_SUFFIX))?;
+        let field_policy = load_field_policy(&self.db_path)?;
+        let idle_settings = load_idle_settings(&self.db_path)?;
+
         // Ensure parent directory exists
         if let Some(parent) = self.db_path.parent() {
             std::fs::create_dir_all(parent)
                 .map_err(|e| format!("Failed to create DB directory: {e}"))?;
         }
 
-        let pool = DbPool::new(&self.db_path, key, self.app_handle.clone())
-            .map_err(|e| format!("Failed to open database: {e}"))?;
+        let cipher = match saved_cipher {
+            Some(saved) => {
+                *self.cipher.write() = saved;
+                saved
+            }
+            None => *self.cipher.read(),
+        };
+        let pool = match (self.open_pool(key, &cipher), pending_cipher) {
+            (Ok(pool), pending) => {
+                // A rekey that never replaced the file left its settings behind
+                if pending.is_some() {
+                    let pending_path = sibling_path(&self.db_path, PENDING_CIPHER_SUFFIX);
+                    let _ = std::fs::remove_file(pending_path);
+                }
+                pool
+            }
+            // The file was replaced but the sidecar never caught up: finish the rekey
+            (Err(e), Some(pending)) => {
+                let pool = self.open_pool(key, &pending).map_err(|_| e)?;
+                std::fs::rename(
+                    sibling_path(&self.db_path, PENDING_CIPHER_SUFFIX),
+                    sibling_path(&self.db_path, CIPHER_SETTINGS_SUFFIX),
+                )
+                .map_err(|e| format!("Failed to save cipher settings: {e}"))?;
+                *self.cipher.write() = pending;
+                log::info!("Finished an interrupted cipher rekey");
+                pool
+            }
+            (Err(e), None) => return Err(e),
+        };
 
         // Run migrations (needs mutable connection)
         pool.with_connection_mut(|conn| {
-            schema::run_migrations(conn)
-                .map_err(|e| format!("Migration failed: {e}"))
+            schema::run_migrations(conn).map_err(|e| format!("Migration failed: {e}"))
         })?;
 
+        if let Some(field_policy) = field_policy {
+            *self.field_policy.write() = field_policy;
+        }
+        if let Some(idle_settings) = idle_settings {
+            self.idle.set_timeout(idle_settings.timeout());
+        }
         *self.pool.write() = Some(pool);
+        self.idle.touch();
         log::info!("Database opened at {:?}", self.db_path);
+        self.emit(DB_LOCK_STATE_EVENT, false);
         Ok(())
     }
 
-    /// Close the database (wipe connection pool)
+    /// Connect to the database file with the given key and settings
+    fn open_pool(&self, key: &str, cipher: &CipherSettings) -> Result<DbPool, String> {
+        DbPool::new(
+            &self.db_path,
+            key,
+            cipher,
+            self.app_handle.clone(),
+            self.observers.clone(),
+        )
+        .map_err(|e| format!("Failed to open database: {e}"))
+    }
+
+    /// Open the database and install the key for policy-encrypted columns
+    ///
+    /// The field key is only kept if the database opens.
+    pub fn open_with_field_key(
+        &self,
+        key: &str,
+        field_key: Zeroizing<Vec<u8>>,
+    ) -> Result<(), String> {
+        if field_key.len() != 32 {
+            return Err("Invalid field key: expected 32 bytes".to_string());
+        }
+        self.open(key)?;
+        *self.field_key.write() = Some(field_key);
+        Ok(())
+    }
+
+    /// Close the database (wipe connection pool and field key)
     pub fn close(&self) {
-        let mut pool = self.pool.write();
-        if pool.is_some() {
-            *pool = None;
+        let closed = {
+            let mut pool = self.pool.write();
+            self.field_key.write().take();
+            pool.take().is_some()
+        };
+        if closed {
             log::info!("Database closed");
+            self.emit(DB_LOCK_STATE_EVENT, true);
+        }
+    }
+
+    /// Close the database and refuse to open it again until the app restarts
+    ///
+    /// Used by a duress unlock: no key, however obtained, reopens the real
+    /// database behind the decoy identity.
+    pub fn seal(&self) {
+        self.sealed.store(true, Ordering::SeqCst);
+        self.close();
+    }
+
+    /// Whether `seal` was called
+    pub fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::SeqCst)
+    }
+
+    /// Emit an event to the frontend (and app-level listeners such as the tray)
+    fn emit<S: serde::Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some(ref app) = *self.app_handle.read() {
+            let _ = app.emit(event, payload);
         }
     }
 
@@ -92,6 +285,260 @@ impl Database {
         self.pool.read().is_some()
     }
 
+    /// Current idle auto-lock timeout (`None` when disabled)
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle.timeout()
+    }
+
+    /// Set the idle auto-lock timeout and save it with the database
+    ///
+    /// `None` disables auto-lock. Nothing changes if saving fails.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) -> Result<(), String> {
+        save_idle_settings(&self.db_path, &IdleSettings::new(timeout))?;
+        self.idle.set_timeout(timeout);
+        Ok(())
+    }
+
+    /// Close the database if it has been idle for longer than the timeout
+    ///
+    /// Emits `db-auto-locked` and returns true when the database was closed.
+    pub fn close_if_idle(&self) -> bool {
+        {
+            // Check and close under the write lock so no access can slip in between
+            let mut pool = self.pool.write();
+            if pool.is_none() || !self.idle.is_idle() {
+                return false;
+            }
+            *pool = None;
+            self.field_key.write().take();
+        }
+        log::info!("Database auto-locked after inactivity");
+
+        self.emit(idle::DB_AUTO_LOCKED_EVENT, ());
+        self.emit(DB_LOCK_STATE_EVENT, true);
+        true
+    }
+
+    /// SQLCipher settings used when opening the database
+    pub fn cipher_settings(&self) -> CipherSettings {
+        *self.cipher.read()
+    }
+
+    /// Set the SQLCipher settings for a database that does not exist yet
+    ///
+    /// An existing file can only be opened with the settings it was created
+    /// with, so changing them there is refused; use `rekey_cipher` instead.
+    pub fn set_cipher_settings(&self, settings: CipherSettings) -> Result<(), String> {
+        settings.validate()?;
+        let mut cipher = self.cipher.write();
+        if *cipher == settings {
+            return Ok(());
+        }
+        if self.db_path.exists() {
+            return Err(format!(
+                "{CONFLICT_ERROR} database already exists; changing cipher settings requires a rekey"
+            ));
+        }
+        save_cipher_settings(&self.db_path, &settings)?;
+        *cipher = settings;
+        Ok(())
+    }
+
+    /// Columns currently sealed with the field key
+    pub fn field_encryption_policy(&self) -> FieldEncryptionPolicy {
+        self.field_policy.read().clone()
+    }
+
+    /// Replace the field encryption policy and save it with the database
+    ///
+    /// Applies to subsequent reads and writes. Rows written before a column
+    /// joined the policy stay plaintext until rewritten. A column can only
+    /// leave the policy while the database is open and the column holds no
+    /// sealed values, since they could no longer be read.
+    pub fn set_field_encryption_policy(&self, policy: FieldEncryptionPolicy) -> Result<(), String> {
+        let mut current = self.field_policy.write();
+        let dropped: Vec<(&str, &str)> = current
+            .tables()
+            .flat_map(|table| current.columns(table).map(move |column| (table, column)))
+            .filter(|(table, column)| !policy.is_encrypted(table, column))
+            .collect();
+        if !dropped.is_empty() {
+            self.with_connection(|conn| {
+                for (table, column) in &dropped {
+                    if field_encryption::holds_sealed_values(conn, table, column)? {
+                        return Err(format!(
+                            "{CONFLICT_ERROR} {table}.{column} still holds encrypted values"
+                        ));
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        save_field_policy(&self.db_path, &policy)?;
+        *current = policy;
+        Ok(())
+    }
+
+    /// Snapshot of the policy and field key for sealing and opening columns
+    pub fn field_cipher(&self) -> FieldCipher {
+        FieldCipher::new(
+            self.field_encryption_policy(),
+            self.field_key.read().clone(),
+        )
+    }
+
+    /// Run `rewrap` and, if it succeeds, install `new_key` as the field key
+    ///
+    /// The field key is held for writing while `rewrap` re-encrypts the
+    /// sealed columns, so nothing is sealed under the old key behind it.
+    /// A database opened without a field key stays without one.
+    pub fn rotate_field_key<F, T>(
+        &self,
+        new_key: Zeroizing<Vec<u8>>,
+        rewrap: F,
+    ) -> Result<T, String>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, String>,
+    {
+        if new_key.len() != 32 {
+            return Err("Invalid field key: expected 32 bytes".to_string());
+        }
+        self.with_connection_mut(|conn| {
+            let mut field_key = self.field_key.write();
+            let result = rewrap(conn)?;
+            if field_key.is_some() {
+                *field_key = Some(new_key);
+            }
+            Ok(result)
+        })
+    }
+
+    /// Deliver changes to one record to `sink`
+    ///
+    /// The record is the one whose primary key column `pk_column` is `key`.
+    /// It need not exist yet: its insertion is reported like any other
+    /// change, as are rewrites by `INSERT OR REPLACE`. Returns the observer
+    /// id for [`Database::unobserve`].
+    pub fn observe(
+        &self,
+        table: &str,
+        pk_column: &str,
+        key: &str,
+        sink: ChangeSink,
+    ) -> Result<String, String> {
+        let rowid = self.with_connection(|conn| {
+            conn.query_row(
+                &format!("SELECT rowid FROM \"{table}\" WHERE \"{pk_column}\" = ?1"),
+                [key],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to find record to observe: {e}"))
+        })?;
+        Ok(self.observers.observe(table, pk_column, key, rowid, sink))
+    }
+
+    /// Stop an observation; returns false if the id was unknown
+    pub fn unobserve(&self, observer_id: &str) -> bool {
+        self.observers.unobserve(observer_id)
+    }
+
+    /// Cipher settings in effect on the open database
+    pub fn cipher_info(&self) -> Result<CipherInfo, String> {
+        let pool_guard = self.pool.read();
+        let pool = pool_guard
+            .as_ref()
+            .ok_or_else(|| DB_CLOSED_ERROR.to_string())?;
+        pool.cipher_info()
+    }
+
+    /// Re-encrypt the open database under `new_key` with new cipher settings
+    ///
+    /// `PRAGMA rekey` only swaps the key, so the data is exported with
+    /// `sqlcipher_export` into a staging file created with the new settings,
+    /// which then replaces the original before reopening. The original file
+    /// is untouched if the export fails. The new settings are staged in a
+    /// pending sidecar before the file is replaced and committed after, so
+    /// the file and its settings never disagree without a way back for
+    /// `open`. The database stays unlocked
+    /// throughout, so no lock-state event is emitted unless it cannot be
+    /// reopened, in which case it is left locked like `close` leaves it.
+    pub fn rekey_cipher(&self, new_key: &str, settings: CipherSettings) -> Result<(), String> {
+        self.rekey_cipher_with(new_key, settings, |key, settings| {
+            DbPool::new(
+                &self.db_path,
+                key,
+                settings,
+                self.app_handle.clone(),
+                self.observers.clone(),
+            )
+        })
+    }
+
+    /// [`Database::rekey_cipher`], reopening the replaced file with `reopen`
+    fn rekey_cipher_with<F>(
+        &self,
+        new_key: &str,
+        settings: CipherSettings,
+        reopen: F,
+    ) -> Result<(), String>
+    where
+        F: FnOnce(&str, &CipherSettings) -> Result<DbPool, String>,
+    {
+        settings.validate()?;
+        let staging = sibling_path(&self.db_path, ".rekey");
+        remove_db_files(&staging);
+
+        let mut pool = self.pool.write();
+        let current = pool.as_ref().ok_or_else(|| DB_CLOSED_ERROR.to_string())?;
+        if let Err(e) =
+            current.with_connection(|conn| export_rekeyed(conn, &staging, new_key, &settings))
+        {
+            remove_db_files(&staging);
+            return Err(e);
+        }
+
+        let pending = sibling_path(&self.db_path, PENDING_CIPHER_SUFFIX);
+        if let Err(e) = write_cipher_settings(&pending, &settings) {
+            remove_db_files(&staging);
+            let _ = std::fs::remove_file(&pending);
+            return Err(e);
+        }
+
+        // Closing the last connection checkpoints and removes the WAL
+        *pool = None;
+        if let Err(e) = std::fs::rename(&staging, &self.db_path) {
+            remove_db_files(&staging);
+            let _ = std::fs::remove_file(&pending);
+            self.field_key.write().take();
+            drop(pool);
+            self.emit(DB_LOCK_STATE_EVENT, true);
+            return Err(format!("Failed to replace database after rekey: {e}"));
+        }
+        // The file now uses the new settings; if committing the sidecar
+        // fails, the pending one lets the next open finish the job
+        let saved = std::fs::rename(
+            &pending,
+            sibling_path(&self.db_path, CIPHER_SETTINGS_SUFFIX),
+        )
+        .map_err(|e| format!("Failed to save cipher settings: {e}"));
+        *self.cipher.write() = settings;
+
+        match reopen(new_key, &settings) {
+            Ok(reopened) => *pool = Some(reopened),
+            Err(e) => {
+                self.field_key.write().take();
+                drop(pool);
+                self.emit(DB_LOCK_STATE_EVENT, true);
+                return Err(format!("Failed to reopen database after rekey: {e}"));
+            }
+        }
+        self.idle.touch();
+        log::info!("Database re-encrypted with new cipher settings");
+        saved
+    }
+
     /// Execute a function with a database connection from the pool
     pub fn with_connection<F, T>(&self, f: F) -> Result<T, String>
     where
@@ -100,7 +547,23 @@ impl Database {
         let pool_guard = self.pool.read();
         let pool = pool_guard
             .as_ref()
-            .ok_or_else(|| "Database is locked/closed".to_string())?;
+            .ok_or_else(|| DB_CLOSED_ERROR.to_string())?;
+        self.idle.touch();
+        pool.with_connection(f)
+    }
+
+    /// Like [`Database::with_connection`], but without counting as activity
+    ///
+    /// For background bookkeeping (e.g. relay watermarks) that must not keep
+    /// the database from auto-locking.
+    pub fn with_background_connection<F, T>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&Connection) -> Result<T, String>,
+    {
+        let pool_guard = self.pool.read();
+        let pool = pool_guard
+            .as_ref()
+            .ok_or_else(|| DB_CLOSED_ERROR.to_string())?;
         pool.with_connection(f)
     }
 
@@ -113,11 +576,145 @@ impl Database {
         let pool_guard = self.pool.read();
         let pool = pool_guard
             .as_ref()
-            .ok_or_else(|| "Database is locked/closed".to_string())?;
+            .ok_or_else(|| DB_CLOSED_ERROR.to_string())?;
+        self.idle.touch();
         pool.with_connection_mut(f)
     }
 }
 
+/// `path` with `suffix` appended to the file name
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Remove a database file along with its WAL and shared-memory files
+fn remove_db_files(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(sibling_path(path, suffix));
+    }
+}
+
+/// Load the saved cipher settings (`None` if none were saved)
+fn load_cipher_settings(db_path: &Path) -> Result<Option<CipherSettings>, String> {
+    read_cipher_settings(&sibling_path(db_path, CIPHER_SETTINGS_SUFFIX))
+}
+
+fn save_cipher_settings(db_path: &Path, settings: &CipherSettings) -> Result<(), String> {
+    write_cipher_settings(&sibling_path(db_path, CIPHER_SETTINGS_SUFFIX), settings)
+}
+
+/// Read cipher settings from `path` (`None` if the file does not exist)
+fn read_cipher_settings(path: &Path) -> Result<Option<CipherSettings>, String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read cipher settings: {e}")),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Corrupt cipher settings {path:?}: {e}"))
+}
+
+fn write_cipher_settings(path: &Path, settings: &CipherSettings) -> Result<(), String> {
+    let json = serde_json::to_string(settings)
+        .map_err(|e| format!("Failed to serialize cipher settings: {e}"))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create DB directory: {e}"))?;
+    }
+    std::fs::write(path, json).map_err(|e| format!("Failed to save cipher settings: {e}"))
+}
+
+/// Load the saved field encryption policy (`None` if none was saved)
+fn load_field_policy(db_path: &Path) -> Result<Option<FieldEncryptionPolicy>, String> {
+    let path = sibling_path(db_path, FIELD_POLICY_SUFFIX);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read field encryption policy: {e}")),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Corrupt field encryption policy {path:?}: {e}"))
+}
+
+fn save_field_policy(db_path: &Path, policy: &FieldEncryptionPolicy) -> Result<(), String> {
+    let json = serde_json::to_string(policy)
+        .map_err(|e| format!("Failed to serialize field encryption policy: {e}"))?;
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create DB directory: {e}"))?;
+    }
+    std::fs::write(sibling_path(db_path, FIELD_POLICY_SUFFIX), json)
+        .map_err(|e| format!("Failed to save field encryption policy: {e}"))
+}
+
+/// Load the saved idle auto-lock setting (`None` if none was saved)
+fn load_idle_settings(db_path: &Path) -> Result<Option<IdleSettings>, String> {
+    let path = sibling_path(db_path, IDLE_SETTINGS_SUFFIX);
+    let json = match std::fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read idle settings: {e}")),
+    };
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| format!("Corrupt idle settings {path:?}: {e}"))
+}
+
+fn save_idle_settings(db_path: &Path, settings: &IdleSettings) -> Result<(), String> {
+    let json = serde_json::to_string(settings)
+        .map_err(|e| format!("Failed to serialize idle settings: {e}"))?;
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create DB directory: {e}"))?;
+    }
+    std::fs::write(sibling_path(db_path, IDLE_SETTINGS_SUFFIX), json)
+        .map_err(|e| format!("Failed to save idle settings: {e}"))
+}
+
+/// Copy the main database into a new file at `staging` keyed with `key`
+fn export_rekeyed(
+    conn: &Connection,
+    staging: &Path,
+    key: &str,
+    settings: &CipherSettings,
+) -> Result<(), String> {
+    // sqlcipher_export copies schema and data but not the migration version
+    let user_version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read user_version: {e}"))?;
+
+    conn.execute(
+        "ATTACH DATABASE ?1 AS rekeyed KEY ?2",
+        rusqlite::params![staging.to_string_lossy(), key],
+    )
+    .map_err(|e| format!("Failed to attach rekey target: {e}"))?;
+
+    let result = settings.apply(conn, Some("rekeyed")).and_then(|()| {
+        conn.query_row("SELECT sqlcipher_export('rekeyed')", [], |_| Ok(()))
+            .map_err(|e| format!("Failed to export database: {e}"))?;
+        conn.pragma_update(Some("rekeyed"), "user_version", user_version)
+            .map_err(|e| format!("Failed to set user_version: {e}"))
+    });
+
+    let _ = conn.execute("DETACH DATABASE rekeyed", []);
+    result
+}
+
+/// Start the background task that auto-locks the managed `Database`
+pub fn spawn_idle_lock_task(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(idle::IDLE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            app.state::<Database>().close_if_idle();
+        }
+    });
+}
+
 /// Get the default database path for the current platform
 pub fn default_db_path() -> PathBuf {
     let app_dir = dirs_next().unwrap_or_else(|| PathBuf::from("."));
@@ -155,3 +752,473 @@ fn dirs_next() -> Option<PathBuf> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::idle::MockClock;
+    use crate::db::pool::MIN_KDF_ITER;
+
+    fn temp_db_path() -> PathBuf {
+        std::env::temp_dir().join(format!("buildit-idle-{}.db", uuid::Uuid::new_v4().simple()))
+    }
+
+    fn open_test_db(clock: Arc<MockClock>) -> (Database, PathBuf) {
+        let path = temp_db_path();
+        let db = Database::with_clock(path.clone(), clock);
+        db.set_idle_timeout(Some(Duration::from_secs(60))).unwrap();
+        db.open("test-key").unwrap();
+        (db, path)
+    }
+
+    fn cleanup(path: &std::path::Path) {
+        for suffix in [
+            "",
+            "-wal",
+            "-shm",
+            CIPHER_SETTINGS_SUFFIX,
+            PENDING_CIPHER_SUFFIX,
+            FIELD_POLICY_SUFFIX,
+            IDLE_SETTINGS_SUFFIX,
+        ] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    fn insert_note(db: &Database, body: &str) {
+        db.with_connection(|conn| {
+            conn.execute_batch("CREATE TABLE IF NOT EXISTS cipher_notes (body TEXT);")
+                .and_then(|()| conn.execute("INSERT INTO cipher_notes VALUES (?1)", [body]))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+    }
+
+    fn read_notes(db: &Database) -> Vec<String> {
+        db.with_connection(|conn| {
+            let mut stmt = conn
+                .prepare("SELECT body FROM cipher_notes")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| e.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_auto_close_after_inactivity() {
+        let clock = MockClock::new();
+        let (db, path) = open_test_db(clock.clone());
+
+        clock.advance(Duration::from_secs(59));
+        assert!(!db.close_if_idle());
+        assert!(db.is_open());
+
+        clock.advance(Duration::from_secs(1));
+        assert!(db.close_if_idle());
+        assert!(!db.is_open());
+        assert!(db.with_connection(|_| Ok(())).is_err());
+
+        // Already closed: nothing more to do
+        assert!(!db.close_if_idle());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_activity_resets_idle_timer() {
+        let clock = MockClock::new();
+        let (db, path) = open_test_db(clock.clone());
+
+        clock.advance(Duration::from_secs(50));
+        db.with_connection(|conn| conn.execute_batch("SELECT 1;").map_err(|e| e.to_string()))
+            .unwrap();
+        clock.advance(Duration::from_secs(50));
+        assert!(!db.close_if_idle());
+
+        db.with_connection_mut(|_| Ok(())).unwrap();
+        clock.advance(Duration::from_secs(59));
+        assert!(!db.close_if_idle());
+        clock.advance(Duration::from_secs(1));
+        assert!(db.close_if_idle());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_disabled_idle_timeout() {
+        let clock = MockClock::new();
+        let (db, path) = open_test_db(clock.clone());
+
+        db.set_idle_timeout(None).unwrap();
+        assert_eq!(db.idle_timeout(), None);
+        clock.advance(Duration::from_secs(24 * 60 * 60));
+        assert!(!db.close_if_idle());
+        assert!(db.is_open());
+
+        db.close();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_idle_timeout_saved_with_database() {
+        let clock = MockClock::new();
+        let (db, path) = open_test_db(clock.clone());
+        db.set_idle_timeout(Some(Duration::from_secs(120))).unwrap();
+        db.close();
+
+        // A fresh instance (as after a restart) picks the timeout up again
+        let db = Database::with_clock(path.clone(), clock.clone());
+        db.open("test-key").unwrap();
+        assert_eq!(db.idle_timeout(), Some(Duration::from_secs(120)));
+        clock.advance(Duration::from_secs(119));
+        assert!(!db.close_if_idle());
+        clock.advance(Duration::from_secs(1));
+        assert!(db.close_if_idle());
+
+        // Disabling survives a restart too
+        db.set_idle_timeout(None).unwrap();
+        let db = Database::with_clock(path.clone(), clock.clone());
+        db.open("test-key").unwrap();
+        assert_eq!(db.idle_timeout(), None);
+        db.close();
+
+        // A corrupt sidecar fails the open rather than never locking
+        std::fs::write(sibling_path(&path, IDLE_SETTINGS_SUFFIX), "{not json").unwrap();
+        let db = Database::with_clock(path.clone(), clock);
+        let err = db.open("test-key").unwrap_err();
+        assert!(err.starts_with("Corrupt idle settings"), "{err}");
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_field_key_wiped_on_lock() {
+        let clock = MockClock::new();
+        let path = temp_db_path();
+        let db = Database::with_clock(path.clone(), clock.clone());
+        db.set_idle_timeout(Some(Duration::from_secs(60))).unwrap();
+        db.set_field_encryption_policy(FieldEncryptionPolicy::new().with_table("chat", &["body"]))
+            .unwrap();
+        let value = serde_json::json!("hello");
+
+        assert!(db
+            .open_with_field_key("test-key", Zeroizing::new(vec![7u8; 16]))
+            .is_err());
+        assert!(!db.is_open());
+
+        db.open_with_field_key("test-key", Zeroizing::new(vec![7u8; 32]))
+            .unwrap();
+        let sealed = db.field_cipher().seal("chat", "body", &value).unwrap();
+        db.close();
+        let locked = db.field_cipher();
+        assert!(locked.open("chat", "body", sealed.clone()).is_err());
+
+        // Idle auto-lock wipes the key too
+        db.open_with_field_key("test-key", Zeroizing::new(vec![7u8; 32]))
+            .unwrap();
+        let unlocked = db.field_cipher();
+        assert_eq!(
+            unlocked.open("chat", "body", sealed.clone()).unwrap(),
+            value
+        );
+        clock.advance(Duration::from_secs(60));
+        assert!(db.close_if_idle());
+        assert!(db.field_cipher().open("chat", "body", sealed).is_err());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_field_policy_saved_with_database() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.open_with_field_key("test-key", Zeroizing::new(vec![7u8; 32]))
+            .unwrap();
+        let policy = FieldEncryptionPolicy::new().with_table("chat", &["body"]);
+        db.set_field_encryption_policy(policy.clone()).unwrap();
+        let sealed = db
+            .field_cipher()
+            .seal("chat", "body", &serde_json::json!("hello"))
+            .unwrap();
+        db.with_connection(|conn| {
+            conn.execute_batch("CREATE TABLE chat (body TEXT);")
+                .and_then(|()| conn.execute("INSERT INTO chat VALUES (?1)", [sealed.as_str()]))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+
+        // A column holding sealed values cannot leave the policy
+        let err = db
+            .set_field_encryption_policy(FieldEncryptionPolicy::new())
+            .unwrap_err();
+        assert!(err.starts_with("Conflict:"), "{err}");
+        db.close();
+
+        // A fresh instance picks the policy up on open
+        let db = Database::new(path.clone());
+        db.open("test-key").unwrap();
+        assert_eq!(db.field_encryption_policy(), policy);
+        db.close();
+
+        // An unreadable policy fails the open instead of dropping it
+        std::fs::write(sibling_path(&path, FIELD_POLICY_SUFFIX), "{not json").unwrap();
+        assert!(db.open("test-key").is_err());
+        assert!(!db.is_open());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_open_with_custom_cipher_settings() {
+        let path = temp_db_path();
+        let settings = CipherSettings {
+            page_size: Some(8192),
+            kdf_iter: Some(MIN_KDF_ITER),
+        };
+
+        let db = Database::new(path.clone());
+        db.set_cipher_settings(settings).unwrap();
+        db.open("test-key").unwrap();
+        insert_note(&db, "hello");
+        let info = db.cipher_info().unwrap();
+        assert_eq!((info.page_size, info.kdf_iter), (8192, MIN_KDF_ITER));
+        db.close();
+
+        // A fresh instance picks the settings up from the sidecar
+        let db = Database::new(path.clone());
+        assert_eq!(db.cipher_settings(), settings);
+        db.open("test-key").unwrap();
+        assert_eq!(read_notes(&db), vec!["hello".to_string()]);
+
+        // The existing file cannot silently switch settings
+        assert!(db.set_cipher_settings(CipherSettings::default()).is_err());
+        assert!(db
+            .set_cipher_settings(CipherSettings {
+                page_size: Some(1000),
+                kdf_iter: None,
+            })
+            .unwrap_err()
+            .starts_with("Invalid cipher page size"));
+        assert!(db
+            .set_cipher_settings(CipherSettings {
+                page_size: None,
+                kdf_iter: Some(1000),
+            })
+            .unwrap_err()
+            .starts_with("Invalid cipher KDF iterations"));
+        db.close();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_corrupt_cipher_settings_fail_open() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.open("test-key").unwrap();
+        db.close();
+
+        std::fs::write(sibling_path(&path, CIPHER_SETTINGS_SUFFIX), "{not json").unwrap();
+        let db = Database::new(path.clone());
+        let err = db.open("test-key").unwrap_err();
+        assert!(err.starts_with("Corrupt cipher settings"), "{err}");
+        assert!(!db.is_open());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_rekey_cipher_changes_settings_and_key() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.open("old-key").unwrap();
+        insert_note(&db, "survives rekey");
+
+        let settings = CipherSettings {
+            page_size: Some(16384),
+            kdf_iter: Some(MIN_KDF_ITER + 1000),
+        };
+        db.rekey_cipher("new-key", settings).unwrap();
+        assert!(db.is_open());
+        let info = db.cipher_info().unwrap();
+        assert_eq!(
+            (info.page_size, info.kdf_iter),
+            (16384, MIN_KDF_ITER + 1000)
+        );
+        assert_eq!(read_notes(&db), vec!["survives rekey".to_string()]);
+        db.close();
+
+        let db = Database::new(path.clone());
+        assert!(db.open("old-key").is_err());
+        db.open("new-key").unwrap();
+        assert_eq!(read_notes(&db), vec!["survives rekey".to_string()]);
+        db.close();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_failed_reopen_after_rekey_wipes_field_key() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.set_field_encryption_policy(FieldEncryptionPolicy::new().with_table("chat", &["body"]))
+            .unwrap();
+        db.open_with_field_key("old-key", Zeroizing::new(vec![7u8; 32]))
+            .unwrap();
+        insert_note(&db, "survives a failed reopen");
+        let sealed = db
+            .field_cipher()
+            .seal("chat", "body", &serde_json::json!("hello"))
+            .unwrap();
+
+        let settings = CipherSettings {
+            page_size: Some(8192),
+            kdf_iter: Some(MIN_KDF_ITER + 1000),
+        };
+        let err = db
+            .rekey_cipher_with("new-key", settings, |_, _| Err("disk gone".to_string()))
+            .unwrap_err();
+        assert!(
+            err.starts_with("Failed to reopen database after rekey"),
+            "{err}"
+        );
+
+        // Locked as `close` would leave it, key included
+        assert!(!db.is_open());
+        assert!(db.field_cipher().open("chat", "body", sealed).is_err());
+
+        // The file was replaced, so the new key opens it
+        db.open("new-key").unwrap();
+        assert_eq!(
+            read_notes(&db),
+            vec!["survives a failed reopen".to_string()]
+        );
+        assert_eq!(db.cipher_settings(), settings);
+        db.close();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_interrupted_rekey_finishes_on_open() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.open("old-key").unwrap();
+        insert_note(&db, "survives a crash");
+        let old_sidecar = std::fs::read(sibling_path(&path, CIPHER_SETTINGS_SUFFIX)).ok();
+
+        let settings = CipherSettings {
+            page_size: Some(8192),
+            kdf_iter: Some(MIN_KDF_ITER + 2000),
+        };
+        db.rekey_cipher("new-key", settings).unwrap();
+        db.close();
+
+        // Crash between the database rename and the sidecar rename: the file
+        // is rekeyed, the sidecar still describes the old settings
+        write_cipher_settings(&sibling_path(&path, PENDING_CIPHER_SUFFIX), &settings).unwrap();
+        match old_sidecar {
+            Some(old) => std::fs::write(sibling_path(&path, CIPHER_SETTINGS_SUFFIX), old).unwrap(),
+            None => std::fs::remove_file(sibling_path(&path, CIPHER_SETTINGS_SUFFIX)).unwrap(),
+        }
+
+        let db = Database::new(path.clone());
+        assert!(db.open("old-key").is_err());
+        db.open("new-key").unwrap();
+        assert_eq!(read_notes(&db), vec!["survives a crash".to_string()]);
+        assert_eq!(db.cipher_settings(), settings);
+        assert_eq!(load_cipher_settings(&path).unwrap(), Some(settings));
+        assert!(!sibling_path(&path, PENDING_CIPHER_SUFFIX).exists());
+        db.close();
+
+        // Crash before the database rename: the pending sidecar is stale
+        write_cipher_settings(
+            &sibling_path(&path, PENDING_CIPHER_SUFFIX),
+            &CipherSettings {
+                page_size: Some(16384),
+                kdf_iter: Some(MIN_KDF_ITER + 5000),
+            },
+        )
+        .unwrap();
+        let db = Database::new(path.clone());
+        db.open("new-key").unwrap();
+        assert_eq!(db.cipher_settings(), settings);
+        assert!(!sibling_path(&path, PENDING_CIPHER_SUFFIX).exists());
+        db.close();
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_observe_reports_only_the_observed_record() {
+        let path = temp_db_path();
+        let db = Database::new(path.clone());
+        db.open("test-key").unwrap();
+        db.with_connection(|conn| {
+            conn.execute_batch(
+                "CREATE TABLE observed_notes (id TEXT PRIMARY KEY, body TEXT);
+                INSERT INTO observed_notes VALUES ('n1', 'first'), ('n2', 'second');",
+            )
+            .map_err(|e| e.to_string())
+        })
+        .unwrap();
+
+        let changes = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink_changes = Arc::clone(&changes);
+        let late_changes = Arc::clone(&changes);
+        let observer_id = db
+            .observe(
+                "observed_notes",
+                "id",
+                "n1",
+                Arc::new(move |change| sink_changes.lock().push(change)),
+            )
+            .unwrap();
+        let update = |id: &str| {
+            db.with_connection(|conn| {
+                conn.execute(
+                    "UPDATE observed_notes SET body = 'edited' WHERE id = ?1",
+                    [id],
+                )
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+            })
+            .unwrap()
+        };
+
+        update("n2");
+        assert!(changes.lock().is_empty());
+
+        update("n1");
+        let observed = changes.lock().clone();
+        assert_eq!(observed.len(), 1);
+        assert_eq!(observed[0].observer_id, observer_id);
+        assert_eq!(observed[0].action, "UPDATE");
+        assert_eq!(
+            (observed[0].table.as_str(), observed[0].key.as_str()),
+            ("observed_notes", "n1")
+        );
+
+        assert!(db.unobserve(&observer_id));
+        update("n1");
+        assert_eq!(changes.lock().len(), 1);
+
+        // A record that does not exist yet is reported once inserted
+        let pending = db
+            .observe(
+                "observed_notes",
+                "id",
+                "n3",
+                Arc::new(move |change| late_changes.lock().push(change)),
+            )
+            .unwrap();
+        db.with_connection(|conn| {
+            conn.execute("INSERT INTO observed_notes VALUES ('n3', 'third')", [])
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+        let observed = changes.lock().clone();
+        assert_eq!(observed.len(), 2);
+        assert_eq!(observed[1].observer_id, pending);
+        assert_eq!(observed[1].action, "INSERT");
+        db.close();
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,200 @@
+//! Change observation for single records
+//!
+//! The update hook reports every change as `db-change`. Components that
+//! care about one record register an observation instead and receive only
+//! that record's changes on [`DB_OBSERVE_CHANNEL`].
+//!
+//! SQLite's update hook reports the rowid, not the primary key, and the
+//! rowid of a record changes when `INSERT OR REPLACE` rewrites it. So the
+//! hook only queues changes to observed tables; once the statement that
+//! caused them is done, [`ChangeObservers::deliver`] reads the primary key
+//! of each inserted or updated row and matches observations on it. Deleted
+//! rows can no longer be read and are matched on the rowid the record was
+//! last seen at.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::{Mutex, RwLock};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+
+/// Tauri event name carrying [`ObservedChange`]s
+pub const DB_OBSERVE_CHANNEL: &str = "db-observed-change";
+
+/// A change to an observed record
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObservedChange {
+    /// Id returned when the observation was registered
+    pub observer_id: String,
+    /// The operation type: "INSERT", "UPDATE", or "DELETE"
+    pub action: String,
+    pub table: String,
+    /// Primary key of the changed record
+    pub key: String,
+}
+
+/// Destination for observed changes
+pub type ChangeSink = Arc<dyn Fn(ObservedChange) + Send + Sync>;
+
+struct Observation {
+    table: String,
+    pk_column: String,
+    key: String,
+    /// Where the record was last seen; `None` while it does not exist
+    rowid: Option<i64>,
+    sink: ChangeSink,
+}
+
+/// A change queued by the update hook
+struct PendingChange {
+    action: &'static str,
+    table: String,
+    rowid: i64,
+}
+
+/// Registered observations, keyed by observer id
+#[derive(Default)]
+pub struct ChangeObservers {
+    observations: RwLock<HashMap<String, Observation>>,
+    pending: Mutex<Vec<PendingChange>>,
+}
+
+impl ChangeObservers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver changes to the record of `table` whose `pk_column` is `key`
+    /// to `sink`
+    ///
+    /// `rowid` is where the record currently is, or `None` if it does not
+    /// exist yet; it is then reported once inserted. Returns the observer id
+    /// to pass to [`ChangeObservers::unobserve`].
+    pub fn observe(
+        &self,
+        table: &str,
+        pk_column: &str,
+        key: &str,
+        rowid: Option<i64>,
+        sink: ChangeSink,
+    ) -> String {
+        let observer_id = uuid::Uuid::new_v4().simple().to_string();
+        self.observations.write().insert(
+            observer_id.clone(),
+            Observation {
+                table: table.to_string(),
+                pk_column: pk_column.to_string(),
+                key: key.to_string(),
+                rowid,
+                sink,
+            },
+        );
+        observer_id
+    }
+
+    /// Stop an observation; returns false if the id was unknown
+    pub fn unobserve(&self, observer_id: &str) -> bool {
+        self.observations.write().remove(observer_id).is_some()
+    }
+
+    /// Number of registered observations
+    pub fn len(&self) -> usize {
+        self.observations.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queue a change reported by the update hook if its table is observed
+    ///
+    /// The hook may not touch the connection, so matching waits for
+    /// [`ChangeObservers::deliver`].
+    pub fn notify(&self, action: &'static str, table: &str, rowid: i64) {
+        let observed = self
+            .observations
+            .read()
+            .values()
+            .any(|observation| observation.table == table);
+        if observed {
+            self.pending.lock().push(PendingChange {
+                action,
+                table: table.to_string(),
+                rowid,
+            });
+        }
+    }
+
+    /// Forward queued changes to matching observers
+    ///
+    /// Called with the connection that made the changes, after the
+    /// statements that caused them have finished.
+    pub fn deliver(&self, conn: &Connection) {
+        let pending = std::mem::take(&mut *self.pending.lock());
+        if pending.is_empty() {
+            return;
+        }
+
+        let mut observations = self.observations.write();
+        for change in pending {
+            for (observer_id, observation) in observations.iter_mut() {
+                if observation.table != change.table {
+                    continue;
+                }
+                let matches = if change.action == "DELETE" {
+                    let deleted = observation.rowid == Some(change.rowid);
+                    if deleted {
+                        observation.rowid = None;
+                    }
+                    deleted
+                } else {
+                    let key = match primary_key_at(conn, observation, change.rowid) {
+                        Ok(key) => key,
+                        Err(e) => {
+                            log::warn!("Failed to match observed change: {}", e);
+                            continue;
+                        }
+                    };
+                    let found = key.as_deref() == Some(observation.key.as_str());
+                    if found {
+                        observation.rowid = Some(change.rowid);
+                    } else if observation.rowid == Some(change.rowid) {
+                        // The record's key was changed away from the observed one
+                        observation.rowid = None;
+                    }
+                    found
+                };
+
+                if matches {
+                    (observation.sink)(ObservedChange {
+                        observer_id: observer_id.clone(),
+                        action: change.action.to_string(),
+                        table: change.table.clone(),
+                        key: observation.key.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Primary key, as text, of the row `rowid` of the observation's table
+fn primary_key_at(
+    conn: &Connection,
+    observation: &Observation,
+    rowid: i64,
+) -> Result<Option<String>, String> {
+    conn.query_row(
+        &format!(
+            "SELECT CAST(\"{}\" AS TEXT) FROM \"{}\" WHERE rowid = ?1",
+            observation.pk_column, observation.table
+        ),
+        [rowid],
+        |row| row.get(0),
+    )
+    .optional()
+    .map(Option::flatten)
+    .map_err(|e| format!("Query failed: {e}"))
+}
@@ -12,10 +12,13 @@ use std::sync::Arc;
 
 use parking_lot::{Mutex, RwLock};
 use rusqlite::hooks::Action;
+use rusqlite::types::ValueRef;
 use rusqlite::Connection;
 use tauri::{AppHandle, Emitter};
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::db::observe::ChangeObservers;
 
 /// Change event emitted to the frontend when data changes
 #[derive(Debug, Clone, Serialize)]
@@ -28,9 +31,75 @@ pub struct DataChangeEvent {
     pub rowid: i64,
 }
 
+/// SQLCipher settings applied right after the key, before the first read
+///
+/// `None` keeps the SQLCipher default (4096-byte pages, 256000 PBKDF2
+/// iterations in SQLCipher 4). These must match the values the database file
+/// was created with, or the key check fails as if the key were wrong;
+/// changing them on an existing database requires `Database::rekey_cipher`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherSettings {
+    /// `PRAGMA cipher_page_size`: a power of two from 512 to 65536
+    pub page_size: Option<u32>,
+    /// `PRAGMA cipher_kdf_iter`: PBKDF2 iterations applied to the key
+    pub kdf_iter: Option<u32>,
+}
+
+/// Error prefix for rejected cipher settings (mapped to invalid_input)
+pub const INVALID_CIPHER_ERROR: &str = "Invalid cipher";
+
+/// Fewest PBKDF2 iterations accepted for new settings (SQLCipher 4's default)
+pub const MIN_KDF_ITER: u32 = 256_000;
+
+impl CipherSettings {
+    /// Check the settings before they reach SQLCipher, which ignores bad values
+    ///
+    /// Only new settings are checked; settings a database was already created
+    /// with are used as saved, or the file could not be opened.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(size) = self.page_size {
+            if !size.is_power_of_two() || !(512..=65536).contains(&size) {
+                return Err(format!(
+                    "{INVALID_CIPHER_ERROR} page size {size}: must be a power of two from 512 to 65536"
+                ));
+            }
+        }
+        if let Some(iter) = self.kdf_iter {
+            if iter < MIN_KDF_ITER {
+                return Err(format!(
+                    "{INVALID_CIPHER_ERROR} KDF iterations {iter}: must be at least {MIN_KDF_ITER}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply to `schema` (`None` for main) on a keyed connection
+    pub fn apply(&self, conn: &Connection, schema: Option<&str>) -> Result<(), String> {
+        if let Some(size) = self.page_size {
+            conn.pragma_update(schema, "cipher_page_size", size)
+                .map_err(|e| format!("Failed to set cipher_page_size: {e}"))?;
+        }
+        if let Some(iter) = self.kdf_iter {
+            conn.pragma_update(schema, "cipher_kdf_iter", iter)
+                .map_err(|e| format!("Failed to set cipher_kdf_iter: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Cipher settings in effect on an open database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherInfo {
+    pub cipher_version: String,
+    pub page_size: u32,
+    pub kdf_iter: u32,
+}
+
 /// Manages a single SQLCipher-encrypted connection with change notifications
 pub struct DbPool {
     conn: Mutex<Connection>,
+    observers: Arc<ChangeObservers>,
 }
 
 impl DbPool {
@@ -38,14 +107,19 @@ impl DbPool {
     pub fn new(
         db_path: &Path,
         key: &str,
+        cipher: &CipherSettings,
         app_handle: Arc<RwLock<Option<AppHandle>>>,
+        observers: Arc<ChangeObservers>,
     ) -> Result<Self, String> {
-        let conn = Connection::open(db_path)
-            .map_err(|e| format!("Failed to open database: {e}"))?;
+        cipher.validate()?;
+
+        let conn =
+            Connection::open(db_path).map_err(|e| format!("Failed to open database: {e}"))?;
 
         // Apply SQLCipher encryption key
         conn.pragma_update(None, "key", key)
             .map_err(|e| format!("Failed to set encryption key: {e}"))?;
+        cipher.apply(&conn, None)?;
 
         // Performance tuning
         conn.pragma_update(None, "journal_mode", "WAL")
@@ -60,6 +134,7 @@ impl DbPool {
             .map_err(|e| format!("Failed to set cipher_memory_security: {e}"))?;
 
         // Install update_hook for change notifications
+        let hook_observers = observers.clone();
         conn.update_hook(Some(
             move |action: Action, _db: &str, table: &str, rowid: i64| {
                 let action_str = match action {
@@ -69,6 +144,8 @@ impl DbPool {
                     _ => return,
                 };
 
+                hook_observers.notify(action_str, table, rowid);
+
                 let event = DataChangeEvent {
                     action: action_str.to_string(),
                     table: table.to_string(),
@@ -87,6 +164,33 @@ impl DbPool {
 
         Ok(Self {
             conn: Mutex::new(conn),
+            observers,
+        })
+    }
+
+    /// Read the cipher settings in effect on this connection
+    pub fn cipher_info(&self) -> Result<CipherInfo, String> {
+        let conn = self.conn.lock();
+        // SQLCipher reports its settings as text, but accept integers too
+        let read = |pragma: &str| -> Result<String, String> {
+            conn.pragma_query_value(None, pragma, |row| {
+                Ok(match row.get_ref(0)? {
+                    ValueRef::Integer(i) => i.to_string(),
+                    ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+                    other => format!("{other:?}"),
+                })
+            })
+            .map_err(|e| format!("Failed to read {pragma}: {e}"))
+        };
+        let read_u32 = |pragma: &str| -> Result<u32, String> {
+            read(pragma)?
+                .parse()
+                .map_err(|e| format!("Failed to parse {pragma}: {e}"))
+        };
+        Ok(CipherInfo {
+            cipher_version: read("cipher_version")?,
+            page_size: read_u32("cipher_page_size")?,
+            kdf_iter: read_u32("cipher_kdf_iter")?,
         })
     }
 
@@ -96,7 +200,9 @@ impl DbPool {
         F: FnOnce(&Connection) -> Result<T, String>,
     {
         let conn = self.conn.lock();
-        f(&conn)
+        let result = f(&conn);
+        self.observers.deliver(&conn);
+        result
     }
 
     /// Execute a function with a mutable database connection reference
@@ -106,6 +212,8 @@ impl DbPool {
         F: FnOnce(&mut Connection) -> Result<T, String>,
     {
         let mut conn = self.conn.lock();
-        f(&mut conn)
+        let result = f(&mut conn);
+        self.observers.deliver(&conn);
+        result
     }
 }
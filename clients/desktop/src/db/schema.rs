@@ -20,6 +20,16 @@ pub fn run_migrations(conn: &mut Connection) -> Result<(), String> {
         M::up(include_str!("migrations/002_module_tables.sql")),
         // 003: Multi-device and offline support
         M::up(include_str!("migrations/003_device_offline.sql")),
+        // 004: Revision counters for optimistic concurrency (db_put_checked)
+        M::up(include_str!("migrations/004_record_revisions.sql")),
+        // 005: Encrypted key-value store (secure_kv_* commands)
+        M::up(include_str!("migrations/005_secure_kv.sql")),
+        // 006: Blocked contacts (block_contact / unblock_contact commands)
+        M::up(include_str!("migrations/006_blocked_contacts.sql")),
+        // 007: Relay subscription watermarks (resuming subscribe)
+        M::up(include_str!("migrations/007_relay_watermarks.sql")),
+        // 008: Outbox of events waiting to be published (queue_event)
+        M::up(include_str!("migrations/008_outbox.sql")),
     ]);
 
     migrations
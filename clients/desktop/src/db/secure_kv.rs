@@ -0,0 +1,109 @@
+//! Encrypted key/value storage inside the database
+//!
+//! Values in the `secure_kv` table are AES-256-GCM encrypted under a key the
+//! caller supplies, on top of SQLCipher's file encryption. Ratchet session
+//! state and other secrets that must survive a restart live here.
+
+use rusqlite::OptionalExtension;
+use zeroize::Zeroizing;
+
+/// Error for an empty secure_kv key name (mapped to invalid_input)
+pub const EMPTY_KEY_ERROR: &str = "secure_kv key cannot be empty";
+
+/// Domain prefix for the AAD binding a secure_kv value to its key name
+const SECURE_KV_AAD_PREFIX: &[u8] = b"buildit-secure-kv:";
+
+fn aad(key: &str) -> Vec<u8> {
+    [SECURE_KV_AAD_PREFIX, key.as_bytes()].concat()
+}
+
+/// Encrypt `plaintext` under `enc_key` and store it as `key`
+///
+/// The key name is authenticated as AAD, so a row copied under another
+/// name fails to decrypt.
+pub fn put(
+    conn: &rusqlite::Connection,
+    key: &str,
+    plaintext: &str,
+    enc_key: &[u8],
+) -> Result<(), String> {
+    if key.is_empty() {
+        return Err(EMPTY_KEY_ERROR.to_string());
+    }
+    let encrypted = buildit_crypto::aes_encrypt_with_aad(enc_key, plaintext.as_bytes(), &aad(key))
+        .map_err(|e| format!("Encryption failed: {e}"))?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    conn.execute(
+        "INSERT OR REPLACE INTO secure_kv (key, ciphertext, nonce, updated_at) \
+         VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![key, encrypted.ciphertext, encrypted.nonce, now],
+    )
+    .map_err(|e| format!("Insert failed: {e}"))?;
+    Ok(())
+}
+
+/// Read and decrypt the value stored as `key`
+pub fn read(
+    conn: &rusqlite::Connection,
+    key: &str,
+    enc_key: &[u8],
+) -> Result<Option<Zeroizing<String>>, String> {
+    let row = conn
+        .query_row(
+            "SELECT ciphertext, nonce FROM secure_kv WHERE key = ?1",
+            [key],
+            |row| {
+                Ok(buildit_crypto::EncryptedData {
+                    ciphertext: row.get(0)?,
+                    nonce: row.get(1)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Query failed: {e}"))?;
+    let Some(encrypted) = row else {
+        return Ok(None);
+    };
+
+    let plaintext = Zeroizing::new(
+        buildit_crypto::aes_decrypt_with_aad(enc_key, &encrypted, &aad(key))
+            .map_err(|e| format!("Decryption failed for secure_kv entry {key}: {e}"))?,
+    );
+    let value = std::str::from_utf8(&plaintext)
+        .map_err(|_| format!("secure_kv entry {key} is not valid UTF-8"))?;
+    Ok(Some(Zeroizing::new(value.to_string())))
+}
+
+/// Delete the value stored as `key`, returning whether it existed
+pub fn remove(conn: &rusqlite::Connection, key: &str) -> Result<bool, String> {
+    conn.execute("DELETE FROM secure_kv WHERE key = ?1", [key])
+        .map(|deleted| deleted > 0)
+        .map_err(|e| format!("Delete failed: {e}"))
+}
+
+/// Re-encrypt every secure_kv value from `old_key` to `new_key`
+pub fn rewrap(conn: &rusqlite::Connection, old_key: &[u8], new_key: &[u8]) -> Result<u32, String> {
+    let keys: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT key FROM secure_kv")
+            .map_err(|e| format!("Query failed: {e}"))?;
+        let rows = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Query failed: {e}"))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("Row fetch failed: {e}"))?
+    };
+
+    let mut rewrapped = 0;
+    for key in &keys {
+        if let Some(plaintext) = read(conn, key, old_key)? {
+            put(conn, key, &plaintext, new_key)?;
+            rewrapped += 1;
+        }
+    }
+    Ok(rewrapped)
+}
@@ -5,22 +5,80 @@
 //! and call window management.
 
 pub mod ble;
+pub mod capabilities;
 pub mod commands;
+pub mod contacts;
 pub mod crypto;
 pub mod db;
+pub mod logging;
 pub mod nostr;
+pub mod notifications;
 pub mod tray;
 pub mod windows;
 
+use buildit_crypto::{generate_keypair, DuressAlertConfig, NostrEvent};
+use parking_lot::{Mutex, RwLock};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use parking_lot::RwLock;
-use tauri::{Emitter, Listener, Manager};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Listener, Manager, Runtime};
+use zeroize::Zeroizing;
 
 use ble::manager::BleManager;
-use crypto::keyring::KeyringManager;
+use ble::mesh::{MeshError, MeshNetwork};
+use ble::send_queue::{PendingSend, SendQueue};
+use contacts::BlockList;
+use crypto::keyring::{KeyringError, KeyringManager};
 use db::Database;
+use nostr::cert_pinning::CertPinStore;
+use nostr::registry;
 use nostr::relay::NostrRelay;
+use nostr::subscriptions::SubscriptionManager;
+use notifications::Notifications;
+
+/// How long exit waits on BLE peers and relays before abandoning them
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Why `AppState::rotate_identity_key` left the identity unchanged
+#[derive(Debug, thiserror::Error)]
+pub enum IdentityRotationError {
+    #[error(transparent)]
+    Keyring(#[from] KeyringError),
+    #[error(transparent)]
+    Mesh(#[from] MeshError),
+    #[error("Identity changes are disabled in duress mode")]
+    DuressMode,
+}
+
+/// Why `AppState::set_active_identity` left the identity unchanged
+#[derive(Debug, thiserror::Error)]
+pub enum ActiveIdentityError {
+    #[error(transparent)]
+    Mesh(#[from] MeshError),
+    #[error("Identity changes are disabled in duress mode")]
+    DuressMode,
+}
+
+/// Outcome of `AppState::enter_duress_mode`
+#[derive(Debug, serde::Serialize)]
+pub struct DuressModeEntered {
+    /// Public key of the decoy identity now active
+    pub decoy_pubkey: String,
+    /// Alerts signed by the real identity, ready to publish
+    pub alerts: Vec<NostrEvent>,
+}
+
+/// What `AppState::clear_ephemeral_state` dropped
+#[derive(Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct EphemeralStateCleared {
+    /// Cached mesh conversation keys
+    pub conversation_keys: usize,
+    /// Mesh correlation tokens remembered for deduplication
+    pub seen_tokens: usize,
+    /// Identity commitments learned from BLE advertisements
+    pub advertised_commitments: usize,
+}
 
 /// Application state shared across all Tauri commands
 pub struct AppState {
@@ -30,16 +88,277 @@ pub struct AppState {
     pub keyring_manager: Arc<KeyringManager>,
     /// Nostr relay connections
     pub nostr_relays: Arc<RwLock<HashMap<String, Arc<NostrRelay>>>>,
+    /// Certificate pins shared by every relay connection
+    pub pin_store: Arc<CertPinStore>,
+    /// Open frontend subscriptions across relays
+    pub nostr_subscriptions: Arc<SubscriptionManager>,
+    /// Mesh routing state for the active identity
+    pub mesh_network: Arc<RwLock<Option<MeshNetwork>>>,
+    /// Outbound mesh sends waiting their turn, highest priority first
+    pub mesh_send_queue: Arc<Mutex<SendQueue<PendingSend>>>,
+    /// Contacts whose mesh messages and relay events are dropped
+    pub blocked_contacts: Arc<BlockList>,
+    /// System notifications raised for BLE and relay events
+    pub notifications: Arc<Notifications>,
+    /// Public key of the active identity, shared by every subsystem
+    active_pubkey: Arc<RwLock<Option<String>>>,
+    /// Set by a duress unlock; only the decoy identity may be active
+    duress_mode: Arc<AtomicBool>,
 }
 
 impl AppState {
     /// Create a new application state
     pub fn new() -> Self {
+        let blocked_contacts = Arc::new(BlockList::new());
         Self {
             ble_manager: Arc::new(RwLock::new(BleManager::new())),
             keyring_manager: Arc::new(KeyringManager::new("network.buildit.desktop")),
             nostr_relays: Arc::new(RwLock::new(HashMap::new())),
+            pin_store: Arc::new(CertPinStore::with_default_pins(None)),
+            nostr_subscriptions: Arc::new(
+                SubscriptionManager::new().with_block_list(Arc::clone(&blocked_contacts)),
+            ),
+            mesh_network: Arc::new(RwLock::new(None)),
+            mesh_send_queue: Arc::new(Mutex::new(SendQueue::new())),
+            blocked_contacts,
+            notifications: Arc::new(Notifications::new()),
+            active_pubkey: Arc::new(RwLock::new(None)),
+            duress_mode: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Make `private_key` the identity used by BLE and the mesh
+    ///
+    /// Sets the BLE identity commitment and a fresh mesh network for the
+    /// key, and records its public key. Advertising under a previous
+    /// identity is stopped. On an invalid key nothing changes. Refused in
+    /// duress mode, so the real identity cannot be loaded behind the decoy.
+    pub fn set_active_identity(&self, private_key: Vec<u8>) -> Result<String, ActiveIdentityError> {
+        if self.is_duress_mode() {
+            return Err(ActiveIdentityError::DuressMode);
+        }
+        let mesh = MeshNetwork::new(private_key)?;
+        let pubkey = mesh.our_pubkey.clone();
+        self.install_identity(mesh);
+
+        log::info!("Active identity set");
+        Ok(pubkey)
+    }
+
+    /// Replace a possibly compromised identity key with a fresh one
+    ///
+    /// Generates a keypair and hands its hex private key to `store_new_key`
+    /// (production code writes it to the keyring under the user), then
+    /// switches BLE and the mesh to it and wipes the old key with
+    /// `secure_destroy_key`. Returns the new public key so the UI can
+    /// publish a migration notice.
+    ///
+    /// Everything fallible happens before the switch: if the new key cannot
+    /// be stored, it is discarded and the current identity stays in place.
+    pub fn rotate_identity_key<F>(&self, store_new_key: F) -> Result<String, IdentityRotationError>
+    where
+        F: FnOnce(&str) -> Result<(), KeyringError>,
+    {
+        if self.is_duress_mode() {
+            return Err(IdentityRotationError::DuressMode);
+        }
+        let keypair = generate_keypair();
+        let private_key_hex = Zeroizing::new(hex::encode(&keypair.private_key));
+        // Dropping the mesh network on an early return zeroizes the new key
+        let mesh = MeshNetwork::new(keypair.private_key)?;
+        store_new_key(&private_key_hex)?;
+
+        let pubkey = mesh.our_pubkey.clone();
+        if let Some(old) = self.install_identity(mesh) {
+            if let Err(e) = old.destroy() {
+                log::warn!("Failed to securely destroy the old identity key: {}", e);
+            }
+        }
+
+        log::info!("Identity key rotated");
+        Ok(pubkey)
+    }
+
+    /// Switch BLE and the mesh to `mesh`'s identity, returning the previous
+    /// mesh network
+    ///
+    /// Advertising under the previous identity is stopped.
+    fn install_identity(&self, mut mesh: MeshNetwork) -> Option<MeshNetwork> {
+        let pubkey = mesh.our_pubkey.clone();
+        mesh.set_block_list(Arc::clone(&self.blocked_contacts));
+        {
+            let mut ble = self.ble_manager.write();
+            ble.clear_identity();
+            ble.set_identity(&pubkey);
+        }
+        let previous = self.mesh_network.write().replace(mesh);
+        *self.active_pubkey.write() = Some(pubkey);
+        previous
+    }
+
+    /// Put every subsystem into decoy mode after a duress unlock
+    ///
+    /// Signs duress alerts with the real identity if `alerts` is given,
+    /// then makes `decoy_private_key` the identity for BLE and the mesh,
+    /// wiping the real key from memory. Relay subscriptions opened for the
+    /// real identity stop forwarding, and the real database is closed and
+    /// sealed against reopening. Until the app restarts, the identity can no
+    /// longer be changed and the keyring cannot be reached through
+    /// [`AppState::keyring`].
+    ///
+    /// No decoy database is provided: the frontend runs on the in-memory
+    /// decoy contacts and messages from `generate_decoy_contacts` and
+    /// `generate_decoy_messages`, and every database command fails as locked.
+    ///
+    /// Alerts that fail to sign are logged and skipped rather than blocking
+    /// the switch. On an invalid decoy key nothing changes.
+    pub fn enter_duress_mode(
+        &self,
+        decoy_private_key: Vec<u8>,
+        alerts: Option<DuressAlertConfig>,
+        db: Option<&Database>,
+    ) -> Result<DuressModeEntered, MeshError> {
+        let decoy = MeshNetwork::new(decoy_private_key)?;
+
+        let alerts = match (alerts, self.mesh_network.read().as_ref()) {
+            (Some(config), Some(real)) => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as i64;
+                real.create_duress_alerts(config, now).unwrap_or_else(|e| {
+                    log::warn!("Failed to sign duress alerts: {}", e);
+                    Vec::new()
+                })
+            }
+            _ => Vec::new(),
+        };
+
+        self.duress_mode.store(true, Ordering::SeqCst);
+        // Seal first, so the real database is shut before the decoy goes
+        // live; `install_identity` is what replaces the real identity
+        if let Some(db) = db {
+            db.seal();
         }
+        let decoy_pubkey = decoy.our_pubkey.clone();
+        // Dropping the real mesh network zeroizes its keys
+        drop(self.install_identity(decoy));
+        self.nostr_subscriptions.abandon_all();
+
+        log::info!("Duress mode entered");
+        Ok(DuressModeEntered {
+            decoy_pubkey,
+            alerts,
+        })
+    }
+
+    /// Drop session caches that are safe to rebuild, without logging out
+    ///
+    /// Zeroizes the mesh's cached conversation keys and `PerSession`
+    /// signers, and forgets seen correlation tokens and the commitments
+    /// learned from BLE advertisements; all are rebuilt as traffic resumes.
+    /// The identity, keyring, database, relay certificate pins and
+    /// authenticated connections are untouched. Ratchet sessions live with
+    /// their callers, which drop skipped message keys with
+    /// `RatchetSession::clear_skipped_keys`.
+    pub fn clear_ephemeral_state(&self) -> EphemeralStateCleared {
+        let mut cleared = EphemeralStateCleared {
+            advertised_commitments: self.ble_manager.write().forget_advertised_commitments(),
+            ..Default::default()
+        };
+        if let Some(mesh) = self.mesh_network.write().as_mut() {
+            cleared.conversation_keys = mesh.clear_derived_caches();
+            cleared.seen_tokens = mesh.clear_seen_tokens();
+        }
+
+        log::info!("Ephemeral state cleared: {:?}", cleared);
+        cleared
+    }
+
+    /// Whether a duress unlock put the app into decoy mode
+    pub fn is_duress_mode(&self) -> bool {
+        self.duress_mode.load(Ordering::SeqCst)
+    }
+
+    /// The keyring, unless a duress unlock has sealed it off
+    ///
+    /// Commands reach the keyring only through here, so secrets of the real
+    /// identity stay out of reach behind the decoy.
+    pub fn keyring(&self) -> Result<&KeyringManager, KeyringError> {
+        if self.is_duress_mode() {
+            return Err(KeyringError::DuressMode);
+        }
+        Ok(&self.keyring_manager)
+    }
+
+    /// Public key of the active identity
+    pub fn active_pubkey(&self) -> Option<String> {
+        self.active_pubkey.read().clone()
+    }
+
+    /// Wipe the active identity from every subsystem (e.g. on lock)
+    pub fn clear_active_identity(&self) {
+        self.ble_manager.write().clear_identity();
+        // Dropping the mesh network zeroizes its keys
+        self.mesh_network.write().take();
+        if self.active_pubkey.write().take().is_some() {
+            log::info!("Active identity cleared");
+        }
+    }
+
+    /// Tear everything down for exit
+    ///
+    /// Stops scanning and advertising, disconnects all BLE devices, closes
+    /// all relays, wipes the active identity and closes `db`. Peers and
+    /// relays still busy after `timeout` are abandoned, so a stuck peer
+    /// cannot block exit. Returns the addresses and URLs that did not close
+    /// cleanly.
+    ///
+    /// Blocks; call it from outside the async runtime (e.g. Tauri's exit
+    /// event).
+    pub fn shutdown(&self, db: Option<&Database>, timeout: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        let mut unclean = Vec::new();
+
+        {
+            let mut ble = self.ble_manager.write();
+            if ble.is_scanning() {
+                let stopped = tauri::async_runtime::block_on(tokio::time::timeout_at(
+                    deadline,
+                    ble.stop_scan(),
+                ));
+                if !matches!(stopped, Ok(Ok(()))) {
+                    log::warn!("BLE scan did not stop cleanly on shutdown");
+                }
+            }
+            if ble.is_advertising() {
+                if let Err(e) = ble.stop_advertising() {
+                    log::warn!("Failed to stop BLE advertising on shutdown: {}", e);
+                }
+            }
+            unclean.extend(tauri::async_runtime::block_on(ble.disconnect_all(deadline)));
+        }
+
+        unclean.extend(tauri::async_runtime::block_on(registry::close_all(
+            &self.nostr_relays,
+            deadline,
+        )));
+
+        self.clear_active_identity();
+        if let Some(db) = db {
+            db.close();
+        }
+
+        if unclean.is_empty() {
+            log::info!("Shutdown complete");
+        } else {
+            log::warn!(
+                "Shutdown abandoned {} peer(s): {:?}",
+                unclean.len(),
+                unclean
+            );
+        }
+        unclean
     }
 }
 
@@ -49,10 +368,36 @@ impl Default for AppState {
     }
 }
 
+/// Follow the database lock state in the other subsystems
+///
+/// Locking the database (manually or on idle) wipes the active identity;
+/// unlocking reloads the blocked contacts. The duress seal locks the
+/// database too, but the decoy identity it installs stays.
+fn listen_db_lock_state<R: Runtime>(app: &AppHandle<R>) {
+    let handle = app.clone();
+    app.listen(db::DB_LOCK_STATE_EVENT, move |event| {
+        let state = handle.state::<AppState>();
+        if event.payload() == "true" {
+            if !state.is_duress_mode() {
+                state.clear_active_identity();
+            }
+            state.blocked_contacts.clear();
+        } else {
+            let loaded = handle
+                .state::<Database>()
+                .with_connection(contacts::load_blocked);
+            match loaded {
+                Ok(pubkeys) => state.blocked_contacts.replace(pubkeys),
+                Err(e) => log::error!("Failed to load blocked contacts: {}", e),
+            }
+        }
+    });
+}
+
 /// Initialize the Tauri application with all plugins and commands
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::init();
+    logging::init();
 
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -61,17 +406,70 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_deep_link::init())
         .setup(|app| {
-            // Initialize application state
-            let state = AppState::new();
+            // Initialize application state, with TOFU pins kept next to
+            // the database
+            let mut state = AppState::new();
+            state.pin_store = Arc::new(CertPinStore::with_default_pins(Some(
+                db::default_db_path().with_file_name(nostr::cert_pinning::TOFU_PINS_FILE),
+            )));
             app.manage(state);
 
+            // Raise system notifications for BLE events and duress alerts
+            // as the notification policy allows
+            {
+                use notifications::{NotificationCategory, DURESS_ALERT_EVENT};
+                use tauri_plugin_notification::NotificationExt;
+
+                let state = app.state::<AppState>();
+                let handle = app.handle().clone();
+                state.notifications.set_sink(Arc::new(move |notification| {
+                    let shown = handle
+                        .notification()
+                        .builder()
+                        .title(notification.title)
+                        .body(notification.body)
+                        .show();
+                    if let Err(e) = shown {
+                        log::warn!("Failed to show notification: {}", e);
+                    }
+                }));
+
+                let ble_events = state.ble_manager.read().subscribe_events();
+                let notifications = Arc::clone(&state.notifications);
+                tauri::async_runtime::spawn(notifications.watch_ble(ble_events));
+
+                let notifications = Arc::clone(&state.notifications);
+                app.listen(DURESS_ALERT_EVENT, move |_| {
+                    notifications.notify(NotificationCategory::DuressAlert);
+                });
+            }
+
             // Initialize SQLite database (starts closed/locked, opened on user unlock)
             let db_path = db::default_db_path();
             let database = Database::new(db_path.clone());
             database.set_app_handle(app.handle().clone());
             app.manage(database);
+            db::spawn_idle_lock_task(app.handle().clone());
+            nostr::outbox::spawn_outbox_worker(app.handle().clone());
             log::info!("SQLite database configured at {:?}", db_path);
 
+            listen_db_lock_state(app.handle());
+
+            // Catch broken crypto builds before any user data is touched
+            tauri::async_runtime::spawn_blocking(|| {
+                let report = buildit_crypto::crypto_self_test();
+                for check in report.checks.iter().filter(|c| !c.passed) {
+                    log::error!(
+                        "Crypto self-test check '{}' failed: {}",
+                        check.name,
+                        check.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+                if report.passed {
+                    log::info!("Crypto self-test passed");
+                }
+            });
+
             // Setup system tray
             tray::setup_tray(app)?;
 
@@ -94,14 +492,27 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
+            // Capability detection
+            commands::capability_commands::get_capabilities,
             // BLE commands
             commands::ble_commands::start_ble_scan,
             commands::ble_commands::stop_ble_scan,
+            commands::ble_commands::reset_ble_adapter,
+            commands::ble_commands::start_ble_advertising,
+            commands::ble_commands::stop_ble_advertising,
             commands::ble_commands::get_discovered_devices,
             commands::ble_commands::connect_device,
             commands::ble_commands::disconnect_device,
+            commands::ble_commands::forget_device,
             commands::ble_commands::send_mesh_message,
+            commands::ble_commands::estimate_chunked_transfer,
+            commands::ble_commands::get_device_mtu,
+            commands::ble_commands::broadcast_duress_alert,
+            commands::ble_commands::get_mesh_topology,
+            commands::ble_commands::ping_all_peers,
             commands::ble_commands::get_ble_status,
+            commands::ble_commands::make_identity_qr_payload,
+            commands::ble_commands::verify_identity_qr_payload,
             // Crypto/keyring commands - Core
             commands::crypto_commands::store_secret,
             commands::crypto_commands::retrieve_secret,
@@ -109,13 +520,27 @@ pub fn run() {
             commands::crypto_commands::has_secret,
             commands::crypto_commands::generate_keypair,
             commands::crypto_commands::get_public_key_from_private,
+            commands::crypto_commands::verify_keypair,
+            commands::crypto_commands::conversation_id,
+            commands::crypto_commands::set_active_identity,
+            commands::crypto_commands::rotate_identity_key,
+            commands::crypto_commands::clear_active_identity,
+            commands::crypto_commands::clear_ephemeral_state,
             // Crypto - NIP-44 encryption
             commands::crypto_commands::encrypt_nip44,
             commands::crypto_commands::decrypt_nip44,
             commands::crypto_commands::derive_conversation_key,
             // Crypto - Key derivation (Argon2id)
             commands::crypto_commands::derive_master_key,
+            commands::crypto_commands::calibrate_argon2,
+            commands::crypto_commands::store_kdf_salt,
+            commands::crypto_commands::get_kdf_salt,
+            commands::crypto_commands::get_or_create_kdf_salt,
+            commands::crypto_commands::crypto_self_test,
             commands::crypto_commands::derive_database_key,
+            commands::crypto_commands::create_recovery_kit,
+            commands::crypto_commands::recover_from_kit,
+            commands::crypto_commands::open_recovery_share,
             // Crypto - AES-256-GCM storage encryption
             commands::crypto_commands::aes_encrypt,
             commands::crypto_commands::aes_decrypt,
@@ -123,15 +548,19 @@ pub fn run() {
             commands::crypto_commands::schnorr_sign,
             commands::crypto_commands::schnorr_verify,
             commands::crypto_commands::compute_event_id,
+            // Crypto - Update verification
+            commands::crypto_commands::verify_release_artifact,
             // Crypto - Duress password system
             commands::crypto_commands::hash_duress_password,
             commands::crypto_commands::check_duress_password,
+            commands::crypto_commands::enter_duress_mode,
             commands::crypto_commands::validate_duress_password,
             commands::crypto_commands::generate_decoy_identity,
             commands::crypto_commands::generate_decoy_contacts,
             commands::crypto_commands::generate_decoy_messages,
             commands::crypto_commands::create_duress_alert,
             commands::crypto_commands::create_duress_alerts,
+            commands::crypto_commands::duress_dry_run,
             commands::crypto_commands::secure_destroy_key,
             // Crypto - Utilities
             commands::crypto_commands::generate_salt,
@@ -143,22 +572,66 @@ pub fn run() {
             // Nostr commands
             commands::nostr_commands::sign_nostr_event,
             commands::nostr_commands::verify_nostr_event,
+            commands::nostr_commands::validate_nostr_event,
             commands::nostr_commands::gift_wrap_message,
             commands::nostr_commands::unwrap_gift_message,
+            commands::nostr_commands::publish_event,
+            commands::nostr_commands::queue_event,
+            commands::nostr_commands::list_outbox,
+            commands::nostr_commands::retry_outbox_entry,
+            commands::nostr_commands::remove_outbox_entry,
+            commands::nostr_commands::add_relay,
+            commands::nostr_commands::connect_relays,
+            commands::nostr_commands::validate_tofu_pins,
+            commands::nostr_commands::repair_tofu_pins,
+            commands::nostr_commands::remove_relay,
+            commands::nostr_commands::set_relay_role,
+            commands::nostr_commands::list_relays,
+            commands::nostr_commands::get_default_relays,
+            commands::nostr_commands::test_relay,
+            commands::nostr_commands::subscribe,
+            commands::nostr_commands::unsubscribe,
+            // Notification commands
+            commands::notification_commands::get_notification_policy,
+            commands::notification_commands::set_notification_policy,
             // Database commands
             commands::db_commands::db_open,
             commands::db_commands::db_close,
             commands::db_commands::db_is_open,
+            commands::db_commands::db_get_idle_timeout,
+            commands::db_commands::db_set_idle_timeout,
+            commands::db_commands::db_get_cipher_info,
+            commands::db_commands::db_set_cipher_settings,
+            commands::db_commands::db_rekey_cipher,
+            commands::db_commands::db_get_field_encryption_policy,
+            commands::db_commands::db_set_field_encryption_policy,
+            commands::db_commands::secure_kv_set,
+            commands::db_commands::secure_kv_get,
+            commands::db_commands::secure_kv_delete,
+            commands::db_commands::rewrap_secrets,
+            commands::db_commands::block_contact,
+            commands::db_commands::unblock_contact,
+            commands::db_commands::is_contact_blocked,
             commands::db_commands::db_put,
+            commands::db_commands::db_put_returning,
+            commands::db_commands::db_put_checked,
             commands::db_commands::db_get,
+            commands::db_commands::db_observe,
+            commands::db_commands::db_unobserve,
+            commands::db_commands::db_get_many,
             commands::db_commands::db_get_all,
             commands::db_commands::db_query,
+            commands::db_commands::db_query_json,
             commands::db_commands::db_delete,
             commands::db_commands::db_bulk_put,
+            commands::db_commands::import_legacy_messages,
             commands::db_commands::db_count,
+            commands::db_commands::db_count_grouped,
             commands::db_commands::db_execute_query,
             commands::db_commands::db_delete_where,
             commands::db_commands::db_clear_table,
+            commands::db_commands::db_table_info,
+            commands::db_commands::db_list_tables,
             // Call window commands
             windows::call_window::create_call_window,
             windows::call_window::close_call_window,
@@ -171,6 +644,413 @@ pub fn run() {
             windows::call_window::get_call_windows,
             windows::call_window::close_all_call_windows,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running BuildIt Network Desktop");
+        .build(tauri::generate_context!())
+        .expect("error while building BuildIt Network Desktop")
+        .run(|app, event| {
+            // Closing the last window exits too, so this covers both
+            if let tauri::RunEvent::Exit = event {
+                let db = app.try_state::<Database>();
+                app.state::<AppState>()
+                    .shutdown(db.as_deref(), SHUTDOWN_TIMEOUT);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ble::manager::IdentityCommitment;
+    use ble::mesh::EphemeralPolicy;
+    use buildit_crypto::get_public_key;
+    use nostr::relay::{RelayRole, RelayStatus};
+    use nostr::test_support::{spawn_mock_relay, test_pin_store};
+
+    fn add_mock_relay(state: &AppState) -> Arc<NostrRelay> {
+        tauri::async_runtime::block_on(async {
+            let url = spawn_mock_relay(true, "").await;
+            registry::add_relay(&state.nostr_relays, &url, RelayRole::ReadWrite, |url| {
+                Ok(NostrRelay::new(url, test_pin_store(false)))
+            })
+            .await
+            .unwrap()
+        })
+    }
+
+    fn open_temp_db() -> (Database, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "buildit-shutdown-{}.db",
+            uuid::Uuid::new_v4().simple()
+        ));
+        let db = Database::new(path.clone());
+        db.open("test-key").unwrap();
+        (db, path)
+    }
+
+    fn remove_db_files(path: &std::path::Path) {
+        for suffix in [
+            "",
+            "-wal",
+            "-shm",
+            ".cipher.json",
+            ".fields.json",
+            ".idle.json",
+        ] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[test]
+    fn test_set_active_identity_propagates_to_ble_and_mesh() {
+        let state = AppState::new();
+        let keypair = generate_keypair();
+
+        let pubkey = state
+            .set_active_identity(keypair.private_key.clone())
+            .unwrap();
+        assert_eq!(pubkey, keypair.public_key);
+        assert_eq!(state.active_pubkey(), Some(keypair.public_key.clone()));
+
+        let ble = state.ble_manager.read();
+        let identity = ble.identity().unwrap();
+        assert_eq!(identity.pubkey, keypair.public_key);
+        assert!(IdentityCommitment::verify(
+            &identity.commitment,
+            &keypair.public_key,
+            &identity.nonce
+        ));
+        drop(ble);
+
+        let mesh = state.mesh_network.read();
+        assert_eq!(mesh.as_ref().unwrap().our_pubkey, keypair.public_key);
+    }
+
+    #[test]
+    fn test_switching_identity_replaces_everywhere() {
+        let state = AppState::new();
+        let first = generate_keypair();
+        let second = generate_keypair();
+
+        state.set_active_identity(first.private_key).unwrap();
+        state.set_active_identity(second.private_key).unwrap();
+
+        assert_eq!(state.active_pubkey(), Some(second.public_key.clone()));
+        assert_eq!(
+            state.ble_manager.read().identity().unwrap().pubkey,
+            second.public_key
+        );
+        assert_eq!(
+            state.mesh_network.read().as_ref().unwrap().our_pubkey,
+            second.public_key
+        );
+
+        // An invalid key leaves the current identity in place
+        assert!(state.set_active_identity(vec![0u8; 32]).is_err());
+        assert_eq!(state.active_pubkey(), Some(second.public_key));
+    }
+
+    #[test]
+    fn test_clear_active_identity_wipes_everywhere() {
+        let state = AppState::new();
+        state
+            .set_active_identity(generate_keypair().private_key)
+            .unwrap();
+
+        state.clear_active_identity();
+        assert_eq!(state.active_pubkey(), None);
+        assert!(state.ble_manager.read().identity().is_none());
+        assert!(state.mesh_network.read().is_none());
+
+        // Clearing again is harmless
+        state.clear_active_identity();
+    }
+
+    #[test]
+    fn test_enter_duress_mode_switches_to_decoy_everywhere() {
+        let state = AppState::new();
+        let real = generate_keypair();
+        let decoy = generate_keypair();
+        let contact = generate_keypair();
+        state.set_active_identity(real.private_key.clone()).unwrap();
+        let (db, path) = open_temp_db();
+
+        let entered = state
+            .enter_duress_mode(
+                decoy.private_key.clone(),
+                Some(DuressAlertConfig {
+                    trusted_contact_pubkeys: vec![contact.public_key.clone()],
+                    include_location: false,
+                    custom_message: None,
+                }),
+                Some(&db),
+            )
+            .unwrap();
+        assert_eq!(entered.decoy_pubkey, decoy.public_key);
+        assert!(state.is_duress_mode());
+
+        // Every subsystem now presents the decoy
+        assert_eq!(state.active_pubkey(), Some(decoy.public_key.clone()));
+        let ble_identity = state.ble_manager.read().identity().unwrap().pubkey.clone();
+        assert_eq!(ble_identity, decoy.public_key);
+        let mesh_pubkey = state
+            .mesh_network
+            .read()
+            .as_ref()
+            .unwrap()
+            .our_pubkey
+            .clone();
+        assert_eq!(mesh_pubkey, decoy.public_key);
+        assert!(!db.is_open());
+
+        // Neither the real database nor the keyring can be reached again
+        assert!(db.is_sealed());
+        assert!(db.open("test-key").is_err());
+        assert!(!db.is_open());
+        assert!(matches!(state.keyring(), Err(KeyringError::DuressMode)));
+
+        // The alert was signed by the real identity before the switch
+        assert_eq!(entered.alerts.len(), 1);
+        let unwrapped =
+            buildit_crypto::unwrap_gift_wrap(contact.private_key, entered.alerts[0].clone())
+                .unwrap();
+        assert_eq!(unwrapped.sender_pubkey, real.public_key);
+
+        // The real key cannot be loaded behind the decoy
+        assert!(matches!(
+            state.set_active_identity(real.private_key.clone()),
+            Err(ActiveIdentityError::DuressMode)
+        ));
+        assert!(matches!(
+            state.rotate_identity_key(|_| Ok(())),
+            Err(IdentityRotationError::DuressMode)
+        ));
+        assert_eq!(state.active_pubkey(), Some(decoy.public_key));
+        remove_db_files(&path);
+    }
+
+    #[test]
+    fn test_duress_lock_event_keeps_decoy_identity() {
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        let (db, path) = open_temp_db();
+        app.manage(db);
+        listen_db_lock_state(app.handle());
+
+        let state = app.state::<AppState>();
+        let real = generate_keypair();
+        state.set_active_identity(real.private_key.clone()).unwrap();
+
+        // An ordinary lock wipes the identity
+        app.handle().emit(db::DB_LOCK_STATE_EVENT, true).unwrap();
+        assert_eq!(state.active_pubkey(), None);
+        assert!(state.ble_manager.read().identity().is_none());
+
+        // The lock emitted by the duress seal leaves the decoy in place
+        state.set_active_identity(real.private_key).unwrap();
+        let decoy = generate_keypair();
+        let db = app.state::<Database>();
+        state
+            .enter_duress_mode(decoy.private_key, None, Some(&*db))
+            .unwrap();
+        app.handle().emit(db::DB_LOCK_STATE_EVENT, true).unwrap();
+        assert_eq!(state.active_pubkey(), Some(decoy.public_key.clone()));
+        assert_eq!(
+            state.ble_manager.read().identity().unwrap().pubkey,
+            decoy.public_key
+        );
+        assert!(state.mesh_network.read().is_some());
+        remove_db_files(&path);
+    }
+
+    #[test]
+    fn test_enter_duress_mode_rejects_invalid_decoy() {
+        let state = AppState::new();
+        let real = generate_keypair();
+        state.set_active_identity(real.private_key).unwrap();
+
+        assert!(state.enter_duress_mode(vec![0u8; 32], None, None).is_err());
+        assert!(!state.is_duress_mode());
+        assert_eq!(state.active_pubkey(), Some(real.public_key));
+    }
+
+    #[test]
+    fn test_clear_ephemeral_state_keeps_durable_state() {
+        let state = AppState::new();
+        let identity = generate_keypair();
+        let peer = generate_keypair();
+        state
+            .set_active_identity(identity.private_key.clone())
+            .unwrap();
+        {
+            let mut mesh = state.mesh_network.write();
+            let mesh = mesh.as_mut().unwrap();
+            mesh.create_message(&peer.public_key, b"hi", EphemeralPolicy::PerSession)
+                .unwrap();
+            mesh.mark_token_seen("token");
+        }
+        let (db, path) = open_temp_db();
+        db.with_connection(|conn| {
+            conn.execute(
+                "INSERT INTO secure_kv (key, ciphertext, nonce, updated_at)
+                 VALUES ('session:peer', x'01', x'02', 0)",
+                [],
+            )
+            .map_err(|e| e.to_string())
+        })
+        .unwrap();
+
+        assert_eq!(
+            state.clear_ephemeral_state(),
+            EphemeralStateCleared {
+                conversation_keys: 1,
+                seen_tokens: 1,
+                advertised_commitments: 0,
+            }
+        );
+        assert_eq!(
+            state.clear_ephemeral_state(),
+            EphemeralStateCleared::default()
+        );
+
+        // The identity still works everywhere
+        assert_eq!(state.active_pubkey(), Some(identity.public_key.clone()));
+        assert_eq!(
+            state.ble_manager.read().identity().unwrap().pubkey,
+            identity.public_key
+        );
+        let message = state
+            .mesh_network
+            .write()
+            .as_mut()
+            .unwrap()
+            .create_message(&peer.public_key, b"again", EphemeralPolicy::PerSession)
+            .unwrap();
+        let decrypted = message.try_decrypt_for_us(&peer.private_key).unwrap();
+        assert_eq!(decrypted.sender_pubkey, identity.public_key);
+
+        // Stored sessions are untouched
+        assert!(db.is_open());
+        let stored: i64 = db
+            .with_connection(|conn| {
+                conn.query_row("SELECT COUNT(*) FROM secure_kv", [], |row| row.get(0))
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap();
+        assert_eq!(stored, 1);
+
+        db.close();
+        remove_db_files(&path);
+    }
+
+    #[test]
+    fn test_shutdown_closes_everything() {
+        let state = AppState::new();
+        state
+            .set_active_identity(generate_keypair().private_key)
+            .unwrap();
+        let relays = [add_mock_relay(&state), add_mock_relay(&state)];
+        let (db, path) = open_temp_db();
+
+        let unclean = state.shutdown(Some(&db), Duration::from_secs(5));
+        assert!(unclean.is_empty(), "{unclean:?}");
+
+        assert!(state.nostr_relays.read().is_empty());
+        for relay in &relays {
+            let status = tauri::async_runtime::block_on(relay.status());
+            assert_eq!(status, RelayStatus::Disconnected);
+        }
+        let ble = state.ble_manager.read();
+        assert!(!ble.is_scanning());
+        assert!(!ble.is_advertising());
+        assert!(ble.identity().is_none());
+        drop(ble);
+        assert_eq!(state.active_pubkey(), None);
+        assert!(!db.is_open());
+
+        // Shutting down twice is harmless
+        assert!(state.shutdown(Some(&db), Duration::from_secs(5)).is_empty());
+        remove_db_files(&path);
+    }
+
+    #[test]
+    fn test_shutdown_respects_timeout() {
+        let state = AppState::new();
+        add_mock_relay(&state);
+        let (db, path) = open_temp_db();
+
+        // With no time at all, nothing may hold up exit
+        let started = std::time::Instant::now();
+        state.shutdown(Some(&db), Duration::ZERO);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Resources are released even when abandoned
+        assert!(state.nostr_relays.read().is_empty());
+        assert!(!db.is_open());
+        remove_db_files(&path);
+    }
+
+    #[test]
+    fn test_rotate_identity_key_switches_everywhere() {
+        let state = AppState::new();
+        let old = generate_keypair();
+        state.set_active_identity(old.private_key).unwrap();
+
+        let mut stored = None;
+        let pubkey = state
+            .rotate_identity_key(|key| {
+                stored = Some(key.to_string());
+                Ok(())
+            })
+            .unwrap();
+
+        // The stored key is the one now in use
+        let stored = hex::decode(stored.unwrap()).unwrap();
+        assert_eq!(get_public_key(stored).unwrap(), pubkey);
+        assert_ne!(pubkey, old.public_key);
+
+        assert_eq!(state.active_pubkey(), Some(pubkey.clone()));
+        assert_eq!(state.ble_manager.read().identity().unwrap().pubkey, pubkey);
+        assert_eq!(
+            state.mesh_network.read().as_ref().unwrap().our_pubkey,
+            pubkey
+        );
+    }
+
+    #[test]
+    fn test_rotate_identity_key_rolls_back_on_keyring_failure() {
+        let state = AppState::new();
+        let old = generate_keypair();
+        state.set_active_identity(old.private_key).unwrap();
+        let commitment = state
+            .ble_manager
+            .read()
+            .identity()
+            .unwrap()
+            .commitment
+            .clone();
+
+        let result = state
+            .rotate_identity_key(|_| Err(KeyringError::StoreError("keyring locked".to_string())));
+        assert!(matches!(
+            result,
+            Err(IdentityRotationError::Keyring(KeyringError::StoreError(_)))
+        ));
+
+        // Nothing was switched
+        assert_eq!(state.active_pubkey(), Some(old.public_key.clone()));
+        let ble = state.ble_manager.read();
+        assert_eq!(ble.identity().unwrap().pubkey, old.public_key);
+        assert_eq!(ble.identity().unwrap().commitment, commitment);
+        drop(ble);
+        assert_eq!(
+            state.mesh_network.read().as_ref().unwrap().our_pubkey,
+            old.public_key
+        );
+    }
+
+    #[test]
+    fn test_rotate_identity_key_without_active_identity() {
+        let state = AppState::new();
+        let pubkey = state.rotate_identity_key(|_| Ok(())).unwrap();
+        assert_eq!(state.active_pubkey(), Some(pubkey));
+    }
 }
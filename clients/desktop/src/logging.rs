@@ -0,0 +1,160 @@
+//! Structured logging with per-command correlation IDs
+//!
+//! Every Tauri command runs inside a `tracing` span carrying a fresh
+//! `correlation_id`, so a single user action can be followed through the
+//! BLE, crypto and database layers. Existing `log::` macros are bridged into
+//! `tracing` and pick up the span fields automatically.
+//!
+//! Configuration (environment):
+//! - `RUST_LOG` - filter directives (defaults to `info`)
+//! - `BUILDIT_LOG_FORMAT` - `pretty` (default) or `json`
+//!
+//! SECURITY: Commands are instrumented with `skip_all` so that arguments
+//! (private keys, passwords, plaintext) are never recorded as span fields.
+
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable selecting the log output format
+pub const LOG_FORMAT_ENV: &str = "BUILDIT_LOG_FORMAT";
+
+/// Log output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable single-line output
+    Pretty,
+    /// One JSON object per line, including span fields
+    Json,
+}
+
+impl LogFormat {
+    /// Parse a format name, falling back to `Pretty` for unknown values
+    pub fn parse(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "json" => LogFormat::Json,
+            _ => LogFormat::Pretty,
+        }
+    }
+
+    /// Read the format from `BUILDIT_LOG_FORMAT`
+    pub fn from_env() -> Self {
+        std::env::var(LOG_FORMAT_ENV)
+            .map(|v| Self::parse(&v))
+            .unwrap_or(LogFormat::Pretty)
+    }
+}
+
+/// Install the global log subscriber
+///
+/// Replaces `env_logger`. Safe to call more than once; later calls are no-ops.
+pub fn init() {
+    init_with_format(LogFormat::from_env());
+}
+
+/// Install the global log subscriber with an explicit format
+pub fn init_with_format(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    // try_init also installs the `log` -> `tracing` bridge
+    let result = match format {
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init(),
+        LogFormat::Pretty => builder.try_init(),
+    };
+
+    if result.is_err() {
+        log::debug!("Log subscriber already installed");
+    }
+}
+
+/// Generate a new correlation ID for one command invocation
+pub fn new_correlation_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing::Instrument;
+
+    #[derive(Clone, Default)]
+    struct CaptureWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CaptureWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(correlation_id = %new_correlation_id()))]
+    async fn sample_command(secret: String) -> usize {
+        log::info!("starting sample command");
+        let len = async { secret.len() }.in_current_span().await;
+        log::info!("finished sample command");
+        len
+    }
+
+    #[test]
+    fn test_log_format_parse() {
+        assert_eq!(LogFormat::parse("json"), LogFormat::Json);
+        assert_eq!(LogFormat::parse(" JSON "), LogFormat::Json);
+        assert_eq!(LogFormat::parse("pretty"), LogFormat::Pretty);
+        assert_eq!(LogFormat::parse("bogus"), LogFormat::Pretty);
+    }
+
+    #[test]
+    fn test_correlation_id_is_unique() {
+        let a = new_correlation_id();
+        let b = new_correlation_id();
+        assert_eq!(a.len(), 32);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_correlation_id_consistent_within_command() {
+        let _ = tracing_log::LogTracer::init();
+
+        let writer = CaptureWriter::default();
+        let make_writer = {
+            let writer = writer.clone();
+            move || writer.clone()
+        };
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_writer(make_writer)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            futures::executor::block_on(sample_command("top secret".to_string()));
+        });
+
+        let output = String::from_utf8(writer.0.lock().unwrap().clone()).unwrap();
+        let ids: Vec<String> = output
+            .lines()
+            .map(|line| {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                value["span"]["correlation_id"]
+                    .as_str()
+                    .expect("log line missing correlation_id")
+                    .to_string()
+            })
+            .collect();
+
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(ids[0].len(), 32);
+        // Arguments are skipped, so secrets never reach the log output
+        assert!(!output.contains("top secret"));
+    }
+}
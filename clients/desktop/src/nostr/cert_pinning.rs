@@ -6,6 +6,7 @@
 //! - Trust-on-First-Use (TOFU) for unknown relays
 //! - Backup pins for certificate rotation
 //! - Warning/blocking when certificates change unexpectedly
+//! - A minimum TLS version and cipher suite allow-list (`TlsPolicy`)
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use ring::digest::{digest, SHA256};
@@ -15,7 +16,8 @@ use rustls::{DigitallySignedStruct, Error as TlsError, SignatureScheme};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 use thiserror::Error;
 
@@ -45,6 +47,12 @@ pub enum CertPinError {
     #[error("Pin storage error: {0}")]
     StorageError(String),
 
+    #[error("TOFU pin file {path} is corrupt: {reason}")]
+    TofuStorageCorrupt { path: String, reason: String },
+
+    #[error("Not trusting {host} on first use: TOFU pin file {path} needs repair")]
+    TofuRepairRequired { host: String, path: String },
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 }
@@ -58,8 +66,8 @@ pub struct RelayPinConfig {
     /// Backup pins for certificate rotation
     pub backup_pins: Vec<String>,
 
-    /// When this pin was last verified
-    pub last_verified: Option<u64>,
+    /// When this pin was last verified (YYYY-MM-DD)
+    pub last_verified: Option<String>,
 
     /// Optional notes about this relay
     #[serde(default)]
@@ -93,6 +101,23 @@ impl Default for CertPinConfig {
     }
 }
 
+/// Bundled relay pin configuration
+/// Path relative to clients/desktop/src/nostr/cert_pinning.rs
+pub(crate) const RELAY_PINS_JSON: &str =
+    include_str!("../../../../protocol/security/relay-pins.json");
+
+/// File name of the persisted TOFU pins, next to the database
+pub const TOFU_PINS_FILE: &str = "tofu-pins.json";
+
+/// Result of checking the TOFU pin file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TofuStorageStatus {
+    /// Whether the pin file exists (a missing file is a valid empty set)
+    pub exists: bool,
+    /// Pins loaded from the file
+    pub pins_loaded: usize,
+}
+
 /// Certificate pin storage and verification
 #[derive(Debug)]
 pub struct CertPinStore {
@@ -107,6 +132,10 @@ pub struct CertPinStore {
 
     /// Path to persist TOFU pins
     tofu_storage_path: Option<PathBuf>,
+
+    /// Set when the TOFU file failed to parse; saving and trust on first
+    /// use are suspended until it is repaired
+    tofu_storage_corrupt: AtomicBool,
 }
 
 impl CertPinStore {
@@ -117,15 +146,27 @@ impl CertPinStore {
             known_pins: HashMap::new(),
             tofu_pins: Arc::new(RwLock::new(HashMap::new())),
             tofu_storage_path: None,
+            tofu_storage_corrupt: AtomicBool::new(false),
+        }
+    }
+
+    /// Store with the default config and bundled pins, persisting TOFU pins
+    /// at `tofu_path` when given
+    pub fn with_default_pins(tofu_path: Option<PathBuf>) -> Self {
+        let mut store = Self::new(CertPinConfig::default());
+        if let Err(e) = store.load_known_pins() {
+            log::error!("Failed to load bundled relay pins: {}", e);
+        }
+        if let Some(path) = tofu_path {
+            store.set_tofu_storage_path(path);
         }
+        store
     }
 
     /// Load known pins from the embedded configuration
     pub fn load_known_pins(&mut self) -> Result<(), CertPinError> {
         // Load from embedded relay-pins.json
-        // Path relative to clients/desktop/src/nostr/cert_pinning.rs
-        let pins_json = include_str!("../../../../protocol/security/relay-pins.json");
-        let config: serde_json::Value = serde_json::from_str(pins_json)
+        let config: serde_json::Value = serde_json::from_str(RELAY_PINS_JSON)
             .map_err(|e| CertPinError::ConfigError(e.to_string()))?;
 
         if let Some(relays) = config.get("relays").and_then(|r| r.as_object()) {
@@ -141,28 +182,106 @@ impl CertPinStore {
     }
 
     /// Set the path for TOFU pin persistence
+    ///
+    /// A corrupt pin file is logged and left in place; see
+    /// [`CertPinStore::validate_tofu_storage`].
     pub fn set_tofu_storage_path(&mut self, path: PathBuf) {
         self.tofu_storage_path = Some(path);
-        self.load_tofu_pins();
+        if let Err(e) = self.validate_tofu_storage() {
+            log::error!("{}", e);
+        }
     }
 
-    /// Load TOFU pins from storage
-    fn load_tofu_pins(&self) {
-        if let Some(ref path) = self.tofu_storage_path {
-            if path.exists() {
-                if let Ok(contents) = std::fs::read_to_string(path) {
-                    if let Ok(pins) = serde_json::from_str::<HashMap<String, String>>(&contents) {
-                        if let Ok(mut tofu) = self.tofu_pins.write() {
-                            *tofu = pins;
-                        }
-                    }
-                }
+    /// Reload TOFU pins from storage, reporting a corrupt file
+    ///
+    /// This fails closed: on corruption the pins already in memory are kept,
+    /// saving is suspended so the file is not overwritten, and no host is
+    /// trusted on first use until [`CertPinStore::repair_tofu_storage`] is
+    /// called. A relay pinned in the unreadable file could otherwise present
+    /// any certificate and be pinned afresh.
+    pub fn validate_tofu_storage(&self) -> Result<TofuStorageStatus, CertPinError> {
+        let Some(ref path) = self.tofu_storage_path else {
+            return Ok(TofuStorageStatus {
+                exists: false,
+                pins_loaded: 0,
+            });
+        };
+
+        let pins = match read_tofu_file(path) {
+            Ok(pins) => pins,
+            Err(e) => {
+                self.tofu_storage_corrupt.store(true, Ordering::SeqCst);
+                return Err(e);
             }
+        };
+
+        self.tofu_storage_corrupt.store(false, Ordering::SeqCst);
+        let status = TofuStorageStatus {
+            exists: pins.is_some(),
+            pins_loaded: pins.as_ref().map_or(0, HashMap::len),
+        };
+        self.replace_tofu_pins(pins.unwrap_or_default())?;
+        Ok(status)
+    }
+
+    /// Back up a corrupt TOFU pin file and reset to an empty pin set
+    ///
+    /// Only for the user to call once they accept re-pinning every relay
+    /// from scratch. Returns the backup path, or `None` if the file was not
+    /// corrupt (it is left untouched). The backup sits next to the original
+    /// with a `.corrupt-<unix seconds>` suffix.
+    pub fn repair_tofu_storage(&self) -> Result<Option<PathBuf>, CertPinError> {
+        let Some(ref path) = self.tofu_storage_path else {
+            return Ok(None);
+        };
+        match read_tofu_file(path) {
+            Err(CertPinError::TofuStorageCorrupt { .. }) => {}
+            Ok(_) => return Ok(None),
+            Err(e) => return Err(e),
         }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut backup = path.clone().into_os_string();
+        backup.push(format!(".corrupt-{}", now));
+        let backup = PathBuf::from(backup);
+
+        std::fs::rename(path, &backup).map_err(|e| {
+            CertPinError::StorageError(format!("failed to back up {}: {}", path.display(), e))
+        })?;
+        log::warn!(
+            "Corrupt TOFU pin file moved to {}; TOFU pins reset",
+            backup.display()
+        );
+
+        self.replace_tofu_pins(HashMap::new())?;
+        self.tofu_storage_corrupt.store(false, Ordering::SeqCst);
+        self.save_tofu_pins();
+        Ok(Some(backup))
+    }
+
+    /// Whether the TOFU pin file is corrupt and awaiting repair
+    pub fn is_tofu_storage_corrupt(&self) -> bool {
+        self.tofu_storage_corrupt.load(Ordering::SeqCst)
+    }
+
+    fn replace_tofu_pins(&self, pins: HashMap<String, String>) -> Result<(), CertPinError> {
+        let mut tofu = self
+            .tofu_pins
+            .write()
+            .map_err(|_| CertPinError::StorageError("TOFU pin lock poisoned".to_string()))?;
+        *tofu = pins;
+        Ok(())
     }
 
     /// Save TOFU pins to storage
     fn save_tofu_pins(&self) {
+        if self.tofu_storage_corrupt.load(Ordering::SeqCst) {
+            log::warn!("Not saving TOFU pins: pin file is corrupt and awaiting repair");
+            return;
+        }
         if let Some(ref path) = self.tofu_storage_path {
             if let Ok(tofu) = self.tofu_pins.read() {
                 if let Ok(json) = serde_json::to_string_pretty(&*tofu) {
@@ -250,6 +369,18 @@ impl CertPinStore {
                 }
             }
 
+            // The lost pins may have included this host
+            if self.is_tofu_storage_corrupt() {
+                return Err(CertPinError::TofuRepairRequired {
+                    host: normalized_host,
+                    path: self
+                        .tofu_storage_path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                });
+            }
+
             // First time seeing this host - store the pin
             if let Ok(mut tofu) = self.tofu_pins.write() {
                 tofu.insert(normalized_host.clone(), fingerprint.clone());
@@ -318,6 +449,27 @@ impl CertPinStore {
     }
 }
 
+/// Read a TOFU pin file; `None` if it does not exist
+fn read_tofu_file(path: &Path) -> Result<Option<HashMap<String, String>>, CertPinError> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(CertPinError::StorageError(format!(
+                "failed to read {}: {}",
+                path.display(),
+                e
+            )))
+        }
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|e| CertPinError::TofuStorageCorrupt {
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })
+}
+
 /// Result of certificate verification
 #[derive(Debug, Clone)]
 pub enum CertVerifyResult {
@@ -438,16 +590,85 @@ impl ServerCertVerifier for PinnedCertVerifier {
     }
 }
 
+/// Lowest TLS version a relay connection may negotiate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinTlsVersion {
+    /// TLS 1.2 or 1.3 (rustls default)
+    #[default]
+    Tls12,
+    /// TLS 1.3 only
+    Tls13,
+}
+
+impl MinTlsVersion {
+    fn versions(self) -> &'static [&'static rustls::SupportedProtocolVersion] {
+        match self {
+            MinTlsVersion::Tls12 => rustls::DEFAULT_VERSIONS,
+            MinTlsVersion::Tls13 => &[&rustls::version::TLS13],
+        }
+    }
+}
+
+/// Protocol version and cipher suite policy for relay connections
+///
+/// The default keeps rustls' safe defaults. Servers that cannot meet the
+/// policy fail the handshake.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsPolicy {
+    pub min_version: MinTlsVersion,
+    /// Allowed cipher suites by IANA name (e.g. `TLS13_AES_256_GCM_SHA384`);
+    /// empty allows every suite the crypto provider offers
+    #[serde(default)]
+    pub cipher_suites: Vec<String>,
+}
+
+impl TlsPolicy {
+    /// Crypto provider restricted to the allowed cipher suites
+    fn crypto_provider(&self) -> Result<rustls::crypto::CryptoProvider, CertPinError> {
+        let mut provider = rustls::crypto::CryptoProvider::get_default()
+            .map(|provider| provider.as_ref().clone())
+            .unwrap_or_else(rustls::crypto::aws_lc_rs::default_provider);
+        if self.cipher_suites.is_empty() {
+            return Ok(provider);
+        }
+
+        let suite_name = |suite: &rustls::SupportedCipherSuite| format!("{:?}", suite.suite());
+        if let Some(unknown) = self.cipher_suites.iter().find(|name| {
+            !provider
+                .cipher_suites
+                .iter()
+                .any(|s| &suite_name(s) == *name)
+        }) {
+            return Err(CertPinError::ConfigError(format!(
+                "Unsupported cipher suite: {unknown}"
+            )));
+        }
+        provider
+            .cipher_suites
+            .retain(|suite| self.cipher_suites.contains(&suite_name(suite)));
+        Ok(provider)
+    }
+}
+
 /// Create a TLS connector with certificate pinning enabled
-pub fn create_pinned_tls_config(pin_store: Arc<CertPinStore>) -> Arc<rustls::ClientConfig> {
+///
+/// Fails with [`CertPinError::ConfigError`] if `policy` names an unknown
+/// cipher suite or leaves no suite usable at its minimum version.
+pub fn create_pinned_tls_config(
+    pin_store: Arc<CertPinStore>,
+    policy: &TlsPolicy,
+) -> Result<Arc<rustls::ClientConfig>, CertPinError> {
     let verifier = Arc::new(PinnedCertVerifier::new(pin_store));
 
-    let config = rustls::ClientConfig::builder()
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(policy.crypto_provider()?))
+        .with_protocol_versions(policy.min_version.versions())
+        .map_err(|e| CertPinError::ConfigError(format!("Unusable TLS policy: {e}")))?
         .dangerous()
         .with_custom_certificate_verifier(verifier)
         .with_no_client_auth();
 
-    Arc::new(config)
+    Ok(Arc::new(config))
 }
 
 #[cfg(test)]
@@ -517,4 +738,248 @@ mod tests {
         let result = store.verify_certificate("wss://pinned.relay.io", bad_cert);
         assert!(matches!(result, Err(CertPinError::PinMismatch { .. })));
     }
+
+    fn temp_tofu_path() -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "buildit-tofu-{}.json",
+            uuid::Uuid::new_v4().simple()
+        ))
+    }
+
+    fn test_config() -> CertPinConfig {
+        CertPinConfig {
+            require_pinned_for_write: false,
+            ..CertPinConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_tofu_storage_valid_file() {
+        let path = temp_tofu_path();
+        std::fs::write(
+            &path,
+            r#"{"wss://relay.one.io": "sha256/abc", "wss://relay.two.io": "sha256/def"}"#,
+        )
+        .unwrap();
+
+        let mut store = CertPinStore::new(test_config());
+        store.set_tofu_storage_path(path.clone());
+        assert!(store.is_pinned("wss://relay.one.io"));
+
+        let status = store.validate_tofu_storage().unwrap();
+        assert_eq!(
+            status,
+            TofuStorageStatus {
+                exists: true,
+                pins_loaded: 2
+            }
+        );
+        assert_eq!(store.repair_tofu_storage().unwrap(), None);
+        let _ = std::fs::remove_file(&path);
+
+        // A missing file is an empty, valid pin set
+        let status = store.validate_tofu_storage().unwrap();
+        assert!(!status.exists);
+        assert_eq!(status.pins_loaded, 0);
+    }
+
+    #[test]
+    fn test_corrupt_tofu_storage_fails_closed() {
+        let path = temp_tofu_path();
+        let mut store = CertPinStore::new(test_config());
+        store.set_tofu_storage_path(path.clone());
+        store
+            .verify_certificate("wss://known.relay.io", b"cert")
+            .unwrap();
+
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(
+            store.validate_tofu_storage(),
+            Err(CertPinError::TofuStorageCorrupt { .. })
+        ));
+        assert!(store.is_tofu_storage_corrupt());
+
+        // Pins already loaded still hold
+        assert!(matches!(
+            store.verify_certificate("wss://known.relay.io", b"cert"),
+            Ok(CertVerifyResult::Tofu)
+        ));
+        // No host is trusted on first use, and the file is left alone
+        assert!(matches!(
+            store.verify_certificate("wss://new.relay.io", b"cert"),
+            Err(CertPinError::TofuRepairRequired { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{ not json");
+
+        // A store that starts from the corrupt file trusts nothing new either
+        let mut fresh = CertPinStore::new(test_config());
+        fresh.set_tofu_storage_path(path.clone());
+        assert!(fresh
+            .verify_certificate("wss://known.relay.io", b"other")
+            .is_err());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_repair_tofu_storage_backs_up_and_resets() {
+        let path = temp_tofu_path();
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let mut store = CertPinStore::new(test_config());
+        store.set_tofu_storage_path(path.clone());
+
+        let backup = store.repair_tofu_storage().unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "{ not json");
+        assert!(backup
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .contains(".corrupt-"));
+
+        let status = store.validate_tofu_storage().unwrap();
+        assert_eq!(
+            status,
+            TofuStorageStatus {
+                exists: true,
+                pins_loaded: 0
+            }
+        );
+
+        // Saving resumes after repair
+        store
+            .verify_certificate("wss://new.relay.io", b"cert")
+            .unwrap();
+        assert_eq!(store.validate_tofu_storage().unwrap().pins_loaded, 1);
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&backup);
+    }
+
+    #[test]
+    fn test_load_bundled_known_pins() {
+        let mut store = CertPinStore::new(CertPinConfig::default());
+        store.load_known_pins().unwrap();
+
+        // Entries with a dated last_verified must parse, not be skipped
+        assert!(store.is_pinned("wss://relay.damus.io"));
+        assert!(store.is_pinned("wss://nos.lol"));
+        // Listed but without pins yet
+        assert!(!store.is_pinned("wss://relay.buildit.network"));
+    }
+
+    /// Self-signed `localhost` certificate and PKCS#8 key for handshake tests
+    const TEST_CERT_DER: &[u8] = include_bytes!("testdata/localhost.cert.der");
+    const TEST_KEY_DER: &[u8] = include_bytes!("testdata/localhost.key.der");
+
+    /// Serve a single TLS 1.2-only handshake on a local port
+    fn spawn_tls12_only_server() -> std::net::SocketAddr {
+        use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_protocol_versions(&[&rustls::version::TLS12])
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(TEST_CERT_DER.to_vec())],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(TEST_KEY_DER.to_vec())),
+            )
+            .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut conn = rustls::ServerConnection::new(Arc::new(config)).unwrap();
+            while conn.is_handshaking() {
+                if conn.complete_io(&mut sock).is_err() {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    /// Run a client handshake, returning the TLS error that ended it
+    fn handshake_error(
+        config: Arc<rustls::ClientConfig>,
+        addr: std::net::SocketAddr,
+    ) -> Option<TlsError> {
+        let mut sock = std::net::TcpStream::connect(addr).unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut conn = rustls::ClientConnection::new(config, name).unwrap();
+        while conn.is_handshaking() {
+            if let Err(e) = conn.complete_io(&mut sock) {
+                return e
+                    .get_ref()
+                    .and_then(|inner| inner.downcast_ref::<TlsError>())
+                    .cloned();
+            }
+        }
+        None
+    }
+
+    fn tofu_store() -> Arc<CertPinStore> {
+        Arc::new(CertPinStore::new(CertPinConfig::default()))
+    }
+
+    #[test]
+    fn test_tls13_only_policy_rejects_tls12_server() {
+        let policy = TlsPolicy {
+            min_version: MinTlsVersion::Tls13,
+            cipher_suites: vec![],
+        };
+        let config = create_pinned_tls_config(tofu_store(), &policy).unwrap();
+
+        let err = handshake_error(config, spawn_tls12_only_server());
+        assert_eq!(
+            err,
+            Some(TlsError::AlertReceived(
+                rustls::AlertDescription::ProtocolVersion
+            ))
+        );
+    }
+
+    #[test]
+    fn test_default_policy_negotiates_tls12() {
+        let config = create_pinned_tls_config(tofu_store(), &TlsPolicy::default()).unwrap();
+
+        // Version negotiation succeeds; the self-signed certificate is then
+        // refused by chain validation
+        let err = handshake_error(config, spawn_tls12_only_server());
+        assert!(
+            matches!(err, Some(TlsError::InvalidCertificate(_))),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_tls_policy_cipher_suites() {
+        let policy = TlsPolicy {
+            min_version: MinTlsVersion::Tls13,
+            cipher_suites: vec!["TLS13_AES_256_GCM_SHA384".to_string()],
+        };
+        let provider = policy.crypto_provider().unwrap();
+        assert_eq!(provider.cipher_suites.len(), 1);
+        assert!(create_pinned_tls_config(tofu_store(), &policy).is_ok());
+
+        // Only TLS 1.2 suites cannot satisfy a TLS 1.3 minimum
+        let policy = TlsPolicy {
+            min_version: MinTlsVersion::Tls13,
+            cipher_suites: vec!["TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384".to_string()],
+        };
+        assert!(matches!(
+            create_pinned_tls_config(tofu_store(), &policy),
+            Err(CertPinError::ConfigError(_))
+        ));
+
+        let policy = TlsPolicy {
+            cipher_suites: vec!["TLS_NULL_WITH_NULL_NULL".to_string()],
+            ..TlsPolicy::default()
+        };
+        assert!(matches!(
+            policy.crypto_provider(),
+            Err(CertPinError::ConfigError(_))
+        ));
+    }
 }
@@ -0,0 +1,163 @@
+//! Bundled default relays for onboarding
+//!
+//! The default list is the relay set in the embedded `relay-pins.json`, so
+//! every suggested relay comes with its certificate pins. `test_relay` makes
+//! a short-lived connection to report reachability and round-trip time
+//! before the user commits to a relay.
+
+use super::cert_pinning::{CertPinError, RelayPinConfig, RELAY_PINS_JSON};
+use super::relay::{NostrRelay, RelayError};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// How long `test_relay` waits for the connection to open
+pub const RELAY_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A relay from the bundled default list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultRelay {
+    pub url: String,
+    /// Primary certificate pins (empty when not yet pinned)
+    pub pins: Vec<String>,
+    pub backup_pins: Vec<String>,
+    pub notes: String,
+}
+
+/// Outcome of a relay reachability test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayTestResult {
+    pub url: String,
+    pub reachable: bool,
+    /// Time to open the connection, when reachable
+    pub rtt_ms: Option<u64>,
+    /// Whether the certificate was checked against a pin (known or previously
+    /// trusted) rather than trusted on first use
+    pub pinned: bool,
+    pub error: Option<String>,
+}
+
+/// Parse the relay entries of a relay pins document, sorted by URL
+pub fn parse_default_relays(json: &str) -> Result<Vec<DefaultRelay>, CertPinError> {
+    let config: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| CertPinError::ConfigError(e.to_string()))?;
+    let relays = config
+        .get("relays")
+        .and_then(|r| r.as_object())
+        .ok_or_else(|| CertPinError::ConfigError("missing relays".to_string()))?;
+
+    let mut defaults = Vec::with_capacity(relays.len());
+    for (url, value) in relays {
+        let pin_config: RelayPinConfig = serde_json::from_value(value.clone())
+            .map_err(|e| CertPinError::ConfigError(format!("{url}: {e}")))?;
+        defaults.push(DefaultRelay {
+            url: url.clone(),
+            pins: pin_config.pins,
+            backup_pins: pin_config.backup_pins,
+            notes: pin_config.notes,
+        });
+    }
+    defaults.sort_by(|a, b| a.url.cmp(&b.url));
+    Ok(defaults)
+}
+
+/// The bundled default relays with their pins
+pub fn get_default_relays() -> Result<Vec<DefaultRelay>, CertPinError> {
+    parse_default_relays(RELAY_PINS_JSON)
+}
+
+/// Open a short-lived connection to `relay` and report the result
+///
+/// Never fails: connection errors and timeouts are reported as unreachable.
+pub async fn test_relay(relay: NostrRelay, timeout: Duration) -> RelayTestResult {
+    let url = relay.url().to_string();
+    let pinned = relay.is_certificate_pinned();
+
+    let start = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, relay.connect()).await {
+        Ok(result) => result,
+        Err(_) => Err(RelayError::ConnectionFailed(format!(
+            "timed out after {}ms",
+            timeout.as_millis()
+        ))),
+    };
+    let rtt_ms = start.elapsed().as_millis() as u64;
+
+    match outcome {
+        Ok(()) => {
+            let _ = relay.disconnect().await;
+            RelayTestResult {
+                url,
+                reachable: true,
+                rtt_ms: Some(rtt_ms),
+                pinned,
+                error: None,
+            }
+        }
+        Err(e) => RelayTestResult {
+            url,
+            reachable: false,
+            rtt_ms: None,
+            pinned,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::{spawn_mock_relay, test_pin_store};
+
+    #[test]
+    fn test_bundled_default_relays() {
+        let relays = get_default_relays().unwrap();
+        let damus = relays
+            .iter()
+            .find(|r| r.url == "wss://relay.damus.io")
+            .expect("damus in bundled list");
+        assert_eq!(damus.pins.len(), 1);
+        assert!(damus.pins[0].starts_with("sha256/"));
+        assert_eq!(damus.backup_pins.len(), 2);
+
+        let buildit = relays
+            .iter()
+            .find(|r| r.url == "wss://relay.buildit.network")
+            .unwrap();
+        assert!(buildit.pins.is_empty());
+
+        let urls: Vec<&str> = relays.iter().map(|r| r.url.as_str()).collect();
+        let mut sorted = urls.clone();
+        sorted.sort();
+        assert_eq!(urls, sorted);
+    }
+
+    #[test]
+    fn test_parse_default_relays_rejects_malformed() {
+        assert!(parse_default_relays("not json").is_err());
+        assert!(parse_default_relays("{}").is_err());
+        assert!(parse_default_relays(r#"{"relays": {"wss://a": {"pins": 1}}}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relay_reachable() {
+        let url = spawn_mock_relay(true, "").await;
+        let relay = NostrRelay::new(url.clone(), test_pin_store(false));
+
+        let result = test_relay(relay, RELAY_TEST_TIMEOUT).await;
+        assert_eq!(result.url, url);
+        assert!(result.reachable);
+        assert!(result.rtt_ms.is_some());
+        assert!(!result.pinned);
+        assert!(result.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_relay_unreachable() {
+        let relay = NostrRelay::new("ws://127.0.0.1:1".to_string(), test_pin_store(false));
+
+        let result = test_relay(relay, RELAY_TEST_TIMEOUT).await;
+        assert!(!result.reachable);
+        assert!(result.rtt_ms.is_none());
+        assert!(result.error.is_some());
+    }
+}
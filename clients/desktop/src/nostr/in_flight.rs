@@ -0,0 +1,166 @@
+//! Registry of publishes awaiting the relay's OK
+//!
+//! Each publish registers its event id before the EVENT frame is sent and
+//! gets back an [`InFlightPublish`] that resolves when the message handler
+//! sees the matching OK. The entry is removed when the OK arrives, when the
+//! wait times out, or when the waiting future is dropped, so an OK that
+//! arrives after its publisher gave up finds nothing to resolve and is
+//! discarded.
+
+use super::relay::RelayError;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// `(accepted, message)` as reported by the relay's OK
+type PublishAck = (bool, String);
+
+/// Publishes sent to one relay that have not been acknowledged yet
+#[derive(Debug, Default)]
+pub struct InFlightPublishes {
+    waiting: Mutex<HashMap<String, oneshot::Sender<PublishAck>>>,
+}
+
+impl InFlightPublishes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking `event_id`
+    ///
+    /// Fails if a publish of the same event is already waiting, since the
+    /// relay's single OK could only answer one of them.
+    pub fn register(self: &Arc<Self>, event_id: &str) -> Result<InFlightPublish, RelayError> {
+        let mut waiting = self.waiting.lock();
+        if waiting.contains_key(event_id) {
+            return Err(RelayError::SendFailed(format!(
+                "event {} is already being published",
+                event_id
+            )));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        waiting.insert(event_id.to_string(), tx);
+        Ok(InFlightPublish {
+            event_id: event_id.to_string(),
+            registry: Arc::clone(self),
+            rx,
+        })
+    }
+
+    /// Deliver an OK to the publish waiting on `event_id`
+    ///
+    /// Returns false when nothing is waiting: the publish already timed
+    /// out, was abandoned, or was never sent from here.
+    pub fn resolve(&self, event_id: &str, accepted: bool, message: String) -> bool {
+        match self.waiting.lock().remove(event_id) {
+            Some(tx) => tx.send((accepted, message)).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Fail every waiting publish, e.g. because the connection closed
+    pub fn fail_all(&self, reason: &str) {
+        for (_, tx) in self.waiting.lock().drain() {
+            let _ = tx.send((false, reason.to_string()));
+        }
+    }
+
+    /// Number of publishes still waiting for an OK
+    pub fn len(&self) -> usize {
+        self.waiting.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiting.lock().is_empty()
+    }
+
+    fn forget(&self, event_id: &str) {
+        self.waiting.lock().remove(event_id);
+    }
+}
+
+/// A registered publish; dropping it stops tracking the event
+#[derive(Debug)]
+pub struct InFlightPublish {
+    event_id: String,
+    registry: Arc<InFlightPublishes>,
+    rx: oneshot::Receiver<PublishAck>,
+}
+
+impl InFlightPublish {
+    /// Wait up to `timeout` for the relay's OK
+    ///
+    /// Fails with [`RelayError::PublishTimeout`] once the deadline passes;
+    /// an OK delivered in the same instant still wins.
+    pub async fn wait(mut self, timeout: Duration) -> Result<PublishAck, RelayError> {
+        match tokio::time::timeout(timeout, &mut self.rx).await {
+            Ok(Ok(ack)) => Ok(ack),
+            Ok(Err(_)) => Err(RelayError::NotConnected(self.event_id.clone())),
+            Err(_) => match self.rx.try_recv() {
+                Ok(ack) => Ok(ack),
+                Err(_) => Err(RelayError::PublishTimeout(self.event_id.clone())),
+            },
+        }
+    }
+}
+
+impl Drop for InFlightPublish {
+    fn drop(&mut self) {
+        self.registry.forget(&self.event_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_matches_event_id() {
+        let registry = Arc::new(InFlightPublishes::new());
+        let target = registry.register("target").unwrap();
+        let _other = registry.register("other").unwrap();
+        assert!(matches!(
+            registry.register("target"),
+            Err(RelayError::SendFailed(_))
+        ));
+
+        assert!(registry.resolve("target", false, "blocked: spam".to_string()));
+        assert!(!registry.resolve("unknown", true, String::new()));
+        assert_eq!(
+            target.wait(Duration::from_secs(1)).await.unwrap(),
+            (false, "blocked: spam".to_string())
+        );
+        assert_eq!(registry.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_publish_discards_late_ok() {
+        let registry = Arc::new(InFlightPublishes::new());
+        let publish = registry.register("target").unwrap();
+
+        assert!(matches!(
+            publish.wait(Duration::from_millis(20)).await,
+            Err(RelayError::PublishTimeout(id)) if id == "target"
+        ));
+        assert!(registry.is_empty());
+        assert!(!registry.resolve("target", true, String::new()));
+    }
+
+    #[tokio::test]
+    async fn test_fail_all_and_drop_release_entries() {
+        let registry = Arc::new(InFlightPublishes::new());
+        let first = registry.register("first").unwrap();
+        drop(registry.register("second").unwrap());
+        assert_eq!(registry.len(), 1);
+
+        registry.fail_all("disconnected: Manual disconnect");
+        assert!(registry.is_empty());
+        assert_eq!(
+            first.wait(Duration::from_secs(1)).await.unwrap(),
+            (false, "disconnected: Manual disconnect".to_string())
+        );
+    }
+}
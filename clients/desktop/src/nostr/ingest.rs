@@ -0,0 +1,294 @@
+//! Deduplicating, reordering ingest buffer for relay events
+//!
+//! The same event arrives once per relay holding the subscription and again
+//! when stored events are replayed after a reconnect, and each relay sends
+//! in its own order. [`EventBuffer`] drops repeated event ids and holds new
+//! events for a short window so that events arriving close together are
+//! surfaced in `created_at` order, giving the UI a stable timeline.
+//!
+//! Time is passed in explicitly so the buffer is deterministic under test.
+
+use buildit_crypto::NostrEvent;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Default time an event is held for reordering
+pub const DEFAULT_REORDER_WINDOW: Duration = Duration::from_millis(250);
+
+/// Default maximum number of events held for reordering
+pub const DEFAULT_MAX_BUFFERED: usize = 500;
+
+/// Default number of event ids remembered for deduplication
+pub const DEFAULT_DEDUP_CAPACITY: usize = 10_000;
+
+/// Ingest buffer limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IngestConfig {
+    /// How long a new event is held for reordering (zero disables reordering)
+    pub reorder_window: Duration,
+    /// Events held beyond this are surfaced immediately, oldest first
+    pub max_buffered: usize,
+    /// Event ids remembered for deduplication; the oldest are forgotten first
+    pub dedup_capacity: usize,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            reorder_window: DEFAULT_REORDER_WINDOW,
+            max_buffered: DEFAULT_MAX_BUFFERED,
+            dedup_capacity: DEFAULT_DEDUP_CAPACITY,
+        }
+    }
+}
+
+/// An event ready to surface, with the relay it first arrived from
+#[derive(Debug, Clone)]
+pub struct IngestedEvent {
+    pub relay: String,
+    pub event: NostrEvent,
+}
+
+struct PendingEvent {
+    ingested: IngestedEvent,
+    arrived: Instant,
+}
+
+/// Dedup and reorder buffer for one subscription's events
+pub struct EventBuffer {
+    config: IngestConfig,
+    seen: HashSet<String>,
+    /// Seen ids in arrival order, for evicting the oldest
+    seen_order: VecDeque<String>,
+    /// Held events ordered by (created_at, id)
+    pending: BTreeMap<(i64, String), PendingEvent>,
+}
+
+impl EventBuffer {
+    pub fn new(config: IngestConfig) -> Self {
+        Self {
+            config,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Offer an event received from `relay` at `now`
+    ///
+    /// Returns the events ready to surface, in `created_at` order. A
+    /// repeated event id is dropped.
+    pub fn push(&mut self, relay: &str, event: NostrEvent, now: Instant) -> Vec<IngestedEvent> {
+        if !self.remember(&event.id) {
+            return self.drain_due(now);
+        }
+
+        let ingested = IngestedEvent {
+            relay: relay.to_string(),
+            event,
+        };
+        if self.config.reorder_window.is_zero() {
+            return vec![ingested];
+        }
+
+        self.pending.insert(
+            (ingested.event.created_at, ingested.event.id.clone()),
+            PendingEvent {
+                ingested,
+                arrived: now,
+            },
+        );
+
+        let mut ready = self.drain_due(now);
+        while self.pending.len() > self.config.max_buffered {
+            match self.pending.pop_first() {
+                Some((_, pending)) => ready.push(pending.ingested),
+                None => break,
+            }
+        }
+        ready
+    }
+
+    /// Release events whose reorder window has elapsed at `now`
+    ///
+    /// Every held event created no later than the newest due event is
+    /// released with it, so the output stays in `created_at` order.
+    pub fn drain_due(&mut self, now: Instant) -> Vec<IngestedEvent> {
+        let window = self.config.reorder_window;
+        let cutoff = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.saturating_duration_since(pending.arrived) >= window)
+            .map(|(key, _)| key.clone())
+            .max();
+
+        let Some(cutoff) = cutoff else {
+            return Vec::new();
+        };
+
+        let later = self.pending.split_off(&cutoff);
+        let mut ready = std::mem::replace(&mut self.pending, later);
+        if let Some(pending) = self.pending.remove(&cutoff) {
+            ready.insert(cutoff, pending);
+        }
+        ready
+            .into_values()
+            .map(|pending| pending.ingested)
+            .collect()
+    }
+
+    /// Release every held event (e.g. on end of stored events)
+    pub fn flush(&mut self) -> Vec<IngestedEvent> {
+        std::mem::take(&mut self.pending)
+            .into_values()
+            .map(|pending| pending.ingested)
+            .collect()
+    }
+
+    /// Number of events currently held
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Record an event id; false if it was already seen
+    fn remember(&mut self, id: &str) -> bool {
+        if !self.seen.insert(id.to_string()) {
+            return false;
+        }
+        self.seen_order.push_back(id.to_string());
+        while self.seen_order.len() > self.config.dedup_capacity {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::test_event;
+
+    fn event(id: &str, created_at: i64) -> NostrEvent {
+        NostrEvent {
+            created_at,
+            ..test_event(id)
+        }
+    }
+
+    fn ids(events: &[IngestedEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.event.id.as_str()).collect()
+    }
+
+    fn config(window_ms: u64) -> IngestConfig {
+        IngestConfig {
+            reorder_window: Duration::from_millis(window_ms),
+            ..IngestConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_duplicate_ids_surface_once() {
+        let mut buffer = EventBuffer::new(config(0));
+        let now = Instant::now();
+
+        assert_eq!(ids(&buffer.push("wss://a", event("e1", 10), now)), ["e1"]);
+        assert!(buffer.push("wss://b", event("e1", 10), now).is_empty());
+        assert!(buffer.push("wss://a", event("e1", 10), now).is_empty());
+        assert_eq!(ids(&buffer.push("wss://b", event("e2", 5), now)), ["e2"]);
+    }
+
+    #[test]
+    fn test_duplicates_dropped_while_held() {
+        let mut buffer = EventBuffer::new(config(100));
+        let start = Instant::now();
+
+        buffer.push("wss://a", event("e1", 10), start);
+        buffer.push("wss://b", event("e1", 10), start);
+        assert_eq!(buffer.pending_len(), 1);
+
+        let ready = buffer.drain_due(start + Duration::from_millis(100));
+        assert_eq!(ids(&ready), ["e1"]);
+        assert_eq!(ready[0].relay, "wss://a");
+    }
+
+    #[test]
+    fn test_out_of_order_events_surface_sorted() {
+        let mut buffer = EventBuffer::new(config(100));
+        let start = Instant::now();
+
+        assert!(buffer.push("wss://a", event("c", 30), start).is_empty());
+        assert!(buffer
+            .push("wss://a", event("a", 10), start + Duration::from_millis(20))
+            .is_empty());
+        assert!(buffer
+            .push("wss://b", event("b", 20), start + Duration::from_millis(40))
+            .is_empty());
+
+        // Nothing is due before the first event's window elapses
+        assert!(buffer
+            .drain_due(start + Duration::from_millis(99))
+            .is_empty());
+
+        // "c" is due; everything created before it comes out with it
+        let ready = buffer.drain_due(start + Duration::from_millis(100));
+        assert_eq!(ids(&ready), ["a", "b", "c"]);
+        assert_eq!(buffer.pending_len(), 0);
+    }
+
+    #[test]
+    fn test_newer_events_stay_held_until_due() {
+        let mut buffer = EventBuffer::new(config(100));
+        let start = Instant::now();
+
+        buffer.push("wss://a", event("old", 10), start);
+        buffer.push(
+            "wss://a",
+            event("new", 50),
+            start + Duration::from_millis(60),
+        );
+
+        let ready = buffer.drain_due(start + Duration::from_millis(100));
+        assert_eq!(ids(&ready), ["old"]);
+        assert_eq!(buffer.pending_len(), 1);
+
+        let ready = buffer.drain_due(start + Duration::from_millis(160));
+        assert_eq!(ids(&ready), ["new"]);
+    }
+
+    #[test]
+    fn test_buffer_size_is_bounded() {
+        let mut buffer = EventBuffer::new(IngestConfig {
+            reorder_window: Duration::from_secs(60),
+            max_buffered: 2,
+            dedup_capacity: 100,
+        });
+        let now = Instant::now();
+
+        assert!(buffer.push("wss://a", event("b", 20), now).is_empty());
+        assert!(buffer.push("wss://a", event("c", 30), now).is_empty());
+        assert_eq!(ids(&buffer.push("wss://a", event("a", 10), now)), ["a"]);
+        assert_eq!(buffer.pending_len(), 2);
+
+        assert_eq!(ids(&buffer.flush()), ["b", "c"]);
+        assert_eq!(buffer.pending_len(), 0);
+    }
+
+    #[test]
+    fn test_dedup_capacity_forgets_oldest() {
+        let mut buffer = EventBuffer::new(IngestConfig {
+            reorder_window: Duration::ZERO,
+            max_buffered: 10,
+            dedup_capacity: 2,
+        });
+        let now = Instant::now();
+
+        for id in ["e1", "e2", "e3"] {
+            buffer.push("wss://a", event(id, 1), now);
+        }
+
+        assert!(buffer.push("wss://a", event("e3", 1), now).is_empty());
+        assert_eq!(ids(&buffer.push("wss://a", event("e1", 1), now)), ["e1"]);
+    }
+}
@@ -2,18 +2,42 @@
 //!
 //! Provides WebSocket-based relay connections with:
 //! - Connection management
-//! - Event publishing
-//! - Subscription filtering
+//! - Event publishing with per-publish OK timeouts
+//! - A durable outbox retrying publishes made while offline
+//! - Subscription filtering, resumed from per-relay watermarks
+//! - Event deduplication and ordering across relays
 //! - Automatic reconnection
 //! - Certificate pinning for MITM protection
+//! - NIP-65 relay lists for outbox-model routing
+//! - Bundled default relays for onboarding
 
 pub mod cert_pinning;
+pub mod defaults;
+pub mod in_flight;
+pub mod ingest;
+pub mod nip65;
+pub mod outbox;
+pub mod registry;
 pub mod relay;
+pub mod subscriptions;
 pub mod types;
+pub mod watermarks;
+
+#[cfg(test)]
+pub(crate) mod test_support;
 
 pub use cert_pinning::{
-    CertPinConfig, CertPinError, CertPinStore, CertVerifyResult, PinnedCertVerifier,
-    RelayPinConfig,
+    CertPinConfig, CertPinError, CertPinStore, CertVerifyResult, MinTlsVersion, PinnedCertVerifier,
+    RelayPinConfig, TlsPolicy, TofuStorageStatus,
+};
+pub use defaults::{get_default_relays, DefaultRelay, RelayTestResult};
+pub use in_flight::{InFlightPublish, InFlightPublishes};
+pub use ingest::{EventBuffer, IngestConfig, IngestedEvent};
+pub use nip65::{parse_relay_list, read_relays, RelayHint, KIND_RELAY_LIST};
+pub use registry::{normalize_relay_url, RelayInfo, RelayMap};
+pub use relay::{
+    publish_to_relays, NostrRelay, PublishResult, RelayError, RelayRole, RelayStatus,
+    PUBLISH_ACK_TIMEOUT,
 };
-pub use relay::{NostrRelay, RelayError, RelayStatus};
-pub use types::{Filter, NostrMessage, RelayEvent, Subscription};
+pub use subscriptions::{EventSink, SubscriptionManager, SubscriptionUpdate, RELAY_EVENT_CHANNEL};
+pub use types::{CountResult, Filter, NostrMessage, RelayEvent, Subscription};
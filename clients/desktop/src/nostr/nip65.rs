@@ -0,0 +1,168 @@
+//! NIP-65 relay list metadata (kind 10002) and outbox-model routing
+//!
+//! A relay list advertises where a user writes (their outbox) and where they
+//! read (their inbox). To reach someone, publish to their read relays.
+
+use super::registry::normalize_relay_url;
+use buildit_crypto::NostrEvent;
+use serde::{Deserialize, Serialize};
+
+/// Kind of a NIP-65 relay list event
+pub const KIND_RELAY_LIST: i32 = 10002;
+
+/// One relay entry from a relay list
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayHint {
+    pub url: String,
+    pub read: bool,
+    pub write: bool,
+}
+
+/// Parse the `r` tags of a kind-10002 event
+///
+/// A tag without a marker means both read and write. Tags with an invalid
+/// URL or an unknown marker are skipped, elements after the marker are
+/// ignored, and repeated URLs are merged. Returns an empty list for any other
+/// event kind.
+pub fn parse_relay_list(event: &NostrEvent) -> Vec<RelayHint> {
+    if event.kind != KIND_RELAY_LIST {
+        return Vec::new();
+    }
+
+    let mut hints: Vec<RelayHint> = Vec::new();
+    for tag in &event.tags {
+        if tag.first().map(String::as_str) != Some("r") {
+            continue;
+        }
+        let Some(url) = tag.get(1).and_then(|u| normalize_relay_url(u).ok()) else {
+            log::debug!("Skipping relay list tag with invalid URL: {:?}", tag);
+            continue;
+        };
+        let (read, write) = match tag.get(2).map(|m| m.trim()) {
+            None | Some("") => (true, true),
+            Some("read") => (true, false),
+            Some("write") => (false, true),
+            Some(other) => {
+                log::debug!("Skipping relay list tag with unknown marker: {}", other);
+                continue;
+            }
+        };
+
+        match hints.iter_mut().find(|h| h.url == url) {
+            Some(existing) => {
+                existing.read |= read;
+                existing.write |= write;
+            }
+            None => hints.push(RelayHint { url, read, write }),
+        }
+    }
+    hints
+}
+
+/// Relays to publish to when sending to the owner of `relay_list`
+pub fn read_relays(relay_list: &[RelayHint]) -> Vec<String> {
+    relay_list
+        .iter()
+        .filter(|h| h.read)
+        .map(|h| h.url.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay_list(tags: Vec<Vec<&str>>) -> NostrEvent {
+        NostrEvent {
+            id: "0".repeat(64),
+            pubkey: "a".repeat(64),
+            created_at: 1700000000,
+            kind: KIND_RELAY_LIST,
+            tags: tags
+                .into_iter()
+                .map(|t| t.into_iter().map(String::from).collect())
+                .collect(),
+            content: String::new(),
+            sig: "b".repeat(128),
+        }
+    }
+
+    #[test]
+    fn test_parse_well_formed_relay_list() {
+        let event = relay_list(vec![
+            vec!["r", "wss://alicerelay.example.com"],
+            vec!["r", "wss://brando-relay.com"],
+            vec!["r", "wss://expensive-relay.example2.com", "write"],
+            vec!["r", "wss://nostr-relay.example.com", "read"],
+        ]);
+
+        let hints = parse_relay_list(&event);
+        assert_eq!(hints.len(), 4);
+        assert_eq!(
+            hints[2],
+            RelayHint {
+                url: "wss://expensive-relay.example2.com".to_string(),
+                read: false,
+                write: true,
+            }
+        );
+        assert_eq!(
+            read_relays(&hints),
+            vec![
+                "wss://alicerelay.example.com",
+                "wss://brando-relay.com",
+                "wss://nostr-relay.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_tags() {
+        let event = relay_list(vec![
+            vec!["r"],
+            vec!["r", "not a url"],
+            vec!["r", "https://web.example.com"],
+            vec!["r", "wss://bogus-marker.example.com", "sometimes"],
+            vec!["r", "wss://extra.example.com", "read", "unexpected"],
+            vec!["r", "wss://empty-marker.example.com", ""],
+            vec!["p", "wss://not-an-r-tag.example.com"],
+            vec![],
+        ]);
+
+        let hints = parse_relay_list(&event);
+        assert_eq!(
+            hints,
+            vec![
+                RelayHint {
+                    url: "wss://extra.example.com".to_string(),
+                    read: true,
+                    write: false,
+                },
+                RelayHint {
+                    url: "wss://empty-marker.example.com".to_string(),
+                    read: true,
+                    write: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_urls_are_merged() {
+        let event = relay_list(vec![
+            vec!["r", "wss://relay.example.com", "read"],
+            vec!["r", "wss://relay.example.com/", "write"],
+        ]);
+
+        let hints = parse_relay_list(&event);
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].read && hints[0].write);
+    }
+
+    #[test]
+    fn test_other_kinds_ignored() {
+        let mut event = relay_list(vec![vec!["r", "wss://relay.example.com"]]);
+        event.kind = 3;
+        assert!(parse_relay_list(&event).is_empty());
+    }
+}
@@ -0,0 +1,463 @@
+//! Durable outbox for relay publishes
+//!
+//! `queue_event` stores a signed event in the `outbox` table instead of
+//! publishing it right away, so it survives being offline or restarting.
+//! The worker started by [`spawn_outbox_worker`] publishes due entries to
+//! their relays and removes each one as soon as any relay accepts it with
+//! OK. Rejections and unreachable relays schedule another attempt with
+//! exponential backoff; after [`OUTBOX_MAX_ATTEMPTS`] the entry is marked
+//! failed and kept, [`OUTBOX_FAILED_EVENT`] is emitted, and the user can
+//! retry or discard it.
+//!
+//! The outbox holds at most [`OUTBOX_MAX_ENTRIES`] entries; queueing beyond
+//! that fails instead of growing without bound.
+
+use std::time::Duration;
+
+use buildit_crypto::NostrEvent;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::registry;
+use super::relay::{publish_to_relays, PublishResult, PUBLISH_ACK_TIMEOUT};
+use crate::db::Database;
+use crate::AppState;
+
+/// Most entries the outbox holds, pending and failed together
+pub const OUTBOX_MAX_ENTRIES: u32 = 1000;
+
+/// Publish attempts before an entry is marked failed
+pub const OUTBOX_MAX_ATTEMPTS: u32 = 8;
+
+/// Delay before the second attempt; doubles with each further attempt
+pub const OUTBOX_BASE_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Longest delay between two attempts
+pub const OUTBOX_MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// How often the worker looks for due entries
+pub const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most entries published per worker pass
+const OUTBOX_BATCH_SIZE: u32 = 20;
+
+/// Event emitted with an [`OutboxFailure`] when an entry runs out of attempts
+pub const OUTBOX_FAILED_EVENT: &str = "outbox-failed";
+
+/// Delivery state of an outbox entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    /// Waiting for its next attempt
+    Pending,
+    /// Out of attempts; kept until retried or removed
+    Failed,
+}
+
+impl OutboxStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            OutboxStatus::Pending => "pending",
+            OutboxStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "failed" => OutboxStatus::Failed,
+            _ => OutboxStatus::Pending,
+        }
+    }
+}
+
+/// An event waiting in the outbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub event: NostrEvent,
+    /// Relays to publish to; empty means every writable relay
+    pub relay_urls: Vec<String>,
+    pub status: OutboxStatus,
+    /// Publish attempts made so far
+    pub attempts: u32,
+    /// Unix seconds of the next attempt
+    pub next_attempt_at: i64,
+    /// Why the last attempt failed
+    pub last_error: Option<String>,
+    pub created_at: i64,
+}
+
+/// Result of recording one publish attempt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delivery {
+    /// A relay accepted the event; the entry was removed
+    Sent,
+    /// Rejected everywhere; another attempt is scheduled
+    Retrying { next_attempt_at: i64 },
+    /// Rejected on the last allowed attempt; the entry is now failed
+    Failed { error: String },
+}
+
+/// Payload of [`OUTBOX_FAILED_EVENT`]
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxFailure {
+    pub event_id: String,
+    pub error: String,
+}
+
+/// Delay before the attempt following attempt number `attempts`
+pub fn backoff_delay(attempts: u32) -> Duration {
+    let doublings = attempts.saturating_sub(1).min(16);
+    OUTBOX_BASE_BACKOFF
+        .saturating_mul(1 << doublings)
+        .min(OUTBOX_MAX_BACKOFF)
+}
+
+/// Add a signed event to the outbox, due immediately
+///
+/// Queueing an event that is already in the outbox changes nothing.
+pub fn enqueue(
+    conn: &Connection,
+    event: &NostrEvent,
+    relay_urls: &[String],
+    now: i64,
+) -> Result<(), String> {
+    let count: u32 = conn
+        .query_row("SELECT COUNT(*) FROM outbox", [], |row| row.get(0))
+        .map_err(|e| format!("Query failed: {e}"))?;
+    if count >= OUTBOX_MAX_ENTRIES {
+        return Err(format!(
+            "Outbox is full ({OUTBOX_MAX_ENTRIES} events waiting)"
+        ));
+    }
+
+    let event_json =
+        serde_json::to_string(event).map_err(|e| format!("Failed to encode event: {e}"))?;
+    let relay_urls =
+        serde_json::to_string(relay_urls).map_err(|e| format!("Failed to encode relays: {e}"))?;
+    conn.execute(
+        "INSERT INTO outbox (event_id, event_json, relay_urls, next_attempt_at, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT (event_id) DO NOTHING",
+        rusqlite::params![event.id, event_json, relay_urls, now],
+    )
+    .map_err(|e| format!("Insert failed: {e}"))?;
+    Ok(())
+}
+
+/// Pending entries due at `now`, oldest first
+pub fn due_entries(conn: &Connection, now: i64, limit: u32) -> Result<Vec<OutboxEntry>, String> {
+    query_entries(
+        conn,
+        "WHERE status = 'pending' AND next_attempt_at <= ?1 ORDER BY next_attempt_at LIMIT ?2",
+        rusqlite::params![now, limit],
+    )
+}
+
+/// Every entry, pending and failed, in the order they were queued
+pub fn list_entries(conn: &Connection) -> Result<Vec<OutboxEntry>, String> {
+    query_entries(conn, "ORDER BY created_at, event_id", [])
+}
+
+fn query_entries<P: rusqlite::Params>(
+    conn: &Connection,
+    clause: &str,
+    params: P,
+) -> Result<Vec<OutboxEntry>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT event_json, relay_urls, status, attempts, next_attempt_at, last_error,
+                    created_at
+             FROM outbox {clause}"
+        ))
+        .map_err(|e| format!("Query failed: {e}"))?;
+    let rows = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u32>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, i64>(6)?,
+            ))
+        })
+        .map_err(|e| format!("Query failed: {e}"))?;
+
+    let mut entries = Vec::new();
+    for row in rows {
+        let (event_json, relay_urls, status, attempts, next_attempt_at, last_error, created_at) =
+            row.map_err(|e| format!("Row fetch failed: {e}"))?;
+        entries.push(OutboxEntry {
+            event: serde_json::from_str(&event_json)
+                .map_err(|e| format!("Corrupt outbox event: {e}"))?,
+            relay_urls: serde_json::from_str(&relay_urls)
+                .map_err(|e| format!("Corrupt outbox relays: {e}"))?,
+            status: OutboxStatus::parse(&status),
+            attempts,
+            next_attempt_at,
+            last_error,
+            created_at,
+        });
+    }
+    Ok(entries)
+}
+
+/// Record the per-relay results of publishing `event_id`
+///
+/// Any accepting relay removes the entry. Otherwise the attempt is counted
+/// and the next one scheduled, or the entry is marked failed once
+/// [`OUTBOX_MAX_ATTEMPTS`] is reached.
+pub fn record_attempt(
+    conn: &Connection,
+    event_id: &str,
+    results: &[PublishResult],
+    now: i64,
+) -> Result<Delivery, String> {
+    if results.iter().any(|result| result.accepted) {
+        remove(conn, event_id)?;
+        return Ok(Delivery::Sent);
+    }
+
+    let attempts: u32 = conn
+        .query_row(
+            "SELECT attempts FROM outbox WHERE event_id = ?1",
+            [event_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query failed: {e}"))?
+        .ok_or_else(|| format!("Outbox entry not found: {event_id}"))?;
+    let attempts = attempts + 1;
+
+    let error = if results.is_empty() {
+        "no writable relays".to_string()
+    } else {
+        results
+            .iter()
+            .map(|result| format!("{}: {}", result.relay, result.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    let (status, next_attempt_at) = if attempts >= OUTBOX_MAX_ATTEMPTS {
+        (OutboxStatus::Failed, now)
+    } else {
+        let delay = backoff_delay(attempts).as_secs() as i64;
+        (OutboxStatus::Pending, now + delay)
+    };
+
+    conn.execute(
+        "UPDATE outbox SET status = ?2, attempts = ?3, next_attempt_at = ?4, last_error = ?5
+         WHERE event_id = ?1",
+        rusqlite::params![event_id, status.as_str(), attempts, next_attempt_at, error],
+    )
+    .map_err(|e| format!("Update failed: {e}"))?;
+
+    Ok(match status {
+        OutboxStatus::Failed => Delivery::Failed { error },
+        OutboxStatus::Pending => Delivery::Retrying { next_attempt_at },
+    })
+}
+
+/// Give a failed entry a fresh set of attempts, due immediately
+///
+/// Returns false if there is no failed entry for `event_id`.
+pub fn retry(conn: &Connection, event_id: &str, now: i64) -> Result<bool, String> {
+    conn.execute(
+        "UPDATE outbox SET status = 'pending', attempts = 0, next_attempt_at = ?2
+         WHERE event_id = ?1 AND status = 'failed'",
+        rusqlite::params![event_id, now],
+    )
+    .map(|updated| updated > 0)
+    .map_err(|e| format!("Update failed: {e}"))
+}
+
+/// Remove an entry, returning whether it existed
+pub fn remove(conn: &Connection, event_id: &str) -> Result<bool, String> {
+    conn.execute("DELETE FROM outbox WHERE event_id = ?1", [event_id])
+        .map(|deleted| deleted > 0)
+        .map_err(|e| format!("Delete failed: {e}"))
+}
+
+/// Current time in unix seconds
+pub fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Start the background task that publishes due outbox entries
+///
+/// Entries wait while the database is locked.
+pub fn spawn_outbox_worker(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(OUTBOX_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            deliver_due(&app).await;
+        }
+    });
+}
+
+/// Publish every due entry once and record the outcomes
+async fn deliver_due(app: &AppHandle) {
+    let db = app.state::<Database>();
+    if !db.is_open() {
+        return;
+    }
+    let due = match db
+        .with_background_connection(|conn| due_entries(conn, now_secs(), OUTBOX_BATCH_SIZE))
+    {
+        Ok(due) => due,
+        Err(e) => {
+            log::warn!("Failed to read outbox: {}", e);
+            return;
+        }
+    };
+
+    for entry in due {
+        let urls = (!entry.relay_urls.is_empty()).then_some(entry.relay_urls.as_slice());
+        let (relays, unknown) =
+            registry::writable_relays(&app.state::<AppState>().nostr_relays, urls);
+        let mut results = publish_to_relays(&relays, &entry.event, PUBLISH_ACK_TIMEOUT).await;
+        results.extend(
+            unknown
+                .iter()
+                .map(|url| PublishResult::rejected(url, "relay not configured")),
+        );
+
+        let event_id = entry.event.id;
+        match db.with_background_connection(|conn| {
+            record_attempt(conn, &event_id, &results, now_secs())
+        }) {
+            Ok(Delivery::Sent) => log::info!("Outbox event {} published", event_id),
+            Ok(Delivery::Retrying { .. }) => {
+                log::debug!("Outbox event {} not accepted, will retry", event_id)
+            }
+            Ok(Delivery::Failed { error }) => {
+                log::warn!("Outbox event {} failed to send: {}", event_id, error);
+                let _ = app.emit(OUTBOX_FAILED_EVENT, OutboxFailure { event_id, error });
+            }
+            Err(e) => log::warn!("Failed to update outbox entry {}: {}", event_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::test_event;
+
+    fn migrated_conn() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::schema::run_migrations(&mut conn).unwrap();
+        conn
+    }
+
+    fn rejected_everywhere() -> Vec<PublishResult> {
+        vec![
+            PublishResult::rejected("wss://a", "not connected"),
+            PublishResult::rejected("wss://b", "blocked: rate limited"),
+        ]
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        assert_eq!(backoff_delay(1), OUTBOX_BASE_BACKOFF);
+        assert_eq!(backoff_delay(2), OUTBOX_BASE_BACKOFF * 2);
+        assert_eq!(backoff_delay(3), OUTBOX_BASE_BACKOFF * 4);
+        assert_eq!(backoff_delay(40), OUTBOX_MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_enqueue_retry_then_success_removes_entry() {
+        let conn = migrated_conn();
+        let event = test_event(&"1".repeat(64));
+        enqueue(&conn, &event, &["wss://a".to_string()], 1_000).unwrap();
+        // Queueing the same event again is a no-op
+        enqueue(&conn, &event, &[], 1_000).unwrap();
+
+        let due = due_entries(&conn, 1_000, 10).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].event.id, event.id);
+        assert_eq!(due[0].relay_urls, vec!["wss://a".to_string()]);
+
+        let delivery = record_attempt(&conn, &event.id, &rejected_everywhere(), 1_000).unwrap();
+        let retry_at = 1_000 + OUTBOX_BASE_BACKOFF.as_secs() as i64;
+        assert_eq!(
+            delivery,
+            Delivery::Retrying {
+                next_attempt_at: retry_at
+            }
+        );
+        assert!(due_entries(&conn, retry_at - 1, 10).unwrap().is_empty());
+
+        let due = due_entries(&conn, retry_at, 10).unwrap();
+        assert_eq!(due[0].attempts, 1);
+        assert!(due[0]
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("rate limited"));
+
+        let mut results = rejected_everywhere();
+        results.push(PublishResult {
+            relay: "wss://c".to_string(),
+            accepted: true,
+            message: String::new(),
+        });
+        assert_eq!(
+            record_attempt(&conn, &event.id, &results, retry_at).unwrap(),
+            Delivery::Sent
+        );
+        assert!(list_entries(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_exhausted_retries_leave_failed_entry() {
+        let conn = migrated_conn();
+        let event = test_event(&"2".repeat(64));
+        enqueue(&conn, &event, &[], 0).unwrap();
+
+        let mut now = 0;
+        for attempt in 1..OUTBOX_MAX_ATTEMPTS {
+            match record_attempt(&conn, &event.id, &[], now).unwrap() {
+                Delivery::Retrying { next_attempt_at } => now = next_attempt_at,
+                other => panic!("attempt {attempt}: unexpected {other:?}"),
+            }
+        }
+        assert_eq!(
+            record_attempt(&conn, &event.id, &[], now).unwrap(),
+            Delivery::Failed {
+                error: "no writable relays".to_string()
+            }
+        );
+
+        // Failed entries are never due but stay listed
+        assert!(due_entries(&conn, i64::MAX, 10).unwrap().is_empty());
+        let entries = list_entries(&conn).unwrap();
+        assert_eq!(entries[0].status, OutboxStatus::Failed);
+        assert_eq!(entries[0].attempts, OUTBOX_MAX_ATTEMPTS);
+
+        // A manual retry starts over
+        assert!(retry(&conn, &event.id, now).unwrap());
+        assert!(!retry(&conn, &event.id, now).unwrap());
+        let due = due_entries(&conn, now, 10).unwrap();
+        assert_eq!((due[0].status, due[0].attempts), (OutboxStatus::Pending, 0));
+    }
+
+    #[test]
+    fn test_outbox_is_bounded() {
+        let conn = migrated_conn();
+        for i in 0..OUTBOX_MAX_ENTRIES {
+            enqueue(&conn, &test_event(&format!("{i:064}")), &[], 0).unwrap();
+        }
+        let err = enqueue(&conn, &test_event(&"f".repeat(64)), &[], 0).unwrap_err();
+        assert!(err.starts_with("Outbox is full"), "{err}");
+
+        assert!(remove(&conn, &format!("{:064}", 0)).unwrap());
+        enqueue(&conn, &test_event(&"f".repeat(64)), &[], 0).unwrap();
+    }
+}
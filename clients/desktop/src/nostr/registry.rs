@@ -0,0 +1,511 @@
+//! Runtime management of the configured relay set
+//!
+//! The relay map lives in `AppState` behind a `parking_lot` lock, which must
+//! never be held across an `.await`. Every operation here takes the lock only
+//! long enough to read or mutate the map, then talks to the relay outside it.
+
+use super::relay::{NostrRelay, RelayError, RelayRole, RelayStatus};
+use futures::StreamExt;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Relays keyed by normalized URL
+pub type RelayMap = HashMap<String, Arc<NostrRelay>>;
+
+/// Channel on which `connect_relays` reports each relay as it settles
+pub const RELAY_CONNECT_CHANNEL: &str = "nostr-relay-connect";
+
+/// Connections attempted at once by `connect_all_parallel` unless overridden
+pub const DEFAULT_CONNECT_CONCURRENCY: usize = 4;
+
+/// Overall deadline for connecting the relay set at startup
+pub const STARTUP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Relay entry reported to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayInfo {
+    pub url: String,
+    pub status: RelayStatus,
+    pub pinned: bool,
+    pub role: RelayRole,
+}
+
+impl RelayInfo {
+    /// Snapshot a relay's current state
+    pub async fn of(relay: &NostrRelay) -> Self {
+        Self {
+            url: relay.url().to_string(),
+            status: relay.status().await,
+            pinned: relay.is_certificate_pinned(),
+            role: relay.role(),
+        }
+    }
+}
+
+/// Validate a relay URL and bring it into the form used as the map key
+///
+/// Only `ws://` and `wss://` are accepted. A bare trailing slash is dropped so
+/// that `wss://relay.example` and `wss://relay.example/` refer to one relay.
+pub fn normalize_relay_url(url: &str) -> Result<String, RelayError> {
+    let parsed = url::Url::parse(url.trim()).map_err(|e| RelayError::InvalidUrl(e.to_string()))?;
+
+    if parsed.scheme() != "ws" && parsed.scheme() != "wss" {
+        return Err(RelayError::InvalidUrl(format!(
+            "unsupported scheme '{}'",
+            parsed.scheme()
+        )));
+    }
+    if parsed.host_str().is_none() {
+        return Err(RelayError::InvalidUrl("missing host".to_string()));
+    }
+
+    let normalized = parsed.to_string();
+    Ok(match normalized.strip_suffix('/') {
+        Some(stripped) if parsed.path() == "/" && parsed.query().is_none() => stripped.to_string(),
+        _ => normalized,
+    })
+}
+
+/// Look up a relay by URL, normalizing first
+pub fn get_relay(relays: &RwLock<RelayMap>, url: &str) -> Option<Arc<NostrRelay>> {
+    let url = normalize_relay_url(url).ok()?;
+    relays.read().get(&url).cloned()
+}
+
+/// Relays to publish to, plus the requested URLs that are not configured
+///
+/// With `urls` the named relays are used whatever their role; without,
+/// every relay that can write. Read-only relays never see our writes unless
+/// named explicitly.
+pub fn writable_relays(
+    relays: &RwLock<RelayMap>,
+    urls: Option<&[String]>,
+) -> (Vec<Arc<NostrRelay>>, Vec<String>) {
+    let map = relays.read();
+    match urls {
+        Some(urls) => {
+            let mut found = Vec::new();
+            let mut unknown = Vec::new();
+            for url in urls {
+                let key = normalize_relay_url(url).unwrap_or_default();
+                match map.get(&key) {
+                    Some(relay) => found.push(relay.clone()),
+                    None => unknown.push(url.clone()),
+                }
+            }
+            (found, unknown)
+        }
+        None => (
+            map.values()
+                .filter(|relay| relay.role().can_write())
+                .cloned()
+                .collect(),
+            Vec::new(),
+        ),
+    }
+}
+
+/// Create, connect and register a relay with the given role
+///
+/// `make_relay` builds the client for the normalized URL; production code
+/// builds it on the shared `AppState::pin_store`. The entry is reserved before
+/// connecting so concurrent adds of the same URL fail fast, and is removed
+/// again if the connection fails.
+pub async fn add_relay<F>(
+    relays: &RwLock<RelayMap>,
+    url: &str,
+    role: RelayRole,
+    make_relay: F,
+) -> Result<Arc<NostrRelay>, RelayError>
+where
+    F: FnOnce(String) -> Result<NostrRelay, RelayError>,
+{
+    let url = normalize_relay_url(url)?;
+
+    let relay = {
+        let mut map = relays.write();
+        if map.contains_key(&url) {
+            return Err(RelayError::DuplicateRelay(url));
+        }
+        let relay = Arc::new(make_relay(url.clone())?);
+        relay.set_role(role);
+        map.insert(url.clone(), Arc::clone(&relay));
+        relay
+    };
+
+    if let Err(e) = relay.connect().await {
+        relays.write().remove(&url);
+        return Err(e);
+    }
+
+    log::info!("Added relay {}", url);
+    Ok(relay)
+}
+
+/// Outcome of one connection attempted by [`connect_all_parallel`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayConnectResult {
+    pub url: String,
+    pub connected: bool,
+    /// Time from starting this connection until it settled
+    pub elapsed_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Add and connect several relays at once
+///
+/// At most `max_concurrent` connections are in progress at a time, and any
+/// still pending at `deadline` are abandoned and unregistered. A failing
+/// relay never holds up the others. `on_result` sees each result as soon as
+/// it settles, so callers can report progress and carry on once enough
+/// relays are up; the returned results follow the order of `urls`.
+pub async fn connect_all_parallel<F, P>(
+    relays: &RwLock<RelayMap>,
+    urls: &[String],
+    role: RelayRole,
+    make_relay: F,
+    max_concurrent: usize,
+    deadline: tokio::time::Instant,
+    mut on_result: P,
+) -> Vec<RelayConnectResult>
+where
+    F: Fn(String) -> Result<NostrRelay, RelayError>,
+    P: FnMut(&RelayConnectResult),
+{
+    let make_relay = &make_relay;
+    let mut attempts = futures::stream::iter(urls.iter().enumerate())
+        .map(|(i, url)| async move {
+            let start = tokio::time::Instant::now();
+            let outcome =
+                tokio::time::timeout_at(deadline, add_relay(relays, url, role, make_relay)).await;
+            let error = match outcome {
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => {
+                    // The dropped attempt left its reservation in the map
+                    if let Ok(key) = normalize_relay_url(url) {
+                        relays.write().remove(&key);
+                    }
+                    Some("timed out before the connect deadline".to_string())
+                }
+            };
+            let result = RelayConnectResult {
+                url: url.clone(),
+                connected: error.is_none(),
+                elapsed_ms: start.elapsed().as_millis() as u64,
+                error,
+            };
+            (i, result)
+        })
+        .buffer_unordered(max_concurrent.max(1));
+
+    let mut results: Vec<Option<RelayConnectResult>> = vec![None; urls.len()];
+    while let Some((i, result)) = attempts.next().await {
+        on_result(&result);
+        results[i] = Some(result);
+    }
+
+    let results: Vec<RelayConnectResult> = results.into_iter().flatten().collect();
+    log::info!(
+        "Connected {}/{} relays",
+        results.iter().filter(|r| r.connected).count(),
+        results.len()
+    );
+    results
+}
+
+/// Disconnect and unregister a relay
+pub async fn remove_relay(relays: &RwLock<RelayMap>, url: &str) -> Result<(), RelayError> {
+    let url = normalize_relay_url(url)?;
+
+    let relay = relays
+        .write()
+        .remove(&url)
+        .ok_or_else(|| RelayError::RelayNotFound(url.clone()))?;
+    relay.disconnect().await?;
+
+    log::info!("Removed relay {}", url);
+    Ok(())
+}
+
+/// Change the role of a configured relay
+pub async fn set_relay_role(
+    relays: &RwLock<RelayMap>,
+    url: &str,
+    role: RelayRole,
+) -> Result<RelayInfo, RelayError> {
+    let url = normalize_relay_url(url)?;
+    let relay = relays
+        .read()
+        .get(&url)
+        .cloned()
+        .ok_or_else(|| RelayError::RelayNotFound(url.clone()))?;
+
+    relay.set_role(role);
+    log::info!("Relay {} role set to {:?}", url, role);
+    Ok(RelayInfo::of(&relay).await)
+}
+
+/// Disconnect and unregister every relay in parallel, giving up at `deadline`
+///
+/// Used on shutdown: the map is emptied up front, so a relay whose socket
+/// does not close in time is simply abandoned. Returns the URLs that did not
+/// disconnect cleanly.
+pub async fn close_all(relays: &RwLock<RelayMap>, deadline: tokio::time::Instant) -> Vec<String> {
+    let drained: Vec<(String, Arc<NostrRelay>)> = relays.write().drain().collect();
+
+    let closes = drained.iter().map(|(url, relay)| async move {
+        let clean = match tokio::time::timeout_at(deadline, relay.disconnect()).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                log::warn!("Failed to close relay {}: {}", url, e);
+                false
+            }
+            Err(_) => {
+                log::warn!("Timed out closing relay {}", url);
+                false
+            }
+        };
+        (url.clone(), clean)
+    });
+    let results = futures::future::join_all(closes).await;
+
+    if !results.is_empty() {
+        log::info!("Closed {} relay(s)", results.len());
+    }
+    results
+        .into_iter()
+        .filter_map(|(url, clean)| (!clean).then_some(url))
+        .collect()
+}
+
+/// List configured relays with their current status, sorted by URL
+pub async fn list_relays(relays: &RwLock<RelayMap>) -> Vec<RelayInfo> {
+    let snapshot: Vec<Arc<NostrRelay>> = relays.read().values().cloned().collect();
+
+    let mut infos = Vec::with_capacity(snapshot.len());
+    for relay in snapshot {
+        infos.push(RelayInfo::of(&relay).await);
+    }
+    infos.sort_by(|a, b| a.url.cmp(&b.url));
+    infos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::{
+        spawn_mock_relay, spawn_slow_handshake_relay, test_pin_store, HandshakeGauge,
+    };
+
+    fn mock_relay(url: String) -> Result<NostrRelay, RelayError> {
+        Ok(NostrRelay::new(url, test_pin_store(false)))
+    }
+
+    #[test]
+    fn test_normalize_relay_url() {
+        assert_eq!(
+            normalize_relay_url("wss://relay.example/").unwrap(),
+            "wss://relay.example"
+        );
+        assert_eq!(
+            normalize_relay_url(" wss://relay.example ").unwrap(),
+            "wss://relay.example"
+        );
+        assert_eq!(
+            normalize_relay_url("wss://relay.example/nostr").unwrap(),
+            "wss://relay.example/nostr"
+        );
+        assert!(matches!(
+            normalize_relay_url("https://relay.example"),
+            Err(RelayError::InvalidUrl(_))
+        ));
+        assert!(normalize_relay_url("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_add_list_remove() {
+        let relays = RwLock::new(RelayMap::new());
+        let url = spawn_mock_relay(true, "").await;
+
+        add_relay(&relays, &url, RelayRole::ReadWrite, mock_relay)
+            .await
+            .unwrap();
+
+        let listed = list_relays(&relays).await;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].url, url);
+        assert_eq!(listed[0].status, RelayStatus::Connected);
+        assert!(get_relay(&relays, &format!("{}/", url)).is_some());
+
+        remove_relay(&relays, &url).await.unwrap();
+        assert!(list_relays(&relays).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_add_rejected() {
+        let relays = RwLock::new(RelayMap::new());
+        let url = spawn_mock_relay(true, "").await;
+
+        add_relay(&relays, &url, RelayRole::ReadWrite, mock_relay)
+            .await
+            .unwrap();
+        let result = add_relay(
+            &relays,
+            &format!("{}/", url),
+            RelayRole::ReadWrite,
+            mock_relay,
+        )
+        .await;
+
+        assert!(matches!(result, Err(RelayError::DuplicateRelay(u)) if u == url));
+        assert_eq!(relays.read().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_role_listed_and_changed() {
+        let relays = RwLock::new(RelayMap::new());
+        let url = spawn_mock_relay(true, "").await;
+
+        add_relay(&relays, &url, RelayRole::ReadOnly, mock_relay)
+            .await
+            .unwrap();
+        assert_eq!(list_relays(&relays).await[0].role, RelayRole::ReadOnly);
+
+        let info = set_relay_role(&relays, &url, RelayRole::WriteOnly)
+            .await
+            .unwrap();
+        assert_eq!(info.role, RelayRole::WriteOnly);
+        assert_eq!(
+            get_relay(&relays, &url).unwrap().role(),
+            RelayRole::WriteOnly
+        );
+
+        assert!(matches!(
+            set_relay_role(&relays, "wss://unknown.example", RelayRole::ReadOnly).await,
+            Err(RelayError::RelayNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_remove_unknown_relay() {
+        let relays = RwLock::new(RelayMap::new());
+        let result = remove_relay(&relays, "wss://unknown.example").await;
+        assert!(matches!(result, Err(RelayError::RelayNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_failed_connect_is_not_registered() {
+        let relays = RwLock::new(RelayMap::new());
+        // Nothing listens on port 1
+        let result = add_relay(
+            &relays,
+            "ws://127.0.0.1:1",
+            RelayRole::ReadWrite,
+            mock_relay,
+        )
+        .await;
+
+        assert!(matches!(result, Err(RelayError::ConnectionFailed(_))));
+        assert!(relays.read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_parallel_respects_concurrency() {
+        let relays = RwLock::new(RelayMap::new());
+        let gauge = Arc::new(HandshakeGauge::default());
+        let delay = Duration::from_millis(150);
+        let mut urls = Vec::new();
+        for _ in 0..6 {
+            urls.push(spawn_slow_handshake_relay(delay, Arc::clone(&gauge)).await);
+        }
+
+        let start = tokio::time::Instant::now();
+        let deadline = start + Duration::from_secs(5);
+        let mut progress = Vec::new();
+        let results = connect_all_parallel(
+            &relays,
+            &urls,
+            RelayRole::ReadWrite,
+            mock_relay,
+            2,
+            deadline,
+            |result| progress.push(result.url.clone()),
+        )
+        .await;
+
+        assert!(results.iter().all(|r| r.connected));
+        assert_eq!(
+            results.iter().map(|r| &r.url).collect::<Vec<_>>(),
+            urls.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(progress.len(), 6);
+        assert_eq!(relays.read().len(), 6);
+
+        // Two at a time: three rounds, well under six sequential handshakes
+        assert_eq!(gauge.peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert!(start.elapsed() < delay * 5, "{:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_connect_all_parallel_reports_partial_failures() {
+        let relays = RwLock::new(RelayMap::new());
+        let good = spawn_mock_relay(true, "").await;
+        let stalled = spawn_slow_handshake_relay(Duration::from_secs(30), Arc::default()).await;
+        let urls = vec![
+            good.clone(),
+            // Nothing listens on port 1
+            "ws://127.0.0.1:1".to_string(),
+            stalled.clone(),
+            "https://not-a-relay.example".to_string(),
+        ];
+
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+        let results = connect_all_parallel(
+            &relays,
+            &urls,
+            RelayRole::ReadWrite,
+            mock_relay,
+            DEFAULT_CONNECT_CONCURRENCY,
+            deadline,
+            |_| {},
+        )
+        .await;
+
+        let connected: Vec<bool> = results.iter().map(|r| r.connected).collect();
+        assert_eq!(connected, [true, false, false, false]);
+        assert!(results[2].error.as_deref().unwrap().contains("timed out"));
+        assert!(results[1..].iter().all(|r| r.error.is_some()));
+
+        // Only the relay that connected stays registered
+        let registered: Vec<String> = relays.read().keys().cloned().collect();
+        assert_eq!(registered, [good]);
+    }
+
+    #[tokio::test]
+    async fn test_close_all_disconnects_and_unregisters() {
+        let relays = RwLock::new(RelayMap::new());
+        let mut added = Vec::new();
+        for _ in 0..2 {
+            let url = spawn_mock_relay(true, "").await;
+            added.push(
+                add_relay(&relays, &url, RelayRole::ReadWrite, mock_relay)
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(5);
+        assert!(close_all(&relays, deadline).await.is_empty());
+        assert!(relays.read().is_empty());
+        for relay in added {
+            assert_eq!(relay.status().await, RelayStatus::Disconnected);
+        }
+
+        // Nothing left to close
+        assert!(close_all(&relays, deadline).await.is_empty());
+    }
+}
@@ -3,20 +3,21 @@
 //! All relay connections use certificate pinning to prevent MITM attacks.
 //! Supports both pre-configured pins and Trust-on-First-Use (TOFU).
 
-use super::cert_pinning::{create_pinned_tls_config, CertPinStore};
+use super::cert_pinning::{create_pinned_tls_config, CertPinStore, TlsPolicy};
+use super::in_flight::InFlightPublishes;
 use super::types::{Filter, NostrMessage, RelayEvent, Subscription};
 use buildit_crypto::NostrEvent;
+use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
-use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::net::TcpStream;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::{broadcast, RwLock};
 use tokio_tungstenite::{
-    connect_async_tls_with_config, tungstenite::Message, Connector, MaybeTlsStream,
-    WebSocketStream,
+    connect_async_tls_with_config, tungstenite::Message, Connector, MaybeTlsStream, WebSocketStream,
 };
 
 /// Relay operation errors
@@ -51,6 +52,56 @@ pub enum RelayError {
 
     #[error("TLS error: {0}")]
     TlsError(String),
+
+    #[error("Relay already configured: {0}")]
+    DuplicateRelay(String),
+
+    #[error("Relay not configured: {0}")]
+    RelayNotFound(String),
+
+    #[error("Relay does not support COUNT: {0}")]
+    CountUnsupported(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Not allowed by relay role: {0}")]
+    RoleForbidden(String),
+
+    #[error("Timed out waiting for OK: {0}")]
+    PublishTimeout(String),
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Default time to wait for a relay's OK response after publishing
+pub const PUBLISH_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time to wait for a relay's NIP-45 COUNT response
+pub const COUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `RelayEvent::Disconnected` reason when we closed the connection ourselves
+pub const MANUAL_DISCONNECT_REASON: &str = "Manual disconnect";
+
+/// Outcome of publishing one event to one relay
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishResult {
+    /// Relay URL
+    pub relay: String,
+    /// Whether the relay accepted the event (OK true)
+    pub accepted: bool,
+    /// Relay-provided message, or the local failure reason
+    pub message: String,
+}
+
+impl PublishResult {
+    pub fn rejected(relay: &str, message: impl Into<String>) -> Self {
+        Self {
+            relay: relay.to_string(),
+            accepted: false,
+            message: message.into(),
+        }
+    }
 }
 
 /// Relay connection status
@@ -62,15 +113,47 @@ pub enum RelayStatus {
     Error(String),
 }
 
+/// What a relay may be used for
+///
+/// Read-only suits public aggregators that should never see our writes;
+/// write-only suits relays we publish to but never query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayRole {
+    ReadOnly,
+    WriteOnly,
+    #[default]
+    ReadWrite,
+}
+
+impl RelayRole {
+    /// Whether subscriptions and counts may be sent
+    pub fn can_read(self) -> bool {
+        self != RelayRole::WriteOnly
+    }
+
+    /// Whether events may be published
+    pub fn can_write(self) -> bool {
+        self != RelayRole::ReadOnly
+    }
+}
+
 /// Nostr relay client with certificate pinning
 pub struct NostrRelay {
     url: String,
     status: Arc<RwLock<RelayStatus>>,
-    ws: Arc<RwLock<Option<WebSocketStream<MaybeTlsStream<TcpStream>>>>>,
+    /// Write half of the WebSocket; the read half is owned by the message handler
+    ws: Arc<RwLock<Option<SplitSink<WsStream, Message>>>>,
     subscriptions: Arc<RwLock<HashMap<String, Subscription>>>,
     event_tx: broadcast::Sender<RelayEvent>,
     /// Certificate pin store for MITM protection
     pin_store: Arc<CertPinStore>,
+    /// Whether the relay may be read from, written to, or both
+    role: parking_lot::RwLock<RelayRole>,
+    /// TLS version and cipher suite policy for new connections
+    tls_policy: parking_lot::RwLock<TlsPolicy>,
+    /// Published events still waiting for the relay's OK
+    in_flight: Arc<InFlightPublishes>,
 }
 
 impl NostrRelay {
@@ -87,6 +170,43 @@ impl NostrRelay {
             subscriptions: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
             pin_store,
+            role: parking_lot::RwLock::new(RelayRole::default()),
+            tls_policy: parking_lot::RwLock::new(TlsPolicy::default()),
+            in_flight: Arc::new(InFlightPublishes::new()),
+        }
+    }
+
+    /// TLS policy applied when connecting
+    pub fn tls_policy(&self) -> TlsPolicy {
+        self.tls_policy.read().clone()
+    }
+
+    /// Set the TLS policy for later connections; an open connection keeps
+    /// the policy it was established with
+    pub fn set_tls_policy(&self, policy: TlsPolicy) {
+        *self.tls_policy.write() = policy;
+    }
+
+    /// What this relay may be used for
+    pub fn role(&self) -> RelayRole {
+        *self.role.read()
+    }
+
+    /// Restrict this relay to reading, writing, or both
+    ///
+    /// Applies to later operations; open subscriptions are not closed.
+    pub fn set_role(&self, role: RelayRole) {
+        *self.role.write() = role;
+    }
+
+    fn check_can_read(&self) -> Result<(), RelayError> {
+        if self.role().can_read() {
+            Ok(())
+        } else {
+            Err(RelayError::RoleForbidden(format!(
+                "{} is write-only",
+                self.url
+            )))
         }
     }
 
@@ -116,12 +236,13 @@ impl NostrRelay {
         }
         drop(status);
 
+        // Create pinned TLS configuration under the TLS policy
+        let tls_config = create_pinned_tls_config(Arc::clone(&self.pin_store), &self.tls_policy())
+            .map_err(|e| RelayError::TlsError(e.to_string()))?;
+
         // Update status to connecting
         *self.status.write().await = RelayStatus::Connecting;
 
-        // Create pinned TLS configuration
-        let tls_config = create_pinned_tls_config(Arc::clone(&self.pin_store));
-
         // Create TLS connector with certificate pinning
         let connector = Connector::Rustls(tls_config);
 
@@ -140,8 +261,10 @@ impl NostrRelay {
                 }
             })?;
 
-        // Store connection
-        *self.ws.write().await = Some(ws_stream);
+        // Store the write half; the read half goes to the message handler so
+        // that waiting for frames never blocks outgoing sends
+        let (sink, stream) = ws_stream.split();
+        *self.ws.write().await = Some(sink);
         *self.status.write().await = RelayStatus::Connected;
 
         // Log certificate pinning status
@@ -151,10 +274,7 @@ impl NostrRelay {
                 self.url
             );
         } else {
-            log::info!(
-                "Connected to Nostr relay (TOFU - first use): {}",
-                self.url
-            );
+            log::info!("Connected to Nostr relay (TOFU - first use): {}", self.url);
         }
 
         // Broadcast connected event
@@ -163,7 +283,7 @@ impl NostrRelay {
         });
 
         // Start message handling task
-        self.start_message_handler();
+        self.start_message_handler(stream);
 
         Ok(())
     }
@@ -171,17 +291,19 @@ impl NostrRelay {
     /// Disconnect from the relay
     pub async fn disconnect(&self) -> Result<(), RelayError> {
         let mut ws = self.ws.write().await;
-        if let Some(mut stream) = ws.take() {
-            let _ = stream.close(None).await;
+        if let Some(mut sink) = ws.take() {
+            let _ = sink.close().await;
         }
 
         *self.status.write().await = RelayStatus::Disconnected;
         self.subscriptions.write().await.clear();
+        self.in_flight
+            .fail_all(&format!("disconnected: {}", MANUAL_DISCONNECT_REASON));
 
         // Broadcast disconnected event
         let _ = self.event_tx.send(RelayEvent::Disconnected {
             url: self.url.clone(),
-            reason: "Manual disconnect".to_string(),
+            reason: MANUAL_DISCONNECT_REASON.to_string(),
         });
 
         log::info!("Disconnected from Nostr relay: {}", self.url);
@@ -190,45 +312,115 @@ impl NostrRelay {
 
     /// Publish an event to the relay
     pub async fn publish(&self, event: NostrEvent) -> Result<(), RelayError> {
+        if !self.role().can_write() {
+            return Err(RelayError::RoleForbidden(format!(
+                "{} is read-only",
+                self.url
+            )));
+        }
+
         let ws = self.ws.read().await;
         if ws.is_none() {
             return Err(RelayError::NotConnected(self.url.clone()));
         }
         drop(ws);
 
-        let message = json!(["EVENT", event]);
-        let msg_str = serde_json::to_string(&message)
-            .map_err(|e| RelayError::SerializationError(e.to_string()))?;
-
-        self.send_message(Message::Text(msg_str)).await?;
+        let event_id = event.id.clone();
+        self.send_nostr_message(&NostrMessage::Publish(event))
+            .await?;
 
-        log::debug!("Published event {} to {}", event.id, self.url);
+        log::debug!("Published event {} to {}", event_id, self.url);
         Ok(())
     }
 
+    /// Publish an event and wait for the relay's OK response
+    ///
+    /// Never fails: local errors (not connected, send failure, unpinned
+    /// certificate, timeout) are reported as a rejected `PublishResult`.
+    pub async fn publish_with_ack(&self, event: NostrEvent, timeout: Duration) -> PublishResult {
+        self.publish_with_timeout(event, timeout)
+            .await
+            .unwrap_or_else(|e| PublishResult::rejected(&self.url, e.to_string()))
+    }
+
+    /// Publish an event and wait up to `timeout` for the relay's OK response
+    ///
+    /// The event is tracked as in flight until the OK arrives; after the
+    /// deadline this fails with [`RelayError::PublishTimeout`] and a late OK
+    /// for the event is discarded.
+    pub async fn publish_with_timeout(
+        &self,
+        event: NostrEvent,
+        timeout: Duration,
+    ) -> Result<PublishResult, RelayError> {
+        if self.pin_store.config().require_pinned_for_write && !self.is_certificate_pinned() {
+            return Err(RelayError::SendFailed(
+                "blocked: relay certificate is not pinned (require_pinned_for_write)".to_string(),
+            ));
+        }
+
+        // Register before sending so the OK cannot be missed
+        let in_flight = self.in_flight.register(&event.id)?;
+        self.publish(event).await?;
+
+        let (accepted, message) = in_flight.wait(timeout).await?;
+        Ok(PublishResult {
+            relay: self.url.clone(),
+            accepted,
+            message,
+        })
+    }
+
+    /// Number of published events still waiting for an OK
+    pub fn in_flight_publishes(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Count events matching a filter without downloading them (NIP-45)
+    ///
+    /// Fails with [`RelayError::CountUnsupported`] when the relay answers
+    /// with a NOTICE about COUNT or closes the query.
+    pub async fn count(&self, filter: Filter) -> Result<u64, RelayError> {
+        self.count_with_timeout(filter, COUNT_TIMEOUT).await
+    }
+
+    /// Like [`NostrRelay::count`], waiting at most `timeout` for the response
+    pub async fn count_with_timeout(
+        &self,
+        filter: Filter,
+        timeout: Duration,
+    ) -> Result<u64, RelayError> {
+        self.check_can_read()?;
+
+        // Subscribe before sending so the response cannot be missed
+        let mut rx = self.event_tx.subscribe();
+        let query_id = super::subscriptions::new_subscription_id();
+
+        self.send_nostr_message(&NostrMessage::CountRequest(query_id.clone(), vec![filter]))
+            .await?;
+
+        wait_for_count(&mut rx, &query_id, &self.url, timeout).await
+    }
+
     /// Subscribe to events matching filters
     pub async fn subscribe(
         &self,
         subscription_id: String,
         filters: Vec<Filter>,
     ) -> Result<(), RelayError> {
+        self.check_can_read()?;
+
         let ws = self.ws.read().await;
         if ws.is_none() {
             return Err(RelayError::NotConnected(self.url.clone()));
         }
         drop(ws);
 
-        // Build REQ message
-        let mut req = vec![json!("REQ"), json!(subscription_id)];
-        for filter in &filters {
-            req.push(serde_json::to_value(filter)
-                .map_err(|e| RelayError::SerializationError(e.to_string()))?);
-        }
-
-        let msg_str = serde_json::to_string(&req)
-            .map_err(|e| RelayError::SerializationError(e.to_string()))?;
-
-        self.send_message(Message::Text(msg_str)).await?;
+        self.send_nostr_message(&NostrMessage::Request(
+            subscription_id.clone(),
+            filters.clone(),
+        ))
+        .await?;
 
         // Store subscription
         let now = SystemTime::now()
@@ -261,21 +453,40 @@ impl NostrRelay {
         drop(ws);
 
         // Remove from subscriptions
-        if self.subscriptions.write().await.remove(&subscription_id).is_none() {
+        if self
+            .subscriptions
+            .write()
+            .await
+            .remove(&subscription_id)
+            .is_none()
+        {
             return Err(RelayError::SubscriptionNotFound(subscription_id));
         }
 
         // Send CLOSE message
-        let message = json!(["CLOSE", subscription_id]);
-        let msg_str = serde_json::to_string(&message)
-            .map_err(|e| RelayError::SerializationError(e.to_string()))?;
-
-        self.send_message(Message::Text(msg_str)).await?;
+        self.send_nostr_message(&NostrMessage::Close(subscription_id.clone()))
+            .await?;
 
         log::debug!("Unsubscribed from: {}", subscription_id);
         Ok(())
     }
 
+    /// Whether the relay has sent EOSE for a subscription
+    ///
+    /// `None` if the subscription is not open on this relay.
+    pub async fn eose_received(&self, subscription_id: &str) -> Option<bool> {
+        self.subscriptions
+            .read()
+            .await
+            .get(subscription_id)
+            .map(|sub| sub.eose_received)
+    }
+
+    /// Relay URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
     /// Get current relay status
     pub async fn status(&self) -> RelayStatus {
         self.status.read().await.clone()
@@ -305,12 +516,19 @@ impl NostrRelay {
         self.pin_store.clear_tofu_pin(&self.url)
     }
 
+    /// Serialize and send a wire message to the relay
+    async fn send_nostr_message(&self, message: &NostrMessage) -> Result<(), RelayError> {
+        let msg_str = message
+            .to_json()
+            .map_err(|e| RelayError::SerializationError(e.to_string()))?;
+        self.send_message(Message::Text(msg_str)).await
+    }
+
     /// Send a message to the relay
     async fn send_message(&self, message: Message) -> Result<(), RelayError> {
         let mut ws = self.ws.write().await;
-        if let Some(stream) = ws.as_mut() {
-            stream
-                .send(message)
+        if let Some(sink) = ws.as_mut() {
+            sink.send(message)
                 .await
                 .map_err(|e| RelayError::SendFailed(e.to_string()))?;
             Ok(())
@@ -320,60 +538,47 @@ impl NostrRelay {
     }
 
     /// Start the message handler task
-    fn start_message_handler(&self) {
-        let ws = Arc::clone(&self.ws);
+    fn start_message_handler(&self, mut stream: SplitStream<WsStream>) {
         let subscriptions = Arc::clone(&self.subscriptions);
         let event_tx = self.event_tx.clone();
         let url = self.url.clone();
         let status = Arc::clone(&self.status);
+        let in_flight = Arc::clone(&self.in_flight);
 
         tokio::spawn(async move {
             loop {
-                let mut ws_lock = ws.write().await;
-                if let Some(stream) = ws_lock.as_mut() {
-                    match stream.next().await {
-                        Some(Ok(Message::Text(text))) => {
-                            drop(ws_lock);
-                            if let Err(e) = Self::handle_message(
-                                &text,
-                                &subscriptions,
-                                &event_tx,
-                                &url,
-                            )
-                            .await
-                            {
-                                log::warn!("Failed to handle message: {}", e);
-                            }
-                        }
-                        Some(Ok(Message::Close(_))) => {
-                            drop(ws_lock);
-                            *status.write().await = RelayStatus::Disconnected;
-                            let _ = event_tx.send(RelayEvent::Disconnected {
-                                url: url.clone(),
-                                reason: "Connection closed by relay".to_string(),
-                            });
-                            break;
-                        }
-                        Some(Err(e)) => {
-                            drop(ws_lock);
-                            log::error!("WebSocket error: {}", e);
-                            *status.write().await = RelayStatus::Error(e.to_string());
-                            break;
-                        }
-                        None => {
-                            drop(ws_lock);
-                            *status.write().await = RelayStatus::Disconnected;
-                            break;
-                        }
-                        _ => {
-                            drop(ws_lock);
+                match stream.next().await {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Err(e) =
+                            Self::handle_message(&text, &subscriptions, &event_tx, &in_flight, &url)
+                                .await
+                        {
+                            log::warn!("Failed to handle message: {}", e);
                         }
                     }
-                } else {
-                    drop(ws_lock);
-                    break;
+                    Some(Ok(Message::Close(_))) => {
+                        *status.write().await = RelayStatus::Disconnected;
+                        let _ = event_tx.send(RelayEvent::Disconnected {
+                            url: url.clone(),
+                            reason: "Connection closed by relay".to_string(),
+                        });
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        log::error!("WebSocket error: {}", e);
+                        *status.write().await = RelayStatus::Error(e.to_string());
+                        break;
+                    }
+                    None => {
+                        *status.write().await = RelayStatus::Disconnected;
+                        break;
+                    }
+                    _ => {}
                 }
             }
+
+            // No OK can arrive on this connection any more
+            in_flight.fail_all("disconnected: connection closed");
         });
     }
 
@@ -382,66 +587,484 @@ impl NostrRelay {
         text: &str,
         subscriptions: &Arc<RwLock<HashMap<String, Subscription>>>,
         event_tx: &broadcast::Sender<RelayEvent>,
+        in_flight: &InFlightPublishes,
         url: &str,
     ) -> Result<(), RelayError> {
-        let value: serde_json::Value = serde_json::from_str(text)
-            .map_err(|e| RelayError::SerializationError(e.to_string()))?;
-
-        if let Some(array) = value.as_array() {
-            if array.is_empty() {
-                return Ok(());
+        let message =
+            NostrMessage::parse(text).map_err(|e| RelayError::SerializationError(e.to_string()))?;
+
+        match message {
+            NostrMessage::Event(subscription_id, event) => {
+                let _ = event_tx.send(RelayEvent::Event {
+                    subscription_id,
+                    event,
+                });
             }
+            NostrMessage::Count(sub_id, result) => {
+                let _ = event_tx.send(RelayEvent::Count {
+                    subscription_id: sub_id,
+                    result,
+                });
+            }
+            NostrMessage::EndOfStoredEvents(sub_id) => {
+                // Mark subscription as having received EOSE
+                if let Some(sub) = subscriptions.write().await.get_mut(&sub_id) {
+                    sub.eose_received = true;
+                }
 
-            match array[0].as_str() {
-                Some("EVENT") if array.len() >= 3 => {
-                    let sub_id = array[1].as_str().unwrap_or("").to_string();
-                    let event: NostrEvent = serde_json::from_value(array[2].clone())
-                        .map_err(|e| RelayError::SerializationError(e.to_string()))?;
-
-                    let _ = event_tx.send(RelayEvent::Event {
-                        subscription_id: sub_id,
-                        event,
-                    });
+                let _ = event_tx.send(RelayEvent::EndOfStoredEvents {
+                    subscription_id: sub_id,
+                });
+            }
+            NostrMessage::Notice(message) => {
+                let _ = event_tx.send(RelayEvent::Notice {
+                    url: url.to_string(),
+                    message,
+                });
+            }
+            NostrMessage::Ok(event_id, success, message) => {
+                if !in_flight.resolve(&event_id, success, message.clone()) {
+                    log::debug!(
+                        "Discarding OK for {} from {}: no publish waiting",
+                        event_id,
+                        url
+                    );
+                }
+                if success {
+                    let _ = event_tx.send(RelayEvent::EventPublished { event_id });
+                } else {
+                    let _ = event_tx.send(RelayEvent::EventFailed { event_id, message });
                 }
-                Some("EOSE") if array.len() >= 2 => {
-                    let sub_id = array[1].as_str().unwrap_or("").to_string();
+            }
+            NostrMessage::Closed(sub_id, message) => {
+                // The relay has already dropped it; no CLOSE needed
+                subscriptions.write().await.remove(&sub_id);
+                log::debug!("Relay {} closed subscription {}: {}", url, sub_id, message);
+
+                let _ = event_tx.send(RelayEvent::SubscriptionClosed {
+                    subscription_id: sub_id,
+                    message,
+                });
+            }
+            other => {
+                log::debug!("Unhandled message from {}: {:?}", url, other);
+            }
+        }
 
-                    // Mark subscription as having received EOSE
-                    if let Some(sub) = subscriptions.write().await.get_mut(&sub_id) {
-                        sub.eose_received = true;
-                    }
+        Ok(())
+    }
+}
 
-                    let _ = event_tx.send(RelayEvent::EndOfStoredEvents {
-                        subscription_id: sub_id,
-                    });
+/// Wait for the COUNT response to `query_id` on a relay's event channel
+///
+/// Relays without NIP-45 usually reply with a NOTICE naming the unknown
+/// message type; a NOTICE mentioning COUNT, or CLOSED for the query, is
+/// reported as unsupported.
+async fn wait_for_count(
+    rx: &mut broadcast::Receiver<RelayEvent>,
+    query_id: &str,
+    url: &str,
+    timeout: Duration,
+) -> Result<u64, RelayError> {
+    let wait = async {
+        loop {
+            match rx.recv().await {
+                Ok(RelayEvent::Count {
+                    subscription_id,
+                    result,
+                }) if subscription_id == query_id => return Ok(result.count),
+                Ok(RelayEvent::Notice { message, .. })
+                    if message.to_ascii_lowercase().contains("count") =>
+                {
+                    return Err(RelayError::CountUnsupported(format!(
+                        "{}: {}",
+                        url, message
+                    )));
                 }
-                Some("NOTICE") if array.len() >= 2 => {
-                    let message = array[1].as_str().unwrap_or("").to_string();
-
-                    let _ = event_tx.send(RelayEvent::Notice {
-                        url: url.to_string(),
-                        message,
-                    });
+                Ok(RelayEvent::SubscriptionClosed {
+                    subscription_id,
+                    message,
+                }) if subscription_id == query_id => {
+                    return Err(RelayError::CountUnsupported(format!(
+                        "{}: {}",
+                        url, message
+                    )));
                 }
-                Some("OK") if array.len() >= 3 => {
-                    let event_id = array[1].as_str().unwrap_or("").to_string();
-                    let success = array[2].as_bool().unwrap_or(false);
-                    let message = array.get(3).and_then(|v| v.as_str()).unwrap_or("").to_string();
-
-                    if success {
-                        let _ = event_tx.send(RelayEvent::EventPublished { event_id });
-                    } else {
-                        let _ = event_tx.send(RelayEvent::EventFailed { event_id, message });
-                    }
+                Ok(RelayEvent::Disconnected { reason, .. }) => {
+                    return Err(RelayError::NotConnected(format!("{}: {}", url, reason)));
                 }
-                _ => {
-                    log::debug!("Unknown message type: {:?}", array[0]);
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => {
+                    return Err(RelayError::NotConnected(url.to_string()));
                 }
             }
         }
+    };
 
-        Ok(())
-    }
+    tokio::time::timeout(timeout, wait)
+        .await
+        .unwrap_or_else(|_| Err(RelayError::Timeout(format!("COUNT response from {}", url))))
+}
+
+/// Publish an event to several relays concurrently
+///
+/// Returns one result per relay, in the same order, including relays that
+/// rejected the event or could not be reached.
+pub async fn publish_to_relays(
+    relays: &[Arc<NostrRelay>],
+    event: &NostrEvent,
+    timeout: Duration,
+) -> Vec<PublishResult> {
+    futures::future::join_all(
+        relays
+            .iter()
+            .map(|relay| relay.publish_with_ack(event.clone(), timeout)),
+    )
+    .await
 }
 
 use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::{
+        spawn_count_relay, spawn_delayed_ok_relay, spawn_mock_relay, spawn_recording_relay,
+        test_event, test_pin_store,
+    };
+    use crate::nostr::types::CountResult;
+
+    #[tokio::test]
+    async fn test_eose_marks_subscription_and_emits_event() {
+        let subscriptions = Arc::new(RwLock::new(HashMap::new()));
+        subscriptions.write().await.insert(
+            "sub1".to_string(),
+            Subscription {
+                id: "sub1".to_string(),
+                filters: vec![Filter::new()],
+                created_at: 0,
+                eose_received: false,
+            },
+        );
+        let (tx, mut rx) = broadcast::channel(16);
+        let in_flight = InFlightPublishes::new();
+
+        NostrRelay::handle_message(
+            r#"["EOSE","sub1"]"#,
+            &subscriptions,
+            &tx,
+            &in_flight,
+            "wss://a",
+        )
+        .await
+        .unwrap();
+
+        match rx.try_recv().unwrap() {
+            RelayEvent::EndOfStoredEvents { subscription_id } => {
+                assert_eq!(subscription_id, "sub1")
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(subscriptions.read().await["sub1"].eose_received);
+
+        // EOSE for an unknown subscription is still surfaced
+        NostrRelay::handle_message(
+            r#"["EOSE","other"]"#,
+            &subscriptions,
+            &tx,
+            &in_flight,
+            "wss://a",
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            RelayEvent::EndOfStoredEvents { subscription_id } if subscription_id == "other"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_eose_received_tracks_subscription() {
+        let (url, _frames) = spawn_recording_relay().await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+        let mut rx = relay.subscribe_events();
+
+        assert_eq!(relay.eose_received("sub1").await, None);
+        relay
+            .subscribe("sub1".to_string(), vec![Filter::new()])
+            .await
+            .unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            event,
+            RelayEvent::EndOfStoredEvents { subscription_id } if subscription_id == "sub1"
+        ));
+        assert_eq!(relay.eose_received("sub1").await, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_count_matches_query_id() {
+        let (tx, mut rx) = broadcast::channel(16);
+        tx.send(RelayEvent::Count {
+            subscription_id: "other".to_string(),
+            result: CountResult {
+                count: 1,
+                approximate: None,
+            },
+        })
+        .unwrap();
+        tx.send(RelayEvent::Count {
+            subscription_id: "q1".to_string(),
+            result: CountResult {
+                count: 342,
+                approximate: None,
+            },
+        })
+        .unwrap();
+
+        let count = wait_for_count(&mut rx, "q1", "wss://a", Duration::from_secs(1)).await;
+        assert_eq!(count.unwrap(), 342);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_count_unsupported_and_timeout() {
+        let (tx, mut rx) = broadcast::channel(16);
+        tx.send(RelayEvent::Notice {
+            url: "wss://a".to_string(),
+            message: "rate limited".to_string(),
+        })
+        .unwrap();
+        tx.send(RelayEvent::Notice {
+            url: "wss://a".to_string(),
+            message: "ERROR: unknown message type COUNT".to_string(),
+        })
+        .unwrap();
+        assert!(matches!(
+            wait_for_count(&mut rx, "q1", "wss://a", Duration::from_secs(1)).await,
+            Err(RelayError::CountUnsupported(m)) if m.contains("unknown message type")
+        ));
+
+        tx.send(RelayEvent::SubscriptionClosed {
+            subscription_id: "q1".to_string(),
+            message: "auth-required: log in first".to_string(),
+        })
+        .unwrap();
+        assert!(matches!(
+            wait_for_count(&mut rx, "q1", "wss://a", Duration::from_secs(1)).await,
+            Err(RelayError::CountUnsupported(_))
+        ));
+
+        assert!(matches!(
+            wait_for_count(&mut rx, "q1", "wss://a", Duration::from_millis(20)).await,
+            Err(RelayError::Timeout(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_count_sends_request_and_parses_response() {
+        let (url, frames) = spawn_count_relay(Some(342)).await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+
+        let count = relay
+            .count(Filter::new().kinds(vec![4]).p_tags(vec!["c".repeat(64)]))
+            .await
+            .unwrap();
+        assert_eq!(count, 342);
+
+        let frames = frames.lock();
+        let sent: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(sent[0], "COUNT");
+        assert!(sent[1].is_string());
+        assert_eq!(sent[2]["kinds"], serde_json::json!([4]));
+    }
+
+    #[tokio::test]
+    async fn test_count_unsupported_relay() {
+        let (url, _frames) = spawn_count_relay(None).await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+
+        assert!(matches!(
+            relay.count(Filter::new()).await,
+            Err(RelayError::CountUnsupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_count_requires_connection() {
+        let relay = NostrRelay::new("wss://offline.relay.io".to_string(), test_pin_store(false));
+        assert!(matches!(
+            relay.count(Filter::new()).await,
+            Err(RelayError::NotConnected(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_read_only_relay_rejected() {
+        let url = spawn_mock_relay(true, "").await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+        relay.set_role(RelayRole::ReadOnly);
+
+        assert!(matches!(
+            relay.publish(test_event("evt1")).await,
+            Err(RelayError::RoleForbidden(_))
+        ));
+        let result = relay
+            .publish_with_ack(test_event("evt1"), Duration::from_secs(1))
+            .await;
+        assert!(!result.accepted);
+        assert!(result.message.contains("read-only"));
+
+        relay.set_role(RelayRole::ReadWrite);
+        let result = relay
+            .publish_with_ack(test_event("evt1"), Duration::from_secs(5))
+            .await;
+        assert!(result.accepted);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_write_only_relay_rejected() {
+        let (url, frames) = spawn_recording_relay().await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+        relay.set_role(RelayRole::WriteOnly);
+
+        assert!(matches!(
+            relay
+                .subscribe("sub1".to_string(), vec![Filter::new()])
+                .await,
+            Err(RelayError::RoleForbidden(_))
+        ));
+        assert!(matches!(
+            relay.count(Filter::new()).await,
+            Err(RelayError::RoleForbidden(_))
+        ));
+        assert_eq!(relay.eose_received("sub1").await, None);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(frames.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_requires_pinned_certificate() {
+        let relay = NostrRelay::new("wss://unpinned.relay.io".to_string(), test_pin_store(true));
+        let result = relay
+            .publish_with_ack(test_event("abc"), Duration::from_millis(50))
+            .await;
+        assert!(!result.accepted);
+        assert!(result.message.contains("not pinned"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_relays_collects_per_relay_results() {
+        let accepting_url = spawn_mock_relay(true, "").await;
+        let rejecting_url = spawn_mock_relay(false, "blocked: not on whitelist").await;
+
+        let accepting = Arc::new(NostrRelay::new(
+            accepting_url.clone(),
+            test_pin_store(false),
+        ));
+        let rejecting = Arc::new(NostrRelay::new(
+            rejecting_url.clone(),
+            test_pin_store(false),
+        ));
+        let offline = Arc::new(NostrRelay::new(
+            "ws://127.0.0.1:1".to_string(),
+            test_pin_store(false),
+        ));
+        accepting.connect().await.unwrap();
+        rejecting.connect().await.unwrap();
+
+        let relays = vec![accepting, rejecting, offline];
+        let results = publish_to_relays(&relays, &test_event("evt1"), Duration::from_secs(5)).await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].relay, accepting_url);
+        assert!(results[0].accepted);
+        assert_eq!(results[1].relay, rejecting_url);
+        assert!(!results[1].accepted);
+        assert_eq!(results[1].message, "blocked: not on whitelist");
+        assert!(!results[2].accepted);
+        assert!(results[2].message.contains("Not connected"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_times_out_without_ok() {
+        let (url, frames) = spawn_recording_relay().await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+
+        assert!(matches!(
+            relay
+                .publish_with_timeout(test_event("evt1"), Duration::from_millis(100))
+                .await,
+            Err(RelayError::PublishTimeout(id)) if id == "evt1"
+        ));
+        assert_eq!(frames.lock().len(), 1);
+        assert_eq!(relay.in_flight_publishes(), 0);
+
+        let result = relay
+            .publish_with_ack(test_event("evt2"), Duration::from_millis(50))
+            .await;
+        assert!(!result.accepted);
+        assert!(result.message.contains("Timed out waiting for OK"));
+    }
+
+    #[tokio::test]
+    async fn test_late_ok_after_timeout_discarded() {
+        let url = spawn_delayed_ok_relay(Duration::from_millis(200)).await;
+        let relay = NostrRelay::new(url, test_pin_store(false));
+        relay.connect().await.unwrap();
+        let mut rx = relay.subscribe_events();
+
+        assert!(matches!(
+            relay
+                .publish_with_timeout(test_event("evt1"), Duration::from_millis(50))
+                .await,
+            Err(RelayError::PublishTimeout(_))
+        ));
+
+        // The OK still reaches subscribers but resolves nothing
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            event,
+            RelayEvent::EventPublished { event_id } if event_id == "evt1"
+        ));
+        assert_eq!(relay.in_flight_publishes(), 0);
+
+        // The same event can be published again afterwards
+        let result = relay
+            .publish_with_timeout(test_event("evt1"), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(result.accepted);
+    }
+
+    #[tokio::test]
+    async fn test_ok_just_before_deadline_matched() {
+        let url = spawn_delayed_ok_relay(Duration::from_millis(150)).await;
+        let relay = NostrRelay::new(url.clone(), test_pin_store(false));
+        relay.connect().await.unwrap();
+
+        let (first, second) = tokio::join!(
+            relay.publish_with_timeout(test_event("evt1"), Duration::from_millis(250)),
+            relay.publish_with_timeout(test_event("evt2"), Duration::from_millis(250)),
+        );
+        for (result, id) in [(first, "evt1"), (second, "evt2")] {
+            let result = result.unwrap_or_else(|e| panic!("{} not matched: {}", id, e));
+            assert_eq!(result.relay, url);
+            assert!(result.accepted);
+        }
+        assert_eq!(relay.in_flight_publishes(), 0);
+    }
+}
@@ -0,0 +1,622 @@
+//! Frontend-driven REQ subscriptions spanning one or more relays
+//!
+//! Each subscription gets one forwarding task per relay that filters the
+//! relay's broadcast channel down to events for that subscription id and
+//! hands them to an [`EventSink`]. The Tauri commands use a sink that emits
+//! on [`RELAY_EVENT_CHANNEL`]; tests use a plain channel.
+//!
+//! Events from all of a subscription's relays pass through one shared
+//! [`EventBuffer`], so an event held by several relays surfaces once and
+//! events arriving close together surface in `created_at` order. Events
+//! authored by a blocked contact are dropped there too.
+//!
+//! [`SubscriptionManager::subscribe_resuming`] additionally tracks each
+//! relay's watermark (see [`super::watermarks`]) so reopening the same
+//! filter later fetches only newer events.
+
+use super::ingest::{EventBuffer, IngestConfig, IngestedEvent};
+use super::relay::{NostrRelay, RelayError};
+use super::types::{Filter, RelayEvent};
+use super::watermarks::{self, RelayWatermark, WatermarkStore};
+use crate::contacts::BlockList;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+/// Tauri event name carrying [`SubscriptionUpdate`]s
+pub const RELAY_EVENT_CHANNEL: &str = "nostr-relay-event";
+
+/// A relay event delivered for one subscription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionUpdate {
+    pub subscription_id: String,
+    pub relay: String,
+    pub event: RelayEvent,
+}
+
+/// Destination for subscription updates
+pub type EventSink = Arc<dyn Fn(SubscriptionUpdate) + Send + Sync>;
+
+struct TrackedSubscription {
+    relays: Vec<Arc<NostrRelay>>,
+    /// Per-relay forwarders plus the ingest flush task
+    forwarders: Vec<JoinHandle<()>>,
+    /// URLs of relays that have sent EOSE
+    eose_relays: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Tracks which relays hold each subscription
+#[derive(Default)]
+pub struct SubscriptionManager {
+    subscriptions: Mutex<HashMap<String, TrackedSubscription>>,
+    ingest: IngestConfig,
+    blocked: Arc<BlockList>,
+}
+
+/// Generate a random subscription id (32 hex chars, within the NIP-01 limit of 64)
+pub fn new_subscription_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a manager with custom dedup/reorder buffer limits
+    pub fn with_ingest(ingest: IngestConfig) -> Self {
+        Self {
+            ingest,
+            ..Self::default()
+        }
+    }
+
+    /// Drop events authored by contacts on `blocked`
+    pub fn with_block_list(mut self, blocked: Arc<BlockList>) -> Self {
+        self.blocked = blocked;
+        self
+    }
+
+    /// Open a subscription on every given relay
+    ///
+    /// Relays that fail to accept the REQ are skipped; the call only fails if
+    /// none of them do.
+    pub async fn subscribe(
+        &self,
+        relays: Vec<Arc<NostrRelay>>,
+        filters: Vec<Filter>,
+        sink: EventSink,
+    ) -> Result<String, RelayError> {
+        self.open(relays, filters, sink, None).await
+    }
+
+    /// Open a subscription that resumes from the watermarks in `store`
+    ///
+    /// Each relay is asked only for events since its saved watermark for
+    /// these filters (less the overlap), and the watermark advances as
+    /// events arrive. Filters that cannot be resumed (see
+    /// [`watermarks::is_resumable`]) are subscribed in full.
+    pub async fn subscribe_resuming(
+        &self,
+        relays: Vec<Arc<NostrRelay>>,
+        filters: Vec<Filter>,
+        sink: EventSink,
+        store: Arc<dyn WatermarkStore>,
+    ) -> Result<String, RelayError> {
+        let resume =
+            watermarks::is_resumable(&filters).then(|| (store, watermarks::filter_key(&filters)));
+        self.open(relays, filters, sink, resume).await
+    }
+
+    async fn open(
+        &self,
+        relays: Vec<Arc<NostrRelay>>,
+        filters: Vec<Filter>,
+        sink: EventSink,
+        resume: Option<(Arc<dyn WatermarkStore>, String)>,
+    ) -> Result<String, RelayError> {
+        let subscription_id = new_subscription_id();
+        let mut tracked = TrackedSubscription {
+            relays: Vec::new(),
+            forwarders: Vec::new(),
+            eose_relays: Arc::new(Mutex::new(HashSet::new())),
+        };
+        let mut last_error = None;
+        let buffer = Arc::new(Mutex::new(EventBuffer::new(self.ingest)));
+
+        for relay in relays {
+            let mut relay_filters = filters.clone();
+            let mut watermark = None;
+            if let Some((store, key)) = &resume {
+                let saved = store.load(key, relay.url());
+                if let Some(saved) = saved {
+                    relay_filters = watermarks::resume_filters(&filters, saved);
+                }
+                watermark = Some(RelayWatermark::new(
+                    Arc::clone(store),
+                    key.clone(),
+                    relay.url().to_string(),
+                    saved,
+                ));
+            }
+
+            // Listen before sending REQ so stored events are not missed
+            let forwarder = spawn_forwarder(
+                &relay,
+                subscription_id.clone(),
+                Arc::clone(&sink),
+                Arc::clone(&buffer),
+                Arc::clone(&tracked.eose_relays),
+                Arc::clone(&self.blocked),
+                watermark,
+            );
+            match relay
+                .subscribe(subscription_id.clone(), relay_filters)
+                .await
+            {
+                Ok(()) => {
+                    tracked.relays.push(relay);
+                    tracked.forwarders.push(forwarder);
+                }
+                Err(e) => {
+                    log::warn!("Relay {} rejected subscription: {}", relay.url(), e);
+                    forwarder.abort();
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if tracked.relays.is_empty() {
+            return Err(
+                last_error.unwrap_or_else(|| RelayError::InvalidUrl("no relays given".to_string()))
+            );
+        }
+
+        if !self.ingest.reorder_window.is_zero() {
+            tracked.forwarders.push(spawn_flusher(
+                subscription_id.clone(),
+                sink,
+                buffer,
+                self.ingest,
+                Arc::clone(&self.blocked),
+            ));
+        }
+
+        log::info!(
+            "Opened subscription {} on {} relays",
+            subscription_id,
+            tracked.relays.len()
+        );
+        self.subscriptions
+            .lock()
+            .insert(subscription_id.clone(), tracked);
+        Ok(subscription_id)
+    }
+
+    /// Close a subscription on every relay holding it
+    ///
+    /// CLOSE failures (e.g. the relay has since disconnected) are logged and
+    /// do not stop the remaining relays from being closed.
+    pub async fn unsubscribe(&self, subscription_id: &str) -> Result<(), RelayError> {
+        let tracked = self
+            .subscriptions
+            .lock()
+            .remove(subscription_id)
+            .ok_or_else(|| RelayError::SubscriptionNotFound(subscription_id.to_string()))?;
+
+        for forwarder in &tracked.forwarders {
+            forwarder.abort();
+        }
+        for relay in &tracked.relays {
+            if let Err(e) = relay.unsubscribe(subscription_id.to_string()).await {
+                log::warn!(
+                    "Failed to close {} on {}: {}",
+                    subscription_id,
+                    relay.url(),
+                    e
+                );
+            }
+        }
+
+        log::info!("Closed subscription {}", subscription_id);
+        Ok(())
+    }
+
+    /// Stop forwarding every subscription without sending CLOSE
+    ///
+    /// For when nothing more may reach the frontend right away (duress
+    /// mode); relays keep the subscriptions until they disconnect. Returns
+    /// the abandoned subscription ids.
+    pub fn abandon_all(&self) -> Vec<String> {
+        let abandoned: Vec<(String, TrackedSubscription)> =
+            self.subscriptions.lock().drain().collect();
+        for (_, tracked) in &abandoned {
+            for forwarder in &tracked.forwarders {
+                forwarder.abort();
+            }
+        }
+        abandoned.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Whether every relay holding a subscription has sent EOSE
+    ///
+    /// Until then the subscription is still receiving stored events.
+    /// `None` if the subscription is not tracked.
+    pub fn stored_events_complete(&self, subscription_id: &str) -> Option<bool> {
+        let subscriptions = self.subscriptions.lock();
+        let tracked = subscriptions.get(subscription_id)?;
+        let eose_relays = tracked.eose_relays.lock();
+        Some(
+            tracked
+                .relays
+                .iter()
+                .all(|relay| eose_relays.contains(relay.url())),
+        )
+    }
+
+    /// Whether a subscription is currently tracked
+    pub fn contains(&self, subscription_id: &str) -> bool {
+        self.subscriptions.lock().contains_key(subscription_id)
+    }
+
+    /// Number of open subscriptions
+    pub fn len(&self) -> usize {
+        self.subscriptions.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Hand buffered events to the sink, dropping those from blocked authors
+fn emit_ingested(
+    sink: &EventSink,
+    subscription_id: &str,
+    ready: Vec<IngestedEvent>,
+    blocked: &BlockList,
+) {
+    for ingested in ready {
+        if blocked.is_blocked(&ingested.event.pubkey) {
+            log::debug!("Dropped event {} from a blocked contact", ingested.event.id);
+            continue;
+        }
+        sink(SubscriptionUpdate {
+            subscription_id: subscription_id.to_string(),
+            relay: ingested.relay,
+            event: RelayEvent::Event {
+                subscription_id: subscription_id.to_string(),
+                event: ingested.event,
+            },
+        });
+    }
+}
+
+/// Periodically release buffered events whose reorder window has elapsed
+fn spawn_flusher(
+    subscription_id: String,
+    sink: EventSink,
+    buffer: Arc<Mutex<EventBuffer>>,
+    ingest: IngestConfig,
+    blocked: Arc<BlockList>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let period = (ingest.reorder_window / 2).max(Duration::from_millis(1));
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            let ready = buffer.lock().drain_due(Instant::now());
+            emit_ingested(&sink, &subscription_id, ready, &blocked);
+        }
+    })
+}
+
+/// Forward a relay's events for one subscription to the sink
+///
+/// Events go through the subscription's shared buffer; end of stored events
+/// flushes it first so stored events are never reported after EOSE.
+fn spawn_forwarder(
+    relay: &NostrRelay,
+    subscription_id: String,
+    sink: EventSink,
+    buffer: Arc<Mutex<EventBuffer>>,
+    eose_relays: Arc<Mutex<HashSet<String>>>,
+    blocked: Arc<BlockList>,
+    mut watermark: Option<RelayWatermark>,
+) -> JoinHandle<()> {
+    let mut rx = relay.subscribe_events();
+    let url = relay.url().to_string();
+
+    tokio::spawn(async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!(
+                        "Subscription {} lagged by {} events",
+                        subscription_id,
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            let matches = match &event {
+                RelayEvent::Event {
+                    subscription_id: id,
+                    ..
+                }
+                | RelayEvent::EndOfStoredEvents {
+                    subscription_id: id,
+                }
+                | RelayEvent::SubscriptionClosed {
+                    subscription_id: id,
+                    ..
+                } => *id == subscription_id,
+                _ => false,
+            };
+            if !matches {
+                continue;
+            }
+
+            match event {
+                RelayEvent::Event { event, .. } => {
+                    if let Some(watermark) = watermark.as_mut() {
+                        watermark.observe(event.created_at as i64);
+                    }
+                    let ready = buffer.lock().push(&url, event, Instant::now());
+                    emit_ingested(&sink, &subscription_id, ready, &blocked);
+                }
+                RelayEvent::EndOfStoredEvents { .. } => {
+                    if let Some(watermark) = watermark.as_mut() {
+                        watermark.end_of_stored_events();
+                    }
+                    eose_relays.lock().insert(url.clone());
+                    let ready = buffer.lock().flush();
+                    emit_ingested(&sink, &subscription_id, ready, &blocked);
+                    sink(SubscriptionUpdate {
+                        subscription_id: subscription_id.clone(),
+                        relay: url.clone(),
+                        event,
+                    });
+                }
+                event => sink(SubscriptionUpdate {
+                    subscription_id: subscription_id.clone(),
+                    relay: url.clone(),
+                    event,
+                }),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::{
+        memory_watermarks, spawn_recording_relay, spawn_stored_events_relay, test_event,
+        test_pin_store,
+    };
+    use crate::nostr::watermarks::WATERMARK_OVERLAP_SECS;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    async fn connected_relay(url: String) -> Arc<NostrRelay> {
+        let relay = Arc::new(NostrRelay::new(url, test_pin_store(false)));
+        relay.connect().await.unwrap();
+        relay
+    }
+
+    fn channel_sink() -> (EventSink, mpsc::UnboundedReceiver<SubscriptionUpdate>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sink: EventSink = Arc::new(move |update| {
+            let _ = tx.send(update);
+        });
+        (sink, rx)
+    }
+
+    /// Receive updates until the first EOSE
+    async fn wait_for_eose(rx: &mut mpsc::UnboundedReceiver<SubscriptionUpdate>) {
+        loop {
+            let update = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if matches!(update.event, RelayEvent::EndOfStoredEvents { .. }) {
+                return;
+            }
+        }
+    }
+
+    #[test]
+    fn test_subscription_id_generation() {
+        let a = new_subscription_id();
+        let b = new_subscription_id();
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_forwards_and_unsubscribe_closes() {
+        let (url_a, frames_a) = spawn_recording_relay().await;
+        let (url_b, frames_b) = spawn_recording_relay().await;
+        let relays = vec![connected_relay(url_a).await, connected_relay(url_b).await];
+        let manager = SubscriptionManager::new();
+        let (sink, mut rx) = channel_sink();
+
+        let id = manager
+            .subscribe(relays, vec![Filter::new().kinds(vec![1])], sink)
+            .await
+            .unwrap();
+        assert!(manager.contains(&id));
+
+        // Each recording relay answers REQ with EOSE
+        for _ in 0..2 {
+            let update = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(update.subscription_id, id);
+            assert!(matches!(update.event, RelayEvent::EndOfStoredEvents { .. }));
+        }
+
+        assert_eq!(manager.stored_events_complete(&id), Some(true));
+
+        manager.unsubscribe(&id).await.unwrap();
+        assert_eq!(manager.stored_events_complete(&id), None);
+        assert!(!manager.contains(&id));
+        assert!(manager.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for frames in [frames_a, frames_b] {
+            let frames = frames.lock();
+            assert!(frames
+                .iter()
+                .any(|f| f.starts_with("[\"REQ\"") && f.contains(&id)));
+            assert!(frames.contains(&format!("[\"CLOSE\",\"{}\"]", id)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resubscribe_starts_from_watermark() {
+        let mut older = test_event("older");
+        older.created_at = 1_700_000_000;
+        let mut newest = test_event("newest");
+        newest.created_at = 1_700_000_500;
+        let (url, frames) = spawn_stored_events_relay(vec![newest, older]).await;
+        let store = memory_watermarks();
+        let manager = SubscriptionManager::new();
+        let filters = vec![Filter::new().kinds(vec![1])];
+
+        let req_since = |id: &str| {
+            let frames = frames.lock();
+            let req = frames
+                .iter()
+                .find(|f| f.starts_with("[\"REQ\"") && f.contains(id))
+                .unwrap();
+            let value: serde_json::Value = serde_json::from_str(req).unwrap();
+            value[2]["since"].as_i64()
+        };
+
+        // First run: no watermark yet, full history
+        let (sink, mut rx) = channel_sink();
+        let first = manager
+            .subscribe_resuming(
+                vec![connected_relay(url.clone()).await],
+                filters.clone(),
+                sink,
+                store.clone(),
+            )
+            .await
+            .unwrap();
+        wait_for_eose(&mut rx).await;
+        assert_eq!(req_since(&first), None);
+        manager.unsubscribe(&first).await.unwrap();
+
+        // After a restart the same filter resumes from the newest event seen,
+        // less the overlap so events in the boundary second are fetched again
+        let (sink, mut rx) = channel_sink();
+        let second = manager
+            .subscribe_resuming(
+                vec![connected_relay(url.clone()).await],
+                filters.clone(),
+                sink,
+                store.clone(),
+            )
+            .await
+            .unwrap();
+        wait_for_eose(&mut rx).await;
+        assert_eq!(
+            req_since(&second),
+            Some(1_700_000_500 - WATERMARK_OVERLAP_SECS)
+        );
+
+        // Plain subscribe and bounded filters ignore watermarks
+        let (sink, _rx) = channel_sink();
+        let plain = manager
+            .subscribe(vec![connected_relay(url.clone()).await], filters, sink)
+            .await
+            .unwrap();
+        let (sink, _rx) = channel_sink();
+        let bounded = manager
+            .subscribe_resuming(
+                vec![connected_relay(url).await],
+                vec![Filter::new().kinds(vec![1]).until(1_800_000_000)],
+                sink,
+                store,
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(req_since(&plain), None);
+        assert_eq!(req_since(&bounded), None);
+    }
+
+    #[test]
+    fn test_events_from_blocked_authors_dropped() {
+        let (sink, mut rx) = channel_sink();
+        let blocked = BlockList::new();
+        let mut spam = test_event("spam");
+        spam.pubkey = "b".repeat(64);
+        let ready = || {
+            vec![
+                IngestedEvent {
+                    relay: "wss://a".to_string(),
+                    event: test_event("ok"),
+                },
+                IngestedEvent {
+                    relay: "wss://a".to_string(),
+                    event: spam.clone(),
+                },
+            ]
+        };
+        let delivered_ids = |rx: &mut mpsc::UnboundedReceiver<SubscriptionUpdate>| {
+            let mut ids = Vec::new();
+            while let Ok(update) = rx.try_recv() {
+                if let RelayEvent::Event { event, .. } = update.event {
+                    ids.push(event.id);
+                }
+            }
+            ids
+        };
+
+        blocked.block(&spam.pubkey).unwrap();
+        emit_ingested(&sink, "sub", ready(), &blocked);
+        assert_eq!(delivered_ids(&mut rx), vec!["ok"]);
+
+        blocked.unblock(&spam.pubkey).unwrap();
+        emit_ingested(&sink, "sub", ready(), &blocked);
+        assert_eq!(delivered_ids(&mut rx), vec!["ok", "spam"]);
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_unknown() {
+        let manager = SubscriptionManager::new();
+        assert!(matches!(
+            manager.unsubscribe("missing").await,
+            Err(RelayError::SubscriptionNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_fails_without_connected_relays() {
+        let relay = Arc::new(NostrRelay::new(
+            "ws://127.0.0.1:1".to_string(),
+            test_pin_store(false),
+        ));
+        let manager = SubscriptionManager::new();
+        let (sink, _rx) = channel_sink();
+
+        let result = manager
+            .subscribe(vec![relay], vec![Filter::new()], sink)
+            .await;
+        assert!(matches!(result, Err(RelayError::NotConnected(_))));
+        assert!(manager.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Shared fixtures for relay tests
+
+use super::cert_pinning::{CertPinConfig, CertPinStore};
+use super::watermarks::{load_watermark, save_watermark, WatermarkStore};
+use buildit_crypto::NostrEvent;
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+pub fn test_pin_store(require_pinned_for_write: bool) -> Arc<CertPinStore> {
+    Arc::new(CertPinStore::new(CertPinConfig {
+        tofu_enabled: true,
+        tofu_warn_on_change: true,
+        require_pinned_for_write,
+        pin_expiry_days: 365,
+    }))
+}
+
+pub fn test_event(id: &str) -> NostrEvent {
+    NostrEvent {
+        id: id.to_string(),
+        pubkey: "a".repeat(64),
+        created_at: 1700000000,
+        kind: 1,
+        tags: vec![],
+        content: "hello".to_string(),
+        sig: "b".repeat(128),
+    }
+}
+
+/// Start a mock relay that answers every EVENT with a fixed OK response
+pub async fn spawn_mock_relay(accept: bool, message: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if value[0] == "EVENT" {
+                        let ok = json!(["OK", value[1]["id"], accept, message]);
+                        ws.send(Message::Text(ok.to_string())).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+/// Start a mock relay that accepts every EVENT, sending the OK after `delay`
+pub async fn spawn_delayed_ok_relay(delay: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                let (sink, mut stream) = ws.split();
+                let sink = Arc::new(tokio::sync::Mutex::new(sink));
+                while let Some(Ok(Message::Text(text))) = stream.next().await {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    if value[0] == "EVENT" {
+                        let ok = json!(["OK", value[1]["id"], true, ""]);
+                        let sink = Arc::clone(&sink);
+                        tokio::spawn(async move {
+                            tokio::time::sleep(delay).await;
+                            let _ = sink.lock().await.send(Message::Text(ok.to_string())).await;
+                        });
+                    }
+                }
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+/// Start a mock relay that records every text frame and answers REQ with EOSE
+pub async fn spawn_recording_relay() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = Arc::clone(&frames);
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let recorded = Arc::clone(&recorded);
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    recorded.lock().push(text);
+                    if value[0] == "REQ" {
+                        let eose = json!(["EOSE", value[1]]);
+                        ws.send(Message::Text(eose.to_string())).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    (format!("ws://{}", addr), frames)
+}
+
+/// Start a mock relay that records every text frame and answers each REQ
+/// with `stored` followed by EOSE
+pub async fn spawn_stored_events_relay(
+    stored: Vec<NostrEvent>,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = Arc::clone(&frames);
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let recorded = Arc::clone(&recorded);
+            let stored = stored.clone();
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    recorded.lock().push(text);
+                    if value[0] == "REQ" {
+                        for event in &stored {
+                            let frame = json!(["EVENT", value[1], event]);
+                            ws.send(Message::Text(frame.to_string())).await.unwrap();
+                        }
+                        let eose = json!(["EOSE", value[1]]);
+                        ws.send(Message::Text(eose.to_string())).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    (format!("ws://{}", addr), frames)
+}
+
+/// Watermark store over an in-memory migrated database
+pub struct MemoryWatermarks(Mutex<Connection>);
+
+pub fn memory_watermarks() -> Arc<MemoryWatermarks> {
+    let mut conn = Connection::open_in_memory().unwrap();
+    crate::db::schema::run_migrations(&mut conn).unwrap();
+    Arc::new(MemoryWatermarks(Mutex::new(conn)))
+}
+
+impl WatermarkStore for MemoryWatermarks {
+    fn load(&self, filter_key: &str, relay_url: &str) -> Option<i64> {
+        load_watermark(&self.0.lock(), filter_key, relay_url).unwrap()
+    }
+
+    fn save(&self, filter_key: &str, relay_url: &str, created_at: i64) {
+        save_watermark(&self.0.lock(), filter_key, relay_url, created_at).unwrap();
+    }
+}
+
+/// Start a mock relay that records every text frame and answers COUNT
+///
+/// With `None` it replies the way relays without NIP-45 do, with a NOTICE.
+pub async fn spawn_count_relay(count: Option<u64>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let frames = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = Arc::clone(&frames);
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let recorded = Arc::clone(&recorded);
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                while let Some(Ok(Message::Text(text))) = ws.next().await {
+                    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+                    recorded.lock().push(text);
+                    if value[0] == "COUNT" {
+                        let reply = match count {
+                            Some(n) => json!(["COUNT", value[1], {"count": n}]),
+                            None => json!(["NOTICE", "ERROR: unknown message type COUNT"]),
+                        };
+                        ws.send(Message::Text(reply.to_string())).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+
+    (format!("ws://{}", addr), frames)
+}
+
+/// Handshakes in progress across a set of mock relays, and the most seen at once
+#[derive(Debug, Default)]
+pub struct HandshakeGauge {
+    pub current: std::sync::atomic::AtomicUsize,
+    pub peak: std::sync::atomic::AtomicUsize,
+}
+
+/// Start a mock relay that holds each WebSocket handshake for `delay`,
+/// counting it on `gauge` meanwhile
+pub async fn spawn_slow_handshake_relay(delay: Duration, gauge: Arc<HandshakeGauge>) -> String {
+    use std::sync::atomic::Ordering;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let gauge = Arc::clone(&gauge);
+            tokio::spawn(async move {
+                let now = gauge.current.fetch_add(1, Ordering::SeqCst) + 1;
+                gauge.peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(delay).await;
+                gauge.current.fetch_sub(1, Ordering::SeqCst);
+
+                let Ok(mut ws) = tokio_tungstenite::accept_async(tcp).await else {
+                    return;
+                };
+                while let Some(Ok(_)) = ws.next().await {}
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
@@ -1,39 +1,195 @@
 //! Nostr message types and filters
 
 use buildit_crypto::NostrEvent;
-use serde::{Deserialize, Serialize};
-
-/// Nostr relay message types
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "0", content = "1")]
+use serde::de::{self, DeserializeOwned};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+
+/// NIP-01 wire messages (plus NIP-42 AUTH and NIP-45 COUNT)
+///
+/// Serialized as the positional JSON arrays relays use. `EVENT`, `AUTH` and
+/// `COUNT` each have a client and a relay form, told apart by arity or by
+/// the type of their payload.
+#[derive(Debug, Clone)]
 pub enum NostrMessage {
     /// Event message ["EVENT", subscription_id, event]
-    #[serde(rename = "EVENT")]
     Event(String, NostrEvent),
 
     /// Publish event ["EVENT", event]
-    #[serde(rename = "EVENT")]
     Publish(NostrEvent),
 
     /// Request message ["REQ", subscription_id, ...filters]
-    #[serde(rename = "REQ")]
     Request(String, Vec<Filter>),
 
     /// Close subscription ["CLOSE", subscription_id]
-    #[serde(rename = "CLOSE")]
     Close(String),
 
     /// End of stored events ["EOSE", subscription_id]
-    #[serde(rename = "EOSE")]
     EndOfStoredEvents(String),
 
     /// Notice message ["NOTICE", message]
-    #[serde(rename = "NOTICE")]
     Notice(String),
 
     /// OK message ["OK", event_id, success, message]
-    #[serde(rename = "OK")]
     Ok(String, bool, String),
+
+    /// Subscription closed by the relay ["CLOSED", subscription_id, message]
+    Closed(String, String),
+
+    /// Authentication challenge from the relay ["AUTH", challenge]
+    AuthChallenge(String),
+
+    /// Authentication response ["AUTH", event]
+    Auth(NostrEvent),
+
+    /// Count request ["COUNT", subscription_id, ...filters]
+    CountRequest(String, Vec<Filter>),
+
+    /// Count response ["COUNT", subscription_id, {"count": n}]
+    Count(String, CountResult),
+}
+
+/// Payload of a NIP-45 COUNT response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountResult {
+    pub count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approximate: Option<bool>,
+}
+
+impl NostrMessage {
+    /// Parse a wire message
+    pub fn parse(text: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(text)
+    }
+
+    /// Serialize to the wire format
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+impl Serialize for NostrMessage {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        match self {
+            NostrMessage::Event(sub_id, event) => {
+                seq.serialize_element("EVENT")?;
+                seq.serialize_element(sub_id)?;
+                seq.serialize_element(event)?;
+            }
+            NostrMessage::Publish(event) => {
+                seq.serialize_element("EVENT")?;
+                seq.serialize_element(event)?;
+            }
+            NostrMessage::Request(sub_id, filters) => {
+                seq.serialize_element("REQ")?;
+                seq.serialize_element(sub_id)?;
+                for filter in filters {
+                    seq.serialize_element(filter)?;
+                }
+            }
+            NostrMessage::Close(sub_id) => {
+                seq.serialize_element("CLOSE")?;
+                seq.serialize_element(sub_id)?;
+            }
+            NostrMessage::EndOfStoredEvents(sub_id) => {
+                seq.serialize_element("EOSE")?;
+                seq.serialize_element(sub_id)?;
+            }
+            NostrMessage::Notice(message) => {
+                seq.serialize_element("NOTICE")?;
+                seq.serialize_element(message)?;
+            }
+            NostrMessage::Ok(event_id, accepted, message) => {
+                seq.serialize_element("OK")?;
+                seq.serialize_element(event_id)?;
+                seq.serialize_element(accepted)?;
+                seq.serialize_element(message)?;
+            }
+            NostrMessage::Closed(sub_id, message) => {
+                seq.serialize_element("CLOSED")?;
+                seq.serialize_element(sub_id)?;
+                seq.serialize_element(message)?;
+            }
+            NostrMessage::AuthChallenge(challenge) => {
+                seq.serialize_element("AUTH")?;
+                seq.serialize_element(challenge)?;
+            }
+            NostrMessage::Auth(event) => {
+                seq.serialize_element("AUTH")?;
+                seq.serialize_element(event)?;
+            }
+            NostrMessage::CountRequest(sub_id, filters) => {
+                seq.serialize_element("COUNT")?;
+                seq.serialize_element(sub_id)?;
+                for filter in filters {
+                    seq.serialize_element(filter)?;
+                }
+            }
+            NostrMessage::Count(sub_id, result) => {
+                seq.serialize_element("COUNT")?;
+                seq.serialize_element(sub_id)?;
+                seq.serialize_element(result)?;
+            }
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for NostrMessage {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<Value>::deserialize(deserializer)?;
+        Self::from_values(&items).map_err(de::Error::custom)
+    }
+}
+
+impl NostrMessage {
+    fn from_values(items: &[Value]) -> Result<Self, String> {
+        let (kind, rest) = items.split_first().ok_or("empty message")?;
+        let kind = kind.as_str().ok_or("message type must be a string")?;
+
+        fn field<T: DeserializeOwned>(rest: &[Value], i: usize) -> Result<T, String> {
+            let value = rest
+                .get(i)
+                .ok_or_else(|| format!("missing element {}", i + 1))?;
+            T::deserialize(value).map_err(|e| e.to_string())
+        }
+
+        fn filters(rest: &[Value]) -> Result<Vec<Filter>, String> {
+            rest.iter()
+                .skip(1)
+                .map(|v| Filter::deserialize(v).map_err(|e| e.to_string()))
+                .collect()
+        }
+
+        let message = match kind {
+            "EVENT" if rest.len() == 1 => NostrMessage::Publish(field(rest, 0)?),
+            "EVENT" => NostrMessage::Event(field(rest, 0)?, field(rest, 1)?),
+            "REQ" => NostrMessage::Request(field(rest, 0)?, filters(rest)?),
+            "CLOSE" => NostrMessage::Close(field(rest, 0)?),
+            "EOSE" => NostrMessage::EndOfStoredEvents(field(rest, 0)?),
+            "NOTICE" => NostrMessage::Notice(field(rest, 0)?),
+            // Tolerate relays that omit the human-readable message
+            "OK" => NostrMessage::Ok(
+                field(rest, 0)?,
+                field(rest, 1)?,
+                field(rest, 2).unwrap_or_default(),
+            ),
+            "CLOSED" => NostrMessage::Closed(field(rest, 0)?, field(rest, 1).unwrap_or_default()),
+            "AUTH" if rest.first().is_some_and(Value::is_string) => {
+                NostrMessage::AuthChallenge(field(rest, 0)?)
+            }
+            "AUTH" => NostrMessage::Auth(field(rest, 0)?),
+            "COUNT" if rest.len() == 2 && rest[1].get("count").is_some() => {
+                NostrMessage::Count(field(rest, 0)?, field(rest, 1)?)
+            }
+            "COUNT" => NostrMessage::CountRequest(field(rest, 0)?, filters(rest)?),
+            other => return Err(format!("unknown message type {other}")),
+        };
+        Ok(message)
+    }
 }
 
 /// Nostr subscription filter (NIP-01)
@@ -91,11 +247,26 @@ pub enum RelayEvent {
     Disconnected { url: String, reason: String },
 
     /// Received an event
-    Event { subscription_id: String, event: NostrEvent },
+    Event {
+        subscription_id: String,
+        event: NostrEvent,
+    },
 
     /// End of stored events
     EndOfStoredEvents { subscription_id: String },
 
+    /// NIP-45 count response
+    Count {
+        subscription_id: String,
+        result: CountResult,
+    },
+
+    /// Subscription closed by the relay
+    SubscriptionClosed {
+        subscription_id: String,
+        message: String,
+    },
+
     /// Notice from relay
     Notice { url: String, message: String },
 
@@ -154,3 +325,132 @@ impl Filter {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event_json() -> Value {
+        json!({
+            "id": "a".repeat(64),
+            "pubkey": "b".repeat(64),
+            "created_at": 1700000000,
+            "kind": 1,
+            "tags": [["p", "c".repeat(64)]],
+            "content": "hello",
+            "sig": "d".repeat(128),
+        })
+    }
+
+    /// Parse canonical JSON, check the variant, and re-serialize to the same JSON
+    fn round_trip(canonical: Value) -> NostrMessage {
+        let message = NostrMessage::parse(&canonical.to_string()).unwrap();
+        let reserialized: Value = serde_json::from_str(&message.to_json().unwrap()).unwrap();
+        assert_eq!(reserialized, canonical);
+        message
+    }
+
+    #[test]
+    fn test_event_messages() {
+        let relay_event = round_trip(json!(["EVENT", "sub1", event_json()]));
+        assert!(matches!(relay_event, NostrMessage::Event(ref id, _) if id == "sub1"));
+
+        let publish = round_trip(json!(["EVENT", event_json()]));
+        assert!(matches!(publish, NostrMessage::Publish(ref e) if e.content == "hello"));
+    }
+
+    #[test]
+    fn test_subscription_messages() {
+        let req = round_trip(json!([
+            "REQ",
+            "sub1",
+            {"kinds": [1], "authors": ["b".repeat(64)], "limit": 10},
+            {"#p": ["c".repeat(64)], "since": 1700000000}
+        ]));
+        match req {
+            NostrMessage::Request(id, filters) => {
+                assert_eq!(id, "sub1");
+                assert_eq!(filters.len(), 2);
+                assert_eq!(filters[0].limit, Some(10));
+                assert_eq!(filters[1].p_tags.as_ref().unwrap().len(), 1);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        assert!(matches!(
+            round_trip(json!(["CLOSE", "sub1"])),
+            NostrMessage::Close(ref id) if id == "sub1"
+        ));
+        assert!(matches!(
+            round_trip(json!(["EOSE", "sub1"])),
+            NostrMessage::EndOfStoredEvents(ref id) if id == "sub1"
+        ));
+        assert!(matches!(
+            round_trip(json!(["CLOSED", "sub1", "error: shutting down idle subscription"])),
+            NostrMessage::Closed(ref id, ref msg) if id == "sub1" && msg.starts_with("error:")
+        ));
+    }
+
+    #[test]
+    fn test_relay_responses() {
+        assert!(matches!(
+            round_trip(json!(["NOTICE", "rate limited"])),
+            NostrMessage::Notice(ref m) if m == "rate limited"
+        ));
+        assert!(matches!(
+            round_trip(json!(["OK", "a".repeat(64), true, ""])),
+            NostrMessage::Ok(_, true, _)
+        ));
+        assert!(matches!(
+            round_trip(json!(["OK", "a".repeat(64), false, "blocked: not on whitelist"])),
+            NostrMessage::Ok(_, false, ref m) if m == "blocked: not on whitelist"
+        ));
+
+        // Missing OK message is tolerated
+        let short_ok = NostrMessage::parse(r#"["OK", "abc", true]"#).unwrap();
+        assert!(matches!(short_ok, NostrMessage::Ok(_, true, ref m) if m.is_empty()));
+    }
+
+    #[test]
+    fn test_auth_messages() {
+        assert!(matches!(
+            round_trip(json!(["AUTH", "challenge-string"])),
+            NostrMessage::AuthChallenge(ref c) if c == "challenge-string"
+        ));
+        assert!(matches!(
+            round_trip(json!(["AUTH", event_json()])),
+            NostrMessage::Auth(_)
+        ));
+    }
+
+    #[test]
+    fn test_count_messages() {
+        assert!(matches!(
+            round_trip(json!(["COUNT", "q1", {"kinds": [3], "#p": ["c".repeat(64)]}])),
+            NostrMessage::CountRequest(ref id, ref f) if id == "q1" && f.len() == 1
+        ));
+        match round_trip(json!(["COUNT", "q1", {"count": 238}])) {
+            NostrMessage::Count(_, result) => {
+                assert_eq!(result.count, 238);
+                assert_eq!(result.approximate, None);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match round_trip(json!(["COUNT", "q1", {"count": 93412452, "approximate": true}])) {
+            NostrMessage::Count(_, result) => assert_eq!(result.approximate, Some(true)),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_malformed_messages() {
+        assert!(NostrMessage::parse("not json").is_err());
+        assert!(NostrMessage::parse("[]").is_err());
+        assert!(NostrMessage::parse(r#"{"EVENT": 1}"#).is_err());
+        assert!(NostrMessage::parse(r#"[1, "x"]"#).is_err());
+        assert!(NostrMessage::parse(r#"["BOGUS", "x"]"#).is_err());
+        assert!(NostrMessage::parse(r#"["EOSE"]"#).is_err());
+        assert!(NostrMessage::parse(r#"["EVENT", "sub1", {"id": 5}]"#).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! "Since last seen" watermarks for resumed subscriptions
+//!
+//! Sending a subscription's original filter again after a restart makes
+//! every relay replay its whole history. Instead the highest `created_at`
+//! seen from each relay is recorded per filter in the `relay_watermarks`
+//! table, and a later subscription with the same filter asks that relay only
+//! for events since the watermark.
+//!
+//! The watermark is moved back by [`WATERMARK_OVERLAP_SECS`] when applied.
+//! `created_at` has one-second granularity and events reach relays late or
+//! with skewed clocks, so starting exactly at the watermark could miss
+//! events on the boundary. The overlap costs a few re-delivered events.
+//!
+//! While a relay is still sending stored events the watermark is only
+//! tracked in memory and saved at EOSE. After that live events move it
+//! forward in memory and it is saved at most every
+//! [`LIVE_SAVE_INTERVAL`], and once more when tracking stops, so a busy
+//! feed does not cost a database write per event.
+//!
+//! `created_at` is chosen by the event's author. An event dated more than
+//! [`MAX_FUTURE_SKEW_SECS`] ahead of our clock is not counted, or a single
+//! one could push the watermark past every event still to come.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use super::types::Filter;
+
+/// Seconds subtracted from a watermark before using it as `since`
+pub const WATERMARK_OVERLAP_SECS: i64 = 60;
+
+/// How far ahead of our clock an event's `created_at` may be and still count
+pub const MAX_FUTURE_SKEW_SECS: i64 = 15 * 60;
+
+/// Minimum time between saves of a live watermark
+pub const LIVE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Persistent watermark storage
+///
+/// Implementations log failures instead of returning them: a lost
+/// watermark only means history is fetched again.
+pub trait WatermarkStore: Send + Sync {
+    /// Highest `created_at` saved for `filter_key` on `relay_url`
+    fn load(&self, filter_key: &str, relay_url: &str) -> Option<i64>;
+
+    /// Raise the saved watermark to `created_at` (never lowers it)
+    fn save(&self, filter_key: &str, relay_url: &str, created_at: i64);
+}
+
+/// Stable key identifying a subscription by its filters
+///
+/// `since` is left out so a resumed subscription keeps the key of the
+/// original one.
+pub fn filter_key(filters: &[Filter]) -> String {
+    let mut hasher = Sha256::new();
+    for filter in filters {
+        let filter = Filter {
+            since: None,
+            ..filter.clone()
+        };
+        let json = serde_json::to_string(&filter).unwrap_or_default();
+        hasher.update(json.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Whether filters describe an open-ended feed that can be resumed
+///
+/// Lookups by id and bounded (`until`) queries are fetched in full every
+/// time.
+pub fn is_resumable(filters: &[Filter]) -> bool {
+    !filters.is_empty()
+        && filters
+            .iter()
+            .all(|filter| filter.ids.is_none() && filter.until.is_none())
+}
+
+/// Filters asking only for events since `watermark`, less the overlap
+///
+/// A `since` already later than that is kept.
+pub fn resume_filters(filters: &[Filter], watermark: i64) -> Vec<Filter> {
+    let since = watermark.saturating_sub(WATERMARK_OVERLAP_SECS).max(0);
+    filters
+        .iter()
+        .map(|filter| Filter {
+            since: Some(filter.since.map_or(since, |s| s.max(since))),
+            ..filter.clone()
+        })
+        .collect()
+}
+
+/// Load a saved watermark from the database
+pub fn load_watermark(
+    conn: &Connection,
+    filter_key: &str,
+    relay_url: &str,
+) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT created_at FROM relay_watermarks WHERE filter_key = ?1 AND relay_url = ?2",
+        rusqlite::params![filter_key, relay_url],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Query failed: {e}"))
+}
+
+/// Save a watermark; a lower value than the one stored is ignored
+pub fn save_watermark(
+    conn: &Connection,
+    filter_key: &str,
+    relay_url: &str,
+    created_at: i64,
+) -> Result<(), String> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    conn.execute(
+        "INSERT INTO relay_watermarks (filter_key, relay_url, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (filter_key, relay_url) DO UPDATE SET
+             created_at = max(created_at, excluded.created_at),
+             updated_at = excluded.updated_at",
+        rusqlite::params![filter_key, relay_url, created_at, now],
+    )
+    .map_err(|e| format!("Insert failed: {e}"))?;
+    Ok(())
+}
+
+/// Watermark tracking for one relay of one subscription
+pub struct RelayWatermark {
+    store: Arc<dyn WatermarkStore>,
+    filter_key: String,
+    relay_url: String,
+    /// Highest `created_at` received so far
+    seen: i64,
+    /// Highest `created_at` written to the store
+    saved: i64,
+    /// Whether EOSE has been received
+    live: bool,
+    /// When the watermark was last written to the store
+    last_save: Option<Instant>,
+}
+
+impl RelayWatermark {
+    pub fn new(
+        store: Arc<dyn WatermarkStore>,
+        filter_key: String,
+        relay_url: String,
+        saved: Option<i64>,
+    ) -> Self {
+        let saved = saved.unwrap_or(0);
+        Self {
+            store,
+            filter_key,
+            relay_url,
+            seen: saved,
+            saved,
+            live: false,
+            last_save: None,
+        }
+    }
+
+    /// Record an event's `created_at`
+    ///
+    /// Ignored if it lies more than [`MAX_FUTURE_SKEW_SECS`] in the future.
+    pub fn observe(&mut self, created_at: i64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        if created_at > now.saturating_add(MAX_FUTURE_SKEW_SECS) {
+            log::debug!(
+                "Not advancing watermark for {} to future created_at {}",
+                self.relay_url,
+                created_at
+            );
+            return;
+        }
+
+        self.seen = self.seen.max(created_at);
+        let due = self
+            .last_save
+            .map_or(true, |at| at.elapsed() >= LIVE_SAVE_INTERVAL);
+        if self.live && due {
+            self.save();
+        }
+    }
+
+    /// Stored events are complete; save and keep saving from now on
+    pub fn end_of_stored_events(&mut self) {
+        self.live = true;
+        self.save();
+    }
+
+    fn save(&mut self) {
+        if self.seen > self.saved {
+            self.store
+                .save(&self.filter_key, &self.relay_url, self.seen);
+            self.saved = self.seen;
+            self.last_save = Some(Instant::now());
+        }
+    }
+}
+
+impl Drop for RelayWatermark {
+    /// Save what live events moved since the last save
+    fn drop(&mut self) {
+        if self.live {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::memory_watermarks;
+
+    #[test]
+    fn test_filter_key_ignores_since() {
+        let filters = vec![Filter::new().kinds(vec![1]).authors(vec!["a".repeat(64)])];
+        let resumed = resume_filters(&filters, 1_700_000_000);
+        assert_eq!(filter_key(&filters), filter_key(&resumed));
+        assert_ne!(
+            filter_key(&filters),
+            filter_key(&[Filter::new().kinds(vec![7])])
+        );
+    }
+
+    #[test]
+    fn test_resume_applies_overlap() {
+        let filters = vec![Filter::new().kinds(vec![1]), Filter::new().since(50)];
+        let resumed = resume_filters(&filters, 1_000);
+        assert_eq!(resumed[0].since, Some(1_000 - WATERMARK_OVERLAP_SECS));
+        assert_eq!(resumed[1].since, Some(1_000 - WATERMARK_OVERLAP_SECS));
+
+        // An event created in the watermark's second still matches
+        assert!(resumed[0].since.unwrap() <= 1_000);
+
+        // A later explicit since wins; the overlap never goes negative
+        let resumed = resume_filters(&[Filter::new().since(5_000)], 1_000);
+        assert_eq!(resumed[0].since, Some(5_000));
+        let resumed = resume_filters(&filters, 10);
+        assert_eq!(resumed[0].since, Some(0));
+    }
+
+    #[test]
+    fn test_only_open_ended_filters_resume() {
+        assert!(is_resumable(&[Filter::new().kinds(vec![1])]));
+        assert!(!is_resumable(&[]));
+        assert!(!is_resumable(&[
+            Filter::new().kinds(vec![1]),
+            Filter::new().until(100)
+        ]));
+        assert!(!is_resumable(&[Filter::new().ids(vec!["a".repeat(64)])]));
+    }
+
+    #[test]
+    fn test_watermarks_persist_and_never_lower() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::schema::run_migrations(&mut conn).unwrap();
+
+        assert_eq!(load_watermark(&conn, "key", "wss://a").unwrap(), None);
+        save_watermark(&conn, "key", "wss://a", 200).unwrap();
+        save_watermark(&conn, "key", "wss://a", 100).unwrap();
+        save_watermark(&conn, "key", "wss://b", 50).unwrap();
+        assert_eq!(load_watermark(&conn, "key", "wss://a").unwrap(), Some(200));
+        assert_eq!(load_watermark(&conn, "key", "wss://b").unwrap(), Some(50));
+        assert_eq!(load_watermark(&conn, "other", "wss://a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_relay_watermark_saves_from_eose() {
+        let store = memory_watermarks();
+        let mut watermark = RelayWatermark::new(
+            Arc::clone(&store) as Arc<dyn WatermarkStore>,
+            "key".to_string(),
+            "wss://a".to_string(),
+            None,
+        );
+
+        watermark.observe(300);
+        watermark.observe(100);
+        assert_eq!(store.load("key", "wss://a"), None);
+
+        watermark.end_of_stored_events();
+        assert_eq!(store.load("key", "wss://a"), Some(300));
+
+        // Live events are saved in batches, and when tracking stops
+        watermark.observe(400);
+        assert_eq!(store.load("key", "wss://a"), Some(300));
+        drop(watermark);
+        assert_eq!(store.load("key", "wss://a"), Some(400));
+    }
+
+    #[test]
+    fn test_relay_watermark_ignores_future_events() {
+        let store = memory_watermarks();
+        let mut watermark = RelayWatermark::new(
+            Arc::clone(&store) as Arc<dyn WatermarkStore>,
+            "key".to_string(),
+            "wss://a".to_string(),
+            Some(1_000),
+        );
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        watermark.observe(now + MAX_FUTURE_SKEW_SECS + 60);
+        watermark.observe(i64::MAX);
+        watermark.end_of_stored_events();
+        assert_eq!(store.load("key", "wss://a"), None);
+
+        // Ordinary clock skew still counts
+        watermark.observe(now + 60);
+        drop(watermark);
+        assert_eq!(store.load("key", "wss://a"), Some(now + 60));
+    }
+}
@@ -0,0 +1,508 @@
+//! System notifications for backend events
+//!
+//! A [`NotificationPolicy`] picks which BLE and relay events raise a system
+//! notification, and [`Notifier`] applies it: each category is rate limited
+//! on its own, so a burst of messages cannot hide a duress alert, and only
+//! duress alerts are shown during quiet hours. Notification text never
+//! contains message content, public keys or addresses, since it may be shown
+//! on a locked screen.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::ble::manager::{BleEvent, BleEventReceiver};
+use crate::nostr::relay::MANUAL_DISCONNECT_REASON;
+use crate::nostr::RelayEvent;
+
+/// Frontend event reporting that a duress alert was received
+pub const DURESS_ALERT_EVENT: &str = "duress-alert-received";
+
+/// NIP-59 gift wrap, the envelope of every private message
+const KIND_GIFT_WRAP: i32 = 1059;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// Largest UTC offset in use (UTC+14)
+const MAX_UTC_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// Kind of event that can raise a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    /// A BLE peer completed the identity handshake
+    PeerAuthenticated,
+    /// A mesh message or a gift-wrapped relay message arrived
+    MessageReceived,
+    /// A relay closed the connection
+    RelayDisconnected,
+    /// A contact's duress alert arrived; not silenced by quiet hours
+    DuressAlert,
+}
+
+impl NotificationCategory {
+    const ALL: [Self; 4] = [
+        Self::PeerAuthenticated,
+        Self::MessageReceived,
+        Self::RelayDisconnected,
+        Self::DuressAlert,
+    ];
+
+    /// Category of a BLE event, if it can raise a notification
+    pub fn from_ble_event(event: &BleEvent) -> Option<Self> {
+        match event {
+            BleEvent::HandshakeCompleted { .. } => Some(Self::PeerAuthenticated),
+            BleEvent::MessageReceived { .. } => Some(Self::MessageReceived),
+            _ => None,
+        }
+    }
+
+    /// Category of a relay event, if it can raise a notification
+    ///
+    /// Disconnects we asked for ourselves are not reported.
+    pub fn from_relay_event(event: &RelayEvent) -> Option<Self> {
+        match event {
+            RelayEvent::Event { event, .. } if event.kind == KIND_GIFT_WRAP => {
+                Some(Self::MessageReceived)
+            }
+            RelayEvent::Disconnected { reason, .. } if reason != MANUAL_DISCONNECT_REASON => {
+                Some(Self::RelayDisconnected)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A system notification ready to be shown
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Notification {
+    pub category: NotificationCategory,
+    pub title: &'static str,
+    pub body: &'static str,
+}
+
+impl Notification {
+    fn for_category(category: NotificationCategory) -> Self {
+        let (title, body) = match category {
+            NotificationCategory::PeerAuthenticated => {
+                ("Peer verified", "A nearby device completed verification")
+            }
+            NotificationCategory::MessageReceived => ("New message", "You have a new message"),
+            NotificationCategory::RelayDisconnected => {
+                ("Relay disconnected", "A relay connection was lost")
+            }
+            NotificationCategory::DuressAlert => ("Duress alert", "A contact sent a duress alert"),
+        };
+        Self {
+            category,
+            title,
+            body,
+        }
+    }
+}
+
+/// Daily window in which notifications are held back
+///
+/// Times are minutes after local midnight. A window whose end is before its
+/// start spans midnight; equal start and end make an empty window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start_minute: u16,
+    pub end_minute: u16,
+    /// Local offset from UTC in minutes, as reported by the frontend
+    pub utc_offset_minutes: i32,
+}
+
+impl QuietHours {
+    /// Check the window before it is stored
+    pub fn validate(&self) -> Result<(), String> {
+        let last_minute = (MINUTES_PER_DAY - 1) as u16;
+        if self.start_minute > last_minute || self.end_minute > last_minute {
+            return Err(format!(
+                "Invalid quiet hours: minutes must be from 0 to {last_minute}"
+            ));
+        }
+        if self.utc_offset_minutes.abs() > MAX_UTC_OFFSET_MINUTES {
+            return Err(format!(
+                "Invalid quiet hours: UTC offset must be within +/-{MAX_UTC_OFFSET_MINUTES} minutes"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `at` falls inside the window
+    pub fn contains(&self, at: SystemTime) -> bool {
+        let utc_minutes = at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| (d.as_secs() / 60) as i64)
+            .unwrap_or(0);
+        let minute = (utc_minutes + i64::from(self.utc_offset_minutes)).rem_euclid(MINUTES_PER_DAY);
+        let (start, end) = (i64::from(self.start_minute), i64::from(self.end_minute));
+        if start <= end {
+            (start..end).contains(&minute)
+        } else {
+            minute >= start || minute < end
+        }
+    }
+}
+
+/// User configuration for system notifications
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationPolicy {
+    /// Categories that raise a notification
+    pub categories: BTreeSet<NotificationCategory>,
+    pub quiet_hours: Option<QuietHours>,
+    /// Notifications allowed per category within `rate_limit_window_secs`
+    pub rate_limit: u32,
+    pub rate_limit_window_secs: u64,
+}
+
+impl Default for NotificationPolicy {
+    fn default() -> Self {
+        Self {
+            categories: NotificationCategory::ALL.into_iter().collect(),
+            quiet_hours: None,
+            rate_limit: 3,
+            rate_limit_window_secs: 60,
+        }
+    }
+}
+
+impl NotificationPolicy {
+    /// Check the policy before it is applied
+    pub fn validate(&self) -> Result<(), String> {
+        if self.rate_limit == 0 || self.rate_limit_window_secs == 0 {
+            return Err(
+                "Invalid notification rate limit: count and window must be positive".to_string(),
+            );
+        }
+        self.quiet_hours
+            .as_ref()
+            .map_or(Ok(()), QuietHours::validate)
+    }
+
+    fn rate_limit_window(&self) -> Duration {
+        Duration::from_secs(self.rate_limit_window_secs)
+    }
+}
+
+/// Applies a [`NotificationPolicy`] to a stream of events
+#[derive(Debug, Default)]
+pub struct Notifier {
+    policy: NotificationPolicy,
+    /// When each category last notified, oldest first
+    recent: HashMap<NotificationCategory, VecDeque<Instant>>,
+}
+
+impl Notifier {
+    pub fn new(policy: NotificationPolicy) -> Self {
+        Self {
+            policy,
+            recent: HashMap::new(),
+        }
+    }
+
+    pub fn policy(&self) -> &NotificationPolicy {
+        &self.policy
+    }
+
+    /// Replace the policy; notifications already shown still count against
+    /// the new rate limit
+    pub fn set_policy(&mut self, policy: NotificationPolicy) {
+        self.policy = policy;
+    }
+
+    /// Decide whether an event of `category` raises a notification
+    ///
+    /// `now` drives the rate limit and `wall_clock` the quiet hours. Events
+    /// held back by quiet hours do not use up the rate limit.
+    pub fn decide(
+        &mut self,
+        category: NotificationCategory,
+        now: Instant,
+        wall_clock: SystemTime,
+    ) -> Option<Notification> {
+        if !self.policy.categories.contains(&category) {
+            return None;
+        }
+        if category != NotificationCategory::DuressAlert
+            && self
+                .policy
+                .quiet_hours
+                .is_some_and(|quiet| quiet.contains(wall_clock))
+        {
+            return None;
+        }
+
+        let window = self.policy.rate_limit_window();
+        let recent = self.recent.entry(category).or_default();
+        while recent
+            .front()
+            .is_some_and(|shown| now.saturating_duration_since(*shown) >= window)
+        {
+            recent.pop_front();
+        }
+        if recent.len() >= self.policy.rate_limit as usize {
+            return None;
+        }
+        recent.push_back(now);
+
+        Some(Notification::for_category(category))
+    }
+}
+
+/// Shows a notification to the user
+pub type NotificationSink = Arc<dyn Fn(Notification) + Send + Sync>;
+
+/// Shared notifier fed by the BLE and relay event streams
+#[derive(Default)]
+pub struct Notifications {
+    notifier: Mutex<Notifier>,
+    sink: RwLock<Option<NotificationSink>>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(&self) -> NotificationPolicy {
+        self.notifier.lock().policy().clone()
+    }
+
+    /// Validate and apply `policy`
+    pub fn set_policy(&self, policy: NotificationPolicy) -> Result<(), String> {
+        policy.validate()?;
+        self.notifier.lock().set_policy(policy);
+        Ok(())
+    }
+
+    /// Set where notifications are shown; until then they are dropped
+    pub fn set_sink(&self, sink: NotificationSink) {
+        *self.sink.write() = Some(sink);
+    }
+
+    /// Raise a notification for `category` if the policy allows it,
+    /// returning whether one was shown
+    pub fn notify(&self, category: NotificationCategory) -> bool {
+        let Some(notification) =
+            self.notifier
+                .lock()
+                .decide(category, Instant::now(), SystemTime::now())
+        else {
+            return false;
+        };
+        let Some(sink) = self.sink.read().clone() else {
+            return false;
+        };
+        sink(notification);
+        true
+    }
+
+    /// Notify for BLE events until the manager goes away
+    pub async fn watch_ble(self: Arc<Self>, mut events: BleEventReceiver) {
+        while let Some(event) = events.recv().await {
+            if let Some(category) = NotificationCategory::from_ble_event(&event) {
+                self.notify(category);
+            }
+        }
+    }
+
+    /// Notify for one relay's events until the relay goes away
+    pub async fn watch_relay(self: Arc<Self>, mut events: broadcast::Receiver<RelayEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let Some(category) = NotificationCategory::from_relay_event(&event) {
+                        self.notify(category);
+                    }
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::test_support::test_event;
+
+    const NOON: u64 = 12 * 3600;
+
+    fn at_utc(secs_after_midnight: u64) -> SystemTime {
+        // 2024-01-01T00:00:00Z
+        UNIX_EPOCH + Duration::from_secs(1_704_067_200 + secs_after_midnight)
+    }
+
+    fn quiet(start_minute: u16, end_minute: u16, utc_offset_minutes: i32) -> QuietHours {
+        QuietHours {
+            start_minute,
+            end_minute,
+            utc_offset_minutes,
+        }
+    }
+
+    #[test]
+    fn test_event_categories() {
+        let handshake = BleEvent::HandshakeCompleted {
+            address: "AA:BB".to_string(),
+            pubkey: "ab".repeat(32),
+        };
+        assert_eq!(
+            NotificationCategory::from_ble_event(&handshake),
+            Some(NotificationCategory::PeerAuthenticated)
+        );
+        assert_eq!(
+            NotificationCategory::from_ble_event(&BleEvent::DeviceLost("AA:BB".to_string())),
+            None
+        );
+
+        let dropped = RelayEvent::Disconnected {
+            url: "wss://relay.example".to_string(),
+            reason: "Connection closed by relay".to_string(),
+        };
+        let manual = RelayEvent::Disconnected {
+            url: "wss://relay.example".to_string(),
+            reason: MANUAL_DISCONNECT_REASON.to_string(),
+        };
+        assert_eq!(
+            NotificationCategory::from_relay_event(&dropped),
+            Some(NotificationCategory::RelayDisconnected)
+        );
+        assert_eq!(NotificationCategory::from_relay_event(&manual), None);
+
+        let mut wrap = test_event("1");
+        wrap.kind = KIND_GIFT_WRAP;
+        let message = RelayEvent::Event {
+            subscription_id: "sub".to_string(),
+            event: wrap,
+        };
+        let note = RelayEvent::Event {
+            subscription_id: "sub".to_string(),
+            event: test_event("1"),
+        };
+        assert_eq!(
+            NotificationCategory::from_relay_event(&message),
+            Some(NotificationCategory::MessageReceived)
+        );
+        assert_eq!(NotificationCategory::from_relay_event(&note), None);
+    }
+
+    #[test]
+    fn test_disabled_category_is_silent() {
+        let mut notifier = Notifier::new(NotificationPolicy {
+            categories: [NotificationCategory::DuressAlert].into_iter().collect(),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert_eq!(
+            notifier.decide(NotificationCategory::MessageReceived, now, at_utc(NOON)),
+            None
+        );
+        let shown = notifier
+            .decide(NotificationCategory::DuressAlert, now, at_utc(NOON))
+            .unwrap();
+        assert_eq!(shown.category, NotificationCategory::DuressAlert);
+    }
+
+    #[test]
+    fn test_rate_limit_suppresses_bursts() {
+        let mut notifier = Notifier::new(NotificationPolicy {
+            rate_limit: 2,
+            rate_limit_window_secs: 60,
+            ..Default::default()
+        });
+        let start = Instant::now();
+        let message = NotificationCategory::MessageReceived;
+
+        assert!(notifier.decide(message, start, at_utc(NOON)).is_some());
+        assert!(notifier
+            .decide(message, start + Duration::from_secs(1), at_utc(NOON))
+            .is_some());
+        assert!(notifier
+            .decide(message, start + Duration::from_secs(2), at_utc(NOON))
+            .is_none());
+
+        // A flood of messages does not use up other categories
+        assert!(notifier
+            .decide(NotificationCategory::DuressAlert, start, at_utc(NOON))
+            .is_some());
+
+        // The first notification leaves the window after a minute
+        assert!(notifier
+            .decide(message, start + Duration::from_secs(60), at_utc(NOON))
+            .is_some());
+        assert!(notifier
+            .decide(message, start + Duration::from_secs(61), at_utc(NOON))
+            .is_none());
+    }
+
+    #[test]
+    fn test_quiet_hours_filter() {
+        // 22:00 to 07:00 local time at UTC+2
+        let mut notifier = Notifier::new(NotificationPolicy {
+            quiet_hours: Some(quiet(22 * 60, 7 * 60, 120)),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let message = NotificationCategory::MessageReceived;
+
+        // 21:00 UTC is 23:00 local
+        assert!(notifier.decide(message, now, at_utc(21 * 3600)).is_none());
+        // 04:59 UTC is 06:59 local, 05:00 UTC is 07:00 local
+        assert!(notifier
+            .decide(message, now, at_utc(4 * 3600 + 59 * 60))
+            .is_none());
+        assert!(notifier.decide(message, now, at_utc(5 * 3600)).is_some());
+        // 19:59 UTC is 21:59 local
+        assert!(notifier
+            .decide(message, now, at_utc(19 * 3600 + 59 * 60))
+            .is_some());
+
+        // Duress alerts are never held back
+        assert!(notifier
+            .decide(NotificationCategory::DuressAlert, now, at_utc(21 * 3600))
+            .is_some());
+    }
+
+    #[test]
+    fn test_quiet_hours_do_not_use_up_rate_limit() {
+        let mut notifier = Notifier::new(NotificationPolicy {
+            quiet_hours: Some(quiet(0, 60, 0)),
+            rate_limit: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let message = NotificationCategory::MessageReceived;
+
+        for minute in 0..5 {
+            assert!(notifier.decide(message, now, at_utc(minute * 60)).is_none());
+        }
+        assert!(notifier.decide(message, now, at_utc(3600)).is_some());
+    }
+
+    #[test]
+    fn test_policy_validation() {
+        assert!(NotificationPolicy::default().validate().is_ok());
+        assert!(quiet(23 * 60, 6 * 60, -300).validate().is_ok());
+        assert!(quiet(24 * 60, 0, 0).validate().is_err());
+        assert!(quiet(0, 60, 15 * 60).validate().is_err());
+
+        let no_limit = NotificationPolicy {
+            rate_limit: 0,
+            ..Default::default()
+        };
+        assert!(no_limit.validate().is_err());
+
+        let notifications = Notifications::new();
+        assert!(notifications.set_policy(no_limit).is_err());
+        assert_eq!(notifications.policy(), NotificationPolicy::default());
+    }
+}
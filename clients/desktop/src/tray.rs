@@ -3,11 +3,15 @@
 //! Provides:
 //! - System tray icon
 //! - Quick actions menu
-//! - Notification badge (overlay dot on tray icon)
+//! - Notification badge (overlay dot on tray icon, unread count where supported)
+//! - Lock state (dimmed icon and tooltip while the database is locked)
 //! - Badge state tracking and auto-clear on app focus
+//!
+//! Platform notes: the unread count is shown as tray title text on macOS
+//! (menu bar) and Linux (appindicator label). Windows has no tray title, so
+//! the count is only available in the tooltip.
 
-use std::sync::atomic::{AtomicBool, Ordering};
-
+use parking_lot::Mutex;
 use tauri::{
     image::Image,
     menu::{Menu, MenuItem, PredefinedMenuItem, Submenu},
@@ -18,8 +22,108 @@ use tauri::{
 /// Tray icon identifier constant
 const TRAY_ID: &str = "buildit-tray";
 
-/// Global badge state: true when there are unread notifications
-static HAS_BADGE: AtomicBool = AtomicBool::new(false);
+use crate::db::idle::DB_AUTO_LOCKED_EVENT;
+use crate::db::DB_LOCK_STATE_EVENT;
+
+/// Base tooltip text
+const TOOLTIP: &str = "BuildIt Network";
+
+/// Largest unread count shown verbatim; higher counts render as "99+"
+const MAX_DISPLAYED_UNREAD: u32 = 99;
+
+/// Unread notification badge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnreadBadge {
+    None,
+    /// Unread notifications of unknown count
+    Unread,
+    Count(u32),
+}
+
+impl UnreadBadge {
+    /// Parse a `notification-badge` payload: a JSON boolean or unread count
+    pub fn from_payload(payload: &str) -> Self {
+        let value = payload.trim().trim_matches('"');
+        match value {
+            "true" => UnreadBadge::Unread,
+            "false" => UnreadBadge::None,
+            _ => match value.parse::<u32>() {
+                Ok(0) | Err(_) => UnreadBadge::None,
+                Ok(n) => UnreadBadge::Count(n),
+            },
+        }
+    }
+
+    fn is_set(self) -> bool {
+        self != UnreadBadge::None
+    }
+}
+
+/// Everything the tray reflects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrayState {
+    /// Database closed (app locked)
+    pub locked: bool,
+    pub unread: UnreadBadge,
+}
+
+/// How the tray should look for a given state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrayAppearance {
+    /// Render the icon dimmed to indicate the locked state
+    pub dimmed: bool,
+    /// Draw the red notification dot
+    pub badge: bool,
+    pub tooltip: String,
+    /// Text next to the icon (macOS/Linux only)
+    pub title: Option<String>,
+}
+
+/// Map tray state to its icon and tooltip
+///
+/// While locked the unread count is withheld, so a glance at the tray of a
+/// locked machine only reveals that something arrived.
+pub fn tray_appearance(state: &TrayState) -> TrayAppearance {
+    let badge = state.unread.is_set();
+    if state.locked {
+        let tooltip = if badge {
+            format!("{TOOLTIP} - Locked (new notifications)")
+        } else {
+            format!("{TOOLTIP} - Locked")
+        };
+        return TrayAppearance {
+            dimmed: true,
+            badge,
+            tooltip,
+            title: None,
+        };
+    }
+
+    let (tooltip, title) = match state.unread {
+        UnreadBadge::None => (TOOLTIP.to_string(), None),
+        UnreadBadge::Unread => (format!("{TOOLTIP} - New notifications"), None),
+        UnreadBadge::Count(n) => {
+            let shown = if n > MAX_DISPLAYED_UNREAD {
+                format!("{MAX_DISPLAYED_UNREAD}+")
+            } else {
+                n.to_string()
+            };
+            (format!("{TOOLTIP} - {shown} unread"), Some(shown))
+        }
+    };
+    TrayAppearance {
+        dimmed: false,
+        badge,
+        tooltip,
+        title,
+    }
+}
+
+/// Global tray state (the database starts locked)
+static TRAY_STATE: Mutex<TrayState> = parking_lot::const_mutex(TrayState {
+    locked: true,
+    unread: UnreadBadge::None,
+});
 
 /// Set up the system tray
 pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
@@ -31,18 +135,14 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
     // Quick actions submenu
     let scan_ble = MenuItem::with_id(app, "scan_ble", "Scan for Devices", true, None::<&str>)?;
     let stop_scan = MenuItem::with_id(app, "stop_scan", "Stop Scanning", true, None::<&str>)?;
-    let quick_actions = Submenu::with_items(
-        app,
-        "Quick Actions",
-        true,
-        &[&scan_ble, &stop_scan],
-    )?;
+    let quick_actions = Submenu::with_items(app, "Quick Actions", true, &[&scan_ble, &stop_scan])?;
 
     // Status submenu
     let status_online = MenuItem::with_id(app, "status_online", "Online", true, None::<&str>)?;
     let status_away = MenuItem::with_id(app, "status_away", "Away", true, None::<&str>)?;
     let status_dnd = MenuItem::with_id(app, "status_dnd", "Do Not Disturb", true, None::<&str>)?;
-    let status_invisible = MenuItem::with_id(app, "status_invisible", "Invisible", true, None::<&str>)?;
+    let status_invisible =
+        MenuItem::with_id(app, "status_invisible", "Invisible", true, None::<&str>)?;
     let status_menu = Submenu::with_items(
         app,
         "Status",
@@ -78,7 +178,7 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
         .icon(app.default_window_icon().unwrap().clone())
         .menu(&menu)
         .menu_on_left_click(false)
-        .tooltip("BuildIt Network")
+        .tooltip(tray_appearance(&TRAY_STATE.lock()).tooltip)
         .on_menu_event(move |app, event| {
             handle_menu_event(app, event.id.as_ref());
         })
@@ -97,21 +197,19 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
                 }
 
                 // Clear the badge when the user clicks the tray to focus the app
-                if HAS_BADGE.load(Ordering::Relaxed) {
-                    let _ = clear_badge(app);
-                }
+                let _ = clear_badge(app);
             }
         })
         .build(app)?;
+    apply_tray_state(app.handle())?;
 
     // Listen for notification badge events from the frontend
+    // Payload is a JSON boolean ("true"/"false") or an unread count
     let handle = app.handle().clone();
     app.listen("notification-badge", move |event| {
-        let payload = event.payload();
-        // Payload is a JSON boolean string: "true" or "false"
-        let has_notifications = payload.trim().trim_matches('"') == "true";
-        if has_notifications {
-            let _ = set_badge(&handle);
+        let badge = UnreadBadge::from_payload(event.payload());
+        if badge.is_set() {
+            let _ = set_badge(&handle, badge);
         } else {
             let _ = clear_badge(&handle);
         }
@@ -120,9 +218,18 @@ pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
     // Listen for window focus events to clear badge
     let handle2 = app.handle().clone();
     app.listen("tauri://focus", move |_event| {
-        if HAS_BADGE.load(Ordering::Relaxed) {
-            let _ = clear_badge(&handle2);
-        }
+        let _ = clear_badge(&handle2);
+    });
+
+    // Follow database lock state (payload: true when locked)
+    let handle3 = app.handle().clone();
+    app.listen(DB_LOCK_STATE_EVENT, move |event| {
+        let locked = event.payload().trim() == "true";
+        let _ = set_tray_locked(&handle3, locked);
+    });
+    let handle4 = app.handle().clone();
+    app.listen(DB_AUTO_LOCKED_EVENT, move |_event| {
+        let _ = set_tray_locked(&handle4, true);
     });
 
     log::info!("System tray initialized");
@@ -181,39 +288,68 @@ fn handle_menu_event<R: Runtime>(app: &tauri::AppHandle<R>, menu_id: &str) {
     }
 }
 
-/// Set the notification badge on the tray icon by compositing a red dot overlay
-/// onto the default app icon.
-fn set_badge<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
-    if HAS_BADGE.swap(true, Ordering::Relaxed) {
-        // Badge already set, no need to update the icon again
-        return Ok(());
+/// Push the current tray state to the platform tray icon
+fn apply_tray_state<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let appearance = tray_appearance(&TRAY_STATE.lock());
+
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        tray.set_icon(Some(create_tray_icon(app, &appearance)?))?;
+        tray.set_tooltip(Some(&appearance.tooltip))?;
+        // Windows has no tray title; the count stays in the tooltip there
+        #[cfg(any(target_os = "macos", target_os = "linux"))]
+        tray.set_title(appearance.title.as_deref())?;
     }
+    Ok(())
+}
 
-    let badge_icon = create_badge_icon(app)?;
+/// Update the tray state, re-rendering only when it actually changed
+fn update_state<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    update: impl FnOnce(&mut TrayState),
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let changed = {
+        let mut state = TRAY_STATE.lock();
+        let before = *state;
+        update(&mut state);
+        *state != before
+    };
+    if changed {
+        apply_tray_state(app)?;
+    }
+    Ok(changed)
+}
 
-    if let Some(tray) = app.tray_by_id(TRAY_ID) {
-        tray.set_icon(Some(badge_icon))?;
-        tray.set_tooltip(Some("BuildIt Network - New notifications"))?;
+/// Reflect the database lock state in the tray
+pub fn set_tray_locked<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    locked: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if update_state(app, |state| state.locked = locked)? {
+        log::debug!("Tray lock state set: locked={}", locked);
     }
+    Ok(())
+}
 
-    log::debug!("Tray badge set");
+/// Set the notification badge on the tray icon
+fn set_badge<R: Runtime>(
+    app: &tauri::AppHandle<R>,
+    badge: UnreadBadge,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if update_state(app, |state| state.unread = badge)? {
+        log::debug!("Tray badge set");
+    }
     Ok(())
 }
 
-/// Clear the notification badge from the tray icon, restoring the default icon.
+/// Clear the notification badge from the tray icon
 fn clear_badge<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
-    if !HAS_BADGE.swap(false, Ordering::Relaxed) {
+    if !update_state(app, |state| state.unread = UnreadBadge::None)? {
         // Badge already cleared
         return Ok(());
     }
 
-    if let Some(tray) = app.tray_by_id(TRAY_ID) {
-        if let Some(default_icon) = app.default_window_icon() {
-            tray.set_icon(Some(default_icon.clone()))?;
-        }
-        tray.set_tooltip(Some("BuildIt Network"))?;
-    }
-
     // Notify the frontend that the badge was cleared
     let _ = app.emit("badge-cleared", ());
 
@@ -221,10 +357,12 @@ fn clear_badge<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<(), Box<dyn std:
     Ok(())
 }
 
-/// Create a tray icon with a notification badge overlay (red dot in the top-right corner).
-/// Takes the default app icon and draws a filled red circle on it.
-fn create_badge_icon<R: Runtime>(
+/// Render the tray icon for an appearance from the default app icon.
+/// Locked: grayscale at reduced opacity. Badge: filled red circle in the
+/// top-right corner.
+fn create_tray_icon<R: Runtime>(
     app: &tauri::AppHandle<R>,
+    appearance: &TrayAppearance,
 ) -> Result<Image<'static>, Box<dyn std::error::Error>> {
     let default_icon = app
         .default_window_icon()
@@ -237,6 +375,21 @@ fn create_badge_icon<R: Runtime>(
 
     let mut pixels = rgba;
 
+    if appearance.dimmed {
+        for px in pixels.chunks_exact_mut(4) {
+            let luma =
+                (u32::from(px[0]) * 299 + u32::from(px[1]) * 587 + u32::from(px[2]) * 114) / 1000;
+            px[0] = luma as u8;
+            px[1] = luma as u8;
+            px[2] = luma as u8;
+            px[3] /= 2;
+        }
+    }
+
+    if !appearance.badge {
+        return Ok(Image::new_owned(pixels, width, height));
+    }
+
     // Draw a red badge dot in the top-right corner
     // Badge is approximately 25% of the icon size, positioned at top-right
     let badge_radius = (width.min(height) as f64 * 0.15).max(2.0) as u32;
@@ -277,7 +430,7 @@ pub fn update_tray_icon<R: Runtime>(
     has_notifications: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     if has_notifications {
-        set_badge(app)
+        set_badge(app, UnreadBadge::Unread)
     } else {
         clear_badge(app)
     }
@@ -294,3 +447,57 @@ pub fn update_tray_tooltip<R: Runtime>(
     log::debug!("Tray tooltip update requested: {}", tooltip);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(locked: bool, unread: UnreadBadge) -> TrayState {
+        TrayState { locked, unread }
+    }
+
+    #[test]
+    fn test_unlocked_appearance() {
+        let idle = tray_appearance(&state(false, UnreadBadge::None));
+        assert!(!idle.dimmed && !idle.badge);
+        assert_eq!(idle.tooltip, "BuildIt Network");
+        assert_eq!(idle.title, None);
+
+        let unread = tray_appearance(&state(false, UnreadBadge::Unread));
+        assert!(unread.badge);
+        assert_eq!(unread.tooltip, "BuildIt Network - New notifications");
+        assert_eq!(unread.title, None);
+
+        let counted = tray_appearance(&state(false, UnreadBadge::Count(3)));
+        assert!(counted.badge);
+        assert_eq!(counted.tooltip, "BuildIt Network - 3 unread");
+        assert_eq!(counted.title.as_deref(), Some("3"));
+
+        let many = tray_appearance(&state(false, UnreadBadge::Count(250)));
+        assert_eq!(many.title.as_deref(), Some("99+"));
+    }
+
+    #[test]
+    fn test_locked_appearance_hides_count() {
+        let locked = tray_appearance(&state(true, UnreadBadge::None));
+        assert!(locked.dimmed && !locked.badge);
+        assert_eq!(locked.tooltip, "BuildIt Network - Locked");
+
+        let locked_unread = tray_appearance(&state(true, UnreadBadge::Count(7)));
+        assert!(locked_unread.dimmed && locked_unread.badge);
+        assert_eq!(
+            locked_unread.tooltip,
+            "BuildIt Network - Locked (new notifications)"
+        );
+        assert_eq!(locked_unread.title, None);
+    }
+
+    #[test]
+    fn test_badge_payload_parsing() {
+        assert_eq!(UnreadBadge::from_payload("true"), UnreadBadge::Unread);
+        assert_eq!(UnreadBadge::from_payload("\"false\""), UnreadBadge::None);
+        assert_eq!(UnreadBadge::from_payload("12"), UnreadBadge::Count(12));
+        assert_eq!(UnreadBadge::from_payload("0"), UnreadBadge::None);
+        assert_eq!(UnreadBadge::from_payload("garbage"), UnreadBadge::None);
+    }
+}
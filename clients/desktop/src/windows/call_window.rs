@@ -74,6 +74,7 @@ fn get_window_label(call_id: &str) -> String {
 /// Creates a separate window for the call, similar to Microsoft Teams.
 /// The window loads the call view route with the call ID.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn create_call_window(
     app: AppHandle,
     config: CallWindowConfig,
@@ -126,6 +127,7 @@ pub async fn create_call_window(
 ///
 /// Closes the window associated with the given call ID.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn close_call_window(app: AppHandle, call_id: String) -> Result<(), String> {
     let window_label = get_window_label(&call_id);
 
@@ -145,6 +147,7 @@ pub async fn close_call_window(app: AppHandle, call_id: String) -> Result<(), St
 ///
 /// Resizes the window to a small floating view and enables always-on-top.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn minimize_call_window(app: AppHandle, call_id: String) -> Result<(), String> {
     let window_label = get_window_label(&call_id);
 
@@ -187,6 +190,7 @@ pub async fn minimize_call_window(app: AppHandle, call_id: String) -> Result<(),
 ///
 /// Restores the window to full size and disables always-on-top.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn maximize_call_window(app: AppHandle, call_id: String) -> Result<(), String> {
     let window_label = get_window_label(&call_id);
 
@@ -221,6 +225,7 @@ pub async fn maximize_call_window(app: AppHandle, call_id: String) -> Result<(),
 ///
 /// Allows the user to keep the call window above other windows.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn toggle_call_window_always_on_top(
     app: AppHandle,
     call_id: String,
@@ -249,6 +254,7 @@ pub async fn toggle_call_window_always_on_top(
 ///
 /// Updates the window title (e.g., when participants join/leave).
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn update_call_window_title(
     app: AppHandle,
     call_id: String,
@@ -273,6 +279,7 @@ pub async fn update_call_window_title(
 ///
 /// Brings the call window to the front.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn focus_call_window(app: AppHandle, call_id: String) -> Result<(), String> {
     let window_label = get_window_label(&call_id);
 
@@ -302,6 +309,7 @@ pub async fn focus_call_window(app: AppHandle, call_id: String) -> Result<(), St
 
 /// Check if a call window exists
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn call_window_exists(app: AppHandle, call_id: String) -> Result<bool, String> {
     let window_label = get_window_label(&call_id);
     Ok(app.get_webview_window(&window_label).is_some())
@@ -309,6 +317,7 @@ pub async fn call_window_exists(app: AppHandle, call_id: String) -> Result<bool,
 
 /// Get the state of all active call windows
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn get_call_windows(app: AppHandle) -> Result<Vec<CallWindowState>, String> {
     let mut windows = Vec::new();
 
@@ -340,6 +349,7 @@ pub async fn get_call_windows(app: AppHandle) -> Result<Vec<CallWindowState>, St
 ///
 /// Used when logging out or shutting down.
 #[tauri::command]
+#[tracing::instrument(skip_all, fields(correlation_id = %crate::logging::new_correlation_id()))]
 pub async fn close_all_call_windows(app: AppHandle) -> Result<(), String> {
     let labels: Vec<String> = app
         .webview_windows()
@@ -19,6 +19,13 @@
  * Frontend uses camelCase (JS convention). The Rust backend handles the
  * camelCase↔snake_case conversion transparently. The DAL passes camelCase
  * objects directly to invoke().
+ *
+ * ## Errors
+ *
+ * The Rust `db_*` commands reject with a structured `{ code, message,
+ * retryable }` object rather than a bare string. The DAL rethrows it as a
+ * {@link DatabaseError}, so callers can branch on `code` (e.g. `db_locked`,
+ * `db_conflict`, `invalid_input`) and still read `message` as before.
  */
 
 import type { BuildItDB } from './db';
@@ -60,6 +67,48 @@ export interface CustomQueryOptions<T> {
   dexieFallback: (db: BuildItDB) => Promise<T[] | void>;
 }
 
+/** Structured error rejected by the Rust `db_*` commands */
+interface CommandErrorPayload {
+  code: string;
+  message: string;
+  retryable: boolean;
+}
+
+/** A database command failed; `code` is stable, `message` is for humans */
+export class DatabaseError extends Error {
+  readonly code: string;
+  readonly retryable: boolean;
+
+  constructor(code: string, message: string, retryable = false) {
+    super(message);
+    this.name = 'DatabaseError';
+    this.code = code;
+    this.retryable = retryable;
+  }
+}
+
+function isCommandErrorPayload(value: unknown): value is CommandErrorPayload {
+  return (
+    typeof value === 'object' &&
+    value !== null &&
+    typeof (value as CommandErrorPayload).code === 'string' &&
+    typeof (value as CommandErrorPayload).message === 'string'
+  );
+}
+
+/** Invoke a Rust `db_*` command, rethrowing its rejection as a DatabaseError */
+async function invokeDb<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  try {
+    return await invoke<T>(command, args);
+  } catch (error) {
+    if (isCommandErrorPayload(error)) {
+      throw new DatabaseError(error.code, error.message, error.retryable === true);
+    }
+    // Older backends reject with the bare message
+    throw new DatabaseError('db_error', String(error));
+  }
+}
+
 type ChangeCallback = (event: DataChangeEvent) => void;
 type UnsubscribeFn = () => void;
 
@@ -105,8 +154,7 @@ class DataAccessLayer {
   /** Insert or replace a record */
   async put<T extends object>(table: string, record: T): Promise<void> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      await invoke('db_put', { table, record });
+      await invokeDb('db_put', { table, record });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -117,8 +165,7 @@ class DataAccessLayer {
   /** Get a single record by primary key */
   async get<T>(table: string, key: string | number): Promise<T | undefined> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      const result = await invoke<T | null>('db_get', { table, key: String(key) });
+      const result = await invokeDb<T | null>('db_get', { table, key: String(key) });
       return result ?? undefined;
     } else {
       const { getDB } = await import('./db');
@@ -130,8 +177,7 @@ class DataAccessLayer {
   /** Get all records from a table */
   async getAll<T>(table: string): Promise<T[]> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<T[]>('db_get_all', { table });
+      return invokeDb<T[]>('db_get_all', { table });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -142,8 +188,7 @@ class DataAccessLayer {
   /** Query records with filtering, sorting, and pagination */
   async query<T>(table: string, filter: QueryFilter): Promise<T[]> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<T[]>('db_query', { table, filter });
+      return invokeDb<T[]>('db_query', { table, filter });
     } else {
       // Dexie fallback: basic where/orderBy/limit support
       const { getDB } = await import('./db');
@@ -192,8 +237,7 @@ class DataAccessLayer {
   /** Delete a record by primary key */
   async delete(table: string, key: string | number): Promise<boolean> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<boolean>('db_delete', { table, key: String(key) });
+      return invokeDb<boolean>('db_delete', { table, key: String(key) });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -207,8 +251,7 @@ class DataAccessLayer {
     if (records.length === 0) return 0;
 
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<number>('db_bulk_put', { table, records });
+      return invokeDb<number>('db_bulk_put', { table, records });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -220,8 +263,7 @@ class DataAccessLayer {
   /** Count records, optionally with a filter */
   async count(table: string, filter?: Record<string, unknown>): Promise<number> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<number>('db_count', { table, filter });
+      return invokeDb<number>('db_count', { table, filter });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -245,8 +287,7 @@ class DataAccessLayer {
   /** Clear all records from a table */
   async clearTable(table: string): Promise<void> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      await invoke('db_clear_table', { table });
+      await invokeDb('db_clear_table', { table });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -262,11 +303,10 @@ class DataAccessLayer {
   ): Promise<void> {
     if (isTauri()) {
       // For SQLite: read existing, merge, write back
-      const { invoke } = await import('@tauri-apps/api/core');
-      const existing = await invoke<T | null>('db_get', { table, key: String(key) });
+      const existing = await invokeDb<T | null>('db_get', { table, key: String(key) });
       if (!existing) throw new Error(`Record not found in ${table} with key ${key}`);
       const merged = { ...existing, ...changes };
-      await invoke('db_put', { table, record: merged });
+      await invokeDb('db_put', { table, record: merged });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -277,14 +317,13 @@ class DataAccessLayer {
   /** Add a record (fails if key already exists) */
   async add<T extends object>(table: string, record: T): Promise<void> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
       // Check existence first to mirror Dexie's add() behavior
       const pk = this.guessPrimaryKey(table, record);
       if (pk !== undefined) {
-        const existing = await invoke('db_get', { table, key: String(pk) });
+        const existing = await invokeDb('db_get', { table, key: String(pk) });
         if (existing) throw new Error(`Key already exists in ${table}`);
       }
-      await invoke('db_put', { table, record });
+      await invokeDb('db_put', { table, record });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -295,8 +334,7 @@ class DataAccessLayer {
   /** Delete records matching a WHERE clause */
   async deleteWhere(table: string, whereClause: Record<string, unknown>): Promise<number> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<number>('db_delete_where', { table, whereClause });
+      return invokeDb<number>('db_delete_where', { table, whereClause });
     } else {
       const { getDB } = await import('./db');
       const db = getDB();
@@ -327,8 +365,7 @@ class DataAccessLayer {
    */
   async queryCustom<T>(options: CustomQueryOptions<T>): Promise<T[]> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<T[]>('db_execute_query', {
+      return invokeDb<T[]>('db_execute_query', {
         sql: options.sql,
         params: options.params ?? [],
       });
@@ -361,8 +398,7 @@ class DataAccessLayer {
   /** Open the SQLite database with an encryption key (Tauri only) */
   async open(key: string): Promise<void> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      await invoke('db_open', { key });
+      await invokeDb('db_open', { key });
     }
     // Dexie: handled by initializeDatabase() in db.ts
   }
@@ -370,16 +406,14 @@ class DataAccessLayer {
   /** Close the SQLite database (Tauri only) */
   async close(): Promise<void> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      await invoke('db_close');
+      await invokeDb('db_close');
     }
   }
 
   /** Check if database is open */
   async isOpen(): Promise<boolean> {
     if (isTauri()) {
-      const { invoke } = await import('@tauri-apps/api/core');
-      return invoke<boolean>('db_is_open');
+      return invokeDb<boolean>('db_is_open');
     }
     return true; // Dexie is always "open" once initialized
   }
@@ -2,7 +2,7 @@
 
 use crate::error::CryptoError;
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce as AesNonce,
 };
 use rand::rngs::OsRng;
@@ -20,6 +20,15 @@ const AES_GCM_NONCE_SIZE: usize = 12;
 
 /// Encrypt data with AES-256-GCM
 pub fn aes_encrypt(key: Vec<u8>, plaintext: Vec<u8>) -> Result<EncryptedData, CryptoError> {
+    aes_encrypt_with_aad(&key, &plaintext, &[])
+}
+
+/// Encrypt data with AES-256-GCM, authenticating `aad` alongside it
+pub fn aes_encrypt_with_aad(
+    key: &[u8],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<EncryptedData, CryptoError> {
     if key.len() != 32 {
         return Err(CryptoError::InvalidKey);
     }
@@ -29,12 +38,18 @@ pub fn aes_encrypt(key: Vec<u8>, plaintext: Vec<u8>) -> Result<EncryptedData, Cr
     OsRng.fill_bytes(&mut nonce_bytes);
 
     // Create cipher
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| CryptoError::InvalidKey)?;
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKey)?;
     let nonce = AesNonce::from_slice(&nonce_bytes);
 
     // Encrypt
     let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_ref())
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
         .map_err(|_| CryptoError::EncryptionFailed)?;
 
     Ok(EncryptedData {
@@ -45,6 +60,15 @@ pub fn aes_encrypt(key: Vec<u8>, plaintext: Vec<u8>) -> Result<EncryptedData, Cr
 
 /// Decrypt data with AES-256-GCM
 pub fn aes_decrypt(key: Vec<u8>, encrypted: EncryptedData) -> Result<Vec<u8>, CryptoError> {
+    aes_decrypt_with_aad(&key, &encrypted, &[])
+}
+
+/// Decrypt data with AES-256-GCM; fails unless `aad` matches encryption
+pub fn aes_decrypt_with_aad(
+    key: &[u8],
+    encrypted: &EncryptedData,
+    aad: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
     if key.len() != 32 {
         return Err(CryptoError::InvalidKey);
     }
@@ -54,12 +78,18 @@ pub fn aes_decrypt(key: Vec<u8>, encrypted: EncryptedData) -> Result<Vec<u8>, Cr
     }
 
     // Create cipher
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| CryptoError::InvalidKey)?;
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| CryptoError::InvalidKey)?;
     let nonce = AesNonce::from_slice(&encrypted.nonce);
 
     // Decrypt
     cipher
-        .decrypt(nonce, encrypted.ciphertext.as_ref())
+        .decrypt(
+            nonce,
+            Payload {
+                msg: &encrypted.ciphertext,
+                aad,
+            },
+        )
         .map_err(|_| CryptoError::DecryptionFailed)
 }
 
@@ -22,7 +22,7 @@
 
 use crate::error::CryptoError;
 use crate::keys::{generate_keypair, get_public_key, KeyPair};
-use crate::nip17::{create_gift_wrap, create_rumor, create_seal};
+use crate::nip17::{create_gift_wrap, create_rumor, create_seal, KIND_GIFT_WRAP};
 use crate::nostr::NostrEvent;
 use argon2::{Algorithm, Argon2, Params, Version};
 use hkdf::Hkdf;
@@ -38,16 +38,67 @@ const ARGON2_TIME_COST: u32 = 3; // 3 iterations
 const ARGON2_PARALLELISM: u32 = 4; // 4 lanes
 const ARGON2_OUTPUT_LEN: usize = 32; // 256-bit key
 
-/// Number of secure overwrite passes for key destruction
-const SECURE_WIPE_PASSES: usize = 3;
+/// Overwrite patterns used by `secure_destroy_key`: all ones, all zeros, alternating bits
+const DEFAULT_WIPE_PATTERNS: [u8; 3] = [0xFF, 0x00, 0xAA];
+
+/// Most pattern passes a `WipeConfig` may ask for; larger counts are clamped
+pub const MAX_WIPE_PASSES: usize = 16;
 
 /// Duress alert message (appears as normal DM content)
 const DURESS_ALERT_MESSAGE: &str = "DURESS ACTIVATED";
 
+/// Number of decoy contacts generated for a dry run
+const DRY_RUN_DECOY_CONTACTS: u32 = 5;
+
 /// HKDF salt for duress key derivation
 const DURESS_KEY_SALT: &[u8] = b"BuildItNetwork-Duress-v1";
 const DURESS_KEY_INFO: &[u8] = b"duress-password-key";
 
+/// Overwrite passes performed when destroying a key
+///
+/// Each pattern pass overwrites the whole key with `patterns[pass % len]`;
+/// a random pass and zeroization always follow. On SSDs and in RAM extra
+/// passes have diminishing returns, since wear levelling and copies made
+/// elsewhere are out of reach - the default of three is a balance, not a
+/// guarantee.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WipeConfig {
+    /// Number of pattern overwrite passes, at most [`MAX_WIPE_PASSES`]
+    pub passes: usize,
+    /// Byte patterns, cycled across passes (all zeros when empty)
+    pub patterns: Vec<u8>,
+}
+
+impl WipeConfig {
+    /// Pattern written on the given pass
+    fn pattern(&self, pass: usize) -> u8 {
+        if self.patterns.is_empty() {
+            0x00
+        } else {
+            self.patterns[pass % self.patterns.len()]
+        }
+    }
+
+    /// Pattern passes actually performed, clamped to [`MAX_WIPE_PASSES`]
+    pub fn pattern_passes(&self) -> usize {
+        self.passes.min(MAX_WIPE_PASSES)
+    }
+
+    /// Total overwrites including the final random pass
+    pub fn total_passes(&self) -> usize {
+        self.pattern_passes() + 1
+    }
+}
+
+impl Default for WipeConfig {
+    fn default() -> Self {
+        Self {
+            passes: DEFAULT_WIPE_PATTERNS.len(),
+            patterns: DEFAULT_WIPE_PATTERNS.to_vec(),
+        }
+    }
+}
+
 /// Decoy identity with pre-generated content
 #[derive(Debug, Clone)]
 pub struct DecoyIdentity {
@@ -90,6 +141,50 @@ pub struct DecoyContact {
     pub display_name: String,
 }
 
+/// An alert that a real duress activation would send
+#[derive(Debug, Clone)]
+pub struct DuressAlertPreview {
+    /// Trusted contact the alert would be addressed to
+    pub recipient_pubkey: String,
+    /// Plaintext message inside the gift wrap
+    pub message: String,
+    /// Kind of the outer event that would be published
+    pub kind: i32,
+    /// Whether location would be attached
+    pub include_location: bool,
+}
+
+/// A key that a real duress activation would destroy
+#[derive(Debug, Clone)]
+pub struct DuressWipePreview {
+    /// Caller-supplied label identifying the key (e.g. "identity", "db")
+    pub label: String,
+    /// Length of the key material in bytes
+    pub key_len: usize,
+    /// Overwrite passes `secure_destroy_key` would perform
+    pub passes: u32,
+}
+
+/// Result of rehearsing the duress flow
+///
+/// Nothing in this result has been sent or destroyed. `dry_run` is always
+/// true so callers can never mistake it for a real activation.
+#[derive(Debug, Clone)]
+pub struct DuressDryRunResult {
+    /// Always true: no alert was sent and no key was destroyed
+    pub dry_run: bool,
+    /// Decoy identity that would be shown
+    pub decoy_identity: DecoyIdentity,
+    /// Decoy contacts that would be shown
+    pub decoy_contacts: Vec<DecoyContact>,
+    /// Decoy message history that would be shown
+    pub decoy_messages: Vec<String>,
+    /// Alerts that would be sent, one per trusted contact
+    pub alerts: Vec<DuressAlertPreview>,
+    /// Keys that would be destroyed
+    pub wiped_keys: Vec<DuressWipePreview>,
+}
+
 /// Hash a password for duress detection using Argon2id
 ///
 /// This creates a hash specifically for comparing against duress password.
@@ -235,18 +330,33 @@ pub fn generate_decoy_identity(created_at: i64) -> DecoyIdentity {
 ///   - OS page cache
 ///
 /// For complete security, use encrypted memory and secure boot.
-pub fn secure_destroy_key(mut key: Vec<u8>) -> Result<(), CryptoError> {
+pub fn secure_destroy_key(key: Vec<u8>) -> Result<(), CryptoError> {
+    secure_destroy_key_with_config(key, &WipeConfig::default())
+}
+
+/// Securely destroy a private key with custom overwrite passes
+///
+/// Same as [`secure_destroy_key`] but with the pattern passes taken from
+/// `config`. The random pass and final zeroization are always performed.
+pub fn secure_destroy_key_with_config(
+    mut key: Vec<u8>,
+    config: &WipeConfig,
+) -> Result<(), CryptoError> {
     if key.is_empty() {
         return Ok(());
     }
 
+    wipe_with_hook(&mut key, config, |_, _| {});
+    key.zeroize();
+
+    Ok(())
+}
+
+/// Overwrite `key` in place, calling `on_pass` after each overwrite pass
+fn wipe_with_hook(key: &mut [u8], config: &WipeConfig, mut on_pass: impl FnMut(usize, &[u8])) {
     // Multiple overwrite passes with different patterns
-    for pass in 0..SECURE_WIPE_PASSES {
-        let pattern = match pass {
-            0 => 0xFF, // All ones
-            1 => 0x00, // All zeros
-            _ => 0xAA, // Alternating bits
-        };
+    for pass in 0..config.pattern_passes() {
+        let pattern = config.pattern(pass);
 
         // Overwrite each byte
         for byte in key.iter_mut() {
@@ -258,6 +368,7 @@ pub fn secure_destroy_key(mut key: Vec<u8>) -> Result<(), CryptoError> {
 
         // Memory barrier to ensure writes complete
         std::sync::atomic::fence(std::sync::atomic::Ordering::SeqCst);
+        on_pass(pass, key);
     }
 
     // Random overwrite pass using OS RNG
@@ -269,12 +380,12 @@ pub fn secure_destroy_key(mut key: Vec<u8>) -> Result<(), CryptoError> {
             std::ptr::write_volatile(byte, random);
         }
     }
+    std::sync::atomic::fence(std::sync::atomic::Ordering::SeqCst);
+    on_pass(config.pattern_passes(), key);
 
     // Final zeroization using zeroize crate
     random_bytes.zeroize();
     key.zeroize();
-
-    Ok(())
 }
 
 /// Create a silent duress alert message
@@ -289,13 +400,48 @@ pub fn secure_destroy_key(mut key: Vec<u8>) -> Result<(), CryptoError> {
 /// - Timestamp is randomized per NIP-17
 /// - Ephemeral key hides sender
 pub fn create_duress_alert(
-    sender_private_key: Vec<u8>,
+    mut sender_private_key: Vec<u8>,
+    recipient_pubkey: String,
+    created_at: i64,
+    custom_message: Option<String>,
+) -> Result<NostrEvent, CryptoError> {
+    let alert = build_duress_alert(
+        &sender_private_key,
+        recipient_pubkey,
+        created_at,
+        custom_message,
+    );
+    sender_private_key.zeroize();
+    alert
+}
+
+/// Create duress alerts for multiple trusted contacts
+///
+/// Sends the same alert to all configured trusted contacts.
+/// Each alert uses a different ephemeral key for unlinkability.
+pub fn create_duress_alerts(
+    mut sender_private_key: Vec<u8>,
+    config: DuressAlertConfig,
+    created_at: i64,
+) -> Result<Vec<NostrEvent>, CryptoError> {
+    let message = config.custom_message;
+    let alerts = config
+        .trusted_contact_pubkeys
+        .into_iter()
+        .map(|pubkey| build_duress_alert(&sender_private_key, pubkey, created_at, message.clone()))
+        .collect();
+    sender_private_key.zeroize();
+    alerts
+}
+
+fn build_duress_alert(
+    sender_private_key: &[u8],
     recipient_pubkey: String,
     created_at: i64,
     custom_message: Option<String>,
 ) -> Result<NostrEvent, CryptoError> {
     // Get sender public key
-    let sender_pubkey = get_public_key(sender_private_key.clone())?;
+    let sender_pubkey = get_public_key(sender_private_key.to_vec())?;
 
     // Construct alert message
     let message = custom_message.unwrap_or_else(|| DURESS_ALERT_MESSAGE.to_string());
@@ -306,7 +452,7 @@ pub fn create_duress_alert(
 
     // Step 2: Create seal (encrypted rumor, signed by sender)
     let seal = create_seal(
-        sender_private_key,
+        sender_private_key.to_vec(),
         recipient_pubkey.clone(),
         rumor,
         created_at,
@@ -316,30 +462,65 @@ pub fn create_duress_alert(
     create_gift_wrap(recipient_pubkey, seal, created_at)
 }
 
-/// Create duress alerts for multiple trusted contacts
+/// Rehearse the duress flow without side effects
 ///
-/// Sends the same alert to all configured trusted contacts.
-/// Each alert uses a different ephemeral key for unlinkability.
-pub fn create_duress_alerts(
-    sender_private_key: Vec<u8>,
-    config: DuressAlertConfig,
+/// Generates the decoy identity, contacts and messages exactly as a real
+/// activation would, validates every alert recipient and reports the alerts
+/// that would be sent and the keys that would be destroyed. No alert is
+/// signed or returned in sendable form and the keys are only borrowed, so a
+/// rehearsal can never leak a real alert or shred real key material.
+///
+/// `keys` pairs a caller-chosen label with the key material to be wiped.
+pub fn duress_dry_run(
+    sender_private_key: &[u8],
+    config: &DuressAlertConfig,
+    keys: &[(&str, &[u8])],
     created_at: i64,
-) -> Result<Vec<NostrEvent>, CryptoError> {
-    let mut alerts = Vec::with_capacity(config.trusted_contact_pubkeys.len());
+) -> Result<DuressDryRunResult, CryptoError> {
+    // Fail the rehearsal where the real flow would fail
+    get_public_key(sender_private_key.to_vec())?;
 
-    let message = config.custom_message;
+    let message = config
+        .custom_message
+        .clone()
+        .unwrap_or_else(|| DURESS_ALERT_MESSAGE.to_string());
 
-    for pubkey in config.trusted_contact_pubkeys {
-        let alert = create_duress_alert(
-            sender_private_key.clone(),
-            pubkey,
-            created_at,
-            message.clone(),
-        )?;
-        alerts.push(alert);
+    let mut alerts = Vec::with_capacity(config.trusted_contact_pubkeys.len());
+    for pubkey in &config.trusted_contact_pubkeys {
+        let bytes = hex::decode(pubkey).map_err(|_| CryptoError::InvalidPublicKey)?;
+        if bytes.len() != 32 {
+            return Err(CryptoError::InvalidPublicKey);
+        }
+        alerts.push(DuressAlertPreview {
+            recipient_pubkey: pubkey.clone(),
+            message: message.clone(),
+            kind: KIND_GIFT_WRAP,
+            include_location: config.include_location,
+        });
     }
 
-    Ok(alerts)
+    let wiped_keys = keys
+        .iter()
+        .map(|(label, key)| DuressWipePreview {
+            label: label.to_string(),
+            key_len: key.len(),
+            passes: if key.is_empty() {
+                0
+            } else {
+                // Pattern passes plus the random pass
+                WipeConfig::default().total_passes() as u32
+            },
+        })
+        .collect();
+
+    Ok(DuressDryRunResult {
+        dry_run: true,
+        decoy_identity: generate_decoy_identity(created_at),
+        decoy_contacts: generate_decoy_contacts(DRY_RUN_DECOY_CONTACTS),
+        decoy_messages: generate_decoy_messages(),
+        alerts,
+        wiped_keys,
+    })
 }
 
 /// Validate that a duress password is sufficiently different from normal password
@@ -588,10 +769,10 @@ mod tests {
         assert!(!alert.sig.is_empty());
 
         // Should have p tag pointing to recipient
-        assert!(alert
-            .tags
-            .iter()
-            .any(|t| t.len() >= 2 && t[0] == "p" && t[1] == recipient.public_key));
+        assert_eq!(
+            alert.first_tag_value("p"),
+            Some(recipient.public_key.as_str())
+        );
     }
 
     #[test]
@@ -714,4 +895,195 @@ mod tests {
             assert!(!msg.to_lowercase().contains("help me"));
         }
     }
+
+    #[test]
+    fn test_duress_dry_run_previews() {
+        let sender = generate_keypair();
+        let recipient1 = generate_keypair();
+        let recipient2 = generate_keypair();
+        let now = 1700000000i64;
+
+        let config = DuressAlertConfig {
+            trusted_contact_pubkeys: vec![
+                recipient1.public_key.clone(),
+                recipient2.public_key.clone(),
+            ],
+            include_location: true,
+            custom_message: Some("Check on me".to_string()),
+        };
+
+        let result = duress_dry_run(
+            &sender.private_key,
+            &config,
+            &[("identity", &[7u8; 32])],
+            now,
+        )
+        .unwrap();
+
+        assert!(result.dry_run);
+        assert_eq!(result.decoy_identity.created_at, now);
+        assert_ne!(result.decoy_identity.keypair.public_key, sender.public_key);
+        assert_eq!(result.decoy_contacts.len(), 5);
+        assert_eq!(result.decoy_messages, generate_decoy_messages());
+
+        assert_eq!(result.alerts.len(), 2);
+        assert_eq!(result.alerts[0].recipient_pubkey, recipient1.public_key);
+        assert_eq!(result.alerts[1].recipient_pubkey, recipient2.public_key);
+        for alert in &result.alerts {
+            assert_eq!(alert.message, "Check on me");
+            assert_eq!(alert.kind, 1059);
+            assert!(alert.include_location);
+        }
+
+        assert_eq!(result.wiped_keys.len(), 1);
+        assert_eq!(result.wiped_keys[0].label, "identity");
+        assert_eq!(result.wiped_keys[0].key_len, 32);
+        assert_eq!(result.wiped_keys[0].passes, 4);
+    }
+
+    #[test]
+    fn test_duress_dry_run_default_message() {
+        let sender = generate_keypair();
+        let config = DuressAlertConfig {
+            trusted_contact_pubkeys: vec![generate_keypair().public_key],
+            include_location: false,
+            custom_message: None,
+        };
+
+        let result = duress_dry_run(&sender.private_key, &config, &[], 0).unwrap();
+
+        assert_eq!(result.alerts[0].message, DURESS_ALERT_MESSAGE);
+        assert!(result.wiped_keys.is_empty());
+    }
+
+    #[test]
+    fn test_duress_dry_run_is_not_destructive() {
+        let sender = generate_keypair();
+        let identity_key = sender.private_key.clone();
+        let db_key = vec![0x42u8; 32];
+        let config = DuressAlertConfig {
+            trusted_contact_pubkeys: vec![generate_keypair().public_key],
+            include_location: false,
+            custom_message: None,
+        };
+
+        let result = duress_dry_run(
+            &sender.private_key,
+            &config,
+            &[("identity", &identity_key), ("db", &db_key), ("empty", &[])],
+            1700000000,
+        )
+        .unwrap();
+
+        // No key was shredded: the borrowed material is untouched
+        assert_eq!(identity_key, sender.private_key);
+        assert_eq!(db_key, vec![0x42u8; 32]);
+        assert_eq!(result.wiped_keys[2].passes, 0);
+
+        // The sender key still works for a real alert afterwards
+        assert!(create_duress_alert(
+            sender.private_key.clone(),
+            config.trusted_contact_pubkeys[0].clone(),
+            1700000000,
+            None,
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_duress_dry_run_rejects_invalid_input() {
+        let sender = generate_keypair();
+        let mut config = DuressAlertConfig {
+            trusted_contact_pubkeys: vec!["not-a-pubkey".to_string()],
+            include_location: false,
+            custom_message: None,
+        };
+
+        assert!(matches!(
+            duress_dry_run(&sender.private_key, &config, &[], 0),
+            Err(CryptoError::InvalidPublicKey)
+        ));
+
+        config.trusted_contact_pubkeys = vec![generate_keypair().public_key];
+        assert!(duress_dry_run(&[0u8; 32], &config, &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_default_wipe_overwrites_each_pattern_and_ends_zeroed() {
+        // The wipe `secure_destroy_key` runs on the buffer it is given
+        let config = WipeConfig::default();
+        let mut key = vec![0x5C; 32];
+        let mut seen = Vec::new();
+
+        wipe_with_hook(&mut key, &config, |pass, bytes| {
+            if pass < DEFAULT_WIPE_PATTERNS.len() {
+                assert!(bytes.iter().all(|&b| b == DEFAULT_WIPE_PATTERNS[pass]));
+            } else {
+                assert_ne!(bytes, &[0x5C; 32][..]);
+            }
+            seen.push(pass);
+        });
+
+        assert_eq!(seen, vec![0, 1, 2, 3]);
+        assert!(key.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_wipe_passes_are_clamped() {
+        let config = WipeConfig {
+            passes: usize::MAX,
+            patterns: vec![0x11],
+        };
+        assert_eq!(config.pattern_passes(), MAX_WIPE_PASSES);
+        assert_eq!(config.total_passes(), MAX_WIPE_PASSES + 1);
+
+        let mut key = vec![0xAB; 32];
+        let mut passes = 0;
+        wipe_with_hook(&mut key, &config, |_, _| passes += 1);
+        assert_eq!(passes, MAX_WIPE_PASSES + 1);
+        assert!(key.iter().all(|&b| b == 0));
+
+        secure_destroy_key_with_config(vec![0xAB; 32], &config).unwrap();
+    }
+
+    #[test]
+    fn test_custom_wipe_config_runs_each_pass_and_ends_zeroed() {
+        let config = WipeConfig {
+            passes: 5,
+            patterns: vec![0x11, 0x22],
+        };
+        let mut key = vec![0xAB; 32];
+        let mut seen = Vec::new();
+
+        wipe_with_hook(&mut key, &config, |pass, bytes| {
+            if pass < config.pattern_passes() {
+                assert!(bytes.iter().all(|&b| b == config.pattern(pass)));
+            }
+            seen.push(pass);
+        });
+
+        assert_eq!(seen, (0..config.total_passes()).collect::<Vec<_>>());
+        assert!(key.iter().all(|&b| b == 0));
+        assert_eq!(config.pattern(4), 0x11);
+    }
+
+    #[test]
+    fn test_default_wipe_config_matches_previous_behavior() {
+        let config = WipeConfig::default();
+        assert_eq!(config.passes, 3);
+        assert_eq!(config.patterns, vec![0xFF, 0x00, 0xAA]);
+        assert_eq!(config.total_passes(), 4);
+
+        let zero_passes = WipeConfig {
+            passes: 0,
+            patterns: Vec::new(),
+        };
+        let mut key = vec![0xAB; 8];
+        let mut passes = 0;
+        wipe_with_hook(&mut key, &zero_passes, |_, _| passes += 1);
+        assert_eq!(passes, 1);
+        assert!(key.iter().all(|&b| b == 0));
+
+        secure_destroy_key_with_config(vec![0xAB; 32], &zero_passes).unwrap();
+    }
 }
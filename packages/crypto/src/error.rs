@@ -61,4 +61,10 @@ pub enum CryptoError {
 
     #[error("Invalid version string (expected format: MAJOR.MINOR.PATCH)")]
     InvalidVersion,
+
+    #[error("Duplicate message (already decrypted)")]
+    DuplicateMessage,
+
+    #[error("Unsupported ratchet session version")]
+    UnsupportedSessionVersion,
 }
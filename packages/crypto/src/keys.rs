@@ -5,8 +5,11 @@ use argon2::{Algorithm, Argon2, Params, Version};
 use hkdf::Hkdf;
 use rand::rngs::OsRng;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fmt;
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
 use zeroize::Zeroize;
 
 /// Key pair containing private and public keys
@@ -41,6 +44,14 @@ const ARGON2_TIME_COST: u32 = 3; // 3 iterations
 const ARGON2_PARALLELISM: u32 = 4; // 4 lanes
 const ARGON2_OUTPUT_LEN: usize = 32; // 256-bit key
 
+/// Calibration bounds. The floor is the OWASP minimum (19 MiB, t=2) and is
+/// also enforced on stored parameters so they cannot be weakened.
+pub(crate) const ARGON2_MIN_MEMORY_KB: u32 = 19456; // 19 MB
+const ARGON2_MAX_MEMORY_KB: u32 = 262144; // 256 MB cap
+pub(crate) const ARGON2_MIN_TIME_COST: u32 = 2;
+const ARGON2_MAX_TIME_COST: u32 = 10;
+const ARGON2_MAX_CALIBRATION_TRIALS: usize = 6;
+
 /// HKDF salt for database key derivation
 const DATABASE_KEY_SALT: &[u8] = b"BuildItNetwork-DEK-v1";
 const DATABASE_KEY_INFO: &[u8] = b"database-encryption";
@@ -70,6 +81,27 @@ pub fn get_public_key(private_key: Vec<u8>) -> Result<String, CryptoError> {
     Ok(hex::encode(&public_key.serialize()[1..]))
 }
 
+/// Check that a private key derives the expected public key
+///
+/// Guards importing a key from backup. The expected key is the 32-byte
+/// x-only form returned by `get_public_key`, hex encoded; the comparison is
+/// constant-time. A malformed private key is `InvalidKey` and a malformed
+/// expected key `InvalidPublicKey`, never a plain mismatch.
+pub fn verify_keypair(
+    private_key_hex: String,
+    expected_public_key_hex: String,
+) -> Result<bool, CryptoError> {
+    let private_key = hex::decode(private_key_hex.trim()).map_err(|_| CryptoError::InvalidKey)?;
+    let expected =
+        hex::decode(expected_public_key_hex.trim()).map_err(|_| CryptoError::InvalidPublicKey)?;
+    if expected.len() != 32 {
+        return Err(CryptoError::InvalidPublicKey);
+    }
+
+    let derived = hex::decode(get_public_key(private_key)?).map_err(|_| CryptoError::InvalidKey)?;
+    Ok(derived.ct_eq(&expected).into())
+}
+
 /// Derive master encryption key from password using Argon2id
 ///
 /// Argon2id is a memory-hard KDF that is resistant to GPU/ASIC attacks.
@@ -83,29 +115,125 @@ pub fn get_public_key(private_key: Vec<u8>) -> Result<String, CryptoError> {
 ///
 /// SECURITY: The password bytes are zeroized after use.
 /// Callers should ensure they zeroize any copies of the password they hold.
-pub fn derive_master_key(mut password: Vec<u8>, salt: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+pub fn derive_master_key(password: Vec<u8>, salt: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+    derive_master_key_with_params(password, salt, Argon2Params::default())
+}
+
+/// Argon2id cost parameters
+///
+/// Stored alongside the user's salt when produced by `calibrate_argon2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Argon2Params {
+    /// Memory cost in KiB
+    pub memory_kb: u32,
+    /// Number of iterations
+    pub time_cost: u32,
+    /// Number of lanes
+    pub parallelism: u32,
+}
+
+impl Default for Argon2Params {
+    /// The fixed parameters used by `derive_master_key`
+    fn default() -> Self {
+        Self {
+            memory_kb: ARGON2_MEMORY_KB,
+            time_cost: ARGON2_TIME_COST,
+            parallelism: ARGON2_PARALLELISM,
+        }
+    }
+}
+
+/// Derive master encryption key from password using Argon2id with explicit
+/// cost parameters
+///
+/// Parameters below the OWASP minimum (19 MB, 2 iterations) or above the
+/// calibration cap are rejected, so tampered stored parameters cannot weaken
+/// the derivation.
+///
+/// SECURITY: The password bytes are zeroized after use.
+pub fn derive_master_key_with_params(
+    mut password: Vec<u8>,
+    salt: Vec<u8>,
+    params: Argon2Params,
+) -> Result<Vec<u8>, CryptoError> {
+    let result = argon2_derive(&password, &salt, params);
+    password.zeroize();
+    result
+}
+
+/// Time trial Argon2id derivations to find parameters that take about
+/// `target_ms` on this device
+///
+/// Starts from the default parameters (64 MB, 3 iterations) and scales
+/// memory first (up to 256 MB), then iterations (up to 10), using at most a
+/// handful of trial derivations. On hardware too slow to reach the target
+/// the default is returned: calibration only ever strengthens it.
+pub fn calibrate_argon2(target_ms: u32) -> Result<Argon2Params, CryptoError> {
+    let target = Duration::from_millis(u64::from(target_ms.max(1)));
+    let mut params = Argon2Params::default();
+
+    let mut elapsed = time_argon2_trial(params)?;
+    for _ in 1..ARGON2_MAX_CALIBRATION_TRIALS {
+        if elapsed >= target {
+            break;
+        }
+        let scale = target.as_secs_f64() / elapsed.as_secs_f64().max(1e-6);
+
+        if params.memory_kb < ARGON2_MAX_MEMORY_KB {
+            // Round to whole MiB
+            let memory = (f64::from(params.memory_kb) * scale).ceil() as u64;
+            let memory = memory.div_ceil(1024) * 1024;
+            params.memory_kb = memory.min(u64::from(ARGON2_MAX_MEMORY_KB)) as u32;
+        } else if params.time_cost < ARGON2_MAX_TIME_COST {
+            let time_cost = (f64::from(params.time_cost) * scale).ceil() as u64;
+            params.time_cost = time_cost.min(u64::from(ARGON2_MAX_TIME_COST)) as u32;
+        } else {
+            break;
+        }
+
+        elapsed = time_argon2_trial(params)?;
+    }
+
+    Ok(params)
+}
+
+/// Run one derivation with throwaway inputs and measure it
+fn time_argon2_trial(params: Argon2Params) -> Result<Duration, CryptoError> {
+    let start = Instant::now();
+    let mut key = argon2_derive(b"buildit-argon2-calibration", &[0u8; 16], params)?;
+    let elapsed = start.elapsed();
+    key.zeroize();
+    Ok(elapsed)
+}
+
+fn argon2_derive(
+    password: &[u8],
+    salt: &[u8],
+    params: Argon2Params,
+) -> Result<Vec<u8>, CryptoError> {
     if salt.len() < 16 {
         return Err(CryptoError::KeyDerivationFailed);
     }
+    if !(ARGON2_MIN_MEMORY_KB..=ARGON2_MAX_MEMORY_KB).contains(&params.memory_kb)
+        || !(ARGON2_MIN_TIME_COST..=ARGON2_MAX_TIME_COST).contains(&params.time_cost)
+        || params.parallelism == 0
+    {
+        return Err(CryptoError::KeyDerivationFailed);
+    }
 
     let params = Params::new(
-        ARGON2_MEMORY_KB,
-        ARGON2_TIME_COST,
-        ARGON2_PARALLELISM,
+        params.memory_kb,
+        params.time_cost,
+        params.parallelism,
         Some(ARGON2_OUTPUT_LEN),
     )
     .map_err(|_| CryptoError::KeyDerivationFailed)?;
 
     let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
-
     let mut key = vec![0u8; ARGON2_OUTPUT_LEN];
-
-    let result = argon2.hash_password_into(&password, &salt, &mut key);
-
-    // Zeroize password after use
-    password.zeroize();
-
-    result.map_err(|_| CryptoError::KeyDerivationFailed)?;
+    argon2
+        .hash_password_into(password, salt, &mut key)
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
     Ok(key)
 }
 
@@ -124,11 +252,32 @@ pub fn derive_database_key(master_key: Vec<u8>) -> Result<Vec<u8>, CryptoError>
     Ok(database_key)
 }
 
+/// Parse a recipient public key given as 64-char x-only or 66-char compressed hex
+///
+/// x-only keys (the Nostr form) are lifted with even parity per BIP-340. The
+/// parity never changes the ECDH x-coordinate, so both forms of one key
+/// yield the same conversation key.
+pub(crate) fn parse_recipient_pubkey(recipient_pubkey: &str) -> Result<PublicKey, CryptoError> {
+    let pubkey_bytes =
+        hex::decode(recipient_pubkey.trim()).map_err(|_| CryptoError::InvalidPublicKey)?;
+
+    let compressed = match pubkey_bytes.len() {
+        32 => {
+            let mut compressed = vec![0x02u8];
+            compressed.extend_from_slice(&pubkey_bytes);
+            compressed
+        }
+        33 => pubkey_bytes,
+        _ => return Err(CryptoError::InvalidPublicKey),
+    };
+    PublicKey::from_slice(&compressed).map_err(|_| CryptoError::InvalidPublicKey)
+}
+
 /// Derive NIP-44 conversation key from ECDH shared secret
 ///
-/// SECURITY: This function properly handles both even (0x02) and odd (0x03) y-coordinates
-/// by trying both prefixes when constructing the public key. The shared secret is
-/// zeroized after the conversation key is derived.
+/// `recipient_pubkey` may be x-only or compressed hex (see
+/// `parse_recipient_pubkey`). The shared secret is zeroized after the
+/// conversation key is derived.
 pub fn derive_conversation_key(
     private_key: Vec<u8>,
     recipient_pubkey: String,
@@ -137,71 +286,68 @@ pub fn derive_conversation_key(
         return Err(CryptoError::InvalidKey);
     }
 
-    let pubkey_bytes = hex::decode(&recipient_pubkey).map_err(|_| CryptoError::InvalidPublicKey)?;
-    if pubkey_bytes.len() != 32 {
-        return Err(CryptoError::InvalidPublicKey);
-    }
-
-    let _secp = Secp256k1::new();
     let secret_key = SecretKey::from_slice(&private_key).map_err(|_| CryptoError::InvalidKey)?;
-
-    // Try both possible y-coordinate parities (0x02 for even, 0x03 for odd)
-    // NIP-44 specifies x-only public keys, so we need to try both
-    let public_key = {
-        // First try 0x02 (even y-coordinate)
-        let mut compressed_even = vec![0x02u8];
-        compressed_even.extend_from_slice(&pubkey_bytes);
-
-        match PublicKey::from_slice(&compressed_even) {
-            Ok(pk) => pk,
-            Err(_) => {
-                // Try 0x03 (odd y-coordinate)
-                let mut compressed_odd = vec![0x03u8];
-                compressed_odd.extend_from_slice(&pubkey_bytes);
-                PublicKey::from_slice(&compressed_odd).map_err(|_| CryptoError::InvalidPublicKey)?
-            }
-        }
-    };
+    let public_key = parse_recipient_pubkey(&recipient_pubkey)?;
 
     // ECDH shared secret
     let mut shared_point = secp256k1::ecdh::shared_secret_point(&public_key, &secret_key);
     let shared_x = &shared_point[0..32]; // x-coordinate only (shared_point is [x, y])
 
-    // HKDF to derive conversation key
-    let hk = Hkdf::<Sha256>::new(Some(b"nip44-v2"), shared_x);
-    let mut conversation_key = vec![0u8; 32];
-
-    let result = hk
-        .expand(&[], &mut conversation_key)
-        .map_err(|_| CryptoError::KeyDerivationFailed);
+    // NIP-44 v2: conversation key is HKDF-extract(salt = "nip44-v2", IKM = shared_x)
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(b"nip44-v2"), shared_x);
+    let conversation_key = prk.to_vec();
 
     // Zeroize shared secret after use
     shared_point.zeroize();
 
-    result?;
     Ok(conversation_key)
 }
 
 /// Sign arbitrary message with Schnorr signature (BIP-340)
+///
+/// The message is SHA-256 hashed first. Auxiliary randomness comes from OsRng,
+/// so signatures differ between calls.
 pub fn schnorr_sign(message: &[u8], private_key: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
-    if private_key.len() != 32 {
-        return Err(CryptoError::InvalidKey);
-    }
-
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_slice(&private_key).map_err(|_| CryptoError::InvalidKey)?;
+    let mut aux_rand = [0u8; 32];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut aux_rand);
+    schnorr_sign_with_aux(message, private_key, aux_rand)
+}
 
+/// Sign arbitrary message with Schnorr signature using caller-supplied aux randomness
+///
+/// Same message hashing as `schnorr_sign`. A fixed `aux_rand` makes the
+/// signature deterministic, which is still secure under BIP-340 but gives up
+/// the side-channel hardening fresh randomness provides. Prefer `schnorr_sign`.
+pub fn schnorr_sign_with_aux(
+    message: &[u8],
+    private_key: Vec<u8>,
+    aux_rand: [u8; 32],
+) -> Result<Vec<u8>, CryptoError> {
     // Create message hash
     let mut hasher = sha2::Sha256::new();
     hasher.update(message);
-    let message_hash = hasher.finalize();
+    let message_hash: [u8; 32] = hasher.finalize().into();
 
-    let msg = secp256k1::Message::from_digest_slice(&message_hash)
-        .map_err(|_| CryptoError::SigningFailed)?;
+    schnorr_sign_digest_with_aux(&message_hash, &private_key, &aux_rand)
+}
+
+/// Sign a 32-byte BIP-340 message as-is with the given aux randomness
+fn schnorr_sign_digest_with_aux(
+    digest: &[u8; 32],
+    private_key: &[u8],
+    aux_rand: &[u8; 32],
+) -> Result<Vec<u8>, CryptoError> {
+    if private_key.len() != 32 {
+        return Err(CryptoError::InvalidKey);
+    }
+
+    let secp = Secp256k1::new();
+    let secret_key = SecretKey::from_slice(private_key).map_err(|_| CryptoError::InvalidKey)?;
+    let msg = secp256k1::Message::from_digest(*digest);
 
     // Sign with Schnorr
     let keypair = secp256k1::Keypair::from_secret_key(&secp, &secret_key);
-    let signature = secp.sign_schnorr_with_rng(&msg, &keypair, &mut rand::rngs::OsRng);
+    let signature = secp.sign_schnorr_with_aux_rand(&msg, &keypair, aux_rand);
 
     Ok(signature.serialize().to_vec())
 }
@@ -264,6 +410,39 @@ mod tests {
         assert_eq!(pubkey, kp.public_key);
     }
 
+    #[test]
+    fn test_verify_keypair_matching() {
+        let kp = generate_keypair();
+        let private_hex = hex::encode(&kp.private_key);
+        assert!(verify_keypair(private_hex.clone(), kp.public_key.clone()).unwrap());
+        // Case and surrounding whitespace don't matter
+        assert!(
+            verify_keypair(private_hex, format!(" {} ", kp.public_key.to_uppercase())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_verify_keypair_mismatched() {
+        let kp = generate_keypair();
+        let other = generate_keypair();
+        assert!(!verify_keypair(hex::encode(&kp.private_key), other.public_key).unwrap());
+    }
+
+    #[test]
+    fn test_verify_keypair_invalid_keys() {
+        let kp = generate_keypair();
+        for bad in ["not hex".to_string(), "ab".repeat(16), "00".repeat(32)] {
+            assert!(matches!(
+                verify_keypair(bad, kp.public_key.clone()),
+                Err(CryptoError::InvalidKey)
+            ));
+        }
+        assert!(matches!(
+            verify_keypair(hex::encode(&kp.private_key), "ab".repeat(33)),
+            Err(CryptoError::InvalidPublicKey)
+        ));
+    }
+
     #[test]
     fn test_derive_master_key() {
         let password = b"correct horse battery staple".to_vec();
@@ -277,6 +456,66 @@ mod tests {
         assert_eq!(key, key2);
     }
 
+    #[test]
+    fn test_default_params_match_derive_master_key() {
+        let password = b"correct horse battery staple".to_vec();
+        let salt = vec![7u8; 16];
+
+        let fixed = derive_master_key(password.clone(), salt.clone()).unwrap();
+        let explicit =
+            derive_master_key_with_params(password, salt, Argon2Params::default()).unwrap();
+        assert_eq!(fixed, explicit);
+    }
+
+    #[test]
+    fn test_weak_argon2_params_rejected() {
+        let weak = [
+            Argon2Params {
+                memory_kb: 1024,
+                ..Argon2Params::default()
+            },
+            Argon2Params {
+                time_cost: 1,
+                ..Argon2Params::default()
+            },
+            Argon2Params {
+                parallelism: 0,
+                ..Argon2Params::default()
+            },
+        ];
+        for params in weak {
+            assert!(derive_master_key_with_params(b"pw".to_vec(), vec![0u8; 16], params).is_err());
+        }
+    }
+
+    #[test]
+    fn test_calibrate_argon2() {
+        let target_ms = 100;
+        let params = calibrate_argon2(target_ms).unwrap();
+        // Never weaker than the default
+        assert!(params.memory_kb >= ARGON2_MEMORY_KB && params.memory_kb <= ARGON2_MAX_MEMORY_KB);
+        assert!(params.time_cost >= ARGON2_TIME_COST && params.time_cost <= ARGON2_MAX_TIME_COST);
+
+        // On slow (e.g. unoptimized) builds the default alone may exceed the target
+        let floor = time_argon2_trial(Argon2Params::default()).unwrap();
+        let bound = Duration::from_millis(u64::from(target_ms)).max(floor) * 5;
+
+        let start = Instant::now();
+        let key =
+            derive_master_key_with_params(b"password".to_vec(), vec![1u8; 16], params).unwrap();
+        assert_eq!(key.len(), 32);
+        assert!(
+            start.elapsed() < bound,
+            "{:?} exceeded {:?}",
+            start.elapsed(),
+            bound
+        );
+
+        // Parameters survive a storage round trip
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(serde_json::from_str::<Argon2Params>(&json).unwrap(), params);
+    }
+
     #[test]
     fn test_derive_database_key() {
         let master_key = vec![0u8; 32];
@@ -302,6 +541,60 @@ mod tests {
         assert_eq!(key1.len(), 32);
     }
 
+    /// Keypair whose compressed public key has the given parity prefix
+    fn keypair_with_parity(prefix: u8) -> (KeyPair, String) {
+        let secp = Secp256k1::new();
+        loop {
+            let kp = generate_keypair();
+            let secret_key = SecretKey::from_slice(&kp.private_key).unwrap();
+            let compressed = PublicKey::from_secret_key(&secp, &secret_key).serialize();
+            if compressed[0] == prefix {
+                return (kp, hex::encode(compressed));
+            }
+        }
+    }
+
+    #[test]
+    fn test_derive_conversation_key_xonly_and_compressed_agree() {
+        let ours = generate_keypair();
+        for prefix in [0x02, 0x03] {
+            let (theirs, compressed) = keypair_with_parity(prefix);
+            assert_eq!(compressed.len(), 66);
+            assert_eq!(&compressed[2..], theirs.public_key);
+
+            let from_xonly =
+                derive_conversation_key(ours.private_key.clone(), theirs.public_key.clone())
+                    .unwrap();
+            let from_compressed =
+                derive_conversation_key(ours.private_key.clone(), compressed).unwrap();
+            assert_eq!(from_xonly, from_compressed);
+
+            // And it is the key the other side derives from our x-only key
+            let reverse =
+                derive_conversation_key(theirs.private_key.clone(), ours.public_key.clone())
+                    .unwrap();
+            assert_eq!(from_xonly, reverse);
+        }
+    }
+
+    #[test]
+    fn test_derive_conversation_key_rejects_bad_pubkeys() {
+        let ours = generate_keypair();
+        let (_, compressed) = keypair_with_parity(0x02);
+        let bad = [
+            "zz".repeat(32),
+            "ab".repeat(31),
+            format!("04{}", &compressed[2..]),
+            format!("{}00", compressed),
+        ];
+        for pubkey in bad {
+            assert!(matches!(
+                derive_conversation_key(ours.private_key.clone(), pubkey),
+                Err(CryptoError::InvalidPublicKey)
+            ));
+        }
+    }
+
     #[test]
     fn test_schnorr_sign_verify() {
         let kp = generate_keypair();
@@ -331,6 +624,76 @@ mod tests {
         assert!(!invalid);
     }
 
+    /// BIP-340 test vectors 0-3: (seckey, pubkey, aux_rand, message, signature)
+    const BIP340_VECTORS: &[(&str, &str, &str, &str, &str)] = &[
+        (
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "E907831F80848D1069A5371B402410364BDF1C5F8307B0084C55F1CE2DCA821525F66A4A85EA8B71E482A74F382D2CE5EBEEE8FDB2172F477DF4900D310536C0",
+        ),
+        (
+            "B7E151628AED2A6ABF7158809CF4F3C762E7160F38B4DA56A784D9045190CFEF",
+            "DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "243F6A8885A308D313198A2E03707344A4093822299F31D0082EFA98EC4E6C89",
+            "6896BD60EEAE296DB48A229FF71DFE071BDE413E6D43F917DC8DCF8C78DE33418906D11AC976ABCCB20B091292BFF4EA897EFCB639EA871CFA95F6DE339E4B0A",
+        ),
+        (
+            "C90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B14E5C9",
+            "DD308AFEC5777E13121FA72B9CC1B7CC0139715309B086C960E18FD969774EB8",
+            "C87AA53824B4D7AE2EB035A2B5BBBCCC080E76CDC6D1692C4B0B62D798E6D906",
+            "7E2D58D8B3BCDF1ABADEC7829054F90DDA9805AAB56C77333024B9D0A508B75C",
+            "5831AAEED7B44BB74E5EAB94BA9D4294C49BCF2A60728D8B4C200F50DD313C1BAB745879A5AD954A72C45A91C3A51D3C7ADEA98D82F8481E0E1E03674A6F3FB7",
+        ),
+        (
+            "0B432B2677937381AEF05BB02A66ECD012773062CF3FA2549E44F58ED2401710",
+            "25D1DFF95105F5253C4022F628A996AD3A0D95FBF21D468A1B33F8C160D8F517",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF",
+            "7EB0509757E246F19449885651611CB965ECC1A187DD51B64FDA1EDC9637D5EC97582B9CB13DB3933705B32BA982AF5AF25FD78881EBB32771FC5922EFC66EA3",
+        ),
+    ];
+
+    fn hex32(s: &str) -> [u8; 32] {
+        hex::decode(s).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_bip340_vectors() {
+        for (seckey, pubkey, aux_rand, message, signature) in BIP340_VECTORS {
+            let seckey = hex::decode(seckey).unwrap();
+            assert_eq!(
+                get_public_key(seckey.clone()).unwrap(),
+                pubkey.to_lowercase()
+            );
+
+            let sig =
+                schnorr_sign_digest_with_aux(&hex32(message), &seckey, &hex32(aux_rand)).unwrap();
+            assert_eq!(hex::encode_upper(sig), *signature);
+        }
+    }
+
+    #[test]
+    fn test_schnorr_sign_with_aux_deterministic() {
+        let kp = generate_keypair();
+        let message = b"Hello, World!";
+        let aux = [7u8; 32];
+
+        let sig1 = schnorr_sign_with_aux(message, kp.private_key.clone(), aux).unwrap();
+        let sig2 = schnorr_sign_with_aux(message, kp.private_key.clone(), aux).unwrap();
+        assert_eq!(sig1, sig2);
+
+        // Different aux randomness gives a different, equally valid signature
+        let sig3 = schnorr_sign_with_aux(message, kp.private_key.clone(), [8u8; 32]).unwrap();
+        assert_ne!(sig1, sig3);
+
+        let pubkey_bytes = hex::decode(&kp.public_key).unwrap();
+        assert!(schnorr_verify(message, sig1, pubkey_bytes.clone()).unwrap());
+        assert!(schnorr_verify(message, sig3, pubkey_bytes).unwrap());
+    }
+
     #[test]
     fn test_schnorr_invalid_signature_length() {
         let kp = generate_keypair();
@@ -1,7 +1,7 @@
 //! BuildIt Crypto - Shared cryptographic primitives for BuildIt Network
 //!
 //! This crate provides:
-//! - NIP-44 ChaCha20-Poly1305 encryption
+//! - NIP-44 v2 encryption (ChaCha20 + HMAC-SHA256)
 //! - NIP-17 gift wrap/unwrap
 //! - Key derivation (Argon2id, HKDF)
 //! - secp256k1 signing/verification
@@ -17,23 +17,31 @@ mod error;
 pub mod generated;
 mod keys;
 mod multisig;
+mod nip04;
 mod nip17;
 mod nip44;
 mod nostr;
 mod ratchet;
+mod recovery;
+mod selftest;
+mod update;
 
 pub use aes::*;
 pub use duress::*;
 pub use error::CryptoError;
 pub use keys::*;
 pub use multisig::*;
+pub use nip04::*;
 pub use nip17::*;
 pub use nip44::*;
 pub use nostr::*;
 pub use ratchet::*;
+pub use recovery::*;
+pub use selftest::*;
+pub use update::*;
 
 use rand::rngs::OsRng;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 uniffi::include_scaffolding!("buildit_crypto");
 
@@ -60,7 +68,20 @@ pub fn hex_to_bytes(hex_string: String) -> Result<Vec<u8>, CryptoError> {
 ///
 /// SECURITY: Uses OsRng (operating system's cryptographically secure RNG)
 pub fn randomize_timestamp(timestamp: i64, range_seconds: u32) -> i64 {
-    let offset = OsRng.gen_range(-(range_seconds as i64)..=(range_seconds as i64));
+    randomize_timestamp_with_rng(&mut OsRng, timestamp, range_seconds)
+}
+
+/// Randomize a timestamp within a range using the given RNG
+///
+/// The offset is uniform over `[-range_seconds, range_seconds]`. Pass a
+/// seeded RNG to reproduce offsets when auditing the distribution;
+/// production code should use [`randomize_timestamp`].
+pub fn randomize_timestamp_with_rng<R: RngCore + ?Sized>(
+    rng: &mut R,
+    timestamp: i64,
+    range_seconds: u32,
+) -> i64 {
+    let offset = rng.gen_range(-(range_seconds as i64)..=(range_seconds as i64));
     timestamp + offset
 }
 
@@ -114,4 +135,63 @@ mod tests {
             assert!(randomized <= base + range as i64);
         }
     }
+
+    #[test]
+    fn test_randomize_timestamp_with_seeded_rng_is_deterministic() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let base = 1700000000i64;
+        let range = 172800u32;
+
+        let mut first = StdRng::seed_from_u64(42);
+        let mut second = StdRng::seed_from_u64(42);
+        let a: Vec<i64> = (0..16)
+            .map(|_| randomize_timestamp_with_rng(&mut first, base, range))
+            .collect();
+        let b: Vec<i64> = (0..16)
+            .map(|_| randomize_timestamp_with_rng(&mut second, base, range))
+            .collect();
+        assert_eq!(a, b);
+
+        let mut other = StdRng::seed_from_u64(43);
+        let c: Vec<i64> = (0..16)
+            .map(|_| randomize_timestamp_with_rng(&mut other, base, range))
+            .collect();
+        assert_ne!(a, c);
+
+        // A zero range leaves the timestamp untouched
+        assert_eq!(randomize_timestamp_with_rng(&mut first, base, 0), base);
+    }
+
+    #[test]
+    fn test_randomize_timestamp_covers_range_uniformly() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let base = 1700000000i64;
+        let range = 10u32;
+        let buckets = 2 * range as usize + 1;
+        let samples = 21_000;
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = vec![0usize; buckets];
+        for _ in 0..samples {
+            let offset = randomize_timestamp_with_rng(&mut rng, base, range) - base;
+            assert!((-(range as i64)..=range as i64).contains(&offset));
+            counts[(offset + range as i64) as usize] += 1;
+        }
+
+        // Every offset occurs, each within 20% of the expected share
+        let expected = samples / buckets;
+        for (i, &count) in counts.iter().enumerate() {
+            assert!(
+                count.abs_diff(expected) < expected / 5,
+                "offset {} occurred {} times, expected about {}",
+                i as i64 - range as i64,
+                count,
+                expected
+            );
+        }
+    }
 }
@@ -13,14 +13,26 @@
 
 use crate::error::CryptoError;
 use crate::keys::{generate_keypair, get_public_key};
+use crate::nip44::{nip44_decrypt, nip44_encrypt};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::rngs::OsRng;
 use rand::Rng;
 use secp256k1::{Scalar, Secp256k1, SecretKey};
 use sha2::{Digest, Sha256};
-use zeroize::Zeroize;
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Version byte of the binary `KeyShare` encoding
+const KEY_SHARE_FORMAT_VERSION: u8 = 1;
+
+/// Size of the fixed part of the encoding: version, index, threshold,
+/// total_shares and the 32-byte share secret
+const KEY_SHARE_FIXED_LEN: usize = 1 + 4 + 4 + 4 + 32;
 
 /// A key share for one participant in the threshold scheme
-#[derive(Debug, Clone)]
+///
+/// Debug is manually implemented so that `share_secret` never reaches logs.
+#[derive(Clone)]
 pub struct KeyShare {
     /// Index of this share (1-based, never 0)
     pub index: u32,
@@ -36,6 +48,139 @@ pub struct KeyShare {
     pub threshold: u32,
 }
 
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyShare")
+            .field("index", &self.index)
+            .field("share_secret", &"[REDACTED]")
+            .field("share_public_key", &self.share_public_key)
+            .field("group_id", &self.group_id)
+            .field("total_shares", &self.total_shares)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+impl KeyShare {
+    /// Encode the share for distribution
+    ///
+    /// Format (all integers big-endian):
+    /// `version(1) || index(4) || threshold(4) || total_shares(4) ||
+    ///  share_secret(32) || len(2) || group_id || len(2) || share_public_key`
+    ///
+    /// SECURITY: The output contains the share secret. Encrypt it (see
+    /// `encrypt_share_for`) and zeroize it once sent.
+    pub fn to_bytes(&self) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
+        if self.share_secret.len() != 32 {
+            return Err(CryptoError::InvalidKey);
+        }
+        let group_id_len =
+            u16::try_from(self.group_id.len()).map_err(|_| CryptoError::InvalidKey)?;
+        let public_key_len =
+            u16::try_from(self.share_public_key.len()).map_err(|_| CryptoError::InvalidKey)?;
+
+        let mut out = Zeroizing::new(Vec::with_capacity(
+            KEY_SHARE_FIXED_LEN + 4 + self.group_id.len() + self.share_public_key.len(),
+        ));
+        out.push(KEY_SHARE_FORMAT_VERSION);
+        out.extend_from_slice(&self.index.to_be_bytes());
+        out.extend_from_slice(&self.threshold.to_be_bytes());
+        out.extend_from_slice(&self.total_shares.to_be_bytes());
+        out.extend_from_slice(&self.share_secret);
+        out.extend_from_slice(&group_id_len.to_be_bytes());
+        out.extend_from_slice(self.group_id.as_bytes());
+        out.extend_from_slice(&public_key_len.to_be_bytes());
+        out.extend_from_slice(self.share_public_key.as_bytes());
+        Ok(out)
+    }
+
+    /// Decode a share produced by `to_bytes`
+    ///
+    /// Rejects unknown versions, truncated or trailing data, a zero index and
+    /// inconsistent threshold parameters.
+    pub fn from_bytes(bytes: &[u8]) -> Result<KeyShare, CryptoError> {
+        if bytes.len() < KEY_SHARE_FIXED_LEN + 4 {
+            return Err(CryptoError::InvalidKey);
+        }
+        if bytes[0] != KEY_SHARE_FORMAT_VERSION {
+            return Err(CryptoError::InvalidVersion);
+        }
+
+        let read_u32 = |offset: usize| {
+            u32::from_be_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let index = read_u32(1);
+        let threshold = read_u32(5);
+        let total_shares = read_u32(9);
+        let share_secret = bytes[13..KEY_SHARE_FIXED_LEN].to_vec();
+
+        let mut offset = KEY_SHARE_FIXED_LEN;
+        let mut read_string = || -> Result<String, CryptoError> {
+            let len_bytes = bytes
+                .get(offset..offset + 2)
+                .ok_or(CryptoError::InvalidKey)?;
+            let len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+            let value = bytes
+                .get(offset + 2..offset + 2 + len)
+                .ok_or(CryptoError::InvalidKey)?;
+            offset += 2 + len;
+            String::from_utf8(value.to_vec()).map_err(|_| CryptoError::InvalidKey)
+        };
+        let group_id = read_string()?;
+        let share_public_key = read_string()?;
+        if offset != bytes.len() {
+            return Err(CryptoError::InvalidKey);
+        }
+
+        if index == 0 || threshold < 2 || total_shares < threshold || index > total_shares {
+            return Err(CryptoError::InvalidKey);
+        }
+        SecretKey::from_slice(&share_secret).map_err(|_| CryptoError::InvalidKey)?;
+
+        Ok(KeyShare {
+            index,
+            share_secret,
+            share_public_key,
+            group_id,
+            total_shares,
+            threshold,
+        })
+    }
+}
+
+/// Encrypt a key share to its recipient with NIP-44
+///
+/// The share is encoded with `KeyShare::to_bytes` and base64-wrapped, since
+/// NIP-44 carries UTF-8 plaintext.
+pub fn encrypt_share_for(
+    share: &KeyShare,
+    recipient_pubkey: String,
+    sender_privkey: Vec<u8>,
+) -> Result<String, CryptoError> {
+    let bytes = share.to_bytes()?;
+    nip44_encrypt(sender_privkey, recipient_pubkey, BASE64.encode(&*bytes))
+}
+
+/// Decrypt a key share produced by `encrypt_share_for`
+pub fn decrypt_share(
+    payload: String,
+    sender_pubkey: String,
+    recipient_privkey: Vec<u8>,
+) -> Result<KeyShare, CryptoError> {
+    let plaintext = Zeroizing::new(nip44_decrypt(recipient_privkey, sender_pubkey, payload)?);
+    let bytes = Zeroizing::new(
+        BASE64
+            .decode(&*plaintext)
+            .map_err(|_| CryptoError::InvalidKey)?,
+    );
+    KeyShare::from_bytes(&bytes)
+}
+
 /// Configuration for creating a threshold key group
 #[derive(Debug, Clone)]
 pub struct ThresholdConfig {
@@ -114,20 +259,7 @@ pub struct KeyRotationProposal {
 pub fn generate_threshold_key(config: ThresholdConfig) -> Result<ThresholdKeyGroup, CryptoError> {
     let threshold = config.threshold;
     let total_shares = config.total_shares;
-
-    // Validate parameters
-    if threshold < 2 {
-        return Err(CryptoError::InvalidKey);
-    }
-    if total_shares < threshold {
-        return Err(CryptoError::InvalidKey);
-    }
-    if total_shares > 255 {
-        // Practical limit for share management
-        return Err(CryptoError::InvalidKey);
-    }
-
-    let secp = Secp256k1::new();
+    validate_threshold_params(threshold, total_shares)?;
 
     // Generate random group secret key
     let group_keypair = generate_keypair();
@@ -146,13 +278,56 @@ pub fn generate_threshold_key(config: ThresholdConfig) -> Result<ThresholdKeyGro
         hex::encode(hasher.finalize())[..32].to_string()
     };
 
+    let shares = split_secret(&group_secret, &group_id, threshold, total_shares);
+    group_secret.zeroize();
+
+    Ok(ThresholdKeyGroup {
+        group_id,
+        group_public_key,
+        shares: shares?,
+        threshold,
+        total_shares,
+    })
+}
+
+/// Check M-of-N parameters: 2 <= M <= N <= 255
+fn validate_threshold_params(threshold: u32, total_shares: u32) -> Result<(), CryptoError> {
+    if threshold < 2 {
+        return Err(CryptoError::InvalidKey);
+    }
+    if total_shares < threshold {
+        return Err(CryptoError::InvalidKey);
+    }
+    if total_shares > 255 {
+        // Practical limit for share management
+        return Err(CryptoError::InvalidKey);
+    }
+    Ok(())
+}
+
+/// Split an existing 32-byte secret into `total_shares` Shamir shares, any
+/// `threshold` of which reconstruct it
+///
+/// The secret must be a valid secp256k1 scalar. Polynomial coefficients are
+/// zeroized before returning.
+pub(crate) fn split_secret(
+    secret: &[u8],
+    group_id: &str,
+    threshold: u32,
+    total_shares: u32,
+) -> Result<Vec<KeyShare>, CryptoError> {
+    validate_threshold_params(threshold, total_shares)?;
+    SecretKey::from_slice(secret).map_err(|_| CryptoError::InvalidKey)?;
+
+    let secp = Secp256k1::new();
+
     // Generate polynomial coefficients for Shamir's Secret Sharing
     // f(x) = secret + a1*x + a2*x^2 + ... + a_{t-1}*x^{t-1}
     let mut coefficients: Vec<[u8; 32]> = Vec::with_capacity(threshold as usize);
 
     // First coefficient is the secret itself
     let mut secret_bytes = [0u8; 32];
-    secret_bytes.copy_from_slice(&group_secret);
+    secret_bytes.copy_from_slice(secret);
     coefficients.push(secret_bytes);
 
     // Generate random coefficients for degrees 1 through threshold-1
@@ -166,7 +341,7 @@ pub fn generate_threshold_key(config: ThresholdConfig) -> Result<ThresholdKeyGro
     // Evaluate the polynomial at points 1, 2, ..., total_shares
     let mut shares = Vec::with_capacity(total_shares as usize);
 
-    for i in 1..=total_shares {
+    let result = (1..=total_shares).try_for_each(|i| {
         let share_secret = evaluate_polynomial_at_point(&coefficients, i, &secp)?;
 
         // Derive public key for this share
@@ -176,25 +351,19 @@ pub fn generate_threshold_key(config: ThresholdConfig) -> Result<ThresholdKeyGro
             index: i,
             share_secret,
             share_public_key: share_pubkey,
-            group_id: group_id.clone(),
+            group_id: group_id.to_string(),
             total_shares,
             threshold,
         });
-    }
+        Ok(())
+    });
 
     // Zeroize sensitive data
-    group_secret.zeroize();
     for coeff in coefficients.iter_mut() {
         coeff.zeroize();
     }
 
-    Ok(ThresholdKeyGroup {
-        group_id,
-        group_public_key,
-        shares,
-        threshold,
-        total_shares,
-    })
+    result.map(|()| shares)
 }
 
 /// Evaluate a polynomial at a given point using secp256k1 scalar arithmetic
@@ -240,13 +409,49 @@ fn evaluate_polynomial_at_point(
     Ok(result.secret_bytes().to_vec())
 }
 
+/// 32-byte scalar buffer that is zeroized on drop
+///
+/// `SecretKey` is `Copy`, so its temporaries cannot all be tracked down and
+/// wiped. Intermediate values are therefore kept in these buffers, and each
+/// `SecretKey` built from one is erased as soon as it has been consumed.
+#[derive(Zeroize, ZeroizeOnDrop)]
+struct ScalarBuf([u8; 32]);
+
+impl ScalarBuf {
+    /// Move a key's bytes into a buffer and erase the key
+    fn from_key(mut key: SecretKey) -> Self {
+        let buf = Self(key.secret_bytes());
+        key.non_secure_erase();
+        buf
+    }
+
+    fn to_key(&self) -> Result<SecretKey, CryptoError> {
+        SecretKey::from_slice(&self.0).map_err(|_| CryptoError::InvalidKey)
+    }
+}
+
+/// Reconstruct the group secret key from M shares using Lagrange interpolation
+///
+/// Wrapper kept for UniFFI, which cannot return `Zeroizing`. Rust callers
+/// should use `reconstruct_secret_zeroizing` so the secret is wiped on drop.
+///
+/// SECURITY: The returned secret MUST be zeroized by the caller.
+pub fn reconstruct_secret(shares: Vec<KeyShare>) -> Result<Vec<u8>, CryptoError> {
+    let mut secret = reconstruct_secret_zeroizing(shares)?;
+    Ok(std::mem::take(&mut *secret))
+}
+
 /// Reconstruct the group secret key from M shares using Lagrange interpolation
 ///
 /// SECURITY:
 /// - Requires exactly `threshold` shares to reconstruct
 /// - Uses constant-time scalar arithmetic via secp256k1
-/// - The reconstructed secret is returned and MUST be zeroized by the caller
-pub fn reconstruct_secret(shares: Vec<KeyShare>) -> Result<Vec<u8>, CryptoError> {
+/// - The secret is zeroized when the returned value is dropped
+/// - Per-share terms and Lagrange coefficients are zeroized on every path,
+///   including errors
+pub fn reconstruct_secret_zeroizing(
+    shares: Vec<KeyShare>,
+) -> Result<Zeroizing<Vec<u8>>, CryptoError> {
     if shares.is_empty() {
         return Err(CryptoError::InvalidKey);
     }
@@ -277,52 +482,71 @@ pub fn reconstruct_secret(shares: Vec<KeyShare>) -> Result<Vec<u8>, CryptoError>
 
     // Lagrange interpolation at x=0 to recover the secret
     // secret = sum_i( share_i * product_{j!=i}( (0 - x_j) / (x_i - x_j) ) )
-    let mut terms: Vec<Vec<u8>> = Vec::new();
-
-    for (i, share_i) in active_shares.iter().enumerate() {
-        let mut lagrange_coeff = compute_lagrange_coefficient(i, active_shares)?;
+    let coefficients = compute_lagrange_coefficients(active_shares)?;
+    let mut terms: Vec<ScalarBuf> = Vec::with_capacity(active_shares.len());
 
+    for (share_i, lagrange_coeff) in active_shares.iter().zip(coefficients.iter()) {
         // Multiply share by Lagrange coefficient
-        let share_key =
+        let mut share_key =
             SecretKey::from_slice(&share_i.share_secret).map_err(|_| CryptoError::InvalidKey)?;
-        let lagrange_scalar = Scalar::from(
-            SecretKey::from_slice(&lagrange_coeff).map_err(|_| CryptoError::InvalidKey)?,
-        );
-        let term = share_key
-            .mul_tweak(&lagrange_scalar)
-            .map_err(|_| CryptoError::InvalidKey)?;
+        let term = share_key.mul_tweak(&Scalar::from(lagrange_coeff.to_key()?));
+        share_key.non_secure_erase();
 
-        terms.push(term.secret_bytes().to_vec());
-        lagrange_coeff.zeroize();
+        terms.push(ScalarBuf::from_key(
+            term.map_err(|_| CryptoError::InvalidKey)?,
+        ));
     }
 
     // Sum all terms
-    if terms.is_empty() {
-        return Err(CryptoError::InvalidKey);
+    let mut sum = ScalarBuf(terms[0].0);
+    for term in terms.iter().skip(1) {
+        let mut sum_key = sum.to_key()?;
+        let next = sum_key.add_tweak(&Scalar::from(term.to_key()?));
+        sum_key.non_secure_erase();
+        sum = ScalarBuf::from_key(next.map_err(|_| CryptoError::InvalidKey)?);
     }
 
-    let mut sum = SecretKey::from_slice(&terms[0]).map_err(|_| CryptoError::InvalidKey)?;
-    for term in terms.iter().skip(1) {
-        let term_key = SecretKey::from_slice(term).map_err(|_| CryptoError::InvalidKey)?;
-        let term_scalar = Scalar::from(term_key);
-        sum = sum
-            .add_tweak(&term_scalar)
-            .map_err(|_| CryptoError::InvalidKey)?;
+    Ok(Zeroizing::new(sum.0.to_vec()))
+}
+
+/// Compute the Lagrange coefficients for all shares
+/// L_i(0) = product_{j!=i}( (0 - x_j) / (x_i - x_j) )
+///
+/// The denominators are inverted together with Montgomery's trick, so a
+/// reconstruction performs a single modular inversion regardless of the
+/// threshold. The coefficients depend only on the public share indices, but
+/// they are returned in wiped buffers like every other reconstruction
+/// intermediate.
+fn compute_lagrange_coefficients(shares: &[KeyShare]) -> Result<Vec<ScalarBuf>, CryptoError> {
+    let mut numerators = Vec::with_capacity(shares.len());
+    let mut denominators = Vec::with_capacity(shares.len());
+    for i in 0..shares.len() {
+        let (numerator, denominator) = lagrange_fraction(i, shares)?;
+        numerators.push(numerator);
+        denominators.push(denominator);
     }
 
-    let secret = sum.secret_bytes().to_vec();
+    let inverses = batch_inverse_scalars(&denominators)?;
 
-    // Zeroize intermediate values
-    for term in terms.iter_mut() {
-        term.zeroize();
+    let mut coefficients = Vec::with_capacity(shares.len());
+    for (numerator, inverse) in numerators.iter_mut().zip(inverses.iter()) {
+        let coefficient = numerator.mul_tweak(&Scalar::from(inverse.to_key()?));
+        numerator.non_secure_erase();
+        coefficients.push(ScalarBuf::from_key(
+            coefficient.map_err(|_| CryptoError::InvalidKey)?,
+        ));
     }
 
-    Ok(secret)
+    for denominator in denominators.iter_mut() {
+        denominator.non_secure_erase();
+    }
+
+    Ok(coefficients)
 }
 
-/// Compute the Lagrange coefficient for share at position `i`
-/// L_i(0) = product_{j!=i}( (0 - x_j) / (x_i - x_j) )
-fn compute_lagrange_coefficient(i: usize, shares: &[KeyShare]) -> Result<Vec<u8>, CryptoError> {
+/// Compute the numerator and denominator of the Lagrange coefficient for the
+/// share at position `i`
+fn lagrange_fraction(i: usize, shares: &[KeyShare]) -> Result<(SecretKey, SecretKey), CryptoError> {
     let x_i = shares[i].index;
 
     // We work in the secp256k1 scalar field using modular arithmetic
@@ -366,48 +590,62 @@ fn compute_lagrange_coefficient(i: usize, shares: &[KeyShare]) -> Result<Vec<u8>
             .map_err(|_| CryptoError::InvalidKey)?;
     }
 
-    // Compute numerator / denominator (modular inverse via Fermat's little theorem)
-    // For secp256k1 scalar field, inverse of a is a^(n-2) mod n
-    // We use the secret key negate trick: inv(d) can be computed as we compute
-    // numerator * d^{-1} directly.
-    // Actually, secp256k1 library doesn't expose modular inverse directly for scalars.
-    // We'll compute d^{-1} by using the fact that for the group order n:
-    // d^{-1} = d^{n-2} mod n
-    // But this requires exponentiation which we don't have directly.
-    //
-    // Alternative approach: compute the result using the fraction representation
-    // We can use the private key API which provides modular inverse implicitly
-    // through multiplication chains.
-
-    // For small indices (up to 255), we can compute the inverse numerically
-    // by finding k such that (denominator * k) mod n = 1
-    // This is too expensive for large fields, but since our indices are small,
-    // we can use Euler's method or just iterate.
-
-    // Better approach: use extended Euclidean algorithm on the scalar bytes
-    // But secp256k1 doesn't expose this. Instead, we'll use exponentiation
-    // by squaring with n-2.
-
-    // Simplest correct approach: use secp256k1's tweak operations
-    // We need: numerator * denominator^(-1) mod n
-    // Since denominator is a valid SecretKey, we can compute its inverse
-    // by negating and using the group order properties.
-
-    // Actually, we can compute the modular inverse of the denominator
-    // using repeated squaring (square-and-multiply) with exponent = n-2
-    // where n is the secp256k1 group order.
-    let denom_inverse = modular_inverse_scalar(denominator)?;
-    let denom_inv_scalar = Scalar::from(denom_inverse);
-
-    let result = numerator
-        .mul_tweak(&denom_inv_scalar)
-        .map_err(|_| CryptoError::InvalidKey)?;
+    Ok((numerator, denominator))
+}
 
-    Ok(result.secret_bytes().to_vec())
+/// Invert every scalar in `values` with a single modular inversion
+/// (Montgomery's trick)
+///
+/// With prefix products p_k = v_0 * ... * v_k, one inversion of p_{n-1}
+/// yields every inverse by walking back: v_k^(-1) = p_{k-1} * p_k^(-1) and
+/// p_{k-1}^(-1) = v_k * p_k^(-1). All values must be non-zero, which
+/// `SecretKey` already guarantees.
+fn batch_inverse_scalars(values: &[SecretKey]) -> Result<Vec<ScalarBuf>, CryptoError> {
+    if values.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // prefix[k] = v_0 * ... * v_k
+    let mut prefix: Vec<ScalarBuf> = Vec::with_capacity(values.len());
+    prefix.push(ScalarBuf(values[0].secret_bytes()));
+    for value in values.iter().skip(1) {
+        let last = prefix[prefix.len() - 1].to_key()?;
+        let product = last
+            .mul_tweak(&Scalar::from(*value))
+            .map_err(|_| CryptoError::InvalidKey)?;
+        prefix.push(ScalarBuf::from_key(product));
+    }
+
+    // Running inverse of prefix[k], starting from the full product
+    let mut running =
+        ScalarBuf::from_key(modular_inverse_scalar(prefix[values.len() - 1].to_key()?)?);
+
+    let mut inverses: Vec<ScalarBuf> = Vec::with_capacity(values.len());
+    for k in (1..values.len()).rev() {
+        let inverse = prefix[k - 1]
+            .to_key()?
+            .mul_tweak(&Scalar::from(running.to_key()?))
+            .map_err(|_| CryptoError::InvalidKey)?;
+        inverses.push(ScalarBuf::from_key(inverse));
+
+        let next = running
+            .to_key()?
+            .mul_tweak(&Scalar::from(values[k]))
+            .map_err(|_| CryptoError::InvalidKey)?;
+        running = ScalarBuf::from_key(next);
+    }
+    inverses.push(running);
+    inverses.reverse();
+
+    Ok(inverses)
 }
 
 /// Compute modular inverse of a secp256k1 scalar using Fermat's little theorem
 /// For prime p, a^(-1) = a^(p-2) mod p
+///
+/// Always performs 256 squarings and a multiplication for every set bit of
+/// the fixed public exponent, so the running time does not depend on the
+/// input. Reconstruction calls this once via `batch_inverse_scalars`.
 fn modular_inverse_scalar(scalar: SecretKey) -> Result<SecretKey, CryptoError> {
     // secp256k1 group order n:
     // FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141
@@ -545,6 +783,113 @@ pub fn verify_rotation_proposal(
 mod tests {
     use super::*;
 
+    fn assert_same_share(a: &KeyShare, b: &KeyShare) {
+        assert_eq!(a.index, b.index);
+        assert_eq!(a.share_secret, b.share_secret);
+        assert_eq!(a.share_public_key, b.share_public_key);
+        assert_eq!(a.group_id, b.group_id);
+        assert_eq!(a.total_shares, b.total_shares);
+        assert_eq!(a.threshold, b.threshold);
+    }
+
+    fn sample_share() -> KeyShare {
+        let config = ThresholdConfig {
+            threshold: 2,
+            total_shares: 3,
+            group_name: "Share Group".to_string(),
+        };
+        generate_threshold_key(config).unwrap().shares[1].clone()
+    }
+
+    #[test]
+    fn test_key_share_bytes_round_trip() {
+        let share = sample_share();
+        let bytes = share.to_bytes().unwrap();
+        assert_eq!(bytes[0], KEY_SHARE_FORMAT_VERSION);
+        assert_eq!(
+            bytes.len(),
+            KEY_SHARE_FIXED_LEN + 4 + share.group_id.len() + share.share_public_key.len()
+        );
+
+        let decoded = KeyShare::from_bytes(&bytes).unwrap();
+        assert_same_share(&share, &decoded);
+    }
+
+    #[test]
+    fn test_key_share_from_bytes_rejects_malformed() {
+        let share = sample_share();
+        let bytes = share.to_bytes().unwrap();
+
+        // Truncated and trailing data
+        assert!(KeyShare::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(KeyShare::from_bytes(&bytes[..10]).is_err());
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        assert!(KeyShare::from_bytes(&trailing).is_err());
+
+        // Unknown version
+        let mut wrong_version = bytes.to_vec();
+        wrong_version[0] = 2;
+        assert!(matches!(
+            KeyShare::from_bytes(&wrong_version),
+            Err(CryptoError::InvalidVersion)
+        ));
+
+        // Zero index
+        let mut zero_index = bytes.to_vec();
+        zero_index[1..5].copy_from_slice(&0u32.to_be_bytes());
+        assert!(KeyShare::from_bytes(&zero_index).is_err());
+
+        // Threshold above total
+        let mut bad_threshold = bytes.to_vec();
+        bad_threshold[5..9].copy_from_slice(&4u32.to_be_bytes());
+        assert!(KeyShare::from_bytes(&bad_threshold).is_err());
+
+        // Share secret outside the scalar field
+        let mut zero_secret = bytes.to_vec();
+        zero_secret[13..45].fill(0);
+        assert!(KeyShare::from_bytes(&zero_secret).is_err());
+
+        // Short share secret cannot be encoded
+        let mut short = share.clone();
+        short.share_secret.truncate(31);
+        assert!(short.to_bytes().is_err());
+    }
+
+    #[test]
+    fn test_key_share_debug_redacts_secret() {
+        let share = sample_share();
+        let debug = format!("{:?}", share);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(!debug.contains(&format!("{:?}", share.share_secret)));
+        assert!(debug.contains(&share.group_id));
+    }
+
+    #[test]
+    fn test_encrypt_share_for_recipient() {
+        let share = sample_share();
+        let sender = generate_keypair();
+        let recipient = generate_keypair();
+
+        let payload = encrypt_share_for(
+            &share,
+            recipient.public_key.clone(),
+            sender.private_key.clone(),
+        )
+        .unwrap();
+        let decrypted = decrypt_share(
+            payload.clone(),
+            sender.public_key.clone(),
+            recipient.private_key.clone(),
+        )
+        .unwrap();
+        assert_same_share(&share, &decrypted);
+
+        // A third party cannot open it
+        let eavesdropper = generate_keypair();
+        assert!(decrypt_share(payload, sender.public_key, eavesdropper.private_key).is_err());
+    }
+
     #[test]
     fn test_generate_threshold_key_2_of_3() {
         let config = ThresholdConfig {
@@ -602,6 +947,176 @@ mod tests {
         assert_eq!(reconstructed_pubkey, group.group_public_key);
     }
 
+    /// Shares carrying only the given indices (enough for coefficient math)
+    fn index_shares(indices: &[u32]) -> Vec<KeyShare> {
+        indices
+            .iter()
+            .map(|&index| KeyShare {
+                index,
+                share_secret: vec![1u8; 32],
+                share_public_key: String::new(),
+                group_id: "g".to_string(),
+                total_shares: 255,
+                threshold: indices.len() as u32,
+            })
+            .collect()
+    }
+
+    /// Reference path: one full modular inversion per share
+    fn per_share_coefficients(shares: &[KeyShare]) -> Vec<[u8; 32]> {
+        (0..shares.len())
+            .map(|i| {
+                let (numerator, denominator) = lagrange_fraction(i, shares).unwrap();
+                let inverse = modular_inverse_scalar(denominator).unwrap();
+                numerator
+                    .mul_tweak(&Scalar::from(inverse))
+                    .unwrap()
+                    .secret_bytes()
+            })
+            .collect()
+    }
+
+    fn scalar_from_u32(value: u32) -> SecretKey {
+        let mut bytes = [0u8; 32];
+        bytes[28..32].copy_from_slice(&value.to_be_bytes());
+        SecretKey::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_modular_inverse_small_and_large_values() {
+        let one = scalar_from_u32(1);
+        let random = SecretKey::from_slice(&generate_keypair().private_key).unwrap();
+        let values = [
+            scalar_from_u32(1),
+            scalar_from_u32(2),
+            scalar_from_u32(3),
+            scalar_from_u32(255),
+            scalar_from_u32(1).negate(),
+            random,
+        ];
+
+        for value in values {
+            let inverse = modular_inverse_scalar(value).unwrap();
+            let product = value.mul_tweak(&Scalar::from(inverse)).unwrap();
+            assert_eq!(product, one);
+        }
+    }
+
+    #[test]
+    fn test_batch_inverse_matches_per_share() {
+        let index_sets: [&[u32]; 5] = [
+            &[1, 2],
+            &[3, 1],
+            &[2, 5, 7],
+            &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+            &[255, 17, 128, 4, 99, 1],
+        ];
+
+        for indices in index_sets {
+            let shares = index_shares(indices);
+            let batched: Vec<[u8; 32]> = compute_lagrange_coefficients(&shares)
+                .unwrap()
+                .iter()
+                .map(|c| c.0)
+                .collect();
+            assert_eq!(
+                batched,
+                per_share_coefficients(&shares),
+                "indices {:?}",
+                indices
+            );
+        }
+
+        // Each batched inverse really is an inverse
+        let values: Vec<SecretKey> = (1..=20).map(scalar_from_u32).collect();
+        let inverses = batch_inverse_scalars(&values).unwrap();
+        assert_eq!(inverses.len(), values.len());
+        for (value, inverse) in values.iter().zip(inverses.iter()) {
+            let product = value
+                .mul_tweak(&Scalar::from(inverse.to_key().unwrap()))
+                .unwrap();
+            assert_eq!(product, scalar_from_u32(1));
+        }
+
+        assert!(batch_inverse_scalars(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_inverse_large_threshold() {
+        let indices: Vec<u32> = (1..=64).collect();
+        let shares = index_shares(&indices);
+
+        let batched: Vec<[u8; 32]> = compute_lagrange_coefficients(&shares)
+            .unwrap()
+            .iter()
+            .map(|c| c.0)
+            .collect();
+        assert_eq!(batched, per_share_coefficients(&shares));
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with `cargo test --release -- --ignored`"]
+    fn bench_batch_inverse_large_threshold() {
+        let indices: Vec<u32> = (1..=64).collect();
+        let shares = index_shares(&indices);
+
+        let start = std::time::Instant::now();
+        compute_lagrange_coefficients(&shares).unwrap();
+        let batched_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        per_share_coefficients(&shares);
+        let per_share_time = start.elapsed();
+
+        // One inversion for the whole set must beat one per share
+        assert!(
+            batched_time < per_share_time,
+            "batched {:?} not faster than per-share {:?}",
+            batched_time,
+            per_share_time
+        );
+    }
+
+    #[test]
+    fn test_scalar_buf_wipes_its_bytes() {
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<ScalarBuf>();
+
+        let key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let mut buf = ScalarBuf::from_key(key);
+        assert_eq!(buf.0, [7u8; 32]);
+        assert_eq!(buf.to_key().unwrap(), key);
+
+        // What drop runs
+        buf.zeroize();
+        assert_eq!(buf.0, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_reconstruct_secret_zeroizing() {
+        let config = ThresholdConfig {
+            threshold: 3,
+            total_shares: 5,
+            group_name: "Zeroize Group".to_string(),
+        };
+        let group = generate_threshold_key(config).unwrap();
+        let shares = group.shares[..3].to_vec();
+
+        let secret = reconstruct_secret_zeroizing(shares.clone()).unwrap();
+        assert_eq!(
+            get_public_key(secret.to_vec()).unwrap(),
+            group.group_public_key
+        );
+
+        // The UniFFI wrapper returns the same secret
+        assert_eq!(reconstruct_secret(shares).unwrap(), *secret);
+
+        // A bad share fails midway, dropping the buffers built so far
+        let mut bad = group.shares[..3].to_vec();
+        bad[2].share_secret = vec![0u8; 32];
+        assert!(reconstruct_secret_zeroizing(bad).is_err());
+    }
+
     #[test]
     fn test_reconstruct_secret_3_of_5() {
         let config = ThresholdConfig {
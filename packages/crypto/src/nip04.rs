@@ -0,0 +1,193 @@
+//! NIP-04 Encrypted Direct Messages (legacy)
+//!
+//! NIP-04 is deprecated in favour of NIP-44: it uses AES-256-CBC without a
+//! MAC, so ciphertexts are malleable and reveal the message length. It is
+//! implemented here so existing DM history can be decrypted and migrated;
+//! new messages must use NIP-44 or the ratchet.
+//!
+//! Content is `base64(ciphertext)?iv=base64(iv)`, keyed directly by the
+//! x-coordinate of the ECDH shared point (no KDF).
+
+use crate::error::CryptoError;
+use crate::keys::parse_recipient_pubkey;
+use aes::Aes256;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, KeyIvInit};
+use secp256k1::SecretKey;
+use zeroize::{Zeroize, Zeroizing};
+
+type Aes256CbcDec = cbc::Decryptor<Aes256>;
+
+/// Separator between ciphertext and IV in NIP-04 content
+const IV_SEPARATOR: &str = "?iv=";
+
+/// AES block (and IV) length
+const BLOCK_LEN: usize = 16;
+
+/// Whether `content` is NIP-04 rather than NIP-44 ciphertext
+///
+/// NIP-44 payloads are plain base64 and can never contain `?iv=`.
+pub fn is_nip04_payload(content: &str) -> bool {
+    content.contains(IV_SEPARATOR)
+}
+
+/// AES key for a NIP-04 conversation: the raw ECDH x-coordinate
+fn shared_key(private_key: &[u8], pubkey: &str) -> Result<Zeroizing<[u8; 32]>, CryptoError> {
+    let secret_key = SecretKey::from_slice(private_key).map_err(|_| CryptoError::InvalidKey)?;
+    let public_key = parse_recipient_pubkey(pubkey)?;
+
+    let mut shared_point = secp256k1::ecdh::shared_secret_point(&public_key, &secret_key);
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&shared_point[..32]);
+    shared_point.zeroize();
+    Ok(key)
+}
+
+/// Encrypt a message using NIP-04
+///
+/// Only for tests of the decryption path; new messages use NIP-44.
+#[cfg(test)]
+pub(crate) fn nip04_encrypt(
+    private_key: Vec<u8>,
+    recipient_pubkey: String,
+    plaintext: String,
+) -> Result<String, CryptoError> {
+    use cbc::cipher::BlockEncryptMut;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+
+    let key = shared_key(&private_key, &recipient_pubkey)?;
+    let mut iv = [0u8; BLOCK_LEN];
+    OsRng.fill_bytes(&mut iv);
+
+    let plaintext = Zeroizing::new(plaintext);
+    let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(&key[..], &iv)
+        .map_err(|_| CryptoError::InvalidKey)?
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+
+    Ok(format!(
+        "{}{}{}",
+        BASE64.encode(ciphertext),
+        IV_SEPARATOR,
+        BASE64.encode(iv)
+    ))
+}
+
+/// Decrypt a message using NIP-04
+///
+/// Fails with `InvalidCiphertext` for malformed content and
+/// `DecryptionFailed` when the key is wrong (bad padding or non-UTF-8
+/// output). Without a MAC, a wrong key is not always detected.
+pub fn nip04_decrypt(
+    private_key: Vec<u8>,
+    sender_pubkey: String,
+    content: String,
+) -> Result<String, CryptoError> {
+    let (ciphertext, iv) = content
+        .split_once(IV_SEPARATOR)
+        .ok_or(CryptoError::InvalidCiphertext)?;
+    let data = BASE64
+        .decode(ciphertext)
+        .map_err(|_| CryptoError::InvalidCiphertext)?;
+    let iv = BASE64
+        .decode(iv)
+        .map_err(|_| CryptoError::InvalidCiphertext)?;
+    if iv.len() != BLOCK_LEN || data.is_empty() || data.len() % BLOCK_LEN != 0 {
+        return Err(CryptoError::InvalidCiphertext);
+    }
+
+    let key = shared_key(&private_key, &sender_pubkey)?;
+    let plaintext = Aes256CbcDec::new_from_slices(&key[..], &iv)
+        .map_err(|_| CryptoError::InvalidKey)?
+        .decrypt_padded_vec_mut::<Pkcs7>(&data)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+
+    String::from_utf8(plaintext).map_err(|e| {
+        let mut bytes = e.into_bytes();
+        bytes.zeroize();
+        CryptoError::DecryptionFailed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::generate_keypair;
+
+    #[test]
+    fn test_nip04_roundtrip() {
+        let alice = generate_keypair();
+        let bob = generate_keypair();
+
+        for plaintext in ["hi", "exactly sixteen!", "a longer message spanning blocks"] {
+            let content = nip04_encrypt(
+                alice.private_key.clone(),
+                bob.public_key.clone(),
+                plaintext.to_string(),
+            )
+            .unwrap();
+            assert!(is_nip04_payload(&content));
+
+            let decrypted =
+                nip04_decrypt(bob.private_key.clone(), alice.public_key.clone(), content).unwrap();
+            assert_eq!(decrypted, plaintext);
+        }
+    }
+
+    #[test]
+    fn test_nip04_rejects_malformed_and_wrong_key() {
+        let alice = generate_keypair();
+        let bob = generate_keypair();
+        let eve = generate_keypair();
+
+        assert_eq!(
+            nip04_decrypt(
+                bob.private_key.clone(),
+                alice.public_key.clone(),
+                "abc".into()
+            ),
+            Err(CryptoError::InvalidCiphertext)
+        );
+        assert_eq!(
+            nip04_decrypt(
+                bob.private_key.clone(),
+                alice.public_key.clone(),
+                "AAAA?iv=AAAA".into()
+            ),
+            Err(CryptoError::InvalidCiphertext)
+        );
+
+        let content = nip04_encrypt(
+            alice.private_key.clone(),
+            bob.public_key.clone(),
+            "meet at noon".to_string(),
+        )
+        .unwrap();
+        assert_ne!(
+            nip04_decrypt(eve.private_key, alice.public_key, content).ok(),
+            Some("meet at noon".to_string())
+        );
+    }
+
+    #[test]
+    fn test_nip44_payload_not_detected_as_nip04() {
+        let alice = generate_keypair();
+        let bob = generate_keypair();
+        let content =
+            crate::nip44::nip44_encrypt(alice.private_key, bob.public_key, "hi".to_string())
+                .unwrap();
+        assert!(!is_nip04_payload(&content));
+    }
+
+    #[test]
+    fn test_nip04_decrypts_fixed_vector() {
+        // Sent by the key 0x02..02 to the key 0x03..03
+        let sender = "4d4b6cd1361032ca9bd2aeb9d900aa4d45d9ead80ac9423374c451a7254d0766";
+        let content = "oUegg6jUHQsYDaK4sCm+iQ==?iv=5O80XPcRcjXSFSWChVBVYQ==";
+        assert_eq!(
+            nip04_decrypt(vec![3u8; 32], sender.to_string(), content.to_string()).unwrap(),
+            "old nip04 hello"
+        );
+    }
+}
@@ -22,7 +22,7 @@ pub struct UnwrapResult {
 /// Event kinds for NIP-17
 const KIND_SEAL: i32 = 13;
 const KIND_RUMOR: i32 = 14;
-const KIND_GIFT_WRAP: i32 = 1059;
+pub(crate) const KIND_GIFT_WRAP: i32 = 1059;
 
 /// Time randomization range (2 days in seconds)
 const TIMESTAMP_RANGE: u32 = 172800;
@@ -1,7 +1,7 @@
-//! NIP-44 Encryption (ChaCha20-Poly1305)
+//! NIP-44 Encryption (version 2)
 //!
 //! Implements NIP-44 version 2 encryption with:
-//! - ChaCha20-Poly1305 AEAD
+//! - ChaCha20 stream cipher with HMAC-SHA256 (encrypt-then-MAC)
 //! - HKDF-SHA256 key derivation
 //! - Power-of-2 padding
 //! - Constant-time padding verification (side-channel resistant)
@@ -9,10 +9,9 @@
 use crate::error::CryptoError;
 use crate::keys::derive_conversation_key;
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
-use chacha20poly1305::{
-    aead::{Aead, KeyInit},
-    ChaCha20Poly1305, Nonce,
-};
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit, Nonce};
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand::rngs::OsRng;
@@ -24,6 +23,17 @@ use zeroize::Zeroize;
 /// NIP-44 version byte
 const NIP44_VERSION: u8 = 2;
 
+/// Base64 payload length bounds
+const MIN_PAYLOAD_LEN: usize = 132;
+const MAX_PAYLOAD_LEN: usize = 87472;
+
+/// Decoded payload length bounds
+const MIN_DATA_LEN: usize = 99;
+const MAX_DATA_LEN: usize = 65603;
+
+/// Poly1305 tag carried inside pre-spec payloads
+const LEGACY_TAG_LEN: usize = 16;
+
 /// Calculate padded length using power-of-2 scheme
 fn calc_padded_len(unpadded_len: usize) -> usize {
     if unpadded_len <= 32 {
@@ -36,7 +46,20 @@ fn calc_padded_len(unpadded_len: usize) -> usize {
     } else {
         next_power / 8
     };
-    chunk * unpadded_len.div_ceil(chunk)
+    let padded_len = chunk * unpadded_len.div_ceil(chunk);
+    debug_assert!(padded_len >= unpadded_len);
+    padded_len
+}
+
+/// Bytes NIP-44 padding adds to a plaintext of the given length
+///
+/// Diagnostic only: counts the 2-byte length prefix plus zero padding, not
+/// the fixed version/nonce/MAC framing. Padding grows with the next power of
+/// two, so messages just past a boundary (e.g. 257 bytes) pay the most. The
+/// scheme itself is fixed by the spec and cannot be tuned without breaking
+/// interop.
+pub fn padded_overhead(plaintext_len: usize) -> usize {
+    2 + calc_padded_len(plaintext_len) - plaintext_len
 }
 
 /// Pad plaintext according to NIP-44
@@ -77,7 +100,7 @@ fn unpad(padded: &[u8]) -> Result<Vec<u8>, CryptoError> {
         return Err(CryptoError::InvalidPadding);
     }
 
-    if 2 + unpadded_len > padded.len() {
+    if padded.len() != 2 + calc_padded_len(unpadded_len) {
         return Err(CryptoError::InvalidPadding);
     }
 
@@ -97,119 +120,62 @@ fn unpad(padded: &[u8]) -> Result<Vec<u8>, CryptoError> {
     Ok(padded[2..2 + unpadded_len].to_vec())
 }
 
-/// Encrypt a message using NIP-44
-pub fn nip44_encrypt(
-    private_key: Vec<u8>,
-    recipient_pubkey: String,
-    plaintext: String,
-) -> Result<String, CryptoError> {
-    // Derive conversation key
-    let mut conversation_key = derive_conversation_key(private_key, recipient_pubkey)?;
-
-    // Generate random nonce using OS RNG (cryptographically secure)
-    let mut nonce_material = [0u8; 32];
-    OsRng.fill_bytes(&mut nonce_material);
-
-    // Derive message keys using HKDF
-    let hk = Hkdf::<Sha256>::new(Some(&nonce_material), &conversation_key);
-    let mut key_material = [0u8; 76]; // 32 chacha + 12 nonce + 32 hmac
-    hk.expand(b"nip44-v2", &mut key_material)
-        .map_err(|_| CryptoError::EncryptionFailed)?;
-
-    let chacha_key = &key_material[0..32];
-    let chacha_nonce = &key_material[32..44];
-    let hmac_key = &key_material[44..76];
-
-    // Pad plaintext and zeroize the original plaintext bytes
-    let mut plaintext_bytes = plaintext.into_bytes();
-    let mut padded = pad(&plaintext_bytes)?;
-    plaintext_bytes.zeroize();
-
-    // Encrypt with ChaCha20-Poly1305
-    let cipher =
-        ChaCha20Poly1305::new_from_slice(chacha_key).map_err(|_| CryptoError::EncryptionFailed)?;
-    let nonce = Nonce::from_slice(chacha_nonce);
-    let ciphertext = cipher
-        .encrypt(nonce, padded.as_slice())
-        .map_err(|_| CryptoError::EncryptionFailed)?;
-
-    // Zeroize padded plaintext
-    padded.zeroize();
+/// Derive the per-message ChaCha20 key, ChaCha20 nonce and HMAC key
+///
+/// NIP-44 v2: HKDF-expand(PRK = conversation_key, info = nonce, L = 76)
+fn get_message_keys(conversation_key: &[u8], nonce: &[u8]) -> Result<[u8; 76], CryptoError> {
+    let hk = Hkdf::<Sha256>::from_prk(conversation_key).map_err(|_| CryptoError::InvalidKey)?;
+    let mut key_material = [0u8; 76]; // 32 chacha key + 12 chacha nonce + 32 hmac key
+    hk.expand(nonce, &mut key_material)
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
+    Ok(key_material)
+}
 
-    // Compute HMAC
+/// HMAC-SHA256 over nonce || ciphertext
+fn compute_mac(
+    hmac_key: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Hmac<Sha256>, CryptoError> {
     let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key)
-        .map_err(|_| CryptoError::EncryptionFailed)?;
-    mac.update(&nonce_material);
-    mac.update(&ciphertext);
-    let mac_bytes = mac.finalize().into_bytes();
-
-    // Construct payload: version + nonce + ciphertext + mac
-    let mut payload = Vec::with_capacity(1 + 32 + ciphertext.len() + 32);
-    payload.push(NIP44_VERSION);
-    payload.extend_from_slice(&nonce_material);
-    payload.extend_from_slice(&ciphertext);
-    payload.extend_from_slice(&mac_bytes);
-
-    // Zeroize sensitive data
-    key_material.zeroize();
-    conversation_key.zeroize();
-
-    Ok(BASE64.encode(&payload))
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
+    mac.update(nonce);
+    mac.update(ciphertext);
+    Ok(mac)
 }
 
-/// Encrypt a message using NIP-44 with a pre-derived conversation key
-///
-/// Use this when you already have the conversation key derived (e.g., from caching).
-/// For single-use encryption, use `nip44_encrypt` which derives the key internally.
-pub fn nip44_encrypt_with_key(
-    conversation_key: Vec<u8>,
+/// Encrypt with a conversation key and an explicit nonce
+fn encrypt_with_nonce(
+    conversation_key: &[u8],
+    nonce: &[u8; 32],
     plaintext: String,
 ) -> Result<String, CryptoError> {
     if conversation_key.len() != 32 {
         return Err(CryptoError::InvalidKey);
     }
 
-    // Generate random nonce using OS RNG (cryptographically secure)
-    let mut nonce_material = [0u8; 32];
-    OsRng.fill_bytes(&mut nonce_material);
-
-    // Derive message keys using HKDF
-    let hk = Hkdf::<Sha256>::new(Some(&nonce_material), &conversation_key);
-    let mut key_material = [0u8; 76]; // 32 chacha + 12 nonce + 32 hmac
-    hk.expand(b"nip44-v2", &mut key_material)
-        .map_err(|_| CryptoError::EncryptionFailed)?;
-
+    let mut key_material = get_message_keys(conversation_key, nonce)?;
     let chacha_key = &key_material[0..32];
     let chacha_nonce = &key_material[32..44];
     let hmac_key = &key_material[44..76];
 
     // Pad plaintext and zeroize the original plaintext bytes
     let mut plaintext_bytes = plaintext.into_bytes();
-    let mut padded = pad(&plaintext_bytes)?;
+    let mut ciphertext = pad(&plaintext_bytes)?;
     plaintext_bytes.zeroize();
 
-    // Encrypt with ChaCha20-Poly1305
-    let cipher =
-        ChaCha20Poly1305::new_from_slice(chacha_key).map_err(|_| CryptoError::EncryptionFailed)?;
-    let nonce = Nonce::from_slice(chacha_nonce);
-    let ciphertext = cipher
-        .encrypt(nonce, padded.as_slice())
-        .map_err(|_| CryptoError::EncryptionFailed)?;
+    // Encrypt the padded plaintext in place with ChaCha20 (counter starts at 0)
+    let mut cipher = ChaCha20::new(chacha_key.into(), chacha_nonce.into());
+    cipher.apply_keystream(&mut ciphertext);
 
-    // Zeroize padded plaintext
-    padded.zeroize();
-
-    // Compute HMAC
-    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key)
-        .map_err(|_| CryptoError::EncryptionFailed)?;
-    mac.update(&nonce_material);
-    mac.update(&ciphertext);
-    let mac_bytes = mac.finalize().into_bytes();
+    let mac_bytes = compute_mac(hmac_key, nonce, &ciphertext)?
+        .finalize()
+        .into_bytes();
 
     // Construct payload: version + nonce + ciphertext + mac
     let mut payload = Vec::with_capacity(1 + 32 + ciphertext.len() + 32);
     payload.push(NIP44_VERSION);
-    payload.extend_from_slice(&nonce_material);
+    payload.extend_from_slice(nonce);
     payload.extend_from_slice(&ciphertext);
     payload.extend_from_slice(&mac_bytes);
 
@@ -219,25 +185,30 @@ pub fn nip44_encrypt_with_key(
     Ok(BASE64.encode(&payload))
 }
 
-/// Decrypt a message using NIP-44 with a pre-derived conversation key
-///
-/// Use this when you already have the conversation key derived (e.g., from caching).
-/// For single-use decryption, use `nip44_decrypt` which derives the key internally.
-pub fn nip44_decrypt_with_key(
-    conversation_key: Vec<u8>,
-    ciphertext: String,
+/// Decrypt a payload with a conversation key
+fn decrypt_with_conversation_key(
+    conversation_key: &[u8],
+    ciphertext: &str,
 ) -> Result<String, CryptoError> {
     if conversation_key.len() != 32 {
         return Err(CryptoError::InvalidKey);
     }
 
+    // A leading '#' marks a future, non-base64 encryption version
+    if ciphertext.starts_with('#') {
+        return Err(CryptoError::InvalidCiphertext);
+    }
+    if !(MIN_PAYLOAD_LEN..=MAX_PAYLOAD_LEN).contains(&ciphertext.len()) {
+        return Err(CryptoError::InvalidCiphertext);
+    }
+
     // Decode base64
     let payload = BASE64
-        .decode(&ciphertext)
+        .decode(ciphertext)
         .map_err(|_| CryptoError::InvalidCiphertext)?;
 
-    // Check minimum length: version(1) + nonce(32) + min_ciphertext(34) + mac(32)
-    if payload.len() < 99 {
+    // version(1) + nonce(32) + ciphertext(34..=65538) + mac(32)
+    if !(MIN_DATA_LEN..=MAX_DATA_LEN).contains(&payload.len()) {
         return Err(CryptoError::InvalidCiphertext);
     }
 
@@ -246,111 +217,213 @@ pub fn nip44_decrypt_with_key(
         return Err(CryptoError::InvalidCiphertext);
     }
 
-    let nonce_material = &payload[1..33];
+    let nonce = &payload[1..33];
     let encrypted = &payload[33..payload.len() - 32];
     let received_mac = &payload[payload.len() - 32..];
 
-    // Derive message keys
-    let hk = Hkdf::<Sha256>::new(Some(nonce_material), &conversation_key);
-    let mut key_material = [0u8; 76];
-    hk.expand(b"nip44-v2", &mut key_material)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
-
+    let mut key_material = get_message_keys(conversation_key, nonce)?;
     let chacha_key = &key_material[0..32];
     let chacha_nonce = &key_material[32..44];
     let hmac_key = &key_material[44..76];
 
-    // Verify HMAC first (constant-time comparison)
-    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
-    mac.update(nonce_material);
-    mac.update(encrypted);
-
-    mac.verify_slice(received_mac)
-        .map_err(|_| CryptoError::InvalidMac)?;
+    // Verify HMAC before decrypting (constant-time comparison)
+    let mac_result = compute_mac(hmac_key, nonce, encrypted)?
+        .verify_slice(received_mac)
+        .map_err(|_| CryptoError::InvalidMac);
+    if let Err(e) = mac_result {
+        key_material.zeroize();
+        return Err(e);
+    }
 
     // Decrypt
-    let cipher =
-        ChaCha20Poly1305::new_from_slice(chacha_key).map_err(|_| CryptoError::DecryptionFailed)?;
-    let nonce = Nonce::from_slice(chacha_nonce);
-    let mut padded = cipher
-        .decrypt(nonce, encrypted)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
+    let mut padded = encrypted.to_vec();
+    let mut cipher = ChaCha20::new(chacha_key.into(), chacha_nonce.into());
+    cipher.apply_keystream(&mut padded);
+    key_material.zeroize();
 
     // Unpad
-    let plaintext_bytes = unpad(&padded)?;
-
-    // Zeroize sensitive data
-    key_material.zeroize();
+    let result = unpad(&padded);
     padded.zeroize();
+    let plaintext_bytes = result?;
 
     String::from_utf8(plaintext_bytes).map_err(|_| CryptoError::DecryptionFailed)
 }
 
+/// Encrypt a message using NIP-44
+pub fn nip44_encrypt(
+    private_key: Vec<u8>,
+    recipient_pubkey: String,
+    plaintext: String,
+) -> Result<String, CryptoError> {
+    // Derive conversation key
+    let mut conversation_key = derive_conversation_key(private_key, recipient_pubkey)?;
+    let result = nip44_encrypt_with_key(conversation_key.clone(), plaintext);
+    conversation_key.zeroize();
+    result
+}
+
+/// Encrypt a message using NIP-44 with a pre-derived conversation key
+///
+/// Use this when you already have the conversation key derived (e.g., from caching).
+/// For single-use encryption, use `nip44_encrypt` which derives the key internally.
+pub fn nip44_encrypt_with_key(
+    mut conversation_key: Vec<u8>,
+    plaintext: String,
+) -> Result<String, CryptoError> {
+    // Generate random nonce using OS RNG (cryptographically secure)
+    let mut nonce = [0u8; 32];
+    OsRng.fill_bytes(&mut nonce);
+
+    let result = encrypt_with_nonce(&conversation_key, &nonce, plaintext);
+    conversation_key.zeroize();
+    result
+}
+
+/// Encrypt with an explicit nonce
+///
+/// Test seam for reproducing the NIP-44 spec vectors byte-for-byte. Reusing a
+/// nonce with the same conversation key breaks confidentiality, so application
+/// code must use `nip44_encrypt` or `nip44_encrypt_with_key`.
+#[doc(hidden)]
+pub fn nip44_encrypt_with_nonce(
+    mut conversation_key: Vec<u8>,
+    nonce: [u8; 32],
+    plaintext: String,
+) -> Result<String, CryptoError> {
+    let result = encrypt_with_nonce(&conversation_key, &nonce, plaintext);
+    conversation_key.zeroize();
+    result
+}
+
+/// Decrypt a message using NIP-44 with a pre-derived conversation key
+///
+/// Use this when you already have the conversation key derived (e.g., from caching).
+/// For single-use decryption, use `nip44_decrypt` which derives the key internally.
+pub fn nip44_decrypt_with_key(
+    mut conversation_key: Vec<u8>,
+    ciphertext: String,
+) -> Result<String, CryptoError> {
+    let result = decrypt_with_conversation_key(&conversation_key, &ciphertext);
+    conversation_key.zeroize();
+    result
+}
+
 /// Decrypt a message using NIP-44
 pub fn nip44_decrypt(
     private_key: Vec<u8>,
     sender_pubkey: String,
     ciphertext: String,
 ) -> Result<String, CryptoError> {
-    // Decode base64
+    // Derive conversation key
+    let mut conversation_key = derive_conversation_key(private_key, sender_pubkey)?;
+    let result = decrypt_with_conversation_key(&conversation_key, &ciphertext);
+    conversation_key.zeroize();
+    result
+}
+
+/// Derive the pre-spec conversation key from a spec conversation key
+///
+/// The pre-spec key was HKDF extract+expand over the same shared secret, and
+/// the spec key is that extract step's PRK, so only the expand is left.
+fn legacy_conversation_key(conversation_key: &[u8]) -> Result<[u8; 32], CryptoError> {
+    let hk = Hkdf::<Sha256>::from_prk(conversation_key).map_err(|_| CryptoError::InvalidKey)?;
+    let mut legacy_key = [0u8; 32];
+    hk.expand(&[], &mut legacy_key)
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
+    Ok(legacy_key)
+}
+
+/// Decrypt a pre-spec payload with a spec conversation key
+fn decrypt_legacy_with_conversation_key(
+    conversation_key: &[u8],
+    ciphertext: &str,
+) -> Result<String, CryptoError> {
+    if conversation_key.len() != 32 {
+        return Err(CryptoError::InvalidKey);
+    }
+
     let payload = BASE64
-        .decode(&ciphertext)
+        .decode(ciphertext)
         .map_err(|_| CryptoError::InvalidCiphertext)?;
 
-    // Check minimum length: version(1) + nonce(32) + min_ciphertext(34) + mac(32)
-    if payload.len() < 99 {
+    // version(1) + nonce(32) + ciphertext(34..=65538) + tag(16) + mac(32)
+    if !(MIN_DATA_LEN + LEGACY_TAG_LEN..=MAX_DATA_LEN + LEGACY_TAG_LEN).contains(&payload.len()) {
         return Err(CryptoError::InvalidCiphertext);
     }
-
-    // Check version
     if payload[0] != NIP44_VERSION {
         return Err(CryptoError::InvalidCiphertext);
     }
 
-    let nonce_material = &payload[1..33];
+    let nonce = &payload[1..33];
     let encrypted = &payload[33..payload.len() - 32];
     let received_mac = &payload[payload.len() - 32..];
 
-    // Derive conversation key
-    let mut conversation_key = derive_conversation_key(private_key, sender_pubkey)?;
-
-    // Derive message keys
-    let hk = Hkdf::<Sha256>::new(Some(nonce_material), &conversation_key);
+    // Message keys came from a second extract salted with the nonce
+    let mut legacy_key = legacy_conversation_key(conversation_key)?;
+    let hk = Hkdf::<Sha256>::new(Some(nonce), &legacy_key);
+    legacy_key.zeroize();
     let mut key_material = [0u8; 76];
     hk.expand(b"nip44-v2", &mut key_material)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
-
+        .map_err(|_| CryptoError::KeyDerivationFailed)?;
     let chacha_key = &key_material[0..32];
     let chacha_nonce = &key_material[32..44];
     let hmac_key = &key_material[44..76];
 
-    // Verify HMAC first (constant-time comparison)
-    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(hmac_key)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
-    mac.update(nonce_material);
-    mac.update(encrypted);
+    let mac_result = compute_mac(hmac_key, nonce, encrypted)?
+        .verify_slice(received_mac)
+        .map_err(|_| CryptoError::InvalidMac);
+    if let Err(e) = mac_result {
+        key_material.zeroize();
+        return Err(e);
+    }
 
-    mac.verify_slice(received_mac)
-        .map_err(|_| CryptoError::InvalidMac)?;
+    let cipher = ChaCha20Poly1305::new_from_slice(chacha_key);
+    let decrypted = cipher
+        .map_err(|_| CryptoError::DecryptionFailed)
+        .and_then(|cipher| {
+            cipher
+                .decrypt(Nonce::from_slice(chacha_nonce), encrypted)
+                .map_err(|_| CryptoError::DecryptionFailed)
+        });
+    key_material.zeroize();
+    let mut padded = decrypted?;
 
-    // Decrypt
-    let cipher =
-        ChaCha20Poly1305::new_from_slice(chacha_key).map_err(|_| CryptoError::DecryptionFailed)?;
-    let nonce = Nonce::from_slice(chacha_nonce);
-    let mut padded = cipher
-        .decrypt(nonce, encrypted)
-        .map_err(|_| CryptoError::DecryptionFailed)?;
+    let result = unpad(&padded);
+    padded.zeroize();
+    let plaintext_bytes = result?;
 
-    // Unpad
-    let plaintext_bytes = unpad(&padded)?;
+    String::from_utf8(plaintext_bytes).map_err(|_| CryptoError::DecryptionFailed)
+}
 
-    // Zeroize sensitive data
-    key_material.zeroize();
+/// Decrypt a payload written before NIP-44 was aligned with the v2 spec
+///
+/// Pre-spec payloads carry the same version byte but use a different key
+/// schedule and ChaCha20-Poly1305, so the spec decrypt rejects them with
+/// `InvalidMac`. Use this only to read stored history after that failure,
+/// then re-encrypt the plaintext with `nip44_encrypt`. Never use it for
+/// fresh traffic.
+pub fn nip44_decrypt_legacy_pre_spec(
+    private_key: Vec<u8>,
+    sender_pubkey: String,
+    ciphertext: String,
+) -> Result<String, CryptoError> {
+    let mut conversation_key = derive_conversation_key(private_key, sender_pubkey)?;
+    let result = decrypt_legacy_with_conversation_key(&conversation_key, &ciphertext);
     conversation_key.zeroize();
-    padded.zeroize();
+    result
+}
 
-    String::from_utf8(plaintext_bytes).map_err(|_| CryptoError::DecryptionFailed)
+/// Decrypt a pre-spec payload with a pre-derived (spec) conversation key
+///
+/// Takes the key `derive_conversation_key` returns today, so cached keys
+/// work for both formats. See `nip44_decrypt_legacy_pre_spec`.
+pub fn nip44_decrypt_legacy_pre_spec_with_key(
+    mut conversation_key: Vec<u8>,
+    ciphertext: String,
+) -> Result<String, CryptoError> {
+    let result = decrypt_legacy_with_conversation_key(&conversation_key, &ciphertext);
+    conversation_key.zeroize();
+    result
 }
 
 #[cfg(test)]
@@ -372,6 +445,26 @@ mod tests {
         assert_eq!(calc_padded_len(257), 320);
     }
 
+    #[test]
+    fn test_padding_reference_boundaries() {
+        // (unpadded, padded) pairs from the NIP-44 reference table
+        let table = [(32, 32), (33, 64), (256, 256), (257, 320), (65535, 65536)];
+        for (unpadded, padded) in table {
+            assert_eq!(calc_padded_len(unpadded), padded, "len {}", unpadded);
+            assert_eq!(pad(&vec![0x61; unpadded]).unwrap().len(), 2 + padded);
+        }
+    }
+
+    #[test]
+    fn test_padded_overhead() {
+        assert_eq!(padded_overhead(1), 33);
+        assert_eq!(padded_overhead(32), 2);
+        assert_eq!(padded_overhead(33), 33);
+        assert_eq!(padded_overhead(256), 2);
+        assert_eq!(padded_overhead(257), 65);
+        assert_eq!(padded_overhead(65535), 3);
+    }
+
     #[test]
     fn test_pad_unpad_roundtrip() {
         let original = b"Hello, World!";
@@ -519,4 +612,56 @@ mod tests {
         );
         assert!(result.is_err());
     }
+
+    /// Produced by the pre-spec implementation: secret key 1 to the pubkey
+    /// of secret key 2, plaintext "pre-spec payload"
+    const PRE_SPEC_PAYLOAD: &str = "AiB5siVtdCjuJQSupjII8ic3LaDFROPi1lIxE5mjPT+ZuE7aibrXk0IYjLKHA467rf3jgLUa97rZrbvIQKZ15qo+dUJyXqeAwvMnqj4NeJ9EJKrPYylgrdwB1DwsfzRzTQQnbBKYP0fnqj9scb9849nx2Q==";
+    const PRE_SPEC_CONVERSATION_KEY: &str =
+        "aa22d33fe7d2402cd1ced28f3a2f476c1c9658fe3ffbe1236f17ae7dcc3d176b";
+
+    fn scalar_key(n: u8) -> Vec<u8> {
+        let mut key = vec![0u8; 32];
+        key[31] = n;
+        key
+    }
+
+    #[test]
+    fn test_nip44_legacy_pre_spec_payload_decrypts() {
+        use crate::keys::{derive_conversation_key, get_public_key};
+
+        let sender = scalar_key(1);
+        let recipient = scalar_key(2);
+        let recipient_pubkey = get_public_key(recipient.clone()).unwrap();
+        let sender_pubkey = get_public_key(sender.clone()).unwrap();
+
+        let conv_key = derive_conversation_key(sender.clone(), recipient_pubkey.clone()).unwrap();
+        assert_eq!(
+            hex::encode(legacy_conversation_key(&conv_key).unwrap()),
+            PRE_SPEC_CONVERSATION_KEY
+        );
+
+        // The spec decrypt refuses it on the MAC, which is the cue to fall back
+        assert!(matches!(
+            nip44_decrypt(
+                recipient.clone(),
+                sender_pubkey.clone(),
+                PRE_SPEC_PAYLOAD.to_string()
+            ),
+            Err(CryptoError::InvalidMac)
+        ));
+
+        let plaintext =
+            nip44_decrypt_legacy_pre_spec(recipient, sender_pubkey, PRE_SPEC_PAYLOAD.to_string())
+                .unwrap();
+        assert_eq!(plaintext, "pre-spec payload");
+        assert_eq!(
+            nip44_decrypt_legacy_pre_spec_with_key(conv_key.clone(), PRE_SPEC_PAYLOAD.to_string())
+                .unwrap(),
+            "pre-spec payload"
+        );
+
+        // Spec payloads are never accepted by the legacy path
+        let current = nip44_encrypt_with_key(conv_key.clone(), "spec".to_string()).unwrap();
+        assert!(nip44_decrypt_legacy_pre_spec_with_key(conv_key, current).is_err());
+    }
 }
@@ -5,6 +5,15 @@ use secp256k1::{schnorr, Message, Secp256k1, SecretKey, XOnlyPublicKey};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+/// Kind 0: user metadata (NIP-01)
+pub const KIND_METADATA: i32 = 0;
+/// Kind 1: short text note (NIP-01)
+pub const KIND_TEXT_NOTE: i32 = 1;
+/// Kind 3: contact list (NIP-02)
+pub const KIND_CONTACT_LIST: i32 = 3;
+/// Kind 5: event deletion request (NIP-09)
+pub const KIND_DELETION: i32 = 5;
+
 /// Unsigned Nostr event (before signing)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnsignedEvent {
@@ -27,6 +36,55 @@ pub struct NostrEvent {
     pub sig: String,
 }
 
+impl UnsignedEvent {
+    /// Value at position 1 of the first `name` tag that has one
+    pub fn first_tag_value(&self, name: &str) -> Option<&str> {
+        first_tag_value(&self.tags, name)
+    }
+
+    /// Value at position 1 of every `name` tag, in tag order
+    pub fn all_tag_values(&self, name: &str) -> Vec<&str> {
+        all_tag_values(&self.tags, name)
+    }
+
+    /// Append a `[name, values...]` tag
+    pub fn add_tag(&mut self, name: &str, values: Vec<String>) -> &mut Self {
+        let mut tag = Vec::with_capacity(values.len() + 1);
+        tag.push(name.to_string());
+        tag.extend(values);
+        self.tags.push(tag);
+        self
+    }
+}
+
+impl NostrEvent {
+    /// Value at position 1 of the first `name` tag that has one
+    pub fn first_tag_value(&self, name: &str) -> Option<&str> {
+        first_tag_value(&self.tags, name)
+    }
+
+    /// Value at position 1 of every `name` tag, in tag order
+    pub fn all_tag_values(&self, name: &str) -> Vec<&str> {
+        all_tag_values(&self.tags, name)
+    }
+}
+
+/// Tags are positional: `[name, value, extra...]`. Tags without a value are skipped.
+fn tag_value<'a>(tag: &'a [String], name: &str) -> Option<&'a str> {
+    match tag {
+        [tag_name, value, ..] if tag_name == name => Some(value),
+        _ => None,
+    }
+}
+
+fn first_tag_value<'a>(tags: &'a [Vec<String>], name: &str) -> Option<&'a str> {
+    tags.iter().find_map(|tag| tag_value(tag, name))
+}
+
+fn all_tag_values<'a>(tags: &'a [Vec<String>], name: &str) -> Vec<&'a str> {
+    tags.iter().filter_map(|tag| tag_value(tag, name)).collect()
+}
+
 /// Compute the event ID (SHA256 hash of serialized event)
 pub fn compute_event_id(event: UnsignedEvent) -> Result<String, CryptoError> {
     // Serialize according to NIP-01
@@ -145,6 +203,263 @@ pub fn verify_event(event: NostrEvent) -> bool {
         .is_ok()
 }
 
+/// Highest kind NIP-01 allows
+pub const MAX_EVENT_KIND: i32 = 65535;
+
+/// What `validate_event` found wrong with an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventProblemKind {
+    /// `id` is not 64 lowercase hex characters
+    MalformedId,
+    /// `id` is not the hash of the event's contents
+    IdMismatch,
+    /// `pubkey` is not a 64-character hex x-only public key
+    MalformedPubkey,
+    /// `sig` is not 128 lowercase hex characters
+    MalformedSignature,
+    /// `sig` does not verify for `pubkey` over the event's hash
+    InvalidSignature,
+    /// `kind` is outside 0..=65535
+    KindOutOfRange,
+    /// A tag has no name
+    EmptyTag,
+    /// An `e` or `p` tag has no 64-character hex value
+    InvalidTagValue,
+}
+
+/// One defect found by `validate_event`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventProblem {
+    pub kind: EventProblemKind,
+    /// Index of the offending tag, for tag problems
+    pub tag_index: Option<u32>,
+    /// Human-readable description
+    pub message: String,
+}
+
+/// Result of `validate_event`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// True when no problems were found
+    pub valid: bool,
+    /// Every problem found, in the order checked
+    pub problems: Vec<EventProblem>,
+}
+
+/// Validate every part of a signed event, reporting all problems found
+///
+/// Checks the id against the recomputed hash, the hex shape of `id`,
+/// `pubkey` and `sig`, the Schnorr signature, the kind range and the tags
+/// (each tag named, `e`/`p` values 64-character hex). The signature is
+/// verified over the recomputed hash, so a tampered event is reported as an
+/// id mismatch rather than a bad signature too. It is only checked when
+/// `pubkey` and `sig` are well-formed.
+pub fn validate_event(event: &NostrEvent) -> ValidationReport {
+    let mut problems = Vec::new();
+    let mut problem = |kind, tag_index: Option<usize>, message: String| {
+        problems.push(EventProblem {
+            kind,
+            tag_index: tag_index.map(|i| i as u32),
+            message,
+        })
+    };
+
+    let expected_id = compute_event_id(UnsignedEvent {
+        pubkey: event.pubkey.clone(),
+        created_at: event.created_at,
+        kind: event.kind,
+        tags: event.tags.clone(),
+        content: event.content.clone(),
+    })
+    .ok();
+    if !is_lower_hex(&event.id, 64) {
+        problem(
+            EventProblemKind::MalformedId,
+            None,
+            "id must be 64 lowercase hex characters".to_string(),
+        );
+    } else if expected_id.as_deref() != Some(event.id.as_str()) {
+        problem(
+            EventProblemKind::IdMismatch,
+            None,
+            "id does not match the event contents".to_string(),
+        );
+    }
+
+    let pubkey = hex::decode(&event.pubkey)
+        .ok()
+        .filter(|_| is_lower_hex(&event.pubkey, 64))
+        .and_then(|bytes| XOnlyPublicKey::from_slice(&bytes).ok());
+    if pubkey.is_none() {
+        problem(
+            EventProblemKind::MalformedPubkey,
+            None,
+            "pubkey must be a 64-character hex x-only public key".to_string(),
+        );
+    }
+
+    let signature = hex::decode(&event.sig)
+        .ok()
+        .filter(|_| is_lower_hex(&event.sig, 128))
+        .and_then(|bytes| schnorr::Signature::from_slice(&bytes).ok());
+    if signature.is_none() {
+        problem(
+            EventProblemKind::MalformedSignature,
+            None,
+            "sig must be 128 lowercase hex characters".to_string(),
+        );
+    }
+
+    if let (Some(pubkey), Some(signature), Some(expected_id)) = (pubkey, signature, &expected_id) {
+        let verified = hex::decode(expected_id)
+            .ok()
+            .and_then(|id| Message::from_digest_slice(&id).ok())
+            .is_some_and(|message| {
+                Secp256k1::verification_only()
+                    .verify_schnorr(&signature, &message, &pubkey)
+                    .is_ok()
+            });
+        if !verified {
+            problem(
+                EventProblemKind::InvalidSignature,
+                None,
+                "signature does not verify for pubkey".to_string(),
+            );
+        }
+    }
+
+    if !(0..=MAX_EVENT_KIND).contains(&event.kind) {
+        problem(
+            EventProblemKind::KindOutOfRange,
+            None,
+            format!("kind {} is outside 0..={MAX_EVENT_KIND}", event.kind),
+        );
+    }
+
+    for (index, tag) in event.tags.iter().enumerate() {
+        match tag.first().map(String::as_str) {
+            None | Some("") => problem(
+                EventProblemKind::EmptyTag,
+                Some(index),
+                format!("tag {index} has no name"),
+            ),
+            Some(name @ ("e" | "p")) => {
+                if !tag.get(1).is_some_and(|value| is_lower_hex(value, 64)) {
+                    problem(
+                        EventProblemKind::InvalidTagValue,
+                        Some(index),
+                        format!("\"{name}\" tag {index} needs a 64-character hex value"),
+                    );
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    ValidationReport {
+        valid: problems.is_empty(),
+        problems,
+    }
+}
+
+/// Whether `s` is exactly `len` lowercase hex characters
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Current Unix time in seconds
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn unsigned_now(
+    pubkey: String,
+    kind: i32,
+    tags: Vec<Vec<String>>,
+    content: String,
+) -> UnsignedEvent {
+    UnsignedEvent {
+        pubkey,
+        created_at: unix_now(),
+        kind,
+        tags,
+        content,
+    }
+}
+
+/// Build a kind-0 metadata event
+///
+/// Only the fields that are `Some` are written to the JSON content.
+pub fn build_metadata_event(
+    pubkey: String,
+    name: Option<String>,
+    about: Option<String>,
+    picture: Option<String>,
+) -> UnsignedEvent {
+    let mut metadata = serde_json::Map::new();
+    for (key, value) in [("name", name), ("about", about), ("picture", picture)] {
+        if let Some(value) = value {
+            metadata.insert(key.to_string(), serde_json::Value::String(value));
+        }
+    }
+
+    unsigned_now(
+        pubkey,
+        KIND_METADATA,
+        Vec::new(),
+        serde_json::Value::Object(metadata).to_string(),
+    )
+}
+
+/// Build a kind-1 text note
+pub fn build_text_note(pubkey: String, content: String, tags: Vec<Vec<String>>) -> UnsignedEvent {
+    unsigned_now(pubkey, KIND_TEXT_NOTE, tags, content)
+}
+
+/// Build a kind-3 contact list with one `p` tag per followed pubkey
+pub fn build_contact_list(pubkey: String, pubkeys: Vec<String>) -> UnsignedEvent {
+    let tags = pubkeys
+        .into_iter()
+        .map(|pk| vec!["p".to_string(), pk])
+        .collect();
+    unsigned_now(pubkey, KIND_CONTACT_LIST, tags, String::new())
+}
+
+/// Build a kind-5 deletion request with one `e` tag per deleted event
+pub fn build_deletion(pubkey: String, event_ids: Vec<String>) -> UnsignedEvent {
+    let tags = event_ids
+        .into_iter()
+        .map(|id| vec!["e".to_string(), id])
+        .collect();
+    unsigned_now(pubkey, KIND_DELETION, tags, String::new())
+}
+
+/// Domain separator for `conversation_id` hashes
+const CONVERSATION_ID_DOMAIN: &[u8] = b"buildit-conversation-id-v1";
+
+/// Derive a stable conversation id for a pair of pubkeys
+///
+/// Pubkeys are lowercased and sorted before hashing, so both participants get
+/// the same id regardless of argument order. Returns the SHA-256 as hex.
+pub fn conversation_id(pubkey_a: String, pubkey_b: String) -> String {
+    let a = pubkey_a.trim().to_lowercase();
+    let b = pubkey_b.trim().to_lowercase();
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+
+    let mut hasher = Sha256::new();
+    hasher.update(CONVERSATION_ID_DOMAIN);
+    hasher.update([0u8]);
+    hasher.update(first.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(second.as_bytes());
+
+    hex::encode(hasher.finalize())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -235,4 +550,235 @@ mod tests {
         // Verification should fail (ID won't match)
         assert!(!verify_event(signed));
     }
+
+    #[test]
+    fn test_build_metadata_event() {
+        let keypair = generate_keypair();
+        let event = build_metadata_event(
+            keypair.public_key.clone(),
+            Some("alice".to_string()),
+            None,
+            Some("https://example.com/a.png".to_string()),
+        );
+
+        assert_eq!(event.kind, KIND_METADATA);
+        assert!(event.tags.is_empty());
+        let content: serde_json::Value = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content["name"], "alice");
+        assert_eq!(content["picture"], "https://example.com/a.png");
+        assert!(content.get("about").is_none());
+    }
+
+    #[test]
+    fn test_build_text_note() {
+        let keypair = generate_keypair();
+        let tags = vec![vec!["t".to_string(), "organizing".to_string()]];
+        let event = build_text_note(
+            keypair.public_key.clone(),
+            "hello".to_string(),
+            tags.clone(),
+        );
+
+        assert_eq!(event.kind, KIND_TEXT_NOTE);
+        assert_eq!(event.content, "hello");
+        assert_eq!(event.tags, tags);
+        assert!(event.created_at > 1700000000);
+    }
+
+    #[test]
+    fn test_build_contact_list() {
+        let keypair = generate_keypair();
+        let follows = vec!["a".repeat(64), "b".repeat(64)];
+        let event = build_contact_list(keypair.public_key.clone(), follows.clone());
+
+        assert_eq!(event.kind, KIND_CONTACT_LIST);
+        assert_eq!(event.content, "");
+        assert_eq!(
+            event.tags,
+            vec![
+                vec!["p".to_string(), follows[0].clone()],
+                vec!["p".to_string(), follows[1].clone()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_deletion() {
+        let keypair = generate_keypair();
+        let ids = vec!["1".repeat(64), "2".repeat(64)];
+        let event = build_deletion(keypair.public_key.clone(), ids.clone());
+
+        assert_eq!(event.kind, KIND_DELETION);
+        assert_eq!(event.tags.len(), 2);
+        for (tag, id) in event.tags.iter().zip(&ids) {
+            assert_eq!(tag, &vec!["e".to_string(), id.clone()]);
+        }
+
+        // Builders produce events ready to sign
+        let signed = sign_event(keypair.private_key.clone(), event).unwrap();
+        assert!(verify_event(signed));
+    }
+
+    #[test]
+    fn test_conversation_id_is_order_independent() {
+        let alice = generate_keypair().public_key;
+        let bob = generate_keypair().public_key;
+
+        let id = conversation_id(alice.clone(), bob.clone());
+        assert_eq!(id, conversation_id(bob.clone(), alice.clone()));
+        assert_eq!(id.len(), 64);
+
+        // Hex case does not change the id
+        assert_eq!(id, conversation_id(alice.to_uppercase(), bob));
+    }
+
+    #[test]
+    fn test_conversation_id_differs_per_pair() {
+        let alice = generate_keypair().public_key;
+        let bob = generate_keypair().public_key;
+        let carol = generate_keypair().public_key;
+
+        let alice_bob = conversation_id(alice.clone(), bob.clone());
+        assert_ne!(alice_bob, conversation_id(alice.clone(), carol.clone()));
+        assert_ne!(alice_bob, conversation_id(bob, carol));
+        assert_ne!(alice_bob, conversation_id(alice.clone(), alice));
+    }
+
+    #[test]
+    fn test_tag_values_with_multi_value_tags() {
+        let mut event = build_text_note("a".repeat(64), "hi".to_string(), vec![]);
+        event
+            .add_tag(
+                "e",
+                vec![
+                    "1".repeat(64),
+                    "wss://relay.example".to_string(),
+                    "root".to_string(),
+                ],
+            )
+            .add_tag("p", vec!["b".repeat(64)])
+            .add_tag(
+                "e",
+                vec!["2".repeat(64), String::new(), "reply".to_string()],
+            );
+
+        assert_eq!(event.tags[0].len(), 4);
+        assert_eq!(event.first_tag_value("e"), Some("1".repeat(64).as_str()));
+        assert_eq!(
+            event.all_tag_values("e"),
+            vec!["1".repeat(64), "2".repeat(64)]
+        );
+        assert_eq!(event.first_tag_value("p"), Some("b".repeat(64).as_str()));
+    }
+
+    #[test]
+    fn test_tag_values_missing_tags() {
+        let mut event = build_text_note("a".repeat(64), "hi".to_string(), vec![]);
+        assert_eq!(event.first_tag_value("p"), None);
+        assert!(event.all_tag_values("p").is_empty());
+
+        // A bare tag name carries no value
+        event.add_tag("p", vec![]);
+        assert_eq!(event.first_tag_value("p"), None);
+        assert!(event.all_tag_values("p").is_empty());
+    }
+
+    #[test]
+    fn test_build_and_sign_event_with_several_tags() {
+        let keypair = generate_keypair();
+        let mut event = build_text_note(keypair.public_key.clone(), "tagged".to_string(), vec![]);
+        event
+            .add_tag("t", vec!["organizing".to_string()])
+            .add_tag("t", vec!["mutual-aid".to_string()])
+            .add_tag("p", vec![keypair.public_key.clone()]);
+
+        let signed = sign_event(keypair.private_key.clone(), event).unwrap();
+        assert!(verify_event(signed.clone()));
+        assert_eq!(signed.all_tag_values("t"), vec!["organizing", "mutual-aid"]);
+        assert_eq!(
+            signed.first_tag_value("p"),
+            Some(keypair.public_key.as_str())
+        );
+        assert_eq!(signed.first_tag_value("e"), None);
+    }
+
+    fn signed_note(tags: Vec<Vec<String>>) -> NostrEvent {
+        let keypair = generate_keypair();
+        let unsigned = UnsignedEvent {
+            pubkey: keypair.public_key,
+            created_at: 1700000000,
+            kind: KIND_TEXT_NOTE,
+            tags,
+            content: "validate me".to_string(),
+        };
+        sign_event(keypair.private_key, unsigned).unwrap()
+    }
+
+    fn problem_kinds(report: &ValidationReport) -> Vec<(EventProblemKind, Option<u32>)> {
+        report
+            .problems
+            .iter()
+            .map(|problem| (problem.kind, problem.tag_index))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_event_accepts_valid_event() {
+        let event = signed_note(vec![
+            vec!["e".to_string(), "a".repeat(64), "wss://relay".to_string()],
+            vec!["p".to_string(), "b".repeat(64)],
+            vec!["t".to_string()],
+        ]);
+
+        let report = validate_event(&event);
+        assert!(report.valid, "{:?}", report.problems);
+        assert!(report.problems.is_empty());
+    }
+
+    #[test]
+    fn test_validate_event_reports_every_defect() {
+        let mut event = signed_note(vec![]);
+        event.kind = -1;
+        event.pubkey = event.pubkey.to_uppercase();
+        event.sig = "zz".to_string();
+        event.tags = vec![
+            vec![],
+            vec!["e".to_string(), "not-hex".to_string()],
+            vec!["p".to_string()],
+            vec!["p".to_string(), "c".repeat(64)],
+        ];
+
+        let report = validate_event(&event);
+        assert!(!report.valid);
+        assert_eq!(
+            problem_kinds(&report),
+            vec![
+                (EventProblemKind::IdMismatch, None),
+                (EventProblemKind::MalformedPubkey, None),
+                (EventProblemKind::MalformedSignature, None),
+                (EventProblemKind::KindOutOfRange, None),
+                (EventProblemKind::EmptyTag, Some(0)),
+                (EventProblemKind::InvalidTagValue, Some(1)),
+                (EventProblemKind::InvalidTagValue, Some(2)),
+            ]
+        );
+
+        event.id = "abc".to_string();
+        assert_eq!(
+            validate_event(&event).problems[0].kind,
+            EventProblemKind::MalformedId
+        );
+    }
+
+    #[test]
+    fn test_validate_event_reports_foreign_signature() {
+        let mut event = signed_note(vec![]);
+        event.sig = signed_note(vec![]).sig;
+
+        let report = validate_event(&event);
+        assert_eq!(
+            problem_kinds(&report),
+            vec![(EventProblemKind::InvalidSignature, None)]
+        );
+    }
 }
@@ -32,7 +32,9 @@
 //! let plaintext = session.decrypt(&header, &ciphertext)?;
 //! ```
 
-use crate::aes::{aes_decrypt, aes_encrypt, EncryptedData};
+use crate::aes::{
+    aes_decrypt, aes_decrypt_with_aad, aes_encrypt, aes_encrypt_with_aad, EncryptedData,
+};
 use crate::error::CryptoError;
 use chacha20poly1305::{
     aead::{Aead, KeyInit as AeadKeyInit},
@@ -41,18 +43,40 @@ use chacha20poly1305::{
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand::rngs::OsRng;
-use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use secp256k1::{PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{HashMap, VecDeque};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 /// Maximum number of skipped message keys to store
 const MAX_SKIP: usize = 1000;
 
+/// Maximum number of recently decrypted message ids remembered for replay detection
+const MAX_RECENT_DECRYPTED: usize = 1000;
+
+/// Hash iterations per identity key when computing a safety number
+const SAFETY_NUMBER_ITERATIONS: usize = 5200;
+
+/// Domain separator (and version) for safety number fingerprints
+const SAFETY_NUMBER_DOMAIN: &[u8] = b"BuildIt-SafetyNumber-v1";
+
+/// AAD prefix binding an exported session to its session identifier
+const SESSION_EXPORT_AAD_PREFIX: &[u8] = b"BuildIt-RatchetExport-v1:";
+
 /// HKDF info string for root key derivation
 const KDF_RK_INFO: &[u8] = b"BuildIt-Ratchet-RootKey";
 
+/// Length of a compressed secp256k1 DH ratchet public key
+const DH_PUBLIC_KEY_LEN: usize = 33;
+
+/// Layout version written into serialized session state
+///
+/// Bump when `RatchetSessionState` changes in a way older readers cannot
+/// parse, and teach `RatchetSessionState::from_json` to migrate the old one.
+/// State stored before versioning is a bare object and reads as version 0.
+pub const RATCHET_SESSION_VERSION: u32 = 1;
+
 /// Header sent with each encrypted message
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MessageHeader {
@@ -170,6 +194,18 @@ impl DhKeyPair {
     }
 }
 
+/// Check that a header's DH key is a compressed point on secp256k1
+///
+/// Done before any ratchet step so a malformed or attacker-chosen key is
+/// refused without touching session state.
+fn validate_dh_public_key(public_key: &[u8]) -> Result<(), CryptoError> {
+    if public_key.len() != DH_PUBLIC_KEY_LEN {
+        return Err(CryptoError::InvalidPublicKey);
+    }
+    PublicKey::from_slice(public_key).map_err(|_| CryptoError::InvalidPublicKey)?;
+    Ok(())
+}
+
 /// Ratchet session state
 ///
 /// SECURITY: Contains sensitive key material - should be stored encrypted
@@ -206,7 +242,25 @@ pub struct RatchetSessionState {
     previous_chain_length: u32,
 
     /// Skipped message keys: (dh_public_key, message_number) -> message_key
+    #[serde(with = "skipped_keys_serde")]
     skipped_message_keys: HashMap<(Vec<u8>, u32), [u8; 32]>,
+
+    /// Recently decrypted (dh_public_key, message_number) pairs, oldest first
+    #[serde(default)]
+    recently_decrypted: VecDeque<(Vec<u8>, u32)>,
+}
+
+/// Serialized session state tagged with its layout version
+#[derive(Serialize, Deserialize)]
+struct VersionedSessionState<S> {
+    version: u32,
+    state: S,
+}
+
+/// Just the version of serialized session state; all else is skipped
+#[derive(Deserialize)]
+struct SessionVersionProbe {
+    version: Option<u32>,
 }
 
 // Serde helpers for sensitive types
@@ -282,6 +336,55 @@ mod option_key_serde {
     }
 }
 
+/// Skipped keys as a list of `(dh_public_key, message_number, key)`
+///
+/// JSON maps need string keys, so the tuple-keyed map cannot be written as
+/// one. State saved before this could only hold an empty map, which still
+/// reads as no skipped keys.
+mod skipped_keys_serde {
+    use super::*;
+    use serde::de::Error;
+    use serde::{Deserializer, Serializer};
+
+    type SkippedKeys = HashMap<(Vec<u8>, u32), [u8; 32]>;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        List(Vec<(Vec<u8>, u32, Vec<u8>)>),
+        LegacyMap(HashMap<String, serde::de::IgnoredAny>),
+    }
+
+    pub fn serialize<S>(keys: &SkippedKeys, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(
+            keys.iter()
+                .map(|((dh_public_key, number), key)| (dh_public_key, number, key.to_vec())),
+        )
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<SkippedKeys, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Stored::deserialize(deserializer)? {
+            Stored::List(entries) => entries
+                .into_iter()
+                .map(|(dh_public_key, number, bytes)| {
+                    let key: [u8; 32] = bytes
+                        .try_into()
+                        .map_err(|_| D::Error::custom("skipped message key must be 32 bytes"))?;
+                    Ok(((dh_public_key, number), key))
+                })
+                .collect(),
+            Stored::LegacyMap(map) if map.is_empty() => Ok(HashMap::new()),
+            Stored::LegacyMap(_) => Err(D::Error::custom("unsupported skipped key map")),
+        }
+    }
+}
+
 impl RatchetSessionState {
     /// Initialize a new session as Alice (initiator)
     fn initialize_alice_internal(
@@ -307,6 +410,7 @@ impl RatchetSessionState {
             message_number_recv: 0,
             previous_chain_length: 0,
             skipped_message_keys: HashMap::new(),
+            recently_decrypted: VecDeque::new(),
         })
     }
 
@@ -327,6 +431,7 @@ impl RatchetSessionState {
             message_number_recv: 0,
             previous_chain_length: 0,
             skipped_message_keys: HashMap::new(),
+            recently_decrypted: VecDeque::new(),
         })
     }
 
@@ -364,19 +469,34 @@ impl RatchetSessionState {
     /// Decrypt a message
     ///
     /// Handles DH ratchet steps and out-of-order messages automatically.
+    /// A message that was already decrypted yields `DuplicateMessage` and
+    /// leaves the session state untouched, as does a header whose DH key is
+    /// not a valid point (`InvalidPublicKey`).
     pub fn decrypt(&mut self, message: &RatchetMessage) -> Result<Vec<u8>, CryptoError> {
-        // Check if we have a stored key for this message
+        validate_dh_public_key(&message.header.dh_public_key)?;
+
         let key_id = (
             message.header.dh_public_key.clone(),
             message.header.message_number,
         );
-        if let Some(message_key) = self.skipped_message_keys.remove(&key_id) {
-            return decrypt_message(
+
+        // Check if we have a stored key for this message
+        if let Some(message_key) = self.skipped_message_keys.get(&key_id).copied() {
+            let plaintext = decrypt_message(
                 &message_key,
                 &message.ciphertext,
                 &message.nonce,
                 &message.header,
-            );
+            )?;
+            if let Some(mut used) = self.skipped_message_keys.remove(&key_id) {
+                used.zeroize();
+            }
+            self.remember_decrypted(key_id);
+            return Ok(plaintext);
+        }
+
+        if self.is_duplicate(&key_id) {
+            return Err(CryptoError::DuplicateMessage);
         }
 
         // Check if this is a new DH ratchet step
@@ -404,12 +524,33 @@ impl RatchetSessionState {
         self.message_number_recv += 1;
 
         // Decrypt
-        decrypt_message(
+        let plaintext = decrypt_message(
             &message_key,
             &message.ciphertext,
             &message.nonce,
             &message.header,
-        )
+        )?;
+        self.remember_decrypted(key_id);
+        Ok(plaintext)
+    }
+
+    /// Whether a message id has already been consumed
+    ///
+    /// Covers ids in the bounded recent set, plus any number below the
+    /// receive counter of the current chain that has no stored skipped key.
+    fn is_duplicate(&self, key_id: &(Vec<u8>, u32)) -> bool {
+        if self.recently_decrypted.contains(key_id) {
+            return true;
+        }
+        self.dh_remote.as_ref() == Some(&key_id.0) && key_id.1 < self.message_number_recv
+    }
+
+    /// Record a successfully decrypted message id, evicting the oldest
+    fn remember_decrypted(&mut self, key_id: (Vec<u8>, u32)) {
+        self.recently_decrypted.push_back(key_id);
+        while self.recently_decrypted.len() > MAX_RECENT_DECRYPTED {
+            self.recently_decrypted.pop_front();
+        }
     }
 
     /// Perform a DH ratchet step (receiving side)
@@ -490,6 +631,33 @@ impl RatchetSessionState {
         self.dh_self.public_key.clone()
     }
 
+    /// Messages sent plus received since the last DH ratchet step
+    ///
+    /// Both chain counters reset when a new remote DH key arrives, so this
+    /// measures how much traffic the current chain keys have protected.
+    pub fn messages_since_dh_ratchet(&self) -> u32 {
+        self.message_number_send
+            .saturating_add(self.message_number_recv)
+    }
+
+    /// Whether the session has carried at least `threshold` messages since the
+    /// last DH ratchet step and a fresh key agreement should be triggered
+    pub fn should_rekey(&self, threshold: u32) -> bool {
+        self.messages_since_dh_ratchet() >= threshold
+    }
+
+    /// Zeroize and drop every skipped message key, returning how many there were
+    ///
+    /// Root and chain keys are untouched, so the session carries on; only
+    /// messages skipped so far can no longer be decrypted.
+    pub fn clear_skipped_keys(&mut self) -> u32 {
+        let count = self.skipped_message_keys.len() as u32;
+        for (_, mut key) in self.skipped_message_keys.drain() {
+            key.zeroize();
+        }
+        count
+    }
+
     /// Serialize and encrypt session state for safe storage.
     ///
     /// Uses AES-256-GCM to encrypt the serialized ratchet state, ensuring
@@ -499,7 +667,7 @@ impl RatchetSessionState {
     /// # Arguments
     /// * `storage_key` - 32-byte AES-256 key for encrypting the session state
     pub fn serialize_encrypted(&self, storage_key: Vec<u8>) -> Result<EncryptedData, CryptoError> {
-        let mut plaintext = serde_json::to_vec(self).map_err(|_| CryptoError::InvalidJson)?;
+        let mut plaintext = self.to_json()?;
         let result = aes_encrypt(storage_key, plaintext.clone());
         plaintext.zeroize();
         result
@@ -515,7 +683,7 @@ impl RatchetSessionState {
         storage_key: Vec<u8>,
     ) -> Result<Self, CryptoError> {
         let mut plaintext = aes_decrypt(storage_key, encrypted)?;
-        let result = serde_json::from_slice(&plaintext).map_err(|_| CryptoError::InvalidJson);
+        let result = Self::from_json(&plaintext);
         plaintext.zeroize();
         result
     }
@@ -525,7 +693,7 @@ impl RatchetSessionState {
     /// WARNING: Returns unencrypted key material. Use `serialize_encrypted` instead.
     #[doc(hidden)]
     pub fn serialize_unencrypted(&self) -> Result<Vec<u8>, CryptoError> {
-        serde_json::to_vec(self).map_err(|_| CryptoError::InvalidJson)
+        self.to_json()
     }
 
     /// Deserialize session state from plaintext bytes (for internal use only).
@@ -533,7 +701,36 @@ impl RatchetSessionState {
     /// WARNING: Expects unencrypted input. Use `deserialize_encrypted` instead.
     #[doc(hidden)]
     pub fn deserialize_unencrypted(data: &[u8]) -> Result<Self, CryptoError> {
-        serde_json::from_slice(data).map_err(|_| CryptoError::InvalidJson)
+        Self::from_json(data)
+    }
+
+    /// Serialize as `{"version": RATCHET_SESSION_VERSION, "state": {...}}`
+    fn to_json(&self) -> Result<Vec<u8>, CryptoError> {
+        serde_json::to_vec(&VersionedSessionState {
+            version: RATCHET_SESSION_VERSION,
+            state: self,
+        })
+        .map_err(|_| CryptoError::InvalidJson)
+    }
+
+    /// Parse state written by `to_json` at this or any earlier version
+    ///
+    /// Fails with `UnsupportedSessionVersion` for state written by a newer
+    /// release, rather than misreading it.
+    fn from_json(data: &[u8]) -> Result<Self, CryptoError> {
+        let probe: SessionVersionProbe =
+            serde_json::from_slice(data).map_err(|_| CryptoError::InvalidJson)?;
+
+        match probe.version {
+            // Version 0: the bare state, before the envelope existed
+            None => serde_json::from_slice(data).map_err(|_| CryptoError::InvalidJson),
+            Some(RATCHET_SESSION_VERSION) => {
+                serde_json::from_slice::<VersionedSessionState<Self>>(data)
+                    .map(|versioned| versioned.state)
+                    .map_err(|_| CryptoError::InvalidJson)
+            }
+            Some(_) => Err(CryptoError::UnsupportedSessionVersion),
+        }
     }
 }
 
@@ -637,15 +834,83 @@ fn decrypt_message(
     Ok(plaintext)
 }
 
+/// Parse an identity key and return its 32-byte x-only form
+///
+/// Accepts a 32-byte x-only or 33-byte compressed key. The parity prefix is
+/// dropped so both encodings of one identity fingerprint the same.
+fn identity_x_only(identity_pubkey: &[u8]) -> Result<[u8; 32], CryptoError> {
+    let x_only = match identity_pubkey.len() {
+        32 => XOnlyPublicKey::from_slice(identity_pubkey),
+        33 => PublicKey::from_slice(identity_pubkey).map(|key| key.x_only_public_key().0),
+        _ => return Err(CryptoError::InvalidPublicKey),
+    };
+    x_only
+        .map(|key| key.serialize())
+        .map_err(|_| CryptoError::InvalidPublicKey)
+}
+
+/// 30-digit fingerprint of one x-only identity key
+///
+/// SHA-512 iterated over the domain-separated key, then the first 30 bytes
+/// are read as six 40-bit big-endian integers, each reduced to 5 digits.
+fn identity_fingerprint(identity_pubkey: &[u8; 32]) -> String {
+    let mut hash = Sha512::new()
+        .chain_update(SAFETY_NUMBER_DOMAIN)
+        .chain_update(identity_pubkey)
+        .finalize();
+    for _ in 1..SAFETY_NUMBER_ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(identity_pubkey)
+            .finalize();
+    }
+
+    hash[..30]
+        .chunks(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", value % 100_000)
+        })
+        .collect()
+}
+
 /// Thread-safe wrapper for RatchetSession exposed via UniFFI
 ///
 /// This wrapper provides a thread-safe interface to the Double Ratchet
 /// session that can be used from Swift, Kotlin, and TypeScript via UniFFI.
 pub struct RatchetSession {
     state: std::sync::Mutex<RatchetSessionState>,
+    on_state_change: std::sync::Mutex<Option<StateChangeHook>>,
 }
 
+/// Called after a session's state has changed
+pub type StateChangeHook = std::sync::Arc<dyn Fn() + Send + Sync>;
+
 impl RatchetSession {
+    fn from_state(state: RatchetSessionState) -> Self {
+        Self {
+            state: std::sync::Mutex::new(state),
+            on_state_change: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Call `hook` after every change to the session state
+    ///
+    /// Successful encrypts and decrypts and clearing skipped keys all change
+    /// the state, which must be stored again or it is lost on a crash. The
+    /// hook runs after the state lock is released, so it may serialize the
+    /// session. Replaces any previous hook.
+    pub fn set_on_state_change(&self, hook: StateChangeHook) {
+        *self.on_state_change.lock().unwrap() = Some(hook);
+    }
+
+    fn state_changed(&self) {
+        let hook = self.on_state_change.lock().unwrap().clone();
+        if let Some(hook) = hook {
+            hook();
+        }
+    }
+
     /// Initialize a new session as Alice (initiator)
     ///
     /// # Arguments
@@ -664,9 +929,7 @@ impl RatchetSession {
 
         let state = RatchetSessionState::initialize_alice_internal(&secret, &bob_public_key)?;
 
-        Ok(Self {
-            state: std::sync::Mutex::new(state),
-        })
+        Ok(Self::from_state(state))
     }
 
     /// Initialize a new session as Bob (responder)
@@ -687,9 +950,7 @@ impl RatchetSession {
 
         let state = RatchetSessionState::initialize_bob_internal(&secret, &our_signed_prekey)?;
 
-        Ok(Self {
-            state: std::sync::Mutex::new(state),
-        })
+        Ok(Self::from_state(state))
     }
 
     /// Encrypt a message with forward secrecy
@@ -701,7 +962,10 @@ impl RatchetSession {
             .state
             .lock()
             .map_err(|_| CryptoError::EncryptionFailed)?;
-        state.encrypt(&plaintext)
+        let message = state.encrypt(&plaintext)?;
+        drop(state);
+        self.state_changed();
+        Ok(message)
     }
 
     /// Decrypt a message
@@ -712,7 +976,10 @@ impl RatchetSession {
             .state
             .lock()
             .map_err(|_| CryptoError::DecryptionFailed)?;
-        state.decrypt(&message)
+        let plaintext = state.decrypt(&message)?;
+        drop(state);
+        self.state_changed();
+        Ok(plaintext)
     }
 
     /// Get our current public DH key
@@ -721,6 +988,61 @@ impl RatchetSession {
         state.get_public_key()
     }
 
+    /// Messages sent plus received since the last DH ratchet step
+    pub fn messages_since_dh_ratchet(&self) -> u32 {
+        let state = self.state.lock().unwrap();
+        state.messages_since_dh_ratchet()
+    }
+
+    /// Whether a fresh X3DH key agreement should be triggered
+    ///
+    /// Returns true once `threshold` messages have been sent or received
+    /// since the last DH ratchet step.
+    pub fn should_rekey(&self, threshold: u32) -> bool {
+        let state = self.state.lock().unwrap();
+        state.should_rekey(threshold)
+    }
+
+    /// Zeroize and drop the keys kept for messages that arrived out of order
+    ///
+    /// Returns how many were dropped. The ratchet itself is unaffected, but
+    /// skipped messages still in flight can no longer be decrypted.
+    pub fn clear_skipped_keys(&self) -> u32 {
+        let cleared = self.state.lock().unwrap().clear_skipped_keys();
+        if cleared > 0 {
+            self.state_changed();
+        }
+        cleared
+    }
+
+    /// Compute the safety number two users compare out-of-band to rule out a MITM
+    ///
+    /// Returns 60 digits in 12 space-separated groups of 5. Each identity key
+    /// contributes a 30-digit fingerprint of its x-only form; the two halves
+    /// are sorted so both parties get the same string regardless of argument
+    /// order or key encoding.
+    ///
+    /// # Arguments
+    /// * `our_identity_pubkey` - Our identity public key (32-byte x-only or 33-byte compressed)
+    /// * `their_identity_pubkey` - Their identity public key, either encoding
+    pub fn safety_number(
+        our_identity_pubkey: Vec<u8>,
+        their_identity_pubkey: Vec<u8>,
+    ) -> Result<String, CryptoError> {
+        let mut halves = [
+            identity_fingerprint(&identity_x_only(&our_identity_pubkey)?),
+            identity_fingerprint(&identity_x_only(&their_identity_pubkey)?),
+        ];
+        halves.sort();
+        let digits = halves.concat();
+
+        let groups: Vec<&str> = (0..digits.len())
+            .step_by(5)
+            .map(|i| &digits[i..i + 5])
+            .collect();
+        Ok(groups.join(" "))
+    }
+
     /// Serialize and encrypt session state for safe storage.
     ///
     /// # Arguments
@@ -740,9 +1062,7 @@ impl RatchetSession {
         storage_key: Vec<u8>,
     ) -> Result<Self, CryptoError> {
         let state = RatchetSessionState::deserialize_encrypted(encrypted, storage_key)?;
-        Ok(Self {
-            state: std::sync::Mutex::new(state),
-        })
+        Ok(Self::from_state(state))
     }
 
     /// Serialize session state to plaintext bytes (for internal use only).
@@ -760,12 +1080,73 @@ impl RatchetSession {
     #[doc(hidden)]
     pub fn deserialize_unencrypted(data: Vec<u8>) -> Result<Self, CryptoError> {
         let state = RatchetSessionState::deserialize_unencrypted(&data)?;
-        Ok(Self {
-            state: std::sync::Mutex::new(state),
-        })
+        Ok(Self::from_state(state))
     }
 }
 
+/// Decode a hex storage key, requiring 32 bytes
+fn storage_key_from_hex(key_hex: &str) -> Result<Vec<u8>, CryptoError> {
+    let key = hex::decode(key_hex).map_err(|_| CryptoError::InvalidHex)?;
+    if key.len() != 32 {
+        return Err(CryptoError::InvalidKey);
+    }
+    Ok(key)
+}
+
+/// Safety number for two identity keys (UniFFI export)
+///
+/// See [`RatchetSession::safety_number`].
+pub fn ratchet_safety_number(
+    our_identity_pubkey: Vec<u8>,
+    their_identity_pubkey: Vec<u8>,
+) -> Result<String, CryptoError> {
+    RatchetSession::safety_number(our_identity_pubkey, their_identity_pubkey)
+}
+
+fn session_export_aad(session_id: &str) -> Vec<u8> {
+    [SESSION_EXPORT_AAD_PREFIX, session_id.as_bytes()].concat()
+}
+
+/// Serialize and encrypt a session for backup in one step
+///
+/// The state is encrypted with AES-256-GCM under the 32-byte hex
+/// `key_hex`, with `session_id` bound as associated data so a backup
+/// cannot be restored under another session's identifier. The plaintext
+/// state never leaves this function.
+pub fn export_ratchet_session(
+    session: &RatchetSession,
+    session_id: &str,
+    key_hex: &str,
+) -> Result<EncryptedData, CryptoError> {
+    let mut key = storage_key_from_hex(key_hex)?;
+    let mut plaintext = session.serialize_unencrypted()?;
+    let result = aes_encrypt_with_aad(&key, &plaintext, &session_export_aad(session_id));
+    plaintext.zeroize();
+    key.zeroize();
+    result
+}
+
+/// Decrypt and restore a session exported by [`export_ratchet_session`]
+///
+/// Fails with `DecryptionFailed` if the key or `session_id` differ from
+/// export, or the ciphertext has been tampered with.
+pub fn import_ratchet_session(
+    ciphertext: Vec<u8>,
+    nonce: Vec<u8>,
+    session_id: &str,
+    key_hex: &str,
+) -> Result<RatchetSession, CryptoError> {
+    let mut key = storage_key_from_hex(key_hex)?;
+    let encrypted = EncryptedData { ciphertext, nonce };
+    let result = aes_decrypt_with_aad(&key, &encrypted, &session_export_aad(session_id));
+    key.zeroize();
+
+    let mut plaintext = result?;
+    let state = RatchetSessionState::deserialize_unencrypted(&plaintext);
+    plaintext.zeroize();
+    Ok(RatchetSession::from_state(state?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -867,6 +1248,77 @@ mod tests {
         assert_eq!(decrypted2, b"Message 2");
     }
 
+    #[test]
+    fn test_clear_skipped_keys() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let msg1 = alice.encrypt(b"Message 1".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"Message 2".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg2).unwrap(), b"Message 2");
+
+        assert_eq!(bob.clear_skipped_keys(), 1);
+        assert_eq!(bob.clear_skipped_keys(), 0);
+        assert!(bob.decrypt(msg1).is_err());
+
+        // Root and chain keys are kept, so the session continues both ways
+        let msg3 = alice.encrypt(b"Message 3".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg3).unwrap(), b"Message 3");
+        let reply = bob.encrypt(b"Reply".to_vec()).unwrap();
+        assert_eq!(alice.decrypt(reply).unwrap(), b"Reply");
+    }
+
+    #[test]
+    fn test_state_change_hook_runs_after_each_change() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob = Arc::new(
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap(),
+        );
+
+        // The hook can read the session it is attached to
+        let snapshots = Arc::new(AtomicUsize::new(0));
+        let weak_bob = Arc::downgrade(&bob);
+        let counter = snapshots.clone();
+        bob.set_on_state_change(Arc::new(move || {
+            let bob = weak_bob.upgrade().unwrap();
+            assert!(bob.serialize_unencrypted().is_ok());
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let msg1 = alice.encrypt(b"Message 1".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"Message 2".to_vec()).unwrap();
+        bob.decrypt(msg2).unwrap();
+        assert_eq!(snapshots.load(Ordering::SeqCst), 1);
+
+        bob.encrypt(b"Reply".to_vec()).unwrap();
+        assert_eq!(snapshots.load(Ordering::SeqCst), 2);
+
+        // Failures and no-op clears leave the state alone
+        let mut tampered = alice.encrypt(b"Message 3".to_vec()).unwrap();
+        tampered.ciphertext[0] ^= 1;
+        assert!(bob.decrypt(tampered).is_err());
+        assert_eq!(snapshots.load(Ordering::SeqCst), 2);
+
+        assert_eq!(bob.clear_skipped_keys(), 1);
+        assert_eq!(bob.clear_skipped_keys(), 0);
+        assert_eq!(snapshots.load(Ordering::SeqCst), 3);
+        assert!(bob.decrypt(msg1).is_err());
+    }
+
     #[test]
     fn test_serialization_unencrypted() {
         let shared_secret = generate_shared_secret();
@@ -885,6 +1337,98 @@ mod tests {
         assert_eq!(alice.get_public_key(), deserialized.get_public_key());
     }
 
+    #[test]
+    fn test_serialization_versioned() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let serialized = alice.serialize_unencrypted().unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&serialized).unwrap();
+        assert_eq!(value["version"], RATCHET_SESSION_VERSION);
+        assert!(value["state"]["root_key"].is_array());
+
+        let restored = RatchetSession::deserialize_unencrypted(serialized).unwrap();
+        let msg = restored.encrypt(b"Hello Bob!".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg).unwrap(), b"Hello Bob!");
+    }
+
+    #[test]
+    fn test_serialization_keeps_skipped_keys() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let msg1 = alice.encrypt(b"Message 1".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"Message 2".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg2).unwrap(), b"Message 2");
+
+        let restored =
+            RatchetSession::deserialize_unencrypted(bob.serialize_unencrypted().unwrap()).unwrap();
+        assert_eq!(restored.decrypt(msg1).unwrap(), b"Message 1");
+
+        // State written before skipped keys were listed held an empty map
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&alice.serialize_unencrypted().unwrap()).unwrap();
+        value["state"]["skipped_message_keys"] = serde_json::json!({});
+        assert!(
+            RatchetSession::deserialize_unencrypted(serde_json::to_vec(&value).unwrap()).is_ok()
+        );
+    }
+
+    #[test]
+    fn test_unversioned_state_migrates() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret, bob_prekey.public_key.clone()).unwrap();
+        let legacy = serde_json::to_vec(&*alice.state.lock().unwrap()).unwrap();
+
+        let restored = RatchetSession::deserialize_unencrypted(legacy).unwrap();
+        assert_eq!(alice.get_public_key(), restored.get_public_key());
+
+        // Resaving writes the current version
+        let resaved: serde_json::Value =
+            serde_json::from_slice(&restored.serialize_unencrypted().unwrap()).unwrap();
+        assert_eq!(resaved["version"], RATCHET_SESSION_VERSION);
+    }
+
+    #[test]
+    fn test_unknown_session_version_rejected() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret, bob_prekey.public_key.clone()).unwrap();
+        let mut value: serde_json::Value =
+            serde_json::from_slice(&alice.serialize_unencrypted().unwrap()).unwrap();
+        value["version"] = (RATCHET_SESSION_VERSION + 1).into();
+        let bumped = serde_json::to_vec(&value).unwrap();
+
+        assert!(matches!(
+            RatchetSession::deserialize_unencrypted(bumped.clone()),
+            Err(CryptoError::UnsupportedSessionVersion)
+        ));
+
+        let storage_key = vec![0x42u8; 32];
+        let encrypted = aes_encrypt(storage_key.clone(), bumped).unwrap();
+        assert!(matches!(
+            RatchetSession::deserialize_encrypted(encrypted, storage_key),
+            Err(CryptoError::UnsupportedSessionVersion)
+        ));
+    }
+
     #[test]
     fn test_ratchet_encrypted_serialization_roundtrip() {
         let shared_secret = generate_shared_secret();
@@ -936,6 +1480,78 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_export_import_ratchet_session_roundtrip() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+        let _ = bob
+            .decrypt(alice.encrypt(b"Hello Bob!".to_vec()).unwrap())
+            .unwrap();
+
+        let key_hex = hex::encode([0x42u8; 32]);
+        let exported = export_ratchet_session(&alice, "conv-1", &key_hex).unwrap();
+        assert_eq!(exported.nonce.len(), 12);
+        assert_ne!(exported.ciphertext, alice.serialize_unencrypted().unwrap());
+
+        let restored =
+            import_ratchet_session(exported.ciphertext, exported.nonce, "conv-1", &key_hex)
+                .unwrap();
+        assert_eq!(restored.get_public_key(), alice.get_public_key());
+
+        let msg = restored.encrypt(b"Still works!".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg).unwrap(), b"Still works!");
+    }
+
+    #[test]
+    fn test_import_ratchet_session_wrong_key_or_id_fails() {
+        let bob_prekey = DhKeyPair::generate().unwrap();
+        let alice = RatchetSession::initialize_alice(
+            generate_shared_secret(),
+            bob_prekey.public_key.clone(),
+        )
+        .unwrap();
+
+        let key_hex = hex::encode([0x42u8; 32]);
+        let exported = export_ratchet_session(&alice, "conv-1", &key_hex).unwrap();
+
+        let wrong_key = hex::encode([0x99u8; 32]);
+        assert!(matches!(
+            import_ratchet_session(
+                exported.ciphertext.clone(),
+                exported.nonce.clone(),
+                "conv-1",
+                &wrong_key
+            ),
+            Err(CryptoError::DecryptionFailed)
+        ));
+
+        // The session identifier is authenticated
+        assert!(matches!(
+            import_ratchet_session(
+                exported.ciphertext.clone(),
+                exported.nonce.clone(),
+                "conv-2",
+                &key_hex
+            ),
+            Err(CryptoError::DecryptionFailed)
+        ));
+
+        assert!(matches!(
+            export_ratchet_session(&alice, "conv-1", "abcd"),
+            Err(CryptoError::InvalidKey)
+        ));
+        assert!(matches!(
+            import_ratchet_session(exported.ciphertext, exported.nonce, "conv-1", "zz"),
+            Err(CryptoError::InvalidHex)
+        ));
+    }
+
     #[test]
     fn test_forward_secrecy() {
         // This test verifies that message keys are different for each message
@@ -955,4 +1571,177 @@ mod tests {
         // Message numbers should be different
         assert_ne!(msg1.header.message_number, msg2.header.message_number);
     }
+
+    #[test]
+    fn test_should_rekey_tracks_message_count() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        assert_eq!(alice.messages_since_dh_ratchet(), 0);
+        assert!(!alice.should_rekey(5));
+
+        for i in 1..=5 {
+            let message = alice.encrypt(format!("msg {}", i).into_bytes()).unwrap();
+            bob.decrypt(message).unwrap();
+            assert_eq!(alice.messages_since_dh_ratchet(), i);
+        }
+        assert!(alice.should_rekey(5));
+        assert!(!alice.should_rekey(6));
+        assert_eq!(bob.messages_since_dh_ratchet(), 5);
+
+        // Bob's reply carries a new DH key; Alice's ratchet step resets her count
+        let reply = bob.encrypt(b"reply".to_vec()).unwrap();
+        alice.decrypt(reply).unwrap();
+        assert_eq!(alice.messages_since_dh_ratchet(), 1);
+        assert!(!alice.should_rekey(5));
+    }
+
+    #[test]
+    fn test_duplicate_message_detected() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let msg1 = alice.encrypt(b"first".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"second".to_vec()).unwrap();
+
+        assert_eq!(bob.decrypt(msg1.clone()).unwrap(), b"first");
+        assert_eq!(bob.decrypt(msg1), Err(CryptoError::DuplicateMessage));
+
+        // State did not advance: the next message still decrypts
+        assert_eq!(bob.decrypt(msg2.clone()).unwrap(), b"second");
+        assert_eq!(bob.decrypt(msg2), Err(CryptoError::DuplicateMessage));
+    }
+
+    #[test]
+    fn test_duplicate_out_of_order_message_detected() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let msg1 = alice.encrypt(b"first".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"second".to_vec()).unwrap();
+
+        // msg1 is decrypted from a stored skipped key
+        assert_eq!(bob.decrypt(msg2).unwrap(), b"second");
+        assert_eq!(bob.decrypt(msg1.clone()).unwrap(), b"first");
+        assert_eq!(bob.decrypt(msg1), Err(CryptoError::DuplicateMessage));
+    }
+
+    #[test]
+    fn test_invalid_header_dh_key_rejected_early() {
+        let shared_secret = generate_shared_secret();
+        let bob_prekey = DhKeyPair::generate().unwrap();
+
+        let alice =
+            RatchetSession::initialize_alice(shared_secret.clone(), bob_prekey.public_key.clone())
+                .unwrap();
+        let bob =
+            RatchetSession::initialize_bob(shared_secret, bob_prekey.private_key.to_vec()).unwrap();
+
+        let msg1 = alice.encrypt(b"first".to_vec()).unwrap();
+        let msg2 = alice.encrypt(b"second".to_vec()).unwrap();
+        assert_eq!(bob.decrypt(msg1).unwrap(), b"first");
+
+        // Wrong length, not on the curve, and a bogus prefix byte
+        let mut off_curve = vec![0x02];
+        off_curve.extend_from_slice(&[0xff; 32]);
+        let mut bad_prefix = msg2.header.dh_public_key.clone();
+        bad_prefix[0] = 0x05;
+        for garbage in [vec![0xab; 12], off_curve, bad_prefix] {
+            let mut forged = msg2.clone();
+            forged.header.dh_public_key = garbage;
+            forged.header.previous_chain_length = 500;
+            assert_eq!(bob.decrypt(forged), Err(CryptoError::InvalidPublicKey));
+        }
+
+        // No ratchet step or skipped keys happened: the real message decrypts
+        assert_eq!(bob.decrypt(msg2).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_safety_number_order_independent() {
+        let alice = DhKeyPair::generate().unwrap();
+        let bob = DhKeyPair::generate().unwrap();
+
+        let ab = RatchetSession::safety_number(alice.public_key.clone(), bob.public_key.clone())
+            .unwrap();
+        let ba = RatchetSession::safety_number(bob.public_key.clone(), alice.public_key.clone())
+            .unwrap();
+        assert_eq!(ab, ba);
+
+        let groups: Vec<&str> = ab.split(' ').collect();
+        assert_eq!(groups.len(), 12);
+        assert!(groups
+            .iter()
+            .all(|g| g.len() == 5 && g.chars().all(|c| c.is_ascii_digit())));
+    }
+
+    #[test]
+    fn test_safety_number_changes_with_either_key() {
+        let alice = DhKeyPair::generate().unwrap();
+        let bob = DhKeyPair::generate().unwrap();
+        let mallory = DhKeyPair::generate().unwrap();
+
+        let original =
+            RatchetSession::safety_number(alice.public_key.clone(), bob.public_key.clone())
+                .unwrap();
+        let their_changed =
+            RatchetSession::safety_number(alice.public_key.clone(), mallory.public_key.clone())
+                .unwrap();
+        let our_changed =
+            RatchetSession::safety_number(mallory.public_key.clone(), bob.public_key.clone())
+                .unwrap();
+
+        assert_ne!(original, their_changed);
+        assert_ne!(original, our_changed);
+        assert!(RatchetSession::safety_number(vec![1u8; 5], bob.public_key.clone()).is_err());
+    }
+
+    #[test]
+    fn test_safety_number_ignores_key_encoding() {
+        let alice = DhKeyPair::generate().unwrap();
+        let bob = DhKeyPair::generate().unwrap();
+        assert_eq!(alice.public_key.len(), 33);
+
+        let compressed =
+            RatchetSession::safety_number(alice.public_key.clone(), bob.public_key.clone())
+                .unwrap();
+        let x_only =
+            RatchetSession::safety_number(alice.public_key[1..].to_vec(), bob.public_key.clone())
+                .unwrap();
+        let both_x_only = RatchetSession::safety_number(
+            alice.public_key[1..].to_vec(),
+            bob.public_key[1..].to_vec(),
+        )
+        .unwrap();
+        assert_eq!(compressed, x_only);
+        assert_eq!(compressed, both_x_only);
+        assert_eq!(
+            ratchet_safety_number(bob.public_key[1..].to_vec(), alice.public_key.clone()).unwrap(),
+            compressed
+        );
+
+        // Right length, but not a point on the curve
+        let mut bad_prefix = alice.public_key.clone();
+        bad_prefix[0] = 0x04;
+        assert!(RatchetSession::safety_number(bad_prefix, bob.public_key.clone()).is_err());
+        assert!(RatchetSession::safety_number(vec![0xff; 32], bob.public_key.clone()).is_err());
+    }
 }
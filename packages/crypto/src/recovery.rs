@@ -0,0 +1,352 @@
+//! Social Recovery Kits
+//!
+//! Splits the user's master key into Shamir shares (see `multisig`) that can
+//! be printed or handed to trusted contacts. Any `threshold` shares restore
+//! the master key; fewer reveal nothing about it.
+//!
+//! Each share is a self-describing text blob:
+//! - `buildit-recovery-v1:<base64 share>` when kept in the clear (paper backup)
+//! - `buildit-recovery-v1:<ephemeral pubkey>:<NIP-44 payload>` when encrypted
+//!   to a contact, who needs only their own private key to open it
+//!
+//! The kit id is random, so it says nothing about whose key the kit holds.
+//! Shares carry it together with a short check over the master key's public
+//! key, so reconstruction can tell that it recovered the key the kit was made
+//! from rather than a mix of shares from different kits.
+
+use crate::error::CryptoError;
+use crate::keys::{generate_keypair, get_public_key};
+use crate::multisig::{
+    decrypt_share, encrypt_share_for, reconstruct_secret_zeroizing, split_secret, KeyShare,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Prefix (and version) of every recovery share blob
+pub const RECOVERY_SHARE_PREFIX: &str = "buildit-recovery-v1:";
+
+/// Domain separator for the kit check
+const RECOVERY_KIT_ID_DOMAIN: &[u8] = b"BuildIt-RecoveryKit-v1";
+
+/// One printable or shareable share of a recovery kit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryShare {
+    /// Share index (1-based)
+    pub index: u32,
+    /// Text blob to print or send
+    pub blob: String,
+    /// Contact the share is encrypted to, if any
+    pub recipient_pubkey: Option<String>,
+}
+
+/// A master key split into Shamir shares
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryKit {
+    /// Identifies the kit; random
+    pub kit_id: String,
+    /// Shares needed to recover (M)
+    pub threshold: u32,
+    /// Shares in the kit (N)
+    pub total_shares: u32,
+    pub shares: Vec<RecoveryShare>,
+}
+
+/// A fresh random kit id
+fn new_kit_id() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Check binding `kit_id` to a master key: a truncated hash of both the id
+/// and the key's public key
+fn recovery_kit_check(kit_id: &str, master_key: &[u8]) -> Result<String, CryptoError> {
+    let public_key = get_public_key(master_key.to_vec())?;
+    let mut hasher = Sha256::new();
+    hasher.update(RECOVERY_KIT_ID_DOMAIN);
+    hasher.update(kit_id.as_bytes());
+    hasher.update(public_key.as_bytes());
+    Ok(hex::encode(hasher.finalize())[..32].to_string())
+}
+
+/// Split `master_key` into a `threshold`-of-`total_shares` recovery kit
+///
+/// `recipients` is either empty (every share in the clear) or has one entry
+/// per share; `Some(pubkey)` encrypts that share to the contact with NIP-44
+/// under a one-time sender key, `None` leaves it in the clear.
+///
+/// SECURITY: Clear shares are as sensitive as the master key once
+/// `threshold` of them are together. Hand them out to different people or
+/// places.
+pub fn create_recovery_kit(
+    master_key: Vec<u8>,
+    threshold: u32,
+    total_shares: u32,
+    recipients: Vec<Option<String>>,
+) -> Result<RecoveryKit, CryptoError> {
+    let master_key = Zeroizing::new(master_key);
+    if master_key.len() != 32 {
+        return Err(CryptoError::InvalidKey);
+    }
+    if !recipients.is_empty() && recipients.len() != total_shares as usize {
+        return Err(CryptoError::InvalidKey);
+    }
+
+    let kit_id = new_kit_id();
+    let group_id = format!("{}:{}", kit_id, recovery_kit_check(&kit_id, &master_key)?);
+    let mut key_shares = split_secret(&master_key, &group_id, threshold, total_shares)?;
+
+    let shares = key_shares
+        .iter()
+        .enumerate()
+        .map(|(i, share)| {
+            let recipient_pubkey = recipients.get(i).cloned().flatten();
+            let blob = match &recipient_pubkey {
+                Some(pubkey) => share_blob_for(share, pubkey)?,
+                None => clear_share_blob(share)?.to_string(),
+            };
+            Ok(RecoveryShare {
+                index: share.index,
+                blob,
+                recipient_pubkey,
+            })
+        })
+        .collect::<Result<Vec<_>, CryptoError>>();
+    for share in key_shares.iter_mut() {
+        share.share_secret.zeroize();
+    }
+
+    Ok(RecoveryKit {
+        kit_id,
+        threshold,
+        total_shares,
+        shares: shares?,
+    })
+}
+
+/// Clear (paper backup) blob for a share
+fn clear_share_blob(share: &KeyShare) -> Result<Zeroizing<String>, CryptoError> {
+    let bytes = share.to_bytes()?;
+    let encoded = Zeroizing::new(BASE64.encode(&*bytes));
+    Ok(Zeroizing::new(format!(
+        "{}{}",
+        RECOVERY_SHARE_PREFIX, &*encoded
+    )))
+}
+
+/// Encrypt a share to `recipient_pubkey` under a one-time sender key, so
+/// the contact needs nothing but their own key to open it
+fn share_blob_for(share: &KeyShare, recipient_pubkey: &str) -> Result<String, CryptoError> {
+    let mut sender = generate_keypair();
+    let payload = encrypt_share_for(
+        share,
+        recipient_pubkey.to_string(),
+        sender.private_key.clone(),
+    );
+    sender.private_key.zeroize();
+    Ok(format!(
+        "{}{}:{}",
+        RECOVERY_SHARE_PREFIX, sender.public_key, payload?
+    ))
+}
+
+/// Decode a share blob from `create_recovery_kit`
+///
+/// Shares encrypted to a contact need that contact's `private_key`.
+pub fn open_recovery_share(
+    blob: String,
+    private_key: Option<Vec<u8>>,
+) -> Result<KeyShare, CryptoError> {
+    let body = blob
+        .trim()
+        .strip_prefix(RECOVERY_SHARE_PREFIX)
+        .ok_or(CryptoError::InvalidVersion)?;
+
+    match body.split_once(':') {
+        Some((sender_pubkey, payload)) => {
+            let private_key = Zeroizing::new(private_key.ok_or(CryptoError::InvalidKey)?);
+            decrypt_share(
+                payload.to_string(),
+                sender_pubkey.to_string(),
+                private_key.to_vec(),
+            )
+        }
+        None => {
+            let bytes = Zeroizing::new(BASE64.decode(body).map_err(|_| CryptoError::InvalidKey)?);
+            KeyShare::from_bytes(&bytes)
+        }
+    }
+}
+
+/// Open a share blob and return it as a clear blob
+///
+/// Lets a contact turn the share encrypted to them into one the owner can
+/// recover with, without the owner ever needing the contact's key. Clear
+/// blobs are returned unchanged after validation.
+///
+/// SECURITY: The result is as sensitive as the share itself.
+pub fn reveal_recovery_share(
+    blob: String,
+    private_key: Option<Vec<u8>>,
+) -> Result<String, CryptoError> {
+    let mut share = open_recovery_share(blob, private_key)?;
+    let clear = clear_share_blob(&share);
+    share.share_secret.zeroize();
+    Ok(clear?.to_string())
+}
+
+/// Restore the master key from at least `threshold` shares of one kit
+///
+/// The kit check is recomputed from the result and must match the shares',
+/// so too few, corrupted or mixed-up shares fail with `InvalidKey` instead of
+/// returning a wrong key.
+///
+/// SECURITY: The returned key MUST be zeroized by the caller.
+pub fn recover_from_kit(shares: Vec<KeyShare>) -> Result<Vec<u8>, CryptoError> {
+    let group_id = shares
+        .first()
+        .map(|share| share.group_id.clone())
+        .ok_or(CryptoError::InvalidKey)?;
+    let (kit_id, check) = group_id.split_once(':').ok_or(CryptoError::InvalidKey)?;
+
+    let mut master_key = reconstruct_secret_zeroizing(shares)?;
+    let expected = recovery_kit_check(kit_id, &master_key)?;
+    if !bool::from(expected.as_bytes().ct_eq(check.as_bytes())) {
+        return Err(CryptoError::InvalidKey);
+    }
+    Ok(std::mem::take(&mut *master_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_key() -> Vec<u8> {
+        generate_keypair().private_key
+    }
+
+    #[test]
+    fn test_recovery_kit_2_of_3_any_two_shares() {
+        let master = master_key();
+        let kit = create_recovery_kit(master.clone(), 2, 3, vec![]).unwrap();
+        assert_eq!(kit.shares.len(), 3);
+        assert!(kit
+            .shares
+            .iter()
+            .all(|s| s.blob.starts_with(RECOVERY_SHARE_PREFIX) && s.recipient_pubkey.is_none()));
+
+        let opened: Vec<KeyShare> = kit
+            .shares
+            .iter()
+            .map(|s| open_recovery_share(s.blob.clone(), None).unwrap())
+            .collect();
+        for (a, b) in [(0, 1), (0, 2), (1, 2)] {
+            let pair = vec![opened[a].clone(), opened[b].clone()];
+            assert_eq!(recover_from_kit(pair).unwrap(), master);
+        }
+
+        // One share reveals nothing
+        assert_eq!(
+            recover_from_kit(vec![opened[0].clone()]),
+            Err(CryptoError::InvalidKey)
+        );
+    }
+
+    #[test]
+    fn test_recovery_kit_shares_encrypted_to_contacts() {
+        let master = master_key();
+        let alice = generate_keypair();
+        let bob = generate_keypair();
+        let kit = create_recovery_kit(
+            master.clone(),
+            2,
+            3,
+            vec![
+                Some(alice.public_key.clone()),
+                Some(bob.public_key.clone()),
+                None,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            kit.shares[0].recipient_pubkey.as_deref(),
+            Some(alice.public_key.as_str())
+        );
+
+        // Encrypted shares need the contact's key, and only theirs
+        let alice_blob = kit.shares[0].blob.clone();
+        assert!(open_recovery_share(alice_blob.clone(), None).is_err());
+        assert!(open_recovery_share(alice_blob.clone(), Some(bob.private_key.clone())).is_err());
+
+        let from_alice = open_recovery_share(alice_blob, Some(alice.private_key)).unwrap();
+        let from_bob =
+            open_recovery_share(kit.shares[1].blob.clone(), Some(bob.private_key)).unwrap();
+        assert_eq!(
+            recover_from_kit(vec![from_alice, from_bob]).unwrap(),
+            master
+        );
+    }
+
+    #[test]
+    fn test_reveal_recovery_share_for_owner() {
+        let master = master_key();
+        let alice = generate_keypair();
+        let kit = create_recovery_kit(
+            master.clone(),
+            2,
+            2,
+            vec![Some(alice.public_key.clone()), None],
+        )
+        .unwrap();
+
+        // Alice hands back a clear blob; the owner needs no key of hers
+        let revealed =
+            reveal_recovery_share(kit.shares[0].blob.clone(), Some(alice.private_key)).unwrap();
+        assert!(revealed.starts_with(RECOVERY_SHARE_PREFIX));
+        assert_eq!(
+            reveal_recovery_share(kit.shares[1].blob.clone(), None).unwrap(),
+            kit.shares[1].blob
+        );
+
+        let shares = vec![
+            open_recovery_share(revealed, None).unwrap(),
+            open_recovery_share(kit.shares[1].blob.clone(), None).unwrap(),
+        ];
+        assert_eq!(recover_from_kit(shares).unwrap(), master);
+    }
+
+    #[test]
+    fn test_recovery_kit_rejects_mixed_kits_and_bad_input() {
+        let master = master_key();
+        let first = create_recovery_kit(master.clone(), 2, 3, vec![]).unwrap();
+        let second = create_recovery_kit(master, 2, 3, vec![]).unwrap();
+        assert_ne!(first.kit_id, second.kit_id);
+
+        let from_first = open_recovery_share(first.shares[0].blob.clone(), None).unwrap();
+        let mut from_second = open_recovery_share(second.shares[1].blob.clone(), None).unwrap();
+        assert_eq!(
+            recover_from_kit(vec![from_first.clone(), from_second.clone()]),
+            Err(CryptoError::InvalidKey)
+        );
+
+        // Relabelled into the same kit, different polynomials reconstruct
+        // the wrong key, which the check catches
+        from_second.group_id = from_first.group_id.clone();
+        assert_eq!(
+            recover_from_kit(vec![from_first, from_second]),
+            Err(CryptoError::InvalidKey)
+        );
+
+        assert!(create_recovery_kit(vec![0u8; 31], 2, 3, vec![]).is_err());
+        assert!(create_recovery_kit(master_key(), 2, 3, vec![None]).is_err());
+        assert!(matches!(
+            open_recovery_share("not-a-share".to_string(), None),
+            Err(CryptoError::InvalidVersion)
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+//! Crypto self-test
+//!
+//! Runs a quick round-trip through each primitive so a broken build or a
+//! platform-specific crypto bug shows up at startup instead of as corrupted
+//! user data. The report is plain data meant to be pasted into a support
+//! request; it never contains key material.
+
+use crate::aes::{aes_decrypt, aes_encrypt};
+use crate::error::CryptoError;
+use crate::generate_salt;
+use crate::keys::{
+    derive_master_key_with_params, generate_keypair, get_public_key, schnorr_sign, schnorr_verify,
+    Argon2Params, ARGON2_MIN_MEMORY_KB, ARGON2_MIN_TIME_COST,
+};
+use crate::multisig::{generate_threshold_key, reconstruct_secret, ThresholdConfig};
+use crate::nip44::{nip44_decrypt, nip44_encrypt};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// A single check, given a hook to corrupt its intermediate output
+type SelfTestFn = fn(&dyn Fn(&mut Vec<u8>)) -> Result<(), String>;
+
+/// Fixed plaintext used by every round-trip
+const SELF_TEST_MESSAGE: &str = "BuildIt crypto self-test";
+
+/// Outcome of a single self-test check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfTestCheck {
+    /// Check name (e.g. "nip44")
+    pub name: String,
+    /// Whether the round-trip succeeded
+    pub passed: bool,
+    /// Failure reason, if any
+    pub error: Option<String>,
+    /// Time taken in milliseconds
+    pub duration_ms: u64,
+}
+
+/// Result of `crypto_self_test`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelfTestReport {
+    /// True when every check passed
+    pub passed: bool,
+    /// Individual checks, in execution order
+    pub checks: Vec<SelfTestCheck>,
+}
+
+/// Run the crypto self-test
+///
+/// Checks: keypair generation, Schnorr sign/verify, NIP-44 round-trip,
+/// AES-GCM round-trip, Argon2id master key derivation (at the minimum cost,
+/// to stay fast) and a 2-of-3 threshold secret reconstruction.
+pub fn crypto_self_test() -> SelfTestReport {
+    run_self_test(&|_, _| {})
+}
+
+/// Run the self-test, letting `tamper` modify each check's intermediate
+/// output (signature, ciphertext, derived key, ...) before it is verified
+fn run_self_test(tamper: &dyn Fn(&str, &mut Vec<u8>)) -> SelfTestReport {
+    let checks: [(&str, SelfTestFn); 6] = [
+        ("keypair", check_keypair),
+        ("schnorr", check_schnorr),
+        ("nip44", check_nip44),
+        ("aes", check_aes),
+        ("argon2", check_argon2),
+        ("threshold", check_threshold),
+    ];
+
+    let checks: Vec<SelfTestCheck> = checks
+        .iter()
+        .map(|(name, check)| {
+            let started = Instant::now();
+            let result = check(&|data: &mut Vec<u8>| tamper(name, data));
+            SelfTestCheck {
+                name: name.to_string(),
+                passed: result.is_ok(),
+                error: result.err(),
+                duration_ms: started.elapsed().as_millis() as u64,
+            }
+        })
+        .collect();
+
+    SelfTestReport {
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    }
+}
+
+fn crypto_err(e: CryptoError) -> String {
+    e.to_string()
+}
+
+fn check_keypair(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let keypair = generate_keypair();
+    let mut private_key = keypair.private_key.clone();
+    tamper(&mut private_key);
+
+    if get_public_key(private_key).map_err(crypto_err)? != keypair.public_key {
+        return Err("derived public key does not match".to_string());
+    }
+    Ok(())
+}
+
+fn check_schnorr(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let keypair = generate_keypair();
+    let public_key = hex::decode(&keypair.public_key).map_err(|e| e.to_string())?;
+
+    let mut signature =
+        schnorr_sign(SELF_TEST_MESSAGE.as_bytes(), keypair.private_key).map_err(crypto_err)?;
+    tamper(&mut signature);
+
+    if !schnorr_verify(SELF_TEST_MESSAGE.as_bytes(), signature, public_key).map_err(crypto_err)? {
+        return Err("signature did not verify".to_string());
+    }
+    Ok(())
+}
+
+fn check_nip44(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let sender = generate_keypair();
+    let recipient = generate_keypair();
+
+    let payload = nip44_encrypt(
+        sender.private_key,
+        recipient.public_key,
+        SELF_TEST_MESSAGE.to_string(),
+    )
+    .map_err(crypto_err)?;
+    let mut payload = payload.into_bytes();
+    tamper(&mut payload);
+    let payload = String::from_utf8_lossy(&payload).into_owned();
+
+    let decrypted =
+        nip44_decrypt(recipient.private_key, sender.public_key, payload).map_err(crypto_err)?;
+    if decrypted != SELF_TEST_MESSAGE {
+        return Err("decrypted plaintext does not match".to_string());
+    }
+    Ok(())
+}
+
+fn check_aes(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let key = generate_salt(32);
+
+    let mut encrypted =
+        aes_encrypt(key.clone(), SELF_TEST_MESSAGE.as_bytes().to_vec()).map_err(crypto_err)?;
+    tamper(&mut encrypted.ciphertext);
+
+    if aes_decrypt(key, encrypted).map_err(crypto_err)? != SELF_TEST_MESSAGE.as_bytes() {
+        return Err("decrypted plaintext does not match".to_string());
+    }
+    Ok(())
+}
+
+fn check_argon2(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let params = Argon2Params {
+        memory_kb: ARGON2_MIN_MEMORY_KB,
+        time_cost: ARGON2_MIN_TIME_COST,
+        parallelism: 1,
+    };
+    let salt = generate_salt(32);
+    let password = SELF_TEST_MESSAGE.as_bytes().to_vec();
+
+    let mut first = derive_master_key_with_params(password.clone(), salt.clone(), params)
+        .map_err(crypto_err)?;
+    tamper(&mut first);
+    let second = derive_master_key_with_params(password, salt, params).map_err(crypto_err)?;
+
+    if first.len() != 32 || first != second {
+        return Err("derivation is not deterministic".to_string());
+    }
+    Ok(())
+}
+
+fn check_threshold(tamper: &dyn Fn(&mut Vec<u8>)) -> Result<(), String> {
+    let group = generate_threshold_key(ThresholdConfig {
+        threshold: 2,
+        total_shares: 3,
+        group_name: "self-test".to_string(),
+    })
+    .map_err(crypto_err)?;
+
+    let shares = vec![group.shares[0].clone(), group.shares[2].clone()];
+    let mut secret = reconstruct_secret(shares).map_err(crypto_err)?;
+    tamper(&mut secret);
+
+    if get_public_key(secret).map_err(crypto_err)? != group.group_public_key {
+        return Err("reconstructed secret does not match group key".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flip_first_byte(data: &mut [u8]) {
+        if let Some(byte) = data.first_mut() {
+            *byte ^= 0x01;
+        }
+    }
+
+    #[test]
+    fn test_self_test_passes() {
+        let report = crypto_self_test();
+
+        assert!(report.passed, "{:?}", report);
+        let names: Vec<&str> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["keypair", "schnorr", "nip44", "aes", "argon2", "threshold"]
+        );
+        assert!(report.checks.iter().all(|c| c.error.is_none()));
+    }
+
+    #[test]
+    fn test_corrupted_input_fails_only_that_check() {
+        for target in ["keypair", "schnorr", "nip44", "aes", "argon2", "threshold"] {
+            let report = run_self_test(&|name, data| {
+                if name == target {
+                    flip_first_byte(data);
+                }
+            });
+
+            assert!(!report.passed, "{} corruption not detected", target);
+            for check in &report.checks {
+                assert_eq!(check.passed, check.name != target, "{:?}", check);
+            }
+            let failed = report.checks.iter().find(|c| c.name == target).unwrap();
+            assert!(failed.error.is_some());
+        }
+    }
+
+    #[test]
+    fn test_report_serializes() {
+        let report = SelfTestReport {
+            passed: false,
+            checks: vec![SelfTestCheck {
+                name: "aes".to_string(),
+                passed: false,
+                error: Some("Decryption failed".to_string()),
+                duration_ms: 1,
+            }],
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][0]["name"], "aes");
+        assert_eq!(json["checks"][0]["error"], "Decryption failed");
+    }
+}
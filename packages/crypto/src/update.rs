@@ -0,0 +1,168 @@
+//! Release artifact signature verification
+//!
+//! Update artifacts are signed with BIP-340 Schnorr over the SHA-256 digest
+//! of the artifact bytes (the same message hashing as `schnorr_sign`), so a
+//! release can be signed with any Nostr-compatible key tooling. The updater
+//! must refuse to apply any artifact that does not verify against the pinned
+//! release key.
+//!
+//! The release key is pinned at build time through the
+//! `BUILDIT_RELEASE_PUBKEY` environment variable (x-only, hex). Builds
+//! without it cannot verify release artifacts and reject every update.
+
+use crate::error::CryptoError;
+use crate::keys;
+use secp256k1::{schnorr, Message, Secp256k1, XOnlyPublicKey};
+use sha2::{Digest, Sha256};
+
+/// Pinned release signing public key (x-only, hex), set at build time
+pub const RELEASE_SIGNING_PUBKEY: Option<&str> = option_env!("BUILDIT_RELEASE_PUBKEY");
+
+/// SHA-256 digest of an update artifact (hex), for logging and display
+pub fn update_artifact_digest(artifact_bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(artifact_bytes))
+}
+
+/// Verify an update artifact's Schnorr signature against a public key
+///
+/// Returns `Ok(false)` for a well-formed signature that does not match
+/// (tampered artifact or wrong key) and an error for malformed input.
+pub fn verify_update_signature(
+    artifact_bytes: Vec<u8>,
+    signature_hex: String,
+    pubkey_hex: String,
+) -> Result<bool, CryptoError> {
+    let signature = hex::decode(signature_hex.trim()).map_err(|_| CryptoError::InvalidHex)?;
+    let pubkey = hex::decode(pubkey_hex.trim()).map_err(|_| CryptoError::InvalidHex)?;
+
+    keys::schnorr_verify(&artifact_bytes, signature, pubkey)
+}
+
+/// Verify an update artifact against the pinned release signing key
+///
+/// Fails with `InvalidPublicKey` when this build has no pinned release key.
+pub fn verify_release_artifact(
+    artifact_bytes: &[u8],
+    signature_hex: &str,
+) -> Result<bool, CryptoError> {
+    let digest: [u8; 32] = Sha256::digest(artifact_bytes).into();
+    verify_with_pinned_key(&digest, signature_hex, RELEASE_SIGNING_PUBKEY)
+}
+
+/// Verify an artifact's SHA-256 digest against the pinned release signing key
+///
+/// For installer-sized artifacts: hash the file in a streaming pass and
+/// verify the digest here instead of loading the whole file.
+pub fn verify_release_digest(digest: &[u8; 32], signature_hex: &str) -> Result<bool, CryptoError> {
+    verify_with_pinned_key(digest, signature_hex, RELEASE_SIGNING_PUBKEY)
+}
+
+fn verify_with_pinned_key(
+    digest: &[u8; 32],
+    signature_hex: &str,
+    pinned_pubkey: Option<&str>,
+) -> Result<bool, CryptoError> {
+    let pubkey = pinned_pubkey.ok_or(CryptoError::InvalidPublicKey)?;
+    let signature = hex::decode(signature_hex.trim()).map_err(|_| CryptoError::InvalidHex)?;
+    let pubkey = hex::decode(pubkey.trim()).map_err(|_| CryptoError::InvalidHex)?;
+
+    let pubkey = XOnlyPublicKey::from_slice(&pubkey).map_err(|_| CryptoError::InvalidPublicKey)?;
+    let signature =
+        schnorr::Signature::from_slice(&signature).map_err(|_| CryptoError::InvalidSignature)?;
+    let message = Message::from_digest(*digest);
+
+    Ok(Secp256k1::verification_only()
+        .verify_schnorr(&signature, &message, &pubkey)
+        .is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{generate_keypair, schnorr_sign};
+
+    fn sample_artifact() -> Vec<u8> {
+        (0..4096u32).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_verify_signed_artifact() {
+        let release = generate_keypair();
+        let artifact = sample_artifact();
+        let signature = schnorr_sign(&artifact, release.private_key.clone()).unwrap();
+
+        assert!(verify_update_signature(
+            artifact,
+            hex::encode(&signature),
+            release.public_key.clone()
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_mutated_artifact_rejected() {
+        let release = generate_keypair();
+        let artifact = sample_artifact();
+        let signature = hex::encode(schnorr_sign(&artifact, release.private_key.clone()).unwrap());
+
+        let mut tampered = artifact.clone();
+        tampered[1234] ^= 0x01;
+        assert!(!verify_update_signature(tampered, signature.clone(), release.public_key).unwrap());
+
+        // Signature from another key must not verify either
+        let other = generate_keypair();
+        assert!(!verify_update_signature(artifact, signature, other.public_key).unwrap());
+    }
+
+    #[test]
+    fn test_malformed_input_is_an_error() {
+        let release = generate_keypair();
+        let artifact = sample_artifact();
+
+        assert_eq!(
+            verify_update_signature(
+                artifact.clone(),
+                "zz".to_string(),
+                release.public_key.clone()
+            ),
+            Err(CryptoError::InvalidHex)
+        );
+        assert_eq!(
+            verify_update_signature(artifact, "00".repeat(32), release.public_key),
+            Err(CryptoError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_pinned_key_verification() {
+        let release = generate_keypair();
+        let artifact = sample_artifact();
+        let digest: [u8; 32] = Sha256::digest(&artifact).into();
+        let signature = hex::encode(schnorr_sign(&artifact, release.private_key.clone()).unwrap());
+
+        assert!(verify_with_pinned_key(&digest, &signature, Some(&release.public_key)).unwrap());
+        assert_eq!(
+            verify_with_pinned_key(&digest, &signature, None),
+            Err(CryptoError::InvalidPublicKey)
+        );
+
+        let other = generate_keypair();
+        assert!(!verify_with_pinned_key(&digest, &signature, Some(&other.public_key)).unwrap());
+
+        let mut tampered = digest;
+        tampered[0] ^= 0x01;
+        assert!(!verify_with_pinned_key(&tampered, &signature, Some(&release.public_key)).unwrap());
+        assert_eq!(
+            verify_with_pinned_key(&digest, "zz", Some(&release.public_key)),
+            Err(CryptoError::InvalidHex)
+        );
+    }
+
+    #[test]
+    fn test_update_artifact_digest() {
+        assert_eq!(
+            update_artifact_digest(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
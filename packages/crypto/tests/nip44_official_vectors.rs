@@ -0,0 +1,193 @@
+//! NIP-44 v2 Official Test Vectors
+//!
+//! Valid vectors are taken from the official NIP-44 v2 vector set
+//! (paulmillr/nip44, `nip44.vectors.json`) and must match byte-for-byte so
+//! that payloads interoperate with other clients (e.g. nostr-tools on web).
+//! Invalid vectors cover each failure class the spec requires decryption to
+//! reject; the padding cases carry a valid MAC so they reach the unpad step.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use buildit_crypto::*;
+
+fn hex32(s: &str) -> [u8; 32] {
+    hex::decode(s).unwrap().try_into().unwrap()
+}
+
+fn pubkey_of(private_key_hex: &str) -> String {
+    get_public_key(hex::decode(private_key_hex).unwrap()).unwrap()
+}
+
+/// (sec1, pub2, conversation_key)
+const CONVERSATION_KEY_VECTORS: &[(&str, &str, &str)] = &[
+    (
+        "315e59ff51cb9209768cf7da80791ddcaae56ac9775eb25b6dee1234bc5d2268",
+        "c2f9d9948dc8c7c38321e4b85c8558872eafa0641cd269db76848a6073e69133",
+        "3dfef0ce2a4d80a25e7a328accf73448ef67096f65f79588e358d9a0eb9013f1",
+    ),
+    (
+        "a1e37752c9fdc1273be53f68c5f74be7c8905728e8de75800b94262f9497c86e",
+        "03bb7947065dde12ba991ea045132581d0954f042c84e06d8c00066e23c1a800",
+        "4d14f36e81b8452128da64fe6f1eae873baae2f444b02c950b90e43553f2178b",
+    ),
+];
+
+struct EncryptDecryptVector {
+    sec1: &'static str,
+    sec2: &'static str,
+    conversation_key: &'static str,
+    nonce: &'static str,
+    plaintext: &'static str,
+    payload: &'static str,
+}
+
+const ENCRYPT_DECRYPT_VECTORS: &[EncryptDecryptVector] = &[
+    EncryptDecryptVector {
+        sec1: "0000000000000000000000000000000000000000000000000000000000000001",
+        sec2: "0000000000000000000000000000000000000000000000000000000000000002",
+        conversation_key: "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d",
+        nonce: "0000000000000000000000000000000000000000000000000000000000000001",
+        plaintext: "a",
+        payload: "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb",
+    },
+    EncryptDecryptVector {
+        sec1: "0000000000000000000000000000000000000000000000000000000000000002",
+        sec2: "0000000000000000000000000000000000000000000000000000000000000001",
+        conversation_key: "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d",
+        nonce: "f00000000000000000000000000000f00000000000000000000000000000000f",
+        plaintext: "🍕🫃",
+        payload: "AvAAAAAAAAAAAAAAAAAAAPAAAAAAAAAAAAAAAAAAAAAPSKSK6is9ngkX2+cSq85Th16oRTISAOfhStnixqZziKMDvB0QQzgFZdjLTPicCJaV8nDITO+QfaQ61+KbWQIOO2Yj",
+    },
+    EncryptDecryptVector {
+        sec1: "5c0c523f52a5b6fad39ed2403092df8cebc36318b39383bca6c00808626fab3a",
+        sec2: "4b22aa260e4acb7021e32f38a6cdf4b673c6a277755bfce287e370c924dc936d",
+        conversation_key: "3e2b52a63be47d34fe0a80e34e73d436d6963bc8f39827f327057a9986c20a45",
+        nonce: "b635236c42db20f021bb8d1cdff5ca75dd1a0cc72ea742ad750f33010b24f73b",
+        plaintext: "表ポあA鷗ŒéＢ逍Üßªąñ丂㐀𠀀",
+        payload: "ArY1I2xC2yDwIbuNHN/1ynXdGgzHLqdCrXUPMwELJPc7s7JqlCMJBAIIjfkpHReBPXeoMCyuClwgbT419jUWU1PwaNl4FEQYKCDKVJz+97Mp3K+Q2YGa77B6gpxB/lr1QgoqpDf7wDVrDmOqGoiPjWDqy8KzLueKDcm9BVP8xeTJIxs=",
+    },
+];
+
+/// Conversation key used by the invalid decrypt vectors
+const INVALID_CONVERSATION_KEY: &str =
+    "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d";
+
+/// (description, payload) pairs that must fail to decrypt
+const INVALID_DECRYPT_VECTORS: &[(&str, &str)] = &[
+    (
+        "unknown encryption version marker",
+        "#Atqupco0WyaOW2IGDKcshwxI9xO8HgD/P8Ddt46CbxDbrhdG8VmJZE0UICD06CUvEvdnr1cp1fiMtlM/GrE92xAc1EwsVCQEgWEu2gsHUVf4JAa3TpgkmFc3TWsax0v6n/Wq",
+    ),
+    (
+        "invalid base64",
+        "Aq2tra2tra2tra2tra2tra2tra2tra2tra2tra2tra2tEze3DwRMlKyp1lEUsQz776oj1Km8n9vhfiHuGNYbRtbznyzQ4PnwNTdC0AR/mdZIwF8lao/D+8RXRPoXJy/la9c!",
+    ),
+    ("payload too short", "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=="),
+    (
+        "zero-length plaintext",
+        "Aq2tra2tra2tra2tra2tra2tra2tra2tra2tra2tra2tEze3DwRMlKyp1lEUsQz776oj1Km8n9vhfiHuGNYbRtbznyzQ4PnwNTdC0AR/mdZIwF8lao/D+8RXRPoXJy/la9cQ",
+    ),
+    (
+        "non-zero padding bytes",
+        "Aq2tra2tra2tra2tra2tra2tra2tra2tra2tra2tra2tEza3DgRMlKyp1lEUsQz776oj1Km8n9vhfiHuGNYbRtbzn0UyjSVbylWQn8HT8Qupa0KuAZXIKsDADtx4tJ34R9us",
+    ),
+    (
+        "length prefix exceeds padded data",
+        "Aq2tra2tra2tra2tra2tra2tra2tra2tra2tra2tra2tE3e3bmUt9c3ItzB10G2ajstCtcjd/rqAH0CPebd6J7eS/u0qLGEQJ72Xbm/CU+HXsGf+4xY38cZzHABXhvBNMIG9",
+    ),
+    (
+        "padded length does not match length prefix",
+        "Aq2tra2tra2tra2tra2tra2tra2tra2tra2tra2tra2tEza3DwRMlKyp1lEUsQz776oj1Km8n9vhfiHuGNYbRtbzn2FQhAF6YQYRH9+MqO/6OEWICgZkX4KspMTC1fgNsxTog/U4lXDnY53U5tmEcAftPWOUxcJgLGZ9YKd7t82Oj+4=",
+    ),
+];
+
+#[test]
+fn test_official_conversation_keys() {
+    for (sec1, pub2, expected) in CONVERSATION_KEY_VECTORS {
+        let key = derive_conversation_key(hex::decode(sec1).unwrap(), pub2.to_string()).unwrap();
+        assert_eq!(hex::encode(key), *expected, "sec1 {}", sec1);
+    }
+}
+
+#[test]
+fn test_official_encrypt_decrypt_vectors() {
+    for v in ENCRYPT_DECRYPT_VECTORS {
+        // Conversation key is symmetric
+        let key_12 =
+            derive_conversation_key(hex::decode(v.sec1).unwrap(), pubkey_of(v.sec2)).unwrap();
+        let key_21 =
+            derive_conversation_key(hex::decode(v.sec2).unwrap(), pubkey_of(v.sec1)).unwrap();
+        assert_eq!(hex::encode(&key_12), v.conversation_key);
+        assert_eq!(key_12, key_21);
+
+        // Encryption with the vector nonce matches byte-for-byte
+        let payload =
+            nip44_encrypt_with_nonce(key_12.clone(), hex32(v.nonce), v.plaintext.to_string())
+                .unwrap();
+        assert_eq!(payload, v.payload, "plaintext {:?}", v.plaintext);
+
+        // Both decrypt entry points recover the plaintext
+        let decrypted = nip44_decrypt_with_key(key_12, v.payload.to_string()).unwrap();
+        assert_eq!(decrypted, v.plaintext);
+        let decrypted = nip44_decrypt(
+            hex::decode(v.sec2).unwrap(),
+            pubkey_of(v.sec1),
+            v.payload.to_string(),
+        )
+        .unwrap();
+        assert_eq!(decrypted, v.plaintext);
+    }
+}
+
+#[test]
+fn test_invalid_decrypt_vectors() {
+    let key = hex::decode(INVALID_CONVERSATION_KEY).unwrap();
+    for (description, payload) in INVALID_DECRYPT_VECTORS {
+        let result = nip44_decrypt_with_key(key.clone(), payload.to_string());
+        assert!(result.is_err(), "expected failure: {}", description);
+    }
+}
+
+#[test]
+fn test_invalid_padding_is_reported() {
+    let key = hex::decode(INVALID_CONVERSATION_KEY).unwrap();
+    for (_, payload) in &INVALID_DECRYPT_VECTORS[3..] {
+        assert!(matches!(
+            nip44_decrypt_with_key(key.clone(), payload.to_string()),
+            Err(CryptoError::InvalidPadding)
+        ));
+    }
+}
+
+#[test]
+fn test_tampered_official_payload_fails_mac() {
+    let v = &ENCRYPT_DECRYPT_VECTORS[0];
+    let key = hex::decode(v.conversation_key).unwrap();
+
+    let mut payload = BASE64.decode(v.payload).unwrap();
+    let last = payload.len() - 1;
+    payload[last] ^= 0x01;
+    assert!(matches!(
+        nip44_decrypt_with_key(key.clone(), BASE64.encode(&payload)),
+        Err(CryptoError::InvalidMac)
+    ));
+
+    // Flipping a ciphertext bit must also be caught by the MAC
+    let mut payload = BASE64.decode(v.payload).unwrap();
+    payload[40] ^= 0x01;
+    assert!(matches!(
+        nip44_decrypt_with_key(key, BASE64.encode(&payload)),
+        Err(CryptoError::InvalidMac)
+    ));
+}
+
+#[test]
+fn test_wrong_version_byte_rejected() {
+    let v = &ENCRYPT_DECRYPT_VECTORS[0];
+    let mut payload = BASE64.decode(v.payload).unwrap();
+    payload[0] = 1;
+    let result = nip44_decrypt_with_key(
+        hex::decode(v.conversation_key).unwrap(),
+        BASE64.encode(&payload),
+    );
+    assert!(matches!(result, Err(CryptoError::InvalidCiphertext)));
+}